@@ -139,9 +139,45 @@ fn parameterized_decode(c: &mut Criterion) {
     }
 }
 
+/// Decoding with the guest allocating from the arena of the host, against the default path where
+/// the host reuses its cached input allocation and the guest allocates its outputs with `malloc`.
+fn arena_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("arena_decode");
+    group.warm_up_time(Duration::from_secs(2));
+    let default_binary = std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap();
+    let arena_binary = std::fs::read(fff_test_util::ARENA_WASM_PATH.as_path()).unwrap();
+    for size in [1024, 8192, 65536].iter() {
+        let arr = fff_bench::generate_data(*size);
+        let encoded = wasm_test_encoders::encode_fff_general(arr);
+        for (name, binary, config) in [
+            (
+                "default",
+                &default_binary,
+                Config::default().copy_outputs(true),
+            ),
+            (
+                "arena",
+                &arena_binary,
+                Config::default().copy_outputs(true).arena_size(16 << 20),
+            ),
+        ] {
+            let rt = Runtime::with_config(binary, config).unwrap();
+            group.bench_with_input(BenchmarkId::new(name, size), size, |b, &_| {
+                b.iter(|| {
+                    black_box(
+                        rt.call_multi_buf(fff_test_util::WASM_FUNC_GENERAL, &encoded)
+                            .unwrap()
+                            .collect::<Vec<_>>(),
+                    )
+                });
+            });
+        }
+    }
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
-    targets = parameterized_decode
+    targets = parameterized_decode, arena_decode
 }
 criterion_main!(benches);
@@ -121,6 +121,7 @@ async fn random_access_s3() -> Result<()> {
         fff_bench::ReadFFFOpt {
             projections: Some(fff_poc::reader::Projection::All),
             selection: Some(fff_poc::reader::Selection::RowIndexes(vec![row_id])),
+            prefer_mmap: false,
         },
     )
     .unwrap();
@@ -0,0 +1,52 @@
+/// Benchmark the built-in Vortex codec against its WASM build across EncUnit lengths.
+/// Make sure you have built the wasm binaries via `./exp_scripts/build_wasm.sh` before running this.
+///
+/// ```bash
+/// cargo run --release --example codec_bench -- [csv]
+/// ```
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow_array::{ArrayRef, Int64Array, StringArray};
+use fff_bench::codec_bench::{bench_codec, CodecBenchOptions};
+use fff_encoding::schemes::vortex::VortexEncoder;
+use fff_test_util::BUILTIN_WASM_PATH;
+use fff_ude_wasm::Runtime;
+use rand::Rng;
+
+fn main() -> Result<()> {
+    let csv = std::env::args().nth(1).is_some_and(|arg| arg == "csv");
+    let rt = Arc::new(Runtime::try_new(&std::fs::read(
+        BUILTIN_WASM_PATH.as_path(),
+    )?)?);
+    let mut rng = rand::thread_rng();
+    let num_rows = 256 * 1024;
+    let arrays: [(&str, ArrayRef); 2] = [
+        (
+            "int64",
+            Arc::new(Int64Array::from_iter_values(
+                (0..num_rows).map(|_| rng.gen_range(0..1000)),
+            )),
+        ),
+        (
+            "utf8",
+            Arc::new(StringArray::from_iter_values(
+                (0..num_rows).map(|_| format!("value-{}", rng.gen_range(0..100))),
+            )),
+        ),
+    ];
+    for (name, array) in arrays {
+        let report = bench_codec(
+            &array,
+            &VortexEncoder::default(),
+            rt.clone(),
+            &CodecBenchOptions::default(),
+        )?;
+        if csv {
+            print!("{}", report.to_csv());
+        } else {
+            println!("## {name}\n\n{}", report.to_markdown());
+        }
+    }
+    Ok(())
+}
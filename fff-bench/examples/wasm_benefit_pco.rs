@@ -21,7 +21,6 @@
 /// The original two Parquet files can be found at `https://r2.xinyuzeng.xyz/linear.parquet` and
 /// `https://r2.xinyuzeng.xyz/synthetic.parquet`.
 ///
-use std::collections::HashMap;
 use std::fs::File;
 use std::path::PathBuf;
 use std::sync::LazyLock;
@@ -32,7 +31,7 @@ use arrow::datatypes::DataType;
 use bench_vortex::setup_logger;
 use fff_bench::bench_data::parquet_into_batches;
 use fff_bench::{read_fff, write_fff};
-use fff_poc::context::{WASMId, WasmLib};
+use fff_poc::context::WasmLib;
 use fff_poc::options::{CustomEncodingOptions, FileWriterOptions};
 use log::{error, LevelFilter};
 
@@ -55,8 +54,8 @@ fn main() -> Result<()> {
         let batches = parquet_into_batches(input_parquet.into(), Default::default())?;
         error!("Parquet Elapsed: {:?}", start.elapsed());
         // println!("{:?}", batches[0].schema());
-        let wasms = HashMap::from([(
-            WASMId(0),
+        let mut custom_encoding_options = CustomEncodingOptions::default();
+        custom_encoding_options.add_wasm(
             WasmLib::new(
                 // PROJ_ROOT.join("target/release/libfff_ude_example_pco_real_encoder.so"),
                 // std::fs::read(PROJ_ROOT.join("target/wasm32-wasip1/opt-size-lvl3/fff_ude_example_pco_real.wasm")).unwrap().into(),
@@ -68,25 +67,19 @@ fn main() -> Result<()> {
                 .unwrap()
                 .into(),
             ),
-        )]);
-        let mut data_type_to_wasm_id: HashMap<DataType, WASMId> = HashMap::new();
-        data_type_to_wasm_id.insert(DataType::UInt16, WASMId(0));
-        data_type_to_wasm_id.insert(DataType::Int16, WASMId(0));
-        data_type_to_wasm_id.insert(DataType::UInt32, WASMId(0));
-        data_type_to_wasm_id.insert(DataType::Int32, WASMId(0));
-        data_type_to_wasm_id.insert(DataType::UInt64, WASMId(0));
-        data_type_to_wasm_id.insert(DataType::Int64, WASMId(0));
-        data_type_to_wasm_id.insert(DataType::Float32, WASMId(0));
-        data_type_to_wasm_id.insert(DataType::Float64, WASMId(0));
-        data_type_to_wasm_id.insert(
-            DataType::Timestamp(arrow::datatypes::TimeUnit::Nanosecond, Some("UTC".into())),
-            WASMId(0),
+            [
+                DataType::UInt16,
+                DataType::Int16,
+                DataType::UInt32,
+                DataType::Int32,
+                DataType::UInt64,
+                DataType::Int64,
+                DataType::Float32,
+                DataType::Float64,
+                DataType::Timestamp(arrow::datatypes::TimeUnit::Nanosecond, Some("UTC".into())),
+                DataType::Timestamp(arrow::datatypes::TimeUnit::Microsecond, None),
+            ],
         );
-        data_type_to_wasm_id.insert(
-            DataType::Timestamp(arrow::datatypes::TimeUnit::Microsecond, None),
-            WASMId(0),
-        );
-        let custom_encoding_options = CustomEncodingOptions::new(wasms, data_type_to_wasm_id);
         write_fff(
             &batches,
             &fff,
@@ -1111,7 +1111,14 @@ impl BenchmarkDataset for BenchmarkDatasets {
         for f in self.list_files(FileType::FFF) {
             info!("Reading fff file {}", f.to_str().unwrap());
             let start = Instant::now();
-            let batches = read_fff(f, ReadFFFOpt::default()).unwrap();
+            let batches = read_fff(
+                f,
+                ReadFFFOpt {
+                    prefer_mmap: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
             error!("Reading fff file took {}ms", start.elapsed().as_millis());
             drop(batches);
         }
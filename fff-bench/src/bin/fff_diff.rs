@@ -0,0 +1,34 @@
+use std::{fs::File, path::PathBuf, process::ExitCode, sync::Arc};
+
+use clap::Parser;
+use fff_core::errors::Result;
+use fff_poc::diff::diff_files;
+
+/// Compare two F3 files: schemas, row counts, decoded data and layout.
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct Args {
+    left: PathBuf,
+    right: PathBuf,
+    /// Also fail if only the layout differs.
+    #[arg(long)]
+    strict: bool,
+}
+
+fn main() -> Result<ExitCode> {
+    let args = Args::parse();
+    let left = Arc::new(File::open(&args.left)?);
+    let right = Arc::new(File::open(&args.right)?);
+    let diff = diff_files(left, right)?;
+    print!("{}", diff);
+    let equal = if args.strict {
+        diff.is_identical()
+    } else {
+        diff.is_semantically_equal()
+    };
+    Ok(if equal {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
@@ -0,0 +1,30 @@
+use std::{fs::File, path::PathBuf, sync::Arc};
+
+use clap::Parser;
+use fff_core::errors::Result;
+use fff_poc::{options::FileWriterOptionsBuilder, writer::FileWriter};
+
+/// Rewrite an F3 file with a different IO unit size, without re-encoding its data.
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct Args {
+    input: PathBuf,
+    output: PathBuf,
+    /// Target size of column chunks in bytes, e.g., 8MiB for S3.
+    #[arg(long)]
+    iounit_size: u64,
+    /// Whether the input file was written with the built-in WASM.
+    #[arg(long)]
+    write_built_in_wasm: bool,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let input = Arc::new(File::open(&args.input)?);
+    let output = File::create(&args.output)?;
+    let options = FileWriterOptionsBuilder::with_defaults()
+        .set_iounit_size(args.iounit_size)
+        .write_built_in_wasm(args.write_built_in_wasm)
+        .build();
+    FileWriter::repack(input, output, options)
+}
@@ -0,0 +1,279 @@
+//! Encode and decode benchmarks of a codec WASM against the matching native codec, across EncUnit
+//! lengths, reported as markdown or CSV.
+//!
+//! The WASM module must export [`WASM_FUNC_GENERAL`] and may export [`WASM_FUNC_ENCODE`]. Without
+//! a guest encoder, the WASM decoder decodes the output of the native encoder, which is then the
+//! format it must read.
+
+use std::{
+    fmt::Write,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use arrow::compute::{cast, concat};
+use arrow_array::{Array, ArrayRef};
+use bytes::Bytes;
+use fff_encoding::schemes::Encoder;
+use fff_format::File::fff::flatbuf as fb;
+use fff_poc::decoder::encunit::{
+    EncUnitDecoder, NativeEncUnitDecoder, VortexEncUnitDecoder, WASMEncUnitDecoder,
+};
+use fff_test_util::{WASM_FUNC_ENCODE, WASM_FUNC_GENERAL};
+use fff_ude_wasm::Runtime;
+
+pub struct CodecBenchOptions {
+    /// Number of rows of the EncUnits each run splits the array into.
+    pub encunit_lens: Vec<usize>,
+    /// Number of times each EncUnit is encoded and decoded, after a warm-up run.
+    pub iterations: u32,
+}
+
+impl Default for CodecBenchOptions {
+    fn default() -> Self {
+        Self {
+            encunit_lens: vec![1024, 8 * 1024, 64 * 1024],
+            iterations: 10,
+        }
+    }
+}
+
+/// The mean time to encode or decode an EncUnit of each codec, for one EncUnit length.
+#[derive(Debug, Clone)]
+pub struct CodecBenchRow {
+    pub encunit_len: usize,
+    pub num_encunits: usize,
+    /// Mean in-memory size of the Arrow input of an EncUnit.
+    pub input_size: usize,
+    pub native_encoded_size: usize,
+    pub native_encode: Duration,
+    pub native_decode: Duration,
+    /// None if the module has no guest encoder.
+    pub wasm_encoded_size: Option<usize>,
+    pub wasm_encode: Option<Duration>,
+    pub wasm_decode: Duration,
+}
+
+impl CodecBenchRow {
+    /// Throughput in MB/s of the Arrow input for a mean time of `time` per EncUnit.
+    fn throughput(&self, time: Duration) -> f64 {
+        self.input_size as f64 / time.as_secs_f64() / 1e6
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CodecBenchReport {
+    pub rows: Vec<CodecBenchRow>,
+}
+
+impl CodecBenchReport {
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        writeln!(
+            out,
+            "| EncUnit len | input size | codec | encoded size | encode (us) | encode (MB/s) | decode (us) | decode (MB/s) |"
+        )
+        .unwrap();
+        writeln!(out, "|---|---|---|---|---|---|---|---|").unwrap();
+        for row in &self.rows {
+            for (codec, size, encode, decode) in self.codecs(row) {
+                let (size, encode_us, encode_mbs) = match (size, encode) {
+                    (Some(size), Some(encode)) => (
+                        size.to_string(),
+                        format!("{:.1}", encode.as_secs_f64() * 1e6),
+                        format!("{:.1}", row.throughput(encode)),
+                    ),
+                    _ => ("-".to_string(), "-".to_string(), "-".to_string()),
+                };
+                writeln!(
+                    out,
+                    "| {} | {} | {codec} | {size} | {encode_us} | {encode_mbs} | {:.1} | {:.1} |",
+                    row.encunit_len,
+                    row.input_size,
+                    decode.as_secs_f64() * 1e6,
+                    row.throughput(decode),
+                )
+                .unwrap();
+            }
+        }
+        out
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from(
+            "encunit_len,num_encunits,input_size,codec,encoded_size,encode_ns,decode_ns\n",
+        );
+        for row in &self.rows {
+            for (codec, size, encode, decode) in self.codecs(row) {
+                writeln!(
+                    out,
+                    "{},{},{},{codec},{},{},{}",
+                    row.encunit_len,
+                    row.num_encunits,
+                    row.input_size,
+                    size.map_or(String::new(), |size| size.to_string()),
+                    encode.map_or(String::new(), |encode| encode.as_nanos().to_string()),
+                    decode.as_nanos()
+                )
+                .unwrap();
+            }
+        }
+        out
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn codecs(
+        &self,
+        row: &CodecBenchRow,
+    ) -> [(&'static str, Option<usize>, Option<Duration>, Duration); 2] {
+        [
+            (
+                "native",
+                Some(row.native_encoded_size),
+                Some(row.native_encode),
+                row.native_decode,
+            ),
+            (
+                "wasm",
+                row.wasm_encoded_size,
+                row.wasm_encode,
+                row.wasm_decode,
+            ),
+        ]
+    }
+}
+
+/// The errors of fff_core do not implement `std::error::Error`.
+fn fff_err(e: fff_core::errors::Error) -> anyhow::Error {
+    anyhow!("{e}")
+}
+
+/// Check that `decoded` holds the values of `expected`, e.g., as views for strings.
+fn check_round_trip(decoded: &ArrayRef, expected: &ArrayRef, codec: &str) -> Result<()> {
+    let decoded = cast(decoded, expected.data_type())?;
+    if decoded.as_ref() != expected.as_ref() {
+        bail!(
+            "{codec} codec does not round trip an EncUnit of {} rows",
+            expected.len()
+        );
+    }
+    Ok(())
+}
+
+/// Decode an EncUnit of `encoding` with the decoders built in the reader.
+fn native_decoder(
+    encoding: fb::EncodingType,
+    data: Bytes,
+    array: &ArrayRef,
+) -> Result<Box<dyn EncUnitDecoder>> {
+    let data_type = array.data_type().clone();
+    Ok(match encoding {
+        fb::EncodingType::CASCADE => Box::new(VortexEncUnitDecoder::new(data, data_type)),
+        fb::EncodingType::RLE | fb::EncodingType::DELTA | fb::EncodingType::BOOLEAN => {
+            Box::new(NativeEncUnitDecoder::new(data, data_type, encoding))
+        }
+        encoding => bail!("no native decoder for {encoding:?}"),
+    })
+}
+
+/// Mean time of `f` over `iterations` runs, after a warm-up run whose output is returned.
+fn time<T>(iterations: u32, mut f: impl FnMut() -> Result<T>) -> Result<(T, Duration)> {
+    let output = f()?;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        std::hint::black_box(f()?);
+    }
+    Ok((output, start.elapsed() / iterations.max(1)))
+}
+
+/// Benchmark encoding `array` with `native` and the guest encoder of `rt`, and decoding it with the
+/// built-in decoders and the guest decoder, checking that all outputs decode to `array`.
+pub fn bench_codec(
+    array: &ArrayRef,
+    native: &dyn Encoder,
+    rt: Arc<Runtime>,
+    options: &CodecBenchOptions,
+) -> Result<CodecBenchReport> {
+    // The guest receives the buffers from offset 0.
+    let array = concat(&[array.as_ref()])?;
+    let has_guest_encoder = rt.functions().any(|name| name == WASM_FUNC_ENCODE);
+    let encoding = native.encoding_type().to_fbs_encoding();
+    let mut report = CodecBenchReport::default();
+    for &encunit_len in &options.encunit_lens {
+        let encunits = (0..array.len())
+            .step_by(encunit_len.max(1))
+            .map(|offset| {
+                concat(&[array
+                    .slice(offset, encunit_len.min(array.len() - offset))
+                    .as_ref()])
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut row = CodecBenchRow {
+            encunit_len,
+            num_encunits: encunits.len(),
+            input_size: 0,
+            native_encoded_size: 0,
+            native_encode: Duration::ZERO,
+            native_decode: Duration::ZERO,
+            wasm_encoded_size: has_guest_encoder.then_some(0),
+            wasm_encode: has_guest_encoder.then_some(Duration::ZERO),
+            wasm_decode: Duration::ZERO,
+        };
+        for encunit in &encunits {
+            row.input_size += encunit.get_array_memory_size();
+            let (encoded, encode) = time(options.iterations, || {
+                let mut buf = vec![];
+                native
+                    .encode(encunit.clone())
+                    .and_then(|encunit| encunit.try_serialize(std::io::Cursor::new(&mut buf)))
+                    .map_err(fff_err)?;
+                Ok(Bytes::from(buf))
+            })?;
+            row.native_encoded_size += encoded.len();
+            row.native_encode += encode;
+            let (decoded, decode) = time(options.iterations, || {
+                native_decoder(encoding, encoded.clone(), encunit)?
+                    .decode()
+                    .map_err(fff_err)
+            })?;
+            row.native_decode += decode;
+            check_round_trip(&decoded, encunit, "native")?;
+
+            let encoded = if has_guest_encoder {
+                let (encoded, encode) = time(options.iterations, || {
+                    Ok(Bytes::from(
+                        rt.call_encode(WASM_FUNC_ENCODE, &encunit.to_data())?,
+                    ))
+                })?;
+                *row.wasm_encoded_size.as_mut().unwrap() += encoded.len();
+                *row.wasm_encode.as_mut().unwrap() += encode;
+                encoded
+            } else {
+                encoded
+            };
+            let decoder = WASMEncUnitDecoder::new(
+                encoded,
+                rt.clone(),
+                WASM_FUNC_GENERAL,
+                encunit.data_type().clone(),
+                encunit.len() as u64,
+            );
+            let (decoded, decode) = time(options.iterations, || decoder.decode().map_err(fff_err))
+                .context("WASM decoding failed")?;
+            row.wasm_decode += decode;
+            check_round_trip(&decoded, encunit, "WASM")?;
+        }
+        // Report the means per EncUnit.
+        let num_encunits = encunits.len().max(1);
+        row.input_size /= num_encunits;
+        row.native_encoded_size /= num_encunits;
+        row.native_encode /= num_encunits as u32;
+        row.native_decode /= num_encunits as u32;
+        row.wasm_encoded_size = row.wasm_encoded_size.map(|size| size / num_encunits);
+        row.wasm_encode = row.wasm_encode.map(|time| time / num_encunits as u32);
+        row.wasm_decode /= num_encunits as u32;
+        report.rows.push(row);
+    }
+    Ok(report)
+}
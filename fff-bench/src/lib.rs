@@ -1,5 +1,6 @@
 #![feature(exit_status_error)]
 pub mod bench_data;
+pub mod codec_bench;
 pub mod config;
 pub mod helper;
 use anyhow::Result;
@@ -33,9 +34,10 @@ use vortex_sampling_compressor::{SamplingCompressor, ALL_ENCODINGS_CONTEXT};
 use arrow_array::{ArrayRef, RecordBatch};
 use fff_poc::{
     context::WASMId,
+    io::mmap::LocalFile,
     options::FileWriterOptions,
     reader::{FileReaderV2Builder, Selection},
-    writer::FileWriter,
+    writer::write_batches,
 };
 use futures_util::TryStreamExt;
 use std::io::{BufWriter, Read, Seek, Write};
@@ -91,21 +93,8 @@ pub fn encode(encoder: impl Encoder, arr: ArrayRef) -> BytesMut {
 }
 
 pub fn write_fff(batches: &[RecordBatch], fff: &File, options: FileWriterOptions) -> Result<()> {
-    let mut fff_writer = FileWriter::try_new(batches[0].schema(), fff, options).unwrap();
-    let mut memory_usage_sum = 0;
-    let mut cnt = 0;
-    for batch in batches {
-        fff_writer.write_batch(batch).unwrap();
-        if fff_writer.memory_size() != 0 {
-            memory_usage_sum += fff_writer.memory_size();
-            cnt += 1;
-        }
-        // if cnt % 16 == 0 {
-        //     fff_writer.flush_pending_chunks().unwrap();
-        // }
-    }
-    error!("FFF memory usage: {}", memory_usage_sum / cnt);
-    fff_writer.finish().unwrap();
+    let stats = write_batches(batches[0].schema(), batches, fff, options).unwrap();
+    error!("FFF memory usage: {}", stats.avg_memory_size);
     Ok(())
 }
 
@@ -113,6 +102,8 @@ pub fn write_fff(batches: &[RecordBatch], fff: &File, options: FileWriterOptions
 pub struct ReadFFFOpt {
     pub projections: Option<fff_poc::reader::Projection>,
     pub selection: Option<Selection>,
+    /// Read local files through a memory mapping, see [`FileReaderV2Builder::prefer_mmap`].
+    pub prefer_mmap: bool,
 }
 
 pub fn read_fff(pathbuf: PathBuf, opt: ReadFFFOpt) -> Result<Vec<RecordBatch>> {
@@ -149,7 +140,9 @@ pub fn read_fff(pathbuf: PathBuf, opt: ReadFFFOpt) -> Result<Vec<RecordBatch>> {
     } else {
         let f1 = OpenOptions::new().read(true).open(pathbuf.clone()).unwrap();
 
-        let mut reader = FileReaderV2Builder::new(Arc::new(f1))
+        let builder = FileReaderV2Builder::new(LocalFile::from(f1));
+        // SAFETY: benchmarked files are not modified while read.
+        let mut reader = unsafe { builder.prefer_mmap(opt.prefer_mmap) }
             .with_projections(opt.projections.clone().unwrap_or_default())
             .with_selection(opt.selection.clone().unwrap_or_default())
             .build()
@@ -640,6 +633,7 @@ mod tests {
             crate::ReadFFFOpt {
                 projections: Some(fff_poc::reader::Projection::All),
                 selection: Some(fff_poc::reader::Selection::RowIndexes(vec![100])),
+                prefer_mmap: false,
             },
         )
         .unwrap();
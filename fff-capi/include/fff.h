@@ -0,0 +1,95 @@
+/* C API of the F3 reader, implemented in fff-capi/src/lib.rs.
+ *
+ * Functions return 0 on success. On failure they return -1 and fff_last_error()
+ * describes the error.
+ */
+#ifndef FFF_H
+#define FFF_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Arrow C Data Interface, https://arrow.apache.org/docs/format/CDataInterface.html */
+#ifndef ARROW_C_DATA_INTERFACE
+#define ARROW_C_DATA_INTERFACE
+
+#define ARROW_FLAG_DICTIONARY_ORDERED 1
+#define ARROW_FLAG_NULLABLE 2
+#define ARROW_FLAG_MAP_KEYS_SORTED 4
+
+struct ArrowSchema {
+  const char *format;
+  const char *name;
+  const char *metadata;
+  int64_t flags;
+  int64_t n_children;
+  struct ArrowSchema **children;
+  struct ArrowSchema *dictionary;
+  void (*release)(struct ArrowSchema *);
+  void *private_data;
+};
+
+struct ArrowArray {
+  int64_t length;
+  int64_t null_count;
+  int64_t offset;
+  int64_t n_buffers;
+  int64_t n_children;
+  const void **buffers;
+  struct ArrowArray **children;
+  struct ArrowArray *dictionary;
+  void (*release)(struct ArrowArray *);
+  void *private_data;
+};
+
+#endif /* ARROW_C_DATA_INTERFACE */
+
+/* Arrow C Stream Interface, https://arrow.apache.org/docs/format/CStreamInterface.html */
+#ifndef ARROW_C_STREAM_INTERFACE
+#define ARROW_C_STREAM_INTERFACE
+
+struct ArrowArrayStream {
+  int (*get_schema)(struct ArrowArrayStream *, struct ArrowSchema *out);
+  int (*get_next)(struct ArrowArrayStream *, struct ArrowArray *out);
+  const char *(*get_last_error)(struct ArrowArrayStream *);
+  void (*release)(struct ArrowArrayStream *);
+  void *private_data;
+};
+
+#endif /* ARROW_C_STREAM_INTERFACE */
+
+/* An open F3 file. */
+typedef struct FffReader FffReader;
+
+/* The message of the last error on this thread, or NULL if none.
+ * It is valid until the next failing call on this thread. */
+const char *fff_last_error(void);
+
+/* Open the F3 file at path and store the reader in *out.
+ * The reader must be freed with fff_reader_close. */
+int32_t fff_reader_open(const char *path, FffReader **out);
+
+/* Export the schema of the file to *out. The caller must release it. */
+int32_t fff_reader_schema(const FffReader *reader, struct ArrowSchema *out);
+
+/* Export the next batch of the file to *out as a struct array of the columns
+ * in the schema. The caller must release it. At the end of the file,
+ * out->release is NULL. */
+int32_t fff_reader_next_batch(FffReader *reader, struct ArrowArray *out);
+
+/* Export the batches not read yet to *out through the Arrow C Stream
+ * Interface, and free the reader. The caller must release the stream.
+ * Row groups are decoded as the stream is consumed. */
+int32_t fff_reader_into_stream(FffReader *reader, struct ArrowArrayStream *out);
+
+/* Free a reader. Does nothing if reader is NULL. */
+void fff_reader_close(FffReader *reader);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FFF_H */
@@ -0,0 +1,306 @@
+//! C API to embed the F3 reader in engines written in other languages, e.g., C++.
+//!
+//! Schemas and batches are exported through the Arrow C Data Interface, or the whole file through
+//! the Arrow C Stream Interface, see `include/fff.h` for the declarations. Chunks encoded with the
+//! WASM embedded in a file are decoded by the built-in WASM runtime, so the caller needs nothing
+//! more than this library.
+//!
+//! Functions return 0 on success. On failure they return -1 and [`fff_last_error`] describes the
+//! error.
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    fs::File,
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr,
+    sync::Arc,
+};
+
+use arrow::{
+    array::{Array, StructArray},
+    ffi::{FFI_ArrowArray, FFI_ArrowSchema},
+    ffi_stream::FFI_ArrowArrayStream,
+    record_batch::RecordBatchReader,
+};
+use fff_poc::reader::{FileReaderV2Builder, FileStream};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// An open F3 file, opaque to C.
+pub struct FffReader {
+    /// Row groups are decoded as [`fff_reader_next_batch`] consumes them.
+    stream: FileStream<Arc<File>>,
+}
+
+fn set_last_error(msg: String) {
+    let msg = CString::new(msg.replace('\0', " ")).unwrap();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(msg));
+}
+
+/// Run `f`, turning its errors and panics into -1 and the last error, as panics must not unwind
+/// into C.
+fn try_ffi(f: impl FnOnce() -> Result<(), String>) -> i32 {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => 0,
+        Ok(Err(msg)) => {
+            set_last_error(msg);
+            -1
+        }
+        Err(panic) => {
+            let msg = panic
+                .downcast_ref::<&str>()
+                .map(|msg| msg.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            set_last_error(format!("Panic in the F3 reader: {msg}"));
+            -1
+        }
+    }
+}
+
+/// The message of the last error on this thread, or NULL if none.
+/// It is valid until the next failing call on this thread.
+#[no_mangle]
+pub extern "C" fn fff_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |msg| msg.as_ptr())
+    })
+}
+
+/// Open the F3 file at `path` and store the reader in `*out`.
+/// The reader must be freed with [`fff_reader_close`].
+///
+/// # Safety
+/// `path` must be a NUL-terminated UTF-8 string and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn fff_reader_open(path: *const c_char, out: *mut *mut FffReader) -> i32 {
+    try_ffi(|| {
+        if path.is_null() || out.is_null() {
+            return Err("Null argument to fff_reader_open".to_string());
+        }
+        let path = CStr::from_ptr(path)
+            .to_str()
+            .map_err(|e| format!("Invalid path: {e}"))?;
+        let file = File::open(path).map_err(|e| format!("Failed to open {path}: {e}"))?;
+        let reader = FileReaderV2Builder::new(Arc::new(file))
+            .build()
+            .map_err(|e| e.to_string())?;
+        *out = Box::into_raw(Box::new(FffReader {
+            stream: reader.into_stream(),
+        }));
+        Ok(())
+    })
+}
+
+/// Export the schema of the file to `*out`. The caller owns it and must release it.
+///
+/// # Safety
+/// `reader` must come from [`fff_reader_open`] and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn fff_reader_schema(
+    reader: *const FffReader,
+    out: *mut FFI_ArrowSchema,
+) -> i32 {
+    try_ffi(|| {
+        if reader.is_null() || out.is_null() {
+            return Err("Null argument to fff_reader_schema".to_string());
+        }
+        let schema = FFI_ArrowSchema::try_from((*reader).stream.schema().as_ref())
+            .map_err(|e| e.to_string())?;
+        ptr::write(out, schema);
+        Ok(())
+    })
+}
+
+/// Export the next batch of the file to `*out` as a struct array of the columns in the schema.
+/// The caller owns it and must release it.
+/// At the end of the file, `out->release` is NULL, like in the Arrow C Stream Interface.
+///
+/// # Safety
+/// `reader` must come from [`fff_reader_open`] and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn fff_reader_next_batch(
+    reader: *mut FffReader,
+    out: *mut FFI_ArrowArray,
+) -> i32 {
+    try_ffi(|| {
+        if reader.is_null() || out.is_null() {
+            return Err("Null argument to fff_reader_next_batch".to_string());
+        }
+        let array = match (*reader).stream.next() {
+            Some(batch) => {
+                let batch = batch.map_err(|e| e.to_string())?;
+                FFI_ArrowArray::new(&StructArray::from(batch).to_data())
+            }
+            None => FFI_ArrowArray::empty(),
+        };
+        ptr::write(out, array);
+        Ok(())
+    })
+}
+
+/// Export the batches not read yet to `*out` through the Arrow C Stream Interface, and free the
+/// reader. The caller owns the stream and must release it.
+///
+/// # Safety
+/// `reader` must come from [`fff_reader_open`] and must not be used afterwards, and `out` must be
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn fff_reader_into_stream(
+    reader: *mut FffReader,
+    out: *mut FFI_ArrowArrayStream,
+) -> i32 {
+    try_ffi(|| {
+        if reader.is_null() || out.is_null() {
+            return Err("Null argument to fff_reader_into_stream".to_string());
+        }
+        let reader = Box::from_raw(reader);
+        ptr::write(out, reader.stream.into_ffi());
+        Ok(())
+    })
+}
+
+/// Free a reader. Does nothing if `reader` is NULL.
+///
+/// # Safety
+/// `reader` must come from [`fff_reader_open`] and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn fff_reader_close(reader: *mut FffReader) {
+    if !reader.is_null() {
+        drop(Box::from_raw(reader));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::MaybeUninit;
+
+    use arrow::{
+        array::{Int32Array, Int64Array},
+        datatypes::{DataType, Field, Schema},
+        ffi::from_ffi,
+        ffi_stream::ArrowArrayStreamReader,
+        record_batch::RecordBatch,
+    };
+    use fff_poc::{inspect::inspect_file, options::FileWriterOptionsBuilder, writer::FileWriter};
+
+    use super::*;
+
+    /// Write a test file, with its columns encoded by the built-in WASM if `write_built_in_wasm`,
+    /// so that the C API decodes them with the WASM embedded in the file.
+    fn write_file(path: &std::path::Path, write_built_in_wasm: bool) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Int64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..1000)),
+                Arc::new(Int64Array::from_iter(
+                    (0..1000).map(|x| (x % 7 != 0).then_some(x)),
+                )),
+            ],
+        )
+        .unwrap();
+        let mut writer = FileWriter::try_new(
+            schema,
+            File::create(path).unwrap(),
+            FileWriterOptionsBuilder::with_defaults()
+                .set_row_group_size(300)
+                .write_built_in_wasm(write_built_in_wasm)
+                .build(),
+        )
+        .unwrap();
+        writer.write_batch(&batch).unwrap();
+        writer.finish().unwrap();
+        let summary = inspect_file(&File::open(path).unwrap()).unwrap();
+        assert_eq!(summary.wasm_binaries.is_empty(), !write_built_in_wasm);
+        batch
+    }
+
+    #[test]
+    fn test_read_through_c_api() {
+        test_read_through_c_api_with(false);
+        test_read_through_c_api_with(true);
+    }
+
+    fn test_read_through_c_api_with(write_built_in_wasm: bool) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.fff");
+        let batch = write_file(&path, write_built_in_wasm);
+        let schema = batch.schema();
+
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+        let mut reader = ptr::null_mut();
+        unsafe {
+            assert_eq!(fff_reader_open(path.as_ptr(), &mut reader), 0);
+            let mut ffi_schema = MaybeUninit::uninit();
+            assert_eq!(fff_reader_schema(reader, ffi_schema.as_mut_ptr()), 0);
+            let ffi_schema = ffi_schema.assume_init();
+            assert_eq!(Schema::try_from(&ffi_schema).unwrap(), *schema);
+
+            let mut batches = vec![];
+            loop {
+                let mut ffi_array = MaybeUninit::uninit();
+                assert_eq!(fff_reader_next_batch(reader, ffi_array.as_mut_ptr()), 0);
+                let ffi_array = ffi_array.assume_init();
+                if ffi_array.is_released() {
+                    break;
+                }
+                let struct_array = StructArray::from(from_ffi(ffi_array, &ffi_schema).unwrap());
+                batches.push(RecordBatch::from(struct_array));
+            }
+            fff_reader_close(reader);
+            let output = arrow::compute::concat_batches(&schema, &batches).unwrap();
+            assert_eq!(output, batch);
+        }
+    }
+
+    #[test]
+    fn test_read_through_c_stream() {
+        test_read_through_c_stream_with(false);
+        test_read_through_c_stream_with(true);
+    }
+
+    fn test_read_through_c_stream_with(write_built_in_wasm: bool) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.fff");
+        let batch = write_file(&path, write_built_in_wasm);
+
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+        let mut reader = ptr::null_mut();
+        unsafe {
+            assert_eq!(fff_reader_open(path.as_ptr(), &mut reader), 0);
+            // The stream continues after the batches already read.
+            let mut ffi_array = MaybeUninit::uninit();
+            assert_eq!(fff_reader_next_batch(reader, ffi_array.as_mut_ptr()), 0);
+            let first = ffi_array.assume_init().len();
+            let mut ffi_stream = FFI_ArrowArrayStream::empty();
+            assert_eq!(fff_reader_into_stream(reader, &mut ffi_stream), 0);
+            let stream = ArrowArrayStreamReader::try_new(ffi_stream).unwrap();
+            let schema = stream.schema();
+            let batches = stream.collect::<Result<Vec<_>, _>>().unwrap();
+            let output = arrow::compute::concat_batches(&schema, &batches).unwrap();
+            assert_eq!(output, batch.slice(first, batch.num_rows() - first));
+        }
+    }
+
+    #[test]
+    fn test_open_error() {
+        let path = CString::new("/nonexistent/data.fff").unwrap();
+        let mut reader = ptr::null_mut();
+        unsafe {
+            assert_eq!(fff_reader_open(path.as_ptr(), &mut reader), -1);
+            assert!(reader.is_null());
+            let msg = CStr::from_ptr(fff_last_error()).to_str().unwrap();
+            assert!(msg.contains("/nonexistent/data.fff"));
+        }
+    }
+}
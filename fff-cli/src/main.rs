@@ -0,0 +1,227 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use arrow::{record_batch::RecordBatchReader, util::pretty::pretty_format_batches};
+use clap::{Parser, Subcommand};
+use fff_core::{errors::Result, general_error};
+use fff_poc::{
+    encoding_map::{parquet_writer_properties, writer_options_from_parquet},
+    inspect::inspect_file,
+    options::FileWriterOptionsBuilder,
+    reader::FileReaderV2Builder,
+    writer::FileWriter,
+};
+use parquet::arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter};
+
+/// Inspect, validate and convert F3 files.
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the footer: schema, row groups, chunk sizes and encodings, and embedded WASM.
+    Inspect { input: PathBuf },
+    /// Verify the file and IOUnit checksums and decode every chunk.
+    Validate { input: PathBuf },
+    /// Convert an F3 file to Parquet.
+    ToParquet { input: PathBuf, output: PathBuf },
+    /// Convert a Parquet file to F3.
+    FromParquet {
+        input: PathBuf,
+        output: PathBuf,
+        /// Number of rows per row group.
+        #[arg(long)]
+        row_group_size: Option<u64>,
+        /// Encode with the built-in WASM, so that the file embeds its decoder.
+        #[arg(long)]
+        write_built_in_wasm: bool,
+    },
+    /// Print the rows of an F3 file as a table.
+    Cat {
+        input: PathBuf,
+        /// Comma-separated names of the top-level columns to print. All by default.
+        #[arg(long, value_delimiter = ',')]
+        columns: Option<Vec<String>>,
+        /// Maximum number of rows to print.
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+}
+
+fn open(path: &Path) -> Result<Arc<File>> {
+    File::open(path)
+        .map(Arc::new)
+        .map_err(|e| general_error!(format!("Failed to open {}", path.display()), e))
+}
+
+fn validate(input: &Path) -> Result<()> {
+    let file = open(input)?;
+    let summary = inspect_file(&file)?;
+    let stream = FileReaderV2Builder::new(file)
+        .with_verify_file_checksum(true)
+        .with_verify_io_unit_checksum(true)
+        .with_verify_decoded_length(true)
+        .build()?
+        .into_stream();
+    // Row groups are decoded one at a time, so that the file does not have to fit in memory.
+    let mut num_rows = 0;
+    for batch in stream {
+        num_rows += batch?.num_rows();
+    }
+    if num_rows as u64 != summary.num_rows() {
+        return Err(general_error!(format!(
+            "Decoded {} rows, but the footer records {}",
+            num_rows,
+            summary.num_rows()
+        )));
+    }
+    println!(
+        "OK: {} rows in {} row groups",
+        num_rows,
+        summary.row_groups.len()
+    );
+    Ok(())
+}
+
+fn to_parquet(input: &Path, output: &Path) -> Result<()> {
+    let file = open(input)?;
+    let properties = parquet_writer_properties(&file)?;
+    // Row groups are decoded one at a time, so that the file does not have to fit in memory.
+    let stream = FileReaderV2Builder::new(file).build()?.into_stream();
+    let mut writer = ArrowWriter::try_new(File::create(output)?, stream.schema(), Some(properties))
+        .map_err(|e| general_error!("Failed to create the Parquet writer", e))?;
+    for batch in stream {
+        writer
+            .write(&batch?)
+            .map_err(|e| general_error!("Failed to write Parquet", e))?;
+    }
+    writer
+        .close()
+        .map_err(|e| general_error!("Failed to write Parquet", e))?;
+    Ok(())
+}
+
+fn from_parquet(
+    input: &Path,
+    output: &Path,
+    row_group_size: Option<u64>,
+    write_built_in_wasm: bool,
+) -> Result<()> {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(input)?)
+        .map_err(|e| general_error!("Failed to open the Parquet file", e))?;
+    // Keep the dictionary and native encodings of the Parquet columns.
+    let mut options = writer_options_from_parquet(
+        builder.metadata(),
+        FileWriterOptionsBuilder::with_defaults().write_built_in_wasm(write_built_in_wasm),
+    );
+    let reader = builder
+        .build()
+        .map_err(|e| general_error!("Failed to open the Parquet file", e))?;
+    if let Some(row_group_size) = row_group_size {
+        options = options.set_row_group_size(row_group_size);
+    }
+    let mut writer = FileWriter::try_new(reader.schema(), File::create(output)?, options.build())?;
+    for batch in reader {
+        writer.write_batch(&batch?)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+fn cat(input: &Path, columns: Option<Vec<String>>, limit: Option<usize>) -> Result<()> {
+    let mut builder = FileReaderV2Builder::new(open(input)?);
+    if let Some(columns) = columns {
+        builder = builder.with_projected_names(columns);
+    }
+    // Row groups are decoded one at a time, until the limit is reached.
+    let mut stream = builder.build()?.into_stream();
+    let mut batches = vec![];
+    let mut remaining = limit.unwrap_or(usize::MAX);
+    while remaining > 0 {
+        let Some(batch) = stream.next() else {
+            break;
+        };
+        let batch = batch?;
+        let batch = batch.slice(0, remaining.min(batch.num_rows()));
+        remaining -= batch.num_rows();
+        batches.push(batch);
+    }
+    println!("{}", pretty_format_batches(&batches)?);
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    match args.command {
+        Command::Inspect { input } => print!("{}", inspect_file(&open(&input)?)?),
+        Command::Validate { input } => validate(&input)?,
+        Command::ToParquet { input, output } => to_parquet(&input, &output)?,
+        Command::FromParquet {
+            input,
+            output,
+            row_group_size,
+            write_built_in_wasm,
+        } => from_parquet(&input, &output, row_group_size, write_built_in_wasm)?,
+        Command::Cat {
+            input,
+            columns,
+            limit,
+        } => cat(&input, columns, limit)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::{
+        array::{Int32Array, StringArray},
+        compute::concat_batches,
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReader;
+
+    use super::*;
+
+    #[test]
+    fn test_parquet_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.parquet");
+        let f3 = dir.path().join("data.f3");
+        let output = dir.path().join("output.parquet");
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..10_000)),
+                Arc::new(StringArray::from_iter(
+                    (0..10_000).map(|x| (x % 5 != 0).then(|| format!("s{}", x % 100))),
+                )),
+            ],
+        )
+        .unwrap();
+        let mut writer = ArrowWriter::try_new(File::create(&input).unwrap(), schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        from_parquet(&input, &f3, Some(3000), false).unwrap();
+        validate(&f3).unwrap();
+        cat(&f3, Some(vec!["b".to_string()]), Some(5)).unwrap();
+        assert!(cat(&f3, Some(vec!["c".to_string()]), None).is_err());
+        to_parquet(&f3, &output).unwrap();
+
+        let reader = ParquetRecordBatchReader::try_new(File::open(&output).unwrap(), 1024).unwrap();
+        let batches = reader.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(concat_batches(&batch.schema(), &batches).unwrap(), batch);
+    }
+}
@@ -564,6 +564,41 @@ pub fn primitive_array_from_arrow_buffers_iter(
     }
 }
 
+/// Like [`primitive_array_from_arrow_buffers_iter`], but with the validity stored apart from the
+/// buffers, e.g., in the validity sub-buffer of an EncUnit. The first buffer of `buffer_iter`
+/// must then be empty.
+pub fn primitive_array_from_arrow_buffers_iter_with_validity(
+    data_type: &DataType,
+    mut buffer_iter: impl Iterator<Item = Buffer>,
+    num_rows: u64,
+    validity: Option<NullBuffer>,
+) -> Result<ArrayRef> {
+    let Some(validity) = validity else {
+        return primitive_array_from_arrow_buffers_iter(data_type, buffer_iter, num_rows);
+    };
+    if validity.len() != num_rows as usize {
+        return Err(Error::General(format!(
+            "Validity of {} rows for an array of {} rows",
+            validity.len(),
+            num_rows
+        )));
+    }
+    match buffer_iter.next() {
+        Some(buffer) if buffer.is_empty() => {}
+        _ => {
+            return Err(Error::General(
+                "Validity is stored both apart and in the buffers".to_string(),
+            ))
+        }
+    }
+    let validity = validity.into_inner().sliced();
+    primitive_array_from_arrow_buffers_iter(
+        data_type,
+        std::iter::once(validity).chain(buffer_iter),
+        num_rows,
+    )
+}
+
 pub fn primitive_array_from_buffers(
     data_type: &DataType,
     buffers: Vec<BytesMut>,
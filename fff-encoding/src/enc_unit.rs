@@ -1,7 +1,7 @@
 use super::data_buffer::DataBuffer;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use bytes::{Bytes, BytesMut};
-use fff_core::errors::Result;
+use fff_core::errors::{Error, Result};
 use fff_core::util::bit_util::padding_size;
 use fff_format::File::fff::flatbuf as fb;
 use serde::{Deserialize, Serialize};
@@ -20,8 +20,10 @@ use std::{
 pub struct EncUnit {
     /// Leaf nodes contains the actual encoded data.
     buffers: Vec<Bytes>,
-    /// Deprecated after using Vortex.
-    _encoding: Encoding,
+    /// The validity sub-buffer, stored before the data, if the nulls are not handled by the encoding.
+    validity: Option<Bytes>,
+    /// The encoding of the data, recorded per EncUnit since encoders may choose it per input.
+    encoding: Encoding,
     /// Deprecated after using Vortex.
     /// Any non-leaf nodes must have children.
     _children: Vec<EncUnit>,
@@ -31,11 +33,36 @@ impl EncUnit {
     pub fn new(buffers: Vec<Bytes>, encoding: Encoding, children: Vec<EncUnit>) -> Self {
         Self {
             buffers,
-            _encoding: encoding,
+            validity: None,
+            encoding,
             _children: children,
         }
     }
 
+    /// Attach a validity sub-buffer written by [`encode_validity`](crate::validity::encode_validity).
+    pub fn with_validity(mut self, validity: Bytes) -> Self {
+        self.validity = Some(validity);
+        self
+    }
+
+    /// Size of the validity sub-buffer, if any.
+    pub fn validity_size(&self) -> Option<u32> {
+        self.validity.as_ref().map(|v| v.len() as u32)
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Size in bytes once serialized by [`Self::try_serialize`].
+    pub fn serialized_size(&self) -> usize {
+        self.validity
+            .iter()
+            .chain(self.buffers.iter())
+            .map(|b| b.len())
+            .sum()
+    }
+
     /// Deprecated after using Vortex.
     /// Flatten the EncUnit into data buffers, buffers offsets, and an encoding tree.
     fn _into_flat(self) -> FlatEncUnit {
@@ -46,7 +73,7 @@ impl EncUnit {
             buffers.push(self.buffers.into());
         }
         let mut encoding_tree = EncodingTree {
-            root: self._encoding,
+            root: self.encoding,
             children: vec![],
         };
         for child in self._children.into_iter() {
@@ -65,7 +92,7 @@ impl EncUnit {
     /// (Deprecated) Serialize the EncUnit into a byte buffer, via writting to `W`.
     /// Now directly write all the buffers.
     pub fn try_serialize<W: Write + Seek>(self, mut write: W) -> Result<W> {
-        for buf in self.buffers.iter() {
+        for buf in self.validity.iter().chain(self.buffers.iter()) {
             write.write_all(buf.as_ref())?;
         }
         Ok(write)
@@ -73,12 +100,121 @@ impl EncUnit {
     }
 }
 
+/// The header of a serialized EncUnit, shared by all built-in and custom encodings:
+///
+/// | metadata_size: u32 (little endian) | metadata: [u8; metadata_size] | zero padding to `alignment` | data: [u8] |
+///
+/// The content of `metadata` is up to the encoding (e.g., a Vortex DType or rkyv-serialized
+/// mini-block offsets). The padding makes `data` start at a multiple of `alignment`
+/// relative to the start of the EncUnit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncUnitHeader {
+    metadata: Bytes,
+    alignment: usize,
+}
+
+impl EncUnitHeader {
+    pub fn try_new(metadata: Bytes, alignment: usize) -> Result<Self> {
+        if alignment == 0 {
+            return Err(Error::General(
+                "EncUnit alignment must be positive".to_string(),
+            ));
+        }
+        if u32::try_from(metadata.len()).is_err() {
+            return Err(Error::General(format!(
+                "EncUnit metadata of {} bytes does not fit in u32",
+                metadata.len()
+            )));
+        }
+        Ok(Self {
+            metadata,
+            alignment,
+        })
+    }
+
+    pub fn metadata(&self) -> &Bytes {
+        &self.metadata
+    }
+
+    pub fn alignment(&self) -> usize {
+        self.alignment
+    }
+
+    pub fn padding_len(&self) -> usize {
+        padding_size(4 + self.metadata.len(), self.alignment)
+    }
+
+    /// The serialized size of the header, i.e., the offset of the data in the EncUnit.
+    pub fn data_offset(&self) -> usize {
+        4 + self.metadata.len() + self.padding_len()
+    }
+
+    /// Serialize the header into buffers, which can be prepended to the data buffers of an EncUnit.
+    pub fn to_buffers(&self) -> Vec<Bytes> {
+        vec![
+            Bytes::from((self.metadata.len() as u32).to_le_bytes().to_vec()),
+            self.metadata.clone(),
+            Bytes::from(vec![0u8; self.padding_len()]),
+        ]
+    }
+
+    pub fn serialize<W: Write>(&self, write: &mut W) -> Result<()> {
+        write.write_u32::<LittleEndian>(self.metadata.len() as u32)?;
+        write.write_all(&self.metadata)?;
+        write.write_all(&vec![0u8; self.padding_len()])?;
+        Ok(())
+    }
+
+    /// Parse the header from the front of `encunit`, leaving only the data in it.
+    /// `encunit` is left untouched if the header is malformed.
+    pub fn parse(encunit: &mut Bytes, alignment: usize) -> Result<Self> {
+        if encunit.len() < 4 {
+            return Err(Error::EOF(format!(
+                "EncUnit of {} bytes is too short for the metadata size",
+                encunit.len()
+            )));
+        }
+        let metadata_size = u32::from_le_bytes(encunit[..4].try_into().unwrap()) as usize;
+        if encunit.len() - 4 < metadata_size {
+            return Err(Error::EOF(format!(
+                "EncUnit metadata of {} bytes exceeds the remaining {} bytes",
+                metadata_size,
+                encunit.len() - 4
+            )));
+        }
+        let header = Self::try_new(encunit.slice(4..4 + metadata_size), alignment)?;
+        if encunit.len() < header.data_offset() {
+            return Err(Error::EOF(format!(
+                "EncUnit of {} bytes is too short for its {}-byte header",
+                encunit.len(),
+                header.data_offset()
+            )));
+        }
+        if encunit[4 + metadata_size..header.data_offset()]
+            .iter()
+            .any(|b| *b != 0)
+        {
+            return Err(Error::ParseError(
+                "EncUnit padding must be zeroed".to_string(),
+            ));
+        }
+        let _ = encunit.split_to(header.data_offset());
+        Ok(header)
+    }
+}
+
 #[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
 pub enum Encoding {
     BP, // Deprecated after using Vortex.
     Vortex,
     /// User-provided dylib encoder
     Custom,
+    /// Native run-length encoding for fixed-width primitives.
+    Rle,
+    /// Native delta encoding for fixed-width primitives.
+    Delta,
+    /// Native bit-packed booleans.
+    Boolean,
 }
 
 impl Encoding {
@@ -86,6 +222,9 @@ impl Encoding {
         match self {
             Encoding::Vortex => fb::EncodingType::CASCADE,
             Encoding::Custom => fb::EncodingType::CUSTOM_WASM,
+            Encoding::Rle => fb::EncodingType::RLE,
+            Encoding::Delta => fb::EncodingType::DELTA,
+            Encoding::Boolean => fb::EncodingType::BOOLEAN,
             _ => unimplemented!(),
         }
     }
@@ -96,6 +235,9 @@ impl From<fb::EncodingType> for Encoding {
         match encoding {
             fb::EncodingType::CASCADE => Encoding::Vortex,
             fb::EncodingType::CUSTOM_WASM => Encoding::Custom,
+            fb::EncodingType::RLE => Encoding::Rle,
+            fb::EncodingType::DELTA => Encoding::Delta,
+            fb::EncodingType::BOOLEAN => Encoding::Boolean,
             _ => unimplemented!(),
         }
     }
@@ -218,3 +360,87 @@ impl FlatEncUnit {
         Ok(buffers.pop_front().unwrap())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serialize(header: &EncUnitHeader, data: &[u8]) -> Bytes {
+        let mut buf = vec![];
+        header.serialize(&mut buf).unwrap();
+        buf.extend_from_slice(data);
+        buf.into()
+    }
+
+    #[test]
+    fn test_encunit_header_roundtrip() {
+        for alignment in [1, 4, 8, 64] {
+            for metadata_len in 0..=130 {
+                let metadata: Bytes = (0..metadata_len).map(|i| i as u8 + 1).collect();
+                let header = EncUnitHeader::try_new(metadata, alignment).unwrap();
+                let data = [7u8, 8, 9];
+                let mut encunit = serialize(&header, &data);
+                assert_eq!(encunit.len(), header.data_offset() + data.len());
+                assert_eq!(header.data_offset() % alignment, 0);
+                assert_eq!(
+                    header.to_buffers().concat(),
+                    encunit[..header.data_offset()].to_vec()
+                );
+
+                let parsed = EncUnitHeader::parse(&mut encunit, alignment).unwrap();
+                assert_eq!(parsed, header);
+                assert_eq!(encunit.as_ref(), &data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encunit_header_empty_data() {
+        let header = EncUnitHeader::try_new(Bytes::from_static(b"meta"), 64).unwrap();
+        let mut encunit = serialize(&header, &[]);
+        assert_eq!(EncUnitHeader::parse(&mut encunit, 64).unwrap(), header);
+        assert!(encunit.is_empty());
+    }
+
+    #[test]
+    fn test_encunit_header_malformed() {
+        let header = EncUnitHeader::try_new(Bytes::from_static(b"meta"), 64).unwrap();
+        let valid = serialize(&header, b"data");
+
+        // Too short to hold the metadata size.
+        for len in 0..4 {
+            let mut encunit = valid.slice(..len);
+            assert!(matches!(
+                EncUnitHeader::parse(&mut encunit, 64),
+                Err(Error::EOF(_))
+            ));
+            assert_eq!(encunit.len(), len);
+        }
+        // Truncated metadata or padding.
+        for len in 4..header.data_offset() {
+            let mut encunit = valid.slice(..len);
+            assert!(matches!(
+                EncUnitHeader::parse(&mut encunit, 64),
+                Err(Error::EOF(_))
+            ));
+            assert_eq!(encunit.len(), len);
+        }
+        // Metadata size larger than the EncUnit.
+        let mut encunit = valid.to_vec();
+        encunit[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            EncUnitHeader::parse(&mut Bytes::from(encunit), 64),
+            Err(Error::EOF(_))
+        ));
+        // Non-zero padding.
+        let mut encunit = valid.to_vec();
+        encunit[header.data_offset() - 1] = 1;
+        assert!(matches!(
+            EncUnitHeader::parse(&mut Bytes::from(encunit), 64),
+            Err(Error::ParseError(_))
+        ));
+        // Invalid alignment.
+        assert!(EncUnitHeader::parse(&mut valid.clone(), 0).is_err());
+        assert!(EncUnitHeader::try_new(Bytes::new(), 0).is_err());
+    }
+}
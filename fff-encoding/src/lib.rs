@@ -1,3 +1,4 @@
 mod data_buffer;
 pub mod enc_unit;
 pub mod schemes;
+pub mod validity;
@@ -0,0 +1,246 @@
+//! Per-EncUnit encoding selection by trial-encoding a sample of the input, similar to BtrBlocks.
+
+use std::rc::Rc;
+
+use arrow::compute::concat;
+use arrow_array::{Array, ArrayRef};
+use arrow_schema::DataType;
+use fff_core::errors::{Error, Result};
+
+use super::{
+    boolean::BooleanEncoder, delta::DeltaEncoder, encode_with_validity, rle::RleEncoder,
+    vortex::VortexEncoder, EncUnit, Encoder, Encoding,
+};
+
+/// Rough decode cost of each candidate relative to Vortex.
+const VORTEX_DECODE_COST: f64 = 1.0;
+const RLE_DECODE_COST: f64 = 0.5;
+const DELTA_DECODE_COST: f64 = 0.8;
+const BOOLEAN_DECODE_COST: f64 = 0.2;
+
+/// Options of [`AdaptiveEncoder`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveEncodingOptions {
+    /// Number of runs sampled from each array.
+    pub sample_count: usize,
+    /// Number of contiguous values per sampled run.
+    /// Runs keep the locality that RLE and delta rely on.
+    pub sample_len: usize,
+    /// Penalty on encodings that are slower to decode. 0 picks the smallest output.
+    /// A candidate scores its sample size times `1 + decode_speed_weight * decode_cost`.
+    pub decode_speed_weight: f64,
+}
+
+impl Default for AdaptiveEncodingOptions {
+    fn default() -> Self {
+        Self {
+            sample_count: 10,
+            sample_len: 64,
+            decode_speed_weight: 0.0,
+        }
+    }
+}
+
+/// Chooses the encoding of each EncUnit among the native ones and Vortex, whose cascading
+/// compressor covers bit-packing, dictionary and FSST.
+///
+/// The chosen encoding is recorded in the EncUnit, see [`EncUnit::encoding`].
+/// Nulls are moved to the validity sub-buffer for candidates that do not handle them.
+pub struct AdaptiveEncoder {
+    options: AdaptiveEncodingOptions,
+    /// Candidates and their decode costs.
+    candidates: Vec<(Rc<dyn Encoder>, f64)>,
+}
+
+impl AdaptiveEncoder {
+    pub fn new(options: AdaptiveEncodingOptions, enable_dict: bool) -> Self {
+        Self {
+            options,
+            candidates: vec![
+                (Rc::new(VortexEncoder::new(enable_dict)), VORTEX_DECODE_COST),
+                (Rc::new(RleEncoder), RLE_DECODE_COST),
+                (Rc::new(DeltaEncoder), DELTA_DECODE_COST),
+                (Rc::new(BooleanEncoder), BOOLEAN_DECODE_COST),
+            ],
+        }
+    }
+
+    /// Sample `sample_count` evenly spaced runs of `arr`, or the whole array if it is small.
+    fn sample(&self, arr: &ArrayRef) -> Result<ArrayRef> {
+        let AdaptiveEncodingOptions {
+            sample_count,
+            sample_len,
+            ..
+        } = self.options;
+        if sample_count == 0 || arr.len() <= sample_count * sample_len {
+            return Ok(arr.clone());
+        }
+        let stride = arr.len() / sample_count;
+        let runs: Vec<ArrayRef> = (0..sample_count)
+            .map(|i| arr.slice(i * stride, sample_len))
+            .collect();
+        Ok(concat(
+            &runs.iter().map(|r| r.as_ref()).collect::<Vec<_>>(),
+        )?)
+    }
+
+    /// Candidates able to encode `arr`, best first.
+    fn rank(&self, arr: &ArrayRef) -> Result<Vec<Rc<dyn Encoder>>> {
+        let sample = self.sample(arr)?;
+        let mut scored: Vec<(f64, Rc<dyn Encoder>)> = self
+            .candidates
+            .iter()
+            .filter_map(|(encoder, decode_cost)| {
+                let encunit = encode_with_validity(encoder.as_ref(), sample.clone()).ok()?;
+                let score = encunit.serialized_size() as f64
+                    * (1.0 + self.options.decode_speed_weight * decode_cost);
+                Some((score, encoder.clone()))
+            })
+            .collect();
+        scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(scored.into_iter().map(|(_, encoder)| encoder).collect())
+    }
+}
+
+impl Encoder for AdaptiveEncoder {
+    fn encode(&self, arr: ArrayRef) -> Result<EncUnit> {
+        // Only Vortex handles the other types, so skip the trials.
+        if !arr.data_type().is_primitive() && arr.data_type() != &DataType::Boolean {
+            return self.candidates[0].0.encode(arr);
+        }
+        let mut last_err = None;
+        for encoder in self.rank(&arr)? {
+            match encode_with_validity(encoder.as_ref(), arr.clone()) {
+                Ok(encunit) => return Ok(encunit),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            Error::NYI(format!(
+                "Adaptive encoding for data type {}",
+                arr.data_type()
+            ))
+        }))
+    }
+
+    /// The encoding varies per EncUnit. This is the one used for types without native encodings.
+    fn encoding_type(&self) -> Encoding {
+        Encoding::Vortex
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{
+        types::TimestampNanosecondType, BooleanArray, Int64Array, PrimitiveArray, StringArray,
+    };
+    use arrow_buffer::NullBuffer;
+
+    use super::*;
+
+    fn chosen(arr: ArrayRef, options: AdaptiveEncodingOptions) -> Encoding {
+        AdaptiveEncoder::new(options, true)
+            .encode(arr)
+            .unwrap()
+            .encoding()
+    }
+
+    #[test]
+    fn test_adaptive_selection() {
+        let options = AdaptiveEncodingOptions::default();
+        let ts: Vec<i64> = (0..64 * 1024)
+            .map(|x| 1_700_000_000_000_000_000 + x * 1_000_000_007)
+            .collect();
+        let ts = Arc::new(PrimitiveArray::<TimestampNanosecondType>::from(ts)) as ArrayRef;
+        assert_eq!(chosen(ts, options), Encoding::Delta);
+
+        let runs = Int64Array::new(
+            (0..64 * 1024).map(|x| x / 4096).collect::<Vec<_>>().into(),
+            Some(NullBuffer::from_iter(
+                (0..64 * 1024).map(|x| x % 10_000 >= 100),
+            )),
+        );
+        let runs = Arc::new(runs) as ArrayRef;
+        let encunit = AdaptiveEncoder::new(options, true)
+            .encode(runs.clone())
+            .unwrap();
+        assert_eq!(encunit.encoding(), Encoding::Rle);
+        assert!(encunit.validity_size().is_some());
+
+        // Scrambled bits do not compress, so no candidate is smaller than the bit-packed values,
+        // and the cheaper decoding makes the boolean encoding win.
+        let scramble = |x: u32| {
+            let x = (x ^ (x >> 16)).wrapping_mul(0x85eb_ca6b);
+            let x = (x ^ (x >> 13)).wrapping_mul(0xc2b2_ae35);
+            x ^ (x >> 16)
+        };
+        let bools = Arc::new(BooleanArray::from_iter(
+            (0..64 * 1024u32).map(|x| Some(scramble(x) & 1 == 1)),
+        )) as ArrayRef;
+        let weighted = AdaptiveEncodingOptions {
+            decode_speed_weight: 1.0,
+            ..options
+        };
+        assert_eq!(chosen(bools, weighted), Encoding::Boolean);
+
+        let strings = Arc::new(StringArray::from(vec!["a", "b", "c"])) as ArrayRef;
+        assert_eq!(chosen(strings, options), Encoding::Vortex);
+
+        let empty = Arc::new(Int64Array::from(Vec::<i64>::new())) as ArrayRef;
+        AdaptiveEncoder::new(options, true).encode(empty).unwrap();
+    }
+
+    #[test]
+    fn test_adaptive_decode_speed_weight() {
+        let encoder = |decode_speed_weight| AdaptiveEncoder {
+            options: AdaptiveEncodingOptions {
+                decode_speed_weight,
+                ..Default::default()
+            },
+            candidates: vec![
+                (Rc::new(RleEncoder), RLE_DECODE_COST),
+                (Rc::new(DeltaEncoder), DELTA_DECODE_COST),
+            ],
+        };
+        // 16 runs of 40 values: 204 bytes with RLE, and 2-bit deltas in 180 bytes.
+        let runs = Arc::new(Int64Array::from_iter_values((0..640).map(|x| x / 40))) as ArrayRef;
+        assert_eq!(
+            RleEncoder.encode(runs.clone()).unwrap().serialized_size(),
+            204
+        );
+        assert_eq!(
+            DeltaEncoder.encode(runs.clone()).unwrap().serialized_size(),
+            180
+        );
+        let chosen = |decode_speed_weight| {
+            encoder(decode_speed_weight)
+                .encode(runs.clone())
+                .unwrap()
+                .encoding()
+        };
+        // The smallest output wins without penalty, the faster to decode above a weight of 4/7.
+        assert_eq!(chosen(0.0), Encoding::Delta);
+        assert_eq!(chosen(0.5), Encoding::Delta);
+        assert_eq!(chosen(1.0), Encoding::Rle);
+    }
+
+    #[test]
+    fn test_adaptive_sample() {
+        let encoder = AdaptiveEncoder::new(AdaptiveEncodingOptions::default(), true);
+        let small = Arc::new(Int64Array::from_iter_values(0..100)) as ArrayRef;
+        assert_eq!(encoder.sample(&small).unwrap().len(), 100);
+        let large = Arc::new(Int64Array::from_iter_values(0..100_000)) as ArrayRef;
+        let sample = encoder.sample(&large).unwrap();
+        assert_eq!(sample.len(), 10 * 64);
+        assert_eq!(
+            sample
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .unwrap()
+                .value(64),
+            10_000
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! Bit-packed encoding for boolean arrays.
+
+use std::sync::Arc;
+
+use arrow::array::AsArray;
+use arrow_array::{ArrayRef, BooleanArray};
+use arrow_buffer::{BooleanBuffer, Buffer};
+use arrow_schema::DataType;
+use bytes::Bytes;
+use fff_core::errors::{Error, Result};
+use fff_core::util::bit_util::ceil;
+
+use super::fixed_width::MetadataReader;
+use super::{Decoder, EncUnit, Encoder, Encoding};
+use crate::enc_unit::{EncUnitHeader, ALIGNMENT};
+
+/// Layout (after the [`EncUnitHeader`]):
+/// metadata: | num_values: u32 |
+/// data: | values: [u8; ceil(num_values / 8)], LSB first |
+///
+/// Nulls are not handled by the encoding but stored in the validity sub-buffer of the EncUnit.
+pub struct BooleanEncoder;
+
+impl Encoder for BooleanEncoder {
+    fn encode(&self, arr: ArrayRef) -> Result<EncUnit> {
+        if arr.data_type() != &DataType::Boolean {
+            return Err(Error::NYI(format!(
+                "Boolean encoding for data type {}",
+                arr.data_type()
+            )));
+        }
+        let values = arr.as_boolean().values().sliced();
+        let metadata = Bytes::from((arr.len() as u32).to_le_bytes().to_vec());
+        let mut buffers = EncUnitHeader::try_new(metadata, ALIGNMENT)?.to_buffers();
+        buffers.push(Bytes::from(values.as_slice().to_vec()));
+        Ok(EncUnit::new(buffers, Encoding::Boolean, vec![]))
+    }
+
+    fn encoding_type(&self) -> Encoding {
+        Encoding::Boolean
+    }
+
+    fn handles_nulls(&self) -> bool {
+        false
+    }
+}
+
+pub struct BooleanDecoder {
+    num_values: usize,
+    data: Bytes,
+}
+
+impl BooleanDecoder {
+    pub fn try_new(mut encunit: Bytes) -> Result<Self> {
+        let header = EncUnitHeader::parse(&mut encunit, ALIGNMENT)?;
+        let num_values = MetadataReader::new(header.metadata()).read_u32()? as usize;
+        if encunit.len() < ceil(num_values, 8) {
+            return Err(Error::EOF(format!(
+                "Boolean EncUnit with {} values is truncated",
+                num_values
+            )));
+        }
+        Ok(Self {
+            num_values,
+            data: encunit,
+        })
+    }
+}
+
+impl Decoder for BooleanDecoder {
+    fn decode_all_as_array(&mut self) -> Result<ArrayRef> {
+        let values = BooleanBuffer::new(Buffer::from(self.data.as_ref()), 0, self.num_values);
+        Ok(Arc::new(BooleanArray::new(values, None)))
+    }
+
+    fn slice(&mut self, start: usize, stop: usize) -> Result<ArrayRef> {
+        Ok(self.decode_all_as_array()?.slice(start, stop - start))
+    }
+
+    fn take(&mut self, indexes: &[u32]) -> Result<ArrayRef> {
+        if let Some(&i) = indexes.iter().find(|&&i| i as usize >= self.num_values) {
+            return Err(Error::IndexOutOfBound(i as usize, self.num_values));
+        }
+        let values = BooleanBuffer::collect_bool(indexes.len(), |i| {
+            let index = indexes[i] as usize;
+            self.data[index / 8] & (1 << (index % 8)) != 0
+        });
+        Ok(Arc::new(BooleanArray::new(values, None)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use arrow_array::{Array, Int32Array, UInt32Array};
+
+    use super::*;
+    use crate::schemes::{
+        encode_to_bytes, encode_to_bytes_with_validity, split_validity, with_nulls,
+    };
+
+    fn roundtrip(arr: BooleanArray) {
+        let arr = Arc::new(arr) as ArrayRef;
+        let (mut bytes, validity_size, _) =
+            encode_to_bytes_with_validity(Rc::new(BooleanEncoder), arr.clone()).unwrap();
+        let nulls = split_validity(&mut bytes, validity_size, arr.len());
+        assert_eq!(nulls.as_ref(), arr.nulls().filter(|n| n.null_count() > 0));
+        let mut dec = BooleanDecoder::try_new(bytes).unwrap();
+        let decoded = with_nulls(dec.decode_all_as_array().unwrap(), nulls.clone());
+        assert_eq!(*decoded, *arr);
+        let values = dec.decode_all_as_array().unwrap();
+        let indexes = (0..arr.len() as u32).rev().step_by(3).collect::<Vec<_>>();
+        assert_eq!(
+            *dec.take(&indexes).unwrap(),
+            *arrow::compute::take(&values, &UInt32Array::from(indexes), None).unwrap()
+        );
+        assert!(dec.take(&[arr.len() as u32]).is_err());
+        if arr.len() > 2 {
+            let nulls = nulls.map(|n| n.slice(1, arr.len() - 2));
+            assert_eq!(
+                *with_nulls(dec.slice(1, arr.len() - 1).unwrap(), nulls),
+                *arr.slice(1, arr.len() - 2)
+            );
+        }
+    }
+
+    #[test]
+    fn test_boolean() {
+        let arr = BooleanArray::from((0..64 * 1024).map(|i| i % 3 == 0).collect::<Vec<_>>());
+        let bytes = encode_to_bytes(Rc::new(BooleanEncoder), Arc::new(arr.clone()));
+        assert!(bytes.len() <= 64 * 1024 / 8 + 8);
+        roundtrip(arr);
+
+        roundtrip(BooleanArray::from(vec![
+            Some(true),
+            None,
+            Some(false),
+            None,
+        ]));
+        roundtrip(BooleanArray::from(vec![None::<bool>; 3]));
+        roundtrip(BooleanArray::from(Vec::<bool>::new()));
+        roundtrip(BooleanArray::from(
+            (0..100)
+                .map(|i| (i % 5 != 0).then_some(i % 2 == 0))
+                .collect::<Vec<_>>(),
+        ));
+        let sliced = BooleanArray::from((0..100).map(|i| i % 7 == 0).collect::<Vec<_>>());
+        roundtrip(sliced.slice(3, 60));
+    }
+
+    #[test]
+    fn test_boolean_unsupported_and_malformed() {
+        let arr = Arc::new(Int32Array::from(vec![1])) as ArrayRef;
+        assert!(BooleanEncoder.encode(arr).is_err());
+
+        let arr = Arc::new(BooleanArray::from(vec![true; 20])) as ArrayRef;
+        let bytes = encode_to_bytes(Rc::new(BooleanEncoder), arr);
+        assert!(BooleanDecoder::try_new(bytes.slice(..bytes.len() - 1)).is_err());
+    }
+}
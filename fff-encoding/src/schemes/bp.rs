@@ -1,16 +1,20 @@
 /// Deprecated after using Vortex.
 /// Leave it here only for legacy usage.
 use std::mem;
+use std::sync::Arc;
 
-use crate::enc_unit::ALIGNMENT;
 use crate::enc_unit::MINIBLOCK_SIZE;
+use crate::enc_unit::{EncUnitHeader, ALIGNMENT};
 use crate::schemes::{EncUnitMetadata, Encoding};
 use arrow_array::{ArrayRef, UInt32Array};
 use arrow_buffer::{Buffer, MutableBuffer};
 use arrow_schema::DataType;
 use bytes::Bytes;
 use fastlanes::BitPacking;
-use fff_core::{errors::Result, util::bit_util::ceil};
+use fff_core::{
+    errors::{Error, Result},
+    util::bit_util::ceil,
+};
 
 use super::{Decoder, EncUnit, Encoder, NonNullDecoderState};
 
@@ -69,8 +73,6 @@ impl BPEncoder {
                 .unwrap()
                 .into_boxed_slice(),
         );
-        let metadata_size = metadata.len() as u32;
-        let metadata_size = Bytes::from(metadata_size.to_le_bytes().to_vec());
         let encoded_data: Vec<u8> = unsafe {
             let ratio = mem::size_of::<u32>() / mem::size_of::<u8>();
             let length = encoded_data.len() * ratio;
@@ -81,13 +83,8 @@ impl BPEncoder {
             // Construct new Vec
             Vec::from_raw_parts(ptr, length, capacity)
         };
-        let padding = Bytes::from(vec![
-            0u8;
-            (metadata_size.len() + metadata.len())
-                .next_multiple_of(ALIGNMENT)
-                - (metadata_size.len() + metadata.len())
-        ]);
-        let buffers = vec![metadata_size, metadata, padding, Bytes::from(encoded_data)];
+        let mut buffers = EncUnitHeader::try_new(metadata, ALIGNMENT)?.to_buffers();
+        buffers.push(Bytes::from(encoded_data));
         Ok(EncUnit::new(buffers, Encoding::BP, vec![]))
     }
 }
@@ -144,6 +141,37 @@ impl Decoder for BPDecoder {
         Ok(vec![output_buffer.into()])
     }
 
+    /// Only the mini blocks holding an index are unpacked.
+    fn take(&mut self, indexes: &[u32]) -> Result<ArrayRef> {
+        let metadata = self.state.metadata();
+        let num_values = metadata.num_values as usize;
+        let bw_per_mini_block = metadata.metadata.as_ref().unwrap();
+        let offsets = &metadata.mini_blocks_offsets;
+        let data: &[u32] = bytemuck::cast_slice(&self.state.data);
+        let mut mini_block = [0u32; MINIBLOCK_SIZE];
+        let mut unpacked = None;
+        let mut values = Vec::with_capacity(indexes.len());
+        for &i in indexes {
+            let i = i as usize;
+            if i >= num_values {
+                return Err(Error::IndexOutOfBound(i, num_values));
+            }
+            let block = i / MINIBLOCK_SIZE;
+            if unpacked != Some(block) {
+                unsafe {
+                    BitPacking::unchecked_unpack(
+                        bw_per_mini_block[block] as usize,
+                        &data[offsets[block] as usize..offsets[block + 1] as usize],
+                        &mut mini_block,
+                    );
+                }
+                unpacked = Some(block);
+            }
+            values.push(mini_block[i % MINIBLOCK_SIZE]);
+        }
+        Ok(Arc::new(UInt32Array::from(values)))
+    }
+
     fn decode_a_vector(&mut self) -> Result<Option<Vec<Buffer>>> {
         unimplemented!();
     }
@@ -175,6 +203,11 @@ mod tests {
         let output = primitive_array_from_arrow_buffers(arr.data_type(), res, 64 * 1024).unwrap();
         assert_eq!(*arr, *output);
 
+        let indexes = [5, 3000, 3001, 2, 64 * 1024 - 1];
+        let expected = UInt32Array::from_iter_values(indexes.iter().map(|&i| (i + 1) % 128));
+        assert_eq!(*dec.take(&indexes).unwrap(), expected);
+        assert!(dec.take(&[64 * 1024]).is_err());
+
         // for (_, row_id) in (0..64 * 1024).step_by(MINIBLOCK_SIZE).enumerate() {
         //     let res = dec.decode_a_vector().unwrap().unwrap();
         //     let output =
@@ -0,0 +1,258 @@
+//! Delta encoding for fixed-width primitive arrays, suited to sorted integers and timestamps.
+
+use arrow_array::ArrayRef;
+use arrow_schema::DataType;
+use bytes::Bytes;
+use fff_core::errors::{Error, Result};
+use fff_core::util::bit_util::ceil;
+
+use super::fixed_width::{array_from_u64s, value_width, values_as_u64, width_mask, MetadataReader};
+use super::{Decoder, EncUnit, Encoder, Encoding};
+use crate::enc_unit::{EncUnitHeader, ALIGNMENT};
+
+/// Layout (after the [`EncUnitHeader`]):
+/// metadata: | num_values: u32 | first_value: u64 | bit_width: u8 |
+/// data: | deltas: [num_values - 1; bit_width bits] |
+///
+/// Deltas are computed with wrapping arithmetic on the bit patterns of the values, zigzag encoded
+/// and bit-packed LSB first with a single bit width. Nulls are not handled by the encoding but
+/// stored in the validity sub-buffer of the EncUnit; values of null slots are encoded as is.
+pub struct DeltaEncoder;
+
+fn zigzag_encode(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+fn zigzag_decode(v: u64) -> i64 {
+    ((v >> 1) as i64) ^ -((v & 1) as i64)
+}
+
+/// Interpret the low `width` bytes of `v` as a signed integer.
+fn sign_extend(v: u64, width: usize) -> i64 {
+    let shift = 64 - width * 8;
+    ((v << shift) as i64) >> shift
+}
+
+fn bit_pack(values: &[u64], bit_width: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(ceil(values.len() * bit_width, 8));
+    let mut acc: u128 = 0;
+    let mut num_bits = 0;
+    for v in values {
+        acc |= (*v as u128) << num_bits;
+        num_bits += bit_width;
+        while num_bits >= 8 {
+            out.push(acc as u8);
+            acc >>= 8;
+            num_bits -= 8;
+        }
+    }
+    if num_bits > 0 {
+        out.push(acc as u8);
+    }
+    out
+}
+
+fn bit_unpack(packed: &[u8], num_values: usize, bit_width: usize) -> Vec<u64> {
+    let mask = if bit_width == 64 {
+        u64::MAX
+    } else {
+        (1u64 << bit_width) - 1
+    };
+    let mut out = Vec::with_capacity(num_values);
+    let mut bytes = packed.iter();
+    let mut acc: u128 = 0;
+    let mut num_bits = 0;
+    for _ in 0..num_values {
+        while num_bits < bit_width {
+            acc |= (*bytes.next().unwrap() as u128) << num_bits;
+            num_bits += 8;
+        }
+        out.push(acc as u64 & mask);
+        acc >>= bit_width;
+        num_bits -= bit_width;
+    }
+    out
+}
+
+impl Encoder for DeltaEncoder {
+    fn encode(&self, arr: ArrayRef) -> Result<EncUnit> {
+        let width = value_width(arr.data_type())?;
+        let values = values_as_u64(arr.as_ref(), width);
+        let deltas: Vec<u64> = values
+            .windows(2)
+            .map(|w| zigzag_encode(sign_extend(w[1].wrapping_sub(w[0]), width)))
+            .collect();
+        let bit_width = deltas
+            .iter()
+            .map(|d| 64 - d.leading_zeros() as usize)
+            .max()
+            .unwrap_or(0);
+
+        let data = bit_pack(&deltas, bit_width);
+        let mut metadata = vec![];
+        metadata.extend_from_slice(&(arr.len() as u32).to_le_bytes());
+        metadata.extend_from_slice(&values.first().copied().unwrap_or(0).to_le_bytes());
+        metadata.push(bit_width as u8);
+
+        let mut buffers = EncUnitHeader::try_new(Bytes::from(metadata), ALIGNMENT)?.to_buffers();
+        buffers.push(Bytes::from(data));
+        Ok(EncUnit::new(buffers, Encoding::Delta, vec![]))
+    }
+
+    fn encoding_type(&self) -> Encoding {
+        Encoding::Delta
+    }
+
+    fn handles_nulls(&self) -> bool {
+        false
+    }
+}
+
+pub struct DeltaDecoder {
+    data_type: DataType,
+    num_values: usize,
+    first_value: u64,
+    bit_width: usize,
+    data: Bytes,
+}
+
+impl DeltaDecoder {
+    pub fn try_new(mut encunit: Bytes, data_type: DataType) -> Result<Self> {
+        let header = EncUnitHeader::parse(&mut encunit, ALIGNMENT)?;
+        let mut metadata = MetadataReader::new(header.metadata());
+        let decoder = Self {
+            data_type,
+            num_values: metadata.read_u32()? as usize,
+            first_value: metadata.read_u64()?,
+            bit_width: metadata.read_u8()? as usize,
+            data: encunit,
+        };
+        if decoder.bit_width > 64 {
+            return Err(Error::ParseError(format!(
+                "Invalid delta bit width {}",
+                decoder.bit_width
+            )));
+        }
+        Ok(decoder)
+    }
+
+    /// Number of values recorded in the EncUnit. They are allocated up front when decoding, so
+    /// callers check it against the row count of the EncUnit in the footer first.
+    pub fn num_values(&self) -> usize {
+        self.num_values
+    }
+}
+
+impl Decoder for DeltaDecoder {
+    fn decode_all_as_array(&mut self) -> Result<ArrayRef> {
+        let width = value_width(&self.data_type)?;
+        let data = &self.data;
+        let num_deltas = self.num_values.saturating_sub(1);
+        if data.len() < ceil(num_deltas * self.bit_width, 8) {
+            return Err(Error::EOF(format!(
+                "Delta EncUnit with {} values is truncated",
+                self.num_values
+            )));
+        }
+        let mask = width_mask(width);
+        let mut prev = self.first_value;
+        let values = (self.num_values > 0).then_some(prev).into_iter().chain(
+            bit_unpack(data, num_deltas, self.bit_width)
+                .into_iter()
+                .map(|d| {
+                    prev = prev.wrapping_add(zigzag_decode(d) as u64) & mask;
+                    prev
+                }),
+        );
+        array_from_u64s(&self.data_type, values, self.num_values)
+    }
+
+    fn slice(&mut self, start: usize, stop: usize) -> Result<ArrayRef> {
+        Ok(self.decode_all_as_array()?.slice(start, stop - start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    use arrow_array::{
+        types::TimestampNanosecondType, Array, Date32Array, Float32Array, Int32Array, Int64Array,
+        Int8Array, PrimitiveArray, StringArray, UInt64Array,
+    };
+
+    use super::*;
+    use crate::schemes::{
+        encode_to_bytes, encode_to_bytes_with_validity, split_validity, with_nulls,
+    };
+
+    fn roundtrip(arr: ArrayRef) {
+        let (mut bytes, validity_size, _) =
+            encode_to_bytes_with_validity(Rc::new(DeltaEncoder), arr.clone()).unwrap();
+        let nulls = split_validity(&mut bytes, validity_size, arr.len());
+        let mut dec = DeltaDecoder::try_new(bytes.clone(), arr.data_type().clone()).unwrap();
+        let decoded = with_nulls(dec.decode_all_as_array().unwrap(), nulls.clone());
+        assert_eq!(*decoded, *arr);
+        if arr.len() > 2 {
+            let mut dec = DeltaDecoder::try_new(bytes, arr.data_type().clone()).unwrap();
+            let nulls = nulls.map(|n| n.slice(1, arr.len() - 2));
+            assert_eq!(
+                *with_nulls(dec.slice(1, arr.len() - 1).unwrap(), nulls),
+                *arr.slice(1, arr.len() - 2)
+            );
+        }
+    }
+
+    #[test]
+    fn test_delta() {
+        // Sorted timestamps about a second apart take less than half of their plain size.
+        let ts: Vec<i64> = (0..64 * 1024)
+            .map(|x| 1_700_000_000_000_000_000 + x * 1_000_000_000 + x % 7)
+            .collect();
+        let arr = Arc::new(PrimitiveArray::<TimestampNanosecondType>::from(ts)) as ArrayRef;
+        let bytes = encode_to_bytes(Rc::new(DeltaEncoder), arr.clone());
+        assert!(bytes.len() < 64 * 1024 * 4);
+        roundtrip(arr);
+
+        roundtrip(Arc::new(Int32Array::from_iter_values(0..10000)));
+        roundtrip(Arc::new(Int32Array::from(vec![
+            Some(5),
+            None,
+            Some(3),
+            Some(i32::MIN),
+        ])));
+        roundtrip(Arc::new(Int8Array::from(vec![
+            i8::MAX,
+            i8::MIN,
+            0,
+            -1,
+            i8::MAX,
+        ])));
+        roundtrip(Arc::new(UInt64Array::from(vec![
+            0,
+            u64::MAX,
+            1,
+            u64::MAX / 2,
+        ])));
+        roundtrip(Arc::new(Int64Array::from(vec![i64::MIN, i64::MAX, 0])));
+        roundtrip(Arc::new(Float32Array::from(vec![1.0, -2.5, 3.25])));
+        roundtrip(Arc::new(Date32Array::from(vec![19000; 17])));
+        roundtrip(Arc::new(Int64Array::from(vec![42])));
+        roundtrip(Arc::new(Int64Array::from(Vec::<i64>::new())));
+        roundtrip(Arc::new(Int32Array::from_iter_values(0..100).slice(3, 60)));
+    }
+
+    #[test]
+    fn test_delta_unsupported_and_malformed() {
+        let arr = Arc::new(StringArray::from(vec!["a"])) as ArrayRef;
+        assert!(DeltaEncoder.encode(arr).is_err());
+
+        let arr = Arc::new(Int64Array::from(vec![1, 100, -5, 7])) as ArrayRef;
+        let bytes = encode_to_bytes(Rc::new(DeltaEncoder), arr);
+        let truncated = bytes.slice(..bytes.len() - 1);
+        let mut dec = DeltaDecoder::try_new(truncated, DataType::Int64).unwrap();
+        assert!(dec.decode_all_as_array().is_err());
+        assert!(DeltaDecoder::try_new(bytes.slice(..8), DataType::Int64).is_err());
+    }
+}
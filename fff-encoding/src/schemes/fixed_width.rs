@@ -0,0 +1,110 @@
+//! Helpers shared by the native encodings of fixed-width primitive arrays.
+//!
+//! Values are handled as their little-endian bit patterns zero-extended to u64, so the same code
+//! serves signed, unsigned, floating-point and temporal types of 1, 2, 4 or 8 bytes.
+
+use arrow::array::ArrayData;
+use arrow_array::{make_array, Array, ArrayRef};
+use arrow_buffer::Buffer;
+use arrow_schema::DataType;
+use fff_core::errors::{Error, Result};
+
+/// Byte width of the values of `data_type`, if it is supported by the native encodings.
+pub(crate) fn value_width(data_type: &DataType) -> Result<usize> {
+    match data_type.primitive_width() {
+        Some(width @ (1 | 2 | 4 | 8)) if data_type.is_primitive() => Ok(width),
+        _ => Err(Error::NYI(format!(
+            "Native encoding for data type {}",
+            data_type
+        ))),
+    }
+}
+
+/// Mask of the bits used by a value of `width` bytes.
+pub(crate) fn width_mask(width: usize) -> u64 {
+    if width == 8 {
+        u64::MAX
+    } else {
+        (1u64 << (width * 8)) - 1
+    }
+}
+
+/// Read the values of a fixed-width primitive array, ignoring the validity.
+pub(crate) fn values_as_u64(arr: &dyn Array, width: usize) -> Vec<u64> {
+    let data = arr.to_data();
+    let start = data.offset() * width;
+    let bytes = &data.buffers()[0].as_slice()[start..start + data.len() * width];
+    read_u64s(bytes, width)
+}
+
+pub(crate) fn read_u64s(bytes: &[u8], width: usize) -> Vec<u64> {
+    bytes
+        .chunks_exact(width)
+        .map(|v| {
+            let mut buf = [0u8; 8];
+            buf[..width].copy_from_slice(v);
+            u64::from_le_bytes(buf)
+        })
+        .collect()
+}
+
+pub(crate) fn write_u64s(out: &mut Vec<u8>, values: impl IntoIterator<Item = u64>, width: usize) {
+    for v in values {
+        out.extend_from_slice(&v.to_le_bytes()[..width]);
+    }
+}
+
+/// Build an array of `data_type` from the bit patterns of its values.
+pub(crate) fn array_from_u64s(
+    data_type: &DataType,
+    values: impl IntoIterator<Item = u64>,
+    len: usize,
+) -> Result<ArrayRef> {
+    let width = value_width(data_type)?;
+    let mut buf = Vec::with_capacity(len * width);
+    write_u64s(&mut buf, values, width);
+    if buf.len() != len * width {
+        return Err(Error::ParseError(format!(
+            "Expected {} values but decoded {}",
+            len,
+            buf.len() / width
+        )));
+    }
+    let data = ArrayData::builder(data_type.clone())
+        .len(len)
+        .add_buffer(Buffer::from_vec(buf))
+        .build()?;
+    Ok(make_array(data))
+}
+
+/// Little-endian reader for the fixed-size fields of an EncUnit metadata.
+pub(crate) struct MetadataReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> MetadataReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        if self.bytes.len() < N {
+            return Err(Error::EOF("EncUnit metadata is truncated".to_string()));
+        }
+        let (head, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        Ok(head.try_into().unwrap())
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+}
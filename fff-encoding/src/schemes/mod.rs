@@ -1,20 +1,43 @@
 use std::{io::Cursor, rc::Rc};
 
-use arrow_array::ArrayRef;
+use arrow_array::{make_array, Array, ArrayRef};
 use arrow_buffer::Buffer;
-use byteorder::{LittleEndian, ReadBytesExt};
 use bytes::Bytes;
 use fff_core::{errors::Result, nyi_err};
 use rkyv::{Archive, Deserialize as rkyvDe, Serialize as rkyvSer};
 
-use crate::enc_unit::{EncUnit, Encoding, ALIGNMENT};
+use crate::enc_unit::{EncUnit, EncUnitHeader, Encoding, ALIGNMENT};
+use crate::validity::encode_validity;
 
+pub mod adaptive;
+pub mod boolean;
 pub mod bp;
+pub mod delta;
+mod fixed_width;
+pub mod rle;
 pub mod vortex;
 
 pub trait Encoder {
     fn encode(&self, arr: ArrayRef) -> Result<EncUnit>;
     fn encoding_type(&self) -> Encoding;
+    /// Whether the encoding keeps the nulls of the input itself.
+    /// If not, [`encode_with_validity`] stores them in the validity sub-buffer of the EncUnit.
+    fn handles_nulls(&self) -> bool {
+        true
+    }
+}
+
+/// Encode `arr`, moving its nulls to the validity sub-buffer of the EncUnit
+/// if the encoder does not handle them.
+pub fn encode_with_validity(encoder: &dyn Encoder, arr: ArrayRef) -> Result<EncUnit> {
+    match arr.nulls().filter(|nulls| nulls.null_count() > 0) {
+        Some(nulls) if !encoder.handles_nulls() => {
+            let validity = encode_validity(nulls);
+            let values = make_array(arr.to_data().into_builder().nulls(None).build()?);
+            Ok(encoder.encode(values)?.with_validity(validity))
+        }
+        _ => encoder.encode(arr),
+    }
 }
 
 pub trait Decoder {
@@ -31,6 +54,11 @@ pub trait Decoder {
         nyi_err!("slice")
     }
 
+    /// Decode only the values at `indexes`, in their order, without materializing the others.
+    fn take(&mut self, _indexes: &[u32]) -> Result<ArrayRef> {
+        nyi_err!("take")
+    }
+
     fn decode_a_vector(&mut self) -> Result<Option<Vec<Buffer>>> {
         nyi_err!("decode_a_vector")
     }
@@ -62,6 +90,39 @@ pub fn encode_to_bytes(encoder: Rc<dyn Encoder>, arr: ArrayRef) -> Bytes {
     encblock.try_serialize(&mut cursor).unwrap();
     buffer.into()
 }
+
+/// Like [`encode_to_bytes`] but via [`encode_with_validity`].
+/// Also returns the size of the validity sub-buffer at the start of the bytes, if any,
+/// and the encoding of the EncUnit.
+pub fn encode_to_bytes_with_validity(
+    encoder: Rc<dyn Encoder>,
+    arr: ArrayRef,
+) -> Result<(Bytes, Option<u32>, Encoding)> {
+    let encblock = encode_with_validity(encoder.as_ref(), arr)?;
+    let validity_size = encblock.validity_size();
+    let encoding = encblock.encoding();
+    let mut buffer = Vec::new();
+    let mut cursor = Cursor::new(&mut buffer);
+    encblock.try_serialize(&mut cursor)?;
+    Ok((buffer.into(), validity_size, encoding))
+}
+
+/// Split the validity sub-buffer off bytes from [`encode_to_bytes_with_validity`] and decode it.
+#[cfg(test)]
+pub(crate) fn split_validity(
+    bytes: &mut Bytes,
+    validity_size: Option<u32>,
+    len: usize,
+) -> Option<arrow_buffer::NullBuffer> {
+    validity_size
+        .map(|size| crate::validity::decode_validity(&bytes.split_to(size as usize), len).unwrap())
+}
+
+/// Attach `nulls` to an array decoded from an EncUnit.
+#[cfg(test)]
+pub(crate) fn with_nulls(arr: ArrayRef, nulls: Option<arrow_buffer::NullBuffer>) -> ArrayRef {
+    make_array(arr.into_data().into_builder().nulls(nulls).build().unwrap())
+}
 pub(crate) struct NonNullDecoderState {
     _vector_index: usize,
     metadata_bytes: Bytes,
@@ -72,17 +133,10 @@ pub(crate) struct NonNullDecoderState {
 impl NonNullDecoderState {
     /// We need to ensure the input encblock is aligned on 64B.
     pub fn new(mut encblock: Bytes) -> Self {
-        let metadata_size = encblock
-            .split_to(4)
-            .as_ref()
-            .read_u32::<LittleEndian>()
-            .unwrap();
-        let metadata = encblock.split_to(metadata_size as usize);
-        let _padding = encblock
-            .split_to((4 + metadata.len()).next_multiple_of(ALIGNMENT) - (4 + metadata.len()));
+        let header = EncUnitHeader::parse(&mut encblock, ALIGNMENT).unwrap();
         Self {
             _vector_index: 1,
-            metadata_bytes: metadata,
+            metadata_bytes: header.metadata().clone(),
             data: encblock,
         }
     }
@@ -0,0 +1,255 @@
+//! Run-length encoding for fixed-width primitive arrays.
+
+use std::sync::Arc;
+
+use arrow_array::{types::Int32Type, ArrayRef, Int32Array, RunArray};
+use arrow_schema::DataType;
+use bytes::Bytes;
+use fff_core::errors::{Error, Result};
+
+use super::fixed_width::{
+    array_from_u64s, read_u64s, value_width, values_as_u64, write_u64s, MetadataReader,
+};
+use super::{Decoder, EncUnit, Encoder, Encoding};
+use crate::enc_unit::{EncUnitHeader, ALIGNMENT};
+
+/// Layout (after the [`EncUnitHeader`]):
+/// metadata: | num_values: u32 | num_runs: u32 |
+/// data: | run_values: [T; num_runs] | run_ends: [u32; num_runs] |
+///
+/// Nulls are not handled by the encoding but stored in the validity sub-buffer of the EncUnit.
+/// Values of null slots are encoded as is, so they may split runs.
+pub struct RleEncoder;
+
+impl Encoder for RleEncoder {
+    fn encode(&self, arr: ArrayRef) -> Result<EncUnit> {
+        let width = value_width(arr.data_type())?;
+        let values = values_as_u64(arr.as_ref(), width);
+        let mut run_values = vec![];
+        let mut run_ends: Vec<u32> = vec![];
+        for (i, v) in values.iter().enumerate() {
+            if run_values.last() == Some(v) {
+                *run_ends.last_mut().unwrap() = i as u32 + 1;
+            } else {
+                run_values.push(*v);
+                run_ends.push(i as u32 + 1);
+            }
+        }
+
+        let mut data = vec![];
+        write_u64s(&mut data, run_values, width);
+        for end in run_ends.iter() {
+            data.extend_from_slice(&end.to_le_bytes());
+        }
+        let mut metadata = vec![];
+        metadata.extend_from_slice(&(arr.len() as u32).to_le_bytes());
+        metadata.extend_from_slice(&(run_ends.len() as u32).to_le_bytes());
+
+        let mut buffers = EncUnitHeader::try_new(Bytes::from(metadata), ALIGNMENT)?.to_buffers();
+        buffers.push(Bytes::from(data));
+        Ok(EncUnit::new(buffers, Encoding::Rle, vec![]))
+    }
+
+    fn encoding_type(&self) -> Encoding {
+        Encoding::Rle
+    }
+
+    fn handles_nulls(&self) -> bool {
+        false
+    }
+}
+
+pub struct RleDecoder {
+    data_type: DataType,
+    num_values: usize,
+    num_runs: usize,
+    data: Bytes,
+}
+
+impl RleDecoder {
+    pub fn try_new(mut encunit: Bytes, data_type: DataType) -> Result<Self> {
+        let header = EncUnitHeader::parse(&mut encunit, ALIGNMENT)?;
+        let mut metadata = MetadataReader::new(header.metadata());
+        Ok(Self {
+            data_type,
+            num_values: metadata.read_u32()? as usize,
+            num_runs: metadata.read_u32()? as usize,
+            data: encunit,
+        })
+    }
+
+    /// Number of values recorded in the EncUnit. They are allocated up front when decoding, so
+    /// callers check it against the row count of the EncUnit in the footer first.
+    pub fn num_values(&self) -> usize {
+        self.num_values
+    }
+
+    /// The values and the ends of the runs.
+    fn runs(&self) -> Result<(Vec<u64>, Vec<usize>)> {
+        let width = value_width(&self.data_type)?;
+        let data = &self.data;
+        if data.len() < self.num_runs * (width + 4) {
+            return Err(Error::EOF(format!(
+                "RLE EncUnit with {} runs is truncated",
+                self.num_runs
+            )));
+        }
+        let run_values = read_u64s(&data[..self.num_runs * width], width);
+        let run_ends = data[self.num_runs * width..self.num_runs * (width + 4)]
+            .chunks_exact(4)
+            .map(|e| u32::from_le_bytes(e.try_into().unwrap()) as usize)
+            .collect();
+        Ok((run_values, run_ends))
+    }
+
+    /// Decode the runs as a `RunArray` of `Int32` run ends, without expanding them.
+    pub fn decode_runs(&mut self) -> Result<ArrayRef> {
+        let (run_values, run_ends) = self.runs()?;
+        if run_ends.last().copied().unwrap_or(0) != self.num_values
+            || run_ends.windows(2).any(|w| w[0] >= w[1])
+        {
+            return Err(Error::ParseError(format!(
+                "Invalid RLE run ends for {} values",
+                self.num_values
+            )));
+        }
+        let values = array_from_u64s(&self.data_type, run_values, self.num_runs)?;
+        let run_ends = Int32Array::from_iter_values(run_ends.into_iter().map(|end| end as i32));
+        Ok(Arc::new(RunArray::<Int32Type>::try_new(
+            &run_ends, &values,
+        )?))
+    }
+}
+
+impl Decoder for RleDecoder {
+    fn decode_all_as_array(&mut self) -> Result<ArrayRef> {
+        let (run_values, run_ends) = self.runs()?;
+        let mut values = Vec::with_capacity(self.num_values);
+        for (v, end) in run_values.into_iter().zip(run_ends) {
+            if end < values.len() || end > self.num_values {
+                return Err(Error::ParseError(format!(
+                    "Invalid RLE run end {} for {} values",
+                    end, self.num_values
+                )));
+            }
+            values.resize(end, v);
+        }
+        array_from_u64s(&self.data_type, values, self.num_values)
+    }
+
+    fn slice(&mut self, start: usize, stop: usize) -> Result<ArrayRef> {
+        Ok(self.decode_all_as_array()?.slice(start, stop - start))
+    }
+
+    /// Look up the run of each index by binary search over the run ends, without expanding the
+    /// runs.
+    fn take(&mut self, indexes: &[u32]) -> Result<ArrayRef> {
+        let (run_values, run_ends) = self.runs()?;
+        let values = indexes
+            .iter()
+            .map(|&i| {
+                let i = i as usize;
+                if i >= self.num_values {
+                    return Err(Error::IndexOutOfBound(i, self.num_values));
+                }
+                run_values
+                    .get(run_ends.partition_point(|&end| end <= i))
+                    .copied()
+                    .ok_or_else(|| {
+                        Error::ParseError(format!(
+                            "RLE runs end before value {i} of {}",
+                            self.num_values
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        array_from_u64s(&self.data_type, values, indexes.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    use arrow_array::{
+        types::TimestampMicrosecondType, Array, Float64Array, Int16Array, Int64Array,
+        PrimitiveArray, StringArray, UInt32Array, UInt8Array,
+    };
+
+    use super::*;
+    use crate::schemes::{
+        encode_to_bytes, encode_to_bytes_with_validity, split_validity, with_nulls,
+    };
+
+    fn roundtrip(arr: ArrayRef) {
+        let (mut bytes, validity_size, _) =
+            encode_to_bytes_with_validity(Rc::new(RleEncoder), arr.clone()).unwrap();
+        let nulls = split_validity(&mut bytes, validity_size, arr.len());
+        let mut dec = RleDecoder::try_new(bytes.clone(), arr.data_type().clone()).unwrap();
+        let decoded = with_nulls(dec.decode_all_as_array().unwrap(), nulls.clone());
+        assert_eq!(*decoded, *arr);
+        let values = dec.decode_all_as_array().unwrap();
+        let indexes = (0..arr.len() as u32).rev().step_by(3).collect::<Vec<_>>();
+        assert_eq!(
+            *dec.take(&indexes).unwrap(),
+            *arrow::compute::take(&values, &UInt32Array::from(indexes), None).unwrap()
+        );
+        assert!(dec.take(&[arr.len() as u32]).is_err());
+        let runs = dec.decode_runs().unwrap();
+        let runs = runs.as_any().downcast_ref::<RunArray<Int32Type>>().unwrap();
+        let physical = runs
+            .get_physical_indices(&(0..arr.len() as u32).collect::<Vec<_>>())
+            .unwrap();
+        let indices = UInt32Array::from_iter_values(physical.into_iter().map(|i| i as u32));
+        assert_eq!(
+            *arrow::compute::take(runs.values(), &indices, None).unwrap(),
+            *values
+        );
+        if arr.len() > 2 {
+            let mut dec = RleDecoder::try_new(bytes, arr.data_type().clone()).unwrap();
+            let nulls = nulls.map(|n| n.slice(1, arr.len() - 2));
+            assert_eq!(
+                *with_nulls(dec.slice(1, arr.len() - 1).unwrap(), nulls),
+                *arr.slice(1, arr.len() - 2)
+            );
+        }
+    }
+
+    #[test]
+    fn test_rle() {
+        let runs: Vec<i64> = (0..64 * 1024).map(|x| x / 1000 - 20).collect();
+        let arr = Arc::new(Int64Array::from(runs)) as ArrayRef;
+        let bytes = encode_to_bytes(Rc::new(RleEncoder), arr.clone());
+        assert!(bytes.len() < 2048);
+        roundtrip(arr);
+
+        roundtrip(Arc::new(Int16Array::from(vec![
+            Some(1),
+            None,
+            None,
+            Some(1),
+            Some(-3),
+        ])));
+        roundtrip(Arc::new(UInt8Array::from(vec![255u8; 100])));
+        roundtrip(Arc::new(Float64Array::from(vec![1.5, 1.5, 2.0, -0.0])));
+        roundtrip(Arc::new(
+            PrimitiveArray::<TimestampMicrosecondType>::from(vec![7, 7, 7, 9]).with_timezone("UTC"),
+        ));
+        roundtrip(Arc::new(Int64Array::from(Vec::<i64>::new())));
+        roundtrip(Arc::new(Int64Array::from_iter_values(0..100).slice(10, 50)));
+    }
+
+    #[test]
+    fn test_rle_unsupported_and_malformed() {
+        let arr = Arc::new(StringArray::from(vec!["a"])) as ArrayRef;
+        assert!(RleEncoder.encode(arr).is_err());
+
+        let arr = Arc::new(Int64Array::from(vec![1, 1, 2, 2, 3])) as ArrayRef;
+        let bytes = encode_to_bytes(Rc::new(RleEncoder), arr);
+        let truncated = bytes.slice(..bytes.len() - 1);
+        let mut dec = RleDecoder::try_new(truncated, DataType::Int64).unwrap();
+        assert!(dec.decode_all_as_array().is_err());
+        assert!(dec.decode_runs().is_err());
+    }
+}
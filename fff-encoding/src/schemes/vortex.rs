@@ -1,21 +1,19 @@
 use std::ops::Range;
 use std::sync::Arc;
 
-use crate::enc_unit::ZEROS;
+use crate::enc_unit::EncUnitHeader;
 
 use super::{Decoder, EncUnit, Encoder, Encoding};
 use arrow::array::AsArray;
 use arrow::datatypes::{Int32Type, Int64Type};
 use arrow_array::downcast_integer;
 use arrow_array::downcast_primitive_array_helper;
-use arrow_array::{Array, ArrayRef, BooleanArray, DictionaryArray, PrimitiveArray};
+use arrow_array::{Array, ArrayRef, BooleanArray, DictionaryArray, PrimitiveArray, UInt32Array};
 use arrow_buffer::{BooleanBuffer, Buffer};
 use arrow_schema::DataType;
-use byteorder::{LittleEndian, ReadBytesExt};
 use bytes::Bytes;
 use fff_core::errors::{Error, Result};
 use fff_core::non_nest_types;
-use fff_core::util::bit_util::padding_size;
 use fff_core::util::buffer_to_array::new_list_offsets_validity_from_buffers;
 use futures::executor::block_on;
 use serde::{Deserialize, Serialize};
@@ -91,20 +89,15 @@ impl VortexEncoder {
         let encoded_data = Vec::<u8>::new();
         let mut writer = MessageWriter::new(encoded_data);
         block_on(writer.write_dtype(compressed_array.dtype().clone()))?;
-        let dtype_buf = writer.into_inner();
-        let dtype_size = Bytes::from((dtype_buf.len() as u32).to_le_bytes().to_vec());
-        let written_len = dtype_buf.len() + dtype_size.len();
+        let header = EncUnitHeader::try_new(Bytes::from(writer.into_inner()), VORTEX_ALIGNMENT)?;
         let encoded_data = Vec::<u8>::new();
         let mut writer = MessageWriter::new(encoded_data);
         block_on(writer.write_array(compressed_array))?;
         let w = writer.into_inner();
 
-        Ok(vec![
-            dtype_size,
-            Bytes::from(dtype_buf),
-            Bytes::from(&ZEROS[..padding_size(written_len, VORTEX_ALIGNMENT)]),
-            Bytes::from(w),
-        ])
+        let mut buffers = header.to_buffers();
+        buffers.push(Bytes::from(w));
+        Ok(buffers)
     }
 
     fn regular_encode(&self, arr: ArrayRef) -> Result<EncUnit> {
@@ -245,8 +238,8 @@ impl Encoder for VortexEncoder {
 }
 
 pub fn vortex_deser(encblock: &mut Bytes, context: Arc<Context>) -> Result<ArrayData> {
-    let metadata_size = encblock.split_to(4).as_ref().read_u32::<LittleEndian>()?;
-    let mut dtype_bytes = encblock.split_to(metadata_size as usize);
+    let header = EncUnitHeader::parse(encblock, VORTEX_ALIGNMENT)?;
+    let mut dtype_bytes = header.metadata().clone();
 
     let mut dtype_reader = DTypeBufferReader::new();
     let mut read_buf = Bytes::new();
@@ -254,7 +247,6 @@ pub fn vortex_deser(encblock: &mut Bytes, context: Arc<Context>) -> Result<Array
         read_buf = dtype_bytes.split_to(u);
     }
     let dtype = dtype_reader.into_dtype();
-    let _padding = encblock.split_to(padding_size(metadata_size as usize + 4, VORTEX_ALIGNMENT));
     let mut array_reader = ArrayMessageReader::new();
     let mut read_buf = Bytes::new();
     while let Some(u) = array_reader.read(read_buf)? {
@@ -376,6 +368,20 @@ impl Decoder for VortexDecoder {
         Ok(arrow_array)
     }
 
+    /// Take from the compressed array, so that encodings supporting it, e.g., bit-packing,
+    /// dictionary or run-end, only decode the values at `indexes`.
+    fn take(&mut self, indexes: &[u32]) -> Result<ArrayRef> {
+        let arr = self.vortex_array.take().unwrap();
+        let indexes = ArrayData::from_arrow(
+            Arc::new(UInt32Array::from(indexes.to_vec())) as ArrayRef,
+            false,
+        );
+        let arr = vortex_array::compute::take(&arr, &indexes)
+            .and_then(IntoCanonical::into_canonical)
+            .map(ArrayData::from)?;
+        Ok(arr.into_arrow()?)
+    }
+
     fn decode_all_as_array(&mut self) -> Result<ArrayRef> {
         if self.partial_decode {
             let arr = self.vortex_array.take().unwrap();
@@ -598,9 +604,14 @@ mod tests {
         let enc = Rc::new(VortexEncoder::default()) as Rc<dyn Encoder>;
         let bytes = encode_to_bytes(enc, arr.clone());
         // For the new simplified approach, we can directly use the bytes
-        let mut dec = VortexDecoder::try_new(bytes, ALL_ENCODINGS_CONTEXT.clone()).unwrap();
+        let mut dec = VortexDecoder::try_new(bytes.clone(), ALL_ENCODINGS_CONTEXT.clone()).unwrap();
         let decoded = dec.decode_all_as_array().unwrap();
         assert_eq!(*arr, *decoded);
+
+        let indexes = [5, 3000, 3001, 2, 64 * 1024 - 1];
+        let mut dec = VortexDecoder::try_new(bytes, ALL_ENCODINGS_CONTEXT.clone()).unwrap();
+        let expected = UInt32Array::from_iter_values(indexes.iter().map(|&i| (i + 1) % 128));
+        assert_eq!(*dec.take(&indexes).unwrap(), expected);
     }
 
     #[test]
@@ -0,0 +1,120 @@
+//! The validity sub-buffer of an EncUnit, which lets encodings assume no nulls.
+//!
+//! Layout (RLE of alternating valid and null runs):
+//! | num_runs: u32 | first_run_is_valid: u8 | run_lengths: [u32; num_runs] |
+
+use arrow_buffer::{BooleanBufferBuilder, NullBuffer};
+use bytes::Bytes;
+use fff_core::errors::{Error, Result};
+
+/// Encode the validity of an array as runs of valid and null values.
+pub fn encode_validity(nulls: &NullBuffer) -> Bytes {
+    let mut runs: Vec<u32> = vec![];
+    let mut pos = 0;
+    let mut first_run_is_valid = true;
+    for (start, end) in nulls.inner().set_slices() {
+        if start > pos {
+            if runs.is_empty() {
+                first_run_is_valid = false;
+            }
+            runs.push((start - pos) as u32);
+        }
+        runs.push((end - start) as u32);
+        pos = end;
+    }
+    if pos < nulls.len() {
+        if runs.is_empty() {
+            first_run_is_valid = false;
+        }
+        runs.push((nulls.len() - pos) as u32);
+    }
+
+    let mut out = Vec::with_capacity(5 + runs.len() * 4);
+    out.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+    out.push(first_run_is_valid as u8);
+    for run in runs {
+        out.extend_from_slice(&run.to_le_bytes());
+    }
+    Bytes::from(out)
+}
+
+/// Decode the validity of `len` values written by [`encode_validity`].
+pub fn decode_validity(data: &[u8], len: usize) -> Result<NullBuffer> {
+    if data.len() < 5 {
+        return Err(Error::EOF(format!(
+            "Validity sub-buffer of {} bytes is too short for its header",
+            data.len()
+        )));
+    }
+    let num_runs = u32::from_le_bytes(data[..4].try_into().unwrap()) as usize;
+    let mut is_valid = data[4] != 0;
+    let runs = &data[5..];
+    if runs.len() != num_runs * 4 {
+        return Err(Error::ParseError(format!(
+            "Validity sub-buffer has {} bytes of runs but {} runs are recorded",
+            runs.len(),
+            num_runs
+        )));
+    }
+    let mut builder = BooleanBufferBuilder::new(len);
+    for run in runs.chunks_exact(4) {
+        let run = u32::from_le_bytes(run.try_into().unwrap()) as usize;
+        if builder.len() + run > len {
+            return Err(Error::ParseError(format!(
+                "Validity runs exceed the {} values of the EncUnit",
+                len
+            )));
+        }
+        builder.append_n(run, is_valid);
+        is_valid = !is_valid;
+    }
+    if builder.len() != len {
+        return Err(Error::ParseError(format!(
+            "Validity runs cover {} values but the EncUnit has {}",
+            builder.len(),
+            len
+        )));
+    }
+    Ok(NullBuffer::new(builder.finish()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(validity: Vec<bool>) {
+        let nulls = NullBuffer::from(validity);
+        let encoded = encode_validity(&nulls);
+        assert_eq!(decode_validity(&encoded, nulls.len()).unwrap(), nulls);
+    }
+
+    #[test]
+    fn test_validity_roundtrip() {
+        roundtrip(vec![]);
+        roundtrip(vec![true; 100]);
+        roundtrip(vec![false; 100]);
+        roundtrip(vec![false, true, true, false]);
+        roundtrip(vec![true, false, false, true]);
+        roundtrip((0..10_000).map(|i| i % 7 != 0).collect());
+
+        // Long runs take a few bytes.
+        let nulls = NullBuffer::from((0..64 * 1024).map(|i| i < 1000).collect::<Vec<_>>());
+        assert_eq!(encode_validity(&nulls).len(), 5 + 2 * 4);
+
+        // Sliced validity.
+        let nulls = NullBuffer::from((0..100).map(|i| i % 3 == 0).collect::<Vec<_>>());
+        let sliced = nulls.slice(5, 50);
+        let encoded = encode_validity(&sliced);
+        assert_eq!(decode_validity(&encoded, 50).unwrap(), sliced);
+    }
+
+    #[test]
+    fn test_validity_malformed() {
+        let nulls = NullBuffer::from(vec![true, false, false, true]);
+        let encoded = encode_validity(&nulls);
+        assert!(decode_validity(&encoded[..4], 4).is_err());
+        assert!(decode_validity(&encoded[..encoded.len() - 1], 4).is_err());
+        assert!(decode_validity(&encoded, 3).is_err());
+        assert!(decode_validity(&encoded, 5).is_err());
+    }
+}
@@ -3,8 +3,12 @@ use flatbuffers::{FlatBufferBuilder, WIPOffset};
 pub mod File;
 
 pub const MAJOR_VERSION: u16 = 0;
-pub const MINOR_VERSION: u16 = 1;
+/// Bumped when files may hold what older readers cannot decode. 0.2 added BOOLEAN EncUnits and
+/// validity sub-buffers.
+pub const MINOR_VERSION: u16 = 2;
 pub const MAGIC: &[u8; 2] = b"F3";
+/// Ends the postscript instead of [`MAGIC`] when the metadata is encrypted.
+pub const MAGIC_ENCRYPTED_FOOTER: &[u8; 2] = b"FE";
 pub const POSTSCRIPT_SIZE: u64 = 32;
 
 pub trait ToFlatBuffer {
@@ -0,0 +1,66 @@
+//! Encrypt a column and the footer of a file at rest with data keys of AWS KMS, and read it
+//! back.
+//!
+//! ```sh
+//! AWS_REGION=us-east-1 cargo run -p fff-poc --features kms --example kms_encryption -- \
+//!     alias/my-fff-key
+//! ```
+//!
+//! The credentials are taken from the environment, and need `kms:GenerateDataKey` to write and
+//! `kms:Decrypt` to read with the given KMS key.
+
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, Int64Array, RecordBatch, StringArray};
+use fff_poc::{
+    encryption::AwsKmsKeyProvider, options::FileWriterOptionsBuilder, reader::FileReaderV2Builder,
+    writer::FileWriter,
+};
+
+fn main() {
+    let kms_key_id = std::env::args()
+        .nth(1)
+        .expect("Usage: kms_encryption <KMS key id, ARN or alias>");
+    let batch = RecordBatch::try_from_iter(vec![
+        (
+            "id",
+            Arc::new(Int64Array::from_iter_values(0..1000)) as ArrayRef,
+        ),
+        (
+            "email",
+            Arc::new(StringArray::from_iter_values(
+                (0..1000).map(|i| format!("user{i}@example.com")),
+            )),
+        ),
+    ])
+    .unwrap();
+    let key_provider = Arc::new(AwsKmsKeyProvider::from_env());
+
+    // The "email" column and the footer each get a data key, stored wrapped by KMS.
+    let mut file = tempfile::tempfile().unwrap();
+    let mut writer = FileWriter::try_new(
+        batch.schema(),
+        &mut file,
+        FileWriterOptionsBuilder::with_defaults()
+            .encrypt_columns([(1, kms_key_id.clone())].into())
+            .encrypt_footer(kms_key_id)
+            .set_key_provider(key_provider.clone())
+            .build(),
+    )
+    .unwrap();
+    writer.write_batch(&batch).unwrap();
+    writer.finish().unwrap();
+    let file = Arc::new(file);
+
+    // Without access to the KMS key, the file cannot even be opened.
+    assert!(FileReaderV2Builder::new(file.clone()).build().is_err());
+    let output = FileReaderV2Builder::new(file)
+        .with_key_provider(key_provider)
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    let num_rows: usize = output.iter().map(|batch| batch.num_rows()).sum();
+    assert_eq!(num_rows, batch.num_rows());
+    println!("Read back {num_rows} rows decrypted with data keys of KMS");
+}
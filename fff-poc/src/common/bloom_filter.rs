@@ -0,0 +1,178 @@
+//! Split-block bloom filters of chunks, like Parquet's.
+//!
+//! Values are hashed with xxHash64 of their bytes: the bytes of binary and string values, the
+//! little-endian bytes of fixed-width values and a single byte for booleans. The filter is an
+//! array of 256-bit blocks, each value setting one bit in each of the 8 words of a block.
+
+use arrow::array::AsArray;
+use arrow_array::Array;
+use arrow_schema::DataType;
+use byteorder::{ByteOrder, LittleEndian};
+use fff_core::{errors::Result, nyi_err};
+use xxhash_rust::xxh64::xxh64;
+
+const SALT: [u32; 8] = [
+    0x47b6137b, 0x44974d91, 0x8824ad5b, 0xa2b7289d, 0x705495c7, 0x2df1424b, 0x9efc4947, 0x5c6bfb31,
+];
+const BLOCK_SIZE: usize = 32;
+const MAX_FILTER_SIZE: usize = 128 * 1024 * 1024;
+
+type Block = [u32; 8];
+
+fn block_mask(hash: u32) -> Block {
+    std::array::from_fn(|i| 1 << (hash.wrapping_mul(SALT[i]) >> 27))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter {
+    blocks: Vec<Block>,
+}
+
+impl BloomFilter {
+    /// An empty filter sized for `num_distinct` values at a false positive probability of `fpp`.
+    pub fn new(num_distinct: usize, fpp: f64) -> Self {
+        let num_bits = -8.0 * num_distinct as f64 / (1.0 - fpp.powf(1.0 / 8.0)).ln();
+        let num_bytes = ((num_bits / 8.0) as usize)
+            .clamp(BLOCK_SIZE, MAX_FILTER_SIZE)
+            .next_power_of_two();
+        Self {
+            blocks: vec![[0; 8]; num_bytes / BLOCK_SIZE],
+        }
+    }
+
+    fn block_index(&self, hash: u64) -> usize {
+        (((hash >> 32) * self.blocks.len() as u64) >> 32) as usize
+    }
+
+    pub fn insert_hash(&mut self, hash: u64) {
+        let index = self.block_index(hash);
+        let mask = block_mask(hash as u32);
+        for (word, bit) in self.blocks[index].iter_mut().zip(mask) {
+            *word |= bit;
+        }
+    }
+
+    /// False if no value with this hash was inserted. True may be a false positive.
+    pub fn check_hash(&self, hash: u64) -> bool {
+        let block = &self.blocks[self.block_index(hash)];
+        block
+            .iter()
+            .zip(block_mask(hash as u32))
+            .all(|(word, bit)| word & bit != 0)
+    }
+
+    /// Size of the filter in bytes.
+    pub fn size(&self) -> usize {
+        self.blocks.len() * BLOCK_SIZE
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.blocks
+            .iter()
+            .flatten()
+            .flat_map(|w| w.to_le_bytes())
+            .collect()
+    }
+
+    /// None if `buf` is not a whole number of blocks.
+    pub fn from_bytes(buf: &[u8]) -> Option<Self> {
+        if buf.is_empty() || buf.len() % BLOCK_SIZE != 0 {
+            return None;
+        }
+        Some(Self {
+            blocks: buf
+                .chunks_exact(BLOCK_SIZE)
+                .map(|block| std::array::from_fn(|i| LittleEndian::read_u32(&block[i * 4..])))
+                .collect(),
+        })
+    }
+}
+
+/// Hash of each value of `array` as inserted in bloom filters, None for nulls.
+pub fn hash_values(array: &dyn Array) -> Result<Vec<Option<u64>>> {
+    fn hash_bytes<'a>(values: impl Iterator<Item = Option<&'a [u8]>>) -> Vec<Option<u64>> {
+        values.map(|v| v.map(|v| xxh64(v, 0))).collect()
+    }
+    Ok(match array.data_type() {
+        DataType::Utf8 => hash_bytes(
+            array
+                .as_string::<i32>()
+                .iter()
+                .map(|v| v.map(str::as_bytes)),
+        ),
+        DataType::LargeUtf8 => hash_bytes(
+            array
+                .as_string::<i64>()
+                .iter()
+                .map(|v| v.map(str::as_bytes)),
+        ),
+        DataType::Utf8View => {
+            hash_bytes(array.as_string_view().iter().map(|v| v.map(str::as_bytes)))
+        }
+        DataType::Binary => hash_bytes(array.as_binary::<i32>().iter()),
+        DataType::LargeBinary => hash_bytes(array.as_binary::<i64>().iter()),
+        DataType::BinaryView => hash_bytes(array.as_binary_view().iter()),
+        DataType::Boolean => array
+            .as_boolean()
+            .iter()
+            .map(|v| v.map(|v| xxh64(&[v as u8], 0)))
+            .collect(),
+        data_type if data_type.is_primitive() => {
+            let width = data_type.primitive_width().unwrap();
+            let data = array.to_data();
+            let values = &data.buffers()[0].as_slice()[data.offset() * width..];
+            values
+                .chunks_exact(width)
+                .take(array.len())
+                .enumerate()
+                .map(|(i, v)| array.is_valid(i).then(|| xxh64(v, 0)))
+                .collect()
+        }
+        data_type => return nyi_err!(format!("Bloom filters of {data_type} columns")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{ArrayRef, Int64Array, StringArray, StringViewArray};
+
+    use super::*;
+    use crate::options::DEFAULT_BLOOM_FILTER_FPP;
+
+    #[test]
+    fn test_bloom_filter() {
+        let values: ArrayRef = Arc::new(StringArray::from_iter(
+            (0..1000).map(|i| (i % 10 != 0).then(|| format!("id{i}"))),
+        ));
+        let hashes = hash_values(values.as_ref()).unwrap();
+        let mut filter = BloomFilter::new(1000, DEFAULT_BLOOM_FILTER_FPP);
+        hashes.iter().flatten().for_each(|&h| filter.insert_hash(h));
+        assert!(hashes.iter().flatten().all(|&h| filter.check_hash(h)));
+
+        // String views hash like strings.
+        let view = StringViewArray::from_iter_values(["id1"]);
+        assert_eq!(hash_values(&view).unwrap(), vec![hashes[1]]);
+
+        let absent = hash_values(&StringArray::from_iter_values(
+            (1000..11000).map(|i| format!("id{i}")),
+        ))
+        .unwrap();
+        let false_positives = absent
+            .iter()
+            .flatten()
+            .filter(|&&h| filter.check_hash(h))
+            .count();
+        assert!(false_positives < 300, "{false_positives} false positives");
+
+        assert_eq!(BloomFilter::from_bytes(&filter.to_bytes()), Some(filter));
+        assert!(BloomFilter::from_bytes(&[0; 33]).is_none());
+
+        let ints = Int64Array::from(vec![Some(1), None, Some(3)]).slice(1, 2);
+        assert_eq!(
+            hash_values(&ints).unwrap(),
+            vec![None, Some(xxh64(&3i64.to_le_bytes(), 0))]
+        );
+    }
+}
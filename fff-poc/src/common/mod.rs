@@ -1,4 +1,9 @@
+pub mod bloom_filter;
 pub mod checksum;
+pub mod physical_type;
+pub mod statistics;
+
+use fff_core::{errors::Result, general_error};
 
 #[derive(Default)]
 pub struct ColumnIndexSequence {
@@ -22,3 +27,13 @@ impl ColumnIndexSequence {
         self.current_index
     }
 }
+
+/// Convert a size or count to the u32 the format stores it as, failing instead of truncating.
+pub(crate) fn checked_u32(value: u64, what: &str) -> Result<u32> {
+    u32::try_from(value).map_err(|_| {
+        general_error!(format!(
+            "{what} is {value}, which exceeds the maximum of {} in the format",
+            u32::MAX
+        ))
+    })
+}
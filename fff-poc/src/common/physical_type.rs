@@ -0,0 +1,317 @@
+//! Mapping of the logical types the encodings do not support to the physical types their columns
+//! are stored as.
+//!
+//! Decimals are stored as their unscaled values: `Decimal128` columns of a precision up to 18 as
+//! `Int64`, other decimals as `Binary` values of the little-endian bytes of each value.
+//! Dictionary columns are stored as their materialized values, so that the writer chooses the
+//! dictionary encoding of each chunk. Durations and intervals are stored as integers of the same
+//! width, apart from `MonthDayNano` intervals stored as 16 little-endian bytes.
+//!
+//! The Arrow schema of the footer is the logical type system of the file: the precision, the
+//! scale, the dictionary key type, the time unit, the time zone and the interval unit come back
+//! from it on read, see [`from_physical`].
+
+use std::sync::Arc;
+
+use arrow::compute::{cast, cast_with_options, CastOptions};
+use arrow_array::{
+    cast::AsArray,
+    make_array,
+    types::{
+        ArrowPrimitiveType, Decimal128Type, Decimal256Type, Int32Type, Int64Type,
+        IntervalDayTimeType, IntervalMonthDayNanoType,
+    },
+    Array, ArrayRef, BinaryArray, Int32Array, PrimitiveArray, RunArray,
+};
+use arrow_buffer::{i256, IntervalDayTime, IntervalMonthDayNano};
+use arrow_schema::{DataType, IntervalUnit};
+use fff_core::errors::{Error, Result};
+
+/// Maximum precision of the `Decimal128` columns stored as `Int64`.
+const MAX_INT64_DECIMAL_PRECISION: u8 = 18;
+
+/// Type of the physical column storing a flat column of `data_type`.
+pub fn physical_type(data_type: &DataType) -> DataType {
+    match data_type {
+        DataType::Decimal128(precision, _) if *precision <= MAX_INT64_DECIMAL_PRECISION => {
+            DataType::Int64
+        }
+        DataType::Decimal128(_, _) | DataType::Decimal256(_, _) => DataType::Binary,
+        DataType::Dictionary(_, value_type) => physical_type(value_type),
+        DataType::Duration(_) | DataType::Interval(IntervalUnit::DayTime) => DataType::Int64,
+        DataType::Interval(IntervalUnit::YearMonth) => DataType::Int32,
+        DataType::Interval(IntervalUnit::MonthDayNano) => DataType::Binary,
+        _ => data_type.clone(),
+    }
+}
+
+/// Convert `array` to the [`physical_type`] of its data type.
+pub fn to_physical(array: ArrayRef) -> Result<ArrayRef> {
+    Ok(match array.data_type() {
+        DataType::Decimal128(precision, _) if *precision <= MAX_INT64_DECIMAL_PRECISION => {
+            Arc::new(
+                array
+                    .as_primitive::<Decimal128Type>()
+                    .unary::<_, Int64Type>(|v| v as i64),
+            )
+        }
+        DataType::Decimal128(_, _) => Arc::new(BinaryArray::from_iter(
+            array
+                .as_primitive::<Decimal128Type>()
+                .iter()
+                .map(|v| v.map(i128::to_le_bytes)),
+        )),
+        DataType::Decimal256(_, _) => Arc::new(BinaryArray::from_iter(
+            array
+                .as_primitive::<Decimal256Type>()
+                .iter()
+                .map(|v| v.map(i256::to_le_bytes)),
+        )),
+        DataType::Dictionary(_, value_type) => to_physical(cast(&array, value_type)?)?,
+        DataType::Duration(_) | DataType::Interval(IntervalUnit::YearMonth) => {
+            let data_type = physical_type(array.data_type());
+            reinterpret(&array, &data_type)?
+        }
+        DataType::Interval(IntervalUnit::DayTime) => Arc::new(
+            array
+                .as_primitive::<IntervalDayTimeType>()
+                .unary::<_, Int64Type>(|v| ((v.days as i64) << 32) | v.milliseconds as u32 as i64),
+        ),
+        DataType::Interval(IntervalUnit::MonthDayNano) => Arc::new(BinaryArray::from_iter(
+            array
+                .as_primitive::<IntervalMonthDayNanoType>()
+                .iter()
+                .map(|v| v.map(month_day_nano_to_le_bytes)),
+        )),
+        _ => array,
+    })
+}
+
+fn month_day_nano_to_le_bytes(v: IntervalMonthDayNano) -> [u8; 16] {
+    let mut bytes = [0; 16];
+    bytes[..4].copy_from_slice(&v.months.to_le_bytes());
+    bytes[4..8].copy_from_slice(&v.days.to_le_bytes());
+    bytes[8..].copy_from_slice(&v.nanoseconds.to_le_bytes());
+    bytes
+}
+
+fn month_day_nano_from_le_bytes(bytes: [u8; 16]) -> IntervalMonthDayNano {
+    IntervalMonthDayNano::new(
+        i32::from_le_bytes(bytes[..4].try_into().unwrap()),
+        i32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+        i64::from_le_bytes(bytes[8..].try_into().unwrap()),
+    )
+}
+
+/// Relabel `array` as `data_type`, of the same layout.
+fn reinterpret(array: &ArrayRef, data_type: &DataType) -> Result<ArrayRef> {
+    Ok(make_array(
+        array
+            .to_data()
+            .into_builder()
+            .data_type(data_type.clone())
+            .build()?,
+    ))
+}
+
+/// Convert `array`, decoded from a physical column, back to `data_type`.
+///
+/// Codecs may also round-trip flat types with another time unit or time zone, which are restored
+/// too. Strings and binaries are left as views, and dictionaries preserved by the reader as is.
+/// Runs preserved by the reader stay runs, of values converted to `data_type`.
+pub fn from_physical(array: ArrayRef, data_type: &DataType) -> Result<ArrayRef> {
+    if let DataType::RunEndEncoded(_, _) = array.data_type() {
+        let runs = array.as_run::<Int32Type>();
+        let values = from_physical(runs.values().clone(), data_type)?;
+        let run_ends = Int32Array::new(runs.run_ends().inner().clone(), None);
+        return Ok(Arc::new(RunArray::<Int32Type>::try_new(
+            &run_ends,
+            values.as_ref(),
+        )?));
+    }
+    Ok(match data_type {
+        DataType::Decimal128(precision, scale) => {
+            let values = match array.data_type() {
+                DataType::Int64 => array
+                    .as_primitive::<Int64Type>()
+                    .unary::<_, Decimal128Type>(i128::from),
+                _ => from_le_bytes::<Decimal128Type, 16>(&array, i128::from_le_bytes)?,
+            };
+            Arc::new(values.with_precision_and_scale(*precision, *scale)?)
+        }
+        DataType::Decimal256(precision, scale) => Arc::new(
+            from_le_bytes::<Decimal256Type, 32>(&array, i256::from_le_bytes)?
+                .with_precision_and_scale(*precision, *scale)?,
+        ),
+        DataType::Dictionary(_, value_type) => {
+            // Dictionaries preserved by the reader only need their key and value types cast.
+            let array = match array.data_type() {
+                DataType::Dictionary(_, _) => array,
+                _ => from_physical(array, value_type)?,
+            };
+            let array = match array.data_type() {
+                DataType::Utf8View | DataType::BinaryView => cast(&array, value_type)?,
+                _ => array,
+            };
+            cast(&array, data_type)?
+        }
+        DataType::Duration(_)
+        | DataType::Interval(IntervalUnit::YearMonth)
+        | DataType::Interval(IntervalUnit::DayTime)
+            if array.data_type() != data_type =>
+        {
+            match array.data_type() {
+                DataType::Int64 if data_type == &DataType::Interval(IntervalUnit::DayTime) => {
+                    Arc::new(
+                        array
+                            .as_primitive::<Int64Type>()
+                            .unary::<_, IntervalDayTimeType>(|v| {
+                                IntervalDayTime::new((v >> 32) as i32, v as i32)
+                            }),
+                    )
+                }
+                _ => reinterpret(&array, data_type)?,
+            }
+        }
+        DataType::Interval(IntervalUnit::MonthDayNano) if array.data_type() != data_type => {
+            Arc::new(from_le_bytes::<IntervalMonthDayNanoType, 16>(
+                &array,
+                month_day_nano_from_le_bytes,
+            )?)
+        }
+        _ => restore_type(array, data_type)?,
+    })
+}
+
+/// Output `array` as `data_type` when a codec round-tripped it with another type.
+fn restore_type(array: ArrayRef, data_type: &DataType) -> Result<ArrayRef> {
+    let options = CastOptions {
+        safe: false,
+        ..Default::default()
+    };
+    Ok(match (array.data_type(), data_type) {
+        (decoded, _) if decoded == data_type => array,
+        (DataType::Utf8View | DataType::BinaryView | DataType::Dictionary(_, _), _) => array,
+        // Casting between time zones would shift timestamps without one, only relabel them.
+        (DataType::Timestamp(unit, tz), DataType::Timestamp(target_unit, _)) => {
+            let array = if unit == target_unit {
+                array
+            } else {
+                let data_type = DataType::Timestamp(*target_unit, tz.clone());
+                cast_with_options(&array, &data_type, &options)?
+            };
+            reinterpret(&array, data_type)?
+        }
+        _ => cast_with_options(&array, data_type, &options)?,
+    })
+}
+
+fn from_le_bytes<T: ArrowPrimitiveType, const N: usize>(
+    array: &ArrayRef,
+    from_le_bytes: fn([u8; N]) -> T::Native,
+) -> Result<PrimitiveArray<T>> {
+    let array = cast(array, &DataType::Binary)?;
+    array
+        .as_binary::<i32>()
+        .iter()
+        .map(|bytes| {
+            bytes
+                .map(|bytes| {
+                    <[u8; N]>::try_from(bytes).map(from_le_bytes).map_err(|_| {
+                        Error::ParseError(format!("Value of {} bytes instead of {N}", bytes.len()))
+                    })
+                })
+                .transpose()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::types::Int16Type;
+    use arrow_array::{
+        Decimal128Array, Decimal256Array, DictionaryArray, DurationSecondArray,
+        IntervalDayTimeArray, IntervalMonthDayNanoArray, IntervalYearMonthArray, StringArray,
+        TimestampMillisecondArray, TimestampSecondArray,
+    };
+    use arrow_schema::TimeUnit;
+
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let arrays: Vec<ArrayRef> = vec![
+            Arc::new(
+                Decimal128Array::from(vec![Some(12345), None, Some(-99_999)])
+                    .with_precision_and_scale(10, 2)
+                    .unwrap(),
+            ),
+            Arc::new(
+                Decimal128Array::from(vec![Some(i128::MAX / 3), None, Some(-1)])
+                    .with_precision_and_scale(38, 10)
+                    .unwrap(),
+            ),
+            Arc::new(
+                Decimal256Array::from(vec![Some(i256::from_i128(-5)), None, Some(i256::MAX)])
+                    .with_precision_and_scale(76, 0)
+                    .unwrap(),
+            ),
+            Arc::new(DictionaryArray::<Int16Type>::from_iter([
+                Some("a"),
+                None,
+                Some("b"),
+                Some("a"),
+            ])),
+            Arc::new(DurationSecondArray::from(vec![
+                Some(-3),
+                None,
+                Some(i64::MAX),
+            ])),
+            Arc::new(IntervalYearMonthArray::from(vec![
+                Some(-13),
+                None,
+                Some(25),
+            ])),
+            Arc::new(IntervalDayTimeArray::from(vec![
+                Some(IntervalDayTime::new(-2, 3_600_000)),
+                None,
+                Some(IntervalDayTime::new(i32::MAX, i32::MIN)),
+            ])),
+            Arc::new(IntervalMonthDayNanoArray::from(vec![
+                Some(IntervalMonthDayNano::new(1, -2, 3)),
+                None,
+                Some(IntervalMonthDayNano::new(i32::MIN, i32::MAX, i64::MIN)),
+            ])),
+        ];
+        for array in arrays {
+            let physical = to_physical(array.clone()).unwrap();
+            assert_eq!(physical.data_type(), &physical_type(array.data_type()));
+            assert_eq!(physical.nulls(), array.logical_nulls().as_ref());
+            // Strings are decoded as views.
+            let physical = match physical.data_type() {
+                DataType::Utf8 => cast(&physical, &DataType::Utf8View).unwrap(),
+                _ => physical,
+            };
+            assert_eq!(&from_physical(physical, array.data_type()).unwrap(), &array);
+        }
+        assert_eq!(
+            to_physical(Arc::new(StringArray::from(vec!["x"])) as ArrayRef)
+                .unwrap()
+                .data_type(),
+            &DataType::Utf8
+        );
+    }
+
+    #[test]
+    fn test_restore_timestamp() {
+        let decoded: ArrayRef = Arc::new(TimestampSecondArray::from(vec![Some(1), None]));
+        let data_type = DataType::Timestamp(TimeUnit::Millisecond, Some("+08:00".into()));
+        // The values are UTC, only the unit changes.
+        assert_eq!(
+            &from_physical(decoded, &data_type).unwrap(),
+            &(Arc::new(
+                TimestampMillisecondArray::from(vec![Some(1000), None]).with_timezone("+08:00")
+            ) as ArrayRef)
+        );
+    }
+}
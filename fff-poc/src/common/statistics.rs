@@ -0,0 +1,404 @@
+//! Min/max statistics of chunks, and zone maps of EncUnits.
+//!
+//! Binary and string values are compared by their bytes. Long values are truncated to a prefix
+//! when written, like in Parquet: a truncated min is still a lower bound of the values, and the
+//! last byte (or character for strings) of a truncated max is incremented so that it stays an
+//! upper bound.
+//!
+//! Zone maps also cover fixed-width values, stored as their [`sortable_key`] so that they compare
+//! by their bytes as well.
+
+use arrow::{
+    array::AsArray,
+    compute::{max_binary, max_string, min_binary, min_string},
+};
+use arrow_array::Array;
+use arrow_schema::DataType;
+
+use crate::file::footer::Statistics;
+
+/// Accumulates the untruncated min/max of the arrays appended to a chunk.
+#[derive(Debug, Clone, Default)]
+pub struct MinMaxAccumulator {
+    min: Option<Vec<u8>>,
+    max: Option<Vec<u8>>,
+    /// Truncation keeps whole characters of UTF-8 values.
+    is_utf8: bool,
+}
+
+impl MinMaxAccumulator {
+    /// Account the values of `array`. Does nothing for types without statistics.
+    pub fn update(&mut self, array: &dyn Array) {
+        let (min, max) = match array.data_type() {
+            DataType::Utf8 => {
+                let array = array.as_string::<i32>();
+                (
+                    min_string(array).map(str::as_bytes),
+                    max_string(array).map(str::as_bytes),
+                )
+            }
+            DataType::LargeUtf8 => {
+                let array = array.as_string::<i64>();
+                (
+                    min_string(array).map(str::as_bytes),
+                    max_string(array).map(str::as_bytes),
+                )
+            }
+            DataType::Binary => {
+                let array = array.as_binary::<i32>();
+                (min_binary(array), max_binary(array))
+            }
+            DataType::LargeBinary => {
+                let array = array.as_binary::<i64>();
+                (min_binary(array), max_binary(array))
+            }
+            _ => return,
+        };
+        self.is_utf8 = matches!(array.data_type(), DataType::Utf8 | DataType::LargeUtf8);
+        self.update_min_max(min, max);
+    }
+
+    /// Like [`Self::update`], but also account fixed-width values by their [`sortable_key`],
+    /// e.g., for the zone map of an EncUnit.
+    pub fn update_zone_map(&mut self, array: &dyn Array) {
+        let Some(width) = sortable_key_width(array.data_type()) else {
+            return self.update(array);
+        };
+        let data = array.to_data();
+        let values = &data.buffers()[0].as_slice()[data.offset() * width..];
+        let (min, max) = values
+            .chunks_exact(width)
+            .take(array.len())
+            .enumerate()
+            .filter(|(i, _)| array.is_valid(*i))
+            .map(|(_, v)| sortable_key(array.data_type(), v))
+            .fold(
+                (None, None),
+                |(min, max): (Option<u64>, Option<u64>), key| {
+                    (
+                        Some(min.map_or(key, |min| min.min(key))),
+                        Some(max.map_or(key, |max| max.max(key))),
+                    )
+                },
+            );
+        let to_bytes = |key: u64| key.to_be_bytes()[8 - width..].to_vec();
+        self.is_utf8 = false;
+        self.update_min_max(min.map(to_bytes).as_deref(), max.map(to_bytes).as_deref());
+    }
+
+    /// Account the values accounted by `other`, e.g., an EncUnit of this chunk.
+    pub fn merge(&mut self, other: &Self) {
+        if other.min.is_some() || other.max.is_some() {
+            self.is_utf8 = other.is_utf8;
+        }
+        self.update_min_max(other.min.as_deref(), other.max.as_deref());
+    }
+
+    fn update_min_max(&mut self, min: Option<&[u8]>, max: Option<&[u8]>) {
+        if let Some(min) = min {
+            if self.min.as_deref().is_none_or(|cur| min < cur) {
+                self.min = Some(min.to_vec());
+            }
+        }
+        if let Some(max) = max {
+            if self.max.as_deref().is_none_or(|cur| max > cur) {
+                self.max = Some(max.to_vec());
+            }
+        }
+    }
+
+    /// The statistics to write, with values truncated to `truncate_length` bytes if any.
+    /// None if no values were accounted.
+    pub fn finish(&self, truncate_length: Option<usize>) -> Option<Statistics> {
+        let (min, max) = (self.min.as_deref()?, self.max.as_deref()?);
+        let Some(len) = truncate_length else {
+            return Some(Statistics::new(
+                Some(min.to_vec()),
+                Some(max.to_vec()),
+                true,
+                true,
+            ));
+        };
+        Some(Statistics::new(
+            Some(truncate_min(min, len, self.is_utf8)),
+            truncate_max(max, len, self.is_utf8),
+            min.len() <= len,
+            max.len() <= len,
+        ))
+    }
+}
+
+/// How `stored`, the statistics of a chunk, misstate `decoded`, the untruncated statistics of its
+/// values, if they do: bounds that do not bound the values, or exact bounds that are not the min
+/// or max. `decoded` is None if the chunk has no non-null values.
+pub fn statistics_mismatch(stored: &Statistics, decoded: Option<&Statistics>) -> Option<String> {
+    let (Some(min), Some(max)) = (
+        decoded.and_then(Statistics::min_value),
+        decoded.and_then(Statistics::max_value),
+    ) else {
+        return stored.min_value().map(|stored_min| {
+            format!(
+                "min value {} for no non-null values",
+                display_bound(stored_min)
+            )
+        });
+    };
+    let Some(stored_min) = stored.min_value() else {
+        return Some(format!("no min value, the min is {}", display_bound(min)));
+    };
+    if stored_min > min || (stored.is_min_value_exact() && stored_min != min) {
+        return Some(format!(
+            "min value {}, the min is {}",
+            display_bound(stored_min),
+            display_bound(min)
+        ));
+    }
+    match stored.max_value() {
+        // No upper bound fits in the truncation length.
+        None => None,
+        Some(stored_max)
+            if stored_max < max || (stored.is_max_value_exact() && stored_max != max) =>
+        {
+            Some(format!(
+                "max value {}, the max is {}",
+                display_bound(stored_max),
+                display_bound(max)
+            ))
+        }
+        Some(_) => None,
+    }
+}
+
+/// A bound as a string if it is UTF-8, as its bytes otherwise.
+fn display_bound(bound: &[u8]) -> String {
+    match std::str::from_utf8(bound) {
+        Ok(bound) => format!("{bound:?}"),
+        Err(_) => format!("{bound:02x?}"),
+    }
+}
+
+/// Width of the [`sortable_key`] of the values of `data_type`, None if they have none.
+pub fn sortable_key_width(data_type: &DataType) -> Option<usize> {
+    match data_type {
+        DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::UInt64
+        | DataType::Float16
+        | DataType::Float32
+        | DataType::Float64
+        | DataType::Date32
+        | DataType::Date64
+        | DataType::Time32(_)
+        | DataType::Time64(_)
+        | DataType::Timestamp(_, _)
+        | DataType::Duration(_) => data_type.primitive_width(),
+        _ => None,
+    }
+}
+
+/// A key of the little-endian fixed-width `value` that sorts like the values of `data_type`, to
+/// be stored as its big-endian bytes: the sign bit of signed integers is flipped, and so are all
+/// the bits of negative floats, like a total order of floats with NaNs last.
+pub fn sortable_key(data_type: &DataType, value: &[u8]) -> u64 {
+    let mut bytes = [0; 8];
+    bytes[..value.len()].copy_from_slice(value);
+    let raw = u64::from_le_bytes(bytes);
+    let sign: u64 = 1 << (value.len() * 8 - 1);
+    match data_type {
+        DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => raw,
+        DataType::Float16 | DataType::Float32 | DataType::Float64 if raw & sign != 0 => {
+            !raw & (sign | (sign - 1))
+        }
+        _ => raw ^ sign,
+    }
+}
+
+/// Length of the longest prefix of `value` of at most `len` bytes.
+fn prefix_len(value: &[u8], len: usize, is_utf8: bool) -> usize {
+    if value.len() <= len {
+        return value.len();
+    }
+    match std::str::from_utf8(value) {
+        Ok(value) if is_utf8 => (0..=len)
+            .rev()
+            .find(|&i| value.is_char_boundary(i))
+            .unwrap(),
+        _ => len,
+    }
+}
+
+/// Truncate `value` to a lower bound of at most `len` bytes.
+pub fn truncate_min(value: &[u8], len: usize, is_utf8: bool) -> Vec<u8> {
+    value[..prefix_len(value, len, is_utf8)].to_vec()
+}
+
+/// Truncate `value` to an upper bound of at most `len` bytes.
+/// None if there is no such bound, e.g., if the first `len` bytes are all 0xFF.
+pub fn truncate_max(value: &[u8], len: usize, is_utf8: bool) -> Option<Vec<u8>> {
+    if value.len() <= len {
+        return Some(value.to_vec());
+    }
+    let prefix = &value[..prefix_len(value, len, is_utf8)];
+    match std::str::from_utf8(prefix) {
+        Ok(prefix) if is_utf8 => {
+            let mut chars: Vec<char> = prefix.chars().collect();
+            while let Some(c) = chars.pop() {
+                let Some(next) = (c as u32 + 1..=char::MAX as u32).find_map(char::from_u32) else {
+                    continue;
+                };
+                chars.push(next);
+                let upper_bound = String::from_iter(&chars);
+                if upper_bound.len() <= len {
+                    return Some(upper_bound.into_bytes());
+                }
+                chars.pop();
+            }
+            None
+        }
+        _ => {
+            let mut bytes = prefix.to_vec();
+            while let Some(b) = bytes.pop() {
+                if b < u8::MAX {
+                    bytes.push(b + 1);
+                    return Some(bytes);
+                }
+            }
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{BinaryArray, Float64Array, Int32Array, StringArray};
+
+    use super::*;
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate_min(b"abcdef", 3, false), b"abc");
+        assert_eq!(truncate_max(b"abcdef", 3, false).unwrap(), b"abd");
+        assert_eq!(truncate_max(b"ab\xff\xffef", 4, false).unwrap(), b"ac");
+        assert_eq!(truncate_max(b"\xff\xff\xff", 2, false), None);
+        assert_eq!(truncate_max(b"abc", 3, false).unwrap(), b"abc");
+        // Do not split "é", which is 2 bytes.
+        assert_eq!(truncate_min("aéb".as_bytes(), 2, true), b"a");
+        assert_eq!(truncate_max("aéb".as_bytes(), 2, true).unwrap(), b"b");
+        assert_eq!(
+            truncate_max("aéb".as_bytes(), 3, true).unwrap(),
+            "aê".as_bytes()
+        );
+        // The next character of U+D7FF skips the surrogates.
+        assert_eq!(
+            truncate_max("a\u{d7ff}b".as_bytes(), 4, true).unwrap(),
+            "a\u{e000}".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_sortable_key() {
+        let ints = [i64::MIN, -2, -1, 0, 1, i64::MAX];
+        let keys = ints.map(|v| sortable_key(&DataType::Int64, &v.to_le_bytes()));
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+        let floats = [
+            f64::NEG_INFINITY,
+            -1.5,
+            -0.0,
+            0.0,
+            2.0,
+            f64::INFINITY,
+            f64::NAN,
+        ];
+        let keys = floats.map(|v| sortable_key(&DataType::Float64, &v.to_le_bytes()));
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+        let floats = Float64Array::from(floats.to_vec());
+        let mut acc = MinMaxAccumulator::default();
+        acc.update_zone_map(&floats);
+        let stats = acc.finish(None).unwrap();
+        assert_eq!(stats.min_value().unwrap(), keys[0].to_be_bytes());
+        assert_eq!(stats.max_value().unwrap(), keys[6].to_be_bytes());
+        assert_eq!(sortable_key(&DataType::UInt8, &[200]), 200);
+        assert_eq!(sortable_key(&DataType::Int8, &[(-1i8) as u8]), 0x7f);
+    }
+
+    #[test]
+    fn test_min_max_accumulator() {
+        let mut acc = MinMaxAccumulator::default();
+        assert_eq!(acc.finish(Some(4)), None);
+        acc.update(&StringArray::from(vec![
+            Some("banana"),
+            None,
+            Some("cherry"),
+        ]));
+        acc.update(&StringArray::from(vec!["apple pie", "cherries"]));
+        let stats = acc.finish(Some(4)).unwrap();
+        assert_eq!(stats.min_value().unwrap(), b"appl");
+        assert_eq!(stats.max_value().unwrap(), b"ches");
+        assert!(!stats.is_min_value_exact());
+        assert!(!stats.is_max_value_exact());
+        let stats = acc.finish(None).unwrap();
+        assert_eq!(stats.min_value().unwrap(), b"apple pie");
+        assert_eq!(stats.max_value().unwrap(), b"cherry");
+        assert!(stats.is_min_value_exact() && stats.is_max_value_exact());
+
+        // Only zone maps cover fixed-width values.
+        let ints = Int32Array::from(vec![Some(-3), None, Some(7), Some(-100), Some(0)]);
+        let mut acc = MinMaxAccumulator::default();
+        acc.update(&ints);
+        assert_eq!(acc.finish(None), None);
+        acc.update_zone_map(&ints.slice(1, 4));
+        let stats = acc.finish(None).unwrap();
+        let key =
+            |v: i32| sortable_key(&DataType::Int32, &v.to_le_bytes()).to_be_bytes()[4..].to_vec();
+        assert_eq!(stats.min_value().unwrap(), key(-100));
+        assert_eq!(stats.max_value().unwrap(), key(7));
+
+        let mut acc = MinMaxAccumulator::default();
+        acc.update(&BinaryArray::from_vec(vec![&b"\xff\xff\xff"[..], b"\x00"]));
+        let stats = acc.finish(Some(2)).unwrap();
+        assert_eq!(stats.min_value().unwrap(), b"\x00");
+        assert!(stats.is_min_value_exact());
+        assert_eq!(stats.max_value(), None);
+    }
+
+    #[test]
+    fn test_statistics_mismatch() {
+        let mut acc = MinMaxAccumulator::default();
+        acc.update(&StringArray::from(vec!["banana", "cherry"]));
+        let decoded = acc.finish(None).unwrap();
+        let stats = |min: &str, max: Option<&str>, exact: bool| {
+            Statistics::new(
+                Some(min.as_bytes().to_vec()),
+                max.map(|max| max.as_bytes().to_vec()),
+                exact,
+                exact,
+            )
+        };
+        assert_eq!(statistics_mismatch(&decoded, Some(&decoded)), None);
+        // Truncated bounds only need to bound the values.
+        let truncated = acc.finish(Some(2)).unwrap();
+        assert_eq!(statistics_mismatch(&truncated, Some(&decoded)), None);
+        assert_eq!(
+            statistics_mismatch(&stats("a", None, false), Some(&decoded)),
+            None
+        );
+        assert_eq!(
+            statistics_mismatch(&stats("b", Some("cherry"), true), Some(&decoded)).unwrap(),
+            r#"min value "b", the min is "banana""#
+        );
+        assert_eq!(
+            statistics_mismatch(&stats("bb", Some("ch"), false), Some(&decoded)).unwrap(),
+            r#"min value "bb", the min is "banana""#
+        );
+        assert_eq!(
+            statistics_mismatch(&stats("banana", Some("ch"), false), Some(&decoded)).unwrap(),
+            r#"max value "ch", the max is "cherry""#
+        );
+        assert!(statistics_mismatch(&decoded, None).is_some());
+    }
+}
@@ -3,26 +3,353 @@ use bytes::Bytes;
 use fff_core::errors::{Error, Result};
 use fff_format::File::fff::flatbuf as fb;
 
-/// Compress data based on the compression type
-pub fn compress_data(data: Bytes, compression_type: fb::CompressionType) -> Result<Bytes> {
-    match compression_type {
-        fb::CompressionType::Uncompressed => Ok(data),
-        fb::CompressionType::Lz4 => {
+/// Approximate single-threaded Zstd compression speed (MB/s) per level, from fastest to strongest.
+const ZSTD_LEVEL_SPEEDS: [(i32, f64); 7] = [
+    (1, 500.0),
+    (3, 350.0),
+    (6, 120.0),
+    (9, 90.0),
+    (12, 40.0),
+    (15, 25.0),
+    (19, 5.0),
+];
+
+/// Highest Zstd level for data that is only moderately compressible.
+/// Stronger levels rarely pay off there.
+const ZSTD_MODERATE_MAX_LEVEL: i32 = 3;
+
+/// Number of bytes sampled to estimate the entropy of an EncUnit.
+const ENTROPY_SAMPLE_SIZE: usize = 64 * 1024;
+
+/// Fewest EncUnits a Zstd dictionary is trained on. Zstd rejects smaller sample sets.
+const MIN_DICTIONARY_SAMPLES: usize = 8;
+
+/// Brotli quality of the EncUnits the cost model decides to compress. Brotli levels are much
+/// slower than the Zstd ones, so the levels of [`CompressionCostModel`] do not carry over.
+#[cfg(feature = "brotli")]
+const BROTLI_AUTO_QUALITY: u32 = 5;
+
+/// Brotli window size, as a power of two.
+#[cfg(feature = "brotli")]
+const BROTLI_LG_WINDOW_SIZE: u32 = 22;
+
+/// Cost model to pick a compression level per EncUnit from its size and estimated compressibility.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompressionCostModel {
+    /// EncUnits smaller than this are left uncompressed.
+    pub min_compress_size: usize,
+    /// EncUnits smaller than this are compressed with the fastest level.
+    pub small_size: usize,
+    /// Desired compression throughput in MB/s. Bounds the strongest level used.
+    pub target_throughput_mb_s: f64,
+    /// EncUnits whose estimated compression ratio is below this are left uncompressed,
+    /// e.g., data already bit-packed by the encoding.
+    pub min_ratio: f64,
+    /// EncUnits whose estimated compression ratio reaches this may use the strongest level
+    /// allowed by the target throughput.
+    pub high_ratio: f64,
+}
+
+impl Default for CompressionCostModel {
+    fn default() -> Self {
+        Self {
+            min_compress_size: 4 * 1024,
+            small_size: 64 * 1024,
+            target_throughput_mb_s: 100.0,
+            min_ratio: 1.1,
+            high_ratio: 3.0,
+        }
+    }
+}
+
+impl CompressionCostModel {
+    /// Choose the Zstd level for `data`, or `None` if it is not worth compressing.
+    fn zstd_level(&self, data: &[u8]) -> Option<i32> {
+        if data.len() < self.min_compress_size {
+            return None;
+        }
+        let ratio = estimate_compression_ratio(data);
+        if ratio < self.min_ratio {
+            return None;
+        }
+        if data.len() < self.small_size {
+            return Some(ZSTD_LEVEL_SPEEDS[0].0);
+        }
+        let max_level = ZSTD_LEVEL_SPEEDS
+            .iter()
+            .filter(|(_, speed)| *speed >= self.target_throughput_mb_s)
+            .map(|(level, _)| *level)
+            .last()
+            .unwrap_or(ZSTD_LEVEL_SPEEDS[0].0);
+        Some(if ratio >= self.high_ratio {
+            max_level
+        } else {
+            max_level.min(ZSTD_MODERATE_MAX_LEVEL)
+        })
+    }
+
+    /// Whether `data` is worth compressing with a codec that has no levels.
+    fn should_compress(&self, data: &[u8]) -> bool {
+        data.len() >= self.min_compress_size && estimate_compression_ratio(data) >= self.min_ratio
+    }
+}
+
+/// How the compression level is chosen for each EncUnit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompressionLevel {
+    /// Compress every EncUnit, with this level for Zstd.
+    Fixed(i32),
+    /// Choose the level (or no compression) per EncUnit with a cost model.
+    Auto(CompressionCostModel),
+}
+
+impl Default for CompressionLevel {
+    fn default() -> Self {
+        Self::Auto(CompressionCostModel::default())
+    }
+}
+
+/// The codec used for EncUnits and how its level is chosen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Compression {
+    compression_type: fb::CompressionType,
+    level: CompressionLevel,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self::new(
+            fb::CompressionType::Uncompressed,
+            CompressionLevel::default(),
+        )
+    }
+}
+
+impl Compression {
+    pub fn new(compression_type: fb::CompressionType, level: CompressionLevel) -> Self {
+        Self {
+            compression_type,
+            level,
+        }
+    }
+
+    pub fn compression_type(&self) -> fb::CompressionType {
+        self.compression_type
+    }
+
+    pub fn level(&self) -> CompressionLevel {
+        self.level
+    }
+}
+
+/// Estimate the compression ratio of `data` from the Shannon entropy of a sample of its bytes.
+fn estimate_compression_ratio(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 1.0;
+    }
+    let step = data.len().div_ceil(ENTROPY_SAMPLE_SIZE);
+    let mut histogram = [0usize; 256];
+    let mut num_samples = 0;
+    for b in data.iter().step_by(step) {
+        histogram[*b as usize] += 1;
+        num_samples += 1;
+    }
+    let entropy: f64 = histogram
+        .iter()
+        .filter(|c| **c > 0)
+        .map(|c| {
+            let p = *c as f64 / num_samples as f64;
+            -p * p.log2()
+        })
+        .sum();
+    8.0 / entropy.max(0.01)
+}
+
+/// The compression types this build can write and read, depending on the `snappy` and `brotli`
+/// features.
+pub fn supported_compression_types() -> Vec<fb::CompressionType> {
+    let mut supported = vec![
+        fb::CompressionType::Uncompressed,
+        fb::CompressionType::Zstd,
+        fb::CompressionType::Lz4,
+    ];
+    if cfg!(feature = "snappy") {
+        supported.push(fb::CompressionType::Snappy);
+    }
+    if cfg!(feature = "brotli") {
+        supported.push(fb::CompressionType::Brotli);
+    }
+    supported
+}
+
+/// Fail if this build cannot write or read `compression_type`, listing the supported ones.
+pub fn check_supported(compression_type: fb::CompressionType) -> Result<()> {
+    let supported = supported_compression_types();
+    if supported.contains(&compression_type) {
+        return Ok(());
+    }
+    let name = compression_type
+        .variant_name()
+        .map_or_else(|| format!("{}", compression_type.0), str::to_string);
+    Err(Error::General(format!(
+        "Unsupported compression type: {name}, this build supports {}",
+        supported
+            .iter()
+            .filter_map(|compression_type| compression_type.variant_name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    )))
+}
+
+/// Compress data based on the compression options.
+/// Returns the compressed data and the compression type actually applied, which is
+/// `Uncompressed` if the cost model decided the data is not worth compressing.
+pub fn compress_data(
+    data: Bytes,
+    compression: Compression,
+) -> Result<(Bytes, fb::CompressionType)> {
+    trace_span!("compress", bytes = data.len());
+    let compressed = match (compression.compression_type, compression.level) {
+        (fb::CompressionType::Uncompressed, _) => data,
+        (
+            fb::CompressionType::Lz4 | fb::CompressionType::Snappy | fb::CompressionType::Brotli,
+            CompressionLevel::Auto(model),
+        ) if !model.should_compress(&data) => {
+            return Ok((data, fb::CompressionType::Uncompressed));
+        }
+        (fb::CompressionType::Lz4, _) => {
             let compressed = lz4_flex::compress_prepend_size(data.as_ref());
-            Ok(Bytes::from(compressed))
+            Bytes::from(compressed)
         }
-        fb::CompressionType::Zstd => {
-            let compressed = zstd::stream::encode_all(data.as_ref(), 0)?;
-            Ok(Bytes::from(compressed))
+        (fb::CompressionType::Zstd, level) => {
+            let level = match level {
+                CompressionLevel::Fixed(level) => level,
+                CompressionLevel::Auto(model) => match model.zstd_level(&data) {
+                    Some(level) => level,
+                    None => return Ok((data, fb::CompressionType::Uncompressed)),
+                },
+            };
+            let compressed = zstd::stream::encode_all(data.as_ref(), level)?;
+            Bytes::from(compressed)
         }
-        _ => Err(fff_core::errors::Error::General(
-            "Unsupported compression type".to_string(),
-        )),
+        #[cfg(feature = "snappy")]
+        (fb::CompressionType::Snappy, _) => Bytes::from(
+            snap::raw::Encoder::new()
+                .compress_vec(&data)
+                .map_err(|e| Error::External(Box::new(e)))?,
+        ),
+        #[cfg(feature = "brotli")]
+        (fb::CompressionType::Brotli, level) => {
+            let quality = match level {
+                CompressionLevel::Fixed(level) => level.clamp(0, 11) as u32,
+                CompressionLevel::Auto(_) => BROTLI_AUTO_QUALITY,
+            };
+            let mut compressed = Vec::new();
+            let mut encoder = brotli::CompressorWriter::new(
+                &mut compressed,
+                4096,
+                quality,
+                BROTLI_LG_WINDOW_SIZE,
+            );
+            std::io::Write::write_all(&mut encoder, &data)?;
+            drop(encoder);
+            Bytes::from(compressed)
+        }
+        (compression_type, _) => {
+            check_supported(compression_type)?;
+            unreachable!("{compression_type:?} is supported but not handled")
+        }
+    };
+    Ok((compressed, compression.compression_type))
+}
+
+/// Zstd level of an EncUnit compressed with a dictionary. Small EncUnits, left uncompressed by the
+/// cost model, get the fastest level, as the dictionary is what makes them compressible.
+fn dictionary_level(data: &[u8], level: CompressionLevel) -> i32 {
+    match level {
+        CompressionLevel::Fixed(level) => level,
+        CompressionLevel::Auto(model) => model.zstd_level(data).unwrap_or(ZSTD_LEVEL_SPEEDS[0].0),
     }
 }
 
+/// Train a Zstd dictionary of at most `max_size` bytes on the EncUnits of a chunk, given with
+/// their compression type, and compress them with it.
+///
+/// Returns the dictionary and the EncUnits compressed with it, or None if the dictionary does not
+/// make the chunk smaller, e.g., for few or large EncUnits. Uncompressed EncUnits stay so if the
+/// dictionary does not shrink them. All the Zstd EncUnits of the result use the dictionary.
+pub fn compress_with_trained_dictionary(
+    encunits: &[(Bytes, fb::CompressionType)],
+    compression: Compression,
+    max_size: usize,
+) -> Result<Option<(Bytes, Vec<(Bytes, fb::CompressionType)>)>> {
+    if compression.compression_type != fb::CompressionType::Zstd
+        || encunits.len() < MIN_DICTIONARY_SAMPLES
+        || encunits.iter().any(|(_, compression_type)| {
+            !matches!(
+                compression_type,
+                fb::CompressionType::Zstd | fb::CompressionType::Uncompressed
+            )
+        })
+    {
+        return Ok(None);
+    }
+    let samples = encunits
+        .iter()
+        .map(|(data, compression_type)| decompress_data(data.clone(), *compression_type))
+        .collect::<Result<Vec<_>>>()?;
+    // Training fails on samples too small or too few to learn from.
+    let Ok(dictionary) = zstd::dict::from_samples(&samples, max_size) else {
+        return Ok(None);
+    };
+    let mut compressed = Vec::with_capacity(encunits.len());
+    for ((data, compression_type), sample) in encunits.iter().zip(samples) {
+        let mut encoder = zstd::stream::Encoder::with_dictionary(
+            Vec::new(),
+            dictionary_level(&sample, compression.level),
+            &dictionary,
+        )?;
+        std::io::Write::write_all(&mut encoder, &sample)?;
+        let with_dictionary = encoder.finish()?;
+        compressed.push(
+            if *compression_type == fb::CompressionType::Uncompressed
+                && with_dictionary.len() >= data.len()
+            {
+                (data.clone(), fb::CompressionType::Uncompressed)
+            } else {
+                (Bytes::from(with_dictionary), fb::CompressionType::Zstd)
+            },
+        );
+    }
+    let size = |encunits: &[(Bytes, fb::CompressionType)]| {
+        encunits.iter().map(|(data, _)| data.len()).sum::<usize>()
+    };
+    if dictionary.len() + size(&compressed) >= size(encunits) {
+        return Ok(None);
+    }
+    Ok(Some((Bytes::from(dictionary), compressed)))
+}
+
+/// Decompress the Zstd `data` of a chunk compressed with `dictionary`, see
+/// [`compress_with_trained_dictionary`]. Other compression types ignore the dictionary.
+pub fn decompress_data_with_dictionary(
+    data: Bytes,
+    compression_type: fb::CompressionType,
+    dictionary: &[u8],
+) -> Result<Bytes> {
+    if compression_type != fb::CompressionType::Zstd {
+        return decompress_data(data, compression_type);
+    }
+    trace_span!("decompress", bytes = data.len());
+    let mut decoder = zstd::stream::Decoder::with_dictionary(data.as_ref(), dictionary)?;
+    let mut decompressed = Vec::new();
+    std::io::Read::read_to_end(&mut decoder, &mut decompressed)?;
+    Ok(Bytes::from(decompressed))
+}
+
 /// Decompress data based on the compression type
 pub fn decompress_data(data: Bytes, compression_type: fb::CompressionType) -> Result<Bytes> {
+    trace_span!("decompress", bytes = data.len());
     match compression_type {
         fb::CompressionType::Uncompressed => Ok(data),
         fb::CompressionType::Lz4 => Ok(Bytes::from(
@@ -30,9 +357,154 @@ pub fn decompress_data(data: Bytes, compression_type: fb::CompressionType) -> Re
                 .map_err(|e| Error::External(Box::new(e)))?,
         )),
         fb::CompressionType::Zstd => Ok(Bytes::from(zstd::stream::decode_all(data.as_ref())?)),
-        _ => Err(Error::General(format!(
-            "Unsupported compression type: {:?}",
-            compression_type
-        ))),
+        #[cfg(feature = "snappy")]
+        fb::CompressionType::Snappy => Ok(Bytes::from(
+            snap::raw::Decoder::new()
+                .decompress_vec(&data)
+                .map_err(|e| Error::External(Box::new(e)))?,
+        )),
+        #[cfg(feature = "brotli")]
+        fb::CompressionType::Brotli => {
+            let mut decompressed = Vec::new();
+            brotli::BrotliDecompress(&mut data.as_ref(), &mut decompressed)?;
+            Ok(Bytes::from(decompressed))
+        }
+        compression_type => {
+            check_supported(compression_type)?;
+            unreachable!("{compression_type:?} is supported but not handled")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::RngCore;
+
+    use super::*;
+
+    fn auto_zstd() -> Compression {
+        Compression::new(fb::CompressionType::Zstd, CompressionLevel::default())
+    }
+
+    fn roundtrip(data: Bytes, compression: Compression) -> fb::CompressionType {
+        let (compressed, compression_type) = compress_data(data.clone(), compression).unwrap();
+        assert_eq!(decompress_data(compressed, compression_type).unwrap(), data);
+        compression_type
+    }
+
+    #[test]
+    fn test_auto_compression_level() {
+        let model = CompressionCostModel::default();
+        let zeros = vec![0u8; 1024 * 1024];
+        let mut random = vec![0u8; 1024 * 1024];
+        rand::thread_rng().fill_bytes(&mut random);
+        // 64 distinct bytes: about 6 bits of entropy per byte.
+        let moderate: Vec<u8> = random.iter().map(|b| b % 64).collect();
+
+        assert_eq!(model.zstd_level(&zeros[..1024]), None);
+        assert_eq!(model.zstd_level(&zeros[..16 * 1024]), Some(1));
+        assert_eq!(model.zstd_level(&zeros), Some(6));
+        assert_eq!(model.zstd_level(&moderate), Some(3));
+        assert_eq!(model.zstd_level(&random), None);
+        let slow = CompressionCostModel {
+            target_throughput_mb_s: 10.0,
+            ..Default::default()
+        };
+        assert_eq!(slow.zstd_level(&zeros), Some(15));
+
+        assert_eq!(
+            roundtrip(Bytes::from(zeros.clone()), auto_zstd()),
+            fb::CompressionType::Zstd
+        );
+        assert_eq!(
+            roundtrip(Bytes::from(random), auto_zstd()),
+            fb::CompressionType::Uncompressed
+        );
+        assert_eq!(
+            roundtrip(Bytes::from(zeros[..100].to_vec()), auto_zstd()),
+            fb::CompressionType::Uncompressed
+        );
+        assert_eq!(
+            roundtrip(
+                Bytes::from(zeros[..100].to_vec()),
+                Compression::new(fb::CompressionType::Lz4, CompressionLevel::default())
+            ),
+            fb::CompressionType::Uncompressed
+        );
+    }
+
+    #[test]
+    fn test_trained_dictionary() {
+        // Small EncUnits sharing most of their content, each too small to compress alone.
+        let mut rng = rand::thread_rng();
+        let mut common = vec![0u8; 1024];
+        rng.fill_bytes(&mut common);
+        let encunits: Vec<_> = (0..128)
+            .map(|i| {
+                let mut data = common.clone();
+                data.extend(format!("encunit {i}").bytes());
+                let compression =
+                    Compression::new(fb::CompressionType::Zstd, CompressionLevel::default());
+                compress_data(Bytes::from(data), compression).unwrap()
+            })
+            .collect();
+        assert!(encunits
+            .iter()
+            .all(|(_, compression_type)| *compression_type == fb::CompressionType::Uncompressed));
+
+        let (dictionary, compressed) =
+            compress_with_trained_dictionary(&encunits, auto_zstd(), 4096)
+                .unwrap()
+                .unwrap();
+        let size = |encunits: &[(Bytes, fb::CompressionType)]| {
+            encunits.iter().map(|(data, _)| data.len()).sum::<usize>()
+        };
+        assert!(dictionary.len() + size(&compressed) < size(&encunits));
+        for ((data, _), (compressed, compression_type)) in encunits.iter().zip(compressed) {
+            assert_eq!(compression_type, fb::CompressionType::Zstd);
+            assert_eq!(
+                decompress_data_with_dictionary(compressed, compression_type, &dictionary).unwrap(),
+                data
+            );
+        }
+
+        // Too few EncUnits, or another codec.
+        assert!(
+            compress_with_trained_dictionary(&encunits[..2], auto_zstd(), 4096)
+                .unwrap()
+                .is_none()
+        );
+        let lz4 = Compression::new(fb::CompressionType::Lz4, CompressionLevel::default());
+        assert!(compress_with_trained_dictionary(&encunits, lz4, 4096)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_fixed_compression_level() {
+        let data = Bytes::from(vec![1u8; 100]);
+        for compression_type in supported_compression_types() {
+            let compression = Compression::new(compression_type, CompressionLevel::Fixed(0));
+            assert_eq!(roundtrip(data.clone(), compression), compression_type);
+        }
+        assert_eq!(
+            roundtrip(data, Compression::default()),
+            fb::CompressionType::Uncompressed
+        );
+    }
+
+    #[test]
+    fn test_unsupported_compression_type() {
+        let unknown = fb::CompressionType(42);
+        let err = compress_data(
+            Bytes::from(vec![1u8; 100]),
+            Compression::new(unknown, CompressionLevel::Fixed(0)),
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("42, this build supports Uncompressed, Zstd, Lz4"));
+        assert!(decompress_data(Bytes::new(), unknown).is_err());
+        assert!(check_supported(fb::CompressionType::Zstd).is_ok());
     }
 }
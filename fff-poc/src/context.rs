@@ -1,43 +1,57 @@
 use std::{
     collections::HashMap,
     path::PathBuf,
-    rc::Rc,
-    sync::{Arc, OnceLock},
+    sync::{Arc, Mutex, OnceLock},
 };
 
 use arrow_schema::DataType;
+use fff_core::{
+    errors::{Error, Result},
+    general_error,
+};
+use fff_encoding::schemes::adaptive::AdaptiveEncodingOptions;
 use fff_format::File::fff::flatbuf as fb;
-use fff_test_util::BUILTIN_WASM_PATH;
-use fff_ude_wasm::Runtime;
+use fff_test_util::{BUILTIN_WASM_PATH, WASM_FUNC_ENCODE};
+use fff_ude_wasm::{aot::AotTarget, Runtime};
 use semver::Version;
+use serde::{Deserialize, Serialize};
 
-use crate::{file::footer::MetadataSection, io::reader::Reader};
+use crate::{
+    decoder::encunit::{decode_support, DecodeSupport},
+    file::{
+        footer::MetadataSection,
+        wasm_aot::{deserialize_wasm_aot_artifacts, WasmAotArtifact},
+        wasm_modules::{deserialize_wasm_modules, wasm_module_hash, WasmModuleInfo},
+    },
+    io::reader::Reader,
+    reader::{WasmModuleCache, WasmResolver},
+};
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct WASMId(pub u32);
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct WasmLib {
-    encode_lib_path: Rc<PathBuf>,
-    decode_wasm_binary: Rc<Vec<u8>>,
+    encode_lib_path: Arc<PathBuf>,
+    decode_wasm_binary: Arc<Vec<u8>>,
 }
 
 impl WasmLib {
     pub fn new(enc_path: PathBuf, dec_wasm: Vec<u8>) -> Self {
         Self {
-            encode_lib_path: Rc::new(enc_path),
-            decode_wasm_binary: Rc::new(dec_wasm),
+            encode_lib_path: Arc::new(enc_path),
+            decode_wasm_binary: Arc::new(dec_wasm),
         }
     }
 
-    pub fn encode_lib_path(&self) -> Rc<PathBuf> {
+    pub fn encode_lib_path(&self) -> Arc<PathBuf> {
         self.encode_lib_path.clone()
     }
 }
 
 /// Behavior is a little weird for the research use now. We either use default_with_always_set_custom_wasm() to write all built-in as wasm,
 /// or we set built-in as native and allow custom wasm.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct WASMWritingContext {
     /// WASMId to its binaries
     wasms: HashMap<WASMId, WasmLib>,
@@ -47,6 +61,12 @@ pub struct WASMWritingContext {
     always_set_custom_wasm_for_built_in: bool,
     /// WasmId for built-in
     builtin_wasm_id: Option<WASMId>,
+    /// Choose the encoding of each EncUnit by sampling, instead of one encoder per data type.
+    adaptive_encoding: Option<AdaptiveEncodingOptions>,
+    /// Runtimes of the WASM binaries registered with [`Self::register_wasm`].
+    registered_runtimes: HashMap<WASMId, Arc<Runtime>>,
+    /// WASM encoder of the column this context is for, see [`Self::for_column`].
+    column_wasm_encoder: Option<WASMId>,
 }
 
 impl Default for WASMWritingContext {
@@ -63,6 +83,9 @@ impl Default for WASMWritingContext {
             data_type_to_wasm_id: HashMap::default(),
             always_set_custom_wasm_for_built_in: false,
             builtin_wasm_id: Some(WASMId(0)),
+            adaptive_encoding: None,
+            registered_runtimes: HashMap::new(),
+            column_wasm_encoder: None,
         }
     }
 }
@@ -81,6 +104,9 @@ impl WASMWritingContext {
             data_type_to_wasm_id: HashMap::new(),
             always_set_custom_wasm_for_built_in: false,
             builtin_wasm_id: None,
+            adaptive_encoding: None,
+            registered_runtimes: HashMap::new(),
+            column_wasm_encoder: None,
         }
     }
 
@@ -94,6 +120,9 @@ impl WASMWritingContext {
             data_type_to_wasm_id,
             always_set_custom_wasm_for_built_in: false,
             builtin_wasm_id: None,
+            adaptive_encoding: None,
+            registered_runtimes: HashMap::new(),
+            column_wasm_encoder: None,
         }
     }
 
@@ -106,8 +135,10 @@ impl WASMWritingContext {
             .collect()
     }
 
+    /// The WASM decoding EncUnits of `dt`. The WASM encoder of the column takes precedence.
     pub fn data_type_to_wasm_id(&self, dt: &DataType) -> Option<WASMId> {
-        self.data_type_to_wasm_id.get(dt).copied()
+        self.column_wasm_encoder
+            .or_else(|| self.data_type_to_wasm_id.get(dt).copied())
     }
 
     pub fn data_type_to_wasm_lib(&self, dt: &DataType) -> Option<WasmLib> {
@@ -123,33 +154,197 @@ impl WASMWritingContext {
     pub fn builtin_wasm_id(&self) -> Option<WASMId> {
         self.builtin_wasm_id
     }
+
+    pub fn with_adaptive_encoding(
+        mut self,
+        adaptive_encoding: Option<AdaptiveEncodingOptions>,
+    ) -> Self {
+        self.adaptive_encoding = adaptive_encoding;
+        self
+    }
+
+    pub fn adaptive_encoding(&self) -> Option<AdaptiveEncodingOptions> {
+        self.adaptive_encoding
+    }
+
+    /// Register a WASM binary to be written to the file. The binary is written once, even if
+    /// it is registered again or used by several columns.
+    pub fn register_wasm(&mut self, wasm_binary: Vec<u8>) -> Result<WASMId> {
+        if let Some((&wasm_id, _)) = self
+            .registered_runtimes
+            .iter()
+            .find(|(id, _)| *self.wasms[*id].decode_wasm_binary == wasm_binary)
+        {
+            return Ok(wasm_id);
+        }
+        let runtime =
+            Runtime::try_new(&wasm_binary).map_err(|e| general_error!("Invalid WASM binary", e))?;
+        let wasm_id = self.allocate_wasm_id();
+        self.wasms
+            .insert(wasm_id, WasmLib::new(PathBuf::new(), wasm_binary));
+        self.registered_runtimes.insert(wasm_id, Arc::new(runtime));
+        Ok(wasm_id)
+    }
+
+    /// The lowest WASMId not taken yet. WASMIds are the positions of the binaries in the file, so
+    /// ids allocated this way stay dense.
+    pub fn allocate_wasm_id(&self) -> WASMId {
+        allocate_wasm_id(&self.wasms)
+    }
+
+    /// Check that the WASMIds are the positions of the binaries in the file, i.e., `0..n`, that
+    /// every data type maps to one of them and that no binary is written twice under two ids.
+    pub fn validate_wasm_ids(&self) -> Result<()> {
+        let mut wasm_ids = self.wasms.keys().map(|id| id.0).collect::<Vec<_>>();
+        wasm_ids.sort_unstable();
+        if let Some((position, id)) = wasm_ids
+            .iter()
+            .enumerate()
+            .find(|(position, id)| *position as u32 != **id)
+        {
+            return Err(general_error!(format!(
+                "WASMId {id} is not the position {position} of its binary in the file"
+            )));
+        }
+        if let Some((dt, wasm_id)) = self
+            .data_type_to_wasm_id
+            .iter()
+            .find(|(_, wasm_id)| !self.wasms.contains_key(wasm_id))
+        {
+            return Err(general_error!(format!(
+                "{dt} is mapped to WASMId {}, which has no binary",
+                wasm_id.0
+            )));
+        }
+        for (i, a) in wasm_ids.iter().enumerate() {
+            if let Some(b) = wasm_ids[i + 1..].iter().find(|b| {
+                self.wasms[&WASMId(*a)].decode_wasm_binary
+                    == self.wasms[&WASMId(**b)].decode_wasm_binary
+            }) {
+                return Err(general_error!(format!(
+                    "WASMIds {a} and {b} have the same binary"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// A context for the encoders of a top-level column whose EncUnits are all encoded by the
+    /// registered WASM binary `wasm_id`, which must export both an `encode_ffi` encoder and the
+    /// matching `decode_general_ffi` decoder.
+    pub fn for_column(&self, wasm_id: WASMId) -> Result<Self> {
+        let runtime = self.registered_runtimes.get(&wasm_id).ok_or_else(|| {
+            general_error!(format!("WASM binary {} is not registered", wasm_id.0))
+        })?;
+        if !runtime.functions().any(|name| name == WASM_FUNC_ENCODE) {
+            return Err(general_error!(format!(
+                "WASM binary {} does not export {WASM_FUNC_ENCODE}",
+                wasm_id.0
+            )));
+        }
+        Ok(Self {
+            column_wasm_encoder: Some(wasm_id),
+            ..self.clone()
+        })
+    }
+
+    /// The support of EncUnits of `data_type`, a physical type, encoded with `encoding` and
+    /// tagged with the registered WASM binary `wasm_id`, see [`decode_support`].
+    pub fn decode_support(
+        &self,
+        wasm_id: WASMId,
+        data_type: &DataType,
+        encoding: fb::EncodingType,
+    ) -> DecodeSupport {
+        decode_support(
+            data_type,
+            encoding,
+            self.registered_runtimes.get(&wasm_id).map(Arc::as_ref),
+        )
+    }
+
+    /// The WASM encoder of the column, if any.
+    pub fn wasm_encoder(&self) -> Option<Arc<Runtime>> {
+        self.column_wasm_encoder
+            .map(|wasm_id| self.registered_runtimes[&wasm_id].clone())
+    }
+}
+
+/// The lowest WASMId not in `wasms`.
+pub(crate) fn allocate_wasm_id<V>(wasms: &HashMap<WASMId, V>) -> WASMId {
+    (0..)
+        .map(WASMId)
+        .find(|wasm_id| !wasms.contains_key(wasm_id))
+        .unwrap()
 }
 
 pub struct WASMReadingContext<R> {
-    /// runtime
-    lazy_wasm: OnceLock<HashMap<WASMId, Arc<Runtime>>>,
+    /// Runtimes instantiated so far. Each module is instantiated on the first decoding of one of
+    /// its EncUnits, so modules not referenced by the projected columns are never compiled.
+    runtimes: Mutex<HashMap<WASMId, Arc<Runtime>>>,
+    /// Locations of the WASM binaries in the file, indexed by WASMId. Read once.
+    binary_locations: OnceLock<Vec<(u64, u32)>>,
     wasm_locations: Option<MetadataSection>,
     r: Option<R>,
     /// Mapping of encoding types to their semantic versions
     encoding_versions: Option<HashMap<fb::EncodingType, Version>>,
+    /// Location of the "WASMModules" section, see [`crate::file::wasm_modules`].
+    modules_section: Option<MetadataSection>,
+    /// Parsed from `modules_section` once.
+    modules: OnceLock<Vec<WasmModuleInfo>>,
+    module_cache: Option<Arc<WasmModuleCache>>,
+    resolver: Option<Arc<dyn WasmResolver>>,
+    /// Location of the "WASMAotArtifacts" section, see [`crate::file::wasm_aot`], if the
+    /// modules are instantiated from their AOT artifacts.
+    aot_section: Option<MetadataSection>,
+    /// Parsed from `aot_section` once.
+    aot_artifacts: OnceLock<Vec<WasmAotArtifact>>,
 }
 
 impl<R: Reader> WASMReadingContext<R> {
     // Private constructor to reduce code duplication
     fn new_internal(
-        lazy_wasm: OnceLock<HashMap<WASMId, Arc<Runtime>>>,
+        runtimes: HashMap<WASMId, Arc<Runtime>>,
         wasm_locations: Option<MetadataSection>,
         r: Option<R>,
         encoding_versions: Option<HashMap<fb::EncodingType, Version>>,
     ) -> Self {
         Self {
-            lazy_wasm,
+            runtimes: Mutex::new(runtimes),
+            binary_locations: OnceLock::new(),
             wasm_locations,
             r,
             encoding_versions,
+            modules_section: None,
+            modules: OnceLock::new(),
+            module_cache: None,
+            resolver: None,
+            aot_section: None,
+            aot_artifacts: OnceLock::new(),
         }
     }
 
+    /// Share the modules with other files by content hash, see
+    /// [`FileReaderV2Builder::with_wasm_module_cache`](crate::reader::FileReaderV2Builder::with_wasm_module_cache).
+    pub fn with_module_sharing(
+        mut self,
+        modules_section: Option<MetadataSection>,
+        module_cache: Option<Arc<WasmModuleCache>>,
+        resolver: Option<Arc<dyn WasmResolver>>,
+    ) -> Self {
+        self.modules_section = modules_section;
+        self.module_cache = module_cache;
+        self.resolver = resolver;
+        self
+    }
+
+    /// Instantiate the modules from their AOT artifacts for the host in `aot_section`, if any,
+    /// see [`FileReaderV2Builder::with_trusted_wasm_aot`](crate::reader::FileReaderV2Builder::with_trusted_wasm_aot).
+    pub fn with_aot_artifacts(mut self, aot_section: Option<MetadataSection>) -> Self {
+        self.aot_section = aot_section;
+        self
+    }
+
     // For lazy loading from file
     pub fn new(wasm_locations: MetadataSection, r: R) -> Self {
         Self::new_with_versions(wasm_locations, r, None)
@@ -161,7 +356,7 @@ impl<R: Reader> WASMReadingContext<R> {
         encoding_versions: Option<HashMap<fb::EncodingType, Version>>,
     ) -> Self {
         Self::new_internal(
-            OnceLock::new(),
+            HashMap::new(),
             Some(wasm_locations),
             Some(r),
             encoding_versions,
@@ -177,34 +372,192 @@ impl<R: Reader> WASMReadingContext<R> {
         wasm_rts: HashMap<WASMId, Arc<Runtime>>,
         encoding_versions: Option<HashMap<fb::EncodingType, Version>>,
     ) -> Self {
-        let lazy_wasm = OnceLock::new();
-        lazy_wasm.get_or_init(|| wasm_rts);
-        Self::new_internal(lazy_wasm, None, None, encoding_versions)
-    }
-
-    pub fn get_runtime(&self, wasm_id: WASMId) -> Arc<Runtime> {
-        self.lazy_wasm
-            .get_or_init(|| {
-                let wasm_locations = self.wasm_locations.as_ref().unwrap();
-                let mut wasms = HashMap::new();
-                let mut buf = vec![0; wasm_locations.size as usize];
-                let read = self.r.as_ref().unwrap();
-                read.read_exact_at(&mut buf, wasm_locations.offset).unwrap();
-                let wasm_binaries = flatbuffers::root::<fb::WASMBinaries>(&buf).unwrap();
-                for (id, loc) in wasm_binaries.wasm_binaries().unwrap().iter().enumerate() {
-                    let mut buf: Vec<u8> = vec![0; loc.size_() as usize];
-                    read.read_exact_at(&mut buf, loc.offset()).unwrap();
-                    let wasm_id = WASMId(id as u32);
-                    // let start = std::time::Instant::now();
-                    let rt = Arc::new(Runtime::try_new(&buf).unwrap());
-                    // println!("WASM runtime creation time: {:?}", start.elapsed());
-                    wasms.insert(wasm_id, rt);
-                }
-                wasms
-            })
-            .get(&wasm_id)
+        Self::new_internal(wasm_rts, None, None, encoding_versions)
+    }
+
+    /// Offset and size of each module stored in the file, read on first use.
+    fn binary_locations(&self) -> Result<&[(u64, u32)]> {
+        if let Some(locations) = self.binary_locations.get() {
+            return Ok(locations);
+        }
+        let (Some(wasm_locations), Some(r)) = (&self.wasm_locations, &self.r) else {
+            return Ok(&[]);
+        };
+        let mut buf = vec![0; wasm_locations.size as usize];
+        r.read_exact_at(&mut buf, wasm_locations.offset)?;
+        let wasm_binaries = flatbuffers::root::<fb::WASMBinaries>(&buf)?;
+        let locations = wasm_binaries
+            .wasm_binaries()
+            .into_iter()
+            .flatten()
+            .map(|loc| (loc.offset(), loc.size_()))
+            .collect();
+        Ok(self.binary_locations.get_or_init(|| locations))
+    }
+
+    /// Content hash and URI of each module, absent in files written before they were recorded.
+    fn modules(&self) -> Result<&[WasmModuleInfo]> {
+        let Some(modules_section) = &self.modules_section else {
+            return Ok(&[]);
+        };
+        if let Some(modules) = self.modules.get() {
+            return Ok(modules);
+        }
+        let mut buf = vec![0; modules_section.size as usize];
+        self.r
+            .as_ref()
             .unwrap()
-            .clone()
+            .read_exact_at(&mut buf, modules_section.offset)?;
+        let modules = deserialize_wasm_modules(&buf)?;
+        Ok(self.modules.get_or_init(|| modules))
+    }
+
+    /// The AOT artifacts of the modules, if they are instantiated from them.
+    fn aot_artifacts(&self) -> Result<&[WasmAotArtifact]> {
+        let (Some(aot_section), Some(r)) = (&self.aot_section, &self.r) else {
+            return Ok(&[]);
+        };
+        if let Some(artifacts) = self.aot_artifacts.get() {
+            return Ok(artifacts);
+        }
+        let mut buf = vec![0; aot_section.size as usize];
+        r.read_exact_at(&mut buf, aot_section.offset)?;
+        let artifacts = deserialize_wasm_aot_artifacts(&buf)?;
+        Ok(self.aot_artifacts.get_or_init(|| artifacts))
+    }
+
+    /// The runtime of `wasm_id` instantiated from its AOT artifact for the host, if any.
+    /// Artifacts failing to load, e.g., compiled with another configuration, are skipped.
+    fn load_aot_runtime(&self, wasm_id: WASMId) -> Result<Option<Runtime>> {
+        let host = AotTarget::host();
+        for artifact in self
+            .aot_artifacts()?
+            .iter()
+            .filter(|artifact| artifact.wasm_id == wasm_id && artifact.target == host)
+        {
+            let mut buf = vec![0; artifact.size as usize];
+            self.r
+                .as_ref()
+                .unwrap()
+                .read_exact_at(&mut buf, artifact.offset)?;
+            if let Ok(runtime) = Runtime::try_new_from_aot(&buf) {
+                return Ok(Some(runtime));
+            }
+        }
+        Ok(None)
+    }
+
+    fn load_binary(&self, wasm_id: WASMId, module: Option<&WasmModuleInfo>) -> Result<Vec<u8>> {
+        if let Some(WasmModuleInfo {
+            hash,
+            uri: Some(uri),
+        }) = module
+        {
+            let resolver = self.resolver.as_ref().ok_or_else(|| {
+                general_error!(format!(
+                    "WASM module {} is stored by reference to {uri}, but no resolver is set",
+                    wasm_id.0
+                ))
+            })?;
+            let wasm_binary = resolver.resolve(*hash, uri)?;
+            if wasm_module_hash(&wasm_binary) != *hash {
+                return Err(general_error!(format!(
+                    "WASM module resolved from {uri} does not match its hash"
+                )));
+            }
+            return Ok(wasm_binary);
+        }
+        let Some(r) = &self.r else {
+            return Err(general_error!(format!(
+                "No runtime provided for WASM module {}",
+                wasm_id.0
+            )));
+        };
+        let idx = wasm_id.0 as usize;
+        let binary_locations = self.binary_locations()?;
+        let (offset, size) = *binary_locations
+            .get(idx)
+            .ok_or(Error::IndexOutOfBound(idx, binary_locations.len()))?;
+        let mut buf: Vec<u8> = vec![0; size as usize];
+        r.read_exact_at(&mut buf, offset)?;
+        Ok(buf)
+    }
+
+    /// The runtime of `wasm_id`, instantiated on first use. Modules are looked up by hash in the
+    /// [`WasmModuleCache`], if any, and the ones stored by reference are fetched with the
+    /// [`WasmResolver`].
+    pub fn get_runtime(&self, wasm_id: WASMId) -> Result<Arc<Runtime>> {
+        let mut runtimes = self.runtimes.lock().unwrap();
+        if let Some(runtime) = runtimes.get(&wasm_id) {
+            return Ok(runtime.clone());
+        }
+        let module = self.modules()?.get(wasm_id.0 as usize);
+        let runtime = match (module, &self.module_cache) {
+            (Some(module), Some(cache)) => {
+                cache.get_or_try_insert(module.hash, || self.load_binary(wasm_id, Some(module)))?
+            }
+            _ => Arc::new(match self.load_aot_runtime(wasm_id)? {
+                Some(runtime) => runtime,
+                None => Runtime::try_new(&self.load_binary(wasm_id, module)?)
+                    .map_err(|e| general_error!("Invalid WASM binary", e))?,
+            }),
+        };
+        runtimes.insert(wasm_id, runtime.clone());
+        Ok(runtime)
+    }
+
+    /// Whether the runtime of `wasm_id` can be obtained without failing for lack of its module:
+    /// it is loaded or cached already, embedded in the file, or stored by reference and a
+    /// [`WasmResolver`] is set. Nothing is fetched nor compiled, so resolving the module may still
+    /// fail.
+    pub fn is_available(&self, wasm_id: WASMId) -> Result<bool> {
+        if self.runtimes.lock().unwrap().contains_key(&wasm_id) {
+            return Ok(true);
+        }
+        if self.has_external_runtimes() {
+            return Ok(false);
+        }
+        Ok(match self.modules()?.get(wasm_id.0 as usize) {
+            Some(module) => {
+                module.uri.is_none()
+                    || self.resolver.is_some()
+                    || self
+                        .module_cache
+                        .as_ref()
+                        .is_some_and(|cache| cache.contains(module.hash))
+            }
+            // Files written before modules were recorded embed all of them.
+            None => true,
+        })
+    }
+
+    /// Content hash of the module `wasm_id` of the file, see [`crate::file::wasm_modules`]. None
+    /// for files written before hashes were recorded, or when the runtimes were provided by the
+    /// caller.
+    pub fn module_hash(&self, wasm_id: WASMId) -> Result<Option<u64>> {
+        Ok(self
+            .modules()?
+            .get(wasm_id.0 as usize)
+            .map(|module| module.hash))
+    }
+
+    /// The runtimes loaded so far, sorted by WASMId. Nothing is loaded before the first WASM
+    /// EncUnit is decoded, unless the runtimes were provided by the caller.
+    pub fn loaded_runtimes(&self) -> Vec<(WASMId, Arc<Runtime>)> {
+        let mut runtimes = self
+            .runtimes
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(wasm_id, rt)| (*wasm_id, rt.clone()))
+            .collect::<Vec<_>>();
+        runtimes.sort_by_key(|(wasm_id, _)| wasm_id.0);
+        runtimes
+    }
+
+    /// Whether the runtimes were provided by the caller instead of loaded from the file.
+    pub fn has_external_runtimes(&self) -> bool {
+        self.wasm_locations.is_none()
     }
 
     pub fn get_encoding_versions(&self) -> Option<&HashMap<fb::EncodingType, Version>> {
@@ -0,0 +1,200 @@
+//! Writing a directory of F3 files that together form one logical table.
+//!
+//! [`DatasetWriter`] wraps [`FileWriter`] and rolls over to a new file once a target file size or
+//! row count is reached. On [`DatasetWriter::finish`], a [`DatasetManifest`] listing every file,
+//! its row count and the shared schema is written next to the data files.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow_array::RecordBatch;
+use arrow_ipc::convert::{schema_to_fb, try_schema_from_flatbuffer_bytes};
+use arrow_schema::SchemaRef;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use fff_core::{errors::Result, general_error};
+use serde::{Deserialize, Serialize};
+
+use crate::options::FileWriterOptions;
+use crate::writer::FileWriter;
+
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// A data file of a dataset, relative to the dataset directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DatasetFile {
+    pub path: String,
+    pub num_rows: u64,
+    /// Size of the file in bytes.
+    pub size: u64,
+}
+
+/// The manifest of a dataset: the schema shared by all files and the files in write order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatasetManifest {
+    schema: SchemaRef,
+    files: Vec<DatasetFile>,
+}
+
+/// On-disk representation of the manifest. The schema is stored as a base64-encoded
+/// Arrow IPC flatbuffer so that it round-trips losslessly.
+#[derive(Serialize, Deserialize)]
+struct ManifestJson {
+    schema: String,
+    num_rows: u64,
+    files: Vec<DatasetFile>,
+}
+
+impl DatasetManifest {
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    pub fn files(&self) -> &[DatasetFile] {
+        &self.files
+    }
+
+    pub fn num_rows(&self) -> u64 {
+        self.files.iter().map(|f| f.num_rows).sum()
+    }
+
+    /// Absolute paths of the data files, given the dataset directory.
+    pub fn file_paths(&self, dir: impl AsRef<Path>) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .map(|f| dir.as_ref().join(&f.path))
+            .collect()
+    }
+
+    /// Read the manifest of the dataset in `dir`.
+    pub fn try_load(dir: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(dir.as_ref().join(MANIFEST_FILE_NAME))?;
+        let json: ManifestJson = serde_json::from_reader(file)
+            .map_err(|e| general_error!("Failed to parse dataset manifest", e))?;
+        let schema_bytes = STANDARD
+            .decode(json.schema)
+            .map_err(|e| general_error!("Failed to decode schema in dataset manifest", e))?;
+        let schema = try_schema_from_flatbuffer_bytes(&schema_bytes)?;
+        let manifest = Self {
+            schema: Arc::new(schema),
+            files: json.files,
+        };
+        if manifest.num_rows() != json.num_rows {
+            return Err(general_error!(format!(
+                "Dataset manifest has {} rows in total but its files sum up to {}",
+                json.num_rows,
+                manifest.num_rows()
+            )));
+        }
+        Ok(manifest)
+    }
+
+    fn write(&self, dir: impl AsRef<Path>) -> Result<()> {
+        let json = ManifestJson {
+            schema: STANDARD.encode(schema_to_fb(&self.schema).finished_data()),
+            num_rows: self.num_rows(),
+            files: self.files.clone(),
+        };
+        let file = File::create(dir.as_ref().join(MANIFEST_FILE_NAME))?;
+        serde_json::to_writer_pretty(file, &json)
+            .map_err(|e| general_error!("Failed to write dataset manifest", e))?;
+        Ok(())
+    }
+}
+
+/// Writes record batches into a directory of F3 files, starting a new file when the current one
+/// reaches the target file size or the maximum number of rows.
+///
+/// Both limits are thresholds checked after each `write_batch`, so a file may exceed them by up
+/// to one batch. The file size is estimated from the bytes flushed plus the encoders' buffered data.
+pub struct DatasetWriter {
+    dir: PathBuf,
+    schema: SchemaRef,
+    options: FileWriterOptions,
+    target_file_size: u64,
+    max_rows_per_file: u64,
+    cur_writer: Option<FileWriter<File>>,
+    files: Vec<DatasetFile>,
+}
+
+impl DatasetWriter {
+    /// Create a writer for the dataset in `dir`. The directory is created if it does not exist.
+    /// `options` is used for every file of the dataset.
+    pub fn try_new(
+        dir: impl AsRef<Path>,
+        schema: SchemaRef,
+        options: FileWriterOptions,
+    ) -> Result<Self> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            schema,
+            options,
+            target_file_size: u64::MAX,
+            max_rows_per_file: u64::MAX,
+            cur_writer: None,
+            files: vec![],
+        })
+    }
+
+    /// Roll over to a new file once the current one reaches this size in bytes.
+    pub fn with_target_file_size(mut self, target_file_size: u64) -> Self {
+        self.target_file_size = target_file_size;
+        self
+    }
+
+    /// Roll over to a new file once the current one reaches this number of rows.
+    pub fn with_max_rows_per_file(mut self, max_rows_per_file: u64) -> Self {
+        self.max_rows_per_file = max_rows_per_file;
+        self
+    }
+
+    fn cur_file_name(&self) -> String {
+        format!("part-{:05}.fff", self.files.len())
+    }
+
+    pub fn write_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+        if self.cur_writer.is_none() {
+            let file = File::create(self.dir.join(self.cur_file_name()))?;
+            self.cur_writer = Some(FileWriter::try_new(
+                self.schema.clone(),
+                file,
+                self.options.clone(),
+            )?);
+        }
+        let writer = self.cur_writer.as_mut().unwrap();
+        writer.write_batch(batch)?;
+        let estimated_size = writer.bytes_written()? + writer.memory_size() as u64;
+        if writer.num_rows() >= self.max_rows_per_file || estimated_size >= self.target_file_size {
+            self.finish_file()?;
+        }
+        Ok(())
+    }
+
+    /// Finish the file being written, if any, and record it in the manifest.
+    fn finish_file(&mut self) -> Result<()> {
+        if let Some(writer) = self.cur_writer.take() {
+            let num_rows = writer.num_rows();
+            writer.finish()?;
+            let path = self.cur_file_name();
+            let size = std::fs::metadata(self.dir.join(&path))?.len();
+            self.files.push(DatasetFile {
+                path,
+                num_rows,
+                size,
+            });
+        }
+        Ok(())
+    }
+
+    /// Finish the last file and write the manifest.
+    pub fn finish(mut self) -> Result<DatasetManifest> {
+        self.finish_file()?;
+        let manifest = DatasetManifest {
+            schema: self.schema,
+            files: self.files,
+        };
+        manifest.write(&self.dir)?;
+        Ok(manifest)
+    }
+}
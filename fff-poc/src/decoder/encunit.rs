@@ -1,26 +1,34 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, ops::Range, sync::Arc};
 
-use arrow_array::ArrayRef;
+use arrow_array::{make_array, Array, ArrayRef};
+use arrow_buffer::{BooleanBuffer, Buffer, NullBuffer};
 use arrow_schema::DataType;
 use bytes::Bytes;
 use fff_core::{
     errors::{Error, Result},
     general_error, non_nest_types, nyi_err,
-    util::buffer_to_array::primitive_array_from_arrow_buffers_iter,
+    util::buffer_to_array::primitive_array_from_arrow_buffers_iter_with_validity,
 };
 use fff_encoding::schemes::{
+    boolean::BooleanDecoder,
+    delta::DeltaDecoder,
+    rle::RleDecoder,
     vortex::{VortexDecoder, VortexListDecoder, VortexListStructDecoder},
     Decoder,
 };
+use fff_encoding::validity::decode_validity;
 use fff_format::File::fff::flatbuf as fb;
-use fff_test_util::WASM_FUNC_GENERAL;
-use fff_ude_wasm::Runtime;
+use fff_test_util::{WASM_FUNC_GENERAL, WASM_FUNC_GENERAL_BATCH};
+use fff_ude_wasm::{RowSelection, Runtime};
 use log::debug;
+use serde::{Deserialize, Serialize};
 use vortex_sampling_compressor::ALL_ENCODINGS_CONTEXT;
 
 use crate::{
-    compression::decompress_data, context::WASMReadingContext,
-    file::footer::DEFAULT_ENCODING_VERSIONS, io::reader::Reader,
+    compression::{decompress_data, decompress_data_with_dictionary},
+    context::{WASMId, WASMReadingContext},
+    file::footer::DEFAULT_ENCODING_VERSIONS,
+    io::reader::Reader,
 };
 
 /// Common API for decoding a EncUnit.
@@ -39,6 +47,24 @@ pub trait EncUnitDecoder {
     fn slice(&self, _start: usize, _stop: usize) -> Result<ArrayRef> {
         nyi_err!("slice")
     }
+    /// Decode only the rows in the sorted and non-overlapping `ranges`, concatenated.
+    fn select(&self, _ranges: &[Range<usize>]) -> Result<ArrayRef> {
+        nyi_err!("select")
+    }
+    /// Decode only the rows at `indexes`, in their order, see [`Decoder::take`].
+    fn take(&self, _indexes: &[u32]) -> Result<ArrayRef> {
+        nyi_err!("take")
+    }
+    /// Decode the runs of a run-length encoded EncUnit as a `RunArray`, without expanding them.
+    /// Only for EncUnits without nulls, as null slots may split runs.
+    fn decode_runs(&self) -> Result<ArrayRef> {
+        nyi_err!("decode_runs")
+    }
+    /// The WASM decoder of the EncUnit, to decode it together with others, see
+    /// [`decode_wasm_batch`].
+    fn as_wasm(&self) -> Option<&WASMEncUnitDecoder<'_>> {
+        None
+    }
 }
 
 /// The optional Key-Word args for advanced features.
@@ -82,6 +108,8 @@ pub struct WASMEncUnitDecoder<'a> {
     func_name: &'a str,
     output_type: DataType,
     num_rows: u64,
+    /// Validity from the validity sub-buffer of the EncUnit, if the encoding did not keep it.
+    validity: Option<NullBuffer>,
 }
 
 impl<'a> WASMEncUnitDecoder<'a> {
@@ -98,7 +126,72 @@ impl<'a> WASMEncUnitDecoder<'a> {
             func_name,
             output_type,
             num_rows,
+            validity: None,
+        }
+    }
+
+    pub fn with_validity(mut self, validity: Option<NullBuffer>) -> Self {
+        self.validity = validity;
+        self
+    }
+
+    /// Whether the module exports a batched decoding function for the output type, see
+    /// [`decode_wasm_batch`].
+    pub fn supports_batch(&self) -> bool {
+        matches!(self.output_type, non_nest_types!())
+            && self
+                .rt
+                .functions()
+                .any(|name| name == WASM_FUNC_GENERAL_BATCH)
+    }
+
+    /// Whether `other` can be decoded in the same batched call, see [`decode_wasm_batch`].
+    pub fn shares_runtime(&self, other: &WASMEncUnitDecoder<'_>) -> bool {
+        Arc::ptr_eq(&self.rt, &other.rt) && self.output_type == other.output_type
+    }
+
+    /// Build the array from the buffers output for this EncUnit.
+    fn array_from_buffers(&self, buffers: Vec<Buffer>) -> Result<ArrayRef> {
+        Ok(primitive_array_from_arrow_buffers_iter_with_validity(
+            &self.output_type,
+            buffers.into_iter(),
+            self.num_rows,
+            self.validity.clone(),
+        )?)
+    }
+
+    /// Decode an EncUnit holding several arrays, e.g., the children of a struct encoded together,
+    /// with a function exported with `multi_array_wrapper`. The guest outputs the arrays in the
+    /// order of `output_types`, each with its own length and validity, which are split here by
+    /// the manifest preceding their buffers.
+    pub fn decode_arrays(&self, output_types: &[DataType]) -> Result<Vec<ArrayRef>> {
+        let arrays = self
+            .rt
+            .call_multi_array(self.func_name, &self.data)
+            .map_err(|e| general_error!("WASM call failed", e))?;
+        if arrays.len() != output_types.len() {
+            return Err(general_error!(format!(
+                "Expected {} arrays from {}, got {}",
+                output_types.len(),
+                self.func_name,
+                arrays.len()
+            )));
         }
+        arrays
+            .into_iter()
+            .zip(output_types)
+            .map(|(array, output_type)| match output_type {
+                non_nest_types!() => Ok(primitive_array_from_arrow_buffers_iter_with_validity(
+                    output_type,
+                    array.buffers.into_iter(),
+                    array.len as u64,
+                    None,
+                )?),
+                _ => nyi_err!(format!(
+                    "Decoding {output_type} arrays of a multi-array EncUnit"
+                )),
+            })
+            .collect()
     }
 }
 
@@ -106,19 +199,93 @@ impl EncUnitDecoder for WASMEncUnitDecoder<'_> {
     fn decode(&self) -> Result<ArrayRef> {
         match &self.output_type {
             non_nest_types!() => {
-                let res = self
+                let buffers = self
                     .rt
-                    .call_multi_buf(self.func_name, &self.data)
+                    .call_multi_buf_validated(
+                        self.func_name,
+                        &self.data,
+                        &self.output_type,
+                        self.num_rows as usize,
+                    )
                     .map_err(|e| general_error!("WASM call failed", e))?;
-                Ok(primitive_array_from_arrow_buffers_iter(
-                    &self.output_type,
-                    res,
-                    self.num_rows,
-                )?)
+                self.array_from_buffers(buffers)
             }
             _ => unimplemented!(),
         }
     }
+
+    fn slice(&self, start: usize, stop: usize) -> Result<ArrayRef> {
+        self.select(&[start..stop])
+    }
+
+    /// Selective decoding inside the guest, for modules exporting `read_batch_ffi`.
+    fn select(&self, ranges: &[Range<usize>]) -> Result<ArrayRef> {
+        if !matches!(self.output_type, non_nest_types!())
+            || !self.rt.functions().any(|name| name == "read_batch_ffi")
+        {
+            return nyi_err!("Selective decoding in WASM");
+        }
+        let num_rows: usize = ranges.iter().map(|range| range.len()).sum();
+        let validity = self.validity.as_ref().map(|nulls| {
+            NullBuffer::new(BooleanBuffer::from_iter(
+                ranges
+                    .iter()
+                    .flat_map(|range| range.clone().map(|i| nulls.is_valid(i))),
+            ))
+        });
+        let mut batches = self
+            .rt
+            .read_batch(&self.data, &[], &RowSelection::Select(ranges.to_vec()))
+            .map_err(|e| general_error!("WASM call failed", e))?;
+        if batches.len() != 1 {
+            return Err(general_error!(format!(
+                "Expected a single batch from read_batch_ffi, got {}",
+                batches.len()
+            )));
+        }
+        Ok(primitive_array_from_arrow_buffers_iter_with_validity(
+            &self.output_type,
+            batches.pop().unwrap().into_iter(),
+            num_rows as u64,
+            validity,
+        )?)
+    }
+
+    fn as_wasm(&self) -> Option<&WASMEncUnitDecoder<'_>> {
+        Some(self)
+    }
+}
+
+/// Decode the EncUnits of `decoders` with a single call of the batched decoding function of their
+/// module, amortizing the call overhead over small EncUnits. All the decoders must share the same
+/// runtime and output type, and [`WASMEncUnitDecoder::supports_batch`].
+pub fn decode_wasm_batch(decoders: &[&WASMEncUnitDecoder<'_>]) -> Result<Vec<ArrayRef>> {
+    let Some(first) = decoders.first() else {
+        return Ok(vec![]);
+    };
+    if !decoders.iter().all(|d| first.shares_runtime(d)) {
+        return Err(general_error!(
+            "Batched EncUnits must share the WASM runtime and output type"
+        ));
+    }
+    let inputs = decoders.iter().map(|d| &d.data[..]).collect::<Vec<_>>();
+    let num_rows = decoders
+        .iter()
+        .map(|d| d.num_rows as usize)
+        .collect::<Vec<_>>();
+    first
+        .rt
+        .call_batch_validated(
+            WASM_FUNC_GENERAL_BATCH,
+            &inputs,
+            &first.output_type,
+            &num_rows,
+        )
+        .map_err(|e| general_error!("WASM call failed", e))?
+        .into_iter()
+        .zip(decoders)
+        .map(|(buffers, decoder)| decoder.array_from_buffers(buffers))
+        .collect()
 }
 
 pub struct VortexEncUnitDecoder {
@@ -132,6 +299,60 @@ impl VortexEncUnitDecoder {
     }
 }
 
+/// Wraps the decoder of an EncUnit whose nulls are stored in its validity sub-buffer,
+/// attaching them to the decoded arrays.
+struct ValidityEncUnitDecoder {
+    inner: Box<dyn EncUnitDecoder>,
+    nulls: NullBuffer,
+}
+
+impl ValidityEncUnitDecoder {
+    fn attach(array: ArrayRef, nulls: NullBuffer) -> Result<ArrayRef> {
+        if array.len() != nulls.len() {
+            return Err(general_error!(format!(
+                "Decoded {} rows but the validity has {}",
+                array.len(),
+                nulls.len()
+            )));
+        }
+        let data = array
+            .into_data()
+            .into_builder()
+            .nulls(Some(nulls))
+            .build()?;
+        Ok(make_array(data))
+    }
+}
+
+impl EncUnitDecoder for ValidityEncUnitDecoder {
+    fn decode(&self) -> Result<ArrayRef> {
+        Self::attach(self.inner.decode()?, self.nulls.clone())
+    }
+
+    fn slice(&self, start: usize, stop: usize) -> Result<ArrayRef> {
+        Self::attach(
+            self.inner.slice(start, stop)?,
+            self.nulls.slice(start, stop - start),
+        )
+    }
+
+    fn select(&self, ranges: &[Range<usize>]) -> Result<ArrayRef> {
+        let nulls = NullBuffer::new(BooleanBuffer::from_iter(
+            ranges
+                .iter()
+                .flat_map(|range| range.clone().map(|i| self.nulls.is_valid(i))),
+        ));
+        Self::attach(self.inner.select(ranges)?, nulls)
+    }
+
+    fn take(&self, indexes: &[u32]) -> Result<ArrayRef> {
+        let nulls = NullBuffer::new(BooleanBuffer::from_iter(
+            indexes.iter().map(|&i| self.nulls.is_valid(i as usize)),
+        ));
+        Self::attach(self.inner.take(indexes)?, nulls)
+    }
+}
+
 impl EncUnitDecoder for VortexEncUnitDecoder {
     fn decode(&self) -> Result<ArrayRef> {
         let bytes = self.data.clone();
@@ -230,40 +451,200 @@ impl EncUnitDecoder for VortexEncUnitDecoder {
         // )));
         Ok(array)
     }
+
+    fn take(&self, indexes: &[u32]) -> Result<ArrayRef> {
+        if !matches!(self.output_type, non_nest_types!()) {
+            return nyi_err!("Take from nested EncUnits");
+        }
+        let mut vortex_decoder =
+            VortexDecoder::try_new(self.data.clone(), ALL_ENCODINGS_CONTEXT.clone())?;
+        vortex_decoder.take(indexes)
+    }
 }
 
-pub fn create_encunit_decoder<R: Reader>(
-    encoding: fb::Encoding,
-    compression_type: fb::CompressionType,
-    mut data: Bytes,
-    num_rows: u64,
+/// Decoder for the native encodings of fixed-width primitives (RLE and delta) and booleans,
+/// which need neither Vortex nor WASM.
+pub struct NativeEncUnitDecoder {
+    data: Bytes,
     output_type: DataType,
-    wasm_context: Option<Arc<WASMReadingContext<R>>>,
-) -> Result<Box<dyn EncUnitDecoder>> {
-    if compression_type != fb::CompressionType::Uncompressed {
-        data = decompress_data(data, compression_type)?;
-    }
-    let return_wasm_decoder = |data: Bytes,
-                               output_type: DataType,
-                               wasm_context: Arc<WASMReadingContext<R>>,
-                               num_rows: u64|
-     -> Result<Box<dyn EncUnitDecoder>> {
-        Ok(Box::new(WASMEncUnitDecoder::new(
+    encoding_type: fb::EncodingType,
+    /// Row count of the EncUnit in the footer, which bounds the values decoded.
+    num_rows: usize,
+}
+
+impl NativeEncUnitDecoder {
+    pub fn new(
+        data: Bytes,
+        output_type: DataType,
+        encoding_type: fb::EncodingType,
+        num_rows: usize,
+    ) -> Self {
+        Self {
             data,
-            wasm_context.get_runtime(crate::context::WASMId(
-                encoding
-                    .wasm_encoding()
-                    .ok_or_else(|| {
-                        Error::General("not provided custom WASM in the file".to_string())
-                    })?
-                    .wasm_id(),
-            )),
-            WASM_FUNC_GENERAL, // FIXME: should get from wasm binary
             output_type,
+            encoding_type,
             num_rows,
-        )))
-    };
-    Ok(match encoding.type_() {
+        }
+    }
+
+    /// Reject an EncUnit recording another number of values than its row count, before its
+    /// values are allocated.
+    fn check_num_values(&self, num_values: usize) -> Result<()> {
+        if num_values != self.num_rows {
+            return Err(Error::ParseError(format!(
+                "{:?} EncUnit of {} rows holds {} values",
+                self.encoding_type, self.num_rows, num_values
+            )));
+        }
+        Ok(())
+    }
+
+    fn rle_decoder(&self) -> Result<RleDecoder> {
+        let decoder = RleDecoder::try_new(self.data.clone(), self.output_type.clone())?;
+        self.check_num_values(decoder.num_values())?;
+        Ok(decoder)
+    }
+
+    fn decoder(&self) -> Result<Box<dyn Decoder>> {
+        Ok(match self.encoding_type {
+            fb::EncodingType::RLE => Box::new(self.rle_decoder()?),
+            fb::EncodingType::DELTA => {
+                let decoder = DeltaDecoder::try_new(self.data.clone(), self.output_type.clone())?;
+                self.check_num_values(decoder.num_values())?;
+                Box::new(decoder)
+            }
+            // The values are bounded by the size of the EncUnit.
+            fb::EncodingType::BOOLEAN => Box::new(BooleanDecoder::try_new(self.data.clone())?),
+            _ => return nyi_err!("Native decoding for this encoding"),
+        })
+    }
+}
+
+impl EncUnitDecoder for NativeEncUnitDecoder {
+    fn decode(&self) -> Result<ArrayRef> {
+        self.decoder()?.decode_all_as_array()
+    }
+
+    fn slice(&self, start: usize, stop: usize) -> Result<ArrayRef> {
+        self.decoder()?.slice(start, stop)
+    }
+
+    fn take(&self, indexes: &[u32]) -> Result<ArrayRef> {
+        self.decoder()?.take(indexes)
+    }
+
+    fn decode_runs(&self) -> Result<ArrayRef> {
+        match self.encoding_type {
+            fb::EncodingType::RLE => self.rle_decoder()?.decode_runs(),
+            _ => nyi_err!("Runs of a non-RLE EncUnit"),
+        }
+    }
+}
+
+/// Split the validity sub-buffer off the decompressed `data` of `encunit` and decode it.
+/// Returns `None` if the EncUnit has none, or if it is not needed due to `skip_validity`.
+fn split_validity(
+    encunit: &fb::EncUnit,
+    data: &mut Bytes,
+    skip_validity: bool,
+) -> Result<Option<NullBuffer>> {
+    let validity_size = encunit.validity_size() as usize;
+    match encunit.validity_encoding() {
+        fb::ValidityEncoding::NONE => return Ok(None),
+        fb::ValidityEncoding::RLE => {}
+        validity_encoding => {
+            return nyi_err!(format!("Validity encoding {:?}", validity_encoding));
+        }
+    }
+    if data.len() < validity_size {
+        return Err(Error::EOF(format!(
+            "EncUnit of {} bytes is too short for its validity of {} bytes",
+            data.len(),
+            validity_size
+        )));
+    }
+    let validity = data.split_to(validity_size);
+    if skip_validity {
+        return Ok(None);
+    }
+    decode_validity(&validity, encunit.num_rows() as usize).map(Some)
+}
+
+/// How an EncUnit is decoded.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum DecodePath {
+    /// Decoded by the decoders built into the reader.
+    BuiltIn,
+    /// Decoded by the WASM module of the given id embedded in the file.
+    EmbeddedWasm(WASMId),
+    /// Decoded by the runtime of the given id registered by the caller,
+    /// see [`crate::reader::FileReaderV2Builder::with_existing_runtimes`].
+    ExternalRuntime(WASMId),
+}
+
+/// Whether the decoders built into the reader decode EncUnits of `data_type`, a physical type,
+/// encoded with `encoding`.
+pub fn built_in_supports(data_type: &DataType, encoding: fb::EncodingType) -> bool {
+    match encoding {
+        fb::EncodingType::CASCADE => matches!(
+            data_type,
+            non_nest_types!() | DataType::List(_) | DataType::LargeList(_)
+        ),
+        fb::EncodingType::RLE | fb::EncodingType::DELTA => {
+            data_type.is_primitive() && matches!(data_type.primitive_width(), Some(1 | 2 | 4 | 8))
+        }
+        fb::EncodingType::BOOLEAN => data_type == &DataType::Boolean,
+        _ => false,
+    }
+}
+
+/// Who decodes EncUnits of a data type and an encoding, see [`decode_support`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DecodeSupport {
+    /// The decoders built into the reader do.
+    pub built_in: bool,
+    /// The WASM module does, by its capability flags, see [`Runtime::supports`]. None without a
+    /// module, or if the module declares no capability.
+    pub wasm: Option<bool>,
+}
+
+impl DecodeSupport {
+    /// Whether the module is known not to decode the EncUnits, so that tagging them with it
+    /// leaves readers without a fallback if the built-in decoders do not either.
+    pub fn wasm_refuses(&self) -> bool {
+        self.wasm == Some(false)
+    }
+
+    /// Whether some decoder is known to decode the EncUnits.
+    pub fn is_supported(&self) -> bool {
+        self.built_in || self.wasm == Some(true)
+    }
+}
+
+/// The support matrix entry of EncUnits of `data_type`, a physical type, encoded with `encoding`
+/// and tagged with the module of `runtime`, if any: the built-in decoders combined with the
+/// capability flags of the module. Readers use it to plan fallbacks before decoding, and writers
+/// to refuse tagging EncUnits with a module that cannot decode them.
+pub fn decode_support(
+    data_type: &DataType,
+    encoding: fb::EncodingType,
+    runtime: Option<&Runtime>,
+) -> DecodeSupport {
+    DecodeSupport {
+        built_in: built_in_supports(data_type, encoding),
+        wasm: runtime.and_then(|runtime| {
+            runtime.supports(data_type, encoding.variant_name().unwrap_or_default())
+        }),
+    }
+}
+
+/// Decide how an EncUnit with `encoding` is decoded.
+/// Built-in encodings fall back to WASM if the file was written with a newer incompatible version.
+pub fn decode_path<R: Reader>(
+    encoding: fb::Encoding,
+    wasm_context: Option<&WASMReadingContext<R>>,
+) -> Result<DecodePath> {
+    let use_wasm = match encoding.type_() {
         fb::EncodingType::CASCADE => {
             let wasm_context =
                 wasm_context.ok_or_else(|| general_error!("WASM context not found"))?;
@@ -275,21 +656,132 @@ pub fn create_encunit_decoder<R: Reader>(
                 .ok_or_else(|| general_error!("Encoding version not found"))?;
             let reader_version = DEFAULT_ENCODING_VERSIONS.get(&encoding.type_()).unwrap();
             // if reader has less version than the file, and major version is different or major version is 0, then it is incompatible
-            if reader_version.cmp_precedence(encoding_version).is_lt()
+            reader_version.cmp_precedence(encoding_version).is_lt()
                 && (reader_version.major != encoding_version.major || reader_version.major == 0)
-            {
-                return_wasm_decoder(data, output_type, wasm_context, num_rows)?
-            } else {
-                Box::new(VortexEncUnitDecoder::new(data, output_type))
-            }
         }
         fb::EncodingType::CUSTOM_WASM => {
-            if let Some(wasm_context) = wasm_context {
-                return_wasm_decoder(data, output_type, wasm_context, num_rows)?
-            } else {
+            if wasm_context.is_none() {
                 return Err(general_error!("WASM context required for custom encoding"));
             }
+            true
         }
-        _ => unimplemented!(),
+        _ => false,
+    };
+    if !use_wasm {
+        return Ok(DecodePath::BuiltIn);
+    }
+    let wasm_id = WASMId(
+        encoding
+            .wasm_encoding()
+            .ok_or_else(|| Error::General("not provided custom WASM in the file".to_string()))?
+            .wasm_id(),
+    );
+    Ok(if wasm_context.unwrap().has_external_runtimes() {
+        DecodePath::ExternalRuntime(wasm_id)
+    } else {
+        DecodePath::EmbeddedWasm(wasm_id)
     })
 }
+
+pub fn create_encunit_decoder<R: Reader>(
+    encunit: fb::EncUnit,
+    mut data: Bytes,
+    output_type: DataType,
+    wasm_context: Option<Arc<WASMReadingContext<R>>>,
+    skip_validity: bool,
+    compression_dictionary: Option<&[u8]>,
+) -> Result<Box<dyn EncUnitDecoder>> {
+    let encoding = encunit
+        .encoding()
+        .ok_or_else(|| general_error!("EncUnit without encoding"))?;
+    let compression_type = encunit.compression();
+    let num_rows = encunit.num_rows() as u64;
+    if compression_type != fb::CompressionType::Uncompressed {
+        data = match compression_dictionary {
+            Some(dictionary) => {
+                decompress_data_with_dictionary(data, compression_type, dictionary)?
+            }
+            None => decompress_data(data, compression_type)?,
+        };
+    }
+    // With `skip_validity`, the validity sub-buffer is skipped over without being decoded.
+    // Encodings keeping the nulls themselves, e.g., Vortex, have no validity to decode in a chunk
+    // without nulls, as its EncUnits are encoded as non-nullable.
+    let validity = split_validity(&encunit, &mut data, skip_validity)?;
+    let decoder: Box<dyn EncUnitDecoder> = match decode_path(encoding, wasm_context.as_deref())? {
+        // The WASM decoder merges the validity into the buffers it returns.
+        DecodePath::EmbeddedWasm(wasm_id) | DecodePath::ExternalRuntime(wasm_id) => {
+            return Ok(Box::new(
+                WASMEncUnitDecoder::new(
+                    data,
+                    wasm_context.unwrap().get_runtime(wasm_id)?,
+                    WASM_FUNC_GENERAL, // FIXME: should get from wasm binary
+                    output_type,
+                    num_rows,
+                )
+                .with_validity(validity),
+            ));
+        }
+        DecodePath::BuiltIn => match encoding.type_() {
+            fb::EncodingType::CASCADE => Box::new(VortexEncUnitDecoder::new(data, output_type)),
+            fb::EncodingType::RLE | fb::EncodingType::DELTA | fb::EncodingType::BOOLEAN => {
+                Box::new(NativeEncUnitDecoder::new(
+                    data,
+                    output_type,
+                    encoding.type_(),
+                    num_rows as usize,
+                ))
+            }
+            encoding_type => {
+                return nyi_err!(format!("Built-in decoding of {encoding_type:?} EncUnits"));
+            }
+        },
+    };
+    Ok(match validity {
+        Some(nulls) => Box::new(ValidityEncUnitDecoder {
+            inner: decoder,
+            nulls,
+        }),
+        None => decoder,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use arrow_array::Int64Array;
+    use fff_encoding::schemes::{delta::DeltaEncoder, encode_to_bytes, rle::RleEncoder, Encoder};
+
+    use super::*;
+
+    #[test]
+    fn test_native_num_values_bounded_by_num_rows() {
+        // Constant values: 20 bytes of delta metadata without any data, and a single run.
+        let arr = Arc::new(Int64Array::from(vec![7; 1000])) as ArrayRef;
+        for (encoder, encoding_type) in [
+            (
+                Rc::new(RleEncoder) as Rc<dyn Encoder>,
+                fb::EncodingType::RLE,
+            ),
+            (Rc::new(DeltaEncoder), fb::EncodingType::DELTA),
+        ] {
+            let bytes = encode_to_bytes(encoder, arr.clone());
+            let decoder =
+                |data| NativeEncUnitDecoder::new(data, DataType::Int64, encoding_type, 1000);
+            assert_eq!(
+                decoder(bytes.clone()).decode().unwrap().as_ref(),
+                arr.as_ref()
+            );
+            // num_values follows the u32 size of the metadata.
+            let mut patched = bytes.to_vec();
+            patched[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+            let err = decoder(Bytes::from(patched)).decode().unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains("EncUnit of 1000 rows holds 4294967295 values"),
+                "{err}"
+            );
+        }
+    }
+}
@@ -1,17 +1,28 @@
 use std::sync::Arc;
 
 use crate::common::checksum::{create_checksum, ChecksumType};
+use crate::common::physical_type::{from_physical, physical_type};
+use crate::common::statistics::{statistics_mismatch, MinMaxAccumulator};
 use crate::dict::shared_dictionary_cache::SharedDictionaryCache;
-use crate::io::reader::Reader;
+use crate::encryption::Decryptor;
+use crate::file::encunit_index::{encunit_entries, find_encunit};
+use crate::file::footer::Statistics;
+use crate::io::{prefetch::PrefetchedChunks, reader::Reader};
+use crate::memory::{MemoryPool, MemoryReservation};
+use crate::reader::FileChunkCache;
 use crate::{common::ColumnIndexSequence, context::WASMReadingContext};
 use arrow::array::AsArray;
-use arrow_array::{Array, ArrayRef, LargeListArray, ListArray, StructArray};
+use arrow::compute::{cast, cast_with_options, concat, take, CastOptions};
+use arrow_array::{
+    types::Int32Type, Array, ArrayRef, FixedSizeListArray, LargeListArray, ListArray, MapArray,
+    StructArray, UInt64Array,
+};
 use arrow_buffer::{NullBuffer, OffsetBuffer, OffsetBufferBuilder, ScalarBuffer};
-use arrow_schema::{DataType, Field, FieldRef, Fields};
-use bytes::BytesMut;
+use arrow_schema::{DataType, Field, FieldRef, Fields, TimeUnit};
+use bytes::{Bytes, BytesMut};
 use fff_core::{
     errors::{Error, Result},
-    general_error,
+    general_error, nyi_err,
 };
 use fff_format::File::fff::flatbuf as fb;
 use flatbuffers::{ForwardsUOffset, VectorIter};
@@ -26,6 +37,23 @@ pub trait LogicalColDecoder {
     fn decode_batch(&mut self) -> Result<Vec<ArrayRef>>;
     /// Decode some rows out starting at row_id.
     fn decode_row_at(&mut self, row_id: usize, len: usize) -> Result<Vec<ArrayRef>>;
+    /// Gather the rows at the given sorted row ids in current row group into a single array.
+    /// The default implementation decodes the whole row group and applies the take kernel.
+    fn take_rows(&mut self, sorted_row_ids: &[u64]) -> Result<ArrayRef> {
+        let arrays = self.decode_batch()?;
+        let array = concat(
+            arrays
+                .iter()
+                .map(|a| a.as_ref())
+                .collect::<Vec<_>>()
+                .as_slice(),
+        )?;
+        Ok(take(
+            &array,
+            &UInt64Array::from(sorted_row_ids.to_vec()),
+            None,
+        )?)
+    }
 }
 
 /// A specific trait for testing select+proj performance of different nested implementation.
@@ -38,6 +66,88 @@ pub trait LogicalListStructNonNestedColDecoder {
     ) -> Result<Vec<ArrayRef>>;
 }
 
+/// How the logical decoder outputs timestamp columns.
+///
+/// Codecs, e.g., vortex or custom WASM ones, may round-trip timestamps with a different unit or
+/// time zone than the writer. Readers comparing columns across files written by different
+/// engines can ask for a single representation instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampNormalization {
+    /// Output timestamps with the unit and the time zone of the schema.
+    #[default]
+    Preserve,
+    /// Output timestamps as microseconds in UTC. Timestamps without a time zone are taken as UTC,
+    /// so the result does not depend on the local time zone. Nanoseconds are truncated, values out
+    /// of the range of microseconds are errors instead of nulls.
+    UtcMicros,
+}
+
+impl TimestampNormalization {
+    /// Type of the arrays output for a column of `data_type`.
+    pub fn output_type(&self, data_type: &DataType) -> DataType {
+        match (self, data_type) {
+            (Self::UtcMicros, DataType::Timestamp(_, _)) => {
+                DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+            }
+            (Self::UtcMicros, DataType::Dictionary(key_type, value_type)) => {
+                DataType::Dictionary(key_type.clone(), Box::new(self.output_type(value_type)))
+            }
+            _ => data_type.clone(),
+        }
+    }
+
+    pub(crate) fn normalize(&self, array: ArrayRef) -> Result<ArrayRef> {
+        let data_type = self.output_type(array.data_type());
+        if &data_type == array.data_type() {
+            return Ok(array);
+        }
+        let options = CastOptions {
+            safe: false,
+            ..Default::default()
+        };
+        Ok(cast_with_options(&array, &data_type, &options)?)
+    }
+}
+
+/// Options of the decoders of the physical columns, shared by all the columns of a read.
+#[derive(Clone, Copy, Default)]
+pub struct DecodeOptions<'a> {
+    /// If not None, the checksums of the chunks are verified.
+    pub checksum_type: Option<ChecksumType>,
+    /// Whether we verify that the decoded arrays have exactly as many rows as the chunk.
+    pub verify_decoded_length: bool,
+    /// Whether we verify the statistics of the chunks decoded in full against their values.
+    pub verify_statistics: bool,
+    /// Whether dictionary-encoded chunks are output as `DictionaryArray`s.
+    pub preserve_dictionary: bool,
+    /// Whether RLE EncUnits are output as `RunArray`s.
+    pub preserve_runs: bool,
+    pub timestamp_normalization: TimestampNormalization,
+    /// Chunks read along with the other chunks of their column family, if any.
+    pub prefetched: Option<&'a PrefetchedChunks>,
+    /// Decrypts the EncUnits of encrypted chunks, if the reader has keys.
+    pub decryptor: Option<&'a Decryptor>,
+    /// Caches the chunks read, verified and decrypted, if the reader has a chunk cache.
+    pub chunk_cache: Option<&'a FileChunkCache>,
+    /// Reserves the encoded chunks held by the decoders, if the reader has a memory pool.
+    pub memory_pool: Option<&'a Arc<dyn MemoryPool>>,
+}
+
+impl DecodeOptions<'_> {
+    /// The options of the columns nested in another, and of the validity and offsets of nested
+    /// columns. Statistics are only verified, dictionaries and runs only preserved and timestamps
+    /// only normalized for top-level columns, nested arrays keep their schema types.
+    fn nested(&self) -> Self {
+        Self {
+            verify_statistics: false,
+            preserve_dictionary: false,
+            preserve_runs: false,
+            timestamp_normalization: TimestampNormalization::Preserve,
+            ..*self
+        }
+    }
+}
+
 /// Decoder for a single physical column inside a file
 /// Currently used by both pritimive types and list's (validity + offsets)
 /// lifetime here is because data_encoder stores iter of FlatBuf which has lifetime 'a
@@ -48,60 +158,268 @@ pub struct PrimitiveColDecoder<'a, R> {
     primitive_type: DataType,
     wasm_context: Option<Arc<WASMReadingContext<R>>>,
     shared_dictionary_cache: &'a SharedDictionaryCache,
-    /// if checksum is not None, we will verify the checksum of the chunk
-    checksum_type: Option<ChecksumType>,
+    /// Index of the physical column, used in error messages.
+    column_index: u32,
+    options: DecodeOptions<'a>,
+    /// Reservation of the encoded chunk held by `chunk_decoder`, if the reader has a memory pool.
+    reservation: Option<MemoryReservation>,
+}
+
+impl<'a, R: Reader> PrimitiveColDecoder<'a, R> {
+    fn new(
+        r: &'a R,
+        chunks_meta_iter: VectorIter<'a, ForwardsUOffset<fb::Chunk<'a>>>,
+        primitive_type: DataType,
+        wasm_context: Option<Arc<WASMReadingContext<R>>>,
+        shared_dictionary_cache: &'a SharedDictionaryCache,
+        column_index: u32,
+        options: &DecodeOptions<'a>,
+    ) -> Self {
+        Self {
+            r,
+            chunk_decoder: None,
+            chunks_meta_iter,
+            primitive_type,
+            wasm_context,
+            shared_dictionary_cache,
+            column_index,
+            options: *options,
+            reservation: options
+                .memory_pool
+                .map(|pool| MemoryReservation::new(pool.clone(), format!("column {column_index}"))),
+        }
+    }
 }
 
 impl<R: Reader> PrimitiveColDecoder<'_, R> {
+    /// Allocate a buffer for `size` bytes of encoded data, replacing the chunk held so far in the
+    /// memory reservation.
+    fn alloc_chunk_buffer(&mut self, size: usize) -> Result<BytesMut> {
+        // The previous chunk is released before reserving the next one.
+        self.chunk_decoder = None;
+        if let Some(reservation) = &mut self.reservation {
+            reservation.try_resize(size)?;
+        }
+        Ok(BytesMut::zeroed(size))
+    }
+
+    /// Read from the prefetched chunks if they cover the range, from the reader otherwise.
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        if self
+            .options
+            .prefetched
+            .is_some_and(|prefetched| prefetched.read_exact_at(buf, offset))
+        {
+            return Ok(());
+        }
+        self.r.read_exact_at(buf, offset)
+    }
+
     /// Read a chunk from the reader
     /// IO and compute are sequential in this case. Separation is left for future work.
     fn read_chunk(&mut self, offset: u64, size: u32, checksum: Option<u64>) -> Result<BytesMut> {
-        let mut buf = BytesMut::zeroed(size as usize);
-        self.r.read_exact_at(&mut buf, offset)?;
-        if let Some(checksum_type) = &mut self.checksum_type {
-            let checksum = checksum.ok_or_else(|| {
-                general_error!(format!(
-                    "No checksum in column meta for chunk at offset {}",
-                    offset
-                ))
-            })?;
-            let computed_checksum = {
-                let mut checksum = create_checksum(checksum_type);
-                checksum.update(&buf);
-                checksum.finalize()
-            };
-            if checksum != computed_checksum {
+        trace_span!("read_chunk", bytes = size);
+        let mut buf = self.alloc_chunk_buffer(size as usize)?;
+        self.read_exact_at(&mut buf, offset)?;
+        // Retry from the reader, e.g., another replica, before failing the scan.
+        let mut attempt = 0;
+        while !self.verify_checksum(&buf, offset, checksum)? {
+            attempt += 1;
+            if !self.r.retry_read_exact_at(&mut buf, offset, attempt)? {
                 return Err(Error::General("Checksum verification failed".to_string()));
             }
         }
         Ok(buf)
     }
+
+    /// Whether `buf` matches the `checksum` of the chunk at `offset`. Always true when checksums
+    /// are not verified.
+    fn verify_checksum(&self, buf: &[u8], offset: u64, checksum: Option<u64>) -> Result<bool> {
+        let Some(checksum_type) = &self.options.checksum_type else {
+            return Ok(true);
+        };
+        let checksum = checksum.ok_or_else(|| {
+            general_error!(format!(
+                "No checksum in column meta for chunk at offset {}",
+                offset
+            ))
+        })?;
+        let mut computed = create_checksum(checksum_type);
+        computed.update(buf);
+        Ok(computed.finalize() == checksum)
+    }
+
+    /// Read the chunk of `chunk_meta`, verified and decrypted, from the chunk cache if it holds it.
+    /// The cache holds the chunks as read, so that each reader verifies and decrypts them with
+    /// its own settings and keys. Cached chunks failing verification are read again.
+    fn load_chunk(&mut self, chunk_meta: &fb::Chunk) -> Result<BytesMut> {
+        let cached = self
+            .chunk_cache
+            .and_then(|cache| cache.get(chunk_meta.offset()));
+        let mut buf = match cached {
+            Some(chunk)
+                if self.verify_checksum(&chunk, chunk_meta.offset(), chunk_meta.checksum())? =>
+            {
+                let mut buf = self.alloc_chunk_buffer(chunk.len())?;
+                buf.copy_from_slice(&chunk);
+                buf
+            }
+            _ => {
+                let buf = self.read_chunk(
+                    chunk_meta.offset(),
+                    chunk_meta.size_(),
+                    chunk_meta.checksum(),
+                )?;
+                if let Some(cache) = self.options.chunk_cache {
+                    cache.insert(chunk_meta.offset(), Bytes::copy_from_slice(&buf));
+                }
+                buf
+            }
+        };
+        self.decrypt(chunk_meta, 0, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Decrypt in place the EncUnits of `chunk_meta` read in `buf`, from the `first_encunit`-th.
+    /// Checksums are computed over the encrypted EncUnits, so this comes after verifying them.
+    fn decrypt(&self, chunk_meta: &fb::Chunk, first_encunit: usize, buf: &mut [u8]) -> Result<()> {
+        match (chunk_meta.encryption(), self.options.decryptor) {
+            (None, _) => Ok(()),
+            (Some(_), Some(decryptor)) => {
+                decryptor.decrypt_encunits(chunk_meta, first_encunit, buf)
+            }
+            (Some(_), None) => Err(general_error!(format!(
+                "Column {} is encrypted, read it with a key retriever",
+                self.column_index
+            ))),
+        }
+    }
+
+    /// Check that decoding the `chunk_ordinal`-th chunk produced the `expected` number of rows,
+    /// if enabled. A codec emitting a wrong length otherwise surfaces much later as an Arrow error.
+    fn check_decoded_length(
+        &self,
+        chunk_meta: &fb::Chunk,
+        chunk_ordinal: usize,
+        expected: usize,
+        decoded: usize,
+    ) -> Result<()> {
+        if !self.options.verify_decoded_length || decoded == expected {
+            return Ok(());
+        }
+        Err(general_error!(format!(
+            "Decoded {} rows but expected {} from chunk {} of column {} encoded with {}",
+            decoded,
+            expected,
+            chunk_ordinal,
+            self.column_index,
+            describe_codecs(chunk_meta)
+        )))
+    }
+
+    /// Check the null count and min/max statistics of the `chunk_ordinal`-th chunk against its
+    /// decoded `arrays`, if enabled. Stale or wrong statistics otherwise silently prune the chunk
+    /// from reads that should see it.
+    fn check_statistics(
+        &self,
+        chunk_meta: &fb::Chunk,
+        chunk_ordinal: usize,
+        arrays: &[ArrayRef],
+    ) -> Result<()> {
+        if !self.options.verify_statistics {
+            return Ok(());
+        }
+        let mut null_count = 0;
+        let mut min_max = MinMaxAccumulator::default();
+        for array in arrays {
+            null_count += array.logical_nulls().map_or(0, |nulls| nulls.null_count()) as u64;
+            // Preserved dictionaries and runs are accounted by the values of their rows.
+            let values = match array.data_type() {
+                DataType::Dictionary(_, _) => cast(array, &self.primitive_type)?,
+                DataType::RunEndEncoded(_, _) => array.as_run::<Int32Type>().values().clone(),
+                _ => array.clone(),
+            };
+            min_max.update(values.as_ref());
+        }
+        if let Some(stored) = chunk_meta.null_count() {
+            if stored != null_count {
+                return Err(general_error!(format!(
+                    "Chunk {chunk_ordinal} of column {} has {null_count} nulls but its \
+                     statistics say {stored}",
+                    self.column_index
+                )));
+            }
+        }
+        let Some(stored) = chunk_meta.statistics() else {
+            return Ok(());
+        };
+        match statistics_mismatch(&Statistics::from(&stored), min_max.finish(None).as_ref()) {
+            Some(mismatch) => Err(general_error!(format!(
+                "Statistics of chunk {chunk_ordinal} of column {} do not match its values: \
+                 {mismatch}",
+                self.column_index
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Describe the distinct encodings of the EncUnits in a chunk, e.g., `CUSTOM_WASM(wasm_id=1)`.
+fn describe_codecs(chunk_meta: &fb::Chunk) -> String {
+    let mut codecs: Vec<String> = vec![];
+    for encunit in chunk_meta.encunits().into_iter().flatten() {
+        let codec = match encunit.encoding() {
+            Some(encoding) => match encoding.wasm_encoding() {
+                Some(wasm_encoding) => {
+                    format!(
+                        "{:?}(wasm_id={})",
+                        encoding.type_(),
+                        wasm_encoding.wasm_id()
+                    )
+                }
+                None => format!("{:?}", encoding.type_()),
+            },
+            None => "unknown encoding".to_string(),
+        };
+        if !codecs.contains(&codec) {
+            codecs.push(codec);
+        }
+    }
+    codecs.join(", ")
 }
 
 impl<R: Reader> LogicalColDecoder for PrimitiveColDecoder<'_, R> {
     fn decode_batch(&mut self) -> Result<Vec<ArrayRef>> {
         let mut arrays = vec![];
+        let mut chunk_ordinal = 0;
         while let Some(chunk_meta) = self.chunks_meta_iter.next() {
-            let encoded_chunk_buf = self.read_chunk(
-                chunk_meta.offset(),
-                chunk_meta.size_(),
-                chunk_meta.checksum(),
-            )?;
+            let encoded_chunk_buf = self.load_chunk(&chunk_meta)?;
             self.chunk_decoder = Some(create_physical_decoder::<R>(
+                &chunk_meta,
                 chunk_meta
                     .encunits()
                     .ok_or_else(|| general_error!("No chunks in column meta"))?
                     .iter(),
-                chunk_meta.encoding_type(),
-                chunk_meta.encoding_as_shared_dictionary(),
                 &self.primitive_type,
                 encoded_chunk_buf,
                 self.wasm_context.as_ref().map(Arc::clone),
                 Some(self.shared_dictionary_cache),
+                &self.options,
             )?);
+            let mut decoded = 0;
+            let first_array = arrays.len();
             while let Some(array) = self.chunk_decoder.as_mut().unwrap().decode_batch()? {
-                arrays.push(array);
+                decoded += array.len();
+                arrays.push(self.options.timestamp_normalization.normalize(array)?);
             }
+            self.check_decoded_length(
+                &chunk_meta,
+                chunk_ordinal,
+                chunk_meta.num_rows() as usize,
+                decoded,
+            )?;
+            self.check_statistics(&chunk_meta, chunk_ordinal, &arrays[first_array..])?;
+            chunk_ordinal += 1;
         }
         Ok(arrays)
     }
@@ -109,12 +427,14 @@ impl<R: Reader> LogicalColDecoder for PrimitiveColDecoder<'_, R> {
         let mut arrays = vec![];
         let mut cur_row = 0; // FIXME: Not correct if we have muliple row groups
         let mut remaining = len;
+        let mut chunk_ordinal = 0;
         while let Some(chunk_meta) = self.chunks_meta_iter.next() {
             if remaining == 0 {
                 break;
             }
             if cur_row + chunk_meta.num_rows() as usize <= row_id {
                 cur_row += chunk_meta.num_rows() as usize;
+                chunk_ordinal += 1;
                 continue;
             }
             let mut to_decode = std::cmp::min(
@@ -122,46 +442,142 @@ impl<R: Reader> LogicalColDecoder for PrimitiveColDecoder<'_, R> {
                 remaining,
             );
 
-            let encoded_chunk_buf = self.read_chunk(
-                chunk_meta.offset(),
-                chunk_meta.size_(),
-                chunk_meta.checksum(),
-            )?;
-            // println!(
-            //     "read chunk at offset {} with size {}",
-            //     chunk_meta.offset(),
-            //     chunk_meta.size_()
-            // );
+            let encunits = chunk_meta
+                .encunits()
+                .ok_or_else(|| general_error!("No chunks in column meta"))?;
+            let mut encunit_iter = encunits.iter();
+            let mut row_in_chunk = row_id - cur_row;
+            // Without dictionary nor checksum to verify, only read the EncUnits holding the rows,
+            // found by binary search. With a chunk cache, whole chunks are read to be cached.
+            let encoded_chunk_buf = if self.options.checksum_type.is_none()
+                && self.options.chunk_cache.is_none()
+                && chunk_meta.encoding_type() == fb::DictionaryEncoding::NoDictionary
+            {
+                let entries = encunit_entries(encunits.iter().map(|e| (e.num_rows(), e.size_())));
+                let first = find_encunit(&entries, row_in_chunk as u64).ok_or_else(|| {
+                    general_error!(format!(
+                        "Row {row_in_chunk} not found in the EncUnits of chunk {chunk_ordinal}"
+                    ))
+                })?;
+                let last = find_encunit(&entries, (row_in_chunk + to_decode - 1) as u64)
+                    .unwrap_or(entries.len() - 1);
+                let (start, end) = (&entries[first], &entries[last]);
+                if first > 0 {
+                    encunit_iter.nth(first - 1);
+                }
+                row_in_chunk -= start.first_row as usize;
+                let mut buf =
+                    self.alloc_chunk_buffer((end.offset + end.size - start.offset) as usize)?;
+                self.read_exact_at(&mut buf, chunk_meta.offset() + start.offset as u64)?;
+                self.decrypt(&chunk_meta, first, &mut buf)?;
+                buf
+            } else {
+                self.load_chunk(&chunk_meta)?
+            };
             self.chunk_decoder = Some(create_physical_decoder::<R>(
-                chunk_meta
-                    .encunits()
-                    .ok_or_else(|| general_error!("No chunks in column meta"))?
-                    .iter(),
-                chunk_meta.encoding_type(),
-                chunk_meta.encoding_as_shared_dictionary(),
+                &chunk_meta,
+                encunit_iter,
                 &self.primitive_type,
                 encoded_chunk_buf,
                 self.wasm_context.as_ref().map(Arc::clone),
                 Some(self.shared_dictionary_cache),
+                &self.options,
             )?);
+            let expected = to_decode;
             let mut decoded = 0;
             while let Some(array) = self
                 .chunk_decoder
                 .as_mut()
                 .unwrap()
-                .decode_row_at(row_id - cur_row, to_decode)?
+                .decode_row_at(row_in_chunk, to_decode)?
             {
+                if array.len() > to_decode {
+                    self.check_decoded_length(
+                        &chunk_meta,
+                        chunk_ordinal,
+                        expected,
+                        decoded + array.len(),
+                    )?;
+                }
                 to_decode -= array.len();
                 decoded += array.len();
-                arrays.push(array);
+                arrays.push(self.options.timestamp_normalization.normalize(array)?);
                 if to_decode == 0 {
                     break;
                 }
             }
+            self.check_decoded_length(&chunk_meta, chunk_ordinal, expected, decoded)?;
+            chunk_ordinal += 1;
             remaining -= decoded;
         }
         Ok(arrays)
     }
+
+    fn take_rows(&mut self, sorted_row_ids: &[u64]) -> Result<ArrayRef> {
+        let mut arrays = vec![];
+        let mut cur_row = 0u64;
+        let mut pos = 0;
+        let mut chunk_ordinal = 0;
+        while pos < sorted_row_ids.len() {
+            let Some(chunk_meta) = self.chunks_meta_iter.next() else {
+                break;
+            };
+            let end_row = cur_row + chunk_meta.num_rows() as u64;
+            let start_pos = pos;
+            while pos < sorted_row_ids.len() && sorted_row_ids[pos] < end_row {
+                pos += 1;
+            }
+            // Chunks without any selected row are not even read.
+            if pos > start_pos {
+                let encoded_chunk_buf = self.load_chunk(&chunk_meta)?;
+                self.chunk_decoder = Some(create_physical_decoder::<R>(
+                    &chunk_meta,
+                    chunk_meta
+                        .encunits()
+                        .ok_or_else(|| general_error!("No chunks in column meta"))?
+                        .iter(),
+                    &self.primitive_type,
+                    encoded_chunk_buf,
+                    self.wasm_context.as_ref().map(Arc::clone),
+                    Some(self.shared_dictionary_cache),
+                    &self.options,
+                )?);
+                let row_ids_in_chunk = sorted_row_ids[start_pos..pos]
+                    .iter()
+                    .map(|row_id| row_id - cur_row)
+                    .collect::<Vec<_>>();
+                if let Some(array) = self
+                    .chunk_decoder
+                    .as_mut()
+                    .unwrap()
+                    .take_rows(&row_ids_in_chunk)?
+                {
+                    self.check_decoded_length(
+                        &chunk_meta,
+                        chunk_ordinal,
+                        row_ids_in_chunk.len(),
+                        array.len(),
+                    )?;
+                    arrays.push(self.options.timestamp_normalization.normalize(array)?);
+                }
+            }
+            cur_row = end_row;
+            chunk_ordinal += 1;
+        }
+        if pos < sorted_row_ids.len() {
+            return Err(Error::IndexOutOfBound(
+                sorted_row_ids[pos] as usize,
+                cur_row as usize,
+            ));
+        }
+        Ok(concat(
+            arrays
+                .iter()
+                .map(|a| a.as_ref())
+                .collect::<Vec<_>>()
+                .as_slice(),
+        )?)
+    }
 }
 
 /// Decoder for List column
@@ -205,6 +621,20 @@ impl<R: Reader> LogicalColDecoder for ListColDecoder<'_, R> {
                         nulls,
                     )) as Arc<dyn Array>);
                 }
+                // Maps are stored like Lists of their entries.
+                DataType::Map(entries, sorted) => {
+                    let offsets: ScalarBuffer<i32> =
+                        v_o.as_list::<i32>().to_data().buffers()[0].clone().into();
+                    let offsets = OffsetBuffer::new(offsets);
+                    let nulls = v_o.as_list::<i32>().nulls().cloned();
+                    res.push(Arc::new(MapArray::try_new(
+                        field_to_view(entries.clone()),
+                        offsets,
+                        val.as_struct().clone(),
+                        nulls,
+                        *sorted,
+                    )?) as Arc<dyn Array>);
+                }
                 _ => unreachable!(),
             }
         }
@@ -216,6 +646,100 @@ impl<R: Reader> LogicalColDecoder for ListColDecoder<'_, R> {
     }
 }
 
+/// Decoder for FixedSizeList column
+/// The values of all the lists are stored as a single child column, `size` per list.
+pub struct FixedSizeListColDecoder<'a, R> {
+    child: FieldRef,
+    size: i32,
+    validity_decoder: PrimitiveColDecoder<'a, R>,
+    values_decoder: Box<dyn LogicalColDecoder + 'a>,
+}
+
+impl<R: Reader> FixedSizeListColDecoder<'_, R> {
+    fn to_array(&self, validity: &[ArrayRef], values: &[ArrayRef]) -> Result<ArrayRef> {
+        let validity = concat(&validity.iter().map(|a| a.as_ref()).collect::<Vec<_>>())?;
+        let values = concat(&values.iter().map(|a| a.as_ref()).collect::<Vec<_>>())?;
+        let nulls = NullBuffer::new(validity.as_boolean().values().clone());
+        Ok(Arc::new(FixedSizeListArray::try_new(
+            field_to_view(self.child.clone()),
+            self.size,
+            values,
+            (nulls.null_count() > 0).then_some(nulls),
+        )?))
+    }
+}
+
+impl<R: Reader> LogicalColDecoder for FixedSizeListColDecoder<'_, R> {
+    fn decode_batch(&mut self) -> Result<Vec<ArrayRef>> {
+        let validity = self.validity_decoder.decode_batch()?;
+        let values = self.values_decoder.decode_batch()?;
+        if validity.is_empty() {
+            return Ok(vec![]);
+        }
+        let array = self.to_array(&validity, &values)?;
+        // Split like the validity, whose EncUnits hold as many rows as the sibling columns.
+        let mut offset = 0;
+        Ok(validity
+            .iter()
+            .map(|v| {
+                let slice = array.slice(offset, v.len());
+                offset += v.len();
+                slice
+            })
+            .collect())
+    }
+
+    fn decode_row_at(&mut self, row_id: usize, len: usize) -> Result<Vec<ArrayRef>> {
+        let size = self.size as usize;
+        let validity = self.validity_decoder.decode_row_at(row_id, len)?;
+        let values = self
+            .values_decoder
+            .decode_row_at(row_id * size, len * size)?;
+        Ok(vec![self.to_array(&validity, &values)?])
+    }
+
+    fn take_rows(&mut self, sorted_row_ids: &[u64]) -> Result<ArrayRef> {
+        let size = self.size as u64;
+        let validity = self.validity_decoder.take_rows(sorted_row_ids)?;
+        let value_ids = sorted_row_ids
+            .iter()
+            .flat_map(|row_id| row_id * size..(row_id + 1) * size)
+            .collect::<Vec<_>>();
+        let values = self.values_decoder.take_rows(&value_ids)?;
+        self.to_array(&[validity], &[values])
+    }
+}
+
+/// Decoder of a flat column outputting the type of the schema, whether the column is stored as
+/// another physical type or the codecs changed it, see
+/// [`physical_type`](crate::common::physical_type).
+pub struct PhysicalTypeColDecoder<'a> {
+    data_type: DataType,
+    inner: Box<dyn LogicalColDecoder + 'a>,
+}
+
+impl LogicalColDecoder for PhysicalTypeColDecoder<'_> {
+    fn decode_batch(&mut self) -> Result<Vec<ArrayRef>> {
+        self.inner
+            .decode_batch()?
+            .into_iter()
+            .map(|array| from_physical(array, &self.data_type))
+            .collect()
+    }
+
+    fn decode_row_at(&mut self, row_id: usize, len: usize) -> Result<Vec<ArrayRef>> {
+        self.inner
+            .decode_row_at(row_id, len)?
+            .into_iter()
+            .map(|array| from_physical(array, &self.data_type))
+            .collect()
+    }
+
+    fn take_rows(&mut self, sorted_row_ids: &[u64]) -> Result<ArrayRef> {
+        from_physical(self.inner.take_rows(sorted_row_ids)?, &self.data_type)
+    }
+}
+
 /// A custom experimental ListStruct(non_nest) decoder with Offsets pushdown for List.
 /// Will only be enabled with feature = "list-offsets-pushdown"
 pub struct OffsetPushdownListStructColDecoder<'a, R> {
@@ -419,6 +943,26 @@ impl<R: Reader> LogicalColDecoder for StructColDecoder<'_, R> {
     fn decode_row_at(&mut self, _row_id: usize, _len: usize) -> Result<Vec<ArrayRef>> {
         todo!()
     }
+
+    /// Gather the rows from the validity and each child, so that flat children only read the
+    /// EncUnits covering them. The validity is written for every row, see `extract_validity`.
+    fn take_rows(&mut self, sorted_row_ids: &[u64]) -> Result<ArrayRef> {
+        let validity = self.validity_decoder.take_rows(sorted_row_ids)?;
+        let children = self
+            .children
+            .iter_mut()
+            .map(|c| c.take_rows(sorted_row_ids))
+            .collect::<Result<Vec<_>>>()?;
+        let nulls = NullBuffer::new(validity.as_boolean().values().clone());
+        Ok(Arc::new(StructArray::new(
+            self.fields
+                .iter()
+                .map(|f| field_to_view(f.clone()))
+                .collect(),
+            children,
+            (nulls.null_count() > 0).then_some(nulls),
+        )))
+    }
 }
 
 /// Create a LogicalListStructNonNestedColDecoder
@@ -451,11 +995,10 @@ pub fn create_list_struct_decoder<'a, R: Reader>(
                     if i < fields.len() {
                         loop {
                             // println!("create decoder for index {}", column_index);
-                            children.push(PrimitiveColDecoder {
+                            children.push(PrimitiveColDecoder::new(
                                 r,
-                                chunk_decoder: None,
                                 chunks_meta_iter,
-                                primitive_type: {
+                                {
                                     match field.data_type() {
                                         DataType::List(child) => DataType::List(
                                             Field::new_struct(
@@ -476,10 +1019,11 @@ pub fn create_list_struct_decoder<'a, R: Reader>(
                                         _ => unreachable!(),
                                     }
                                 },
-                                wasm_context: wasm_context.as_ref().map(Arc::clone),
+                                wasm_context.as_ref().map(Arc::clone),
                                 shared_dictionary_cache,
-                                checksum_type: None,
-                            });
+                                column_index,
+                                &DecodeOptions::default(),
+                            ));
                             i += 1;
                             if i == fields.len() {
                                 break;
@@ -499,21 +1043,20 @@ pub fn create_list_struct_decoder<'a, R: Reader>(
                     // create ordinary list struct decoder
                     Ok(Box::new(ListStructColDecoder {
                         field: Arc::clone(&field),
-                        validity_offsets_decoder: PrimitiveColDecoder {
+                        validity_offsets_decoder: PrimitiveColDecoder::new(
                             r,
-                            chunk_decoder: None,
                             chunks_meta_iter,
-                            primitive_type: field.data_type().clone(),
-                            wasm_context: wasm_context.as_ref().map(Arc::clone),
+                            field.data_type().clone(),
+                            wasm_context.as_ref().map(Arc::clone),
                             shared_dictionary_cache,
-                            checksum_type: None,
-                        },
+                            column_index,
+                            &DecodeOptions::default(),
+                        ),
                         children: StructOfNonNestColDecoder {
                             fields: fields.clone(),
-                            struct_validity_decoder: PrimitiveColDecoder {
+                            struct_validity_decoder: PrimitiveColDecoder::new(
                                 r,
-                                chunk_decoder: None,
-                                chunks_meta_iter: {
+                                {
                                     column_index = column_idx.next_column_index();
                                     column_meta = column_metas.get(column_index as usize).unwrap();
                                     let chunks_meta_iter = column_meta
@@ -524,35 +1067,38 @@ pub fn create_list_struct_decoder<'a, R: Reader>(
                                         .iter();
                                     chunks_meta_iter
                                 },
-                                primitive_type: DataType::Boolean,
-                                wasm_context: wasm_context.as_ref().map(Arc::clone),
+                                DataType::Boolean,
+                                wasm_context.as_ref().map(Arc::clone),
                                 shared_dictionary_cache,
-                                checksum_type: None,
-                            },
+                                column_index,
+                                &DecodeOptions::default(),
+                            ),
                             children: fields
                                 .iter()
-                                .map(|f| PrimitiveColDecoder {
-                                    r,
-                                    chunk_decoder: None,
-                                    chunks_meta_iter: {
-                                        column_index = column_idx.next_column_index();
-                                        column_meta =
-                                            column_metas.get(column_index as usize).unwrap();
-                                        let chunks_meta_iter = column_meta
-                                            .column_chunks()
-                                            .ok_or_else(|| {
-                                                Error::General(
-                                                    "No chunks in column meta".to_string(),
-                                                )
-                                            })
-                                            .unwrap()
-                                            .iter();
-                                        chunks_meta_iter
-                                    },
-                                    primitive_type: f.data_type().clone(),
-                                    wasm_context: wasm_context.as_ref().map(Arc::clone),
-                                    shared_dictionary_cache,
-                                    checksum_type: None,
+                                .map(|f| {
+                                    PrimitiveColDecoder::new(
+                                        r,
+                                        {
+                                            column_index = column_idx.next_column_index();
+                                            column_meta =
+                                                column_metas.get(column_index as usize).unwrap();
+                                            let chunks_meta_iter = column_meta
+                                                .column_chunks()
+                                                .ok_or_else(|| {
+                                                    Error::General(
+                                                        "No chunks in column meta".to_string(),
+                                                    )
+                                                })
+                                                .unwrap()
+                                                .iter();
+                                            chunks_meta_iter
+                                        },
+                                        f.data_type().clone(),
+                                        wasm_context.as_ref().map(Arc::clone),
+                                        shared_dictionary_cache,
+                                        column_index,
+                                        &DecodeOptions::default(),
+                                    )
                                 })
                                 .collect(),
                         },
@@ -572,7 +1118,7 @@ pub fn create_logical_decoder<'a, R: Reader>(
     column_idx: &mut ColumnIndexSequence,
     wasm_context: Option<Arc<WASMReadingContext<R>>>,
     shared_dictionary_cache: &'a SharedDictionaryCache,
-    checksum_type: Option<ChecksumType>,
+    options: &DecodeOptions<'a>,
 ) -> Result<Box<dyn LogicalColDecoder + 'a>> {
     // match field.data_type() {
     //     DataType::List(child) | DataType::LargeList(child)
@@ -610,31 +1156,39 @@ pub fn create_logical_decoder<'a, R: Reader>(
         .ok_or_else(|| Error::General("No chunks in column meta".to_string()))?
         .iter();
     match field.data_type() {
-        non_nest_types!() => {
-            let data_type = field.data_type().clone();
-            Ok(Box::new(PrimitiveColDecoder {
+        data_type if matches!(physical_type(data_type), non_nest_types!()) => {
+            let primitive_type = physical_type(data_type);
+            let decoder = Box::new(PrimitiveColDecoder::new(
                 r,
-                chunk_decoder: None,
                 chunks_meta_iter,
-                primitive_type: data_type,
-                wasm_context: wasm_context.map(|wasm_context| Arc::clone(&wasm_context)),
+                primitive_type,
+                wasm_context.map(|wasm_context| Arc::clone(&wasm_context)),
                 shared_dictionary_cache,
-                checksum_type,
+                column_index,
+                options,
+            ));
+            // Codecs may not round-trip the type of the schema even if stored as is.
+            Ok(Box::new(PhysicalTypeColDecoder {
+                data_type: options.timestamp_normalization.output_type(data_type),
+                inner: decoder,
             }))
         }
-        DataType::List(child) | DataType::LargeList(child) => {
+        DataType::List(child) | DataType::LargeList(child) | DataType::Map(child, _) => {
             Ok(Box::new(ListColDecoder {
                 field: Arc::clone(&field),
-                validity_offsets_decoder: PrimitiveColDecoder {
+                validity_offsets_decoder: PrimitiveColDecoder::new(
                     r,
-                    chunk_decoder: None,
                     chunks_meta_iter,
                     // CAUTION: here we create a list primitive decoder but only output validity and offsets.
-                    primitive_type: field.data_type().clone(),
-                    wasm_context: wasm_context.as_ref().map(Arc::clone),
+                    match field.data_type() {
+                        DataType::Map(entries, _) => DataType::List(entries.clone()),
+                        data_type => data_type.clone(),
+                    },
+                    wasm_context.as_ref().map(Arc::clone),
                     shared_dictionary_cache,
-                    checksum_type,
-                },
+                    column_index,
+                    &options.nested(),
+                ),
                 values_decoder: create_logical_decoder(
                     r,
                     Arc::clone(child),
@@ -642,22 +1196,22 @@ pub fn create_logical_decoder<'a, R: Reader>(
                     column_idx,
                     wasm_context.map(|wasm_context| Arc::clone(&wasm_context)),
                     shared_dictionary_cache,
-                    checksum_type,
+                    &options.nested(),
                 )?,
             }))
         }
         DataType::Struct(child_fields) => Ok(Box::new(StructColDecoder {
             fields: child_fields.clone(),
             // validity decoder for struct is a primitive decoder for Boolean
-            validity_decoder: PrimitiveColDecoder {
+            validity_decoder: PrimitiveColDecoder::new(
                 r,
-                chunk_decoder: None,
                 chunks_meta_iter,
-                primitive_type: DataType::Boolean,
-                wasm_context: wasm_context.as_ref().map(Arc::clone),
+                DataType::Boolean,
+                wasm_context.as_ref().map(Arc::clone),
                 shared_dictionary_cache,
-                checksum_type,
-            },
+                column_index,
+                &options.nested(),
+            ),
             children: child_fields
                 .iter()
                 .map(|f| {
@@ -668,12 +1222,34 @@ pub fn create_logical_decoder<'a, R: Reader>(
                         column_idx,
                         wasm_context.as_ref().map(Arc::clone),
                         shared_dictionary_cache,
-                        checksum_type,
+                        &options.nested(),
                     )
                 })
                 .collect::<Result<Vec<_>>>()?,
         })),
-        _ => todo!("Implement logical encoding for field {}", field),
+        DataType::FixedSizeList(child, size) => Ok(Box::new(FixedSizeListColDecoder {
+            child: child.clone(),
+            size: *size,
+            validity_decoder: PrimitiveColDecoder::new(
+                r,
+                chunks_meta_iter,
+                DataType::Boolean,
+                wasm_context.as_ref().map(Arc::clone),
+                shared_dictionary_cache,
+                column_index,
+                &options.nested(),
+            ),
+            values_decoder: create_logical_decoder(
+                r,
+                Arc::clone(child),
+                column_metas,
+                column_idx,
+                wasm_context,
+                shared_dictionary_cache,
+                &options.nested(),
+            )?,
+        })),
+        _ => nyi_err!(format!("Logical decoding of field {}", field)),
     }
 }
 
@@ -695,13 +1271,20 @@ pub fn advance_column_index(field: FieldRef, column_idx: &mut ColumnIndexSequenc
 }
 
 fn field_to_view(field: FieldRef) -> FieldRef {
-    match field.data_type() {
-        DataType::Utf8 | DataType::LargeUtf8 => {
-            Field::new(field.name(), DataType::Utf8View, field.is_nullable()).into()
+    let data_type = match field.data_type() {
+        DataType::Utf8 | DataType::LargeUtf8 => DataType::Utf8View,
+        DataType::Binary | DataType::LargeBinary => DataType::BinaryView,
+        // Nested fields are output with the view types of their descendants.
+        DataType::List(child) => DataType::List(field_to_view(child.clone())),
+        DataType::LargeList(child) => DataType::LargeList(field_to_view(child.clone())),
+        DataType::FixedSizeList(child, size) => {
+            DataType::FixedSizeList(field_to_view(child.clone()), *size)
         }
-        DataType::Binary | DataType::LargeBinary => {
-            Field::new(field.name(), DataType::BinaryView, field.is_nullable()).into()
+        DataType::Map(entries, sorted) => DataType::Map(field_to_view(entries.clone()), *sorted),
+        DataType::Struct(fields) => {
+            DataType::Struct(fields.iter().map(|f| field_to_view(f.clone())).collect())
         }
-        _ => field,
-    }
+        _ => return field,
+    };
+    Field::new(field.name(), data_type, field.is_nullable()).into()
 }
@@ -1,17 +1,34 @@
-use std::sync::Arc;
+use std::{collections::VecDeque, ops::Range, sync::Arc};
 
 use crate::{
     context::WASMReadingContext, dict::shared_dictionary_cache::SharedDictionaryCache,
     io::reader::Reader,
 };
-use arrow_array::{Array, ArrayRef, UInt16Array, UInt32Array, UInt64Array, UInt8Array};
+use arrow::array::AsArray;
+use arrow::compute::{cast_with_options, CastOptions};
+use arrow_array::{
+    new_null_array, types::UInt32Type, Array, ArrayRef, DictionaryArray, UInt16Array, UInt32Array,
+    UInt64Array, UInt8Array,
+};
 use arrow_schema::{DataType, TimeUnit};
 use bytes::BytesMut;
-use fff_core::{errors::Result, general_error, non_nest_types, nyi_err};
+use fff_core::{
+    errors::{Error, Result},
+    general_error, non_nest_types, nyi_err,
+};
 use fff_format::File::fff::flatbuf as fb;
 use flatbuffers::{ForwardsUOffset, VectorIter};
 
-use super::encunit::create_encunit_decoder;
+use super::{
+    encunit::{create_encunit_decoder, decode_wasm_batch, EncUnitDecoder},
+    logical::DecodeOptions,
+};
+
+/// EncUnits of at most this many rows are decoded together by a single WASM call when their
+/// module supports it, see [`decode_wasm_batch`].
+const MAX_BATCHED_ENCUNIT_ROWS: u32 = 4096;
+/// Maximum number of EncUnits decoded by a single WASM call.
+const MAX_BATCHED_ENCUNITS: usize = 64;
 
 /// Stateful Chunk Decoder that will decode a EncUnit at a time.
 pub trait ChunkDecoder {
@@ -21,6 +38,84 @@ pub trait ChunkDecoder {
 
     /// Decode out the EncUnit at the given row_id_in_chunk in this Chunk.
     fn decode_row_at(&mut self, row_id_in_chunk: usize, len: usize) -> Result<Option<ArrayRef>>;
+
+    /// Gather the rows at the given sorted row ids in this Chunk.
+    /// Every EncUnit is decoded and only the selected rows are materialized with the take kernel.
+    /// Return None if no row id falls into this Chunk.
+    fn take_rows(&mut self, sorted_row_ids_in_chunk: &[u64]) -> Result<Option<ArrayRef>> {
+        let mut cur = 0u64;
+        let mut pos = 0;
+        let mut arrays = vec![];
+        while pos < sorted_row_ids_in_chunk.len() {
+            let Some(array) = self.decode_batch()? else {
+                break;
+            };
+            let end = cur + array.len() as u64;
+            let start_pos = pos;
+            while pos < sorted_row_ids_in_chunk.len() && sorted_row_ids_in_chunk[pos] < end {
+                pos += 1;
+            }
+            if pos > start_pos {
+                let indices = UInt64Array::from_iter_values(
+                    sorted_row_ids_in_chunk[start_pos..pos]
+                        .iter()
+                        .map(|row_id| row_id - cur),
+                );
+                arrays.push(arrow::compute::take(&array, &indices, None)?);
+            }
+            cur = end;
+        }
+        concat_gathered(arrays)
+    }
+}
+
+/// Gather the rows at `sorted_row_ids` from an EncUnit starting at row `first_row`.
+/// Decoders supporting it only decode the selected rows, e.g., the built-in ones with
+/// [`EncUnitDecoder::take`], or the selected ranges, e.g., WASM modules with `read_batch_ffi`.
+fn take_from_encunit(
+    decoder: &dyn EncUnitDecoder,
+    sorted_row_ids: &[u64],
+    first_row: u64,
+) -> Result<ArrayRef> {
+    let indexes = sorted_row_ids
+        .iter()
+        .map(|row_id| (row_id - first_row) as u32)
+        .collect::<Vec<_>>();
+    match decoder.take(&indexes) {
+        Err(Error::NYI(_)) => {}
+        res => return res,
+    }
+    if sorted_row_ids.windows(2).all(|w| w[0] < w[1]) {
+        let mut ranges: Vec<Range<usize>> = vec![];
+        for &row_id in sorted_row_ids {
+            let i = (row_id - first_row) as usize;
+            match ranges.last_mut() {
+                Some(range) if range.end == i => range.end += 1,
+                _ => ranges.push(i..i + 1),
+            }
+        }
+        match decoder.select(&ranges) {
+            Err(Error::NYI(_)) => {}
+            res => return res,
+        }
+    }
+    let indices = UInt32Array::from(indexes);
+    Ok(arrow::compute::take(&decoder.decode()?, &indices, None)?)
+}
+
+/// Concatenate the arrays gathered from the EncUnits of a Chunk.
+fn concat_gathered(arrays: Vec<ArrayRef>) -> Result<Option<ArrayRef>> {
+    Ok(match arrays.len() {
+        0 => None,
+        1 => arrays.into_iter().next(),
+        _ => Some(arrow::compute::concat(
+            arrays
+                .iter()
+                .map(|a| a.as_ref())
+                .collect::<Vec<_>>()
+                .as_slice(),
+        )?),
+    })
 }
 
 /// The column data is not encoded in dictionary, but Plain.
@@ -33,6 +128,14 @@ pub struct NoDictColDecoder<'a, R> {
     /// The data type of the column.
     data_type: DataType,
     wasm_context: Option<Arc<WASMReadingContext<R>>>,
+    /// See [`crate::compression::compress_with_trained_dictionary`].
+    compression_dictionary: Option<&'a [u8]>,
+    /// The chunk has no nulls according to the footer, so validity is not materialized.
+    skip_validity: bool,
+    /// EncUnits decoded ahead by a batched WASM call, returned by the next `decode_batch` calls.
+    decoded: VecDeque<ArrayRef>,
+    /// Output the RLE EncUnits as `RunArray`s instead of expanding their runs.
+    preserve_runs: bool,
 }
 
 impl<'a, R: Reader> NoDictColDecoder<'a, R> {
@@ -47,31 +150,97 @@ impl<'a, R: Reader> NoDictColDecoder<'a, R> {
             encoded_chunk_buf,
             data_type,
             wasm_context,
+            compression_dictionary: None,
+            skip_validity: false,
+            decoded: VecDeque::new(),
+            preserve_runs: false,
+        }
+    }
+
+    pub fn with_skip_validity(mut self, skip_validity: bool) -> Self {
+        self.skip_validity = skip_validity;
+        self
+    }
+
+    pub fn with_preserve_runs(mut self, preserve_runs: bool) -> Self {
+        self.preserve_runs = preserve_runs;
+        self
+    }
+
+    pub fn with_compression_dictionary(mut self, compression_dictionary: Option<&'a [u8]>) -> Self {
+        self.compression_dictionary = compression_dictionary;
+        self
+    }
+
+    fn create_decoder(&mut self, encunit: fb::EncUnit) -> Result<Box<dyn EncUnitDecoder>> {
+        let data = self.encoded_chunk_buf.split_to(encunit.size_() as usize);
+        create_encunit_decoder(
+            encunit,
+            data.freeze(),
+            self.data_type.clone(),
+            self.wasm_context.as_ref().map(Arc::clone),
+            self.skip_validity,
+            self.compression_dictionary,
+        )
+    }
+
+    /// Decode the small EncUnits following the one of `first` in the same WASM call, and queue
+    /// them to be returned by the next `decode_batch` calls.
+    fn decode_wasm_ahead(&mut self, first: Box<dyn EncUnitDecoder>) -> Result<Option<ArrayRef>> {
+        let mut decoders = vec![first];
+        while decoders.len() < MAX_BATCHED_ENCUNITS {
+            match self.encunit_iter.clone().next() {
+                Some(encunit) if encunit.num_rows() <= MAX_BATCHED_ENCUNIT_ROWS => {
+                    self.encunit_iter.next();
+                    decoders.push(self.create_decoder(encunit)?);
+                }
+                _ => break,
+            }
         }
+        // The following EncUnits may use another module, or no module at all.
+        let batched = decoders
+            .iter()
+            .map_while(|decoder| {
+                decoder.as_wasm().filter(|wasm| {
+                    wasm.supports_batch()
+                        && decoders[0]
+                            .as_wasm()
+                            .is_some_and(|first| first.shares_runtime(wasm))
+                })
+            })
+            .collect::<Vec<_>>();
+        let num_batched = batched.len();
+        self.decoded.extend(decode_wasm_batch(&batched)?);
+        for decoder in &decoders[num_batched..] {
+            self.decoded.push_back(decoder.decode()?);
+        }
+        Ok(self.decoded.pop_front())
     }
 }
 
 impl<R: Reader> ChunkDecoder for NoDictColDecoder<'_, R> {
     fn decode_batch(&mut self) -> Result<Option<ArrayRef>> {
+        if let Some(array) = self.decoded.pop_front() {
+            return Ok(Some(array));
+        }
         let encunit = self.encunit_iter.next();
         if encunit.is_none() {
             return Ok(None);
         }
         let encblock_fb = encunit.unwrap();
-
-        let data = self
-            .encoded_chunk_buf
-            .split_to(encblock_fb.size_() as usize);
-        let decoder = create_encunit_decoder(
-            encblock_fb.encoding().unwrap(),
-            encblock_fb.compression(),
-            data.freeze(),
-            encblock_fb.num_rows() as u64,
-            self.data_type.clone(),
-            self.wasm_context
-                .as_ref()
-                .map(Arc::clone),
-        )?;
+        let decoder = self.create_decoder(encblock_fb)?;
+        if self.preserve_runs {
+            match decoder.decode_runs() {
+                Err(Error::NYI(_)) => {}
+                res => return res.map(Some),
+            }
+        }
+        // Small EncUnits of WASM encodings are decoded several at a time to amortize the calls.
+        if encblock_fb.num_rows() <= MAX_BATCHED_ENCUNIT_ROWS
+            && decoder.as_wasm().is_some_and(|wasm| wasm.supports_batch())
+        {
+            return self.decode_wasm_ahead(decoder);
+        }
         decoder.decode().map(Some)
     }
 
@@ -96,14 +265,14 @@ impl<R: Reader> ChunkDecoder for NoDictColDecoder<'_, R> {
                     .encoded_chunk_buf
                     .split_to(encblock_fb.size_() as usize);
                 let decoder = create_encunit_decoder(
-                    encblock_fb.encoding().unwrap(),
-                    encblock_fb.compression(),
+                    encblock_fb,
                     data.freeze(),
-                    enc_unit_num_rows as u64,
                     self.data_type.clone(),
                     self.wasm_context
                         .as_ref()
                         .map(Arc::clone),
+                    self.skip_validity,
+                    self.compression_dictionary,
                 )?;
                 // Return the array with only one element at the given index.
                 let array = match decoder.slice(idx, idx + to_decode) {
@@ -132,6 +301,44 @@ impl<R: Reader> ChunkDecoder for NoDictColDecoder<'_, R> {
             )?),
         })
     }
+    fn take_rows(&mut self, sorted_row_ids_in_chunk: &[u64]) -> Result<Option<ArrayRef>> {
+        let mut cur = 0u64;
+        let mut pos = 0;
+        let mut arrays = vec![];
+        while pos < sorted_row_ids_in_chunk.len() {
+            let Some(encblock_fb) = self.encunit_iter.next() else {
+                break;
+            };
+            let enc_unit_num_rows = encblock_fb.num_rows() as u64;
+            let end = cur + enc_unit_num_rows;
+            let data = self
+                .encoded_chunk_buf
+                .split_to(encblock_fb.size_() as usize);
+            let start_pos = pos;
+            while pos < sorted_row_ids_in_chunk.len() && sorted_row_ids_in_chunk[pos] < end {
+                pos += 1;
+            }
+            // Skip decoding EncUnits without any selected row.
+            if pos > start_pos {
+                let decoder = create_encunit_decoder(
+                    encblock_fb,
+                    data.freeze(),
+                    self.data_type.clone(),
+                    self.wasm_context.as_ref().map(Arc::clone),
+                    self.skip_validity,
+                    self.compression_dictionary,
+                )?;
+                arrays.push(take_from_encunit(
+                    decoder.as_ref(),
+                    &sorted_row_ids_in_chunk[start_pos..pos],
+                    cur,
+                )?);
+            }
+            cur = end;
+        }
+        concat_gathered(arrays)
+    }
+
     // Deprecated decode logic with null info
     // fn decode_batch(&mut self) -> Result<Option<ArrayRef>> {
     //     let block = self.encunit_iter.next();
@@ -222,6 +429,10 @@ pub struct DictColDecoder<'a, R> {
     /// The data type of the column.
     data_type: DataType,
     wasm_context: Option<Arc<WASMReadingContext<R>>>,
+    /// See [`crate::compression::compress_with_trained_dictionary`].
+    compression_dictionary: Option<&'a [u8]>,
+    /// Output `DictionaryArray`s instead of materializing the values.
+    preserve_dictionary: bool,
 }
 
 impl<'a, R: Reader> DictColDecoder<'a, R> {
@@ -236,8 +447,71 @@ impl<'a, R: Reader> DictColDecoder<'a, R> {
             encoded_chunk_buf,
             data_type,
             wasm_context,
+            compression_dictionary: None,
+            preserve_dictionary: false,
         }
     }
+
+    pub fn with_preserve_dictionary(mut self, preserve_dictionary: bool) -> Self {
+        self.preserve_dictionary = preserve_dictionary;
+        self
+    }
+
+    pub fn with_compression_dictionary(mut self, compression_dictionary: Option<&'a [u8]>) -> Self {
+        self.compression_dictionary = compression_dictionary;
+        self
+    }
+
+    /// Decode the local dictionary of the EncUnits that follow.
+    fn decode_dictionary(&self, encunit: fb::EncUnit, data: BytesMut) -> Result<ArrayRef> {
+        let dict_decoder = create_encunit_decoder(
+            encunit,
+            data.freeze(),
+            self.data_type.clone(),
+            self.wasm_context.as_ref().map(Arc::clone),
+            false,
+            self.compression_dictionary,
+        )?;
+        Ok(if encunit.num_rows() > 0 {
+            dict_decoder.decode()?
+        } else {
+            // The dictionary of a chunk of nulls only holds a null.
+            new_null_array(&self.data_type, 1)
+        })
+    }
+
+    /// Map the `indices` decoded from an index EncUnit to the values of `dict`.
+    fn lookup(&self, indices_ref: &ArrayRef, dict: ArrayRef) -> Result<Option<ArrayRef>> {
+        if self.preserve_dictionary {
+            return to_dictionary_array(indices_ref, dict);
+        }
+        let indices = indices_ref.as_any().downcast_ref::<UInt64Array>().ok_or(
+            fff_core::errors::Error::General("Incorrect type of indices".to_owned()),
+        )?;
+        // Create an array of the same type as dict, then map
+        // TODO: use DictionaryArray with zero-copy
+        // DictionaryArray::<arrow::datatypes::Int64Type>::try_new(indices.clone(), dict)
+        //     .map_err(|err| fff_core::errors::Error::External(Box::new(err)))
+        //     .map(|arr| Some(Arc::new(arr) as ArrayRef))
+        dictionary_values(&dict, indices)
+    }
+}
+
+/// Wrap the decoded indices and the dictionary into a `DictionaryArray` with `UInt32` keys,
+/// whatever the index width in the file, so that arrays of different chunks can be concatenated.
+fn to_dictionary_array(indices: &ArrayRef, dict: ArrayRef) -> Result<Option<ArrayRef>> {
+    let keys = cast_with_options(
+        indices,
+        &DataType::UInt32,
+        &CastOptions {
+            safe: false,
+            ..Default::default()
+        },
+    )?;
+    Ok(Some(Arc::new(DictionaryArray::<UInt32Type>::try_new(
+        keys.as_primitive::<UInt32Type>().clone(),
+        dict,
+    )?) as ArrayRef))
 }
 
 macro_rules! index_downcast {
@@ -269,6 +543,63 @@ macro_rules! dict_index_to_data {
     }};
 }
 
+/// Materialize the values of `dict` at `indices`.
+fn dictionary_values(dict: &ArrayRef, indices: &dyn Array) -> Result<Option<ArrayRef>> {
+    match dict.data_type() {
+        DataType::Int32 => {
+            dict_index_to_data!(arrow_array::Int32Array, dict, indices)
+        }
+        DataType::Int64 => {
+            dict_index_to_data!(arrow_array::Int64Array, dict, indices)
+        }
+        DataType::Float32 => {
+            dict_index_to_data!(arrow_array::Float32Array, dict, indices)
+        }
+        DataType::Float64 => {
+            dict_index_to_data!(arrow_array::Float64Array, dict, indices)
+        }
+        DataType::Utf8 | DataType::LargeUtf8 => {
+            dict_index_to_data!(arrow_array::StringArray, dict, indices)
+        }
+        DataType::Utf8View => {
+            dict_index_to_data!(arrow_array::StringViewArray, dict, indices)
+        }
+        DataType::Timestamp(TimeUnit::Second, _) => {
+            dict_index_to_data!(arrow_array::TimestampSecondArray, dict, indices)
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, _) => {
+            dict_index_to_data!(arrow_array::TimestampMillisecondArray, dict, indices)
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            dict_index_to_data!(arrow_array::TimestampMicrosecondArray, dict, indices)
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+            dict_index_to_data!(arrow_array::TimestampNanosecondArray, dict, indices)
+        }
+        DataType::Time32(TimeUnit::Second) => {
+            dict_index_to_data!(arrow_array::Time32SecondArray, dict, indices)
+        }
+        DataType::Time32(TimeUnit::Millisecond) => {
+            dict_index_to_data!(arrow_array::Time32MillisecondArray, dict, indices)
+        }
+        DataType::Time64(TimeUnit::Microsecond) => {
+            dict_index_to_data!(arrow_array::Time64MicrosecondArray, dict, indices)
+        }
+        DataType::Time64(TimeUnit::Nanosecond) => {
+            dict_index_to_data!(arrow_array::Time64NanosecondArray, dict, indices)
+        }
+        DataType::Date32 => {
+            dict_index_to_data!(arrow_array::Date32Array, dict, indices)
+        }
+        DataType::Boolean => {
+            dict_index_to_data!(arrow_array::BooleanArray, dict, indices)
+        }
+        _ => Err(fff_core::errors::Error::General(
+            "Decoding other datatypes are not supported".to_owned(),
+        )),
+    }
+}
+
 impl<R: Reader> ChunkDecoder for DictColDecoder<'_, R> {
     fn decode_batch(&mut self) -> Result<Option<ArrayRef>> {
         let dict_encunit = self.encunit_iter.next();
@@ -280,21 +611,7 @@ impl<R: Reader> ChunkDecoder for DictColDecoder<'_, R> {
         let dict = self
             .encoded_chunk_buf
             .split_to(dict_encblock_fb.size_() as usize);
-        let dict_decoder = create_encunit_decoder(
-            dict_encblock_fb.encoding().unwrap(),
-            dict_encblock_fb.compression(),
-            dict.freeze(),
-            dict_encblock_fb.num_rows() as u64,
-            self.data_type.clone(),
-            self.wasm_context
-                .as_ref()
-                .map(Arc::clone),
-        )?;
-        let dict = if dict_encblock_fb.num_rows() > 0 {
-            dict_decoder.decode()?
-        } else {
-            Arc::new(arrow_array::Int32Array::new_null(1))
-        };
+        let dict = self.decode_dictionary(dict_encblock_fb, dict)?;
         let index_encunit = self.encunit_iter.next();
         if index_encunit.is_none() {
             return nyi_err!("Index does not exists");
@@ -305,83 +622,68 @@ impl<R: Reader> ChunkDecoder for DictColDecoder<'_, R> {
             .encoded_chunk_buf
             .split_to(index_encblock_fb.size_() as usize);
         let indices_decoder = create_encunit_decoder(
-            index_encblock_fb.encoding().unwrap(),
-            index_encblock_fb.compression(),
+            index_encblock_fb,
             indices.freeze(),
-            index_encblock_fb.num_rows() as u64,
             DataType::Int64,
             self.wasm_context
                 .as_ref()
                 .map(Arc::clone),
+            false,
+            self.compression_dictionary,
         )?;
         let indices_ref = indices_decoder.decode()?;
-        let indices = indices_ref.as_any().downcast_ref::<UInt64Array>().ok_or(
-            fff_core::errors::Error::General("Incorrect type of indices".to_owned()),
-        )?;
-        // Create an array of the same type as dict, then map
-        // TODO: use DictionaryArray with zero-copy
-        // DictionaryArray::<arrow::datatypes::Int64Type>::try_new(indices.clone(), dict)
-        //     .map_err(|err| fff_core::errors::Error::External(Box::new(err)))
-        //     .map(|arr| Some(Arc::new(arr) as ArrayRef))
-        match *dict.data_type() {
-            DataType::Int32 => {
-                dict_index_to_data!(arrow_array::Int32Array, dict, indices)
-            }
-            DataType::Int64 => {
-                dict_index_to_data!(arrow_array::Int64Array, dict, indices)
-            }
-            DataType::Float32 => {
-                dict_index_to_data!(arrow_array::Float32Array, dict, indices)
-            }
-            DataType::Float64 => {
-                dict_index_to_data!(arrow_array::Float64Array, dict, indices)
-            }
-            DataType::Utf8 | DataType::LargeUtf8 => {
-                dict_index_to_data!(arrow_array::StringArray, dict, indices)
-            }
-            DataType::Utf8View => {
-                dict_index_to_data!(arrow_array::StringViewArray, dict, indices)
-            }
-            DataType::Timestamp(TimeUnit::Second, _) => {
-                dict_index_to_data!(arrow_array::TimestampSecondArray, dict, indices)
-            }
-            DataType::Timestamp(TimeUnit::Millisecond, _) => {
-                dict_index_to_data!(arrow_array::TimestampMillisecondArray, dict, indices)
-            }
-            DataType::Timestamp(TimeUnit::Microsecond, _) => {
-                dict_index_to_data!(arrow_array::TimestampMicrosecondArray, dict, indices)
-            }
-            DataType::Timestamp(TimeUnit::Nanosecond, _) => {
-                dict_index_to_data!(arrow_array::TimestampNanosecondArray, dict, indices)
-            }
-            DataType::Time32(TimeUnit::Second) => {
-                dict_index_to_data!(arrow_array::Time32SecondArray, dict, indices)
-            }
-            DataType::Time32(TimeUnit::Millisecond) => {
-                dict_index_to_data!(arrow_array::Time32MillisecondArray, dict, indices)
-            }
-            DataType::Time64(TimeUnit::Microsecond) => {
-                dict_index_to_data!(arrow_array::Time64MicrosecondArray, dict, indices)
-            }
-            DataType::Time64(TimeUnit::Nanosecond) => {
-                dict_index_to_data!(arrow_array::Time64NanosecondArray, dict, indices)
-            }
-            DataType::Date32 => {
-                dict_index_to_data!(arrow_array::Date32Array, dict, indices)
-            }
-            DataType::Boolean => {
-                dict_index_to_data!(arrow_array::BooleanArray, dict, indices)
-            }
-            _ => Err(fff_core::errors::Error::General(
-                "Decoding other datatypes are not supported".to_owned(),
-            )),
-        }
+        self.lookup(&indices_ref, dict)
     }
 
     fn decode_row_at(&mut self, _row_id_in_chunk: usize, _len: usize) -> Result<Option<ArrayRef>> {
         // TODO: random access for dict (decode the index first then the dict?)
         nyi_err!("Random access for dict is not implemented yet")
     }
+
+    /// Only the EncUnits holding selected rows are decoded, and only the selected indices, see
+    /// [`take_from_encunit`].
+    fn take_rows(&mut self, sorted_row_ids_in_chunk: &[u64]) -> Result<Option<ArrayRef>> {
+        let mut cur = 0u64;
+        let mut pos = 0;
+        let mut arrays = vec![];
+        while pos < sorted_row_ids_in_chunk.len() {
+            let (Some(dict_encblock_fb), Some(index_encblock_fb)) =
+                (self.encunit_iter.next(), self.encunit_iter.next())
+            else {
+                break;
+            };
+            let dict = self
+                .encoded_chunk_buf
+                .split_to(dict_encblock_fb.size_() as usize);
+            let indices = self
+                .encoded_chunk_buf
+                .split_to(index_encblock_fb.size_() as usize);
+            let end = cur + index_encblock_fb.num_rows() as u64;
+            let start_pos = pos;
+            while pos < sorted_row_ids_in_chunk.len() && sorted_row_ids_in_chunk[pos] < end {
+                pos += 1;
+            }
+            if pos > start_pos {
+                let dict = self.decode_dictionary(dict_encblock_fb, dict)?;
+                let indices_decoder = create_encunit_decoder(
+                    index_encblock_fb,
+                    indices.freeze(),
+                    DataType::Int64,
+                    self.wasm_context.as_ref().map(Arc::clone),
+                    false,
+                    self.compression_dictionary,
+                )?;
+                let indices = take_from_encunit(
+                    indices_decoder.as_ref(),
+                    &sorted_row_ids_in_chunk[start_pos..pos],
+                    cur,
+                )?;
+                arrays.extend(self.lookup(&indices, dict)?);
+            }
+            cur = end;
+        }
+        concat_gathered(arrays)
+    }
 }
 
 /// Local dictionaries are used.
@@ -394,7 +696,11 @@ pub struct SharedDictColDecoder<'a, R> {
     /// The data type of the column.
     _data_type: DataType,
     wasm_context: Option<Arc<WASMReadingContext<R>>>,
+    /// See [`crate::compression::compress_with_trained_dictionary`].
+    compression_dictionary: Option<&'a [u8]>,
     shared_dictionary: ArrayRef,
+    /// Output `DictionaryArray`s referencing `shared_dictionary` instead of materializing the values.
+    preserve_dictionary: bool,
 }
 
 impl<'a, R: Reader> SharedDictColDecoder<'a, R> {
@@ -410,8 +716,30 @@ impl<'a, R: Reader> SharedDictColDecoder<'a, R> {
             encoded_chunk_buf,
             _data_type: data_type,
             wasm_context,
+            compression_dictionary: None,
             shared_dictionary,
+            preserve_dictionary: false,
+        }
+    }
+
+    pub fn with_preserve_dictionary(mut self, preserve_dictionary: bool) -> Self {
+        self.preserve_dictionary = preserve_dictionary;
+        self
+    }
+
+    pub fn with_compression_dictionary(mut self, compression_dictionary: Option<&'a [u8]>) -> Self {
+        self.compression_dictionary = compression_dictionary;
+        self
+    }
+
+    /// Map the `indices` decoded from an index EncUnit to the values of the shared dictionary.
+    fn lookup(&self, indices: &ArrayRef) -> Result<Option<ArrayRef>> {
+        if self.preserve_dictionary {
+            // All the chunks referencing this shared dictionary share the same values array.
+            return to_dictionary_array(indices, Arc::clone(&self.shared_dictionary));
         }
+        // Create an array of the same type as dict, then map
+        dictionary_values(&self.shared_dictionary, indices.as_ref())
     }
 }
 
@@ -427,116 +755,118 @@ impl<R: Reader> ChunkDecoder for SharedDictColDecoder<'_, R> {
             .encoded_chunk_buf
             .split_to(index_encblock_fb.size_() as usize);
         let indices_decoder = create_encunit_decoder(
-            index_encblock_fb.encoding().unwrap(),
-            index_encblock_fb.compression(),
+            index_encblock_fb,
             indices.freeze(),
-            index_encblock_fb.num_rows() as u64,
             DataType::Int64,
             self.wasm_context
                 .as_ref()
                 .map(Arc::clone),
+            false,
+            self.compression_dictionary,
         )?;
         let indices = indices_decoder.decode()?;
-        let dict = &self.shared_dictionary;
-        // Create an array of the same type as dict, then map
-        match dict.data_type() {
-            DataType::Int32 => {
-                dict_index_to_data!(arrow_array::Int32Array, dict, indices)
-            }
-            DataType::Int64 => {
-                dict_index_to_data!(arrow_array::Int64Array, dict, indices)
-            }
-            DataType::Float32 => {
-                dict_index_to_data!(arrow_array::Float32Array, dict, indices)
-            }
-            DataType::Float64 => {
-                dict_index_to_data!(arrow_array::Float64Array, dict, indices)
-            }
-            DataType::Utf8 | DataType::LargeUtf8 => {
-                dict_index_to_data!(arrow_array::StringArray, dict, indices)
-            }
-            DataType::Utf8View => {
-                dict_index_to_data!(arrow_array::StringViewArray, dict, indices)
-            }
-            DataType::Timestamp(TimeUnit::Second, _) => {
-                dict_index_to_data!(arrow_array::TimestampSecondArray, dict, indices)
-            }
-            DataType::Timestamp(TimeUnit::Millisecond, _) => {
-                dict_index_to_data!(arrow_array::TimestampMillisecondArray, dict, indices)
-            }
-            DataType::Timestamp(TimeUnit::Microsecond, _) => {
-                dict_index_to_data!(arrow_array::TimestampMicrosecondArray, dict, indices)
-            }
-            DataType::Timestamp(TimeUnit::Nanosecond, _) => {
-                dict_index_to_data!(arrow_array::TimestampNanosecondArray, dict, indices)
-            }
-            DataType::Time32(TimeUnit::Second) => {
-                dict_index_to_data!(arrow_array::Time32SecondArray, dict, indices)
-            }
-            DataType::Time32(TimeUnit::Millisecond) => {
-                dict_index_to_data!(arrow_array::Time32MillisecondArray, dict, indices)
-            }
-            DataType::Time64(TimeUnit::Microsecond) => {
-                dict_index_to_data!(arrow_array::Time64MicrosecondArray, dict, indices)
-            }
-            DataType::Time64(TimeUnit::Nanosecond) => {
-                dict_index_to_data!(arrow_array::Time64NanosecondArray, dict, indices)
-            }
-            DataType::Date32 => {
-                dict_index_to_data!(arrow_array::Date32Array, dict, indices)
-            }
-            DataType::Boolean => {
-                dict_index_to_data!(arrow_array::BooleanArray, dict, indices)
-            }
-            _ => Err(fff_core::errors::Error::General(
-                "Decoding other datatypes are not supported".to_owned(),
-            )),
-        }
+        self.lookup(&indices)
     }
 
     fn decode_row_at(&mut self, _row_id_in_chunk: usize, _len: usize) -> Result<Option<ArrayRef>> {
         // TODO: random access for dict (decode the index first then the dict?)
         nyi_err!("Random access for dict is not implemented yet")
     }
+
+    /// Only the index EncUnits holding selected rows are decoded, and only the selected indices,
+    /// see [`take_from_encunit`].
+    fn take_rows(&mut self, sorted_row_ids_in_chunk: &[u64]) -> Result<Option<ArrayRef>> {
+        let mut cur = 0u64;
+        let mut pos = 0;
+        let mut arrays = vec![];
+        while pos < sorted_row_ids_in_chunk.len() {
+            let Some(index_encblock_fb) = self.encunit_iter.next() else {
+                break;
+            };
+            let indices = self
+                .encoded_chunk_buf
+                .split_to(index_encblock_fb.size_() as usize);
+            let end = cur + index_encblock_fb.num_rows() as u64;
+            let start_pos = pos;
+            while pos < sorted_row_ids_in_chunk.len() && sorted_row_ids_in_chunk[pos] < end {
+                pos += 1;
+            }
+            if pos > start_pos {
+                let indices_decoder = create_encunit_decoder(
+                    index_encblock_fb,
+                    indices.freeze(),
+                    DataType::Int64,
+                    self.wasm_context.as_ref().map(Arc::clone),
+                    false,
+                    self.compression_dictionary,
+                )?;
+                let indices = take_from_encunit(
+                    indices_decoder.as_ref(),
+                    &sorted_row_ids_in_chunk[start_pos..pos],
+                    cur,
+                )?;
+                arrays.extend(self.lookup(&indices)?);
+            }
+            cur = end;
+        }
+        concat_gathered(arrays)
+    }
 }
 
+/// Create the decoder of the EncUnits in `encunit_iter` of the chunk described by `chunk_meta`.
 pub fn create_physical_decoder<'a, R: Reader + 'a>(
+    chunk_meta: &fb::Chunk<'a>,
     encunit_iter: VectorIter<'a, ForwardsUOffset<fb::EncUnit<'a>>>,
-    dict_encoding_type: fb::DictionaryEncoding,
-    shared_dictionary_id: Option<fb::SharedDictionary>,
     data_type: &DataType,
     encoded_chunk_buf: BytesMut,
     wasm_context: Option<Arc<WASMReadingContext<R>>>,
     shared_dictionary_cache: Option<&'a SharedDictionaryCache>,
+    options: &DecodeOptions<'_>,
 ) -> Result<Box<dyn ChunkDecoder + 'a>> {
+    let dict_encoding_type = chunk_meta.encoding_type();
+    let compression_dictionary = chunk_meta.compression_dictionary().map(|x| x.bytes());
     if dict_encoding_type == fb::DictionaryEncoding::NoDictionary {
         match *data_type {
-            non_nest_types!() | DataType::List(_) | DataType::LargeList(_) => {
-                Ok(Box::new(NoDictColDecoder::new(
+            non_nest_types!() => Ok(Box::new(
+                NoDictColDecoder::new(
                     encunit_iter,
                     encoded_chunk_buf,
                     data_type.clone(),
                     wasm_context,
-                )))
-            }
+                )
+                .with_skip_validity(chunk_meta.null_count() == Some(0))
+                .with_preserve_runs(options.preserve_runs)
+                .with_compression_dictionary(compression_dictionary),
+            )),
+            DataType::List(_) | DataType::LargeList(_) => Ok(Box::new(
+                NoDictColDecoder::new(
+                    encunit_iter,
+                    encoded_chunk_buf,
+                    data_type.clone(),
+                    wasm_context,
+                )
+                .with_compression_dictionary(compression_dictionary),
+            )),
             _ => todo!("Implement other data types"),
         }
     } else if dict_encoding_type == fb::DictionaryEncoding::LocalDictionary {
         match *data_type {
-            non_nest_types!() | DataType::List(_) | DataType::LargeList(_) => {
-                Ok(Box::new(DictColDecoder::new(
+            non_nest_types!() | DataType::List(_) | DataType::LargeList(_) => Ok(Box::new(
+                DictColDecoder::new(
                     encunit_iter,
                     encoded_chunk_buf,
                     data_type.clone(),
                     wasm_context,
-                )))
-            }
+                )
+                .with_preserve_dictionary(options.preserve_dictionary)
+                .with_compression_dictionary(compression_dictionary),
+            )),
             _ => todo!("Implement other data types"),
         }
     } else if dict_encoding_type == fb::DictionaryEncoding::SharedDictionary {
         match *data_type {
-            non_nest_types!() | DataType::List(_) | DataType::LargeList(_) => {
-                Ok(Box::new(SharedDictColDecoder::new(
+            non_nest_types!() | DataType::List(_) | DataType::LargeList(_) => Ok(Box::new(
+                SharedDictColDecoder::new(
                     encunit_iter,
                     encoded_chunk_buf,
                     data_type.clone(),
@@ -548,13 +878,16 @@ pub fn create_physical_decoder<'a, R: Reader + 'a>(
                             )
                         })?
                         .get_dict(
-                            shared_dictionary_id
+                            chunk_meta
+                                .encoding_as_shared_dictionary()
                                 .ok_or_else(|| general_error!("Shared dictionary ID not found"))?
                                 .shared_dictionary_idx() as usize,
                         )
                         .ok_or_else(|| general_error!("Shared dictionary not found in cache"))?,
-                )))
-            }
+                )
+                .with_preserve_dictionary(options.preserve_dictionary)
+                .with_compression_dictionary(compression_dictionary),
+            )),
             _ => todo!("Implement other data types"),
         }
     } else {
@@ -28,6 +28,9 @@ pub enum DictionaryTypeOptions {
     GlobalDictionary,
     FixedScopeDictionary(u64),
     GlobalDictionaryMultiColSharing,
+    /// Global dictionaries, one of which is shared by the columns of the same type whose sampled
+    /// values overlap, e.g., the repeated columns of a denormalized table.
+    GlobalDictionaryAutoSharing,
     GLBest(Option<(f64, usize)>),
 }
 
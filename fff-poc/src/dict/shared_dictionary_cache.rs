@@ -8,7 +8,9 @@ use fff_core::{errors::Error, nyi_err};
 use fff_format::File::fff::flatbuf as fb;
 
 use crate::{
-    context::WASMReadingContext, decoder::physical::create_physical_decoder, io::reader::Reader,
+    context::WASMReadingContext,
+    decoder::{logical::DecodeOptions, physical::create_physical_decoder},
+    io::reader::Reader,
 };
 
 pub struct SharedDictionaryCache {
@@ -67,20 +69,20 @@ impl SharedDictionaryCache {
                         let mut encoded_chunk_buf = BytesMut::zeroed(chunk_meta.size_() as usize);
                         reader.read_exact_at(&mut encoded_chunk_buf, chunk_meta.offset())?;
                         let mut decoder = create_physical_decoder::<R>(
+                            &chunk_meta,
                             chunk_meta
                                 .encunits()
                                 .ok_or_else(|| {
                                     Error::General("No chunks in column meta".to_string())
                                 })?
                                 .iter(),
-                            chunk_meta.encoding_type(),
-                            None,
                             datatype,
                             encoded_chunk_buf,
                             wasm_context
                                 .as_ref()
                                 .map(Arc::clone),
                             None,
+                            &DecodeOptions::default(),
                         )?;
                         let mut arrays = vec![];
                         if chunk_meta.num_rows() == 0 {
@@ -100,7 +102,10 @@ impl SharedDictionaryCache {
                     })
                     .collect::<Result<Vec<_>, Error>>()?;
                 dict_sizes.push(dict_size);
-                if dict_arrs.len() == 1 {
+                // Dicts sharing the dict of other columns have no chunks of their own.
+                if dict_arrs.is_empty() {
+                    Ok(None)
+                } else if dict_arrs.len() == 1 {
                     Ok(Some(dict_arrs[0].clone()))
                 } else if dict_arrs.len() == 2 {
                     assert_eq!(dict_arrs[0].data_type(), dict_arrs[1].data_type());
@@ -118,6 +123,11 @@ impl SharedDictionaryCache {
         })
     }
 
+    /// Number of dictionaries in the shared dictionary table, including those without values.
+    pub fn num_dicts(&self) -> usize {
+        self.dictionaries.len()
+    }
+
     pub fn get_dict(&self, index: usize) -> Option<ArrayRef> {
         self.dictionaries.get(index).cloned().flatten()
     }
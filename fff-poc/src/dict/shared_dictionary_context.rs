@@ -3,12 +3,14 @@ use std::{collections::HashMap, sync::Arc};
 use arrow_array::ArrayRef;
 use arrow_schema::DataType;
 use fff_core::errors::Error;
-use fff_encoding::schemes::encode_to_bytes;
+use fff_encoding::schemes::encode_to_bytes_with_validity;
 use fff_format::File::fff::flatbuf as fb;
 
 use crate::{
+    compression::{compress_data, Compression},
     context::WASMWritingContext,
     counter::EncodingCounter,
+    dict::DictionaryTypeOptions,
     encoder::{
         encoded_column_chunk::{EncodedColumnChunk, SerializedEncUnit},
         encunit::create_encunit_encoder,
@@ -23,6 +25,10 @@ use super::{bottom_k_sketch::BottomKSketch, Dictionary};
 const MERGE_THRESHOLD: f64 = 0.01;
 const OVERLAP_THRESHOLD: f64 = 0.99;
 const INTERSECTION_LEN_THRESHOLD: f64 = 1024.0;
+// Threshold of Jaccard similarity for sharing one dict, with auto sharing
+const SHARING_THRESHOLD: f64 = 0.5;
+// Number of values of each column sampled to decide the sharing
+const SHARING_SAMPLE_SIZE: usize = 64 * 1024;
 
 /// This struct manages shared dictionaries for writer
 pub struct SharedDictionaryContext {
@@ -31,7 +37,14 @@ pub struct SharedDictionaryContext {
     _column_chunk_size: u64,
     is_multi_col_sharing: bool,
     merge_result: Vec<Option<(usize, usize)>>,
-    compression_type: fb::CompressionType,
+    /// Whether dicts are shared with [DictionaryTypeOptions::GlobalDictionaryAutoSharing], and
+    /// whether the sharing is decided. Until then, dicts only hold samples of their values.
+    is_auto_sharing: bool,
+    sharing_assigned: bool,
+    sampled_values: Vec<usize>,
+    /// The dict each dict is shared with, whose ID replaces its own.
+    shared_with: Vec<Option<u32>>,
+    compression: Compression,
 }
 
 impl Default for SharedDictionaryContext {
@@ -42,7 +55,11 @@ impl Default for SharedDictionaryContext {
             _column_chunk_size: DEFAULT_IOUNIT_SIZE,
             is_multi_col_sharing: false,
             merge_result: vec![],
-            compression_type: fb::CompressionType::Uncompressed,
+            is_auto_sharing: false,
+            sharing_assigned: false,
+            sampled_values: vec![],
+            shared_with: vec![],
+            compression: Compression::default(),
         }
     }
 }
@@ -51,16 +68,21 @@ impl SharedDictionaryContext {
     pub fn new(
         encoding_unit_size: u64,
         column_chunk_size: u64,
-        is_multi_col_sharing: bool,
-        compression_type: fb::CompressionType,
+        dictionary_type: DictionaryTypeOptions,
+        compression: Compression,
     ) -> Self {
         Self {
             dictionaries: vec![],
             _encoding_unit_size: encoding_unit_size,
             _column_chunk_size: column_chunk_size,
-            is_multi_col_sharing,
+            is_multi_col_sharing: dictionary_type
+                == DictionaryTypeOptions::GlobalDictionaryMultiColSharing,
             merge_result: vec![],
-            compression_type,
+            is_auto_sharing: dictionary_type == DictionaryTypeOptions::GlobalDictionaryAutoSharing,
+            sharing_assigned: false,
+            sampled_values: vec![],
+            shared_with: vec![],
+            compression,
         }
     }
 
@@ -95,24 +117,7 @@ impl SharedDictionaryContext {
         // Use hash functions to determine whether two dictionaries are "similar"
         // And merge similar ones
         let dicts = &mut self.dictionaries;
-        let mut dtype_to_sketches = HashMap::<DataType, Vec<(usize, BottomKSketch)>>::new();
-        for (idx, dict) in dicts.iter().enumerate() {
-            if dict.len()? == 0 {
-                continue;
-            }
-            let mut sketch = BottomKSketch::new();
-            dict.dict_hash_iter()?.for_each(|val| sketch.add_hash(val));
-            sketch.finish();
-            let dtype = &dict.datatype;
-            if dtype_to_sketches.contains_key(dtype) {
-                dtype_to_sketches
-                    .get_mut(dtype)
-                    .unwrap()
-                    .push((idx, sketch));
-            } else {
-                dtype_to_sketches.insert(dtype.clone(), vec![(idx, sketch)]);
-            }
-        }
+        let dtype_to_sketches = sketch_dicts(dicts)?;
         let merge_res = &mut self.merge_result;
         merge_res.resize(dicts.len(), None);
         for sketch_group in dtype_to_sketches.values() {
@@ -166,7 +171,64 @@ impl SharedDictionaryContext {
     }
 
     pub fn submit_values(&mut self, dict_idx: u32, values: ArrayRef) -> Result<(), Error> {
-        self.dictionaries[dict_idx as usize].submit_values(values)
+        if !self.needs_sharing_assignment() {
+            return self.dictionaries[dict_idx as usize].submit_values(values);
+        }
+        self.sampled_values.resize(self.dictionaries.len(), 0);
+        let sampled = &mut self.sampled_values[dict_idx as usize];
+        let sample_len = values.len().min(SHARING_SAMPLE_SIZE - *sampled);
+        if sample_len == 0 {
+            return Ok(());
+        }
+        *sampled += sample_len;
+        self.dictionaries[dict_idx as usize].submit_values(values.slice(0, sample_len))
+    }
+
+    /// Whether the dicts to share are still to be decided from samples of their values, see
+    /// [Self::assign_shared_dicts].
+    pub fn needs_sharing_assignment(&self) -> bool {
+        self.is_auto_sharing && !self.sharing_assigned
+    }
+
+    /// Let the dicts of the same type whose samples are similar share the dict of the lowest ID,
+    /// then drop the samples.
+    pub fn assign_shared_dicts(&mut self) -> Result<(), Error> {
+        self.sharing_assigned = true;
+        let mut shared_with: Vec<usize> = (0..self.dictionaries.len()).collect();
+        for sketch_group in sketch_dicts(&self.dictionaries)?.values() {
+            for i in 1..sketch_group.len() {
+                for j in 0..i {
+                    if sketch_group[i].1.estimate_jaccard(&sketch_group[j].1) < SHARING_THRESHOLD {
+                        continue;
+                    }
+                    let root_i = shared_with[sketch_group[i].0];
+                    let root_j = shared_with[sketch_group[j].0];
+                    let (root, other) = (root_i.min(root_j), root_i.max(root_j));
+                    for idx in shared_with.iter_mut().filter(|idx| **idx == other) {
+                        *idx = root;
+                    }
+                }
+            }
+        }
+        self.shared_with = shared_with
+            .into_iter()
+            .enumerate()
+            .map(|(idx, root)| (idx != root).then_some(root as u32))
+            .collect();
+        self.sampled_values = vec![];
+        for dict in self.dictionaries.iter_mut() {
+            *dict = Dictionary::try_new(dict.datatype.clone())?;
+        }
+        Ok(())
+    }
+
+    /// The ID of the dict shared by `dict_idx`, itself if not shared.
+    pub fn resolve_dict_idx(&self, dict_idx: u32) -> u32 {
+        self.shared_with
+            .get(dict_idx as usize)
+            .copied()
+            .flatten()
+            .unwrap_or(dict_idx)
     }
 
     #[allow(clippy::type_complexity)]
@@ -192,6 +254,10 @@ impl SharedDictionaryContext {
             .enumerate()
             .map(
                 |(i, dict)| -> Result<(Vec<EncodedColumnChunk>, Option<usize>), Error> {
+                    // Shared dicts are written once, under the ID of the dict they share.
+                    if self.resolve_dict_idx(i as u32) != i as u32 {
+                        return Ok((vec![], None));
+                    }
                     let (mut dict, _) = dict.finish()?;
                     let dict_dtype = dict.data_type().clone();
                     let mut dict_chunk = EncodedColumnChunk::builder()
@@ -206,28 +272,29 @@ impl SharedDictionaryContext {
                         false,
                     );
                     let write_slice = |slice, slice_len| -> Result<SerializedEncUnit, Error> {
-                        let encoded_bytes = encode_to_bytes(dict_encoder.clone(), slice);
+                        let (encoded_bytes, validity_size, encoding_type) =
+                            encode_to_bytes_with_validity(dict_encoder.clone(), slice)?;
+                        let (compressed_bytes, compression_type) =
+                            compress_data(encoded_bytes, self.compression)?;
                         Ok(SerializedEncUnit::new(
-                            encoded_bytes,
+                            compressed_bytes,
                             slice_len as u32,
-                            {
-                                let encoding_type = dict_encoder.encoding_type();
-                                footer::Encoding::try_new(
-                                    if wasm_context.always_set_custom_wasm_for_built_in() {
-                                        fb::EncodingType::CUSTOM_WASM
-                                    } else {
-                                        encoding_type.to_fbs_encoding()
-                                    },
-                                    if wasm_context.always_set_custom_wasm_for_built_in() {
-                                        wasm_context.builtin_wasm_id()
-                                    } else {
-                                        wasm_context.data_type_to_wasm_id(&dict_dtype)
-                                    }
-                                    .map(|id| WASMEncoding::new(id.0, Vec::new())),
-                                )?
-                            },
-                            self.compression_type,
-                        ))
+                            footer::Encoding::try_new(
+                                if wasm_context.always_set_custom_wasm_for_built_in() {
+                                    fb::EncodingType::CUSTOM_WASM
+                                } else {
+                                    encoding_type.to_fbs_encoding()
+                                },
+                                if wasm_context.always_set_custom_wasm_for_built_in() {
+                                    wasm_context.builtin_wasm_id()
+                                } else {
+                                    wasm_context.data_type_to_wasm_id(&dict_dtype)
+                                }
+                                .map(|id| WASMEncoding::new(id.0, Vec::new())),
+                            )?,
+                            compression_type,
+                        )
+                        .with_validity_size(validity_size))
                     };
                     if let Some(Some((peer, merge_len))) = self.merge_result.get(i) {
                         if i < *peer {
@@ -270,3 +337,23 @@ impl SharedDictionaryContext {
         self.is_multi_col_sharing
     }
 }
+
+/// Bottom-K sketches of the values of the non-empty dicts, grouped by type.
+fn sketch_dicts(
+    dicts: &[Dictionary],
+) -> Result<HashMap<DataType, Vec<(usize, BottomKSketch)>>, Error> {
+    let mut dtype_to_sketches = HashMap::<DataType, Vec<(usize, BottomKSketch)>>::new();
+    for (idx, dict) in dicts.iter().enumerate() {
+        if dict.len()? == 0 {
+            continue;
+        }
+        let mut sketch = BottomKSketch::new();
+        dict.dict_hash_iter()?.for_each(|val| sketch.add_hash(val));
+        sketch.finish();
+        dtype_to_sketches
+            .entry(dict.datatype.clone())
+            .or_default()
+            .push((idx, sketch));
+    }
+    Ok(dtype_to_sketches)
+}
@@ -0,0 +1,448 @@
+//! Semantic comparison of two F3 files.
+//!
+//! [`diff_files`] compares the schemas, the row counts and a checksum of the decoded data of every
+//! top-level field, so two files holding the same table compare equal even if they were encoded
+//! differently. Layout differences (row groups, chunks, encodings, compression, sizes) are
+//! reported separately, e.g., to check what a re-encoding or a compaction changed.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use arrow::row::{RowConverter, SortField};
+use arrow_array::RecordBatch;
+use arrow_schema::{DataType, SchemaRef};
+use fff_core::errors::Result;
+
+use crate::common::checksum::{create_checksum, ChecksumType};
+use crate::file::footer::Footer;
+use crate::io::reader::Reader;
+use crate::reader::{get_metadata_buffer, read_postscript, FileReaderV2Builder};
+
+/// A difference in the content of the two files.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Difference {
+    /// A top-level field exists only in the left file, or only in the right one.
+    MissingField {
+        name: String,
+        in_left: bool,
+    },
+    FieldType {
+        name: String,
+        left: DataType,
+        right: DataType,
+    },
+    Nullability {
+        name: String,
+        left: bool,
+        right: bool,
+    },
+    NumRows {
+        left: u64,
+        right: u64,
+    },
+    /// The decoded data of a field present in both files differs.
+    ColumnData {
+        name: String,
+        left_checksum: u64,
+        right_checksum: u64,
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::MissingField { name, in_left } => write!(
+                f,
+                "field {:?} only in {} file",
+                name,
+                if *in_left { "left" } else { "right" }
+            ),
+            Difference::FieldType { name, left, right } => {
+                write!(f, "field {:?} type: {} vs {}", name, left, right)
+            }
+            Difference::Nullability { name, left, right } => {
+                write!(f, "field {:?} nullable: {} vs {}", name, left, right)
+            }
+            Difference::NumRows { left, right } => write!(f, "num rows: {} vs {}", left, right),
+            Difference::ColumnData {
+                name,
+                left_checksum,
+                right_checksum,
+            } => write!(
+                f,
+                "field {:?} data checksum: {:#018x} vs {:#018x}",
+                name, left_checksum, right_checksum
+            ),
+        }
+    }
+}
+
+/// A difference in how the same content is laid out in the two files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutDifference {
+    pub item: String,
+    pub left: String,
+    pub right: String,
+}
+
+impl fmt::Display for LayoutDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} vs {}", self.item, self.left, self.right)
+    }
+}
+
+/// Result of [`diff_files`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileDiff {
+    pub differences: Vec<Difference>,
+    pub layout_differences: Vec<LayoutDifference>,
+}
+
+impl FileDiff {
+    /// Whether both files hold the same schema and data, regardless of their layout.
+    pub fn is_semantically_equal(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    /// Whether both files also share the same layout.
+    pub fn is_identical(&self) -> bool {
+        self.is_semantically_equal() && self.layout_differences.is_empty()
+    }
+}
+
+impl fmt::Display for FileDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_identical() {
+            return writeln!(f, "files are identical");
+        }
+        if self.is_semantically_equal() {
+            writeln!(f, "files hold the same data")?;
+        } else {
+            writeln!(f, "content:")?;
+            for d in &self.differences {
+                writeln!(f, "  {}", d)?;
+            }
+        }
+        if !self.layout_differences.is_empty() {
+            writeln!(f, "layout:")?;
+            for d in &self.layout_differences {
+                writeln!(f, "  {}", d)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Layout of a physical column, aggregated over all row groups.
+#[derive(Debug, Default, PartialEq)]
+struct ColumnLayout {
+    num_chunks: usize,
+    num_encunits: usize,
+    size: u64,
+    encodings: BTreeSet<String>,
+    compressions: BTreeSet<String>,
+    dictionary_encodings: BTreeSet<String>,
+}
+
+#[derive(Debug, PartialEq)]
+struct FileLayout {
+    file_size: u64,
+    row_group_row_counts: Vec<u32>,
+    columns: Vec<ColumnLayout>,
+}
+
+fn read_layout<R: Reader>(reader: &R) -> Result<FileLayout> {
+    let file_size = reader.size()?;
+    let post_script = read_postscript(reader, file_size)?;
+    let owner = get_metadata_buffer(reader, &post_script)?;
+    let footer = Footer::try_new(&owner, file_size as usize, &post_script)?;
+    let mut columns: Vec<ColumnLayout> = vec![];
+    let mut row_group_row_counts = vec![];
+    for rg_meta in footer.row_group_metadatas() {
+        row_group_row_counts.push(rg_meta.row_count);
+        for (i, col_meta) in rg_meta.column_metadatas.iter().enumerate() {
+            if columns.len() <= i {
+                columns.push(ColumnLayout::default());
+            }
+            let layout = &mut columns[i];
+            for chunk in col_meta.column_chunks().into_iter().flatten() {
+                layout.num_chunks += 1;
+                layout.size += chunk.size_() as u64;
+                layout
+                    .dictionary_encodings
+                    .insert(format!("{:?}", chunk.encoding_type()));
+                for encunit in chunk.encunits().into_iter().flatten() {
+                    layout.num_encunits += 1;
+                    if let Some(encoding) = encunit.encoding() {
+                        layout.encodings.insert(format!("{:?}", encoding.type_()));
+                    }
+                    layout
+                        .compressions
+                        .insert(format!("{:?}", encunit.compression()));
+                }
+            }
+        }
+    }
+    Ok(FileLayout {
+        file_size,
+        row_group_row_counts,
+        columns,
+    })
+}
+
+fn join(set: &BTreeSet<String>) -> String {
+    set.iter().cloned().collect::<Vec<_>>().join(",")
+}
+
+fn diff_layouts(left: &FileLayout, right: &FileLayout) -> Vec<LayoutDifference> {
+    let mut res = vec![];
+    let mut push = |item: String, l: String, r: String| {
+        if l != r {
+            res.push(LayoutDifference {
+                item,
+                left: l,
+                right: r,
+            });
+        }
+    };
+    push(
+        "file size".to_string(),
+        left.file_size.to_string(),
+        right.file_size.to_string(),
+    );
+    push(
+        "row groups".to_string(),
+        format!("{:?}", left.row_group_row_counts),
+        format!("{:?}", right.row_group_row_counts),
+    );
+    push(
+        "physical columns".to_string(),
+        left.columns.len().to_string(),
+        right.columns.len().to_string(),
+    );
+    for (i, (l, r)) in left.columns.iter().zip(right.columns.iter()).enumerate() {
+        push(
+            format!("column {} chunks", i),
+            l.num_chunks.to_string(),
+            r.num_chunks.to_string(),
+        );
+        push(
+            format!("column {} encunits", i),
+            l.num_encunits.to_string(),
+            r.num_encunits.to_string(),
+        );
+        push(
+            format!("column {} size", i),
+            l.size.to_string(),
+            r.size.to_string(),
+        );
+        push(
+            format!("column {} encodings", i),
+            join(&l.encodings),
+            join(&r.encodings),
+        );
+        push(
+            format!("column {} compression", i),
+            join(&l.compressions),
+            join(&r.compressions),
+        );
+        push(
+            format!("column {} dictionary", i),
+            join(&l.dictionary_encodings),
+            join(&r.dictionary_encodings),
+        );
+    }
+    res
+}
+
+/// Checksum of the decoded values of a column, independent of how it is split into batches.
+/// Values are hashed row by row in the Arrow row format, which normalizes nulls and offsets.
+fn column_checksum(data_type: &DataType, batches: &[RecordBatch], idx: usize) -> Result<u64> {
+    let converter = RowConverter::new(vec![SortField::new(data_type.clone())])?;
+    let mut checksum = create_checksum(&ChecksumType::XxHash);
+    for batch in batches {
+        let rows = converter.convert_columns(&[batch.column(idx).clone()])?;
+        for row in rows.iter() {
+            let row = row.as_ref();
+            checksum.update(&(row.len() as u32).to_le_bytes());
+            checksum.update(row);
+        }
+    }
+    Ok(checksum.finalize())
+}
+
+fn read_all<R: Reader + Clone>(reader: R) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+    let mut reader = FileReaderV2Builder::new(reader).build()?;
+    let schema = reader.schema();
+    let batches = reader.read_file()?;
+    Ok((schema, batches))
+}
+
+/// Compare two F3 files. See the [module documentation](self).
+pub fn diff_files<R1: Reader + Clone, R2: Reader + Clone>(left: R1, right: R2) -> Result<FileDiff> {
+    let layout_differences = diff_layouts(&read_layout(&left)?, &read_layout(&right)?);
+    let (left_schema, left_batches) = read_all(left)?;
+    let (right_schema, right_batches) = read_all(right)?;
+
+    let mut differences = vec![];
+    let right_fields: BTreeMap<&str, usize> = right_schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(i, f)| (f.name().as_str(), i))
+        .collect();
+    let left_num_rows: usize = left_batches.iter().map(|b| b.num_rows()).sum();
+    let right_num_rows: usize = right_batches.iter().map(|b| b.num_rows()).sum();
+    if left_num_rows != right_num_rows {
+        differences.push(Difference::NumRows {
+            left: left_num_rows as u64,
+            right: right_num_rows as u64,
+        });
+    }
+    for (left_idx, left_field) in left_schema.fields().iter().enumerate() {
+        let name = left_field.name().clone();
+        let Some(&right_idx) = right_fields.get(name.as_str()) else {
+            differences.push(Difference::MissingField {
+                name,
+                in_left: true,
+            });
+            continue;
+        };
+        let right_field = right_schema.field(right_idx);
+        if left_field.data_type() != right_field.data_type() {
+            differences.push(Difference::FieldType {
+                name,
+                left: left_field.data_type().clone(),
+                right: right_field.data_type().clone(),
+            });
+            continue;
+        }
+        if left_field.is_nullable() != right_field.is_nullable() {
+            differences.push(Difference::Nullability {
+                name: name.clone(),
+                left: left_field.is_nullable(),
+                right: right_field.is_nullable(),
+            });
+        }
+        let left_checksum = column_checksum(left_field.data_type(), &left_batches, left_idx)?;
+        let right_checksum = column_checksum(right_field.data_type(), &right_batches, right_idx)?;
+        if left_checksum != right_checksum {
+            differences.push(Difference::ColumnData {
+                name,
+                left_checksum,
+                right_checksum,
+            });
+        }
+    }
+    for right_field in right_schema.fields() {
+        if left_schema.field_with_name(right_field.name()).is_err() {
+            differences.push(Difference::MissingField {
+                name: right_field.name().clone(),
+                in_left: false,
+            });
+        }
+    }
+    Ok(FileDiff {
+        differences,
+        layout_differences,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Seek;
+    use std::sync::Arc;
+
+    use arrow_array::{ArrayRef, Int32Array, StringArray};
+    use arrow_schema::{Field, Schema};
+
+    use super::*;
+    use crate::options::{FileWriterOptions, FileWriterOptionsBuilder};
+    use crate::writer::FileWriter;
+
+    fn write(batches: &[RecordBatch], options: FileWriterOptions) -> Arc<std::fs::File> {
+        let mut file = tempfile::tempfile().unwrap();
+        let mut writer = FileWriter::try_new(batches[0].schema(), &file, options).unwrap();
+        for batch in batches {
+            writer.write_batch(batch).unwrap();
+        }
+        writer.finish().unwrap();
+        file.rewind().unwrap();
+        Arc::new(file)
+    }
+
+    fn batch(a: Vec<Option<i32>>, b: Vec<&str>) -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Utf8, false),
+        ]);
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int32Array::from(a)) as ArrayRef,
+                Arc::new(StringArray::from(b)),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_diff_files() {
+        let input = batch(vec![Some(1), None, Some(3)], vec!["x", "y", "z"]);
+        let file = write(&[input.clone()], FileWriterOptions::default());
+        let diff = diff_files(file.clone(), file.clone()).unwrap();
+        assert!(diff.is_identical(), "{}", diff);
+
+        // Same data split into more batches and row groups.
+        let split = [input.slice(0, 1), input.slice(1, 2)];
+        let other = write(
+            &split,
+            FileWriterOptionsBuilder::with_defaults()
+                .set_row_group_size(1)
+                .build(),
+        );
+        let diff = diff_files(file.clone(), other).unwrap();
+        assert!(diff.is_semantically_equal(), "{}", diff);
+        assert!(diff
+            .layout_differences
+            .iter()
+            .any(|d| d.item == "row groups"));
+
+        let other = write(
+            &[batch(vec![Some(1), Some(2), Some(3)], vec!["x", "y", "z"])],
+            FileWriterOptions::default(),
+        );
+        let diff = diff_files(file.clone(), other).unwrap();
+        assert_eq!(
+            diff.differences
+                .iter()
+                .map(|d| match d {
+                    Difference::ColumnData { name, .. } => name.as_str(),
+                    _ => panic!("unexpected difference {}", d),
+                })
+                .collect::<Vec<_>>(),
+            vec!["a"]
+        );
+
+        let schema = Schema::new(vec![Field::new("c", DataType::Int32, false)]);
+        let c = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![Arc::new(Int32Array::from(vec![1, 2])) as ArrayRef],
+        )
+        .unwrap();
+        let other = write(&[c], FileWriterOptions::default());
+        let diff = diff_files(file, other).unwrap();
+        assert!(diff
+            .differences
+            .contains(&Difference::NumRows { left: 3, right: 2 }));
+        assert!(diff.differences.contains(&Difference::MissingField {
+            name: "a".to_string(),
+            in_left: true
+        }));
+        assert!(diff.differences.contains(&Difference::MissingField {
+            name: "c".to_string(),
+            in_left: false
+        }));
+        assert!(diff.to_string().contains("field \"c\" only in right file"));
+    }
+}
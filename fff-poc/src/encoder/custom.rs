@@ -1,5 +1,4 @@
 use std::path::PathBuf;
-use std::rc::Rc;
 use std::result::Result;
 use std::sync::Arc;
 
@@ -22,7 +21,7 @@ pub struct CustomEncoder {
 
 impl CustomEncoder {
     pub fn try_new(
-        lib_path: Rc<PathBuf>,
+        lib_path: Arc<PathBuf>,
         func_name: &str,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         Ok(Self {
@@ -1,7 +1,7 @@
 use bytes::Bytes;
 use fff_format::File::fff::flatbuf::CompressionType;
 
-use crate::file::footer;
+use crate::{common::statistics::MinMaxAccumulator, file::footer};
 
 /// Only used in `EncodedColumnChunk`
 #[derive(Clone)]
@@ -10,6 +10,11 @@ pub struct SerializedEncUnit {
     num_rows: u32,
     encoding: footer::Encoding,
     compression_type: CompressionType,
+    /// Size of the validity sub-buffer at the start of `bytes` before compression, if any.
+    validity_size: Option<u32>,
+    /// Untruncated zone map of the EncUnit, if the encoder tracks it. See
+    /// [`MinMaxAccumulator::update_zone_map`].
+    min_max: MinMaxAccumulator,
 }
 
 impl SerializedEncUnit {
@@ -24,9 +29,32 @@ impl SerializedEncUnit {
             num_rows,
             encoding,
             compression_type,
+            validity_size: None,
+            min_max: MinMaxAccumulator::default(),
         }
     }
 
+    pub fn with_validity_size(mut self, validity_size: Option<u32>) -> Self {
+        self.validity_size = validity_size;
+        self
+    }
+
+    pub fn with_min_max(mut self, min_max: MinMaxAccumulator) -> Self {
+        self.min_max = min_max;
+        self
+    }
+
+    /// Replace the bytes with the same EncUnit compressed as `compression_type`.
+    pub fn with_compressed_bytes(
+        mut self,
+        bytes: Bytes,
+        compression_type: CompressionType,
+    ) -> Self {
+        self.bytes = bytes;
+        self.compression_type = compression_type;
+        self
+    }
+
     pub fn bytes(&self) -> Bytes {
         self.bytes.clone()
     }
@@ -42,6 +70,14 @@ impl SerializedEncUnit {
     pub fn compression_type(&self) -> CompressionType {
         self.compression_type
     }
+
+    pub fn validity_size(&self) -> Option<u32> {
+        self.validity_size
+    }
+
+    pub fn min_max(&self) -> &MinMaxAccumulator {
+        &self.min_max
+    }
 }
 
 /// An encoded ColumnChunk, serves as an IO unit.
@@ -53,6 +89,12 @@ pub struct EncodedColumnChunk {
     pub dict_encoding: footer::DictionaryEncoding,
     /// The physical column index
     pub column_index: u32,
+    /// Number of nulls in the chunk. None if the encoder does not track it.
+    pub null_count: Option<u64>,
+    /// Untruncated min/max of the values in the chunk, if the encoder tracks them.
+    pub min_max: MinMaxAccumulator,
+    /// Offset of the chunk in the file it is copied from, if any.
+    pub source_offset: Option<u64>,
 }
 
 impl Default for EncodedColumnChunk {
@@ -74,6 +116,9 @@ pub struct EncodedColumnChunkBuilder {
     pub dict_encoding: footer::DictionaryEncoding,
     /// The physical column index
     pub column_index: u32,
+    pub null_count: Option<u64>,
+    pub min_max: MinMaxAccumulator,
+    pub source_offset: Option<u64>,
 }
 
 impl EncodedColumnChunkBuilder {
@@ -89,6 +134,9 @@ impl EncodedColumnChunkBuilder {
             num_rows: self.num_rows,
             dict_encoding: self.dict_encoding,
             column_index: self.column_index,
+            null_count: self.null_count,
+            min_max: self.min_max,
+            source_offset: self.source_offset,
         }
     }
 
@@ -99,6 +147,19 @@ impl EncodedColumnChunkBuilder {
 }
 
 impl EncodedColumnChunk {
+    /// Account the nulls of an array appended to this chunk.
+    pub fn add_null_count(&mut self, null_count: usize) {
+        *self.null_count.get_or_insert(0) += null_count as u64;
+    }
+
+    /// Size in bytes of the encoded EncUnits.
+    pub fn size(&self) -> u64 {
+        self.encunits
+            .iter()
+            .map(|unit| unit.bytes().len() as u64)
+            .sum()
+    }
+
     pub fn update_column_index(self, column_index: u32) -> Self {
         Self {
             column_index,
@@ -1,23 +1,38 @@
 use std::{rc::Rc, sync::Arc};
 
 use arrow_schema::DataType;
-use fff_encoding::schemes::{vortex::VortexEncoder, Encoder};
+use fff_encoding::schemes::{
+    adaptive::AdaptiveEncoder, boolean::BooleanEncoder, vortex::VortexEncoder, Encoder,
+};
 
 use crate::context::WASMWritingContext;
 
 use super::custom::CustomEncoder;
+use super::wasm::WasmEncUnitEncoder;
 
 /// Strategy to map physical DataType to EncUnit Encoder.
+/// Columns with a WASM encoder use it for all their EncUnits.
 /// List is using our custom ones since Vortex does not support it.
 /// List appears here because we encode offsets as a List of dummy values.
+/// Native encodings are not used when the built-in WASM must be able to decode every EncUnit.
+/// Otherwise, the encoding is chosen per EncUnit if adaptive encoding is enabled, and booleans
+/// use the native bit-packed encoding.
 pub fn create_encunit_encoder(
     wasm_context: Arc<WASMWritingContext>,
     data_type: DataType,
     enable_dict: bool,
 ) -> Rc<dyn Encoder> {
-    if let Some(lib) = wasm_context.data_type_to_wasm_lib(&data_type) {
+    if let Some(rt) = wasm_context.wasm_encoder() {
+        Rc::new(WasmEncUnitEncoder::new(rt))
+    } else if let Some(lib) = wasm_context.data_type_to_wasm_lib(&data_type) {
         // FIXME: function name is fixed as "encode"
         Rc::new(CustomEncoder::try_new(lib.encode_lib_path(), "encode").unwrap())
+    } else if wasm_context.always_set_custom_wasm_for_built_in() {
+        Rc::new(VortexEncoder::new(enable_dict))
+    } else if let Some(options) = wasm_context.adaptive_encoding() {
+        Rc::new(AdaptiveEncoder::new(options, enable_dict))
+    } else if data_type == DataType::Boolean {
+        Rc::new(BooleanEncoder)
     } else {
         Rc::new(VortexEncoder::new(enable_dict))
     }
@@ -5,7 +5,11 @@ use super::{
     physical::{self, create_physical_encoder, PhysicalColEncoder},
 };
 use crate::{
-    common::ColumnIndexSequence,
+    common::{
+        physical_type::{physical_type, to_physical},
+        ColumnIndexSequence,
+    },
+    compression::Compression,
     context::WASMWritingContext,
     counter::EncodingCounter,
     dict::{shared_dictionary_context::SharedDictionaryContext, DictionaryTypeOptions},
@@ -13,16 +17,17 @@ use crate::{
 use arrow_array::cast::AsArray;
 use arrow_array::Array;
 use arrow_array::ArrayRef;
-use arrow_array::{BooleanArray, Int32Array, Int64Array};
+use arrow_array::{BooleanArray, Int32Array, Int64Array, ListArray};
 use arrow_buffer::BooleanBuffer;
 use arrow_schema::{DataType, FieldRef};
-use fff_core::{errors::Result, non_nest_types};
+use fff_core::{errors::Result, non_nest_types, nyi_err};
 use fff_format::{File::fff::flatbuf as fb, ToFlatBuffer};
 use flatbuffers::{FlatBufferBuilder, WIPOffset};
 
 /// This maps to each logical column (i.e, field) in the Arrow schema.
 /// It emits multiple of EncodedColumnChunk because the logical column may map to multiple physical columns.
-pub trait LogicalColEncoder {
+/// See [`PhysicalColEncoder`] for why encoders are `Send`.
+pub trait LogicalColEncoder: Send {
     fn encode(
         &mut self,
         array: ArrayRef,
@@ -32,6 +37,9 @@ pub trait LogicalColEncoder {
 
     fn memory_size(&self) -> usize;
 
+    /// See [PhysicalColEncoder::set_column_chunk_size], applied to all the physical columns.
+    fn set_column_chunk_size(&mut self, column_chunk_size: u64);
+
     fn finish(
         &mut self,
         counter: &mut EncodingCounter,
@@ -39,6 +47,9 @@ pub trait LogicalColEncoder {
     ) -> Result<Option<Vec<EncodedColumnChunk>>>;
 
     fn submit_dict(&mut self, shared_dict_ctx: &mut SharedDictionaryContext) -> Result<()>;
+
+    /// See [PhysicalColEncoder::spill].
+    fn spill(&mut self) -> Result<Option<Vec<EncodedColumnChunk>>>;
 }
 
 #[derive(Debug)]
@@ -70,6 +81,7 @@ impl ToFlatBuffer for LogicalTree {
 }
 
 /// For data types that are not List or Struct.
+/// Arrays are converted to the [`physical_type`] of their column first.
 pub struct FlatColEncoder {
     data_encoder: Box<dyn PhysicalColEncoder>,
     column_index: u32,
@@ -83,7 +95,10 @@ impl LogicalColEncoder for FlatColEncoder {
         shared_dict_ctx: &mut SharedDictionaryContext,
     ) -> Result<Option<Vec<EncodedColumnChunk>>> {
         let mut res = vec![];
-        for data_chunk in self.data_encoder.encode(array, counter, shared_dict_ctx)? {
+        for data_chunk in self
+            .data_encoder
+            .encode(to_physical(array)?, counter, shared_dict_ctx)?
+        {
             res.push(data_chunk.update_column_index(self.column_index));
         }
         Ok((!res.is_empty()).then_some(res))
@@ -93,6 +108,10 @@ impl LogicalColEncoder for FlatColEncoder {
         self.data_encoder.memory_size()
     }
 
+    fn set_column_chunk_size(&mut self, column_chunk_size: u64) {
+        self.data_encoder.set_column_chunk_size(column_chunk_size)
+    }
+
     fn finish(
         &mut self,
         counter: &mut EncodingCounter,
@@ -108,6 +127,14 @@ impl LogicalColEncoder for FlatColEncoder {
     fn submit_dict(&mut self, shared_dict_ctx: &mut SharedDictionaryContext) -> Result<()> {
         self.data_encoder.submit_dict(shared_dict_ctx)
     }
+
+    fn spill(&mut self) -> Result<Option<Vec<EncodedColumnChunk>>> {
+        let mut res = vec![];
+        for data_chunk in self.data_encoder.spill()? {
+            res.push(data_chunk.update_column_index(self.column_index));
+        }
+        Ok((!res.is_empty()).then_some(res))
+    }
 }
 
 pub struct ListColEncoder {
@@ -126,6 +153,11 @@ impl LogicalColEncoder for ListColEncoder {
         shared_dict_ctx: &mut SharedDictionaryContext,
     ) -> Result<Option<Vec<EncodedColumnChunk>>> {
         let mut res = vec![];
+        // Maps are encoded like Lists of their entries.
+        let array = match array.data_type() {
+            DataType::Map(_, _) => map_to_list(&array),
+            _ => array,
+        };
         // simply encode the validity and offsets buffers in this Array.
         for offsets_chunk in
             self.offsets_encoder
@@ -146,6 +178,12 @@ impl LogicalColEncoder for ListColEncoder {
         self.offsets_encoder.memory_size() + self.values_encoder.memory_size()
     }
 
+    fn set_column_chunk_size(&mut self, column_chunk_size: u64) {
+        self.offsets_encoder
+            .set_column_chunk_size(column_chunk_size);
+        self.values_encoder.set_column_chunk_size(column_chunk_size);
+    }
+
     fn finish(
         &mut self,
         counter: &mut EncodingCounter,
@@ -165,6 +203,17 @@ impl LogicalColEncoder for ListColEncoder {
         self.offsets_encoder.submit_dict(shared_dict_ctx)?;
         self.values_encoder.submit_dict(shared_dict_ctx)
     }
+
+    fn spill(&mut self) -> Result<Option<Vec<EncodedColumnChunk>>> {
+        let mut res = vec![];
+        for offsets_chunk in self.offsets_encoder.spill()? {
+            res.push(offsets_chunk.update_column_index(self.column_index));
+        }
+        if let Some(values_chunks) = self.values_encoder.spill()? {
+            res.extend(values_chunks);
+        }
+        Ok((!res.is_empty()).then_some(res))
+    }
 }
 
 pub struct ListOfStructOfPrimitiveColEncoder {
@@ -222,6 +271,12 @@ impl LogicalColEncoder for ListOfStructOfPrimitiveColEncoder {
         })
     }
 
+    fn set_column_chunk_size(&mut self, column_chunk_size: u64) {
+        for field_encoder in self.fields_encoders.iter_mut() {
+            field_encoder.set_column_chunk_size(column_chunk_size);
+        }
+    }
+
     fn finish(
         &mut self,
         _counter: &mut EncodingCounter,
@@ -239,6 +294,17 @@ impl LogicalColEncoder for ListOfStructOfPrimitiveColEncoder {
     fn submit_dict(&mut self, _shared_dict_ctx: &mut SharedDictionaryContext) -> Result<()> {
         Ok(())
     }
+
+    /// Flush the accumulated chunks early.
+    fn spill(&mut self) -> Result<Option<Vec<EncodedColumnChunk>>> {
+        let mut res = vec![];
+        for (field_encoder, col_idx) in self.fields_encoders.iter_mut().zip(&self.column_indexes) {
+            if let Some(field_chunk) = field_encoder.finish()? {
+                res.push(field_chunk.update_column_index(*col_idx));
+            }
+        }
+        Ok((!res.is_empty()).then_some(res))
+    }
 }
 
 pub struct StructColEncoder {
@@ -285,6 +351,14 @@ impl LogicalColEncoder for StructColEncoder {
                 .sum::<usize>()
     }
 
+    fn set_column_chunk_size(&mut self, column_chunk_size: u64) {
+        self.validity_encoder
+            .set_column_chunk_size(column_chunk_size);
+        for field_encoder in self.fields_encoders.iter_mut() {
+            field_encoder.set_column_chunk_size(column_chunk_size);
+        }
+    }
+
     fn finish(
         &mut self,
         counter: &mut EncodingCounter,
@@ -309,6 +383,93 @@ impl LogicalColEncoder for StructColEncoder {
         }
         Ok(())
     }
+
+    fn spill(&mut self) -> Result<Option<Vec<EncodedColumnChunk>>> {
+        let mut res = vec![];
+        for validity_chunk in self.validity_encoder.spill()? {
+            res.push(validity_chunk.update_column_index(self.column_index));
+        }
+        for field_encoder in self.fields_encoders.iter_mut() {
+            if let Some(field_chunks) = field_encoder.spill()? {
+                res.extend(field_chunks);
+            }
+        }
+        Ok((!res.is_empty()).then_some(res))
+    }
+}
+
+/// Encodes the validity of the lists, then the values of all the lists as a single child column.
+pub struct FixedSizeListColEncoder {
+    validity_encoder: Box<dyn PhysicalColEncoder>,
+    /// This column index is for validity column.
+    column_index: u32,
+    values_encoder: Box<dyn LogicalColEncoder>,
+}
+
+impl LogicalColEncoder for FixedSizeListColEncoder {
+    fn encode(
+        &mut self,
+        array: ArrayRef,
+        counter: &mut EncodingCounter,
+        shared_dict_ctx: &mut SharedDictionaryContext,
+    ) -> Result<Option<Vec<EncodedColumnChunk>>> {
+        let mut res = vec![];
+        for validity_chunk in
+            self.validity_encoder
+                .encode(extract_validity(&array), counter, shared_dict_ctx)?
+        {
+            res.push(validity_chunk.update_column_index(self.column_index));
+        }
+        let values = Arc::clone(array.as_fixed_size_list().values());
+        if let Some(values_chunks) = self
+            .values_encoder
+            .encode(values, counter, shared_dict_ctx)?
+        {
+            res.extend(values_chunks);
+        }
+        Ok((!res.is_empty()).then_some(res))
+    }
+
+    fn memory_size(&self) -> usize {
+        self.validity_encoder.memory_size() + self.values_encoder.memory_size()
+    }
+
+    fn set_column_chunk_size(&mut self, column_chunk_size: u64) {
+        self.validity_encoder
+            .set_column_chunk_size(column_chunk_size);
+        self.values_encoder.set_column_chunk_size(column_chunk_size);
+    }
+
+    fn finish(
+        &mut self,
+        counter: &mut EncodingCounter,
+        shared_dict_ctx: &mut SharedDictionaryContext,
+    ) -> Result<Option<Vec<EncodedColumnChunk>>> {
+        let mut res = vec![];
+        for validity_chunk in self.validity_encoder.finish(counter, shared_dict_ctx)? {
+            res.push(validity_chunk.update_column_index(self.column_index));
+        }
+        if let Some(values_chunks) = self.values_encoder.finish(counter, shared_dict_ctx)? {
+            res.extend(values_chunks);
+        }
+        Ok((!res.is_empty()).then_some(res))
+    }
+
+    fn submit_dict(&mut self, shared_dict_ctx: &mut SharedDictionaryContext) -> Result<()> {
+        self.validity_encoder.submit_dict(shared_dict_ctx)?;
+        self.values_encoder.submit_dict(shared_dict_ctx)
+    }
+
+    fn spill(&mut self) -> Result<Option<Vec<EncodedColumnChunk>>> {
+        let mut res = vec![];
+        for validity_chunk in self.validity_encoder.spill()? {
+            res.push(validity_chunk.update_column_index(self.column_index));
+        }
+        if let Some(values_chunks) = self.values_encoder.spill()? {
+            res.extend(values_chunks);
+        }
+        Ok((!res.is_empty()).then_some(res))
+    }
 }
 
 #[allow(clippy::only_used_in_recursion)]
@@ -319,18 +480,19 @@ pub fn create_logical_encoder(
     column_idx: &mut ColumnIndexSequence,
     wasm_context: Arc<WASMWritingContext>,
     dictionary_type: DictionaryTypeOptions,
-    compression_type: fb::CompressionType,
+    compression: Compression,
 ) -> Result<(Box<dyn LogicalColEncoder>, LogicalTree)> {
     match field.data_type() {
-        non_nest_types!() => Ok((
+        // Including dictionaries of flat values and other types stored as a flat physical type.
+        data_type if matches!(physical_type(data_type), non_nest_types!()) => Ok((
             Box::new(FlatColEncoder {
                 data_encoder: create_physical_encoder(
-                    field.data_type(),
+                    &physical_type(data_type),
                     max_chunk_size,
                     field.is_nullable(),
                     wasm_context,
                     dictionary_type,
-                    compression_type,
+                    compression,
                 )?,
                 column_index: column_idx.next_column_index(),
             }),
@@ -354,7 +516,7 @@ pub fn create_logical_encoder(
                                     physical::ListOfStructColEncoder::new(
                                         max_chunk_size,
                                         wasm_context.clone(),
-                                        compression_type,
+                                        compression,
                                     )
                                 })
                                 .collect(),
@@ -374,7 +536,7 @@ pub fn create_logical_encoder(
                         field.is_nullable(),
                         wasm_context.clone(),
                         dictionary_type,
-                        compression_type,
+                        compression,
                     )?;
                     let (values_encoder, child_tree) = create_logical_encoder(
                         Arc::clone(child),
@@ -383,7 +545,7 @@ pub fn create_logical_encoder(
                         column_idx,
                         wasm_context,
                         dictionary_type,
-                        compression_type,
+                        compression,
                     )?;
                     Ok((
                         Box::new(ListColEncoder {
@@ -408,7 +570,7 @@ pub fn create_logical_encoder(
                     column_idx,
                     wasm_context.clone(),
                     dictionary_type,
-                    compression_type,
+                    compression,
                 )?;
                 fields_encoders.push(enc);
                 child_trees.push(child_tree);
@@ -421,7 +583,7 @@ pub fn create_logical_encoder(
                         false,
                         wasm_context.clone(),
                         dictionary_type,
-                        compression_type,
+                        compression,
                     )?,
                     column_index: validity_index,
                     fields_encoders,
@@ -429,15 +591,108 @@ pub fn create_logical_encoder(
                 LogicalTree::new(fb::LogicalId::STRUCT, child_trees),
             ))
         }
-        DataType::Map(_, _) => {
-            todo!("implement map")
+        DataType::Map(entries, _) => {
+            let offsets_validity_index = column_idx.next_column_index();
+            let offsets_encoder = create_physical_encoder(
+                &DataType::List(Arc::clone(entries)),
+                max_chunk_size,
+                field.is_nullable(),
+                wasm_context.clone(),
+                dictionary_type,
+                compression,
+            )?;
+            let (values_encoder, child_tree) = create_logical_encoder(
+                Arc::clone(entries),
+                field_id,
+                max_chunk_size,
+                column_idx,
+                wasm_context,
+                dictionary_type,
+                compression,
+            )?;
+            Ok((
+                Box::new(ListColEncoder {
+                    offsets_encoder,
+                    column_index: offsets_validity_index,
+                    values_encoder,
+                }),
+                LogicalTree::new(fb::LogicalId::MAP, vec![child_tree]),
+            ))
         }
-        _ => {
-            todo!("Implement logical encoding for field {}", field)
+        DataType::FixedSizeList(child, _) => {
+            let validity_index = column_idx.next_column_index();
+            let validity_encoder = create_physical_encoder(
+                &DataType::Boolean,
+                max_chunk_size,
+                false,
+                wasm_context.clone(),
+                dictionary_type,
+                compression,
+            )?;
+            let (values_encoder, child_tree) = create_logical_encoder(
+                Arc::clone(child),
+                field_id,
+                max_chunk_size,
+                column_idx,
+                wasm_context,
+                dictionary_type,
+                compression,
+            )?;
+            Ok((
+                Box::new(FixedSizeListColEncoder {
+                    validity_encoder,
+                    column_index: validity_index,
+                    values_encoder,
+                }),
+                LogicalTree::new(fb::LogicalId::FIXED_SIZE_LIST, vec![child_tree]),
+            ))
         }
+        _ => nyi_err!(format!("Logical encoding of field {}", field)),
     }
 }
 
+/// Number of physical columns [`create_logical_encoder`] assigns to a field of `data_type`.
+pub fn num_physical_columns(data_type: &DataType) -> usize {
+    match data_type {
+        data_type if matches!(physical_type(data_type), non_nest_types!()) => 1,
+        DataType::List(child) | DataType::LargeList(child) => match child.data_type() {
+            DataType::Struct(fields)
+                if fields
+                    .iter()
+                    .all(|f| matches!(f.data_type(), non_nest_types!()))
+                    && cfg!(feature = "list-offsets-pushdown") =>
+            {
+                fields.len()
+            }
+            child_type => 1 + num_physical_columns(child_type),
+        },
+        DataType::Struct(fields) => {
+            1 + fields
+                .iter()
+                .map(|f| num_physical_columns(f.data_type()))
+                .sum::<usize>()
+        }
+        DataType::Map(child, _) | DataType::FixedSizeList(child, _) => {
+            1 + num_physical_columns(child.data_type())
+        }
+        _ => 0,
+    }
+}
+
+/// View a map array as the List of its entries, sharing its buffers.
+fn map_to_list(map_arr: &dyn Array) -> ArrayRef {
+    let map_arr = map_arr.as_map();
+    let DataType::Map(entries, _) = map_arr.data_type() else {
+        unreachable!()
+    };
+    Arc::new(ListArray::new(
+        Arc::clone(entries),
+        map_arr.offsets().clone(),
+        Arc::new(map_arr.entries().clone()),
+        map_arr.nulls().cloned(),
+    ))
+}
+
 fn extract_items(list_arr: &dyn Array) -> ArrayRef {
     match list_arr.data_type() {
         DataType::List(_) => {
@@ -526,7 +781,7 @@ mod tests {
             &mut ColumnIndexSequence::default(),
             Arc::new(WASMWritingContext::empty()),
             DictionaryTypeOptions::EncoderDictionary,
-            fb::CompressionType::Uncompressed,
+            Compression::default(),
         )
         .unwrap()
         .0;
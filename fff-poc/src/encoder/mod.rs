@@ -3,3 +3,5 @@ pub mod encoded_column_chunk;
 pub(super) mod encunit;
 pub mod logical;
 pub mod physical;
+pub(crate) mod spill;
+mod wasm;
@@ -1,7 +1,8 @@
 use std::{io::Cursor, sync::Arc};
 
 use crate::{
-    compression::compress_data,
+    common::statistics::{sortable_key_width, MinMaxAccumulator},
+    compression::{compress_data, Compression},
     context::WASMWritingContext,
     counter::EncodingCounter,
     dict::{shared_dictionary_context::SharedDictionaryContext, Dictionary, DictionaryTypeOptions},
@@ -19,12 +20,15 @@ use rand::seq::IteratorRandom;
 use super::{
     encoded_column_chunk::{EncodedColumnChunk, SerializedEncUnit},
     encunit::create_encunit_encoder,
+    spill::SpilledArrays,
 };
 
-use fff_encoding::schemes::{encode_to_bytes, vortex::VortexEncoder, Encoder};
+use fff_encoding::schemes::{encode_to_bytes_with_validity, vortex::VortexEncoder, Encoder};
 
 /// This level handles using dictionary or not.
-pub trait PhysicalColEncoder {
+/// Encoders are `Send` so that columns can be encoded on other threads, see
+/// [`FileWriterOptions::encoding_parallelism`](crate::options::FileWriterOptions::encoding_parallelism).
+pub trait PhysicalColEncoder: Send {
     /// TODO: Let us only consider sync writing.
     fn encode(
         &mut self,
@@ -36,6 +40,9 @@ pub trait PhysicalColEncoder {
     /// Return the size of the accumulated chunk in this encoder.
     fn memory_size(&self) -> usize;
 
+    /// Change the size after which the accumulated chunk is flushed, from the next chunk on.
+    fn set_column_chunk_size(&mut self, column_chunk_size: u64);
+
     fn finish(
         &mut self,
         counter: &mut EncodingCounter,
@@ -43,6 +50,12 @@ pub trait PhysicalColEncoder {
     ) -> Result<Vec<EncodedColumnChunk>>;
 
     fn submit_dict(&mut self, shared_dict_ctx: &mut SharedDictionaryContext) -> Result<()>;
+
+    /// Release the memory held by the encoder when the writer exceeds its memory budget.
+    /// Returns the chunks flushed early, if any. Does nothing by default.
+    fn spill(&mut self) -> Result<Vec<EncodedColumnChunk>> {
+        Ok(vec![])
+    }
 }
 
 /// A specific experimental encoder for testing List of Struct of non nest types.
@@ -53,14 +66,14 @@ pub struct ListOfStructColEncoder {
     /// The desired encoded column chunk size, should match I/O unit size (e.g., 8MB on S3)
     column_chunk_size: u64,
     wasm_context: Arc<WASMWritingContext>,
-    compression_type: fb::CompressionType,
+    compression: Compression,
 }
 
 impl ListOfStructColEncoder {
     pub fn new(
         column_chunk_size: u64,
         wasm_context: Arc<WASMWritingContext>,
-        compression_type: fb::CompressionType,
+        compression: Compression,
     ) -> Self {
         Self {
             accumulated_chunk: EncodedColumnChunk::builder()
@@ -69,7 +82,7 @@ impl ListOfStructColEncoder {
             accumulated_size: 0,
             column_chunk_size,
             wasm_context,
-            compression_type,
+            compression,
         }
     }
 
@@ -77,6 +90,10 @@ impl ListOfStructColEncoder {
         self.accumulated_size as usize
     }
 
+    pub fn set_column_chunk_size(&mut self, column_chunk_size: u64) {
+        self.column_chunk_size = column_chunk_size;
+    }
+
     pub fn encode(
         &mut self,
         list_array: ArrayRef,
@@ -95,7 +112,7 @@ impl ListOfStructColEncoder {
         };
 
         // Compress the data if compression is enabled
-        let compressed_enc_unit = compress_data(enc_unit, self.compression_type)?;
+        let (compressed_enc_unit, compression_type) = compress_data(enc_unit, self.compression)?;
         let compressed_size = compressed_enc_unit.len() as u64;
 
         self.accumulated_size += compressed_size;
@@ -119,7 +136,7 @@ impl ListOfStructColEncoder {
                     .map(|id| WASMEncoding::new(id.0, Vec::new())),
                 )?
             },
-            self.compression_type,
+            compression_type,
         ));
         self.accumulated_chunk.num_rows += list_len;
         if self.accumulated_size > self.column_chunk_size {
@@ -148,7 +165,7 @@ pub struct EncoderDictColEncoder {
     column_chunk_size: u64,
     wasm_context: Arc<WASMWritingContext>,
     enable_dict: bool,
-    compression_type: fb::CompressionType,
+    compression: Compression,
 }
 
 impl EncoderDictColEncoder {
@@ -156,7 +173,7 @@ impl EncoderDictColEncoder {
         column_chunk_size: u64,
         wasm_context: Arc<WASMWritingContext>,
         enable_dict: bool,
-        compression_type: fb::CompressionType,
+        compression: Compression,
     ) -> Self {
         Self {
             accumulated_chunk: EncodedColumnChunk::builder()
@@ -166,7 +183,7 @@ impl EncoderDictColEncoder {
             column_chunk_size,
             wasm_context,
             enable_dict,
-            compression_type,
+            compression,
         }
     }
 }
@@ -183,21 +200,23 @@ impl PhysicalColEncoder for EncoderDictColEncoder {
             array.data_type().clone(),
             self.enable_dict,
         );
-        let enc_unit = encode_to_bytes(encoder.clone(), array.clone());
+        let (enc_unit, validity_size, encoding_type) =
+            encode_to_bytes_with_validity(encoder.clone(), array.clone())?;
 
         // Compress the data if compression is enabled
-        let compressed_enc_unit = compress_data(enc_unit, self.compression_type)?;
+        let (compressed_enc_unit, compression_type) = compress_data(enc_unit, self.compression)?;
         let compressed_size = compressed_enc_unit.len() as u64;
 
         // Update accumulated size with compressed size
         self.accumulated_size += compressed_size;
         counter.index_size += compressed_enc_unit.len();
+        let mut zone_map = MinMaxAccumulator::default();
+        zone_map.update_zone_map(array.as_ref());
 
-        self.accumulated_chunk.encunits.push(SerializedEncUnit::new(
-            compressed_enc_unit,
-            array.len() as u32,
-            {
-                let encoding_type = encoder.encoding_type();
+        self.accumulated_chunk.encunits.push(
+            SerializedEncUnit::new(
+                compressed_enc_unit,
+                array.len() as u32,
                 footer::Encoding::try_new(
                     if self.wasm_context.always_set_custom_wasm_for_built_in() {
                         fb::EncodingType::CUSTOM_WASM
@@ -210,11 +229,18 @@ impl PhysicalColEncoder for EncoderDictColEncoder {
                         self.wasm_context.data_type_to_wasm_id(array.data_type())
                     }
                     .map(|id| WASMEncoding::new(id.0, Vec::new())),
-                )?
-            },
-            self.compression_type,
-        ));
+                )?,
+                compression_type,
+            )
+            .with_validity_size(validity_size)
+            .with_min_max(zone_map.clone()),
+        );
         self.accumulated_chunk.num_rows += array.len();
+        self.accumulated_chunk.add_null_count(array.null_count());
+        // Chunk statistics only cover binary and string values so far.
+        if sortable_key_width(array.data_type()).is_none() {
+            self.accumulated_chunk.min_max.merge(&zone_map);
+        }
         if self.accumulated_size > self.column_chunk_size {
             let chunk = std::mem::take(&mut self.accumulated_chunk);
             self.accumulated_size = 0;
@@ -228,12 +254,25 @@ impl PhysicalColEncoder for EncoderDictColEncoder {
         self.accumulated_size as usize
     }
 
+    fn set_column_chunk_size(&mut self, column_chunk_size: u64) {
+        self.column_chunk_size = column_chunk_size;
+    }
+
     fn finish(
         &mut self,
         counter: &mut EncodingCounter,
         _shared_dict_ctx: &mut SharedDictionaryContext,
     ) -> Result<Vec<EncodedColumnChunk>> {
         counter.dict_type = DictionaryTypeOptions::EncoderDictionary;
+        self.spill()
+    }
+
+    fn submit_dict(&mut self, _shared_dict_ctx: &mut SharedDictionaryContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Flush the accumulated chunk early, as a smaller IO unit.
+    fn spill(&mut self) -> Result<Vec<EncodedColumnChunk>> {
         match self.accumulated_chunk.encunits.len() {
             0 => Ok(vec![]),
             _ => {
@@ -242,10 +281,6 @@ impl PhysicalColEncoder for EncoderDictColEncoder {
             }
         }
     }
-
-    fn submit_dict(&mut self, _shared_dict_ctx: &mut SharedDictionaryContext) -> Result<()> {
-        Ok(())
-    }
     //  /// Note: deprecated encode function when null info is explicitly managed.
     // /// If Nullable, each time we flush a validity page first and then data page.
     // fn encode(&mut self, array: ArrayRef) -> Result<Option<EncodedColumnChunk>> {
@@ -360,14 +395,14 @@ pub struct DictColEncoder {
     /// The desired encoded column chunk size, should match I/O unit size (e.g., 8MB on S3)
     column_chunk_size: u64,
     wasm_context: Arc<WASMWritingContext>,
-    compression_type: fb::CompressionType,
+    compression: Compression,
 }
 
 impl DictColEncoder {
     pub fn new(
         column_chunk_size: u64,
         wasm_context: Arc<WASMWritingContext>,
-        compression_type: fb::CompressionType,
+        compression: Compression,
     ) -> Self {
         Self {
             accumulated_chunk: EncodedColumnChunk::builder()
@@ -381,7 +416,7 @@ impl DictColEncoder {
             accumulated_size: 0,
             column_chunk_size,
             wasm_context,
-            compression_type,
+            compression,
         }
     }
 }
@@ -394,6 +429,8 @@ impl PhysicalColEncoder for DictColEncoder {
         _shared_dict_ctx: &mut SharedDictionaryContext,
     ) -> Result<Vec<EncodedColumnChunk>> {
         let dtype = array.data_type().clone();
+        let null_count = array.null_count();
+        self.accumulated_chunk.min_max.update(array.as_ref());
         let mut dict = Dictionary::try_new(dtype.clone())?;
         dict.extend(array)?;
         let (dict, indices) = dict.finish()?;
@@ -401,17 +438,21 @@ impl PhysicalColEncoder for DictColEncoder {
         let indices_dtype = indices.data_type().clone();
         let dict_encoder =
             create_encunit_encoder(self.wasm_context.clone(), dict.data_type().clone(), false);
-        let dict_enc_unit = encode_to_bytes(dict_encoder.clone(), dict.clone());
+        let (dict_enc_unit, dict_validity_size, dict_encoding_type) =
+            encode_to_bytes_with_validity(dict_encoder.clone(), dict.clone())?;
         let indices_encoder = create_encunit_encoder(
             self.wasm_context.clone(),
             indices.data_type().clone(),
             false,
         );
-        let indices_enc_unit = encode_to_bytes(indices_encoder.clone(), indices.clone());
+        let (indices_enc_unit, indices_validity_size, indices_encoding_type) =
+            encode_to_bytes_with_validity(indices_encoder.clone(), indices.clone())?;
 
         // Compress the dictionary data if compression is enabled
-        let compressed_dict_enc_unit = compress_data(dict_enc_unit, self.compression_type)?;
-        let compressed_indices_enc_unit = compress_data(indices_enc_unit, self.compression_type)?;
+        let (compressed_dict_enc_unit, dict_compression_type) =
+            compress_data(dict_enc_unit, self.compression)?;
+        let (compressed_indices_enc_unit, indices_compression_type) =
+            compress_data(indices_enc_unit, self.compression)?;
 
         let dict_compressed_size = compressed_dict_enc_unit.len() as u64;
         let indices_compressed_size = compressed_indices_enc_unit.len() as u64;
@@ -421,17 +462,16 @@ impl PhysicalColEncoder for DictColEncoder {
         counter.dict_size += compressed_dict_enc_unit.len();
         counter.index_size += compressed_indices_enc_unit.len();
 
-        self.accumulated_chunk.encunits.push(SerializedEncUnit::new(
-            compressed_dict_enc_unit,
-            // TODO: be careful this num_rows does not correspond to the original table
-            dict.len() as u32,
-            {
-                let encoding_type = dict_encoder.encoding_type();
+        self.accumulated_chunk.encunits.push(
+            SerializedEncUnit::new(
+                compressed_dict_enc_unit,
+                // TODO: be careful this num_rows does not correspond to the original table
+                dict.len() as u32,
                 footer::Encoding::try_new(
                     if self.wasm_context.always_set_custom_wasm_for_built_in() {
                         fb::EncodingType::CUSTOM_WASM
                     } else {
-                        encoding_type.to_fbs_encoding()
+                        dict_encoding_type.to_fbs_encoding()
                     },
                     if self.wasm_context.always_set_custom_wasm_for_built_in() {
                         self.wasm_context.builtin_wasm_id()
@@ -439,20 +479,20 @@ impl PhysicalColEncoder for DictColEncoder {
                         self.wasm_context.data_type_to_wasm_id(&dtype)
                     }
                     .map(|id| WASMEncoding::new(id.0, Vec::new())),
-                )?
-            },
-            self.compression_type,
-        ));
-        self.accumulated_chunk.encunits.push(SerializedEncUnit::new(
-            compressed_indices_enc_unit,
-            indices.len() as u32,
-            {
-                let encoding_type = indices_encoder.encoding_type();
+                )?,
+                dict_compression_type,
+            )
+            .with_validity_size(dict_validity_size),
+        );
+        self.accumulated_chunk.encunits.push(
+            SerializedEncUnit::new(
+                compressed_indices_enc_unit,
+                indices.len() as u32,
                 footer::Encoding::try_new(
                     if self.wasm_context.always_set_custom_wasm_for_built_in() {
                         fb::EncodingType::CUSTOM_WASM
                     } else {
-                        encoding_type.to_fbs_encoding()
+                        indices_encoding_type.to_fbs_encoding()
                     },
                     if self.wasm_context.always_set_custom_wasm_for_built_in() {
                         self.wasm_context.builtin_wasm_id()
@@ -460,11 +500,13 @@ impl PhysicalColEncoder for DictColEncoder {
                         self.wasm_context.data_type_to_wasm_id(&indices_dtype)
                     }
                     .map(|id| WASMEncoding::new(id.0, Vec::new())),
-                )?
-            },
-            self.compression_type,
-        ));
+                )?,
+                indices_compression_type,
+            )
+            .with_validity_size(indices_validity_size),
+        );
         self.accumulated_chunk.num_rows += indices.len() as usize;
+        self.accumulated_chunk.add_null_count(null_count);
         if self.accumulated_size > self.column_chunk_size {
             self.accumulated_size = 0;
             let chunk = std::mem::take(&mut self.accumulated_chunk);
@@ -479,6 +521,10 @@ impl PhysicalColEncoder for DictColEncoder {
         self.accumulated_size as usize
     }
 
+    fn set_column_chunk_size(&mut self, column_chunk_size: u64) {
+        self.column_chunk_size = column_chunk_size;
+    }
+
     fn finish(
         &mut self,
         counter: &mut EncodingCounter,
@@ -494,11 +540,27 @@ impl PhysicalColEncoder for DictColEncoder {
     fn submit_dict(&mut self, _shared_dict_ctx: &mut SharedDictionaryContext) -> Result<()> {
         Ok(())
     }
+
+    /// Flush the accumulated chunk early, as a smaller IO unit.
+    fn spill(&mut self) -> Result<Vec<EncodedColumnChunk>> {
+        match self.accumulated_chunk.encunits.len() {
+            0 => Ok(vec![]),
+            _ => {
+                self.accumulated_size = 0;
+                let chunk = std::mem::take(&mut self.accumulated_chunk);
+                self.accumulated_chunk.dict_encoding =
+                    footer::DictionaryEncoding::Dictionary(vec![]);
+                Ok(vec![chunk])
+            }
+        }
+    }
 }
 
 /// Shared dictionaries are used.
 pub struct SharedDictColEncoder {
     fixed_dict_scope: u64,
+    /// Arrays of the current dict scope spilled to disk, they precede `buffered_arrays`.
+    spilled_arrays: Option<SpilledArrays>,
     buffered_arrays: Vec<ArrayRef>,
     buffered_array_len: u64,
     buffered_array_mem_size: usize,
@@ -506,7 +568,7 @@ pub struct SharedDictColEncoder {
     column_chunk_size: u64,
     wasm_context: Arc<WASMWritingContext>,
     submitted_dict_idx: Option<u32>,
-    compression_type: fb::CompressionType,
+    compression: Compression,
 }
 
 impl SharedDictColEncoder {
@@ -514,17 +576,28 @@ impl SharedDictColEncoder {
         fixed_dict_scope: u64,
         column_chunk_size: u64,
         wasm_context: Arc<WASMWritingContext>,
-        compression_type: fb::CompressionType,
+        compression: Compression,
     ) -> Self {
         Self {
             fixed_dict_scope,
+            spilled_arrays: None,
             buffered_arrays: vec![],
             buffered_array_len: 0,
             buffered_array_mem_size: 0,
             column_chunk_size,
             wasm_context,
             submitted_dict_idx: None,
-            compression_type,
+            compression,
+        }
+    }
+
+    fn buffered_data_type(&self) -> Option<DataType> {
+        match &self.spilled_arrays {
+            Some(spilled) => Some(spilled.data_type().clone()),
+            None => self
+                .buffered_arrays
+                .first()
+                .map(|arr| arr.data_type().clone()),
         }
     }
 
@@ -533,17 +606,24 @@ impl SharedDictColEncoder {
         counter: &mut EncodingCounter,
         shared_dict_ctx: &mut SharedDictionaryContext,
     ) -> Result<Vec<EncodedColumnChunk>> {
+        let data_type = self.buffered_data_type().unwrap();
+        let mut spilled_arrs = self.spilled_arrays.take();
         let buffered_arrs = std::mem::take(&mut self.buffered_arrays);
         self.buffered_array_len = 0;
         self.buffered_array_mem_size = 0;
         let dict_idx = match self.submitted_dict_idx {
-            Some(idx) => idx,
-            None => shared_dict_ctx
-                .new_dictionary(buffered_arrs.first().unwrap().data_type().clone())?,
+            Some(idx) => shared_dict_ctx.resolve_dict_idx(idx),
+            None => shared_dict_ctx.new_dictionary(data_type)?,
         };
-        let indices_arrs = buffered_arrs
+        // Spilled arrays are read back one at a time, only their indices are kept in memory.
+        let indices_arrs = spilled_arrs
+            .as_mut()
+            .map(|spilled| spilled.read())
+            .transpose()?
             .into_iter()
-            .map(|arr| shared_dict_ctx.extend_and_get_index(dict_idx, arr))
+            .flatten()
+            .chain(buffered_arrs.into_iter().map(Ok))
+            .map(|arr| shared_dict_ctx.extend_and_get_index(dict_idx, arr?))
             .collect::<Result<Vec<_>>>()?;
         let dict_len = shared_dict_ctx.dict_len(dict_idx)?;
         let indices_arrs = indices_arrs
@@ -558,19 +638,20 @@ impl SharedDictColEncoder {
         for arr in indices_arrs {
             let encoder =
                 create_encunit_encoder(self.wasm_context.clone(), arr.data_type().clone(), false);
-            let enc_unit = encode_to_bytes(encoder.clone(), arr.clone());
+            let (enc_unit, validity_size, encoding_type) =
+                encode_to_bytes_with_validity(encoder.clone(), arr.clone())?;
 
             // Compress the data if compression is enabled
-            let compressed_enc_unit = compress_data(enc_unit, self.compression_type)?;
+            let (compressed_enc_unit, compression_type) =
+                compress_data(enc_unit, self.compression)?;
             let compressed_size = compressed_enc_unit.len() as u64;
 
             accumulated_size += compressed_size;
             counter.index_size += compressed_enc_unit.len();
-            accumulated_chunk.encunits.push(SerializedEncUnit::new(
-                compressed_enc_unit,
-                arr.len() as u32,
-                {
-                    let encoding_type = encoder.encoding_type();
+            accumulated_chunk.encunits.push(
+                SerializedEncUnit::new(
+                    compressed_enc_unit,
+                    arr.len() as u32,
                     footer::Encoding::try_new(
                         if self.wasm_context.always_set_custom_wasm_for_built_in() {
                             fb::EncodingType::CUSTOM_WASM
@@ -583,10 +664,11 @@ impl SharedDictColEncoder {
                             self.wasm_context.data_type_to_wasm_id(arr.data_type())
                         }
                         .map(|id| WASMEncoding::new(id.0, Vec::new())),
-                    )?
-                },
-                self.compression_type,
-            ));
+                    )?,
+                    compression_type,
+                )
+                .with_validity_size(validity_size),
+            );
             accumulated_chunk.num_rows += arr.len();
             if accumulated_size > self.column_chunk_size {
                 res.push(accumulated_chunk);
@@ -611,9 +693,8 @@ impl PhysicalColEncoder for SharedDictColEncoder {
         shared_dict_ctx: &mut SharedDictionaryContext,
     ) -> Result<Vec<EncodedColumnChunk>> {
         if self
-            .buffered_arrays
-            .first()
-            .is_some_and(|buf_arr| *buf_arr.data_type() != *array.data_type())
+            .buffered_data_type()
+            .is_some_and(|data_type| data_type != *array.data_type())
         {
             Err(fff_core::errors::Error::General(
                 "Datatypes of arrays do not match".to_owned(),
@@ -634,12 +715,16 @@ impl PhysicalColEncoder for SharedDictColEncoder {
         self.buffered_array_mem_size
     }
 
+    fn set_column_chunk_size(&mut self, column_chunk_size: u64) {
+        self.column_chunk_size = column_chunk_size;
+    }
+
     fn finish(
         &mut self,
         counter: &mut EncodingCounter,
         shared_dict_ctx: &mut SharedDictionaryContext,
     ) -> Result<Vec<EncodedColumnChunk>> {
-        if !self.buffered_arrays.is_empty() {
+        if !self.buffered_arrays.is_empty() || self.spilled_arrays.is_some() {
             self.encode_dict_scope(counter, shared_dict_ctx)
         } else {
             Ok(vec![])
@@ -647,21 +732,47 @@ impl PhysicalColEncoder for SharedDictColEncoder {
     }
 
     fn submit_dict(&mut self, shared_dict_ctx: &mut SharedDictionaryContext) -> Result<()> {
+        // Nothing to submit for a column without values.
+        let Some(data_type) = self.buffered_data_type() else {
+            return Ok(());
+        };
         let dict_idx = match self.submitted_dict_idx {
             Some(idx) => idx,
             None => {
-                let idx = shared_dict_ctx
-                    .new_dictionary(self.buffered_arrays.first().unwrap().data_type().clone())?;
+                let idx = shared_dict_ctx.new_dictionary(data_type)?;
                 self.submitted_dict_idx = Some(idx);
                 idx
             }
         };
+        if let Some(spilled) = &mut self.spilled_arrays {
+            for arr in spilled.read()? {
+                shared_dict_ctx.submit_values(dict_idx, arr?)?;
+            }
+        }
         self.buffered_arrays
             .iter()
             .map(|arr| shared_dict_ctx.submit_values(dict_idx, arr.clone()))
             .collect::<Result<Vec<_>>>()?;
         Ok(())
     }
+
+    /// Move the buffered arrays to a temporary file until the dict scope closes.
+    fn spill(&mut self) -> Result<Vec<EncodedColumnChunk>> {
+        if self.buffered_arrays.is_empty() {
+            return Ok(vec![]);
+        }
+        if self.spilled_arrays.is_none() {
+            self.spilled_arrays = Some(SpilledArrays::try_new(
+                self.buffered_arrays[0].data_type().clone(),
+            )?);
+        }
+        let spilled = self.spilled_arrays.as_mut().unwrap();
+        for arr in std::mem::take(&mut self.buffered_arrays) {
+            spilled.push(arr)?;
+        }
+        self.buffered_array_mem_size = 0;
+        Ok(vec![])
+    }
 }
 
 /// Best of global/local dictionaries is used (may use sampling to estimate).
@@ -673,7 +784,7 @@ pub struct GLBestEncoder {
     /// The desired encoded column chunk size, should match I/O unit size (e.g., 8MB on S3)
     column_chunk_size: u64,
     wasm_context: Arc<WASMWritingContext>,
-    compression_type: fb::CompressionType,
+    compression: Compression,
 }
 
 impl GLBestEncoder {
@@ -681,7 +792,7 @@ impl GLBestEncoder {
         sample_size: Option<(f64, usize)>,
         column_chunk_size: u64,
         wasm_context: Arc<WASMWritingContext>,
-        compression_type: fb::CompressionType,
+        compression: Compression,
     ) -> Self {
         Self {
             sample_size,
@@ -690,7 +801,7 @@ impl GLBestEncoder {
             buffered_array_mem_size: 0,
             column_chunk_size,
             wasm_context,
-            compression_type,
+            compression,
         }
     }
 
@@ -758,10 +869,12 @@ impl GLBestEncoder {
         for arr in arrs {
             let encoder =
                 create_encunit_encoder(self.wasm_context.clone(), arr.data_type().clone(), false);
-            let enc_unit = encode_to_bytes(encoder.clone(), arr.clone());
+            let (enc_unit, validity_size, encoding_type) =
+                encode_to_bytes_with_validity(encoder.clone(), arr.clone())?;
 
             // Compress the data if compression is enabled
-            let compressed_enc_unit = compress_data(enc_unit, self.compression_type)?;
+            let (compressed_enc_unit, compression_type) =
+                compress_data(enc_unit, self.compression)?;
             let compressed_size = compressed_enc_unit.len() as u64;
 
             accumulated_size += compressed_size;
@@ -770,11 +883,10 @@ impl GLBestEncoder {
             } else {
                 counter.dict_size += compressed_enc_unit.len();
             }
-            accumulated_chunk.encunits.push(SerializedEncUnit::new(
-                compressed_enc_unit,
-                arr.len() as u32,
-                {
-                    let encoding_type = encoder.encoding_type();
+            accumulated_chunk.encunits.push(
+                SerializedEncUnit::new(
+                    compressed_enc_unit,
+                    arr.len() as u32,
                     footer::Encoding::try_new(
                         if self.wasm_context.always_set_custom_wasm_for_built_in() {
                             fb::EncodingType::CUSTOM_WASM
@@ -787,10 +899,11 @@ impl GLBestEncoder {
                             self.wasm_context.data_type_to_wasm_id(arr.data_type())
                         }
                         .map(|id| WASMEncoding::new(id.0, Vec::new())),
-                    )?
-                },
-                self.compression_type,
-            ));
+                    )?,
+                    compression_type,
+                )
+                .with_validity_size(validity_size),
+            );
             accumulated_chunk.num_rows += arr.len();
             // Only split to multiple chunks for indices
             if dict_idx.is_some() && accumulated_size > self.column_chunk_size {
@@ -835,6 +948,10 @@ impl PhysicalColEncoder for GLBestEncoder {
         self.buffered_array_mem_size
     }
 
+    fn set_column_chunk_size(&mut self, column_chunk_size: u64) {
+        self.column_chunk_size = column_chunk_size;
+    }
+
     fn finish(
         &mut self,
         counter: &mut EncodingCounter,
@@ -864,7 +981,7 @@ impl PhysicalColEncoder for GLBestEncoder {
                         self.column_chunk_size,
                         self.wasm_context.clone(),
                         true,
-                        fb::CompressionType::Uncompressed,
+                        Compression::default(),
                     );
                     let mut local_counter = EncodingCounter::default();
                     let local_chunks = arrs
@@ -1048,46 +1165,46 @@ pub fn create_physical_encoder(
     _nullable: bool, // We use Vortex and its null info is embedded in the EncUnit.
     wasm_context: Arc<WASMWritingContext>,
     dictionary_type: DictionaryTypeOptions,
-    compression_type: fb::CompressionType,
+    compression: Compression,
 ) -> Result<Box<dyn PhysicalColEncoder>> {
     match *data_type {
-        non_nest_types!() => match dictionary_type {
-            DictionaryTypeOptions::NoDictionary => Ok(Box::new(EncoderDictColEncoder::new(
-                max_chunk_size,
-                wasm_context,
-                false,
-                compression_type,
-            ))),
-            DictionaryTypeOptions::EncoderDictionary => Ok(Box::new(EncoderDictColEncoder::new(
-                max_chunk_size,
-                wasm_context,
-                true,
-                compression_type,
-            ))),
-            DictionaryTypeOptions::LocalDictionary => Ok(Box::new(DictColEncoder::new(
-                max_chunk_size,
-                wasm_context,
-                compression_type,
-            ))),
-            DictionaryTypeOptions::GlobalDictionary
-            | DictionaryTypeOptions::GlobalDictionaryMultiColSharing => Ok(Box::new(
-                SharedDictColEncoder::new(u64::MAX, max_chunk_size, wasm_context, compression_type),
-            )),
-            DictionaryTypeOptions::FixedScopeDictionary(scope) => Ok(Box::new(
-                SharedDictColEncoder::new(scope, max_chunk_size, wasm_context, compression_type),
-            )),
-            DictionaryTypeOptions::GLBest(sample_size) => Ok(Box::new(GLBestEncoder::new(
-                sample_size,
-                max_chunk_size,
-                wasm_context,
-                compression_type,
-            ))),
-        },
+        non_nest_types!() => {
+            match dictionary_type {
+                DictionaryTypeOptions::NoDictionary => Ok(Box::new(EncoderDictColEncoder::new(
+                    max_chunk_size,
+                    wasm_context,
+                    false,
+                    compression,
+                ))),
+                DictionaryTypeOptions::EncoderDictionary => Ok(Box::new(
+                    EncoderDictColEncoder::new(max_chunk_size, wasm_context, true, compression),
+                )),
+                DictionaryTypeOptions::LocalDictionary => Ok(Box::new(DictColEncoder::new(
+                    max_chunk_size,
+                    wasm_context,
+                    compression,
+                ))),
+                DictionaryTypeOptions::GlobalDictionary
+                | DictionaryTypeOptions::GlobalDictionaryMultiColSharing
+                | DictionaryTypeOptions::GlobalDictionaryAutoSharing => Ok(Box::new(
+                    SharedDictColEncoder::new(u64::MAX, max_chunk_size, wasm_context, compression),
+                )),
+                DictionaryTypeOptions::FixedScopeDictionary(scope) => Ok(Box::new(
+                    SharedDictColEncoder::new(scope, max_chunk_size, wasm_context, compression),
+                )),
+                DictionaryTypeOptions::GLBest(sample_size) => Ok(Box::new(GLBestEncoder::new(
+                    sample_size,
+                    max_chunk_size,
+                    wasm_context,
+                    compression,
+                ))),
+            }
+        }
         DataType::List(_) | DataType::LargeList(_) => Ok(Box::new(EncoderDictColEncoder::new(
             max_chunk_size,
             wasm_context,
             true,
-            compression_type,
+            compression,
         ))),
         _ => todo!("Other data types not supported"),
     }
@@ -1096,6 +1213,7 @@ pub fn create_physical_encoder(
 #[cfg(test)]
 mod tests {
     use crate::{
+        compression::Compression,
         counter::EncodingCounter,
         dict::{shared_dictionary_context::SharedDictionaryContext, DictionaryTypeOptions},
     };
@@ -1115,7 +1233,7 @@ mod tests {
             DEFAULT_IOUNIT_SIZE,
             WASMWritingContext::empty().into(),
             true,
-            fb::CompressionType::Uncompressed,
+            Compression::default(),
         );
         let a =
             Arc::new(arrow_array::Int32Array::from(vec![Some(1), None, Some(3)])) as Arc<dyn Array>;
@@ -1155,7 +1273,7 @@ mod tests {
         let mut encoder = super::DictColEncoder::new(
             DEFAULT_IOUNIT_SIZE,
             WASMWritingContext::empty().into(),
-            fb::CompressionType::Uncompressed,
+            Compression::default(),
         );
         let a = Arc::new(arrow_array::Int32Array::from(vec![
             Some(1),
@@ -0,0 +1,88 @@
+//! Temporary on-disk storage for arrays buffered by encoders, to bound the writer memory.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    sync::Arc,
+};
+
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_ipc::{reader::StreamReader, writer::StreamWriter};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use fff_core::errors::Result;
+use tempfile::NamedTempFile;
+
+/// Arrays of one data type spilled to a temporary file in Arrow IPC stream format.
+/// The file is removed when this is dropped.
+pub struct SpilledArrays {
+    schema: SchemaRef,
+    file: NamedTempFile,
+    writer: StreamWriter<BufWriter<File>>,
+    num_arrays: usize,
+}
+
+impl SpilledArrays {
+    pub fn try_new(data_type: DataType) -> Result<Self> {
+        let schema = Arc::new(Schema::new(vec![Field::new("values", data_type, true)]));
+        let file = NamedTempFile::new()?;
+        let writer = StreamWriter::try_new(BufWriter::new(file.reopen()?), &schema)?;
+        Ok(Self {
+            schema,
+            file,
+            writer,
+            num_arrays: 0,
+        })
+    }
+
+    pub fn data_type(&self) -> &DataType {
+        self.schema.field(0).data_type()
+    }
+
+    pub fn num_arrays(&self) -> usize {
+        self.num_arrays
+    }
+
+    pub fn push(&mut self, array: ArrayRef) -> Result<()> {
+        self.writer
+            .write(&RecordBatch::try_new(self.schema.clone(), vec![array])?)?;
+        self.num_arrays += 1;
+        Ok(())
+    }
+
+    /// Read the spilled arrays back in order, one at a time. Arrays can still be pushed after.
+    pub fn read(&mut self) -> Result<impl Iterator<Item = Result<ArrayRef>>> {
+        self.writer.get_mut().flush()?;
+        let reader = StreamReader::try_new(BufReader::new(self.file.reopen()?), None)?;
+        Ok(reader.map(|batch| Ok(batch?.column(0).clone())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Array, StringArray};
+
+    use super::*;
+
+    #[test]
+    fn test_spilled_arrays() {
+        let arrays: Vec<ArrayRef> = (0..3)
+            .map(|i| {
+                Arc::new(StringArray::from_iter(
+                    (0..100).map(|j| (j % 3 != 0).then(|| format!("{}", i * 100 + j))),
+                )) as ArrayRef
+            })
+            .collect();
+        let mut spilled = SpilledArrays::try_new(DataType::Utf8).unwrap();
+        spilled.push(arrays[0].clone()).unwrap();
+        spilled.push(arrays[1].clone()).unwrap();
+        let read = spilled.read().unwrap().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(read.len(), 2);
+        // Reading does not disturb further spills.
+        spilled.push(arrays[2].clone()).unwrap();
+        let read = spilled.read().unwrap().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(read.len(), spilled.num_arrays());
+        for (read, array) in read.iter().zip(&arrays) {
+            assert_eq!(read.to_data(), array.to_data());
+        }
+    }
+}
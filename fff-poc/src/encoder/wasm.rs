@@ -0,0 +1,51 @@
+use std::sync::Arc;
+
+use arrow::compute::concat;
+use arrow_array::{Array, ArrayRef};
+use bytes::Bytes;
+use fff_core::{errors::Result, general_error};
+use fff_encoding::enc_unit::{EncUnit, Encoding};
+use fff_encoding::schemes::Encoder;
+use fff_test_util::WASM_FUNC_ENCODE;
+use fff_ude_wasm::Runtime;
+
+/// Encode EncUnits with the `encode_ffi` function of a WASM binary, which also embeds the
+/// matching decoder in the file.
+pub struct WasmEncUnitEncoder {
+    rt: Arc<Runtime>,
+}
+
+impl WasmEncUnitEncoder {
+    pub fn new(rt: Arc<Runtime>) -> Self {
+        Self { rt }
+    }
+}
+
+impl Encoder for WasmEncUnitEncoder {
+    fn encode(&self, arr: ArrayRef) -> Result<EncUnit> {
+        // The guest receives the buffers from offset 0.
+        let arr = if arr.offset() != 0 {
+            concat(&[arr.as_ref()])?
+        } else {
+            arr
+        };
+        let encoded = self
+            .rt
+            .call_encode(WASM_FUNC_ENCODE, &arr.to_data())
+            .map_err(|e| general_error!("WASM call failed", e))?;
+        Ok(EncUnit::new(
+            vec![Bytes::from(encoded)],
+            Encoding::Custom,
+            vec![],
+        ))
+    }
+
+    fn encoding_type(&self) -> Encoding {
+        Encoding::Custom
+    }
+
+    /// Nulls go to the validity sub-buffer, so that guest encoders only see values.
+    fn handles_nulls(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,318 @@
+//! Mapping tables between the encodings of F3 and those of Parquet, so that converters keep the
+//! encoding intent of each column instead of choosing its encodings from scratch, e.g.,
+//! dictionary-encoded columns stay dictionary-encoded.
+//!
+//! | F3                                               | Parquet                          |
+//! |--------------------------------------------------|----------------------------------|
+//! | `RLE` EncUnits of booleans                       | `RLE`                            |
+//! | `DELTA` EncUnits of integers                     | `DELTA_BINARY_PACKED`            |
+//! | `BOOLEAN` EncUnits                               | `PLAIN` booleans                 |
+//! | `LocalDictionary` and `SharedDictionary` chunks  | `RLE_DICTIONARY`                 |
+//! | `CASCADE` and `CUSTOM_WASM` EncUnits             | chosen by the writer             |
+//!
+//! Vortex cascades choose their encoding per EncUnit, dictionaries included, so they have no
+//! single counterpart: their columns are left to the Parquet writer, and Parquet columns without
+//! a native counterpart are left to the cascades. See [`parquet_writer_properties`] for the
+//! export and [`writer_options_from_parquet`] for the import.
+
+use std::collections::BTreeSet;
+
+use arrow_schema::DataType;
+use fff_core::errors::Result;
+use fff_format::File::fff::flatbuf as fb;
+use parquet::arrow::parquet_to_arrow_schema;
+use parquet::basic::{Encoding, PageType};
+use parquet::file::metadata::{ColumnChunkMetaData, ParquetMetaData};
+use parquet::file::properties::WriterProperties;
+use parquet::schema::types::ColumnPath;
+
+use crate::common::physical_type::physical_type;
+use crate::encoder::logical::num_physical_columns;
+use crate::file::footer::Footer;
+use crate::io::reader::Reader;
+use crate::options::{AdaptiveEncodingOptions, DictionaryTypeOptions, FileWriterOptionsBuilder};
+use crate::reader::{get_metadata_buffer, read_postscript};
+
+/// The Parquet encoding of the values of a `data_type` column encoded with `encoding` in F3, if
+/// Parquet has one.
+pub fn to_parquet_encoding(encoding: fb::EncodingType, data_type: &DataType) -> Option<Encoding> {
+    match (encoding, physical_type(data_type)) {
+        (fb::EncodingType::RLE, DataType::Boolean) => Some(Encoding::RLE),
+        (fb::EncodingType::BOOLEAN, DataType::Boolean) => Some(Encoding::PLAIN),
+        (fb::EncodingType::DELTA, data_type) if is_parquet_integer(&data_type) => {
+            Some(Encoding::DELTA_BINARY_PACKED)
+        }
+        _ => None,
+    }
+}
+
+/// The F3 encoding of the EncUnits of a `data_type` column encoded with `encoding` in Parquet,
+/// if F3 has one.
+pub fn from_parquet_encoding(encoding: Encoding, data_type: &DataType) -> Option<fb::EncodingType> {
+    match (encoding, physical_type(data_type)) {
+        (Encoding::RLE, DataType::Boolean) => Some(fb::EncodingType::RLE),
+        (Encoding::PLAIN, DataType::Boolean) => Some(fb::EncodingType::BOOLEAN),
+        (Encoding::DELTA_BINARY_PACKED, data_type) if is_parquet_integer(&data_type) => {
+            Some(fb::EncodingType::DELTA)
+        }
+        _ => None,
+    }
+}
+
+/// Whether Parquet stores `data_type` as INT32 or INT64, which delta encodings apply to.
+fn is_parquet_integer(data_type: &DataType) -> bool {
+    data_type.is_integer()
+        || matches!(
+            data_type,
+            DataType::Date32
+                | DataType::Date64
+                | DataType::Time32(_)
+                | DataType::Time64(_)
+                | DataType::Timestamp(_, _)
+        )
+}
+
+/// Whether the data pages of `encoding` index a dictionary page.
+pub fn is_parquet_dictionary(encoding: Encoding) -> bool {
+    matches!(
+        encoding,
+        Encoding::PLAIN_DICTIONARY | Encoding::RLE_DICTIONARY
+    )
+}
+
+/// Encodings of the data pages of `column`, without those of the levels.
+fn data_page_encodings(column: &ColumnChunkMetaData) -> Vec<Encoding> {
+    match column.page_encoding_stats() {
+        Some(stats) => stats
+            .iter()
+            .filter(|stats| stats.page_type != PageType::DICTIONARY_PAGE)
+            .map(|stats| stats.encoding)
+            .collect(),
+        // Levels are RLE or BIT_PACKED encoded.
+        None => column
+            .encodings()
+            .iter()
+            .copied()
+            .filter(|encoding| !matches!(encoding, Encoding::RLE | Encoding::BIT_PACKED))
+            .collect(),
+    }
+}
+
+/// Set the options of `builder` to keep the encoding intent of the Parquet file of `metadata`.
+///
+/// The writer chooses dictionaries in its encoder, see
+/// [`DictionaryTypeOptions::EncoderDictionary`], if any column of the file is
+/// dictionary-encoded, and uses no dictionary otherwise. It chooses among the native encodings
+/// per EncUnit, see [`AdaptiveEncodingOptions`], if any column has a native counterpart.
+pub fn writer_options_from_parquet(
+    metadata: &ParquetMetaData,
+    builder: FileWriterOptionsBuilder,
+) -> FileWriterOptionsBuilder {
+    let file_metadata = metadata.file_metadata();
+    let schema = parquet_to_arrow_schema(
+        file_metadata.schema_descr(),
+        file_metadata.key_value_metadata(),
+    )
+    .ok();
+    let mut dictionary = false;
+    let mut native = false;
+    for column in metadata.row_groups().iter().flat_map(|rg| rg.columns()) {
+        // Only flat top-level columns map to native encodings.
+        let data_type = match (column.column_path().parts(), &schema) {
+            ([name], Some(schema)) => schema
+                .field_with_name(name)
+                .ok()
+                .map(|field| field.data_type().clone()),
+            _ => None,
+        };
+        for encoding in data_page_encodings(column) {
+            dictionary |= is_parquet_dictionary(encoding);
+            native |= data_type
+                .as_ref()
+                .is_some_and(|data_type| from_parquet_encoding(encoding, data_type).is_some());
+        }
+    }
+    let builder = builder.set_dictionary_type(if dictionary {
+        DictionaryTypeOptions::EncoderDictionary
+    } else {
+        DictionaryTypeOptions::NoDictionary
+    });
+    if native {
+        builder.set_adaptive_encoding(Some(AdaptiveEncodingOptions::default()))
+    } else {
+        builder
+    }
+}
+
+/// Parquet writer properties keeping the encoding intent of the F3 file of `reader`.
+///
+/// Flat top-level columns whose chunks use dictionaries are dictionary-encoded. Those whose
+/// EncUnits all use the same native encoding use its Parquet counterpart, if any, without a
+/// dictionary. The other columns are left to the defaults of the writer.
+pub fn parquet_writer_properties<R: Reader>(reader: &R) -> Result<WriterProperties> {
+    let file_size = reader.size()?;
+    let post_script = read_postscript(reader, file_size)?;
+    let owner = get_metadata_buffer(reader, &post_script)?;
+    let footer = Footer::try_new(&owner, file_size as usize, &post_script)?;
+    let mut builder = WriterProperties::builder();
+    let mut column_index = 0;
+    for field in footer.schema().fields() {
+        let index = column_index;
+        column_index += num_physical_columns(field.data_type());
+        if num_physical_columns(field.data_type()) != 1 {
+            continue;
+        }
+        let mut dictionary = false;
+        let mut encodings = BTreeSet::new();
+        for rg_meta in footer.row_group_metadatas() {
+            let chunks = rg_meta.column_metadatas[index].column_chunks();
+            for chunk in chunks.into_iter().flatten() {
+                dictionary |= matches!(
+                    chunk.encoding_type(),
+                    fb::DictionaryEncoding::LocalDictionary
+                        | fb::DictionaryEncoding::SharedDictionary
+                );
+                for encunit in chunk.encunits().into_iter().flatten() {
+                    encodings.insert(encunit.encoding().map(|encoding| encoding.type_()));
+                }
+            }
+        }
+        let path = ColumnPath::from(field.name().as_str());
+        if dictionary {
+            builder = builder.set_column_dictionary_enabled(path, true);
+        } else if let [Some(encoding)] = encodings.into_iter().collect::<Vec<_>>()[..] {
+            if let Some(encoding) = to_parquet_encoding(encoding, field.data_type()) {
+                builder = builder
+                    .set_column_dictionary_enabled(path.clone(), false)
+                    .set_column_encoding(path, encoding);
+            }
+        }
+    }
+    Ok(builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{ArrayRef, BooleanArray, Int64Array, RecordBatch, StringArray};
+    use arrow_schema::{Field, Schema};
+    use bytes::Bytes;
+    use parquet::arrow::{arrow_reader::ArrowReaderMetadata, ArrowWriter};
+
+    use super::*;
+    use crate::options::FileWriterOptionsBuilder;
+    use crate::writer::FileWriter;
+
+    #[test]
+    fn test_mapping() {
+        for (encoding, data_type) in [
+            (fb::EncodingType::RLE, DataType::Boolean),
+            (fb::EncodingType::BOOLEAN, DataType::Boolean),
+            (fb::EncodingType::DELTA, DataType::Int32),
+            (fb::EncodingType::DELTA, DataType::UInt64),
+            (fb::EncodingType::DELTA, DataType::Date32),
+        ] {
+            let parquet = to_parquet_encoding(encoding, &data_type).unwrap();
+            assert_eq!(from_parquet_encoding(parquet, &data_type), Some(encoding));
+        }
+        assert_eq!(
+            to_parquet_encoding(fb::EncodingType::DELTA, &DataType::Float64),
+            None
+        );
+        assert_eq!(
+            to_parquet_encoding(fb::EncodingType::CASCADE, &DataType::Int32),
+            None
+        );
+        assert_eq!(
+            from_parquet_encoding(Encoding::BYTE_STREAM_SPLIT, &DataType::Float32),
+            None
+        );
+    }
+
+    fn batch() -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int64, false),
+            Field::new("b", DataType::Utf8, false),
+            Field::new("c", DataType::Boolean, false),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from_iter_values(0..1000)) as ArrayRef,
+                Arc::new(StringArray::from_iter_values(
+                    (0..1000).map(|i| format!("s{}", i % 4)),
+                )),
+                Arc::new(BooleanArray::from_iter((0..1000).map(|i| Some(i < 500)))),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn parquet_metadata(properties: WriterProperties) -> Arc<ParquetMetaData> {
+        let batch = batch();
+        let mut buf = vec![];
+        let mut writer = ArrowWriter::try_new(&mut buf, batch.schema(), Some(properties)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        ArrowReaderMetadata::load(&Bytes::from(buf), Default::default())
+            .unwrap()
+            .metadata()
+            .clone()
+    }
+
+    #[test]
+    fn test_writer_options_from_parquet() {
+        let metadata = parquet_metadata(
+            WriterProperties::builder()
+                .set_dictionary_enabled(false)
+                .set_column_encoding("a".into(), Encoding::DELTA_BINARY_PACKED)
+                .set_column_dictionary_enabled("b".into(), true)
+                .build(),
+        );
+        let options =
+            writer_options_from_parquet(&metadata, FileWriterOptionsBuilder::with_defaults())
+                .build();
+        assert_eq!(
+            options.dictionary_type(),
+            DictionaryTypeOptions::EncoderDictionary
+        );
+        assert!(options.adaptive_encoding().is_some());
+
+        let metadata = parquet_metadata(
+            WriterProperties::builder()
+                .set_dictionary_enabled(false)
+                .set_column_encoding("a".into(), Encoding::PLAIN)
+                .build(),
+        );
+        let options =
+            writer_options_from_parquet(&metadata, FileWriterOptionsBuilder::with_defaults())
+                .build();
+        assert_eq!(
+            options.dictionary_type(),
+            DictionaryTypeOptions::NoDictionary
+        );
+        // PLAIN booleans are bit-packed like the BOOLEAN encoding.
+        assert!(options.adaptive_encoding().is_some());
+    }
+
+    #[test]
+    fn test_parquet_writer_properties() {
+        let batch = batch();
+        let mut file = tempfile::tempfile().unwrap();
+        let mut writer = FileWriter::try_new(
+            batch.schema(),
+            &mut file,
+            FileWriterOptionsBuilder::with_defaults()
+                .set_dictionary_type(DictionaryTypeOptions::LocalDictionary)
+                .build(),
+        )
+        .unwrap();
+        writer.write_batch(&batch).unwrap();
+        writer.finish().unwrap();
+
+        let properties = parquet_writer_properties(&file).unwrap();
+        assert!(properties.dictionary_enabled(&ColumnPath::from("b")));
+        assert_eq!(properties.encoding(&ColumnPath::from("b")), None);
+    }
+}
@@ -0,0 +1,358 @@
+//! Encryption of the EncUnits of selected columns and of the file metadata with AES-GCM, see
+//! [`FileWriterOptionsBuilder::encrypt_columns`](crate::options::FileWriterOptionsBuilder::encrypt_columns).
+//!
+//! Each EncUnit is encrypted with a fresh nonce. Its ciphertext has the size of its plaintext, so
+//! that EncUnits can still be located and read alone, and its nonce and authentication tag are
+//! stored in the metadata of its chunk, with the id of the key. The statistics of encrypted
+//! chunks are not written.
+//!
+//! With an encrypted footer, the (compressed) metadata is stored as the length of the id of its
+//! key as a little-endian u16, the key id, the nonce, the ciphertext and the tag, and the
+//! postscript ends with [`MAGIC_ENCRYPTED_FOOTER`](fff_format::MAGIC_ENCRYPTED_FOOTER). The
+//! schema is then only readable with the key.
+//!
+//! Keys come either from a [`KeyRetriever`], by the ids given to the writer, or from a
+//! [`KeyProvider`] for envelope encryption: each encrypted column and footer then gets a fresh
+//! data key, stored as its key id wrapped with the master key of the given id, so that readers
+//! only need access to the master keys, e.g., through a key management service. The key id of a
+//! data key is [`ENVELOPE_KEY_ID_PREFIX`], the id of the master key, `#` and the wrapped data key
+//! in base64. `AwsKmsKeyProvider` wraps them with AWS KMS, behind the `kms` feature.
+
+#[cfg(feature = "kms")]
+mod kms;
+#[cfg(feature = "kms")]
+pub use kms::AwsKmsKeyProvider;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use aes_gcm::{
+    aead::{AeadCore, AeadInPlace, KeyInit, OsRng},
+    Aes128Gcm, Aes256Gcm, Nonce, Tag,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use byteorder::{ByteOrder, LittleEndian};
+use fff_core::{
+    errors::{Error, Result},
+    general_error,
+};
+use fff_format::File::fff::flatbuf as fb;
+
+pub const NONCE_SIZE: usize = 12;
+pub const TAG_SIZE: usize = 16;
+/// Prefix of the key ids of the data keys of a [`KeyProvider`].
+pub const ENVELOPE_KEY_ID_PREFIX: &str = "envelope:";
+
+/// Provides the keys of the ids given to the writer, e.g., by querying a key management service.
+pub trait KeyRetriever: Send + Sync {
+    /// The AES-128 or AES-256 key identified by `key_id`, of 16 or 32 bytes.
+    fn retrieve_key(&self, key_id: &str) -> Result<Vec<u8>>;
+}
+
+/// Keys by id, e.g., for tests or keys managed by the application.
+impl KeyRetriever for HashMap<String, Vec<u8>> {
+    fn retrieve_key(&self, key_id: &str) -> Result<Vec<u8>> {
+        self.get(key_id)
+            .cloned()
+            .ok_or_else(|| general_error!(format!("Unknown encryption key {key_id}")))
+    }
+}
+
+/// A data key generated by a [`KeyProvider`].
+pub struct DataKey {
+    /// The AES-256 key of 32 bytes the data is encrypted with.
+    pub key: Vec<u8>,
+    /// `key` encrypted with the master key, stored in the file.
+    pub wrapped: Vec<u8>,
+}
+
+/// Generates data keys wrapped with master keys, and unwraps them, e.g., with a key management
+/// service, for envelope encryption.
+pub trait KeyProvider: Send + Sync {
+    /// A fresh data key, wrapped with the master key identified by `master_key_id`.
+    fn generate_data_key(&self, master_key_id: &str) -> Result<DataKey>;
+
+    /// The data key of `wrapped`, as returned by [`Self::generate_data_key`] for `master_key_id`.
+    fn unwrap_data_key(&self, master_key_id: &str, wrapped: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Master keys by id, wrapping the data keys locally with AES-GCM, e.g., for tests. The wrapped
+/// keys are the nonce, the ciphertext and the tag.
+impl KeyProvider for HashMap<String, Vec<u8>> {
+    fn generate_data_key(&self, master_key_id: &str) -> Result<DataKey> {
+        let master_key = Cipher::try_new(master_key_id, &self.retrieve_key(master_key_id)?)?;
+        let key = Aes256Gcm::generate_key(&mut OsRng).to_vec();
+        let mut ciphertext = key.clone();
+        let (nonce, tag) = master_key.encrypt(&mut ciphertext)?;
+        Ok(DataKey {
+            key,
+            wrapped: [&nonce[..], &ciphertext, &tag].concat(),
+        })
+    }
+
+    fn unwrap_data_key(&self, master_key_id: &str, wrapped: &[u8]) -> Result<Vec<u8>> {
+        let master_key = Cipher::try_new(master_key_id, &self.retrieve_key(master_key_id)?)?;
+        if wrapped.len() < NONCE_SIZE + TAG_SIZE {
+            return Err(Error::ParseError("Truncated wrapped data key".to_string()));
+        }
+        let (nonce, rest) = wrapped.split_at(NONCE_SIZE);
+        let (ciphertext, tag) = rest.split_at(rest.len() - TAG_SIZE);
+        let mut key = ciphertext.to_vec();
+        master_key.decrypt(&mut key, nonce, tag)?;
+        Ok(key)
+    }
+}
+
+/// Retrieves the data keys of a [`KeyProvider`] from their key ids, see
+/// [`Encryptor::try_new_envelope`].
+pub(crate) struct EnvelopeKeys(pub(crate) Arc<dyn KeyProvider>);
+
+impl KeyRetriever for EnvelopeKeys {
+    fn retrieve_key(&self, key_id: &str) -> Result<Vec<u8>> {
+        let invalid = || general_error!(format!("Key {key_id} is not an envelope key id"));
+        let (master_key_id, wrapped) = key_id
+            .strip_prefix(ENVELOPE_KEY_ID_PREFIX)
+            .and_then(|envelope| envelope.rsplit_once('#'))
+            .ok_or_else(invalid)?;
+        let wrapped = BASE64.decode(wrapped).map_err(|_| invalid())?;
+        self.0.unwrap_data_key(master_key_id, &wrapped)
+    }
+}
+
+enum Cipher {
+    Aes128(Aes128Gcm),
+    Aes256(Aes256Gcm),
+}
+
+impl Cipher {
+    fn try_new(key_id: &str, key: &[u8]) -> Result<Self> {
+        match key.len() {
+            16 => Ok(Self::Aes128(Aes128Gcm::new_from_slice(key).unwrap())),
+            32 => Ok(Self::Aes256(Aes256Gcm::new_from_slice(key).unwrap())),
+            len => Err(general_error!(format!(
+                "Key {key_id} has {len} bytes, expected 16 or 32"
+            ))),
+        }
+    }
+
+    /// Encrypt `buf` in place with a fresh nonce, and return the nonce and the tag.
+    fn encrypt(&self, buf: &mut [u8]) -> Result<([u8; NONCE_SIZE], [u8; TAG_SIZE])> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let tag = match self {
+            Self::Aes128(cipher) => cipher.encrypt_in_place_detached(&nonce, b"", buf),
+            Self::Aes256(cipher) => cipher.encrypt_in_place_detached(&nonce, b"", buf),
+        }
+        .map_err(|_| general_error!("Encryption failed"))?;
+        Ok((nonce.into(), tag.into()))
+    }
+
+    fn decrypt(&self, buf: &mut [u8], nonce: &[u8], tag: &[u8]) -> Result<()> {
+        let (nonce, tag) = (Nonce::from_slice(nonce), Tag::from_slice(tag));
+        match self {
+            Self::Aes128(cipher) => cipher.decrypt_in_place_detached(nonce, b"", buf, tag),
+            Self::Aes256(cipher) => cipher.decrypt_in_place_detached(nonce, b"", buf, tag),
+        }
+        .map_err(|_| general_error!("Decryption failed, the key is wrong or the data corrupted"))
+    }
+}
+
+/// The key of an encrypted column or footer for the writer.
+#[derive(Clone)]
+pub(crate) struct Encryptor {
+    key_id: String,
+    cipher: Arc<Cipher>,
+}
+
+impl Encryptor {
+    pub(crate) fn try_new(key_retriever: &dyn KeyRetriever, key_id: &str) -> Result<Self> {
+        let key = key_retriever.retrieve_key(key_id)?;
+        Ok(Self {
+            key_id: key_id.to_string(),
+            cipher: Arc::new(Cipher::try_new(key_id, &key)?),
+        })
+    }
+
+    /// Encrypt with a fresh data key of `key_provider`, wrapped with the master key
+    /// `master_key_id` in the key id.
+    pub(crate) fn try_new_envelope(
+        key_provider: &dyn KeyProvider,
+        master_key_id: &str,
+    ) -> Result<Self> {
+        let data_key = key_provider.generate_data_key(master_key_id)?;
+        Ok(Self {
+            key_id: format!(
+                "{ENVELOPE_KEY_ID_PREFIX}{master_key_id}#{}",
+                BASE64.encode(&data_key.wrapped)
+            ),
+            cipher: Arc::new(Cipher::try_new(master_key_id, &data_key.key)?),
+        })
+    }
+
+    pub(crate) fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    pub(crate) fn encrypt(&self, buf: &mut [u8]) -> Result<([u8; NONCE_SIZE], [u8; TAG_SIZE])> {
+        self.cipher.encrypt(buf)
+    }
+
+    /// Encrypt the metadata of a file with an encrypted footer.
+    pub(crate) fn encrypt_metadata(&self, metadata: &[u8]) -> Result<Vec<u8>> {
+        let key_id_len = u16::try_from(self.key_id.len())
+            .map_err(|_| general_error!("Key id of the footer is too long"))?;
+        let mut ciphertext = metadata.to_vec();
+        let (nonce, tag) = self.encrypt(&mut ciphertext)?;
+        let mut buf =
+            Vec::with_capacity(2 + self.key_id.len() + NONCE_SIZE + ciphertext.len() + TAG_SIZE);
+        buf.extend_from_slice(&key_id_len.to_le_bytes());
+        buf.extend_from_slice(self.key_id.as_bytes());
+        buf.extend_from_slice(&nonce);
+        buf.extend_from_slice(&ciphertext);
+        buf.extend_from_slice(&tag);
+        Ok(buf)
+    }
+}
+
+/// The keys of the reader, retrieved once per key id.
+pub(crate) struct Decryptor {
+    key_retriever: Box<dyn KeyRetriever>,
+    ciphers: Mutex<HashMap<String, Arc<Cipher>>>,
+}
+
+impl Decryptor {
+    pub(crate) fn new(key_retriever: Box<dyn KeyRetriever>) -> Self {
+        Self {
+            key_retriever,
+            ciphers: Mutex::default(),
+        }
+    }
+
+    fn cipher(&self, key_id: &str) -> Result<Arc<Cipher>> {
+        if let Some(cipher) = self.ciphers.lock().unwrap().get(key_id) {
+            return Ok(cipher.clone());
+        }
+        let key = self.key_retriever.retrieve_key(key_id)?;
+        let cipher = Arc::new(Cipher::try_new(key_id, &key)?);
+        self.ciphers
+            .lock()
+            .unwrap()
+            .insert(key_id.to_string(), cipher.clone());
+        Ok(cipher)
+    }
+
+    /// Decrypt in place the EncUnits of `buf`, the ones of `chunk` from the `first_encunit`-th.
+    pub(crate) fn decrypt_encunits(
+        &self,
+        chunk: &fb::Chunk,
+        first_encunit: usize,
+        mut buf: &mut [u8],
+    ) -> Result<()> {
+        let Some(encryption) = chunk.encryption() else {
+            return Ok(());
+        };
+        let invalid = || Error::ParseError("Invalid encryption metadata of a chunk".to_string());
+        let key_id = encryption.key_id().ok_or_else(invalid)?;
+        let cipher = self.cipher(key_id)?;
+        let nonces = encryption.nonces().ok_or_else(invalid)?.bytes();
+        let tags = encryption.tags().ok_or_else(invalid)?.bytes();
+        for (i, encunit) in chunk
+            .encunits()
+            .ok_or_else(invalid)?
+            .iter()
+            .enumerate()
+            .skip(first_encunit)
+        {
+            if buf.is_empty() {
+                break;
+            }
+            let (encunit_buf, rest) = buf.split_at_mut((encunit.size_() as usize).min(buf.len()));
+            cipher.decrypt(
+                encunit_buf,
+                nonces
+                    .get(i * NONCE_SIZE..(i + 1) * NONCE_SIZE)
+                    .ok_or_else(invalid)?,
+                tags.get(i * TAG_SIZE..(i + 1) * TAG_SIZE)
+                    .ok_or_else(invalid)?,
+            )?;
+            buf = rest;
+        }
+        Ok(())
+    }
+
+    /// Decrypt the metadata of a file with an encrypted footer.
+    pub(crate) fn decrypt_metadata(&self, buf: &[u8]) -> Result<Vec<u8>> {
+        let truncated = || Error::ParseError("Truncated encrypted metadata".to_string());
+        let key_id_len = LittleEndian::read_u16(buf.get(..2).ok_or_else(truncated)?) as usize;
+        let key_id = std::str::from_utf8(buf.get(2..2 + key_id_len).ok_or_else(truncated)?)
+            .map_err(|_| Error::ParseError("Invalid key id of the footer".to_string()))?;
+        let rest = &buf[2 + key_id_len..];
+        if rest.len() < NONCE_SIZE + TAG_SIZE {
+            return Err(truncated());
+        }
+        let (nonce, rest) = rest.split_at(NONCE_SIZE);
+        let (ciphertext, tag) = rest.split_at(rest.len() - TAG_SIZE);
+        let mut metadata = ciphertext.to_vec();
+        self.cipher(key_id)?.decrypt(&mut metadata, nonce, tag)?;
+        Ok(metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_metadata() {
+        let keys: HashMap<String, Vec<u8>> = [
+            ("footer".to_string(), vec![7; 32]),
+            ("other".to_string(), vec![7; 16]),
+        ]
+        .into();
+        let encryptor = Encryptor::try_new(&keys, "footer").unwrap();
+        let metadata = b"schema and column metadata".to_vec();
+        let encrypted = encryptor.encrypt_metadata(&metadata).unwrap();
+        assert!(!encrypted
+            .windows(metadata.len())
+            .any(|window| window == metadata));
+        let decryptor = Decryptor::new(Box::new(keys.clone()));
+        assert_eq!(decryptor.decrypt_metadata(&encrypted).unwrap(), metadata);
+
+        // Tampered ciphertexts and wrong keys are rejected.
+        let mut tampered = encrypted.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(decryptor.decrypt_metadata(&tampered).is_err());
+        let wrong_keys: HashMap<String, Vec<u8>> = [("footer".to_string(), vec![8; 32])].into();
+        assert!(Decryptor::new(Box::new(wrong_keys))
+            .decrypt_metadata(&encrypted)
+            .is_err());
+        assert!(decryptor.decrypt_metadata(&encrypted[..10]).is_err());
+        assert!(Encryptor::try_new(&keys, "other").is_ok());
+        assert!(Encryptor::try_new(&keys, "missing").is_err());
+        let short_keys: HashMap<String, Vec<u8>> = [("short".to_string(), vec![7; 8])].into();
+        assert!(Encryptor::try_new(&short_keys, "short").is_err());
+    }
+
+    #[test]
+    fn test_envelope_keys() {
+        let master_keys: HashMap<String, Vec<u8>> = [("master".to_string(), vec![7; 32])].into();
+        let encryptor = Encryptor::try_new_envelope(&master_keys, "master").unwrap();
+        let other = Encryptor::try_new_envelope(&master_keys, "master").unwrap();
+        assert!(encryptor.key_id().starts_with("envelope:master#"));
+        assert_ne!(encryptor.key_id(), other.key_id());
+        let metadata = b"schema and column metadata".to_vec();
+        let encrypted = encryptor.encrypt_metadata(&metadata).unwrap();
+        let decryptor = Decryptor::new(Box::new(EnvelopeKeys(Arc::new(master_keys))));
+        assert_eq!(decryptor.decrypt_metadata(&encrypted).unwrap(), metadata);
+
+        // The data keys are only unwrapped with the right master key.
+        let wrong_keys: HashMap<String, Vec<u8>> = [("master".to_string(), vec![8; 32])].into();
+        assert!(Decryptor::new(Box::new(EnvelopeKeys(Arc::new(wrong_keys))))
+            .decrypt_metadata(&encrypted)
+            .is_err());
+        assert!(EnvelopeKeys(Arc::new(HashMap::<String, Vec<u8>>::new()))
+            .retrieve_key("master")
+            .is_err());
+    }
+}
@@ -0,0 +1,81 @@
+//! A [`KeyProvider`] backed by AWS KMS.
+
+use std::future::Future;
+
+use aws_config::BehaviorVersion;
+use aws_sdk_kms::{error::DisplayErrorContext, primitives::Blob, types::DataKeySpec, Client};
+use fff_core::{errors::Result, general_error};
+use futures::executor::block_on;
+
+use super::{DataKey, KeyProvider};
+use crate::io::reader::RUNTIME;
+
+/// Data keys generated and unwrapped by AWS KMS, with the KMS keys whose ids, ARNs or aliases are
+/// given as master key ids. Each data key costs one request to KMS to write and one to read.
+#[derive(Debug, Clone)]
+pub struct AwsKmsKeyProvider {
+    client: Client,
+}
+
+impl AwsKmsKeyProvider {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// A provider with the region and credentials of the environment, e.g., `AWS_REGION` and
+    /// `AWS_PROFILE`.
+    pub fn from_env() -> Self {
+        let config = run(aws_config::load_defaults(BehaviorVersion::latest()));
+        Self::new(Client::new(&config))
+    }
+}
+
+/// Run a KMS request on the IO runtime, as readers and writers are synchronous.
+fn run<F>(future: F) -> F::Output
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    block_on(async move { RUNTIME.spawn(future).await.unwrap() })
+}
+
+impl KeyProvider for AwsKmsKeyProvider {
+    fn generate_data_key(&self, master_key_id: &str) -> Result<DataKey> {
+        let request = self
+            .client
+            .generate_data_key()
+            .key_id(master_key_id)
+            .key_spec(DataKeySpec::Aes256);
+        let output = run(request.send()).map_err(|e| {
+            general_error!(format!(
+                "Failed to generate a data key with KMS key {master_key_id}: {}",
+                DisplayErrorContext(e)
+            ))
+        })?;
+        match (output.plaintext(), output.ciphertext_blob()) {
+            (Some(key), Some(wrapped)) => Ok(DataKey {
+                key: key.as_ref().to_vec(),
+                wrapped: wrapped.as_ref().to_vec(),
+            }),
+            _ => Err(general_error!("KMS returned no data key")),
+        }
+    }
+
+    fn unwrap_data_key(&self, master_key_id: &str, wrapped: &[u8]) -> Result<Vec<u8>> {
+        let request = self
+            .client
+            .decrypt()
+            .key_id(master_key_id)
+            .ciphertext_blob(Blob::new(wrapped));
+        let output = run(request.send()).map_err(|e| {
+            general_error!(format!(
+                "Failed to unwrap a data key with KMS key {master_key_id}: {}",
+                DisplayErrorContext(e)
+            ))
+        })?;
+        output
+            .plaintext()
+            .map(|key| key.as_ref().to_vec())
+            .ok_or_else(|| general_error!("KMS returned no data key"))
+    }
+}
@@ -0,0 +1,55 @@
+//! The "BloomFilters" optional section, the bloom filters of the chunks of the columns selected
+//! with [`FileWriterOptionsBuilder::set_bloom_filter_columns`](crate::options::FileWriterOptionsBuilder::set_bloom_filter_columns).
+//!
+//! The section holds one record per chunk: the physical column index as a little-endian u32, the
+//! offset of the chunk as a u64, then the size of the filter as a u32 and its blocks.
+
+use byteorder::{ByteOrder, LittleEndian};
+use fff_core::errors::{Error, Result};
+
+use crate::common::bloom_filter::BloomFilter;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkBloomFilter {
+    pub column_index: u32,
+    /// Offset of the chunk in the file.
+    pub chunk_offset: u64,
+    pub filter: BloomFilter,
+}
+
+pub(crate) fn serialize_bloom_filters(filters: &[ChunkBloomFilter]) -> Vec<u8> {
+    let mut buf = vec![];
+    for filter in filters {
+        let bytes = filter.filter.to_bytes();
+        buf.extend_from_slice(&filter.column_index.to_le_bytes());
+        buf.extend_from_slice(&filter.chunk_offset.to_le_bytes());
+        buf.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&bytes);
+    }
+    buf
+}
+
+pub(crate) fn deserialize_bloom_filters(mut buf: &[u8]) -> Result<Vec<ChunkBloomFilter>> {
+    let truncated = || Error::ParseError("Truncated BloomFilters section".to_string());
+    let mut filters = vec![];
+    while !buf.is_empty() {
+        if buf.len() < 16 {
+            return Err(truncated());
+        }
+        let column_index = LittleEndian::read_u32(&buf[..4]);
+        let chunk_offset = LittleEndian::read_u64(&buf[4..12]);
+        let size = LittleEndian::read_u32(&buf[12..16]) as usize;
+        let filter = buf.get(16..16 + size).ok_or_else(truncated)?;
+        filters.push(ChunkBloomFilter {
+            column_index,
+            chunk_offset,
+            filter: BloomFilter::from_bytes(filter).ok_or_else(|| {
+                Error::ParseError(format!(
+                    "Invalid bloom filter of the chunk at offset {chunk_offset}"
+                ))
+            })?,
+        });
+        buf = &buf[16 + size..];
+    }
+    Ok(filters)
+}
@@ -0,0 +1,86 @@
+//! The "ColumnFamilies" optional section, the column families declared with
+//! [`FileWriterOptionsBuilder::set_column_families`](crate::options::FileWriterOptionsBuilder::set_column_families).
+//!
+//! The section holds one record per family: the number of its root-level columns as a
+//! little-endian u32 and their indexes as u32s, then the number of its IO units as a u32 and the
+//! offset as a u64 and size as a u32 of each of them. An IO unit of a family is a range of the
+//! file holding chunks of its columns only, written at once.
+
+use std::ops::Range;
+
+use byteorder::{ByteOrder, LittleEndian};
+use fff_core::errors::{Error, Result};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnFamily {
+    /// Indexes of the root-level columns of the family.
+    pub columns: Vec<u32>,
+    /// Ranges of the file holding chunks of the family, in file order.
+    pub iounits: Vec<Range<u64>>,
+}
+
+pub(crate) fn serialize_column_families(families: &[ColumnFamily]) -> Vec<u8> {
+    let mut buf = vec![];
+    for family in families {
+        buf.extend_from_slice(&(family.columns.len() as u32).to_le_bytes());
+        for column in &family.columns {
+            buf.extend_from_slice(&column.to_le_bytes());
+        }
+        buf.extend_from_slice(&(family.iounits.len() as u32).to_le_bytes());
+        for iounit in &family.iounits {
+            buf.extend_from_slice(&iounit.start.to_le_bytes());
+            buf.extend_from_slice(&((iounit.end - iounit.start) as u32).to_le_bytes());
+        }
+    }
+    buf
+}
+
+pub(crate) fn deserialize_column_families(mut buf: &[u8]) -> Result<Vec<ColumnFamily>> {
+    let truncated = || Error::ParseError("Truncated ColumnFamilies section".to_string());
+    let mut families = vec![];
+    while !buf.is_empty() {
+        let mut family = ColumnFamily::default();
+        let num_columns = LittleEndian::read_u32(buf.get(..4).ok_or_else(truncated)?) as usize;
+        let columns = buf.get(4..4 + num_columns * 4).ok_or_else(truncated)?;
+        family.columns = columns
+            .chunks_exact(4)
+            .map(LittleEndian::read_u32)
+            .collect();
+        buf = &buf[4 + num_columns * 4..];
+        let num_iounits = LittleEndian::read_u32(buf.get(..4).ok_or_else(truncated)?) as usize;
+        let iounits = buf.get(4..4 + num_iounits * 12).ok_or_else(truncated)?;
+        family.iounits = iounits
+            .chunks_exact(12)
+            .map(|iounit| {
+                let offset = LittleEndian::read_u64(&iounit[..8]);
+                offset..offset + LittleEndian::read_u32(&iounit[8..]) as u64
+            })
+            .collect();
+        buf = &buf[4 + num_iounits * 12..];
+        families.push(family);
+    }
+    Ok(families)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_families() {
+        let families = vec![
+            ColumnFamily {
+                columns: vec![0, 2],
+                iounits: vec![0..100, 250..300],
+            },
+            ColumnFamily {
+                columns: vec![1],
+                iounits: vec![],
+            },
+        ];
+        let buf = serialize_column_families(&families);
+        assert_eq!(deserialize_column_families(&buf).unwrap(), families);
+        assert!(deserialize_column_families(&buf[..buf.len() - 1]).is_err());
+        assert!(deserialize_column_families(&buf[..10]).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+//! The "EncUnitIndex" optional section, an index of the EncUnits of each chunk, written with
+//! [`FileWriterOptionsBuilder::set_write_encunit_index`](crate::options::FileWriterOptionsBuilder::set_write_encunit_index).
+//!
+//! The section holds one record per chunk: the physical column index, row group and ordinal of
+//! the chunk in its column as little-endian u32s, the offset of the chunk as a u64 and the number
+//! of EncUnits as a u32. Each EncUnit then has its first row in the chunk as a u64, its offset in
+//! the chunk, number of rows and size as u32s, and its statistics: the zone map of the EncUnit,
+//! see [`MinMaxAccumulator::update_zone_map`](crate::common::statistics::MinMaxAccumulator::update_zone_map).
+//!
+//! Statistics start with a flags byte: has statistics, has min, has max, min is exact and max is
+//! exact, from the lowest bit. The min and max follow as a u32 length and their bytes.
+
+use byteorder::{ByteOrder, LittleEndian};
+use fff_core::errors::{Error, Result};
+
+use crate::file::footer::Statistics;
+
+/// An EncUnit of a chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncUnitIndexEntry {
+    /// First row of the EncUnit in its chunk.
+    pub first_row: u64,
+    /// Offset of the EncUnit in its chunk.
+    pub offset: u32,
+    pub num_rows: u32,
+    pub size: u32,
+    /// Zone map of the EncUnit: the min/max of binary, string and fixed-width columns, the latter
+    /// as their [`sortable_key`](crate::common::statistics::sortable_key). See
+    /// [`prune_encunits`](crate::reader::prune_encunits).
+    pub statistics: Option<Statistics>,
+}
+
+/// The EncUnits of a chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkEncUnitIndex {
+    pub column_index: u32,
+    pub row_group: u32,
+    /// Ordinal of the chunk in its column and row group.
+    pub chunk: u32,
+    /// Offset of the chunk in the file.
+    pub chunk_offset: u64,
+    pub encunits: Vec<EncUnitIndexEntry>,
+}
+
+impl ChunkEncUnitIndex {
+    /// Position of the EncUnit holding `row_in_chunk`, by binary search.
+    pub fn find(&self, row_in_chunk: u64) -> Option<usize> {
+        find_encunit(&self.encunits, row_in_chunk)
+    }
+}
+
+/// The EncUnits of all the indexed chunks of a file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EncUnitIndex {
+    chunks: Vec<ChunkEncUnitIndex>,
+}
+
+impl EncUnitIndex {
+    pub fn new(chunks: Vec<ChunkEncUnitIndex>) -> Self {
+        Self { chunks }
+    }
+
+    pub fn chunks(&self) -> &[ChunkEncUnitIndex] {
+        &self.chunks
+    }
+
+    /// The index of the `chunk`-th chunk of the physical column `column_index` in `row_group`.
+    pub fn chunk(
+        &self,
+        column_index: u32,
+        row_group: u32,
+        chunk: u32,
+    ) -> Option<&ChunkEncUnitIndex> {
+        self.chunks.iter().find(|c| {
+            c.column_index == column_index && c.row_group == row_group && c.chunk == chunk
+        })
+    }
+
+    /// Number of indexed EncUnits.
+    pub fn num_encunits(&self) -> usize {
+        self.chunks.iter().map(|c| c.encunits.len()).sum()
+    }
+}
+
+/// The row and byte offsets of consecutive EncUnits, given their number of rows and size.
+pub(crate) fn encunit_entries(
+    encunits: impl IntoIterator<Item = (u32, u32)>,
+) -> Vec<EncUnitIndexEntry> {
+    let mut first_row = 0;
+    let mut offset = 0;
+    encunits
+        .into_iter()
+        .map(|(num_rows, size)| {
+            let entry = EncUnitIndexEntry {
+                first_row,
+                offset,
+                num_rows,
+                size,
+                statistics: None,
+            };
+            first_row += num_rows as u64;
+            offset += size;
+            entry
+        })
+        .collect()
+}
+
+/// Position of the EncUnit holding `row`, by binary search. None if `row` is past the last one.
+pub(crate) fn find_encunit(encunits: &[EncUnitIndexEntry], row: u64) -> Option<usize> {
+    let position = encunits.partition_point(|e| e.first_row + e.num_rows as u64 <= row);
+    (position < encunits.len()).then_some(position)
+}
+
+fn serialize_bytes(buf: &mut Vec<u8>, bytes: Option<&[u8]>) {
+    if let Some(bytes) = bytes {
+        buf.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        buf.extend_from_slice(bytes);
+    }
+}
+
+fn serialize_statistics(buf: &mut Vec<u8>, statistics: Option<&Statistics>) {
+    let Some(statistics) = statistics else {
+        buf.push(0);
+        return;
+    };
+    buf.push(
+        1 | (statistics.min_value().is_some() as u8) << 1
+            | (statistics.max_value().is_some() as u8) << 2
+            | (statistics.is_min_value_exact() as u8) << 3
+            | (statistics.is_max_value_exact() as u8) << 4,
+    );
+    serialize_bytes(buf, statistics.min_value());
+    serialize_bytes(buf, statistics.max_value());
+}
+
+pub(crate) fn serialize_encunit_index(chunks: &[ChunkEncUnitIndex]) -> Vec<u8> {
+    let mut buf = vec![];
+    for chunk in chunks {
+        buf.extend_from_slice(&chunk.column_index.to_le_bytes());
+        buf.extend_from_slice(&chunk.row_group.to_le_bytes());
+        buf.extend_from_slice(&chunk.chunk.to_le_bytes());
+        buf.extend_from_slice(&chunk.chunk_offset.to_le_bytes());
+        buf.extend_from_slice(&(chunk.encunits.len() as u32).to_le_bytes());
+        for encunit in &chunk.encunits {
+            buf.extend_from_slice(&encunit.first_row.to_le_bytes());
+            buf.extend_from_slice(&encunit.offset.to_le_bytes());
+            buf.extend_from_slice(&encunit.num_rows.to_le_bytes());
+            buf.extend_from_slice(&encunit.size.to_le_bytes());
+            serialize_statistics(&mut buf, encunit.statistics.as_ref());
+        }
+    }
+    buf
+}
+
+/// Reads the little-endian fields of the section, failing on truncation.
+struct SectionReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> SectionReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.buf.len() < len {
+            return Err(Error::ParseError(
+                "Truncated EncUnitIndex section".to_string(),
+            ));
+        }
+        let (head, tail) = self.buf.split_at(len);
+        self.buf = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(LittleEndian::read_u32(self.take(4)?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(LittleEndian::read_u64(self.take(8)?))
+    }
+
+    fn bytes(&mut self, present: bool) -> Result<Option<Vec<u8>>> {
+        if !present {
+            return Ok(None);
+        }
+        let len = self.u32()? as usize;
+        Ok(Some(self.take(len)?.to_vec()))
+    }
+
+    fn statistics(&mut self) -> Result<Option<Statistics>> {
+        let flags = self.take(1)?[0];
+        if flags & 1 == 0 {
+            return Ok(None);
+        }
+        let min = self.bytes(flags & 1 << 1 != 0)?;
+        let max = self.bytes(flags & 1 << 2 != 0)?;
+        Ok(Some(Statistics::new(
+            min,
+            max,
+            flags & 1 << 3 != 0,
+            flags & 1 << 4 != 0,
+        )))
+    }
+}
+
+pub(crate) fn deserialize_encunit_index(buf: &[u8]) -> Result<EncUnitIndex> {
+    let mut reader = SectionReader { buf };
+    let mut chunks = vec![];
+    while !reader.buf.is_empty() {
+        let column_index = reader.u32()?;
+        let row_group = reader.u32()?;
+        let chunk = reader.u32()?;
+        let chunk_offset = reader.u64()?;
+        let num_encunits = reader.u32()?;
+        let encunits = (0..num_encunits)
+            .map(|_| {
+                Ok(EncUnitIndexEntry {
+                    first_row: reader.u64()?,
+                    offset: reader.u32()?,
+                    num_rows: reader.u32()?,
+                    size: reader.u32()?,
+                    statistics: reader.statistics()?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        chunks.push(ChunkEncUnitIndex {
+            column_index,
+            row_group,
+            chunk,
+            chunk_offset,
+            encunits,
+        });
+    }
+    Ok(EncUnitIndex::new(chunks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encunit_index_roundtrip() {
+        let mut encunits = encunit_entries([(10, 100), (5, 30), (20, 200)]);
+        assert_eq!(
+            encunits
+                .iter()
+                .map(|e| (e.first_row, e.offset))
+                .collect::<Vec<_>>(),
+            vec![(0, 0), (10, 100), (15, 130)]
+        );
+        assert_eq!(find_encunit(&encunits, 0), Some(0));
+        assert_eq!(find_encunit(&encunits, 10), Some(1));
+        assert_eq!(find_encunit(&encunits, 34), Some(2));
+        assert_eq!(find_encunit(&encunits, 35), None);
+
+        encunits[0].statistics = Some(Statistics::new(Some(b"a".to_vec()), None, true, false));
+        let chunks = vec![ChunkEncUnitIndex {
+            column_index: 2,
+            row_group: 1,
+            chunk: 0,
+            chunk_offset: 4096,
+            encunits,
+        }];
+        let buf = serialize_encunit_index(&chunks);
+        let index = deserialize_encunit_index(&buf).unwrap();
+        assert_eq!(index.chunks(), chunks.as_slice());
+        assert_eq!(index.chunk(2, 1, 0).unwrap().find(12), Some(1));
+        assert!(index.chunk(2, 0, 0).is_none());
+        assert!(deserialize_encunit_index(&buf[..buf.len() - 1]).is_err());
+    }
+}
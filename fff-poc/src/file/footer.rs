@@ -12,10 +12,14 @@ use arrow_ipc::convert::fb_to_schema;
 use arrow_ipc::root_as_message;
 use arrow_schema::Schema;
 use arrow_schema::SchemaRef;
+use bytes::Bytes;
 use fff_format::File::fff::flatbuf as fb;
 
+use crate::common::checked_u32;
 use crate::common::checksum::Checksum;
 use crate::common::checksum::ChecksumType;
+use crate::compression::{compress_data, Compression, CompressionLevel};
+use crate::file::metadata_segments::{ensure_not_segmented, serialize_segment};
 use crate::reader::RowGroupCntNPointer;
 use fff_core::errors::{Error, Result};
 
@@ -26,6 +30,9 @@ pub(crate) static DEFAULT_ENCODING_VERSIONS: LazyLock<HashMap<fb::EncodingType,
             // (fb::EncodingType::PLAIN, Version::parse("0.1.0").unwrap()),
             // (fb::EncodingType::NULLABLE, Version::parse("0.1.0").unwrap()),
             (fb::EncodingType::CASCADE, Version::parse("0.21.0").unwrap()),
+            (fb::EncodingType::RLE, Version::parse("0.1.0").unwrap()),
+            (fb::EncodingType::DELTA, Version::parse("0.1.0").unwrap()),
+            (fb::EncodingType::BOOLEAN, Version::parse("0.1.0").unwrap()),
             (
                 fb::EncodingType::CUSTOM_WASM,
                 Version::parse("1.0.0").unwrap(),
@@ -33,16 +40,19 @@ pub(crate) static DEFAULT_ENCODING_VERSIONS: LazyLock<HashMap<fb::EncodingType,
         ])
     });
 
+#[derive(Clone)]
 pub struct PostScript {
     pub metadata_size: u32,
     pub footer_size: u32,
-    // TODO: probably not need compression for footer.
+    /// Codec of the column metadata and footer, which are compressed together.
     pub compression: fb::CompressionType,
     pub checksum_type: ChecksumType,
     pub data_checksum: u64,
     pub schema_checksum: u64,
     pub major_version: u16,
     pub minor_version: u16,
+    /// Whether the metadata is encrypted, see [`crate::encryption`].
+    pub encrypted_footer: bool,
 }
 
 /// Maps an encoding type to its semantic version
@@ -104,6 +114,28 @@ pub(crate) enum DictionaryEncoding {
     SharedDictionary(u32),
 }
 
+impl From<&fb::Chunk<'_>> for DictionaryEncoding {
+    fn from(chunk: &fb::Chunk) -> Self {
+        match chunk.encoding_type() {
+            fb::DictionaryEncoding::LocalDictionary => DictionaryEncoding::Dictionary(
+                chunk
+                    .encoding_as_local_dictionary()
+                    .and_then(|dict| dict.dictionary_encunit_idxs())
+                    .into_iter()
+                    .flatten()
+                    .collect(),
+            ),
+            fb::DictionaryEncoding::SharedDictionary => DictionaryEncoding::SharedDictionary(
+                chunk
+                    .encoding_as_shared_dictionary()
+                    .map(|dict| dict.shared_dictionary_idx())
+                    .unwrap_or_default(),
+            ),
+            _ => DictionaryEncoding::NoDictionary,
+        }
+    }
+}
+
 /// ColumnMetadata (direct) for writer to use.
 /// Reader should use [fb::ColumnMetadata](fff_format::File::fff::flatbuf::ColumnMetadata) directly.
 #[derive(Default, Clone)]
@@ -111,23 +143,27 @@ pub struct ColumnMetadata {
     column_chunks: Vec<Chunk>,
 }
 
-// impl From<&fb::ColumnMetadata<'_>> for ColumnMetadata {
-//     fn from(column_metadata: &fb::ColumnMetadata) -> Self {
-//         Self {
-//             column_chunks: column_metadata
-//                 .column_chunks()
-//                 .into_iter()
-//                 .flatten()
-//                 .map(|x| Chunk::from(&x))
-//                 .collect(),
-//         }
-//     }
-// }
+impl From<&fb::ColumnMetadata<'_>> for ColumnMetadata {
+    fn from(column_metadata: &fb::ColumnMetadata) -> Self {
+        Self {
+            column_chunks: column_metadata
+                .column_chunks()
+                .into_iter()
+                .flatten()
+                .map(|x| Chunk::from(&x))
+                .collect(),
+        }
+    }
+}
 
 impl ColumnMetadata {
     pub fn add_chunk(&mut self, chunk: Chunk) {
         self.column_chunks.push(chunk);
     }
+
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.column_chunks
+    }
 }
 
 impl ToFlatBuffer for ColumnMetadata {
@@ -162,46 +198,32 @@ pub struct Chunk {
     encoding: DictionaryEncoding,
     blocks: Vec<EncUnit>,
     checksum: Option<u64>,
+    null_count: Option<u64>,
+    statistics: Option<Statistics>,
+    encryption: Option<ChunkEncryption>,
+    compression_dictionary: Option<Vec<u8>>,
+}
+impl From<&fb::Chunk<'_>> for Chunk {
+    fn from(chunk: &fb::Chunk) -> Self {
+        Self {
+            offset: chunk.offset(),
+            size: chunk.size_(),
+            num_rows: chunk.num_rows(),
+            encoding: DictionaryEncoding::from(chunk),
+            blocks: chunk
+                .encunits()
+                .into_iter()
+                .flatten()
+                .map(|x| EncUnit::from(&x))
+                .collect(),
+            checksum: chunk.checksum(),
+            null_count: chunk.null_count(),
+            statistics: chunk.statistics().map(|x| Statistics::from(&x)),
+            encryption: chunk.encryption().map(|x| ChunkEncryption::from(&x)),
+            compression_dictionary: chunk.compression_dictionary().map(|x| x.bytes().to_vec()),
+        }
+    }
 }
-// impl From<&fb::Chunk<'_>> for Chunk {
-//     fn from(chunk: &fb::Chunk) -> Self {
-//         Self {
-//             offset: chunk.offset(),
-//             size: chunk.size_(),
-//             num_rows: chunk.num_rows(),
-//             encoding: match chunk.encoding_type() {
-//                 fb::DictionaryEncoding::NoDictionary => DictionaryEncoding::NoDictionary,
-//                 fb::DictionaryEncoding::LocalDictionary => DictionaryEncoding::Dictionary(
-//                     chunk
-//                         .encoding_as_local_dictionary()
-//                         .unwrap()
-//                         .dictionary_encblock_idxs()
-//                         .unwrap()
-//                         .into_iter()
-//                         .map(|x| x as u32)
-//                         .collect(),
-//                 ),
-//                 fb::DictionaryEncoding::SharedDictionary => DictionaryEncoding::SharedDictionary(
-//                     chunk
-//                         .encoding_as_shared_dictionary()
-//                         .unwrap()
-//                         .column_chunks()
-//                         .unwrap()
-//                         .into_iter()
-//                         .map(|x| Chunk::from(&x))
-//                         .collect(),
-//                 ),
-//                 _ => DictionaryEncoding::NoDictionary,
-//             },
-//             blocks: chunk
-//                 .blocks()
-//                 .into_iter()
-//                 .flatten()
-//                 .map(|x| NullableBlock::from(&x))
-//                 .collect(),
-//         }
-//     }
-// }
 
 impl Chunk {
     pub(crate) fn new(
@@ -211,6 +233,7 @@ impl Chunk {
         encoding: DictionaryEncoding,
         blocks: Vec<EncUnit>,
         checksum: Option<u64>,
+        null_count: Option<u64>,
     ) -> Self {
         Self {
             offset,
@@ -219,12 +242,60 @@ impl Chunk {
             encoding,
             blocks,
             checksum,
+            null_count,
+            statistics: None,
+            encryption: None,
+            compression_dictionary: None,
         }
     }
 
+    pub fn with_statistics(mut self, statistics: Option<Statistics>) -> Self {
+        self.statistics = statistics;
+        self
+    }
+
+    pub(crate) fn with_encryption(mut self, encryption: Option<ChunkEncryption>) -> Self {
+        self.encryption = encryption;
+        self
+    }
+
+    /// The Zstd dictionary the EncUnits are compressed with, see
+    /// [`crate::compression::compress_with_trained_dictionary`].
+    pub(crate) fn with_compression_dictionary(
+        mut self,
+        compression_dictionary: Option<Vec<u8>>,
+    ) -> Self {
+        self.compression_dictionary = compression_dictionary;
+        self
+    }
+
     pub fn offset(&self) -> u64 {
         self.offset
     }
+
+    pub fn num_rows(&self) -> u64 {
+        self.num_rows
+    }
+
+    pub fn checksum(&self) -> Option<u64> {
+        self.checksum
+    }
+
+    pub fn null_count(&self) -> Option<u64> {
+        self.null_count
+    }
+
+    pub fn statistics(&self) -> Option<&Statistics> {
+        self.statistics.as_ref()
+    }
+
+    pub(crate) fn encunits(&self) -> &[EncUnit] {
+        &self.blocks
+    }
+
+    pub(crate) fn is_encrypted(&self) -> bool {
+        self.encryption.is_some()
+    }
 }
 
 impl ToFlatBuffer for Chunk {
@@ -269,6 +340,12 @@ impl ToFlatBuffer for Chunk {
                 ),
             ),
         };
+        let statistics = self.statistics.as_ref().map(|x| x.to_fb(fbb));
+        let encryption = self.encryption.as_ref().map(|x| x.to_fb(fbb));
+        let compression_dictionary = self
+            .compression_dictionary
+            .as_ref()
+            .map(|x| fbb.create_vector(x));
         fb::Chunk::create(
             fbb,
             &fb::ChunkArgs {
@@ -279,6 +356,127 @@ impl ToFlatBuffer for Chunk {
                 encoding,
                 encunits,
                 checksum: self.checksum,
+                null_count: self.null_count,
+                statistics,
+                encryption,
+                compression_dictionary,
+            },
+        )
+    }
+}
+
+/// The key id, and the nonce and tag of each EncUnit of an encrypted chunk, see
+/// [crate::encryption].
+#[derive(Clone, Default)]
+pub(crate) struct ChunkEncryption {
+    pub(crate) key_id: String,
+    pub(crate) nonces: Vec<u8>,
+    pub(crate) tags: Vec<u8>,
+}
+
+impl From<&fb::ChunkEncryption<'_>> for ChunkEncryption {
+    fn from(encryption: &fb::ChunkEncryption) -> Self {
+        Self {
+            key_id: encryption.key_id().unwrap_or_default().to_string(),
+            nonces: encryption
+                .nonces()
+                .map(|x| x.bytes().to_vec())
+                .unwrap_or_default(),
+            tags: encryption
+                .tags()
+                .map(|x| x.bytes().to_vec())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl ToFlatBuffer for ChunkEncryption {
+    type Target<'a> = fb::ChunkEncryption<'a>;
+
+    fn to_fb<'fb>(&self, fbb: &mut FlatBufferBuilder<'fb>) -> WIPOffset<Self::Target<'fb>> {
+        let key_id = fbb.create_string(&self.key_id);
+        let nonces = fbb.create_vector(&self.nonces);
+        let tags = fbb.create_vector(&self.tags);
+        fb::ChunkEncryption::create(
+            fbb,
+            &fb::ChunkEncryptionArgs {
+                key_id: Some(key_id),
+                nonces: Some(nonces),
+                tags: Some(tags),
+            },
+        )
+    }
+}
+
+/// Min/max statistics of a chunk, see [crate::common::statistics].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statistics {
+    min_value: Option<Vec<u8>>,
+    max_value: Option<Vec<u8>>,
+    is_min_value_exact: bool,
+    is_max_value_exact: bool,
+}
+
+impl From<&fb::Statistics<'_>> for Statistics {
+    fn from(statistics: &fb::Statistics) -> Self {
+        Self {
+            min_value: statistics.min_value().map(|x| x.bytes().to_vec()),
+            max_value: statistics.max_value().map(|x| x.bytes().to_vec()),
+            is_min_value_exact: statistics.is_min_value_exact(),
+            is_max_value_exact: statistics.is_max_value_exact(),
+        }
+    }
+}
+
+impl Statistics {
+    pub fn new(
+        min_value: Option<Vec<u8>>,
+        max_value: Option<Vec<u8>>,
+        is_min_value_exact: bool,
+        is_max_value_exact: bool,
+    ) -> Self {
+        Self {
+            min_value,
+            max_value,
+            is_min_value_exact,
+            is_max_value_exact,
+        }
+    }
+
+    /// A lower bound of the values, None if the chunk has no non-null values.
+    pub fn min_value(&self) -> Option<&[u8]> {
+        self.min_value.as_deref()
+    }
+
+    /// An upper bound of the values, None if unknown.
+    pub fn max_value(&self) -> Option<&[u8]> {
+        self.max_value.as_deref()
+    }
+
+    /// Whether `min_value` is the actual min, i.e., it is not truncated.
+    pub fn is_min_value_exact(&self) -> bool {
+        self.is_min_value_exact
+    }
+
+    /// Whether `max_value` is the actual max, i.e., it is not truncated.
+    pub fn is_max_value_exact(&self) -> bool {
+        self.is_max_value_exact
+    }
+}
+
+impl ToFlatBuffer for Statistics {
+    type Target<'a> = fb::Statistics<'a>;
+
+    fn to_fb<'fb>(&self, fbb: &mut FlatBufferBuilder<'fb>) -> WIPOffset<Self::Target<'fb>> {
+        let min_value = self.min_value.as_ref().map(|x| fbb.create_vector(x));
+        let max_value = self.max_value.as_ref().map(|x| fbb.create_vector(x));
+        fb::Statistics::create(
+            fbb,
+            &fb::StatisticsArgs {
+                min_value,
+                max_value,
+                is_min_value_exact: self.is_min_value_exact,
+                is_max_value_exact: self.is_max_value_exact,
             },
         )
     }
@@ -415,16 +613,25 @@ pub struct EncUnit {
     num_rows: u32,
     encoding: Encoding,
     compression: fb::CompressionType,
+    /// Size of the RLE validity sub-buffer at the start of the EncUnit, if any.
+    validity_size: Option<u32>,
 }
 
-// impl From<&fb::EncBlock<'_>> for EncBlock {
-//     fn from(page: &fb::EncBlock) -> Self {
-//         Self {
-//             size: page.size_(),
-//             encoding: Encoding::from(&page.encoding().unwrap()),
-//         }
-//     }
-// }
+impl From<&fb::EncUnit<'_>> for EncUnit {
+    fn from(encunit: &fb::EncUnit) -> Self {
+        Self {
+            size: encunit.size_(),
+            num_rows: encunit.num_rows(),
+            encoding: encunit
+                .encoding()
+                .map(|encoding| Encoding::from(&encoding))
+                .unwrap_or_default(),
+            compression: encunit.compression(),
+            validity_size: (encunit.validity_encoding() != fb::ValidityEncoding::NONE)
+                .then_some(encunit.validity_size()),
+        }
+    }
+}
 
 impl EncUnit {
     pub fn new(
@@ -438,8 +645,22 @@ impl EncUnit {
             num_rows,
             encoding,
             compression,
+            validity_size: None,
         }
     }
+
+    pub fn with_validity_size(mut self, validity_size: Option<u32>) -> Self {
+        self.validity_size = validity_size;
+        self
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn num_rows(&self) -> u32 {
+        self.num_rows
+    }
 }
 
 impl ToFlatBuffer for EncUnit {
@@ -454,6 +675,12 @@ impl ToFlatBuffer for EncUnit {
                 num_rows: self.num_rows,
                 encoding: Some(encoding),
                 compression: self.compression,
+                validity_encoding: if self.validity_size.is_some() {
+                    fb::ValidityEncoding::RLE
+                } else {
+                    fb::ValidityEncoding::NONE
+                },
+                validity_size: self.validity_size.unwrap_or(0),
             },
         )
     }
@@ -461,7 +688,7 @@ impl ToFlatBuffer for EncUnit {
 
 /// Metadata section pointer for writer.
 /// Reader should use [MetadataSectionFBS](fff_format::File::fff::flatbuf::MetadataSection) directly.
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MetadataSection {
     pub offset: u64,
     pub size: u32,
@@ -600,6 +827,10 @@ impl RowGroupsTable {
         &self.sizes
     }
 
+    pub fn row_group_metadata(&self) -> &[RowGroupMetadata] {
+        &self.row_group_metadata
+    }
+
     pub fn indirect_row_group_metadata(&self) -> &[IndirectRowGroupMetadata] {
         &self.indirect_row_group_metadata
     }
@@ -622,7 +853,7 @@ impl RowGroupsTable {
                 let offset = writer.stream_position()?;
                 writer.write_all(data)?;
                 checksum.update(data);
-                let size = data.len() as u32;
+                let size = checked_u32(data.len() as u64, "Size of a column metadata")?;
                 indirect_row_group_metadata.add_col_meta(MetadataSection {
                     offset,
                     size,
@@ -634,6 +865,51 @@ impl RowGroupsTable {
         }
         Ok(start_offset)
     }
+
+    /// Like [`Self::to_indirect_and_flush`], but write the ColumnMetadata of each row group in
+    /// zstd-compressed segments of `columns_per_segment` columns, so that each section of
+    /// indirect_row_group_metadata points to a segment. See [`crate::file::metadata_segments`].
+    pub fn to_segmented_and_flush<W: Write + Seek>(
+        &mut self,
+        writer: &mut W,
+        checksum: &mut dyn Checksum,
+        columns_per_segment: usize,
+    ) -> Result<u64> {
+        let start_offset = writer.stream_position()?;
+        for row_group in &self.row_group_metadata {
+            let mut indirect_row_group_metadata = IndirectRowGroupMetadata::default();
+            for columns in row_group.col_metadatas().chunks(columns_per_segment) {
+                let column_metadatas = columns
+                    .iter()
+                    .map(|col_meta| {
+                        let mut fbb = FlatBufferBuilder::new();
+                        let fbs = col_meta.to_fb(&mut fbb);
+                        fbb.finish(fbs, None);
+                        fbb.finished_data().to_vec()
+                    })
+                    .collect::<Vec<_>>();
+                let (data, compression_type) = compress_data(
+                    Bytes::from(serialize_segment(&column_metadatas)),
+                    Compression::new(
+                        fb::CompressionType::Zstd,
+                        CompressionLevel::Fixed(zstd::DEFAULT_COMPRESSION_LEVEL),
+                    ),
+                )?;
+                let offset = writer.stream_position()?;
+                writer.write_all(&data)?;
+                checksum.update(&data);
+                let size = checked_u32(data.len() as u64, "Size of a column metadata segment")?;
+                indirect_row_group_metadata.add_col_meta(MetadataSection {
+                    offset,
+                    size,
+                    compression_type,
+                });
+            }
+            self.indirect_row_group_metadata
+                .push(indirect_row_group_metadata);
+        }
+        Ok(start_offset)
+    }
 }
 
 /// Footer for reader to use. Basically group all the column metadata flatbuffers
@@ -691,12 +967,12 @@ impl<'a> Footer<'a> {
     /// buf is the preallocated buffer according to postscript
     pub fn try_new(buf: &'a [u8], file_size: usize, post_script: &PostScript) -> Result<Self> {
         let data_size = file_size - POSTSCRIPT_SIZE as usize - post_script.metadata_size as usize;
-        let footer_fbs =
-            root_as_footer(&buf[(post_script.metadata_size - post_script.footer_size) as usize..])
-                .map_err(|e| Error::ParseError(format!("Unable to get root as footer: {e:?}")))?;
+        let footer_fbs = root_as_footer(&buf[buf.len() - post_script.footer_size as usize..])
+            .map_err(|e| Error::ParseError(format!("Unable to get root as footer: {e:?}")))?;
         // FIXME: use logical tree to know which logical encoding to use.
-        let (schema, _logical_tree, row_groups_pointer, _shared_dict, _, _) =
+        let (schema, _logical_tree, row_groups_pointer, _shared_dict, optional_sections, _) =
             parse_footer(&footer_fbs)?;
+        ensure_not_segmented(optional_sections, "Reading the whole footer")?;
         let row_group_metadata_fbs = row_groups_pointer
             .row_group_metadatas()
             .ok_or_else(|| Error::ParseError("Row group metadatas not found".to_string()))?;
@@ -0,0 +1,290 @@
+//! User key-value metadata stored in the footer, see
+//! [`FileWriter::add_metadata`](crate::writer::FileWriter::add_metadata).
+//!
+//! Applications store things like lineage, table versions or encryption descriptors there. The
+//! metadata of the Arrow schema is kept as well, but only holds strings; these values are typed.
+
+use std::collections::BTreeMap;
+
+use fff_core::errors::{Error, Result};
+use fff_format::File::fff::flatbuf as fb;
+use flatbuffers::{FlatBufferBuilder, ForwardsUOffset, Vector, WIPOffset};
+
+/// A typed metadata value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetadataValue {
+    Bytes(Vec<u8>),
+    String(String),
+    Int64(i64),
+    UInt64(u64),
+    Float64(f64),
+    Bool(bool),
+}
+
+impl MetadataValue {
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Bytes(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Int64(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Self::UInt64(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Float64(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn to_fb<'fb>(
+        &self,
+        fbb: &mut FlatBufferBuilder<'fb>,
+        key: &str,
+    ) -> WIPOffset<fb::KeyValue<'fb>> {
+        let (value_type, value) = match self {
+            Self::Bytes(value) => (fb::KeyValueType::BYTES, value.clone()),
+            Self::String(value) => (fb::KeyValueType::STRING, value.as_bytes().to_vec()),
+            Self::Int64(value) => (fb::KeyValueType::INT64, value.to_le_bytes().to_vec()),
+            Self::UInt64(value) => (fb::KeyValueType::UINT64, value.to_le_bytes().to_vec()),
+            Self::Float64(value) => (fb::KeyValueType::FLOAT64, value.to_le_bytes().to_vec()),
+            Self::Bool(value) => (fb::KeyValueType::BOOL, vec![*value as u8]),
+        };
+        let key = fbb.create_string(key);
+        let value = fbb.create_vector(&value);
+        fb::KeyValue::create(
+            fbb,
+            &fb::KeyValueArgs {
+                key: Some(key),
+                value_type,
+                value: Some(value),
+            },
+        )
+    }
+
+    fn from_fb(key_value: &fb::KeyValue) -> Result<Self> {
+        let value = key_value.value().map(|v| v.bytes()).unwrap_or_default();
+        let invalid = || {
+            Error::ParseError(format!(
+                "Invalid value of type {:?} for metadata key {:?}",
+                key_value.value_type(),
+                key_value.key()
+            ))
+        };
+        Ok(match key_value.value_type() {
+            fb::KeyValueType::STRING => {
+                Self::String(String::from_utf8(value.to_vec()).map_err(|_| invalid())?)
+            }
+            fb::KeyValueType::INT64 => {
+                Self::Int64(i64::from_le_bytes(value.try_into().map_err(|_| invalid())?))
+            }
+            fb::KeyValueType::UINT64 => {
+                Self::UInt64(u64::from_le_bytes(value.try_into().map_err(|_| invalid())?))
+            }
+            fb::KeyValueType::FLOAT64 => {
+                Self::Float64(f64::from_le_bytes(value.try_into().map_err(|_| invalid())?))
+            }
+            fb::KeyValueType::BOOL => match value {
+                [0] => Self::Bool(false),
+                [1] => Self::Bool(true),
+                _ => return Err(invalid()),
+            },
+            // Values of types unknown to this version are kept as raw bytes.
+            _ => Self::Bytes(value.to_vec()),
+        })
+    }
+}
+
+impl From<Vec<u8>> for MetadataValue {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Bytes(value)
+    }
+}
+
+impl From<&[u8]> for MetadataValue {
+    fn from(value: &[u8]) -> Self {
+        Self::Bytes(value.to_vec())
+    }
+}
+
+impl From<String> for MetadataValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<&str> for MetadataValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<i64> for MetadataValue {
+    fn from(value: i64) -> Self {
+        Self::Int64(value)
+    }
+}
+
+impl From<u64> for MetadataValue {
+    fn from(value: u64) -> Self {
+        Self::UInt64(value)
+    }
+}
+
+impl From<f64> for MetadataValue {
+    fn from(value: f64) -> Self {
+        Self::Float64(value)
+    }
+}
+
+impl From<bool> for MetadataValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+type KeyValues = BTreeMap<String, MetadataValue>;
+
+/// The key-value metadata of a file and of its top-level columns, by index in the schema of the
+/// file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyValueMetadata {
+    file: KeyValues,
+    columns: BTreeMap<usize, KeyValues>,
+}
+
+impl KeyValueMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.file.is_empty() && self.columns.is_empty()
+    }
+
+    /// The value of `key` in the metadata of the file.
+    pub fn get(&self, key: &str) -> Option<&MetadataValue> {
+        self.file.get(key)
+    }
+
+    /// The value of `key` in the metadata of the top-level column `column`.
+    pub fn get_column(&self, column: usize, key: &str) -> Option<&MetadataValue> {
+        self.columns.get(&column)?.get(key)
+    }
+
+    /// All the key-value pairs of the file, sorted by key.
+    pub fn file_metadata(&self) -> &BTreeMap<String, MetadataValue> {
+        &self.file
+    }
+
+    /// All the key-value pairs of the top-level column `column`, sorted by key.
+    pub fn column_metadata(&self, column: usize) -> Option<&BTreeMap<String, MetadataValue>> {
+        self.columns.get(&column)
+    }
+
+    pub(crate) fn insert(&mut self, key: String, value: MetadataValue) {
+        self.file.insert(key, value);
+    }
+
+    pub(crate) fn insert_column(&mut self, column: usize, key: String, value: MetadataValue) {
+        self.columns.entry(column).or_default().insert(key, value);
+    }
+
+    /// Keep the metadata of the columns of `columns` only, the i-th of which becomes column i.
+    pub(crate) fn select_columns(&mut self, columns: &[usize]) {
+        let mut existing = std::mem::take(&mut self.columns);
+        self.columns = columns
+            .iter()
+            .enumerate()
+            .filter_map(|(i, column)| Some((i, existing.remove(column)?)))
+            .collect();
+    }
+
+    /// Serialize into the `key_values` and `column_key_values` fields of the footer.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn to_fb<'fb>(
+        &self,
+        fbb: &mut FlatBufferBuilder<'fb>,
+    ) -> (
+        Option<WIPOffset<Vector<'fb, ForwardsUOffset<fb::KeyValue<'fb>>>>>,
+        Option<WIPOffset<Vector<'fb, ForwardsUOffset<fb::ColumnKeyValues<'fb>>>>>,
+    ) {
+        let file = (!self.file.is_empty()).then(|| {
+            let key_values = self
+                .file
+                .iter()
+                .map(|(key, value)| value.to_fb(fbb, key))
+                .collect::<Vec<_>>();
+            fbb.create_vector(&key_values)
+        });
+        let columns = (!self.columns.is_empty()).then(|| {
+            let columns = self
+                .columns
+                .iter()
+                .map(|(column, key_values)| {
+                    let key_values = key_values
+                        .iter()
+                        .map(|(key, value)| value.to_fb(fbb, key))
+                        .collect::<Vec<_>>();
+                    let key_values = fbb.create_vector(&key_values);
+                    fb::ColumnKeyValues::create(
+                        fbb,
+                        &fb::ColumnKeyValuesArgs {
+                            column: *column as u32,
+                            key_values: Some(key_values),
+                        },
+                    )
+                })
+                .collect::<Vec<_>>();
+            fbb.create_vector(&columns)
+        });
+        (file, columns)
+    }
+
+    pub(crate) fn from_fb(footer: &fb::Footer) -> Result<Self> {
+        let parse = |key_values: Option<Vector<ForwardsUOffset<fb::KeyValue>>>| {
+            key_values
+                .into_iter()
+                .flatten()
+                .map(|key_value| {
+                    let key = key_value
+                        .key()
+                        .ok_or_else(|| Error::ParseError("Metadata key not found".to_string()))?;
+                    Ok((key.to_string(), MetadataValue::from_fb(&key_value)?))
+                })
+                .collect::<Result<KeyValues>>()
+        };
+        Ok(Self {
+            file: parse(footer.key_values())?,
+            columns: footer
+                .column_key_values()
+                .into_iter()
+                .flatten()
+                .map(|column| Ok((column.column() as usize, parse(column.key_values())?)))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
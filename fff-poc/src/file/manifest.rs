@@ -0,0 +1,164 @@
+//! A JSON summary of an F3 file, written next to it for catalog systems that cannot read F3
+//! metadata yet.
+//!
+//! The manifest of `data.fff` is `data.fff.manifest.json`, see [`manifest_path`].
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow_ipc::convert::{schema_to_fb, try_schema_from_flatbuffer_bytes};
+use arrow_schema::{Schema, SchemaRef};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use fff_core::{errors::Result, general_error};
+use fff_format::{MAJOR_VERSION, MINOR_VERSION};
+use serde::{Deserialize, Serialize};
+
+use crate::common::checksum::{create_checksum, ChecksumType};
+use crate::file::footer::RowGroupMetadata;
+
+pub const MANIFEST_FILE_SUFFIX: &str = ".manifest.json";
+
+/// Path of the manifest of the F3 file at `path`.
+pub fn manifest_path(path: impl AsRef<Path>) -> PathBuf {
+    let mut path = path.as_ref().as_os_str().to_owned();
+    path.push(MANIFEST_FILE_SUFFIX);
+    PathBuf::from(path)
+}
+
+/// A top-level field of the schema, readable without decoding the Arrow schema.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldSummary {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+}
+
+/// Summary of a physical column over all row groups.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnSummary {
+    pub num_chunks: usize,
+    /// None if a chunk does not record its null count.
+    pub null_count: Option<u64>,
+    /// Hex xxHash64 of the row counts, null counts and checksums of the chunks.
+    /// Changes whenever the chunk statistics do.
+    pub stats_digest: String,
+}
+
+/// A WASM module embedded in the file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WasmModuleSummary {
+    pub wasm_id: u32,
+    pub size: u64,
+    /// Hex xxHash64 of the module binary.
+    pub hash: String,
+}
+
+/// Summary of an F3 file, returned by [`crate::writer::FileWriter::finish_with_manifest`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileManifest {
+    /// `major.minor` version of the file format.
+    pub format_version: String,
+    /// Base64-encoded Arrow IPC flatbuffer of the schema, so that it round-trips losslessly.
+    pub schema: String,
+    pub fields: Vec<FieldSummary>,
+    pub num_rows: u64,
+    pub num_row_groups: usize,
+    /// Size of the file in bytes.
+    pub size: u64,
+    /// Physical columns in order.
+    pub columns: Vec<ColumnSummary>,
+    pub wasm_modules: Vec<WasmModuleSummary>,
+}
+
+fn hex_xxhash(data: &[u8]) -> String {
+    let mut checksum = create_checksum(&ChecksumType::XxHash);
+    checksum.update(data);
+    format!("{:016x}", checksum.finalize())
+}
+
+impl FileManifest {
+    pub(crate) fn new(
+        schema: &Schema,
+        num_rows: u64,
+        size: u64,
+        num_columns: usize,
+        row_groups: &[RowGroupMetadata],
+        wasms: &[&[u8]],
+    ) -> Self {
+        let columns = (0..num_columns)
+            .map(|col_idx| {
+                let chunks: Vec<_> = row_groups
+                    .iter()
+                    .flat_map(|row_group| row_group.col_metadatas()[col_idx].chunks())
+                    .collect();
+                let mut stats = Vec::with_capacity(chunks.len() * 24);
+                for chunk in &chunks {
+                    stats.extend_from_slice(&chunk.num_rows().to_le_bytes());
+                    stats.extend_from_slice(&chunk.null_count().unwrap_or(u64::MAX).to_le_bytes());
+                    stats.extend_from_slice(&chunk.checksum().unwrap_or(0).to_le_bytes());
+                }
+                ColumnSummary {
+                    num_chunks: chunks.len(),
+                    null_count: chunks.iter().map(|chunk| chunk.null_count()).sum(),
+                    stats_digest: hex_xxhash(&stats),
+                }
+            })
+            .collect();
+        Self {
+            format_version: format!("{}.{}", MAJOR_VERSION, MINOR_VERSION),
+            schema: STANDARD.encode(schema_to_fb(schema).finished_data()),
+            fields: schema
+                .fields()
+                .iter()
+                .map(|field| FieldSummary {
+                    name: field.name().clone(),
+                    data_type: field.data_type().to_string(),
+                    nullable: field.is_nullable(),
+                })
+                .collect(),
+            num_rows,
+            num_row_groups: row_groups.len(),
+            size,
+            columns,
+            wasm_modules: wasms
+                .iter()
+                .enumerate()
+                .map(|(wasm_id, wasm)| WasmModuleSummary {
+                    wasm_id: wasm_id as u32,
+                    size: wasm.len() as u64,
+                    hash: hex_xxhash(wasm),
+                })
+                .collect(),
+        }
+    }
+
+    /// Decode the Arrow schema.
+    pub fn arrow_schema(&self) -> Result<SchemaRef> {
+        let schema_bytes = STANDARD
+            .decode(&self.schema)
+            .map_err(|e| general_error!("Failed to decode schema in file manifest", e))?;
+        Ok(Arc::new(try_schema_from_flatbuffer_bytes(&schema_bytes)?))
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| general_error!("Failed to serialize file manifest", e))
+    }
+
+    /// Write the manifest of the F3 file at `path` next to it and return the manifest path.
+    pub fn write_next_to(&self, path: impl AsRef<Path>) -> Result<PathBuf> {
+        let manifest_path = manifest_path(path);
+        let file = File::create(&manifest_path)?;
+        serde_json::to_writer_pretty(file, self)
+            .map_err(|e| general_error!("Failed to write file manifest", e))?;
+        Ok(manifest_path)
+    }
+
+    /// Read the manifest written next to the F3 file at `path`.
+    pub fn try_load(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(manifest_path(path))?;
+        serde_json::from_reader(file)
+            .map_err(|e| general_error!("Failed to parse file manifest", e))
+    }
+}
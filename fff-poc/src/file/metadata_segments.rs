@@ -0,0 +1,149 @@
+//! Budgeted metadata for very wide schemas, written with
+//! [`FileWriterOptionsBuilder::set_budgeted_metadata`](crate::options::FileWriterOptionsBuilder::set_budgeted_metadata).
+//!
+//! The column metadata of each row group is grouped into segments of a fixed number of
+//! consecutive physical columns, each compressed on its own. The column metadata sections of a
+//! row group in the footer then point to its segments instead of to each column, so that the
+//! footer does not grow with the number of columns, and opening the file only reads the segments
+//! of the projected columns. A decompressed segment holds the number of columns and the end
+//! offset of the metadata of each column as little-endian u32s, followed by the metadata.
+//!
+//! The "MetadataSegments" optional section holds the number of columns per segment as a
+//! little-endian u32. The "SchemaIndex" optional section holds one record per top-level field,
+//! sorted by the xxHash64 of the field name: the hash as a little-endian u64 and the index of the
+//! field as a u32.
+
+use arrow_schema::Schema;
+use byteorder::{ByteOrder, LittleEndian};
+use bytes::Bytes;
+use fff_core::{
+    errors::{Error, Result},
+    general_error, nyi_err,
+};
+use fff_format::File::fff::flatbuf as fb;
+use xxhash_rust::xxh64::xxh64;
+
+const SCHEMA_INDEX_RECORD_SIZE: usize = 12;
+
+/// Concatenate the metadata of the columns of a segment behind their end offsets.
+pub(crate) fn serialize_segment(column_metadatas: &[Vec<u8>]) -> Vec<u8> {
+    let mut buf = vec![];
+    buf.extend_from_slice(&(column_metadatas.len() as u32).to_le_bytes());
+    let mut end = 0;
+    for column_metadata in column_metadatas {
+        end += column_metadata.len() as u32;
+        buf.extend_from_slice(&end.to_le_bytes());
+    }
+    for column_metadata in column_metadatas {
+        buf.extend_from_slice(column_metadata);
+    }
+    buf
+}
+
+/// The metadata of the `i`-th column of a decompressed segment.
+pub(crate) fn segment_column(segment: &Bytes, i: usize) -> Result<Bytes> {
+    let truncated = || Error::ParseError("Truncated column metadata segment".to_string());
+    let num_columns = LittleEndian::read_u32(segment.get(..4).ok_or_else(truncated)?) as usize;
+    if i >= num_columns {
+        return Err(Error::IndexOutOfBound(i, num_columns));
+    }
+    let ends = segment.get(4..4 + num_columns * 4).ok_or_else(truncated)?;
+    let start = match i {
+        0 => 0,
+        i => LittleEndian::read_u32(&ends[(i - 1) * 4..]) as usize,
+    };
+    let end = LittleEndian::read_u32(&ends[i * 4..]) as usize;
+    let data_start = 4 + num_columns * 4;
+    if start > end || data_start + end > segment.len() {
+        return Err(truncated());
+    }
+    Ok(segment.slice(data_start + start..data_start + end))
+}
+
+/// Error out on files written with budgeted metadata, whose column metadata sections point to
+/// segments, for the operations that expect a section per column.
+pub(crate) fn ensure_not_segmented(
+    optional_sections: Option<fb::OptionalMetadataSections>,
+    operation: &str,
+) -> Result<()> {
+    if optional_sections
+        .and_then(|sections| sections.names())
+        .is_some_and(|names| names.iter().any(|name| name == "MetadataSegments"))
+    {
+        return nyi_err!(format!("{operation} of a file with budgeted metadata"));
+    }
+    Ok(())
+}
+
+/// The schema index of `schema`. Fails if two field names have the same hash, so that a name of
+/// the schema is always found at its index.
+pub(crate) fn serialize_schema_index(schema: &Schema) -> Result<Vec<u8>> {
+    let mut records = schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(i, field)| (xxh64(field.name().as_bytes(), 0), i as u32))
+        .collect::<Vec<_>>();
+    records.sort_unstable();
+    if let Some(pair) = records.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        return Err(general_error!(format!(
+            "Fields {} and {} have the same name hash",
+            pair[0].1, pair[1].1
+        )));
+    }
+    Ok(records
+        .into_iter()
+        .flat_map(|(hash, i)| [hash.to_le_bytes().as_slice(), &i.to_le_bytes()].concat())
+        .collect())
+}
+
+/// Index of the field named `name` in the schema index, by binary search.
+pub(crate) fn lookup_schema_index(index: &[u8], name: &str) -> Result<Option<usize>> {
+    if index.len() % SCHEMA_INDEX_RECORD_SIZE != 0 {
+        return Err(Error::ParseError(
+            "Truncated SchemaIndex section".to_string(),
+        ));
+    }
+    let hash = xxh64(name.as_bytes(), 0);
+    let records = index
+        .chunks_exact(SCHEMA_INDEX_RECORD_SIZE)
+        .collect::<Vec<_>>();
+    Ok(records
+        .binary_search_by_key(&hash, |record| LittleEndian::read_u64(record))
+        .ok()
+        .map(|pos| LittleEndian::read_u32(&records[pos][8..]) as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_schema::{DataType, Field};
+
+    use super::*;
+
+    #[test]
+    fn test_metadata_segments() {
+        let columns = vec![vec![1, 2, 3], vec![], vec![4; 10]];
+        let segment = Bytes::from(serialize_segment(&columns));
+        for (i, column) in columns.iter().enumerate() {
+            assert_eq!(segment_column(&segment, i).unwrap(), column.as_slice());
+        }
+        assert!(segment_column(&segment, 3).is_err());
+        assert!(segment_column(&segment.slice(..20), 2).is_err());
+
+        let schema = Schema::new(
+            (0..100)
+                .map(|i| Field::new(format!("c{i}"), DataType::Int32, true))
+                .collect::<Vec<_>>(),
+        );
+        let index = serialize_schema_index(&schema).unwrap();
+        assert_eq!(index.len(), 100 * SCHEMA_INDEX_RECORD_SIZE);
+        for i in 0..100 {
+            assert_eq!(
+                lookup_schema_index(&index, &format!("c{i}")).unwrap(),
+                Some(i)
+            );
+        }
+        assert_eq!(lookup_schema_index(&index, "c100").unwrap(), None);
+        assert!(lookup_schema_index(&index[1..], "c0").is_err());
+    }
+}
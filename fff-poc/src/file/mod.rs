@@ -1 +1,11 @@
+pub mod bloom_filters;
+pub mod column_families;
+pub mod encunit_index;
 pub mod footer;
+pub mod key_value_metadata;
+pub mod manifest;
+pub mod metadata_segments;
+pub mod schema_fingerprint;
+pub mod sort_order;
+pub mod wasm_aot;
+pub mod wasm_modules;
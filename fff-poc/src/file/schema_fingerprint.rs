@@ -0,0 +1,167 @@
+//! Fingerprint of the schema of a file, stored in the `schema_fingerprint` of the footer, so that
+//! readers of many files, e.g., a dataset reader or a compaction tool, group the files of the same
+//! schema by reading a single integer per file, see
+//! [`get_schema_fingerprint`](crate::reader::get_schema_fingerprint) and
+//! [`same_schema`](crate::reader::same_schema).
+//!
+//! The fingerprint hashes a normalized form of the Arrow schema: the names, the types and the
+//! nullability of the fields, nested ones included. Metadata, of the schema and of the fields,
+//! and dictionary ids are left out, as they do not change how files are read together.
+
+use arrow_schema::{DataType, Field, Schema};
+use fff_core::errors::Result;
+use fff_format::File::fff::flatbuf as fb;
+
+use crate::common::checksum::{create_checksum, ChecksumType};
+use crate::file::footer::parse_footer;
+
+/// Fingerprint of `schema`, never 0.
+pub fn schema_fingerprint(schema: &Schema) -> u64 {
+    let mut normalized = String::new();
+    for field in schema.fields() {
+        write_field(&mut normalized, field);
+    }
+    let mut checksum = create_checksum(&ChecksumType::XxHash);
+    checksum.update(normalized.as_bytes());
+    // 0 stands for no fingerprint in the footer.
+    checksum.finalize().max(1)
+}
+
+fn write_field(out: &mut String, field: &Field) {
+    // Names are length-prefixed so that no two schemas have the same normalized form.
+    out.push_str(&format!("{}:{}", field.name().len(), field.name()));
+    out.push(if field.is_nullable() { '?' } else { '!' });
+    write_type(out, field.data_type());
+    out.push(';');
+}
+
+fn write_type(out: &mut String, data_type: &DataType) {
+    match data_type {
+        DataType::List(child)
+        | DataType::LargeList(child)
+        | DataType::ListView(child)
+        | DataType::LargeListView(child) => {
+            out.push_str(&format!("{}(", list_name(data_type)));
+            write_field(out, child);
+            out.push(')');
+        }
+        DataType::FixedSizeList(child, size) => {
+            out.push_str(&format!("FixedSizeList[{size}]("));
+            write_field(out, child);
+            out.push(')');
+        }
+        DataType::Struct(fields) => {
+            out.push_str("Struct(");
+            fields.iter().for_each(|field| write_field(out, field));
+            out.push(')');
+        }
+        DataType::Map(entries, sorted) => {
+            out.push_str(&format!("Map[{sorted}]("));
+            write_field(out, entries);
+            out.push(')');
+        }
+        DataType::Dictionary(key_type, value_type) => {
+            out.push_str("Dictionary(");
+            write_type(out, key_type);
+            out.push(',');
+            write_type(out, value_type);
+            out.push(')');
+        }
+        DataType::RunEndEncoded(run_ends, values) => {
+            out.push_str("RunEndEncoded(");
+            write_field(out, run_ends);
+            write_field(out, values);
+            out.push(')');
+        }
+        DataType::Union(fields, mode) => {
+            out.push_str(&format!("Union[{mode:?}]("));
+            for (type_id, field) in fields.iter() {
+                out.push_str(&format!("{type_id}="));
+                write_field(out, field);
+            }
+            out.push(')');
+        }
+        _ => out.push_str(&format!("{data_type:?}")),
+    }
+}
+
+fn list_name(data_type: &DataType) -> &'static str {
+    match data_type {
+        DataType::List(_) => "List",
+        DataType::LargeList(_) => "LargeList",
+        DataType::ListView(_) => "ListView",
+        _ => "LargeListView",
+    }
+}
+
+/// The fingerprint of the footer, or of its schema in files written before fingerprints were.
+pub(crate) fn from_fb(footer_fbs: &fb::Footer) -> Result<u64> {
+    match footer_fbs.schema_fingerprint() {
+        0 => {
+            let (schema, ..) = parse_footer(footer_fbs)?;
+            Ok(schema_fingerprint(&schema))
+        }
+        fingerprint => Ok(fingerprint),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use arrow_schema::TimeUnit;
+
+    use super::*;
+
+    #[test]
+    fn test_schema_fingerprint() {
+        let fields = vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new_list("b", Field::new_list_field(DataType::Utf8, true), false),
+            Field::new(
+                "c",
+                DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+                true,
+            ),
+        ];
+        let schema = Schema::new(fields.clone());
+        let fingerprint = schema_fingerprint(&schema);
+        assert_eq!(fingerprint, schema_fingerprint(&schema.clone()));
+
+        // Metadata does not count.
+        let metadata = HashMap::from([("k".to_string(), "v".to_string())]);
+        let with_metadata = Schema::new_with_metadata(
+            vec![
+                fields[0].clone().with_metadata(metadata.clone()),
+                fields[1].clone(),
+                fields[2].clone(),
+            ],
+            metadata,
+        );
+        assert_eq!(schema_fingerprint(&with_metadata), fingerprint);
+
+        // Names, types, nullability and nested fields do.
+        let mut changed = vec![];
+        for field in [
+            Field::new("x", DataType::Int32, true),
+            Field::new("a", DataType::Int64, true),
+            Field::new("a", DataType::Int32, false),
+        ] {
+            let mut fields = fields.clone();
+            fields[0] = field;
+            changed.push(Schema::new(fields));
+        }
+        let mut nested = fields.clone();
+        nested[1] = Field::new_list("b", Field::new_list_field(DataType::Utf8, false), false);
+        changed.push(Schema::new(nested));
+        changed.push(Schema::new(fields[..2].to_vec()));
+        changed.push(Schema::new(vec![
+            fields[1].clone(),
+            fields[0].clone(),
+            fields[2].clone(),
+        ]));
+        for schema in changed {
+            assert_ne!(schema_fingerprint(&schema), fingerprint, "{schema:?}");
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! The order of the rows within each row group, written with
+//! [`FileWriterOptionsBuilder::sort_by`](crate::options::FileWriterOptionsBuilder::sort_by) as the
+//! `sort_order` of the footer, and read with
+//! [`FileReaderV2::sort_order`](crate::reader::FileReaderV2::sort_order).
+//!
+//! Rows are sorted by the first key column, then by the next ones for equal keys, as by
+//! [`lexsort_to_indices`]. Row groups are sorted independently: the order says nothing about rows
+//! of different row groups.
+
+use arrow::compute::{lexsort_to_indices, take_record_batch, SortColumn as ArrowSortColumn};
+use arrow_array::RecordBatch;
+use arrow_schema::{Schema, SortOptions};
+use fff_core::{errors::Result, general_error};
+use fff_format::File::fff::flatbuf as fb;
+use flatbuffers::{FlatBufferBuilder, ForwardsUOffset, Vector, WIPOffset};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortDirection {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+/// A key column of the sort order of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortColumn {
+    /// Index of the top-level column in the schema of the file.
+    pub column: usize,
+    pub descending: bool,
+    pub nulls_first: bool,
+}
+
+impl SortColumn {
+    /// Resolve the `(column name, direction)` keys against `schema`. Nulls come first, as by
+    /// default in Arrow.
+    pub(crate) fn try_from_names(
+        schema: &Schema,
+        keys: &[(String, SortDirection)],
+    ) -> Result<Vec<Self>> {
+        keys.iter()
+            .map(|(name, direction)| {
+                let (column, _) = schema
+                    .column_with_name(name)
+                    .ok_or_else(|| general_error!(format!("Sort column {name} not found")))?;
+                Ok(Self {
+                    column,
+                    descending: *direction == SortDirection::Descending,
+                    nulls_first: true,
+                })
+            })
+            .collect()
+    }
+
+    pub fn sort_options(&self) -> SortOptions {
+        SortOptions {
+            descending: self.descending,
+            nulls_first: self.nulls_first,
+        }
+    }
+}
+
+/// Sort the rows of `batch` by `sort_order`.
+pub(crate) fn sort_batch(batch: &RecordBatch, sort_order: &[SortColumn]) -> Result<RecordBatch> {
+    let columns = sort_order
+        .iter()
+        .map(|key| ArrowSortColumn {
+            values: batch.column(key.column).clone(),
+            options: Some(key.sort_options()),
+        })
+        .collect::<Vec<_>>();
+    let indices = lexsort_to_indices(&columns, None)?;
+    Ok(take_record_batch(batch, &indices)?)
+}
+
+/// The sort order left after keeping the top-level `columns` of the schema, given by their index
+/// in it, in their new order. The keys after the first dropped one are dropped too, as rows are
+/// not sorted by them anymore.
+pub(crate) fn select_columns(sort_order: &[SortColumn], columns: &[usize]) -> Vec<SortColumn> {
+    sort_order
+        .iter()
+        .map_while(|key| {
+            let column = columns.iter().position(|&c| c == key.column)?;
+            Some(SortColumn { column, ..*key })
+        })
+        .collect()
+}
+
+/// Serialize into the `sort_order` field of the footer, None if the rows are not sorted.
+pub(crate) fn to_fb<'fb>(
+    fbb: &mut FlatBufferBuilder<'fb>,
+    sort_order: &[SortColumn],
+) -> Option<WIPOffset<Vector<'fb, ForwardsUOffset<fb::SortColumn<'fb>>>>> {
+    if sort_order.is_empty() {
+        return None;
+    }
+    let keys = sort_order
+        .iter()
+        .map(|key| {
+            fb::SortColumn::create(
+                fbb,
+                &fb::SortColumnArgs {
+                    column: key.column as u32,
+                    descending: key.descending,
+                    nulls_first: key.nulls_first,
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+    Some(fbb.create_vector(&keys))
+}
+
+pub(crate) fn from_fb(footer: &fb::Footer) -> Vec<SortColumn> {
+    footer
+        .sort_order()
+        .into_iter()
+        .flatten()
+        .map(|key| SortColumn {
+            column: key.column() as usize,
+            descending: key.descending(),
+            nulls_first: key.nulls_first(),
+        })
+        .collect()
+}
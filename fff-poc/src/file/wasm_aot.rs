@@ -0,0 +1,105 @@
+//! The "WASMAotArtifacts" optional section, which locates the ahead-of-time compiled artifacts of
+//! the WASM modules embedded in a file, see [`fff_ude_wasm::aot`]. Readers trusting the file
+//! instantiate a module from the artifact matching their host instead of compiling it, see
+//! [`FileReaderV2Builder::with_trusted_wasm_aot`](crate::reader::FileReaderV2Builder::with_trusted_wasm_aot).
+//!
+//! The artifacts are written after the WASM binaries. The section holds one entry per artifact:
+//! the WASMId of its module as a little-endian u32, its offset and size as little-endian u64s,
+//! then the triple and the wasmtime version of its target, each as the length of its UTF-8 bytes
+//! as a little-endian u32 and the bytes.
+
+use byteorder::{ByteOrder, LittleEndian};
+use fff_core::errors::{Error, Result};
+use fff_ude_wasm::aot::AotTarget;
+
+use crate::context::WASMId;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasmAotArtifact {
+    pub wasm_id: WASMId,
+    pub target: AotTarget,
+    pub offset: u64,
+    pub size: u64,
+}
+
+pub(crate) fn serialize_wasm_aot_artifacts(artifacts: &[WasmAotArtifact]) -> Vec<u8> {
+    let mut buf = vec![];
+    for artifact in artifacts {
+        buf.extend_from_slice(&artifact.wasm_id.0.to_le_bytes());
+        buf.extend_from_slice(&artifact.offset.to_le_bytes());
+        buf.extend_from_slice(&artifact.size.to_le_bytes());
+        for s in [&artifact.target.triple, &artifact.target.wasmtime_version] {
+            buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+            buf.extend_from_slice(s.as_bytes());
+        }
+    }
+    buf
+}
+
+pub(crate) fn deserialize_wasm_aot_artifacts(mut buf: &[u8]) -> Result<Vec<WasmAotArtifact>> {
+    let truncated = || Error::ParseError("Truncated WASMAotArtifacts section".to_string());
+    let read_string = |buf: &mut &[u8]| -> Result<String> {
+        if buf.len() < 4 {
+            return Err(truncated());
+        }
+        let len = LittleEndian::read_u32(&buf[..4]) as usize;
+        let s = buf.get(4..4 + len).ok_or_else(truncated)?;
+        let s = std::str::from_utf8(s)
+            .map_err(|e| Error::ParseError(format!("Invalid target of an AOT artifact: {e}")))?
+            .to_string();
+        *buf = &buf[4 + len..];
+        Ok(s)
+    };
+    let mut artifacts = vec![];
+    while !buf.is_empty() {
+        if buf.len() < 20 {
+            return Err(truncated());
+        }
+        let wasm_id = WASMId(LittleEndian::read_u32(&buf[..4]));
+        let offset = LittleEndian::read_u64(&buf[4..12]);
+        let size = LittleEndian::read_u64(&buf[12..20]);
+        buf = &buf[20..];
+        let triple = read_string(&mut buf)?;
+        let wasmtime_version = read_string(&mut buf)?;
+        artifacts.push(WasmAotArtifact {
+            wasm_id,
+            target: AotTarget {
+                triple,
+                wasmtime_version,
+            },
+            offset,
+            size,
+        });
+    }
+    Ok(artifacts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wasm_aot_artifacts() {
+        let artifacts = vec![
+            WasmAotArtifact {
+                wasm_id: WASMId(0),
+                target: AotTarget::host(),
+                offset: 100,
+                size: 2000,
+            },
+            WasmAotArtifact {
+                wasm_id: WASMId(0),
+                target: AotTarget {
+                    triple: "aarch64-macos".to_string(),
+                    wasmtime_version: "27.0.0".to_string(),
+                },
+                offset: 2100,
+                size: 1800,
+            },
+        ];
+        let buf = serialize_wasm_aot_artifacts(&artifacts);
+        assert_eq!(deserialize_wasm_aot_artifacts(&buf).unwrap(), artifacts);
+        assert!(deserialize_wasm_aot_artifacts(&buf[..buf.len() - 1]).is_err());
+        assert!(deserialize_wasm_aot_artifacts(&buf[..10]).is_err());
+    }
+}
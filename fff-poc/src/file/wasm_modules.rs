@@ -0,0 +1,58 @@
+//! The "WASMModules" optional section, which records the content hash of each WASM module of a
+//! file and, for the modules stored by reference only, the URI to fetch them from.
+//!
+//! The section holds one entry per WASMId, in order: the hash as a little-endian u64, then the
+//! length of the URI as a little-endian u32 and its UTF-8 bytes. The URI is empty for the modules
+//! embedded in the file.
+
+use byteorder::{ByteOrder, LittleEndian};
+use fff_core::errors::{Error, Result};
+
+use crate::common::checksum::{create_checksum, ChecksumType};
+
+/// Content hash of a WASM module, see
+/// [`FileWriterOptionsBuilder::wasm_reference_only`](crate::options::FileWriterOptionsBuilder::wasm_reference_only).
+pub fn wasm_module_hash(wasm_binary: &[u8]) -> u64 {
+    let mut checksum = create_checksum(&ChecksumType::XxHash);
+    checksum.update(wasm_binary);
+    checksum.finalize()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasmModuleInfo {
+    pub hash: u64,
+    /// Where to fetch the module from, if it is not embedded in the file.
+    pub uri: Option<String>,
+}
+
+pub(crate) fn serialize_wasm_modules(modules: &[WasmModuleInfo]) -> Vec<u8> {
+    let mut buf = vec![];
+    for module in modules {
+        let uri = module.uri.as_deref().unwrap_or_default();
+        buf.extend_from_slice(&module.hash.to_le_bytes());
+        buf.extend_from_slice(&(uri.len() as u32).to_le_bytes());
+        buf.extend_from_slice(uri.as_bytes());
+    }
+    buf
+}
+
+pub(crate) fn deserialize_wasm_modules(mut buf: &[u8]) -> Result<Vec<WasmModuleInfo>> {
+    let truncated = || Error::ParseError("Truncated WASMModules section".to_string());
+    let mut modules = vec![];
+    while !buf.is_empty() {
+        if buf.len() < 12 {
+            return Err(truncated());
+        }
+        let hash = LittleEndian::read_u64(&buf[..8]);
+        let uri_len = LittleEndian::read_u32(&buf[8..12]) as usize;
+        let uri = buf.get(12..12 + uri_len).ok_or_else(truncated)?;
+        let uri = std::str::from_utf8(uri)
+            .map_err(|e| Error::ParseError(format!("Invalid URI of a WASM module: {e}")))?;
+        modules.push(WasmModuleInfo {
+            hash,
+            uri: (!uri.is_empty()).then(|| uri.to_string()),
+        });
+        buf = &buf[12 + uri_len..];
+    }
+    Ok(modules)
+}
@@ -0,0 +1,310 @@
+//! Human-readable summary of the layout of an F3 file.
+//!
+//! [`inspect_file`] reads the postscript and the footer only, no data is decoded. The summary
+//! lists the schema, the row groups with the chunks of every physical column, and the WASM
+//! binaries embedded in the file. WASM ids are the positions in that list.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use arrow_schema::SchemaRef;
+use fff_core::errors::{Error, Result};
+use fff_format::File::fff::flatbuf::{self as fb, root_as_footer};
+
+use crate::common::checksum::ChecksumType;
+use crate::file::footer::{Footer, MetadataSection};
+use crate::io::reader::Reader;
+use crate::reader::{get_metadata_buffer, locate_section, read_postscript};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncUnitSummary {
+    pub size: u32,
+    pub num_rows: u32,
+    /// Encoding type, with the WASM id for WASM encodings.
+    pub encoding: String,
+    pub compression: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkSummary {
+    pub offset: u64,
+    pub size: u32,
+    pub num_rows: u64,
+    pub dictionary_encoding: String,
+    pub encunits: Vec<EncUnitSummary>,
+    /// Size of the Zstd dictionary the EncUnits are compressed with, if any.
+    pub compression_dictionary_size: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowGroupSummary {
+    pub row_count: u32,
+    pub offset: u64,
+    pub size: u32,
+    /// Chunks of each physical column.
+    pub columns: Vec<Vec<ChunkSummary>>,
+}
+
+/// Result of [`inspect_file`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileSummary {
+    pub file_size: u64,
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub metadata_size: u32,
+    pub footer_size: u32,
+    pub checksum_type: ChecksumType,
+    pub schema: SchemaRef,
+    pub row_groups: Vec<RowGroupSummary>,
+    /// Location of each embedded WASM binary, indexed by WASM id.
+    pub wasm_binaries: Vec<MetadataSection>,
+}
+
+impl FileSummary {
+    pub fn num_rows(&self) -> u64 {
+        self.row_groups.iter().map(|rg| rg.row_count as u64).sum()
+    }
+}
+
+impl fmt::Display for FileSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "file size: {} bytes, version {}.{}",
+            self.file_size, self.major_version, self.minor_version
+        )?;
+        writeln!(
+            f,
+            "metadata: {} bytes, footer: {} bytes, checksum: {:?}",
+            self.metadata_size, self.footer_size, self.checksum_type
+        )?;
+        writeln!(f, "schema:")?;
+        for field in self.schema.fields() {
+            writeln!(
+                f,
+                "  {}: {}{}",
+                field.name(),
+                field.data_type(),
+                if field.is_nullable() { "" } else { " not null" }
+            )?;
+        }
+        writeln!(
+            f,
+            "row groups: {}, rows: {}",
+            self.row_groups.len(),
+            self.num_rows()
+        )?;
+        for (i, rg) in self.row_groups.iter().enumerate() {
+            writeln!(
+                f,
+                "  row group {}: {} rows, {} bytes at offset {}",
+                i, rg.row_count, rg.size, rg.offset
+            )?;
+            for (j, chunks) in rg.columns.iter().enumerate() {
+                for chunk in chunks {
+                    let encodings: BTreeSet<&str> =
+                        chunk.encunits.iter().map(|u| u.encoding.as_str()).collect();
+                    let compressions: BTreeSet<&str> = chunk
+                        .encunits
+                        .iter()
+                        .map(|u| u.compression.as_str())
+                        .collect();
+                    writeln!(
+                        f,
+                        "    column {}: {} rows, {} bytes at offset {}, {} encunits, \
+                         encodings: {}, compression: {}{}, dictionary: {}",
+                        j,
+                        chunk.num_rows,
+                        chunk.size,
+                        chunk.offset,
+                        chunk.encunits.len(),
+                        encodings.into_iter().collect::<Vec<_>>().join(","),
+                        compressions.into_iter().collect::<Vec<_>>().join(","),
+                        chunk
+                            .compression_dictionary_size
+                            .map_or(String::new(), |size| format!(
+                                " with a {size}-byte dictionary"
+                            )),
+                        chunk.dictionary_encoding
+                    )?;
+                }
+            }
+        }
+        writeln!(f, "wasm binaries: {}", self.wasm_binaries.len())?;
+        for (i, wasm) in self.wasm_binaries.iter().enumerate() {
+            writeln!(
+                f,
+                "  wasm {}: {} bytes at offset {}",
+                i, wasm.size, wasm.offset
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn summarize_chunk(chunk: fb::Chunk) -> ChunkSummary {
+    let encunits = chunk
+        .encunits()
+        .into_iter()
+        .flatten()
+        .map(|encunit| {
+            let encoding = match encunit.encoding() {
+                Some(encoding) => match encoding.wasm_encoding() {
+                    Some(wasm) => format!("{:?}#{}", encoding.type_(), wasm.wasm_id()),
+                    None => format!("{:?}", encoding.type_()),
+                },
+                None => "None".to_string(),
+            };
+            EncUnitSummary {
+                size: encunit.size_(),
+                num_rows: encunit.num_rows(),
+                encoding,
+                compression: format!("{:?}", encunit.compression()),
+            }
+        })
+        .collect();
+    ChunkSummary {
+        offset: chunk.offset(),
+        size: chunk.size_(),
+        num_rows: chunk.num_rows(),
+        dictionary_encoding: format!("{:?}", chunk.encoding_type()),
+        encunits,
+        compression_dictionary_size: chunk.compression_dictionary().map(|x| x.len()),
+    }
+}
+
+fn wasm_binary_locations<R: Reader>(
+    reader: &R,
+    sections: Option<fb::OptionalMetadataSections>,
+) -> Result<Vec<MetadataSection>> {
+    let Some(range) = locate_section(sections, "WASMBinaries")? else {
+        return Ok(vec![]);
+    };
+    let mut buf = vec![0; (range.end - range.start) as usize];
+    reader.read_exact_at(&mut buf, range.start)?;
+    let wasm_binaries = flatbuffers::root::<fb::WASMBinaries>(&buf)?;
+    Ok(wasm_binaries
+        .wasm_binaries()
+        .into_iter()
+        .flatten()
+        .map(|loc| MetadataSection {
+            offset: loc.offset(),
+            size: loc.size_(),
+            compression_type: loc.compression_type(),
+        })
+        .collect())
+}
+
+/// Summarize the layout of an F3 file. See the [module documentation](self).
+pub fn inspect_file<R: Reader>(reader: &R) -> Result<FileSummary> {
+    let file_size = reader.size()?;
+    let post_script = read_postscript(reader, file_size)?;
+    let owner = get_metadata_buffer(reader, &post_script)?;
+    let footer = Footer::try_new(&owner, file_size as usize, &post_script)?;
+    let footer_fbs = root_as_footer(&owner[owner.len() - post_script.footer_size as usize..])
+        .map_err(|e| Error::ParseError(format!("Unable to get root as footer: {e:?}")))?;
+    let row_groups = footer
+        .row_group_metadatas()
+        .iter()
+        .map(|rg_meta| RowGroupSummary {
+            row_count: rg_meta.row_count,
+            offset: rg_meta._offset,
+            size: rg_meta._size,
+            columns: rg_meta
+                .column_metadatas
+                .iter()
+                .map(|col_meta| {
+                    col_meta
+                        .column_chunks()
+                        .into_iter()
+                        .flatten()
+                        .map(summarize_chunk)
+                        .collect()
+                })
+                .collect(),
+        })
+        .collect();
+    Ok(FileSummary {
+        file_size,
+        major_version: post_script.major_version,
+        minor_version: post_script.minor_version,
+        metadata_size: post_script.metadata_size,
+        footer_size: post_script.footer_size,
+        checksum_type: post_script.checksum_type,
+        schema: footer.schema().clone(),
+        row_groups,
+        wasm_binaries: wasm_binary_locations(reader, footer_fbs.optional_sections())?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Seek;
+    use std::sync::Arc;
+
+    use arrow_array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+
+    use super::*;
+    use crate::options::FileWriterOptionsBuilder;
+    use crate::writer::FileWriter;
+
+    #[test]
+    fn test_inspect_file() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter(
+                    (0..100).map(|x| (x % 3 != 0).then_some(x)),
+                )) as ArrayRef,
+                Arc::new(StringArray::from_iter_values(
+                    (0..100).map(|x| format!("s{}", x % 10)),
+                )),
+            ],
+        )
+        .unwrap();
+        let mut file = tempfile::tempfile().unwrap();
+        let mut writer = FileWriter::try_new(
+            schema.clone(),
+            &file,
+            FileWriterOptionsBuilder::with_defaults()
+                .set_row_group_size(40)
+                .write_built_in_wasm(true)
+                .build(),
+        )
+        .unwrap();
+        writer.write_batch(&batch).unwrap();
+        writer.finish().unwrap();
+        file.rewind().unwrap();
+        let file = Arc::new(file);
+
+        let summary = inspect_file(&file).unwrap();
+        assert_eq!(summary.file_size, file.size().unwrap());
+        assert_eq!(summary.schema, schema);
+        assert_eq!(summary.num_rows(), 100);
+        assert_eq!(
+            summary
+                .row_groups
+                .iter()
+                .map(|rg| rg.row_count)
+                .collect::<Vec<_>>(),
+            vec![40, 40, 20]
+        );
+        for rg in &summary.row_groups {
+            for chunks in &rg.columns {
+                assert_eq!(
+                    chunks.iter().map(|c| c.num_rows).sum::<u64>(),
+                    rg.row_count as u64
+                );
+            }
+        }
+        assert_eq!(summary.wasm_binaries.len(), 1);
+        let output = summary.to_string();
+        assert!(output.contains("row groups: 3, rows: 100"));
+        assert!(output.contains("wasm 0: "));
+    }
+}
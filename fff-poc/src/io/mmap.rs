@@ -0,0 +1,164 @@
+//! Memory-mapped reads of local files, see
+//! [`FileReaderV2Builder::prefer_mmap`](crate::reader::FileReaderV2Builder::prefer_mmap).
+//!
+//! Reads of a local file otherwise cost a `pread` each. [`MmapReader`] maps the file once and
+//! serves reads from the page cache directly: [`Reader::read_ranges`] hands out slices of the
+//! mapping as [`Bytes`] without copying them, and [`Reader::read_exact_at`] copies from the
+//! mapping without a syscall.
+//!
+//! Mapping a file is `unsafe`: the file must not be modified while mapped. Files are not
+//! immutable once written, e.g., [`FileWriter::try_append`](crate::writer::FileWriter::try_append)
+//! without footer versioning overwrites the footer in place, so callers must make sure that no
+//! writer modifies the file concurrently.
+
+use std::{fs::File, ops::Range, sync::Arc};
+
+use bytes::Bytes;
+use fff_core::errors::{Error, Result};
+use memmap2::Mmap;
+
+use super::reader::Reader;
+
+/// A read-only memory mapping of a file. Clones share the mapping, which is unmapped once the
+/// last of them and of the [`Bytes`] read from it are dropped.
+#[derive(Debug, Clone)]
+pub struct MmapReader {
+    mmap: Bytes,
+}
+
+impl MmapReader {
+    /// # Safety
+    ///
+    /// The file must not be modified nor truncated, by this process or another, as long as the
+    /// mapping or [`Bytes`] read from it are alive: modified bytes change under the readers, and
+    /// accessing pages past a new end raises SIGBUS. Appending to the file with footer
+    /// versioning only grows it, and is fine.
+    pub unsafe fn try_new(file: &File) -> Result<Self> {
+        // SAFETY: the mapping is read-only, and the caller guarantees that the file is not
+        // modified while mapped.
+        let mmap = unsafe { Mmap::map(file)? };
+        Ok(Self {
+            mmap: Bytes::from_owner(mmap),
+        })
+    }
+
+    /// The bytes of `range` of the file, without copying them.
+    pub fn slice(&self, range: Range<u64>) -> Result<Bytes> {
+        if range.end > self.mmap.len() as u64 || range.start > range.end {
+            return Err(Error::IndexOutOfBound(range.end as usize, self.mmap.len()));
+        }
+        Ok(self.mmap.slice(range.start as usize..range.end as usize))
+    }
+}
+
+impl Reader for MmapReader {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        buf.copy_from_slice(&self.slice(offset..offset + buf.len() as u64)?);
+        Ok(())
+    }
+
+    fn size(&self) -> Result<u64> {
+        Ok(self.mmap.len() as u64)
+    }
+
+    /// The ranges are slices of the mapping.
+    fn read_ranges(&self, ranges: &[Range<u64>]) -> Result<Vec<Bytes>> {
+        ranges
+            .iter()
+            .map(|range| self.slice(range.clone()))
+            .collect()
+    }
+}
+
+/// A local file, read either with `pread` or through a memory mapping.
+#[derive(Debug, Clone)]
+pub enum LocalFile {
+    File(Arc<File>),
+    Mmap(MmapReader),
+}
+
+impl LocalFile {
+    /// Read through a memory mapping if `prefer_mmap`, and if the file can be mapped, e.g., it is
+    /// not a pipe. Falls back to `pread` otherwise.
+    ///
+    /// # Safety
+    ///
+    /// With `prefer_mmap`, as for [`MmapReader::try_new`].
+    pub unsafe fn prefer_mmap(self, prefer_mmap: bool) -> Self {
+        match self {
+            // SAFETY: guaranteed by the caller.
+            LocalFile::File(file) if prefer_mmap => match unsafe { MmapReader::try_new(&file) } {
+                Ok(mmap) => LocalFile::Mmap(mmap),
+                Err(_) => LocalFile::File(file),
+            },
+            local_file => local_file,
+        }
+    }
+
+    pub fn is_mmap(&self) -> bool {
+        matches!(self, LocalFile::Mmap(_))
+    }
+}
+
+impl From<File> for LocalFile {
+    fn from(file: File) -> Self {
+        LocalFile::File(Arc::new(file))
+    }
+}
+
+impl From<Arc<File>> for LocalFile {
+    fn from(file: Arc<File>) -> Self {
+        LocalFile::File(file)
+    }
+}
+
+impl Reader for LocalFile {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        match self {
+            LocalFile::File(file) => Reader::read_exact_at(file, buf, offset),
+            LocalFile::Mmap(mmap) => mmap.read_exact_at(buf, offset),
+        }
+    }
+
+    fn size(&self) -> Result<u64> {
+        match self {
+            LocalFile::File(file) => Reader::size(file),
+            LocalFile::Mmap(mmap) => mmap.size(),
+        }
+    }
+
+    fn read_ranges(&self, ranges: &[Range<u64>]) -> Result<Vec<Bytes>> {
+        match self {
+            LocalFile::File(file) => Reader::read_ranges(file, ranges),
+            LocalFile::Mmap(mmap) => mmap.read_ranges(ranges),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn test_mmap_reader() {
+        let data = (0..=255).collect::<Vec<u8>>();
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&data).unwrap();
+        // SAFETY: the file is not modified while mapped.
+        let local_file = unsafe { LocalFile::from(file).prefer_mmap(true) };
+        assert!(local_file.is_mmap());
+        assert_eq!(local_file.size().unwrap(), 256);
+        let mut buf = [0; 3];
+        local_file.read_exact_at(&mut buf, 10).unwrap();
+        assert_eq!(buf, [10, 11, 12]);
+        let ranges = local_file.read_ranges(&[0..2, 254..256]).unwrap();
+        assert_eq!(
+            ranges,
+            vec![Bytes::from_static(&[0, 1]), Bytes::from_static(&[254, 255])]
+        );
+        assert!(local_file.read_exact_at(&mut buf, 254).is_err());
+        assert!(local_file.read_ranges(&[250..257]).is_err());
+    }
+}
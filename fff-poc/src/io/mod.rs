@@ -1 +1,8 @@
+pub mod mmap;
+pub mod prefetch;
+pub mod read_ahead;
 pub mod reader;
+pub mod runtime;
+pub mod scheduler;
+#[cfg(feature = "io-uring")]
+pub mod uring;
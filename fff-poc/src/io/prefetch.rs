@@ -0,0 +1,96 @@
+//! Coalesced fetches of the chunks of a column family, see
+//! [`FileWriterOptionsBuilder::set_column_families`](crate::options::FileWriterOptionsBuilder::set_column_families).
+
+use std::ops::Range;
+
+use bytes::Bytes;
+use fff_core::errors::Result;
+use fff_format::File::fff::flatbuf as fb;
+
+use super::reader::Reader;
+
+/// Byte ranges of the file read ahead of the decoders, sorted by offset.
+#[derive(Debug, Default)]
+pub struct PrefetchedChunks {
+    ranges: Vec<(u64, Bytes)>,
+}
+
+impl PrefetchedChunks {
+    /// Read the chunks of `column_metas` lying in the same IO unit of a column family at once,
+    /// from the first to the last of them. `family_iounits` must be sorted and not overlap.
+    /// Chunks alone in their IO unit, or in none, are left to the decoders.
+    pub fn try_new<R: Reader + ?Sized>(
+        reader: &R,
+        family_iounits: &[Range<u64>],
+        column_metas: &[fb::ColumnMetadata],
+    ) -> Result<Self> {
+        // First and last byte of the chunks of each IO unit, and their number.
+        let mut spans: Vec<(usize, Range<u64>, usize)> = vec![];
+        for chunk in column_metas
+            .iter()
+            .flat_map(|column_meta| column_meta.column_chunks().into_iter().flatten())
+        {
+            let chunk_range = chunk.offset()..chunk.offset() + chunk.size_() as u64;
+            let pos = family_iounits.partition_point(|iounit| iounit.end <= chunk_range.start);
+            if !family_iounits
+                .get(pos)
+                .is_some_and(|iounit| iounit.start <= chunk_range.start)
+            {
+                continue;
+            }
+            match spans.iter_mut().find(|(iounit, _, _)| *iounit == pos) {
+                Some((_, span, num_chunks)) => {
+                    span.start = span.start.min(chunk_range.start);
+                    span.end = span.end.max(chunk_range.end);
+                    *num_chunks += 1;
+                }
+                None => spans.push((pos, chunk_range, 1)),
+            }
+        }
+        let spans = spans
+            .into_iter()
+            .filter(|(_, _, num_chunks)| *num_chunks > 1)
+            .map(|(_, span, _)| span)
+            .collect::<Vec<_>>();
+        if spans.is_empty() {
+            return Ok(Self::default());
+        }
+        trace_span!(
+            "read_chunks",
+            bytes = spans.iter().map(|s| s.end - s.start).sum::<u64>()
+        );
+        let mut ranges = spans
+            .iter()
+            .map(|span| span.start)
+            .zip(reader.read_ranges(&spans)?)
+            .collect::<Vec<_>>();
+        ranges.sort_unstable_by_key(|(start, _)| *start);
+        Ok(Self { ranges })
+    }
+
+    /// Byte ranges already read, by their offset. They must not overlap.
+    pub(crate) fn from_ranges(mut ranges: Vec<(u64, Bytes)>) -> Self {
+        ranges.sort_unstable_by_key(|(start, _)| *start);
+        Self { ranges }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Fill `buf` from the prefetched bytes at `offset`, if they cover it.
+    pub fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> bool {
+        let pos = self.ranges.partition_point(|(start, _)| *start <= offset);
+        let Some((start, bytes)) = pos.checked_sub(1).map(|pos| &self.ranges[pos]) else {
+            return false;
+        };
+        let begin = (offset - start) as usize;
+        match bytes.get(begin..begin + buf.len()) {
+            Some(bytes) => {
+                buf.copy_from_slice(bytes);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! A [`Reader`] reading ahead of the requests according to the access pattern it observes.
+
+use std::{
+    collections::VecDeque,
+    ops::Range,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use bytes::Bytes;
+use fff_core::errors::Result;
+
+use super::reader::{IoMetrics, ReadMetrics, Reader};
+
+/// Number of recent requests the access pattern is classified from.
+const HISTORY_LEN: usize = 8;
+/// Read-ahead of the first sequential request, doubled on each next one.
+const MIN_READ_AHEAD: u64 = 64 * 1024;
+const MAX_READ_AHEAD: u64 = 8 * 1024 * 1024;
+/// Bytes a request may skip after the previous one and still be sequential, e.g., the chunks of
+/// columns left out of a projection. Doubled while sequential, halved while random.
+const MIN_GAP: u64 = 4 * 1024;
+const MAX_GAP: u64 = 1024 * 1024;
+
+/// The access pattern observed by a [`ReadAheadReader`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessPattern {
+    /// Most recent requests start at or shortly after the end of the previous one, e.g., a scan.
+    Sequential,
+    /// E.g., reading rows selected by their indexes, or the footer.
+    Random,
+}
+
+#[derive(Debug)]
+struct ReadAheadState {
+    /// Bytes read ahead of the last request, starting at `buffer_start`.
+    buffer_start: u64,
+    buffer: Bytes,
+    last_end: Option<u64>,
+    /// Whether each recent request was sequential, most recent last.
+    history: VecDeque<bool>,
+    read_ahead: u64,
+    gap: u64,
+}
+
+impl Default for ReadAheadState {
+    fn default() -> Self {
+        Self {
+            buffer_start: 0,
+            buffer: Bytes::new(),
+            last_end: None,
+            history: VecDeque::with_capacity(HISTORY_LEN),
+            read_ahead: 0,
+            gap: MIN_GAP,
+        }
+    }
+}
+
+impl ReadAheadState {
+    fn access_pattern(&self) -> AccessPattern {
+        // Three quarters of a full history, so that a few seeks do not stop a scan.
+        let num_sequential = self
+            .history
+            .iter()
+            .filter(|&&sequential| sequential)
+            .count();
+        if num_sequential * 4 >= HISTORY_LEN * 3 {
+            AccessPattern::Sequential
+        } else {
+            AccessPattern::Random
+        }
+    }
+
+    /// Record a request and adapt the read-ahead and gap to the access pattern.
+    fn record(&mut self, offset: u64, end: u64) {
+        let sequential = self
+            .last_end
+            .is_some_and(|last_end| offset >= last_end && offset - last_end <= self.gap);
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(sequential);
+        self.last_end = Some(end);
+        match self.access_pattern() {
+            AccessPattern::Sequential => {
+                self.read_ahead = (self.read_ahead * 2).clamp(MIN_READ_AHEAD, MAX_READ_AHEAD);
+                self.gap = (self.gap * 2).min(MAX_GAP);
+            }
+            AccessPattern::Random => {
+                self.read_ahead = 0;
+                self.gap = (self.gap / 2).max(MIN_GAP);
+            }
+        }
+    }
+
+    /// Copy the range from the bytes read ahead, if they cover it.
+    fn read_buffered(&self, buf: &mut [u8], offset: u64) -> bool {
+        let Some(begin) = offset.checked_sub(self.buffer_start) else {
+            return false;
+        };
+        match self.buffer.get(begin as usize..begin as usize + buf.len()) {
+            Some(bytes) => {
+                buf.copy_from_slice(bytes);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Read ahead of sequential requests, e.g., to turn the chunk reads of a scan into few large
+/// reads on object stores, without reading more than requested on random access.
+///
+/// The access pattern is classified from the recent requests, instead of being configured: while
+/// sequential, each request missing the bytes read ahead reads up to twice as far ahead as the
+/// previous one, up to 8MiB, and tolerates larger skips. Clones share the bytes read ahead.
+#[derive(Clone)]
+pub struct ReadAheadReader<R> {
+    inner: R,
+    state: Arc<Mutex<ReadAheadState>>,
+    size: Arc<OnceLock<u64>>,
+}
+
+impl<R> ReadAheadReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            state: Arc::default(),
+            size: Arc::default(),
+        }
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn access_pattern(&self) -> AccessPattern {
+        self.state.lock().unwrap().access_pattern()
+    }
+
+    /// Number of bytes read ahead of the next request missing the bytes read ahead so far.
+    pub fn read_ahead(&self) -> u64 {
+        self.state.lock().unwrap().read_ahead
+    }
+}
+
+impl<R: Reader> Reader for ReadAheadReader<R> {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        let end = offset + buf.len() as u64;
+        let read_ahead = {
+            let mut state = self.state.lock().unwrap();
+            let buffered = state.read_buffered(buf, offset);
+            state.record(offset, end);
+            if buffered {
+                return Ok(());
+            }
+            state.read_ahead
+        };
+        // The lock is not held while reading, so that concurrent reads are not serialized.
+        let read_end = if read_ahead > 0 {
+            let size = match self.size.get() {
+                Some(size) => *size,
+                None => {
+                    let size = self.inner.size()?;
+                    *self.size.get_or_init(|| size)
+                }
+            };
+            (end + read_ahead).min(size).max(end)
+        } else {
+            end
+        };
+        if read_end == end {
+            return self.inner.read_exact_at(buf, offset);
+        }
+        let mut read = vec![0; (read_end - offset) as usize];
+        self.inner.read_exact_at(&mut read, offset)?;
+        buf.copy_from_slice(&read[..buf.len()]);
+        let mut state = self.state.lock().unwrap();
+        state.buffer_start = offset;
+        state.buffer = Bytes::from(read);
+        Ok(())
+    }
+
+    fn size(&self) -> Result<u64> {
+        self.inner.size()
+    }
+
+    fn retry_read_exact_at(&self, buf: &mut [u8], offset: u64, attempt: usize) -> Result<bool> {
+        // The bytes read ahead may be the ones that failed verification.
+        self.state.lock().unwrap().buffer = Bytes::new();
+        self.inner.retry_read_exact_at(buf, offset, attempt)
+    }
+
+    fn read_ranges(&self, ranges: &[Range<u64>]) -> Result<Vec<Bytes>> {
+        // Vectored reads are planned by the caller, so they are not read ahead.
+        self.inner.read_ranges(ranges)
+    }
+
+    fn io_metrics(&self) -> Option<IoMetrics> {
+        self.inner.io_metrics()
+    }
+
+    fn read_metrics(&self) -> Option<ReadMetrics> {
+        self.inner.read_metrics()
+    }
+}
@@ -1,22 +1,60 @@
 use bytes::Bytes;
 use fff_core::errors::Result;
-use futures::executor::block_on;
 use lazy_static::lazy_static;
 use object_store::path::Path;
 use object_store::ObjectStore;
 use parquet::file::reader::{ChunkReader, Length};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
 use std::io::Read;
+use std::ops::{AddAssign, Range};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 use std::{fs::File, os::unix::fs::FileExt};
 
+use super::runtime::{default_runtime, run_blocking, IoRuntime};
+
 lazy_static! {
-    static ref RUNTIME: tokio::runtime::Runtime = tokio::runtime::Runtime::new().unwrap();
+    pub(crate) static ref RUNTIME: tokio::runtime::Runtime =
+        tokio::runtime::Runtime::new().unwrap();
 }
 
 /// Read Trait for abstraction over local files and S3.
 pub trait Reader {
     fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()>;
     fn size(&self) -> Result<u64>;
+
+    /// Read several ranges at once, e.g., the chunks of a row group. Readers of remote objects
+    /// issue them as one vectored request, the others read them one by one.
+    fn read_ranges(&self, ranges: &[Range<u64>]) -> Result<Vec<Bytes>> {
+        ranges
+            .iter()
+            .map(|range| {
+                let mut buf = vec![0; (range.end - range.start) as usize];
+                self.read_exact_at(&mut buf, range.start)?;
+                Ok(Bytes::from(buf))
+            })
+            .collect()
+    }
+
+    /// Read the range again after its content failed checksum verification, e.g., from another
+    /// replica. `attempt` starts at 1 for the first retry.
+    /// Return false if there is nothing left to retry from, in which case `buf` is untouched.
+    fn retry_read_exact_at(&self, _buf: &mut [u8], _offset: u64, _attempt: usize) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// The reads so far, for readers counting them like [`CountingReader`].
+    fn io_metrics(&self) -> Option<IoMetrics> {
+        None
+    }
+
+    /// The requests to the remote store so far, for readers of remote objects like
+    /// [`ObjectStoreReadAt`].
+    fn read_metrics(&self) -> Option<ReadMetrics> {
+        None
+    }
 }
 
 impl Reader for File {
@@ -51,6 +89,335 @@ impl Reader for [u8] {
     }
 }
 
+/// Replicas of the same file, e.g., in a primary and a secondary bucket.
+///
+/// Reads go to the first replica and fail over to the next ones on IO errors.
+/// A range failing checksum verification is retried from the next replicas in order,
+/// see [`Reader::retry_read_exact_at`].
+#[derive(Clone)]
+pub struct ReplicaSet<R> {
+    replicas: Vec<R>,
+}
+
+impl<R: Reader> ReplicaSet<R> {
+    /// The first replica is the primary one.
+    pub fn try_new(replicas: Vec<R>) -> Result<Self> {
+        if replicas.is_empty() {
+            return Err(fff_core::errors::Error::General(
+                "A replica set needs at least one replica".to_string(),
+            ));
+        }
+        Ok(Self { replicas })
+    }
+
+    pub fn replicas(&self) -> &[R] {
+        &self.replicas
+    }
+
+    /// Return the result of the first replica that succeeds, or the last error.
+    fn failover<T>(&self, mut f: impl FnMut(&R) -> Result<T>) -> Result<T> {
+        let mut last_err = None;
+        for replica in &self.replicas {
+            match f(replica) {
+                Ok(v) => return Ok(v),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap())
+    }
+}
+
+impl<R: Reader> Reader for ReplicaSet<R> {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        self.failover(|replica| replica.read_exact_at(buf, offset))
+    }
+
+    fn size(&self) -> Result<u64> {
+        self.failover(|replica| replica.size())
+    }
+
+    fn read_ranges(&self, ranges: &[Range<u64>]) -> Result<Vec<Bytes>> {
+        self.failover(|replica| replica.read_ranges(ranges))
+    }
+
+    fn retry_read_exact_at(&self, buf: &mut [u8], offset: u64, attempt: usize) -> Result<bool> {
+        match self.replicas.get(attempt) {
+            Some(replica) => replica.read_exact_at(buf, offset).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    fn io_metrics(&self) -> Option<IoMetrics> {
+        self.replicas
+            .iter()
+            .filter_map(|replica| replica.io_metrics())
+            .reduce(|mut total, metrics| {
+                total += metrics;
+                total
+            })
+    }
+
+    fn read_metrics(&self) -> Option<ReadMetrics> {
+        self.replicas
+            .iter()
+            .filter_map(|replica| replica.read_metrics())
+            .reduce(|mut total, metrics| {
+                total += metrics;
+                total
+            })
+    }
+}
+
+/// Reads issued through a [`CountingReader`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IoMetrics {
+    /// Number of read requests, including retries. `size` calls are not counted, and
+    /// [`Reader::read_ranges`] calls count as one request.
+    pub num_requests: u64,
+    pub bytes_read: u64,
+}
+
+impl AddAssign for IoMetrics {
+    fn add_assign(&mut self, other: Self) {
+        self.num_requests += other.num_requests;
+        self.bytes_read += other.bytes_read;
+    }
+}
+
+/// Count the reads to the inner reader, e.g., to attribute the IO of a scan to a query.
+/// Clones share the counters.
+#[derive(Clone)]
+pub struct CountingReader<R> {
+    inner: R,
+    num_requests: Arc<AtomicU64>,
+    bytes_read: Arc<AtomicU64>,
+}
+
+impl<R> CountingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            num_requests: Arc::default(),
+            bytes_read: Arc::default(),
+        }
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn metrics(&self) -> IoMetrics {
+        IoMetrics {
+            num_requests: self.num_requests.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+        }
+    }
+
+    fn count(&self, len: u64) {
+        self.num_requests.fetch_add(1, Ordering::Relaxed);
+        self.bytes_read.fetch_add(len, Ordering::Relaxed);
+    }
+}
+
+impl<R: Reader> Reader for CountingReader<R> {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        self.count(buf.len() as u64);
+        self.inner.read_exact_at(buf, offset)
+    }
+
+    fn size(&self) -> Result<u64> {
+        self.inner.size()
+    }
+
+    fn read_ranges(&self, ranges: &[Range<u64>]) -> Result<Vec<Bytes>> {
+        self.count(ranges.iter().map(|range| range.end - range.start).sum());
+        self.inner.read_ranges(ranges)
+    }
+
+    fn retry_read_exact_at(&self, buf: &mut [u8], offset: u64, attempt: usize) -> Result<bool> {
+        let retried = self.inner.retry_read_exact_at(buf, offset, attempt)?;
+        if retried {
+            self.count(buf.len() as u64);
+        }
+        Ok(retried)
+    }
+
+    fn io_metrics(&self) -> Option<IoMetrics> {
+        Some(self.metrics())
+    }
+
+    fn read_metrics(&self) -> Option<ReadMetrics> {
+        self.inner.read_metrics()
+    }
+}
+
+/// The first `size` bytes of the inner reader, e.g., an earlier version of a file written with
+/// footer versioning, see [`open_at_version`](crate::reader::open_at_version).
+#[derive(Clone)]
+pub struct SnapshotReader<R> {
+    inner: R,
+    size: u64,
+}
+
+impl<R> SnapshotReader<R> {
+    pub fn new(inner: R, size: u64) -> Self {
+        Self { inner, size }
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+}
+
+impl<R: Reader> SnapshotReader<R> {
+    fn check_range(&self, buf: &[u8], offset: u64) -> Result<()> {
+        if offset + buf.len() as u64 > self.size {
+            return Err(fff_core::errors::Error::IndexOutOfBound(
+                (offset + buf.len() as u64) as usize,
+                self.size as usize,
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<R: Reader> Reader for SnapshotReader<R> {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        self.check_range(buf, offset)?;
+        self.inner.read_exact_at(buf, offset)
+    }
+
+    fn size(&self) -> Result<u64> {
+        Ok(self.size)
+    }
+
+    fn read_ranges(&self, ranges: &[Range<u64>]) -> Result<Vec<Bytes>> {
+        if let Some(range) = ranges.iter().find(|range| range.end > self.size) {
+            return Err(fff_core::errors::Error::IndexOutOfBound(
+                range.end as usize,
+                self.size as usize,
+            ));
+        }
+        self.inner.read_ranges(ranges)
+    }
+
+    fn retry_read_exact_at(&self, buf: &mut [u8], offset: u64, attempt: usize) -> Result<bool> {
+        self.check_range(buf, offset)?;
+        self.inner.retry_read_exact_at(buf, offset, attempt)
+    }
+
+    fn io_metrics(&self) -> Option<IoMetrics> {
+        self.inner.io_metrics()
+    }
+
+    fn read_metrics(&self) -> Option<ReadMetrics> {
+        self.inner.read_metrics()
+    }
+}
+
+/// Retries of the requests of an [`ObjectStoreReadAt`], on top of those of the object store
+/// itself. Requests are retried after timeouts and errors other than client errors, waiting
+/// `initial_backoff` before the first retry and twice as long before each next one, up to
+/// `max_backoff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryConfig {
+    pub max_retries: usize,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryConfig {
+    /// No retries.
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// The wait before the retry `attempt`, starting at 1.
+    fn backoff(&self, attempt: usize) -> Duration {
+        let factor = 1u32 << attempt.saturating_sub(1).min(31);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Requests issued by an [`ObjectStoreReadAt`], see [`Reader::read_metrics`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadMetrics {
+    /// Number of requests, including `size` requests and retries. [`Reader::read_ranges`] calls
+    /// count as one request.
+    pub num_requests: u64,
+    pub bytes_read: u64,
+    pub num_retries: u64,
+    /// Requests failing with a client error, i.e., a 4xx status: the object is not found, the
+    /// credentials are refused or a precondition failed.
+    pub num_client_errors: u64,
+    /// Requests failing otherwise, i.e., with a 5xx status once the object store gave up
+    /// retrying, or a transport error.
+    pub num_server_errors: u64,
+    /// Requests that did not complete within the request timeout.
+    pub num_timeouts: u64,
+    /// Time spent waiting on requests, backoffs included.
+    pub wall_time: Duration,
+}
+
+impl AddAssign for ReadMetrics {
+    fn add_assign(&mut self, other: Self) {
+        self.num_requests += other.num_requests;
+        self.bytes_read += other.bytes_read;
+        self.num_retries += other.num_retries;
+        self.num_client_errors += other.num_client_errors;
+        self.num_server_errors += other.num_server_errors;
+        self.num_timeouts += other.num_timeouts;
+        self.wall_time += other.wall_time;
+    }
+}
+
+#[derive(Debug, Default)]
+struct ReadCounters {
+    num_requests: AtomicU64,
+    bytes_read: AtomicU64,
+    num_retries: AtomicU64,
+    num_client_errors: AtomicU64,
+    num_server_errors: AtomicU64,
+    num_timeouts: AtomicU64,
+    wall_time_nanos: AtomicU64,
+}
+
+impl ReadCounters {
+    fn load(&self) -> ReadMetrics {
+        ReadMetrics {
+            num_requests: self.num_requests.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            num_retries: self.num_retries.load(Ordering::Relaxed),
+            num_client_errors: self.num_client_errors.load(Ordering::Relaxed),
+            num_server_errors: self.num_server_errors.load(Ordering::Relaxed),
+            num_timeouts: self.num_timeouts.load(Ordering::Relaxed),
+            wall_time: Duration::from_nanos(self.wall_time_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Whether the store answered the request with a 4xx status, not worth retrying.
+fn is_client_error(err: &object_store::Error) -> bool {
+    matches!(
+        err,
+        object_store::Error::NotFound { .. }
+            | object_store::Error::AlreadyExists { .. }
+            | object_store::Error::Precondition { .. }
+            | object_store::Error::NotModified { .. }
+            | object_store::Error::PermissionDenied { .. }
+            | object_store::Error::Unauthenticated { .. }
+    )
+}
+
+/// A reader of an object of an [`ObjectStore`]. Clones share the metrics.
 #[derive(Clone)]
 pub struct ObjectStoreReadAt {
     object_store: Arc<dyn ObjectStore>,
@@ -58,6 +425,10 @@ pub struct ObjectStoreReadAt {
     /// CAUTION: here we have the assumption that the file size won't change accross read requests.
     /// This is simply to allow Parquet readers to have less overhead on multiple reads.
     cache_size: OnceLock<u64>,
+    runtime: Arc<dyn IoRuntime>,
+    retry: RetryConfig,
+    request_timeout: Option<Duration>,
+    counters: Arc<ReadCounters>,
 }
 
 impl ObjectStoreReadAt {
@@ -66,52 +437,131 @@ impl ObjectStoreReadAt {
             object_store,
             location,
             cache_size: OnceLock::new(),
+            runtime: default_runtime(),
+            retry: RetryConfig::default(),
+            request_timeout: None,
+            counters: Arc::default(),
         }
     }
+
+    /// Run the requests on `runtime` instead of the tokio runtime of the crate.
+    pub fn with_runtime(mut self, runtime: Arc<dyn IoRuntime>) -> Self {
+        self.runtime = runtime;
+        self
+    }
+
+    /// Retry failed requests, see [`RetryConfig`]. Not retried by default.
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Give up waiting on each request after `timeout`, each retry getting a new deadline.
+    /// Requests giving up keep running on the runtime until the store answers.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    pub fn metrics(&self) -> ReadMetrics {
+        self.counters.load()
+    }
+
+    /// Issue the request made by `request`, retrying it as configured, and count it.
+    fn request<T, F>(
+        &self,
+        request: impl Fn(Arc<dyn ObjectStore>, Arc<Path>) -> F,
+        num_bytes: impl Fn(&T) -> u64,
+    ) -> Result<T>
+    where
+        T: Send + 'static,
+        F: Future<Output = object_store::Result<T>> + Send + 'static,
+    {
+        let counters = &self.counters;
+        let start = Instant::now();
+        let mut attempt = 0;
+        let result = loop {
+            counters.num_requests.fetch_add(1, Ordering::Relaxed);
+            let future = request(Arc::clone(&self.object_store), self.location.clone());
+            let err = match run_blocking(self.runtime.as_ref(), future, self.request_timeout)? {
+                Some(Ok(output)) => {
+                    counters
+                        .bytes_read
+                        .fetch_add(num_bytes(&output), Ordering::Relaxed);
+                    break Ok(output);
+                }
+                Some(Err(err)) if is_client_error(&err) => {
+                    counters.num_client_errors.fetch_add(1, Ordering::Relaxed);
+                    break Err(fff_core::errors::Error::ObjectStore(err));
+                }
+                Some(Err(err)) => {
+                    counters.num_server_errors.fetch_add(1, Ordering::Relaxed);
+                    fff_core::errors::Error::ObjectStore(err)
+                }
+                None => {
+                    counters.num_timeouts.fetch_add(1, Ordering::Relaxed);
+                    fff_core::errors::Error::General(format!(
+                        "The request to {} timed out after {:?}",
+                        self.location,
+                        self.request_timeout.unwrap()
+                    ))
+                }
+            };
+            if attempt >= self.retry.max_retries {
+                break Err(err);
+            }
+            attempt += 1;
+            counters.num_retries.fetch_add(1, Ordering::Relaxed);
+            std::thread::sleep(self.retry.backoff(attempt));
+        };
+        counters
+            .wall_time_nanos
+            .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        result
+    }
 }
 
 impl Reader for ObjectStoreReadAt {
     fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
-        // let start = std::time::Instant::now();
-        let start_range = offset as usize;
-
-        let object_store = Arc::clone(&self.object_store);
-        let location = self.location.clone();
-        let len = buf.len();
-        let head_result = block_on(async move {
-            RUNTIME
-                .spawn(async move {
-                    object_store
-                        .get_range(&location, start_range..(start_range + len))
-                        .await
-                })
-                .await
-                .unwrap()
-        });
-
-        let bytes = head_result.map_err(fff_core::errors::Error::ObjectStore)?;
+        let range = offset as usize..offset as usize + buf.len();
+        let bytes = self.request(
+            |object_store, location| {
+                let range = range.clone();
+                async move { object_store.get_range(&location, range).await }
+            },
+            |bytes| bytes.len() as u64,
+        )?;
         buf.copy_from_slice(bytes.as_ref());
-        // println!("read {:?}", start.elapsed());
         Ok(())
     }
 
     fn size(&self) -> Result<u64> {
-        Ok(*self.cache_size.get_or_init(|| {
-            // let start = std::time::Instant::now();
-            let object_store = Arc::clone(&self.object_store);
-            let location = self.location.clone();
-            let head_result = block_on(async move {
-                RUNTIME
-                    .spawn(async move { object_store.head(&location).await })
-                    .await
-                    .unwrap()
-            });
-            // println!("size {:?}", start.elapsed());
-            head_result
-                .map_err(fff_core::errors::Error::ObjectStore)
-                .map(|o| o.size as u64)
-                .unwrap()
-        }))
+        if let Some(size) = self.cache_size.get() {
+            return Ok(*size);
+        }
+        let meta = self.request(
+            |object_store, location| async move { object_store.head(&location).await },
+            |_| 0,
+        )?;
+        Ok(*self.cache_size.get_or_init(|| meta.size as u64))
+    }
+
+    fn read_ranges(&self, ranges: &[Range<u64>]) -> Result<Vec<Bytes>> {
+        let ranges = ranges
+            .iter()
+            .map(|range| range.start as usize..range.end as usize)
+            .collect::<Vec<_>>();
+        self.request(
+            |object_store, location| {
+                let ranges = ranges.clone();
+                async move { object_store.get_ranges(&location, &ranges).await }
+            },
+            |bytes| bytes.iter().map(|bytes| bytes.len() as u64).sum(),
+        )
+    }
+
+    fn read_metrics(&self) -> Option<ReadMetrics> {
+        Some(self.metrics())
     }
 }
 
@@ -123,6 +573,14 @@ impl Reader for Arc<ObjectStoreReadAt> {
     fn size(&self) -> Result<u64> {
         Reader::size(self.as_ref())
     }
+
+    fn read_ranges(&self, ranges: &[Range<u64>]) -> Result<Vec<Bytes>> {
+        Reader::read_ranges(self.as_ref(), ranges)
+    }
+
+    fn read_metrics(&self) -> Option<ReadMetrics> {
+        Reader::read_metrics(self.as_ref())
+    }
 }
 
 impl Length for ObjectStoreReadAt {
@@ -162,18 +620,99 @@ impl ChunkReader for ObjectStoreReadAt {
 
         let object_store = Arc::clone(&self.object_store);
         let location = self.location.clone();
-        let head_result = block_on(async move {
-            RUNTIME
-                .spawn(async move {
-                    object_store
-                        .get_range(&location, start_range..(start_range + length))
-                        .await
-                })
-                .await
-                .unwrap()
-        });
+        let head_result = run_blocking(
+            self.runtime.as_ref(),
+            async move {
+                object_store
+                    .get_range(&location, start_range..(start_range + length))
+                    .await
+            },
+            None,
+        )
+        .map_err(|err| parquet::errors::ParquetError::External(err.into()))?
+        .expect("No timeout");
         // println!("pq random access {:?}", t.elapsed());
 
         head_result.map_err(|err| parquet::errors::ParquetError::External(err.into()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::{executor::block_on, future::BoxFuture};
+    use object_store::memory::InMemory;
+
+    use super::*;
+
+    /// An executor starting each task late, for its requests to time out.
+    #[derive(Debug)]
+    struct SlowRuntime(Duration);
+
+    impl IoRuntime for SlowRuntime {
+        fn spawn(&self, task: BoxFuture<'static, ()>) {
+            let delay = self.0;
+            std::thread::spawn(move || {
+                std::thread::sleep(delay);
+                block_on(task)
+            });
+        }
+    }
+
+    #[test]
+    fn test_object_store_read_metrics() {
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let location = Path::from("data");
+        RUNTIME
+            .block_on(store.put(&location, vec![7u8; 256].into()))
+            .unwrap();
+        let retry = RetryConfig {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(2),
+        };
+        let reader = ObjectStoreReadAt::new(Arc::clone(&store), Arc::new(location.clone()))
+            .with_retry(retry)
+            .with_request_timeout(Duration::from_secs(10));
+        let mut buf = [0; 16];
+        reader.read_exact_at(&mut buf, 8).unwrap();
+        reader.read_ranges(&[0..4, 100..104]).unwrap();
+        assert_eq!(reader.size().unwrap(), 256);
+        let metrics = reader.read_metrics().unwrap();
+        assert_eq!(metrics.num_requests, 3);
+        assert_eq!(metrics.bytes_read, 24);
+        assert_eq!(metrics.num_retries, 0);
+
+        // Client errors are not retried.
+        let missing = ObjectStoreReadAt::new(Arc::clone(&store), Arc::new(Path::from("missing")))
+            .with_retry(retry);
+        assert!(missing.read_exact_at(&mut buf, 0).is_err());
+        let metrics = missing.metrics();
+        assert_eq!((metrics.num_requests, metrics.num_client_errors), (1, 1));
+
+        // Timeouts are.
+        let slow = ObjectStoreReadAt::new(store, Arc::new(location))
+            .with_runtime(Arc::new(SlowRuntime(Duration::from_millis(200))))
+            .with_retry(retry)
+            .with_request_timeout(Duration::from_millis(5));
+        assert!(slow.read_exact_at(&mut buf, 0).is_err());
+        let metrics = slow.metrics();
+        assert_eq!(metrics.num_requests, 3);
+        assert_eq!(metrics.num_timeouts, 3);
+        assert_eq!(metrics.num_retries, 2);
+        assert!(metrics.wall_time >= Duration::from_millis(15));
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        let retry = RetryConfig {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(350),
+        };
+        let backoffs = (1..=4).map(|attempt| retry.backoff(attempt));
+        assert_eq!(
+            backoffs.map(|backoff| backoff.as_millis()).collect::<Vec<_>>(),
+            [100, 200, 350, 350]
+        );
+    }
+}
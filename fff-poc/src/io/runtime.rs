@@ -0,0 +1,233 @@
+//! Runtimes running the async IO of the readers, so that engines embedding their own scheduler
+//! run the reads on it instead of on the tokio runtime of the crate.
+//!
+//! [`Reader`]s are synchronous: the readers of remote objects, e.g., [`ObjectStoreReadAt`], and
+//! [`RuntimeFile`] hand their requests to an [`IoRuntime`] and block until they complete.
+//! [`TokioRuntime`] runs them on a tokio runtime, by default the one shared by the crate. Other
+//! executors, e.g., tokio-uring or the scheduler of an engine, plug in by implementing
+//! [`IoRuntime::spawn`], and [`IoRuntime::read_at`] for their own file IO.
+//!
+//! [`ObjectStoreReadAt`]: super::reader::ObjectStoreReadAt
+
+use std::fmt::Debug;
+use std::fs::File;
+use std::future::Future;
+use std::ops::Range;
+use std::os::unix::fs::FileExt;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use bytes::Bytes;
+use fff_core::{errors::Result, general_error};
+use futures::{
+    future::{try_join_all, BoxFuture},
+    FutureExt,
+};
+use tokio::runtime::Handle;
+
+use super::reader::{Reader, RUNTIME};
+
+/// An executor of the async IO of the readers.
+pub trait IoRuntime: Send + Sync + Debug {
+    /// Run `task` to completion in the background.
+    fn spawn(&self, task: BoxFuture<'static, ()>);
+
+    /// Read `len` bytes of `file` at `offset`. The default reads synchronously when polled,
+    /// runtimes with their own file IO override it.
+    fn read_at(
+        &self,
+        file: Arc<File>,
+        offset: u64,
+        len: usize,
+    ) -> BoxFuture<'static, Result<Bytes>> {
+        async move { read_exact_at(&file, offset, len) }.boxed()
+    }
+}
+
+fn read_exact_at(file: &File, offset: u64, len: usize) -> Result<Bytes> {
+    let mut buf = vec![0; len];
+    file.read_exact_at(&mut buf, offset)?;
+    Ok(Bytes::from(buf))
+}
+
+/// Run `future` on `runtime`, and block the calling thread until it completes, or until
+/// `timeout`, if any, expires, returning None. The future then keeps running in the background,
+/// and its output is dropped.
+pub fn run_blocking<T: Send + 'static>(
+    runtime: &dyn IoRuntime,
+    future: impl Future<Output = T> + Send + 'static,
+    timeout: Option<Duration>,
+) -> Result<Option<T>> {
+    let (sender, receiver) = mpsc::sync_channel(1);
+    runtime.spawn(
+        async move {
+            // The caller stops waiting once the timeout expires, or if it panicked.
+            let _ = sender.send(future.await);
+        }
+        .boxed(),
+    );
+    let dropped = || general_error!("The IO runtime dropped the task");
+    match timeout {
+        None => receiver.recv().map(Some).map_err(|_| dropped()),
+        Some(timeout) => match receiver.recv_timeout(timeout) {
+            Ok(output) => Ok(Some(output)),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(dropped()),
+        },
+    }
+}
+
+/// An [`IoRuntime`] over a tokio runtime, reading files on its blocking threads.
+#[derive(Debug, Clone)]
+pub struct TokioRuntime {
+    handle: Handle,
+}
+
+impl TokioRuntime {
+    pub fn new(handle: Handle) -> Self {
+        Self { handle }
+    }
+
+    /// The runtime the calling task runs on, if any.
+    pub fn current() -> Option<Self> {
+        Handle::try_current().ok().map(Self::new)
+    }
+}
+
+impl Default for TokioRuntime {
+    /// The runtime shared by the readers of the crate.
+    fn default() -> Self {
+        Self::new(RUNTIME.handle().clone())
+    }
+}
+
+impl IoRuntime for TokioRuntime {
+    fn spawn(&self, task: BoxFuture<'static, ()>) {
+        self.handle.spawn(task);
+    }
+
+    fn read_at(
+        &self,
+        file: Arc<File>,
+        offset: u64,
+        len: usize,
+    ) -> BoxFuture<'static, Result<Bytes>> {
+        let read = self
+            .handle
+            .spawn_blocking(move || read_exact_at(&file, offset, len));
+        async move {
+            read.await
+                .map_err(|e| general_error!(format!("Failed to read the file: {e}")))?
+        }
+        .boxed()
+    }
+}
+
+/// The [`IoRuntime`] of the readers not given one.
+pub fn default_runtime() -> Arc<dyn IoRuntime> {
+    Arc::new(TokioRuntime::default())
+}
+
+/// A local file whose reads run on an [`IoRuntime`], e.g., with io_uring.
+#[derive(Clone)]
+pub struct RuntimeFile {
+    file: Arc<File>,
+    runtime: Arc<dyn IoRuntime>,
+}
+
+impl RuntimeFile {
+    pub fn new(file: Arc<File>, runtime: Arc<dyn IoRuntime>) -> Self {
+        Self { file, runtime }
+    }
+}
+
+impl Reader for RuntimeFile {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        let read = self.runtime.read_at(self.file.clone(), offset, buf.len());
+        let bytes = run_blocking(self.runtime.as_ref(), read, None)?.expect("No timeout");
+        buf.copy_from_slice(&bytes?);
+        Ok(())
+    }
+
+    fn size(&self) -> Result<u64> {
+        Ok(self.file.metadata()?.len())
+    }
+
+    /// The ranges are read concurrently.
+    fn read_ranges(&self, ranges: &[Range<u64>]) -> Result<Vec<Bytes>> {
+        let reads = ranges
+            .iter()
+            .map(|range| {
+                self.runtime.read_at(
+                    self.file.clone(),
+                    range.start,
+                    (range.end - range.start) as usize,
+                )
+            })
+            .collect::<Vec<_>>();
+        run_blocking(self.runtime.as_ref(), try_join_all(reads), None)?.expect("No timeout")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::executor::block_on;
+    use object_store::{memory::InMemory, path::Path, ObjectStore};
+
+    use super::*;
+    use crate::io::reader::ObjectStoreReadAt;
+
+    /// A caller-provided executor, running each task on a thread of its own.
+    #[derive(Debug, Default)]
+    struct ThreadRuntime {
+        num_tasks: AtomicUsize,
+    }
+
+    impl IoRuntime for ThreadRuntime {
+        fn spawn(&self, task: BoxFuture<'static, ()>) {
+            self.num_tasks.fetch_add(1, Ordering::Relaxed);
+            std::thread::spawn(move || block_on(task));
+        }
+    }
+
+    #[test]
+    fn test_custom_runtime() {
+        let data = (0..=255).collect::<Vec<u8>>();
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&data).unwrap();
+        let runtime = Arc::new(ThreadRuntime::default());
+        let reader = RuntimeFile::new(Arc::new(file), runtime.clone());
+        let mut buf = [0; 4];
+        reader.read_exact_at(&mut buf, 10).unwrap();
+        assert_eq!(buf, [10, 11, 12, 13]);
+        let ranges = reader.read_ranges(&[0..2, 200..203]).unwrap();
+        assert_eq!(ranges, [&data[0..2], &data[200..203]]);
+        assert_eq!(reader.size().unwrap(), 256);
+        assert_eq!(runtime.num_tasks.load(Ordering::Relaxed), 2);
+
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let location = Path::from("data");
+        RUNTIME
+            .block_on(store.put(&location, data.clone().into()))
+            .unwrap();
+        let reader =
+            ObjectStoreReadAt::new(store, Arc::new(location)).with_runtime(runtime.clone());
+        reader.read_exact_at(&mut buf, 100).unwrap();
+        assert_eq!(buf, [100, 101, 102, 103]);
+        assert_eq!(reader.size().unwrap(), 256);
+        assert_eq!(runtime.num_tasks.load(Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    fn test_tokio_runtime() {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(b"hello world").unwrap();
+        let reader = RuntimeFile::new(Arc::new(file), default_runtime());
+        assert_eq!(reader.read_ranges(&[6..11]).unwrap(), [&b"world"[..]]);
+        assert!(TokioRuntime::current().is_none());
+        RUNTIME.block_on(async { assert!(TokioRuntime::current().is_some()) });
+    }
+}
@@ -0,0 +1,153 @@
+//! Scheduling of the reads of a scan, see
+//! [`FileReaderV2Builder::with_io_concurrency`](crate::reader::FileReaderV2Builder::with_io_concurrency).
+//!
+//! Decoders otherwise read their chunks on demand, one request at a time, so the latency of each
+//! request to an object store adds up. The scheduler computes the byte ranges of all the chunks of
+//! the scan from the footer up front, and issues them in waves of at most `max_concurrency` ranges
+//! through [`Reader::read_ranges`], which readers of remote objects issue concurrently. Waves run
+//! ahead of the decoders into the following row groups, and the decoders of each row group get
+//! its chunks as [`PrefetchedChunks`] as soon as they have all arrived.
+
+use std::{collections::VecDeque, ops::Range};
+
+use bytes::Bytes;
+use fff_core::errors::{Error, Result};
+use fff_format::File::fff::flatbuf as fb;
+
+use super::{prefetch::PrefetchedChunks, reader::Reader};
+
+/// Issues the reads of the chunks of a scan with bounded concurrency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoScheduler {
+    max_concurrency: usize,
+}
+
+impl IoScheduler {
+    /// A scheduler with at most `max_concurrency` ranges in flight, at least 1.
+    pub fn new(max_concurrency: usize) -> Self {
+        Self {
+            max_concurrency: max_concurrency.max(1),
+        }
+    }
+
+    pub fn max_concurrency(&self) -> usize {
+        self.max_concurrency
+    }
+
+    /// Plan the reads of the chunks of `row_groups`, given by the metadata of their columns to
+    /// decode, in the order the decoders consume them.
+    pub fn schedule<'a, 'b: 'a>(
+        &self,
+        row_groups: impl IntoIterator<Item = &'a [fb::ColumnMetadata<'b>]>,
+    ) -> ScheduledReads {
+        let mut pending = VecDeque::new();
+        let mut num_row_groups = 0;
+        for (rg_idx, column_metas) in row_groups.into_iter().enumerate() {
+            let mut ranges = column_metas
+                .iter()
+                .flat_map(|column_meta| column_meta.column_chunks().into_iter().flatten())
+                .filter(|chunk| chunk.size_() > 0)
+                .map(|chunk| chunk.offset()..chunk.offset() + chunk.size_() as u64)
+                .collect::<Vec<_>>();
+            ranges.sort_unstable_by_key(|range| range.start);
+            ranges.dedup();
+            pending.extend(ranges.into_iter().map(|range| (rg_idx, range)));
+            num_row_groups = rg_idx + 1;
+        }
+        ScheduledReads {
+            max_concurrency: self.max_concurrency,
+            pending,
+            arrived: VecDeque::from(vec![vec![]; num_row_groups]),
+            next_row_group: 0,
+        }
+    }
+}
+
+/// The reads planned by [`IoScheduler::schedule`], handed out row group by row group.
+#[derive(Debug)]
+pub struct ScheduledReads {
+    max_concurrency: usize,
+    /// Ranges not issued yet, with the row group they belong to.
+    pending: VecDeque<(usize, Range<u64>)>,
+    /// Ranges read, from the next row group on.
+    arrived: VecDeque<Vec<(u64, Bytes)>>,
+    next_row_group: usize,
+}
+
+impl ScheduledReads {
+    /// Number of ranges not issued yet.
+    pub fn num_pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// The chunks of the next row group, issuing waves of reads until they have all arrived.
+    pub fn next_row_group<R: Reader + ?Sized>(&mut self, reader: &R) -> Result<PrefetchedChunks> {
+        let rg_idx = self.next_row_group;
+        if self.arrived.is_empty() {
+            return Err(Error::IndexOutOfBound(rg_idx, rg_idx));
+        }
+        while self
+            .pending
+            .front()
+            .is_some_and(|(pending_rg, _)| *pending_rg == rg_idx)
+        {
+            let wave = self
+                .pending
+                .drain(..self.max_concurrency.min(self.pending.len()))
+                .collect::<Vec<_>>();
+            let ranges = wave
+                .iter()
+                .map(|(_, range)| range.clone())
+                .collect::<Vec<_>>();
+            trace_span!(
+                "read_chunks",
+                row_group = rg_idx,
+                bytes = ranges.iter().map(|r| r.end - r.start).sum::<u64>()
+            );
+            for ((pending_rg, range), bytes) in wave.into_iter().zip(reader.read_ranges(&ranges)?) {
+                self.arrived[pending_rg - rg_idx].push((range.start, bytes));
+            }
+        }
+        self.next_row_group += 1;
+        Ok(PrefetchedChunks::from_ranges(
+            self.arrived.pop_front().unwrap(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::io::reader::CountingReader;
+
+    #[test]
+    fn test_scheduled_reads() {
+        let data = (0..=255).collect::<Vec<u8>>();
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&data).unwrap();
+        let reader = CountingReader::new(file);
+        let mut reads = ScheduledReads {
+            max_concurrency: 3,
+            pending: VecDeque::from(vec![(0, 0..4), (0, 10..12), (1, 20..24), (1, 30..32)]),
+            arrived: VecDeque::from(vec![vec![]; 2]),
+            next_row_group: 0,
+        };
+        let first = reads.next_row_group(&reader).unwrap();
+        // The first wave reads ahead into the second row group.
+        assert_eq!(reads.num_pending(), 1);
+        let mut buf = [0; 2];
+        assert!(first.read_exact_at(&mut buf, 10));
+        assert_eq!(buf, [10, 11]);
+        assert!(!first.read_exact_at(&mut buf, 20));
+        let second = reads.next_row_group(&reader).unwrap();
+        assert_eq!(reads.num_pending(), 0);
+        assert!(second.read_exact_at(&mut buf, 22));
+        assert_eq!(buf, [22, 23]);
+        assert!(second.read_exact_at(&mut buf, 30));
+        // A request per wave.
+        assert_eq!(reader.metrics().num_requests, 2);
+        assert!(reads.next_row_group(&reader).is_err());
+    }
+}
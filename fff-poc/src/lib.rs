@@ -1,13 +1,31 @@
 #![feature(new_range_api)]
 use mimalloc::MiMalloc;
 
+/// Enter a debug-level `tracing` span until the end of the scope, with the `tracing` feature.
+/// Spans name their fields `column` for column indexes, `row_group` for row group indexes and
+/// `bytes` for sizes. Without the feature, neither the span nor its fields are evaluated.
+macro_rules! trace_span {
+    ($name:expr $(, $($fields:tt)*)?) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!($name $(, $($fields)*)?).entered();
+    };
+}
+
 pub mod common;
 mod compression;
 pub mod counter;
+pub mod dataset;
+pub mod diff;
+pub mod encoding_map;
+pub mod encryption;
 pub mod file;
+pub mod inspect;
 pub mod io;
+pub mod memory;
 pub mod options;
 pub mod reader;
+#[cfg(feature = "dst")]
+pub mod sim;
 pub mod writer;
 
 pub mod context;
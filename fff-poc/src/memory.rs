@@ -0,0 +1,156 @@
+//! Accounting of the memory buffered by writers and readers, so that engines can enforce a global
+//! memory limit across the files they write and scan at once.
+//!
+//! Writers and readers reserve the bytes they buffer from a [`MemoryPool`] through a
+//! [`MemoryReservation`]: a [`FileWriter`](crate::writer::FileWriter) its data not written yet,
+//! see [`FileWriter::buffered_bytes`](crate::writer::FileWriter::buffered_bytes), and a reader
+//! the encoded chunk held by the decoder of each column. When the pool refuses a reservation,
+//! writers flush and retry, and readers fail. [`TrackingPool`] grants reservations up to an
+//! optional limit. Engines with their own pool, e.g., DataFusion's, plug it in by implementing
+//! [`MemoryPool`] over it.
+
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use fff_core::{errors::Result, general_error};
+
+/// A pool of memory shared by the writers and readers of an engine.
+pub trait MemoryPool: Send + Sync + Debug {
+    /// Reserve `bytes` more for `consumer`, or fail if the pool cannot grant them.
+    fn try_reserve(&self, consumer: &str, bytes: usize) -> Result<()>;
+
+    /// Release `bytes` reserved by `consumer`.
+    fn release(&self, consumer: &str, bytes: usize);
+
+    /// Bytes reserved by all consumers.
+    fn reserved(&self) -> usize;
+}
+
+/// A [`MemoryPool`] tracking the reserved bytes and their peak, up to an optional limit.
+#[derive(Debug, Default)]
+pub struct TrackingPool {
+    limit: Option<usize>,
+    reserved: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl TrackingPool {
+    /// A pool granting all reservations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A pool refusing reservations beyond `limit` bytes in total.
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            limit: Some(limit),
+            ..Default::default()
+        }
+    }
+
+    /// Maximum bytes reserved at once so far.
+    pub fn peak(&self) -> usize {
+        self.peak.load(Ordering::Relaxed)
+    }
+}
+
+impl MemoryPool for TrackingPool {
+    fn try_reserve(&self, consumer: &str, bytes: usize) -> Result<()> {
+        let limit = self.limit.unwrap_or(usize::MAX);
+        let reserved = self
+            .reserved
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |reserved| {
+                reserved.checked_add(bytes).filter(|&total| total <= limit)
+            })
+            .map_err(|reserved| {
+                general_error!(format!(
+                    "Failed to reserve {bytes} bytes for {consumer}: {reserved} of {limit} bytes \
+                     are reserved"
+                ))
+            })?;
+        self.peak.fetch_max(reserved + bytes, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn release(&self, _consumer: &str, bytes: usize) {
+        self.reserved.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    fn reserved(&self) -> usize {
+        self.reserved.load(Ordering::Relaxed)
+    }
+}
+
+/// Bytes reserved by one consumer from a [`MemoryPool`], released when dropped.
+#[derive(Debug)]
+pub struct MemoryReservation {
+    pool: Arc<dyn MemoryPool>,
+    consumer: String,
+    size: usize,
+}
+
+impl MemoryReservation {
+    pub fn new(pool: Arc<dyn MemoryPool>, consumer: impl Into<String>) -> Self {
+        Self {
+            pool,
+            consumer: consumer.into(),
+            size: 0,
+        }
+    }
+
+    /// Bytes reserved.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Grow or shrink the reservation to `size` bytes. The reservation is unchanged if the pool
+    /// cannot grant them.
+    pub fn try_resize(&mut self, size: usize) -> Result<()> {
+        if size > self.size {
+            self.pool.try_reserve(&self.consumer, size - self.size)?;
+        } else if size < self.size {
+            self.pool.release(&self.consumer, self.size - size);
+        }
+        self.size = size;
+        Ok(())
+    }
+
+    /// Release all the bytes reserved.
+    pub fn free(&mut self) {
+        if self.size > 0 {
+            self.pool.release(&self.consumer, self.size);
+            self.size = 0;
+        }
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.free();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracking_pool() {
+        let pool = Arc::new(TrackingPool::with_limit(100));
+        let mut a = MemoryReservation::new(pool.clone(), "a");
+        let mut b = MemoryReservation::new(pool.clone(), "b");
+        a.try_resize(60).unwrap();
+        assert!(b.try_resize(50).is_err());
+        assert_eq!(b.size(), 0);
+        b.try_resize(40).unwrap();
+        assert_eq!(pool.reserved(), 100);
+        a.try_resize(10).unwrap();
+        b.try_resize(90).unwrap();
+        assert_eq!(pool.reserved(), 100);
+        drop(b);
+        a.free();
+        assert_eq!(pool.reserved(), 0);
+        assert_eq!(pool.peak(), 100);
+    }
+}
@@ -1,17 +1,29 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use arrow_schema::DataType;
 use fff_format::File::fff::flatbuf::CompressionType;
+use fff_ude_wasm::aot::AotTarget;
 
+pub use crate::compression::{Compression, CompressionCostModel, CompressionLevel};
 pub use crate::dict::DictionaryTypeOptions;
+pub use crate::file::sort_order::SortDirection;
 use crate::{
     common::checksum::ChecksumType,
-    context::{WASMId, WASMWritingContext, WasmLib},
+    context::{allocate_wasm_id, WASMId, WASMWritingContext, WasmLib},
+    encryption::{KeyProvider, KeyRetriever},
+    memory::MemoryPool,
+    writer::{
+        input_validation::InputValidation,
+        layout_planner::{AutoColumnChunkSize, LayoutPlan},
+    },
 };
+pub use fff_encoding::schemes::adaptive::AdaptiveEncodingOptions;
 
 pub const DEFAULT_IOUNIT_SIZE: u64 = 8 * 1024 * 1024; // in bytes
 pub const DEFAULT_ENCODING_UNIT_LEN: u64 = 64 * 1024; // in number of rows
 pub const DEFAULT_CHECKSUM_TYPE: ChecksumType = ChecksumType::XxHash;
+pub const DEFAULT_BLOOM_FILTER_FPP: f64 = 0.01;
+pub const DEFAULT_STATISTICS_TRUNCATE_LENGTH: usize = 64; // in bytes
 
 #[derive(Clone)]
 pub struct FileWriterOptions {
@@ -26,6 +38,12 @@ pub struct FileWriterOptions {
     write_built_in_wasm: bool,
     /// Mapping between root-level column id and custom encunit len (num of rows)
     custom_encunit_len: HashMap<usize, usize>,
+    /// Mapping between root-level column id and the size in bytes after which its chunks are
+    /// flushed. `iounit_size` for the other columns.
+    column_chunk_sizes: HashMap<usize, u64>,
+    /// Size the chunks of each root-level column from the encoded size of the first rows,
+    /// overriding `column_chunk_sizes` once they are encoded. Disabled by default.
+    auto_column_chunk_size: Option<AutoColumnChunkSize>,
     /// The size of a row group in number of rows. Infinite by default.
     row_group_size: u64,
     /// Custom encoding options, include the encoder dylib and decoder wasm lib
@@ -37,6 +55,90 @@ pub struct FileWriterOptions {
     enable_io_unit_checksum: bool,
     /// The type of compression to use for EncUnits
     compression_type: CompressionType,
+    /// How the compression level is chosen per EncUnit. A cost model by default.
+    compression_level: CompressionLevel,
+    /// Maximum size in bytes of the Zstd dictionary trained on the EncUnits of each chunk, see
+    /// [`FileWriterOptionsBuilder::train_zstd_dictionary`]. None by default.
+    zstd_dictionary_size: Option<usize>,
+    /// Choose the encoding of each EncUnit by trial-encoding a sample. Disabled by default.
+    /// Ignored when the built-in Wasm is written, as it only decodes Vortex.
+    adaptive_encoding: Option<AdaptiveEncodingOptions>,
+    /// Upper bound in bytes of the data buffered by all column encoders. When exceeded, shared
+    /// dictionary encoders spill to temporary files and other encoders flush their chunks early.
+    /// Unbounded by default.
+    memory_budget: Option<u64>,
+    /// Upper bound in bytes of the data buffered by the writer, in its column encoders and column
+    /// families, see [`FileWriter::buffered_bytes`](crate::writer::FileWriter::buffered_bytes).
+    /// When exceeded after a batch, the writer is flushed, see
+    /// [`FileWriter::flush`](crate::writer::FileWriter::flush). Unbounded by default.
+    max_buffered_bytes: Option<u64>,
+    /// Pool the writer reserves its buffered data from, see [`crate::memory`]. When a reservation
+    /// is refused, the writer is flushed and writing fails if it is still refused. None by
+    /// default.
+    memory_pool: Option<Arc<dyn MemoryPool>>,
+    /// Number of threads encoding and compressing the columns of each batch. Chunks are still
+    /// flushed in column order, so the file does not depend on it. Columns with shared
+    /// dictionaries are always encoded on the caller thread. 1 by default.
+    encoding_parallelism: usize,
+    /// Size in bytes of a zeroed region reserved before the file metadata, recorded as the
+    /// "ReservedPadding" optional section. Tools can later add small metadata there by rewriting
+    /// only the tail of the file. No padding by default.
+    footer_padding: u64,
+    /// Codec of the column metadata and footer, recorded in the postscript. Uncompressed by
+    /// default.
+    metadata_compression: CompressionType,
+    /// Write the row offsets, byte offsets and min/max of the EncUnits of each chunk as the
+    /// "EncUnitIndex" optional section. Disabled by default.
+    write_encunit_index: bool,
+    /// Root-level columns whose chunks get a bloom filter, written as the "BloomFilters"
+    /// optional section. None by default.
+    bloom_filter_columns: Vec<usize>,
+    /// False positive probability the bloom filters are sized for.
+    bloom_filter_fpp: f64,
+    /// Keep the footer of a file appended to as an earlier version of it. Disabled by default.
+    footer_versioning: bool,
+    /// Number of physical columns per compressed segment of column metadata, see
+    /// [`FileWriterOptionsBuilder::set_budgeted_metadata`]. None by default.
+    budgeted_metadata: Option<usize>,
+    /// Groups of root-level columns written next to each other in shared IO units, see
+    /// [`FileWriterOptionsBuilder::set_column_families`]. None by default.
+    column_families: Vec<Vec<usize>>,
+    /// Root-level columns, by name, the rows of each row group are sorted by, see
+    /// [`FileWriterOptionsBuilder::sort_by`]. Unsorted by default.
+    sort_by: Vec<(String, SortDirection)>,
+    /// Max length in bytes of the binary and string min/max statistics of chunks. Longer values
+    /// are truncated to bounds. 64 bytes by default, None to never truncate.
+    statistics_truncate_length: Option<usize>,
+    /// How non-canonical input values are treated, see
+    /// [`FileWriterOptionsBuilder::set_input_validation`]. Written as given by default.
+    input_validation: InputValidation,
+    /// WASM binaries written to the file, see [`FileWriterOptionsBuilder::register_wasm`]. The
+    /// WASMId of a binary is its position.
+    wasm_modules: Vec<Vec<u8>>,
+    /// Mapping between root-level column id and the WASMId of the binary encoding its EncUnits.
+    column_wasm_ids: HashMap<usize, WASMId>,
+    /// Mapping between root-level column name and the WASMId of the binary encoding its EncUnits.
+    column_wasm_names: HashMap<String, WASMId>,
+    /// Content hash of the WASM binaries to store by reference only, and the URI to fetch each
+    /// of them from.
+    wasm_references: HashMap<u64, String>,
+    /// Whether an AOT artifact of each embedded WASM binary is compiled for the host and written
+    /// next to it, see [`crate::file::wasm_aot`]. False by default.
+    write_wasm_aot: bool,
+    /// AOT artifacts of the WASM binaries, by content hash, written next to them, e.g., compiled
+    /// for other targets.
+    wasm_aot_artifacts: HashMap<u64, Vec<(AotTarget, Vec<u8>)>>,
+    /// Mapping between root-level column id and the id of the key its EncUnits are encrypted
+    /// with, see [`FileWriterOptionsBuilder::encrypt_columns`]. None by default.
+    encrypted_columns: HashMap<usize, String>,
+    /// Id of the key the metadata is encrypted with, see
+    /// [`FileWriterOptionsBuilder::encrypt_footer`]. Unencrypted by default.
+    footer_key_id: Option<String>,
+    /// Provides the keys of `encrypted_columns` and `footer_key_id`.
+    key_retriever: Option<Arc<dyn KeyRetriever>>,
+    /// Provides data keys wrapped with the master keys of `encrypted_columns` and
+    /// `footer_key_id`, see [`FileWriterOptionsBuilder::set_key_provider`].
+    key_provider: Option<Arc<dyn KeyProvider>>,
 }
 
 impl Default for FileWriterOptions {
@@ -74,6 +176,17 @@ impl FileWriterOptions {
         &self.custom_encunit_len
     }
 
+    pub fn column_chunk_size(&self, column_id: usize) -> u64 {
+        self.column_chunk_sizes
+            .get(&column_id)
+            .copied()
+            .unwrap_or(self.iounit_size)
+    }
+
+    pub fn auto_column_chunk_size(&self) -> Option<AutoColumnChunkSize> {
+        self.auto_column_chunk_size
+    }
+
     pub fn row_group_size(&self) -> u64 {
         self.row_group_size
     }
@@ -97,6 +210,130 @@ impl FileWriterOptions {
     pub fn compression_type(&self) -> CompressionType {
         self.compression_type
     }
+
+    pub fn compression_level(&self) -> CompressionLevel {
+        self.compression_level
+    }
+
+    pub fn zstd_dictionary_size(&self) -> Option<usize> {
+        self.zstd_dictionary_size
+    }
+
+    pub fn adaptive_encoding(&self) -> Option<AdaptiveEncodingOptions> {
+        self.adaptive_encoding
+    }
+
+    pub fn memory_budget(&self) -> Option<u64> {
+        self.memory_budget
+    }
+
+    pub fn max_buffered_bytes(&self) -> Option<u64> {
+        self.max_buffered_bytes
+    }
+
+    pub fn memory_pool(&self) -> Option<&Arc<dyn MemoryPool>> {
+        self.memory_pool.as_ref()
+    }
+
+    pub fn encoding_parallelism(&self) -> usize {
+        self.encoding_parallelism
+    }
+
+    pub fn footer_padding(&self) -> u64 {
+        self.footer_padding
+    }
+
+    pub fn metadata_compression(&self) -> CompressionType {
+        self.metadata_compression
+    }
+
+    pub fn write_encunit_index(&self) -> bool {
+        self.write_encunit_index
+    }
+
+    pub fn bloom_filter_columns(&self) -> &[usize] {
+        &self.bloom_filter_columns
+    }
+
+    pub fn bloom_filter_fpp(&self) -> f64 {
+        self.bloom_filter_fpp
+    }
+
+    pub fn footer_versioning(&self) -> bool {
+        self.footer_versioning
+    }
+
+    pub fn budgeted_metadata(&self) -> Option<usize> {
+        self.budgeted_metadata
+    }
+
+    pub fn column_families(&self) -> &[Vec<usize>] {
+        &self.column_families
+    }
+
+    pub fn sort_by(&self) -> &[(String, SortDirection)] {
+        &self.sort_by
+    }
+
+    pub fn statistics_truncate_length(&self) -> Option<usize> {
+        self.statistics_truncate_length
+    }
+
+    pub fn input_validation(&self) -> InputValidation {
+        self.input_validation
+    }
+
+    pub fn wasm_modules(&self) -> &[Vec<u8>] {
+        &self.wasm_modules
+    }
+
+    /// The WASMId of the binary encoding the root-level column `column` named `name`, if any.
+    pub fn column_wasm(&self, column: usize, name: &str) -> Option<WASMId> {
+        self.column_wasm_ids
+            .get(&column)
+            .or_else(|| self.column_wasm_names.get(name))
+            .copied()
+    }
+
+    pub fn column_wasm_ids(&self) -> &HashMap<usize, WASMId> {
+        &self.column_wasm_ids
+    }
+
+    pub fn column_wasm_names(&self) -> &HashMap<String, WASMId> {
+        &self.column_wasm_names
+    }
+
+    pub fn wasm_references(&self) -> &HashMap<u64, String> {
+        &self.wasm_references
+    }
+
+    pub fn write_wasm_aot(&self) -> bool {
+        self.write_wasm_aot
+    }
+
+    pub fn wasm_aot_artifacts(&self) -> &HashMap<u64, Vec<(AotTarget, Vec<u8>)>> {
+        &self.wasm_aot_artifacts
+    }
+
+    pub fn encrypted_columns(&self) -> &HashMap<usize, String> {
+        &self.encrypted_columns
+    }
+
+    pub fn footer_key_id(&self) -> Option<&str> {
+        self.footer_key_id.as_deref()
+    }
+
+    pub fn key_retriever(&self) -> Option<&Arc<dyn KeyRetriever>> {
+        self.key_retriever.as_ref()
+    }
+
+    pub fn key_provider(&self) -> Option<&Arc<dyn KeyProvider>> {
+        self.key_provider.as_ref()
+    }
+
+    pub fn compression(&self) -> Compression {
+        Compression::new(self.compression_type, self.compression_level)
+    }
 }
 
 pub struct FileWriterOptionsBuilder {
@@ -112,6 +349,12 @@ pub struct FileWriterOptionsBuilder {
     /// Mapping between root-level column id and custom encunit len (num of rows)
     /// TODO: not correctly implement yet. Check FileWriter::write_batch
     custom_encunit_len: HashMap<usize, usize>,
+    /// Mapping between root-level column id and the size in bytes after which its chunks are
+    /// flushed. `iounit_size` for the other columns.
+    column_chunk_sizes: HashMap<usize, u64>,
+    /// Size the chunks of each root-level column from the encoded size of the first rows,
+    /// overriding `column_chunk_sizes` once they are encoded. Disabled by default.
+    auto_column_chunk_size: Option<AutoColumnChunkSize>,
     /// The size of a row group in number of rows. Infinite by default.
     /// This is a threshold. E.g., if row_group_size is 1000 and we already wrote 900 rows,
     /// and then we write a batch of 200 rows, the row group will be 1100 rows.
@@ -125,6 +368,90 @@ pub struct FileWriterOptionsBuilder {
     enable_io_unit_checksum: bool,
     /// The type of compression to use for EncUnits
     compression_type: CompressionType,
+    /// How the compression level is chosen per EncUnit. A cost model by default.
+    compression_level: CompressionLevel,
+    /// Maximum size in bytes of the Zstd dictionary trained on the EncUnits of each chunk, see
+    /// [`FileWriterOptionsBuilder::train_zstd_dictionary`]. None by default.
+    zstd_dictionary_size: Option<usize>,
+    /// Choose the encoding of each EncUnit by trial-encoding a sample. Disabled by default.
+    /// Ignored when the built-in Wasm is written, as it only decodes Vortex.
+    adaptive_encoding: Option<AdaptiveEncodingOptions>,
+    /// Upper bound in bytes of the data buffered by all column encoders. When exceeded, shared
+    /// dictionary encoders spill to temporary files and other encoders flush their chunks early.
+    /// Unbounded by default.
+    memory_budget: Option<u64>,
+    /// Upper bound in bytes of the data buffered by the writer, in its column encoders and column
+    /// families, see [`FileWriter::buffered_bytes`](crate::writer::FileWriter::buffered_bytes).
+    /// When exceeded after a batch, the writer is flushed, see
+    /// [`FileWriter::flush`](crate::writer::FileWriter::flush). Unbounded by default.
+    max_buffered_bytes: Option<u64>,
+    /// Pool the writer reserves its buffered data from, see [`crate::memory`]. When a reservation
+    /// is refused, the writer is flushed and writing fails if it is still refused. None by
+    /// default.
+    memory_pool: Option<Arc<dyn MemoryPool>>,
+    /// Number of threads encoding and compressing the columns of each batch. Chunks are still
+    /// flushed in column order, so the file does not depend on it. Columns with shared
+    /// dictionaries are always encoded on the caller thread. 1 by default.
+    encoding_parallelism: usize,
+    /// Size in bytes of a zeroed region reserved before the file metadata, recorded as the
+    /// "ReservedPadding" optional section. Tools can later add small metadata there by rewriting
+    /// only the tail of the file. No padding by default.
+    footer_padding: u64,
+    /// Codec of the column metadata and footer, recorded in the postscript. Uncompressed by
+    /// default.
+    metadata_compression: CompressionType,
+    /// Write the row offsets, byte offsets and min/max of the EncUnits of each chunk as the
+    /// "EncUnitIndex" optional section. Disabled by default.
+    write_encunit_index: bool,
+    /// Root-level columns whose chunks get a bloom filter, written as the "BloomFilters"
+    /// optional section. None by default.
+    bloom_filter_columns: Vec<usize>,
+    /// False positive probability the bloom filters are sized for.
+    bloom_filter_fpp: f64,
+    /// Keep the footer of a file appended to as an earlier version of it. Disabled by default.
+    footer_versioning: bool,
+    /// Number of physical columns per compressed segment of column metadata, see
+    /// [`FileWriterOptionsBuilder::set_budgeted_metadata`]. None by default.
+    budgeted_metadata: Option<usize>,
+    /// Groups of root-level columns written next to each other in shared IO units, see
+    /// [`FileWriterOptionsBuilder::set_column_families`]. None by default.
+    column_families: Vec<Vec<usize>>,
+    /// Root-level columns, by name, the rows of each row group are sorted by, see
+    /// [`FileWriterOptionsBuilder::sort_by`]. Unsorted by default.
+    sort_by: Vec<(String, SortDirection)>,
+    /// Max length in bytes of the binary and string min/max statistics of chunks. Longer values
+    /// are truncated to bounds. 64 bytes by default, None to never truncate.
+    statistics_truncate_length: Option<usize>,
+    /// How non-canonical input values are treated, see
+    /// [`FileWriterOptionsBuilder::set_input_validation`]. Written as given by default.
+    input_validation: InputValidation,
+    /// WASM binaries written to the file, see [`FileWriterOptionsBuilder::register_wasm`]. The
+    /// WASMId of a binary is its position.
+    wasm_modules: Vec<Vec<u8>>,
+    /// Mapping between root-level column id and the WASMId of the binary encoding its EncUnits.
+    column_wasm_ids: HashMap<usize, WASMId>,
+    /// Mapping between root-level column name and the WASMId of the binary encoding its EncUnits.
+    column_wasm_names: HashMap<String, WASMId>,
+    /// Content hash of the WASM binaries to store by reference only, and the URI to fetch each
+    /// of them from.
+    wasm_references: HashMap<u64, String>,
+    /// Whether an AOT artifact of each embedded WASM binary is compiled for the host and written
+    /// next to it, see [`crate::file::wasm_aot`]. False by default.
+    write_wasm_aot: bool,
+    /// AOT artifacts of the WASM binaries, by content hash, written next to them, e.g., compiled
+    /// for other targets.
+    wasm_aot_artifacts: HashMap<u64, Vec<(AotTarget, Vec<u8>)>>,
+    /// Mapping between root-level column id and the id of the key its EncUnits are encrypted
+    /// with, see [`FileWriterOptionsBuilder::encrypt_columns`]. None by default.
+    encrypted_columns: HashMap<usize, String>,
+    /// Id of the key the metadata is encrypted with, see
+    /// [`FileWriterOptionsBuilder::encrypt_footer`]. Unencrypted by default.
+    footer_key_id: Option<String>,
+    /// Provides the keys of `encrypted_columns` and `footer_key_id`.
+    key_retriever: Option<Arc<dyn KeyRetriever>>,
+    /// Provides data keys wrapped with the master keys of `encrypted_columns` and
+    /// `footer_key_id`, see [`FileWriterOptionsBuilder::set_key_provider`].
+    key_provider: Option<Arc<dyn KeyProvider>>,
 }
 
 impl FileWriterOptionsBuilder {
@@ -136,11 +463,41 @@ impl FileWriterOptionsBuilder {
             checksum_type: DEFAULT_CHECKSUM_TYPE,
             write_built_in_wasm: false,
             custom_encunit_len: Default::default(),
+            column_chunk_sizes: Default::default(),
+            auto_column_chunk_size: None,
             row_group_size: u64::MAX, // By default, only one row group per file.
             custom_encoding_options: Default::default(),
             dictionary_type: DictionaryTypeOptions::EncoderDictionary,
             enable_io_unit_checksum: false,
             compression_type: CompressionType::Uncompressed,
+            compression_level: CompressionLevel::default(),
+            zstd_dictionary_size: None,
+            adaptive_encoding: None,
+            memory_budget: None,
+            max_buffered_bytes: None,
+            memory_pool: None,
+            encoding_parallelism: 1,
+            footer_padding: 0,
+            metadata_compression: CompressionType::Uncompressed,
+            write_encunit_index: false,
+            bloom_filter_columns: vec![],
+            bloom_filter_fpp: DEFAULT_BLOOM_FILTER_FPP,
+            footer_versioning: false,
+            budgeted_metadata: None,
+            column_families: vec![],
+            sort_by: vec![],
+            statistics_truncate_length: Some(DEFAULT_STATISTICS_TRUNCATE_LENGTH),
+            input_validation: InputValidation::default(),
+            wasm_modules: Default::default(),
+            column_wasm_ids: Default::default(),
+            column_wasm_names: Default::default(),
+            wasm_references: Default::default(),
+            write_wasm_aot: false,
+            wasm_aot_artifacts: Default::default(),
+            encrypted_columns: Default::default(),
+            footer_key_id: None,
+            key_retriever: None,
+            key_provider: None,
         }
     }
 
@@ -148,17 +505,52 @@ impl FileWriterOptionsBuilder {
     pub fn build(self) -> FileWriterOptions {
         // TODO: better way of separting built-in wasm and custom extension wasm
         assert!(!self.write_built_in_wasm || self.custom_encoding_options.is_empty());
+        // WASMIds returned by register_wasm are the positions of the binaries in the file.
+        assert!(
+            self.wasm_modules.is_empty()
+                || (!self.write_built_in_wasm && self.custom_encoding_options.is_empty())
+        );
         FileWriterOptions {
             iounit_size: self.iounit_size,
             encoding_unit_len: self.encoding_unit_len,
             checksum_type: self.checksum_type,
             write_built_in_wasm: self.write_built_in_wasm,
             custom_encunit_len: self.custom_encunit_len,
+            column_chunk_sizes: self.column_chunk_sizes,
+            auto_column_chunk_size: self.auto_column_chunk_size,
             row_group_size: self.row_group_size,
             custom_encoding_options: self.custom_encoding_options,
             dictionary_type: self.dictionary_type,
             enable_io_unit_checksum: self.enable_io_unit_checksum,
             compression_type: self.compression_type,
+            compression_level: self.compression_level,
+            zstd_dictionary_size: self.zstd_dictionary_size,
+            adaptive_encoding: self.adaptive_encoding,
+            memory_budget: self.memory_budget,
+            max_buffered_bytes: self.max_buffered_bytes,
+            memory_pool: self.memory_pool,
+            encoding_parallelism: self.encoding_parallelism,
+            footer_padding: self.footer_padding,
+            metadata_compression: self.metadata_compression,
+            write_encunit_index: self.write_encunit_index,
+            bloom_filter_columns: self.bloom_filter_columns,
+            bloom_filter_fpp: self.bloom_filter_fpp,
+            footer_versioning: self.footer_versioning,
+            budgeted_metadata: self.budgeted_metadata,
+            column_families: self.column_families,
+            sort_by: self.sort_by,
+            statistics_truncate_length: self.statistics_truncate_length,
+            input_validation: self.input_validation,
+            wasm_modules: self.wasm_modules,
+            column_wasm_ids: self.column_wasm_ids,
+            column_wasm_names: self.column_wasm_names,
+            wasm_references: self.wasm_references,
+            write_wasm_aot: self.write_wasm_aot,
+            wasm_aot_artifacts: self.wasm_aot_artifacts,
+            encrypted_columns: self.encrypted_columns,
+            footer_key_id: self.footer_key_id,
+            key_retriever: self.key_retriever,
+            key_provider: self.key_provider,
         }
     }
 
@@ -192,6 +584,31 @@ impl FileWriterOptionsBuilder {
         self
     }
 
+    pub fn set_column_chunk_sizes(mut self, column_chunk_sizes: HashMap<usize, u64>) -> Self {
+        self.column_chunk_sizes = column_chunk_sizes;
+        self
+    }
+
+    /// Size the chunks of each root-level column from the encoded size of the first rows. The
+    /// chunk sizes chosen are stored in the metadata of each column under
+    /// [`COLUMN_CHUNK_SIZE_KEY`](crate::writer::COLUMN_CHUNK_SIZE_KEY).
+    pub fn set_auto_column_chunk_size(mut self, auto: AutoColumnChunkSize) -> Self {
+        self.auto_column_chunk_size = Some(auto);
+        self
+    }
+
+    /// Set the row group size and the chunk size of each root-level column from `plan`.
+    pub fn set_layout_plan(self, plan: &LayoutPlan) -> Self {
+        self.set_row_group_size(plan.row_group_size())
+            .set_column_chunk_sizes(
+                plan.column_chunk_sizes()
+                    .iter()
+                    .copied()
+                    .enumerate()
+                    .collect(),
+            )
+    }
+
     pub fn set_custom_encoding_options(
         mut self,
         custom_encoding_options: CustomEncodingOptions,
@@ -214,6 +631,249 @@ impl FileWriterOptionsBuilder {
         self.compression_type = compression_type;
         self
     }
+
+    pub fn set_compression_level(mut self, compression_level: CompressionLevel) -> Self {
+        self.compression_level = compression_level;
+        self
+    }
+
+    /// Train a Zstd dictionary of at most `max_size` bytes on the EncUnits of each chunk, stored in
+    /// the metadata of the chunk, and compress them with it. Helps small EncUnits, which compress
+    /// poorly alone. Chunks the dictionary does not make smaller are written without one.
+    /// Requires Zstd compression.
+    pub fn train_zstd_dictionary(mut self, max_size: usize) -> Self {
+        self.zstd_dictionary_size = Some(max_size);
+        self
+    }
+
+    pub fn set_adaptive_encoding(
+        mut self,
+        adaptive_encoding: Option<AdaptiveEncodingOptions>,
+    ) -> Self {
+        self.adaptive_encoding = adaptive_encoding;
+        self
+    }
+
+    pub fn set_memory_budget(mut self, memory_budget: u64) -> Self {
+        self.memory_budget = Some(memory_budget);
+        self
+    }
+
+    pub fn set_max_buffered_bytes(mut self, max_buffered_bytes: u64) -> Self {
+        self.max_buffered_bytes = Some(max_buffered_bytes);
+        self
+    }
+
+    pub fn set_memory_pool(mut self, memory_pool: Arc<dyn MemoryPool>) -> Self {
+        self.memory_pool = Some(memory_pool);
+        self
+    }
+
+    pub fn set_encoding_parallelism(mut self, encoding_parallelism: usize) -> Self {
+        self.encoding_parallelism = encoding_parallelism;
+        self
+    }
+
+    pub fn set_footer_padding(mut self, footer_padding: u64) -> Self {
+        self.footer_padding = footer_padding;
+        self
+    }
+
+    /// Compress the column metadata and the footer together with `compression_type`. Readers
+    /// then fetch and decompress the whole metadata at once. Ignored when appending to a file
+    /// in place, whose metadata is always uncompressed.
+    pub fn compress_metadata(mut self, compression_type: CompressionType) -> Self {
+        self.metadata_compression = compression_type;
+        self
+    }
+
+    /// Index the EncUnits of each chunk, see [`crate::file::encunit_index`]. Only the chunks
+    /// written by this writer are indexed, e.g., not the existing ones when appending.
+    pub fn set_write_encunit_index(mut self, write_encunit_index: bool) -> Self {
+        self.write_encunit_index = write_encunit_index;
+        self
+    }
+
+    /// Write a bloom filter of each chunk of the root-level `columns`, see
+    /// [`crate::common::bloom_filter`]. Readers skip the chunks whose filter rules out the value
+    /// of a [`RowFilter::eq`](crate::reader::RowFilter::eq). Only flat columns are supported.
+    pub fn set_bloom_filter_columns(mut self, columns: Vec<usize>) -> Self {
+        self.bloom_filter_columns = columns;
+        self
+    }
+
+    /// The false positive probability of the bloom filters, 1% by default. Lower values make
+    /// larger filters.
+    pub fn set_bloom_filter_fpp(mut self, fpp: f64) -> Self {
+        assert!(fpp > 0.0 && fpp < 1.0, "Invalid false positive probability");
+        self.bloom_filter_fpp = fpp;
+        self
+    }
+
+    /// When appending with [`FileWriter::try_append`](crate::writer::FileWriter::try_append),
+    /// write the new row groups and footer after the existing footer instead of over it. Each
+    /// footer points back to the previous one, so that earlier versions of the file stay
+    /// readable with [`open_at_version`](crate::reader::open_at_version), at the cost of the
+    /// space of the old metadata.
+    pub fn set_footer_versioning(mut self, footer_versioning: bool) -> Self {
+        self.footer_versioning = footer_versioning;
+        self
+    }
+
+    /// Metadata layout for very wide schemas, e.g., ML feature tables with thousands of columns.
+    /// The column metadata of each row group is grouped into compressed segments of
+    /// `columns_per_segment` physical columns, loaded on demand for the projected columns, and a
+    /// schema index maps field names to columns, see [`crate::reader::find_columns`]. Opening
+    /// the file then does not read metadata proportional to the number of columns, apart from the
+    /// schema. See [`crate::file::metadata_segments`].
+    pub fn set_budgeted_metadata(mut self, columns_per_segment: Option<usize>) -> Self {
+        assert!(
+            columns_per_segment != Some(0),
+            "Segments need at least one column"
+        );
+        self.budgeted_metadata = columns_per_segment;
+        self
+    }
+
+    /// Declare column families, groups of root-level columns commonly projected together. The
+    /// chunks of the columns of a family are buffered until they add up to `iounit_size`, then
+    /// written next to each other, so that they share IO units. Readers projecting several
+    /// columns of a family fetch their chunks of an IO unit in one read instead of one per chunk.
+    /// A column belongs to at most one family. Families are recorded as the "ColumnFamilies"
+    /// optional section, see [`crate::file::column_families`]. The IO units of the row groups of
+    /// an existing file are not kept when appending to it, nor when rewriting it.
+    pub fn set_column_families(mut self, column_families: Vec<Vec<usize>>) -> Self {
+        self.column_families = column_families;
+        self
+    }
+
+    /// Sort the rows of each row group by the root-level columns of `keys`, given by name, then
+    /// by the next ones for equal keys, with nulls first. Batches are buffered until they fill a
+    /// row group, outside of the memory budget, and sorted before being encoded. The order is
+    /// recorded in the footer, see [`crate::file::sort_order`], e.g., for pruning or merge
+    /// joins. It is kept when rewriting the file, and when appending to it with the same order.
+    pub fn sort_by(mut self, keys: Vec<(impl Into<String>, SortDirection)>) -> Self {
+        self.sort_by = keys
+            .into_iter()
+            .map(|(column, direction)| (column.into(), direction))
+            .collect();
+        self
+    }
+
+    /// Encrypt the EncUnits of the root-level columns of `columns` with AES-GCM, each with the
+    /// key of the given id, see [`crate::encryption`]. Their chunks get no statistics, zone maps
+    /// nor bloom filters, which would leak their values. The keys are provided by
+    /// [`Self::set_key_retriever`] or [`Self::set_key_provider`]. Not supported with shared
+    /// dictionaries.
+    pub fn encrypt_columns(mut self, columns: HashMap<usize, String>) -> Self {
+        self.encrypted_columns = columns;
+        self
+    }
+
+    /// Encrypt the column metadata and the footer, schema included, with the key of id
+    /// `key_id`. Readers then need the key to open the file, and tools reading the metadata
+    /// without a reader, e.g., to append to the file, fail.
+    pub fn encrypt_footer(mut self, key_id: impl Into<String>) -> Self {
+        self.footer_key_id = Some(key_id.into());
+        self
+    }
+
+    /// Provide the keys of [`Self::encrypt_columns`] and [`Self::encrypt_footer`].
+    pub fn set_key_retriever(mut self, key_retriever: Arc<dyn KeyRetriever>) -> Self {
+        self.key_retriever = Some(key_retriever);
+        self
+    }
+
+    /// Encrypt each column of [`Self::encrypt_columns`] and the footer of
+    /// [`Self::encrypt_footer`] with a fresh data key of `key_provider`, stored in the file
+    /// wrapped with the master key of the given id, see [`crate::encryption`]. Readers then only
+    /// need the master keys, see
+    /// [`FileReaderV2Builder::with_key_provider`](crate::reader::FileReaderV2Builder::with_key_provider).
+    /// Takes precedence over [`Self::set_key_retriever`].
+    pub fn set_key_provider(mut self, key_provider: Arc<dyn KeyProvider>) -> Self {
+        self.key_provider = Some(key_provider);
+        self
+    }
+
+    pub fn set_statistics_truncate_length(
+        mut self,
+        statistics_truncate_length: Option<usize>,
+    ) -> Self {
+        self.statistics_truncate_length = statistics_truncate_length;
+        self
+    }
+
+    /// Check each batch for values with several representations before encoding it, see
+    /// [`crate::writer::input_validation`]: normalize them, or reject the batch with the column
+    /// and row of the first one. Batches are written as given by default.
+    pub fn set_input_validation(mut self, input_validation: InputValidation) -> Self {
+        self.input_validation = input_validation;
+        self
+    }
+
+    /// Encode the root-level column `column` with the `encode_ffi` function of `wasm_binary`,
+    /// which must also export the matching `decode_general_ffi`. Only columns of non-nested
+    /// types are supported. Cannot be used together with `write_built_in_wasm`.
+    pub fn with_wasm_encoder(mut self, column: usize, wasm_binary: Vec<u8>) -> Self {
+        let wasm_id = self.register_wasm(wasm_binary);
+        self.column_wasm_ids.insert(column, wasm_id);
+        self
+    }
+
+    /// Write `wasm_binary` to the file and return its WASMId. Registering the same binary twice
+    /// returns the same WASMId. Cannot be used together with `write_built_in_wasm` or
+    /// `set_custom_encoding_options`.
+    pub fn register_wasm(&mut self, wasm_binary: Vec<u8>) -> WASMId {
+        let position = match self.wasm_modules.iter().position(|x| *x == wasm_binary) {
+            Some(position) => position,
+            None => {
+                self.wasm_modules.push(wasm_binary);
+                self.wasm_modules.len() - 1
+            }
+        };
+        WASMId(position as u32)
+    }
+
+    /// Encode the root-level column `column_name` with the `encode_ffi` function of the binary
+    /// registered as `wasm_id`, see [`Self::with_wasm_encoder`]. The mapping is recorded in the
+    /// "ColumnWASMs" optional section of the footer.
+    pub fn set_column_wasm(mut self, column_name: impl Into<String>, wasm_id: WASMId) -> Self {
+        self.column_wasm_names.insert(column_name.into(), wasm_id);
+        self
+    }
+
+    /// Do not embed the WASM binary with content hash `hash`, see
+    /// [`wasm_module_hash`](crate::file::wasm_modules::wasm_module_hash), but only record its
+    /// hash and `uri` in the footer, so that many files can share one module. Readers fetch it
+    /// with a [`WasmResolver`](crate::reader::WasmResolver).
+    pub fn wasm_reference_only(mut self, hash: u64, uri: impl Into<String>) -> Self {
+        self.wasm_references.insert(hash, uri.into());
+        self
+    }
+
+    /// Compile each embedded WASM binary ahead of time for the host and write the artifact next
+    /// to it, so that readers on the same platform trusting the file do not compile it, see
+    /// [`crate::file::wasm_aot`]. Binaries requiring optional WASM features are not compiled.
+    pub fn write_wasm_aot(mut self, write_wasm_aot: bool) -> Self {
+        self.write_wasm_aot = write_wasm_aot;
+        self
+    }
+
+    /// Write `aot_binary`, the AOT artifact of the WASM binary with content hash `hash` for
+    /// `target`, next to the binary, e.g., compiled for another platform with
+    /// [`fff_ude_wasm::aot::precompile`].
+    pub fn add_wasm_aot_artifact(
+        mut self,
+        hash: u64,
+        target: AotTarget,
+        aot_binary: Vec<u8>,
+    ) -> Self {
+        self.wasm_aot_artifacts
+            .entry(hash)
+            .or_default()
+            .push((target, aot_binary));
+        self
+    }
 }
 
 #[derive(Clone, Default)]
@@ -236,6 +896,19 @@ impl CustomEncodingOptions {
         }
     }
 
+    /// Add `wasm_lib` under a newly allocated WASMId, encoding the columns of `data_types`.
+    pub fn add_wasm(
+        &mut self,
+        wasm_lib: WasmLib,
+        data_types: impl IntoIterator<Item = DataType>,
+    ) -> WASMId {
+        let wasm_id = allocate_wasm_id(&self.wasms);
+        self.wasms.insert(wasm_id, wasm_lib);
+        self.data_type_to_wasm_id
+            .extend(data_types.into_iter().map(|dt| (dt, wasm_id)));
+        wasm_id
+    }
+
     pub fn len(&self) -> usize {
         self.wasms.len()
     }
@@ -1,33 +1,97 @@
 use crate::{
     common::checksum::{create_checksum, ChecksumType},
+    compression::decompress_data,
     context::{WASMId, WASMReadingContext},
     dict::shared_dictionary_cache::SharedDictionaryCache,
-    file::footer::{parse_footer, MetadataSection},
-    io::reader::Reader,
+    encoder::logical::num_physical_columns,
+    encryption::{Decryptor, EnvelopeKeys, KeyProvider, KeyRetriever},
+    file::{
+        column_families::deserialize_column_families,
+        footer::{parse_footer, MetadataSection},
+        key_value_metadata::KeyValueMetadata,
+        metadata_segments::segment_column,
+        sort_order,
+    },
+    io::{mmap::LocalFile, reader::Reader, scheduler::IoScheduler},
+    memory::MemoryPool,
     options::DEFAULT_IOUNIT_SIZE,
-    reader::{read_postscript, RowGroupCntNPointer},
+    reader::{
+        footer_cache::{CachedFooter, FooterCache, FooterCacheKey},
+        get_bloom_filters, get_footer_buffer, locate_section, read_metadata, read_postscript,
+        schema_evolution::SchemaEvolution,
+        ChunkCache, DefaultValueProvider, FileChunkCache, RowGroupCntNPointer, WasmModuleCache,
+        WasmResolver,
+    },
 };
 use arrow_buffer::MutableBuffer;
+use arrow_schema::{Schema, SchemaRef};
 use bytes::Bytes;
-use fff_core::errors::{Error, Result};
-use fff_format::File::fff::flatbuf::root_as_footer;
+use fff_core::{
+    errors::{Error, Result},
+    nyi_err,
+};
+use fff_format::File::fff::flatbuf::{
+    root_as_footer, CompressionType, OptionalMetadataSections, RowGroupMetadata,
+};
 use fff_format::POSTSCRIPT_SIZE;
 use fff_ude_wasm::Runtime;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    sync::Arc,
+};
 
-use crate::reader::{FileReaderV2, Projection, Selection};
+use crate::reader::{FileReaderV2, Projection, RowFilter, Selection, TimestampNormalization};
+
+/// How the reader outputs the columns whose chunks are encoded in a form Arrow can represent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Preference {
+    /// Output the values of the schema type.
+    #[default]
+    Decoded,
+    /// Output dictionary-encoded chunks as `DictionaryArray`s, see
+    /// [`FileReaderV2Builder::with_dictionary_preservation`], and RLE EncUnits without nulls as
+    /// `RunArray`s of `Int32` run ends, for engines operating on encoded data directly. Only
+    /// top-level flat columns read in full, i.e., without row indexes or ranges, keep their runs.
+    Encoded,
+}
 
 pub struct FileReaderV2Builder<R: Reader + Clone> {
     reader: R,
     projections: Projection,
+    /// Names of the projected top-level columns, resolved into `projections` when building.
+    projected_names: Option<Vec<String>>,
     selection: Selection,
     /// Whether we do a first 8MB read to the footer at once?
     read_ahead: bool,
     wasm_rts: Option<HashMap<WASMId, Arc<Runtime>>>,
+    /// Existing runtimes keyed by the content hash of their module.
+    wasm_rts_by_hash: Option<HashMap<u64, Arc<Runtime>>>,
     /// Whether we verify the IOUnit checksum.
     verify_io_unit_checksum: bool,
     /// Whether we verify the file checksum.
     verify_file_checksum: bool,
+    /// Whether we verify the number of rows decoded from each chunk.
+    verify_decoded_length: bool,
+    /// Whether we verify the statistics of each chunk against its decoded values.
+    verify_statistics: bool,
+    /// Whether dictionary-encoded chunks are output as `DictionaryArray`s.
+    preserve_dictionary: bool,
+    preferred_output: Preference,
+    timestamp_normalization: TimestampNormalization,
+    footer_cache: Option<(Arc<FooterCache>, FooterCacheKey)>,
+    row_filter: Option<RowFilter>,
+    wasm_module_cache: Option<Arc<WasmModuleCache>>,
+    wasm_resolver: Option<Arc<dyn WasmResolver>>,
+    target_schema: Option<SchemaRef>,
+    default_values: HashMap<String, DefaultValueProvider>,
+    fill_out_of_range_rows: bool,
+    decryptor: Option<Arc<Decryptor>>,
+    /// Whether the WASM modules are instantiated from the AOT artifacts of the file.
+    trusted_wasm_aot: bool,
+    memory_pool: Option<Arc<dyn MemoryPool>>,
+    chunk_cache: Option<FileChunkCache>,
+    io_scheduler: Option<IoScheduler>,
 }
 
 impl<R: Reader + Clone> FileReaderV2Builder<R> {
@@ -35,11 +99,30 @@ impl<R: Reader + Clone> FileReaderV2Builder<R> {
         Self {
             reader,
             projections: Projection::default(),
+            projected_names: None,
             selection: Selection::default(),
             read_ahead: false,
             wasm_rts: None,
+            wasm_rts_by_hash: None,
             verify_io_unit_checksum: false,
             verify_file_checksum: false,
+            verify_decoded_length: false,
+            verify_statistics: false,
+            preserve_dictionary: false,
+            preferred_output: Preference::default(),
+            timestamp_normalization: TimestampNormalization::default(),
+            footer_cache: None,
+            row_filter: None,
+            wasm_module_cache: None,
+            wasm_resolver: None,
+            target_schema: None,
+            default_values: HashMap::new(),
+            fill_out_of_range_rows: false,
+            decryptor: None,
+            trusted_wasm_aot: false,
+            memory_pool: None,
+            chunk_cache: None,
+            io_scheduler: None,
         }
     }
 
@@ -48,13 +131,18 @@ impl<R: Reader + Clone> FileReaderV2Builder<R> {
         self
     }
 
+    /// Project the top-level columns with the given names, in this order, instead of indexes.
+    /// The names are resolved against the schema of the file when building the reader, which
+    /// fails if a column is missing.
+    pub fn with_projected_names<S: Into<String>>(
+        mut self,
+        names: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.projected_names = Some(names.into_iter().map(Into::into).collect());
+        self
+    }
+
     pub fn with_selection(mut self, selection: Selection) -> Self {
-        if let Selection::RowIndexes(row_indexes) = &selection {
-            assert!(
-                row_indexes.len() == 1,
-                "Only one row index is supported for experiment purposes"
-            );
-        }
         self.selection = selection;
         self
     }
@@ -65,11 +153,31 @@ impl<R: Reader + Clone> FileReaderV2Builder<R> {
     }
 
     /// Init the file reader using the existing Wasm Runtime provided, instead of compiling from the Wasm in the file.
+    /// WASMIds are positions in a file, so the runtimes only fit files written with the same
+    /// modules in the same order, see [`Self::with_existing_runtimes_by_hash`].
     pub fn with_existing_runtimes(mut self, wasm_rts: HashMap<WASMId, Arc<Runtime>>) -> Self {
         self.wasm_rts = Some(wasm_rts);
         self
     }
 
+    /// Use the existing runtimes provided for the modules of the file with the same content hash,
+    /// see [`wasm_module_hash`](crate::file::wasm_modules::wasm_module_hash). The other modules,
+    /// and all of them in files written before hashes were recorded, are compiled from the file.
+    /// Ignored with [`Self::with_existing_runtimes`].
+    pub fn with_existing_runtimes_by_hash(mut self, wasm_rts: HashMap<u64, Arc<Runtime>>) -> Self {
+        self.wasm_rts_by_hash = Some(wasm_rts);
+        self
+    }
+
+    /// Instantiate the WASM modules of the file from their AOT artifacts compiled for the host,
+    /// if any, instead of compiling them, see [`crate::file::wasm_aot`]. Modules without a
+    /// matching artifact, or whose artifact fails to load, are compiled. AOT artifacts are native
+    /// code loaded as is, so only enable this for files from trusted writers.
+    pub fn with_trusted_wasm_aot(mut self, trusted_wasm_aot: bool) -> Self {
+        self.trusted_wasm_aot = trusted_wasm_aot;
+        self
+    }
+
     /// Whether we verify the IOUnit checksum.
     pub fn with_verify_io_unit_checksum(mut self, verify_io_unit_checksum: bool) -> Self {
         self.verify_io_unit_checksum = verify_io_unit_checksum;
@@ -82,6 +190,182 @@ impl<R: Reader + Clone> FileReaderV2Builder<R> {
         self
     }
 
+    /// Whether we verify that each chunk decodes to exactly its number of rows.
+    /// Useful to pinpoint a faulty (e.g., Wasm) codec by its column and chunk.
+    pub fn with_verify_decoded_length(mut self, verify_decoded_length: bool) -> Self {
+        self.verify_decoded_length = verify_decoded_length;
+        self
+    }
+
+    /// Whether we recompute the null count and min/max statistics of each chunk of the top-level
+    /// flat columns from its decoded values, and fail the read if they differ from the ones in
+    /// the footer, e.g., stale after editing the footer or wrong from a writer bug. Truncated
+    /// bounds only need to bound the values. Only chunks decoded in full are verified, not the
+    /// ones read for selected rows.
+    pub fn with_verify_statistics(mut self, verify_statistics: bool) -> Self {
+        self.verify_statistics = verify_statistics;
+        self
+    }
+
+    /// Output the dictionary-encoded (local or shared) chunks of top-level columns as
+    /// `DictionaryArray<UInt32Type>`s instead of materializing their values.
+    /// Chunks referencing the same shared dictionary share a single values array.
+    /// Other chunks are still output with the schema type, so the type may vary across batches.
+    pub fn with_dictionary_preservation(mut self, preserve_dictionary: bool) -> Self {
+        self.preserve_dictionary = preserve_dictionary;
+        self
+    }
+
+    /// Whether columns are output decoded or in their encoded form, see [`Preference`].
+    /// The type of a column may then vary across batches, e.g., with the encoding of its chunks.
+    pub fn with_preferred_output(mut self, preferred_output: Preference) -> Self {
+        self.preferred_output = preferred_output;
+        self
+    }
+
+    /// How timestamps of top-level columns are output, see [`TimestampNormalization`].
+    /// [`FileReaderV2::schema`] stays the schema of the file.
+    pub fn with_timestamp_normalization(
+        mut self,
+        timestamp_normalization: TimestampNormalization,
+    ) -> Self {
+        self.timestamp_normalization = timestamp_normalization;
+        self
+    }
+
+    /// Look up the parsed metadata of the file in `cache` under `key` and the projection, and
+    /// store it there on a miss, so that re-opening a hot file skips reading and parsing its footer.
+    /// The shared dictionaries are cached as well.
+    pub fn with_footer_cache(mut self, cache: Arc<FooterCache>, key: FooterCacheKey) -> Self {
+        self.footer_cache = Some((cache, key));
+        self
+    }
+
+    /// Look up the WASM modules of the file in `cache` by content hash, and store them there on a
+    /// miss, so that a module shared by many files is fetched and compiled once.
+    pub fn with_wasm_module_cache(mut self, cache: Arc<WasmModuleCache>) -> Self {
+        self.wasm_module_cache = Some(cache);
+        self
+    }
+
+    /// Fetch the WASM modules that the file stores by reference only with `resolver`. See
+    /// [FileWriterOptionsBuilder::wasm_reference_only](crate::options::FileWriterOptionsBuilder::wasm_reference_only).
+    pub fn with_wasm_resolver(mut self, resolver: Arc<dyn WasmResolver>) -> Self {
+        self.wasm_resolver = Some(resolver);
+        self
+    }
+
+    /// Only output the rows passing `row_filter`. The filter column is decoded first, then the
+    /// other projected columns only for the selected rows. See [`RowFilter`].
+    pub fn with_row_filter(mut self, row_filter: RowFilter) -> Self {
+        self.row_filter = Some(row_filter);
+        self
+    }
+
+    /// Output batches of `target_schema` instead of the schema of the file, e.g., to read files
+    /// written before a schema change. Fields are matched with the top-level columns of the file
+    /// by name. Columns missing from the file are filled with their default value, see
+    /// [`Self::with_default_value`], nulls by default. Columns of the file missing
+    /// from the target schema are not decoded, and columns are cast to their target type if it
+    /// is a promotion: a wider integer or float type, a large or view string or binary type, or a
+    /// timestamp with another unit. Cannot be combined with a projection, the target schema
+    /// already selects the columns to decode.
+    pub fn with_target_schema(mut self, target_schema: SchemaRef) -> Self {
+        self.target_schema = Some(target_schema);
+        self
+    }
+
+    /// The value of `column` in the rows the file does not hold, instead of nulls: the rows of
+    /// a column of the target schema missing from the file, and the placeholder rows of
+    /// [`Self::with_fill_out_of_range_rows`]. A non-nullable column missing from the file can
+    /// be read with a non-null default value.
+    pub fn with_default_value(
+        mut self,
+        column: impl Into<String>,
+        default_value: DefaultValueProvider,
+    ) -> Self {
+        self.default_values.insert(column.into(), default_value);
+        self
+    }
+
+    /// Output a placeholder row of default values, see [`Self::with_default_value`], for each
+    /// selected row past the end of the file instead of failing or skipping it, e.g., for
+    /// best-effort point lookups. Cannot be combined with a row filter.
+    pub fn with_fill_out_of_range_rows(mut self, fill_out_of_range_rows: bool) -> Self {
+        self.fill_out_of_range_rows = fill_out_of_range_rows;
+        self
+    }
+
+    /// Decrypt the encrypted columns and footer of the file with the keys of `key_retriever`,
+    /// see [`crate::encryption`]. Without it, reading them fails.
+    pub fn with_key_retriever(mut self, key_retriever: Box<dyn KeyRetriever>) -> Self {
+        self.decryptor = Some(Arc::new(Decryptor::new(key_retriever)));
+        self
+    }
+
+    /// Decrypt the encrypted columns and footer of a file written with a key provider by
+    /// unwrapping their data keys with `key_provider`, see
+    /// [`FileWriterOptionsBuilder::set_key_provider`](crate::options::FileWriterOptionsBuilder::set_key_provider).
+    /// Each data key is unwrapped once per reader.
+    pub fn with_key_provider(mut self, key_provider: Arc<dyn KeyProvider>) -> Self {
+        self.decryptor = Some(Arc::new(Decryptor::new(Box::new(EnvelopeKeys(
+            key_provider,
+        )))));
+        self
+    }
+
+    /// Reserve the encoded chunks held by the decoder of each column from `memory_pool`, see
+    /// [`crate::memory`]. Reading fails when a reservation is refused.
+    pub fn with_memory_pool(mut self, memory_pool: Arc<dyn MemoryPool>) -> Self {
+        self.memory_pool = Some(memory_pool);
+        self
+    }
+
+    /// Look up the chunks of the file in `cache` under `file_id` and their offset, and store them
+    /// there on a miss, so that repeated accesses to the same rows skip reading, verifying and
+    /// decrypting their chunks. Share `cache` between the readers of the file, and of other files
+    /// under other ids. `file_id` must change whenever the file does, see [`ChunkCacheKey`].
+    ///
+    /// [`ChunkCacheKey`]: crate::reader::ChunkCacheKey
+    pub fn with_chunk_cache(
+        mut self,
+        cache: Arc<dyn ChunkCache>,
+        file_id: impl Into<Arc<str>>,
+    ) -> Self {
+        self.chunk_cache = Some(FileChunkCache {
+            cache,
+            file_id: file_id.into(),
+        });
+        self
+    }
+
+    /// Read the chunks of the projected columns of all the row groups ahead of the decoders, with
+    /// at most `max_concurrency` ranges in flight, instead of one chunk at a time as the decoders
+    /// reach it, see [`crate::io::scheduler`]. Only scans of all the rows without a row filter are
+    /// scheduled.
+    pub fn with_io_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.io_scheduler = Some(IoScheduler::new(max_concurrency));
+        self
+    }
+
+    /// The projected columns, followed by the filter column if it is not projected.
+    fn decoded_projection(&self) -> Projection {
+        match (&self.projections, &self.row_filter) {
+            (Projection::LeafColumnIndexes(projections), Some(row_filter))
+                if !projections.contains(&row_filter.column()) =>
+            {
+                Projection::new(
+                    projections
+                        .iter()
+                        .copied()
+                        .chain(std::iter::once(row_filter.column()))
+                        .collect::<Vec<_>>(),
+                )
+            }
+            (projections, _) => projections.clone(),
+        }
+    }
+
     fn verify_file_checksum(
         &self,
         file_size: u64,
@@ -106,7 +390,211 @@ impl<R: Reader + Clone> FileReaderV2Builder<R> {
         Ok(())
     }
 
-    pub fn build(self) -> Result<FileReaderV2<R>> {
+    /// Open the file. Fails if the WASM modules needed by the projected columns cannot be
+    /// obtained, see [`FileReaderV2::missing_wasm_modules`].
+    pub fn build(mut self) -> Result<FileReaderV2<R>> {
+        if self.fill_out_of_range_rows && self.row_filter.is_some() {
+            return nyi_err!("Filling out-of-range rows with a row filter");
+        }
+        // The columns to decode depend on the schema of the file, which is read first.
+        if let Some(names) = self.projected_names.take() {
+            let schema = self.read_schema()?;
+            let indices = names
+                .iter()
+                .map(|name| schema.index_of(name))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            self.projections = Projection::new(indices);
+        }
+        let schema_evolution = match self.target_schema.take() {
+            Some(_) if self.projections != Projection::All => {
+                return nyi_err!("Projections with a target schema");
+            }
+            Some(target_schema) => {
+                let schema_evolution = SchemaEvolution::try_new(
+                    &self.read_schema()?,
+                    target_schema,
+                    &self.default_values,
+                )?;
+                self.projections = schema_evolution.projection();
+                Some(schema_evolution)
+            }
+            None => None,
+        };
+        let decoded_projection = self.decoded_projection();
+        let cached = self
+            .footer_cache
+            .as_ref()
+            .and_then(|(cache, key)| cache.get(key, &decoded_projection));
+        let (footer, wasm_context) = match cached {
+            Some(footer) => {
+                if self.verify_file_checksum {
+                    self.verify_file_checksum(
+                        self.reader.size()?,
+                        footer.post_script.data_checksum,
+                        footer.post_script.checksum_type,
+                    )?;
+                }
+                let wasm_context = self.wasm_context(&footer);
+                (footer, wasm_context)
+            }
+            None => {
+                let (footer, wasm_context) = self.load_footer(&decoded_projection)?;
+                let footer = Arc::new(footer);
+                if let Some((cache, key)) = &self.footer_cache {
+                    cache.insert(key.clone(), decoded_projection, footer.clone());
+                }
+                (footer, wasm_context)
+            }
+        };
+        let row_filter = match self.row_filter {
+            // The bloom filters are located by the footer, which may be encrypted.
+            Some(row_filter)
+                if row_filter.value_hash().is_some() && !footer.post_script.encrypted_footer =>
+            {
+                let column_index = footer
+                    .schema
+                    .fields()
+                    .iter()
+                    .take(row_filter.column())
+                    .map(|f| num_physical_columns(f.data_type()))
+                    .sum::<usize>() as u32;
+                let bloom_filters = get_bloom_filters(&self.reader)?
+                    .into_iter()
+                    .filter(|f| f.column_index == column_index)
+                    .map(|f| (f.chunk_offset, f.filter))
+                    .collect();
+                Some(row_filter.with_bloom_filters(bloom_filters))
+            }
+            row_filter => row_filter,
+        };
+        let mut reader = FileReaderV2 {
+            reader: self.reader,
+            schema: footer.schema.clone(),
+            projections: self.projections,
+            selection: self.selection,
+            grouped_column_metadata_buffers: footer.grouped_column_metadata_buffers.clone(),
+            row_group_cnt_n_pointers: footer.row_group_cnt_n_pointers.clone(),
+            wasm_context,
+            shared_dictionary_cache: footer.shared_dictionary_cache.clone(),
+            checksum_type: self
+                .verify_io_unit_checksum
+                .then_some(footer.post_script.checksum_type),
+            verify_decoded_length: self.verify_decoded_length,
+            verify_statistics: self.verify_statistics,
+            preserve_dictionary: self.preserve_dictionary
+                || self.preferred_output == Preference::Encoded,
+            preserve_runs: self.preferred_output == Preference::Encoded,
+            timestamp_normalization: self.timestamp_normalization,
+            row_filter,
+            pruned_row_groups: vec![],
+            family_iounits: footer.family_iounits.clone(),
+            key_value_metadata: footer.key_value_metadata.clone(),
+            sort_order: footer.sort_order.clone(),
+            schema_evolution,
+            default_values: self.default_values,
+            fill_out_of_range_rows: self.fill_out_of_range_rows,
+            decryptor: self.decryptor,
+            chunk_cache: self.chunk_cache,
+            memory_pool: self.memory_pool,
+            io_scheduler: self.io_scheduler,
+        };
+        let missing = reader.missing_wasm_modules()?;
+        if !missing.is_empty() {
+            let modules = missing
+                .iter()
+                .map(|(wasm_id, hash)| match hash {
+                    Some(hash) => format!("{hash:016x}"),
+                    None => format!("WASMId {}", wasm_id.0),
+                })
+                .collect::<Vec<_>>();
+            return Err(general_error!(format!(
+                "WASM modules needed by the projected columns are not available: {}",
+                modules.join(", ")
+            )));
+        }
+        if let Some(row_filter) = &reader.row_filter {
+            if reader
+                .sort_order
+                .iter()
+                .any(|key| key.column == row_filter.column())
+            {
+                let row_groups: HashSet<usize> =
+                    reader.prune_row_groups(row_filter)?.into_iter().collect();
+                reader.pruned_row_groups = (0..reader.row_group_cnt_n_pointers.len())
+                    .filter(|rg_idx| !row_groups.contains(rg_idx))
+                    .collect();
+            }
+        }
+        Ok(reader)
+    }
+
+    /// Read and parse the schema of the file only.
+    fn read_schema(&self) -> Result<Schema> {
+        let post_script = read_postscript(&self.reader, self.reader.size()?)?;
+        let footer_buffer = if post_script.encrypted_footer {
+            let metadata = read_metadata(&self.reader, &post_script, self.decryptor.as_deref())?;
+            MutableBuffer::from(
+                metadata[metadata.len() - post_script.footer_size as usize..].to_vec(),
+            )
+        } else {
+            get_footer_buffer(&self.reader, &post_script)?
+        };
+        let footer_fbs = root_as_footer(&footer_buffer)
+            .map_err(|e| Error::ParseError(format!("Unable to get root as footer: {e:?}")))?;
+        Ok(parse_footer(&footer_fbs)?.0)
+    }
+
+    fn wasm_context(&self, footer: &CachedFooter) -> Option<Arc<WASMReadingContext<R>>> {
+        if let Some(wasm_rts) = &self.wasm_rts {
+            Some(
+                WASMReadingContext::new_with_rt_and_versions(
+                    wasm_rts.clone(),
+                    footer.encoding_versions.clone(),
+                )
+                .into(),
+            )
+        } else {
+            footer.wasm_section.clone().map(|wasm_section| {
+                WASMReadingContext::new_with_versions(
+                    wasm_section,
+                    self.reader.clone(),
+                    footer.encoding_versions.clone(),
+                )
+                .with_module_sharing(
+                    footer.wasm_modules_section.clone(),
+                    self.module_cache(),
+                    self.wasm_resolver.clone(),
+                )
+                .with_aot_artifacts(
+                    footer
+                        .wasm_aot_section
+                        .clone()
+                        .filter(|_| self.trusted_wasm_aot),
+                )
+                .into()
+            })
+        }
+    }
+
+    /// The module cache, holding the existing runtimes keyed by hash, if any.
+    fn module_cache(&self) -> Option<Arc<WasmModuleCache>> {
+        let Some(wasm_rts) = &self.wasm_rts_by_hash else {
+            return self.wasm_module_cache.clone();
+        };
+        let cache = self.wasm_module_cache.clone().unwrap_or_default();
+        for (hash, runtime) in wasm_rts {
+            cache.insert(*hash, runtime.clone());
+        }
+        Some(cache)
+    }
+
+    /// Read and parse the footer and the metadata of the columns in `projections`.
+    /// Also return the Wasm context used to decode the shared dictionaries.
+    #[allow(clippy::type_complexity)]
+    fn load_footer(
+        &self,
+        projections: &Projection,
+    ) -> Result<(CachedFooter, Option<Arc<WASMReadingContext<R>>>)> {
         let file_size = self.reader.size()?;
         let read_ahead_buffer = if self.read_ahead {
             let len = std::cmp::min(DEFAULT_IOUNIT_SIZE, file_size) as usize;
@@ -130,8 +618,23 @@ impl<R: Reader + Clone> FileReaderV2Builder<R> {
                 post_script.checksum_type,
             )?;
         }
+        // Compressed or encrypted metadata is read and decoded as a whole.
+        let decompressed_metadata = if post_script.compression != CompressionType::Uncompressed
+            || post_script.encrypted_footer
+        {
+            Some(Bytes::from(
+                read_metadata(&self.reader, &post_script, self.decryptor.as_deref())?
+                    .as_slice()
+                    .to_vec(),
+            ))
+        } else {
+            None
+        };
         let mut footer_buffer = MutableBuffer::from_len_zeroed(post_script.footer_size as usize);
-        let footer_fbs = if self.read_ahead {
+        let footer_fbs = if let Some(metadata) = &decompressed_metadata {
+            root_as_footer(&metadata[metadata.len() - post_script.footer_size as usize..])
+                .map_err(|e| Error::ParseError(format!("Unable to get root as footer: {e:?}")))?
+        } else if self.read_ahead {
             assert!(
                 post_script.footer_size < (DEFAULT_IOUNIT_SIZE - 32) as u32,
                 "Unlikely that footer size is larger than 8MB"
@@ -160,6 +663,11 @@ impl<R: Reader + Clone> FileReaderV2Builder<R> {
             optional_sections,
             encoding_versions,
         ) = parse_footer(&footer_fbs)?;
+        let data_size = file_size - POSTSCRIPT_SIZE - post_script.metadata_size as u64;
+        let metadata_segment_columns = self.read_metadata_segment_columns(optional_sections)?;
+        let family_iounits = self.read_family_iounits(optional_sections)?;
+        let key_value_metadata = KeyValueMetadata::from_fb(&footer_fbs)?;
+        let sort_order = sort_order::from_fb(&footer_fbs);
         // Depending on the ratio between number of projected columns and total columns,
         // we fetch them all or do one by one fetch.
         let total_columns = row_groups_pointer
@@ -182,14 +690,19 @@ impl<R: Reader + Clone> FileReaderV2Builder<R> {
             _size: size,
         })
         .collect();
-        let ratio = match &self.projections {
+        let ratio = match projections {
             Projection::All => 1.0,
             Projection::LeafColumnIndexes(projections) => {
                 projections.len() as f64 / total_columns as f64
             }
         };
         // let all_metadata_buffer = if false {
-        let all_metadata_buffer = if ratio > 0.6 || total_columns <= 100 {
+        let all_metadata_buffer = if let Some(metadata) = &decompressed_metadata {
+            Some(metadata.slice(..metadata.len() - post_script.footer_size as usize))
+        } else if metadata_segment_columns.is_some() {
+            // The segments of the projected columns are read on demand.
+            None
+        } else if ratio > 0.6 || total_columns <= 100 {
             let mut res: Vec<u8> =
                 vec![0; post_script.metadata_size as usize - post_script.footer_size as usize];
             if self.read_ahead {
@@ -214,14 +727,21 @@ impl<R: Reader + Clone> FileReaderV2Builder<R> {
             .ok_or_else(|| Error::ParseError("Row group metadatas not found".to_string()))?;
         let mut grouped_column_metadata_buffers: Vec<Vec<Bytes>> = vec![];
         for rg_meta_fbs in row_group_metadata_fbs.iter() {
+            if let Some(columns_per_segment) = metadata_segment_columns {
+                grouped_column_metadata_buffers.push(self.read_segmented_column_metadata(
+                    rg_meta_fbs,
+                    columns_per_segment,
+                    projections,
+                    &schema,
+                    all_metadata_buffer.as_ref(),
+                    data_size,
+                )?);
+                continue;
+            }
             let mut column_metadata_buffers: Vec<Bytes> = vec![];
-            let column_meta_ptrs = match self.projections {
-                Projection::All => rg_meta_fbs
-                    .col_metadatas()
-                    .unwrap()
-                    .into_iter()
-                    .collect(),
-                Projection::LeafColumnIndexes(ref projections) => {
+            let column_meta_ptrs = match projections {
+                Projection::All => rg_meta_fbs.col_metadatas().unwrap().into_iter().collect(),
+                Projection::LeafColumnIndexes(projections) => {
                     let mut column_meta_offsets = vec![];
                     for i in projections {
                         column_meta_offsets.push(rg_meta_fbs.col_metadatas().unwrap().get(*i));
@@ -241,9 +761,7 @@ impl<R: Reader + Clone> FileReaderV2Builder<R> {
                     }
                     Some(ref buf) => {
                         // column metas are already read at once
-                        let data_size = file_size as usize
-                            - POSTSCRIPT_SIZE as usize
-                            - post_script.metadata_size as usize;
+                        let data_size = data_size as usize;
                         column_metadata_buffers.push(buf.slice(
                             column_meta_pointer.offset() as usize - data_size
                                 ..column_meta_pointer.offset() as usize - data_size
@@ -254,48 +772,163 @@ impl<R: Reader + Clone> FileReaderV2Builder<R> {
             }
             grouped_column_metadata_buffers.push(column_metadata_buffers);
         }
-        let wasm_context = if let Some(wasm_rts) = self.wasm_rts {
-            Some(WASMReadingContext::new_with_rt_and_versions(wasm_rts, encoding_versions).into())
-        } else {
-            optional_sections.map(|sections| {
-                let pos = sections
-                    .names()
-                    .unwrap()
-                    .iter()
-                    .position(|v| v == "WASMBinaries")
-                    .unwrap();
-                WASMReadingContext::new_with_versions(
-                    MetadataSection {
-                        offset: sections.offsets().unwrap().get(pos),
-                        size: sections.sizes().unwrap().get(pos),
-                        compression_type: sections.compression_types().unwrap().get(pos),
-                    },
-                    self.reader.clone(),
-                    encoding_versions,
-                )
-                .into()
+        let find_section = |name: &str| {
+            optional_sections.and_then(|sections| {
+                let pos = sections.names()?.iter().position(|v| v == name)?;
+                Some(MetadataSection {
+                    offset: sections.offsets()?.get(pos),
+                    size: sections.sizes()?.get(pos),
+                    compression_type: sections.compression_types()?.get(pos),
+                })
             })
         };
-        let shared_dictionary_cache = shared_dict_table.map(|shared_dict_table| {
-            SharedDictionaryCache::try_new_read_all(
-                self.reader.clone(),
-                shared_dict_table,
-                wasm_context.clone(),
-            )
-            .unwrap()
+        let wasm_modules_section = find_section("WASMModules");
+        let wasm_aot_section = find_section("WASMAotArtifacts");
+        let wasm_section = optional_sections.map(|sections| {
+            let pos = sections
+                .names()
+                .unwrap()
+                .iter()
+                .position(|v| v == "WASMBinaries")
+                .unwrap();
+            MetadataSection {
+                offset: sections.offsets().unwrap().get(pos),
+                size: sections.sizes().unwrap().get(pos),
+                compression_type: sections.compression_types().unwrap().get(pos),
+            }
         });
-        Ok(FileReaderV2 {
-            reader: self.reader,
+        let mut footer = CachedFooter {
+            post_script,
             schema: schema.into(),
-            projections: self.projections,
-            selection: self.selection,
             grouped_column_metadata_buffers,
             row_group_cnt_n_pointers,
-            wasm_context,
-            shared_dictionary_cache,
-            checksum_type: self
-                .verify_io_unit_checksum
-                .then_some(post_script.checksum_type),
-        })
+            wasm_section,
+            wasm_modules_section,
+            wasm_aot_section,
+            encoding_versions,
+            shared_dictionary_cache: None,
+            family_iounits,
+            key_value_metadata,
+            sort_order,
+        };
+        // The dictionaries may be encoded with the Wasm in the file.
+        let wasm_context = self.wasm_context(&footer);
+        footer.shared_dictionary_cache = shared_dict_table.map(|shared_dict_table| {
+            Arc::new(
+                SharedDictionaryCache::try_new_read_all(
+                    self.reader.clone(),
+                    shared_dict_table,
+                    wasm_context.clone(),
+                )
+                .unwrap(),
+            )
+        });
+        Ok((footer, wasm_context))
+    }
+
+    /// The number of columns per column metadata segment of files written with budgeted
+    /// metadata, see [`crate::file::metadata_segments`].
+    fn read_metadata_segment_columns(
+        &self,
+        optional_sections: Option<OptionalMetadataSections>,
+    ) -> Result<Option<usize>> {
+        let Some(range) = locate_section(optional_sections, "MetadataSegments")? else {
+            return Ok(None);
+        };
+        let mut buf = [0; 4];
+        self.reader.read_exact_at(&mut buf, range.start)?;
+        match u32::from_le_bytes(buf) {
+            0 => Err(Error::ParseError(
+                "Invalid number of columns per metadata segment".to_string(),
+            )),
+            columns_per_segment => Ok(Some(columns_per_segment as usize)),
+        }
+    }
+
+    /// The IO units of all the column families of files written with column families, sorted by
+    /// offset, see [`crate::file::column_families`].
+    fn read_family_iounits(
+        &self,
+        optional_sections: Option<OptionalMetadataSections>,
+    ) -> Result<Vec<Range<u64>>> {
+        let Some(range) = locate_section(optional_sections, "ColumnFamilies")? else {
+            return Ok(vec![]);
+        };
+        let mut buf = vec![0; (range.end - range.start) as usize];
+        self.reader.read_exact_at(&mut buf, range.start)?;
+        let mut iounits = deserialize_column_families(&buf)?
+            .into_iter()
+            .flat_map(|family| family.iounits)
+            .collect::<Vec<_>>();
+        iounits.sort_unstable_by_key(|iounit| iounit.start);
+        Ok(iounits)
+    }
+
+    /// Read the metadata of the columns in `projections` from the segments of a row group,
+    /// reading each needed segment once. `metadata` is the whole metadata if already read.
+    fn read_segmented_column_metadata(
+        &self,
+        rg_meta_fbs: RowGroupMetadata,
+        columns_per_segment: usize,
+        projections: &Projection,
+        schema: &Schema,
+        metadata: Option<&Bytes>,
+        data_size: u64,
+    ) -> Result<Vec<Bytes>> {
+        let segments = rg_meta_fbs
+            .col_metadatas()
+            .ok_or_else(|| Error::ParseError("Column metadatas not found".to_string()))?;
+        let num_columns = schema
+            .fields()
+            .iter()
+            .map(|f| num_physical_columns(f.data_type()))
+            .sum::<usize>();
+        let columns = match projections {
+            Projection::All => (0..num_columns).collect(),
+            Projection::LeafColumnIndexes(projections) => projections.clone(),
+        };
+        let mut loaded_segments: HashMap<usize, Bytes> = HashMap::new();
+        columns
+            .into_iter()
+            .map(|column| {
+                let segment_idx = column / columns_per_segment;
+                if !loaded_segments.contains_key(&segment_idx) {
+                    if segment_idx >= segments.len() {
+                        return Err(Error::IndexOutOfBound(column, num_columns));
+                    }
+                    let section = segments.get(segment_idx);
+                    let start = section.offset() - data_size;
+                    let buf = match metadata {
+                        Some(metadata) => metadata
+                            .slice(start as usize..start as usize + section.size_() as usize),
+                        None => {
+                            let mut buf = vec![0; section.size_() as usize];
+                            self.reader.read_exact_at(&mut buf, section.offset())?;
+                            Bytes::from(buf)
+                        }
+                    };
+                    loaded_segments.insert(
+                        segment_idx,
+                        decompress_data(buf, section.compression_type())?,
+                    );
+                }
+                segment_column(&loaded_segments[&segment_idx], column % columns_per_segment)
+            })
+            .collect()
+    }
+}
+
+impl FileReaderV2Builder<LocalFile> {
+    /// Read the file through a memory mapping, if it can be mapped, instead of with a `pread`
+    /// per read, e.g., for full scans of files on local NVMe. See [`crate::io::mmap`].
+    ///
+    /// # Safety
+    ///
+    /// With `prefer_mmap`, the file must not be modified while read, see
+    /// [`MmapReader::try_new`](crate::io::mmap::MmapReader::try_new).
+    pub unsafe fn prefer_mmap(mut self, prefer_mmap: bool) -> Self {
+        // SAFETY: guaranteed by the caller.
+        self.reader = unsafe { self.reader.prefer_mmap(prefer_mmap) };
+        self
     }
 }
@@ -0,0 +1,180 @@
+//! Caching of chunks across reads of the same file, see
+//! [`FileReaderV2Builder::with_chunk_cache`](super::FileReaderV2Builder::with_chunk_cache).
+//!
+//! Repeated point accesses otherwise read the same chunks again and again. The cache holds the
+//! chunks as read, before their checksums are verified and their EncUnits decrypted, so a hit
+//! only skips the IO: readers sharing a cache each verify and decrypt the chunks with their own
+//! settings and keys. Engines with their own cache plug it in by implementing [`ChunkCache`] over
+//! it.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+
+use bytes::Bytes;
+
+/// Identifies a chunk in a [`ChunkCache`].
+///
+/// `file_id` must change whenever the file does, like the version of a
+/// [`FooterCacheKey`](super::FooterCacheKey).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChunkCacheKey {
+    pub file_id: Arc<str>,
+    /// Offset of the chunk in the file.
+    pub offset: u64,
+}
+
+/// A cache of chunks shared by the readers of one or more files.
+pub trait ChunkCache: Send + Sync + Debug {
+    fn get(&self, key: &ChunkCacheKey) -> Option<Bytes>;
+
+    fn insert(&self, key: ChunkCacheKey, chunk: Bytes);
+}
+
+#[derive(Debug, Default)]
+struct LruEntries {
+    chunks: HashMap<ChunkCacheKey, (Bytes, u64)>,
+    /// Keys of the chunks by the tick of their last access.
+    lru: BTreeMap<u64, ChunkCacheKey>,
+    tick: u64,
+    size: usize,
+}
+
+impl LruEntries {
+    fn touch(&mut self, key: &ChunkCacheKey) -> Option<Bytes> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (chunk, last_access) = self.chunks.get_mut(key)?;
+        let key = self.lru.remove(last_access).unwrap();
+        *last_access = tick;
+        self.lru.insert(tick, key);
+        Some(chunk.clone())
+    }
+}
+
+/// A [`ChunkCache`] evicting the least recently used chunks beyond a budget of bytes.
+#[derive(Debug)]
+pub struct LruChunkCache {
+    capacity: usize,
+    entries: Mutex<LruEntries>,
+}
+
+impl LruChunkCache {
+    /// A cache of at most `capacity` bytes of chunks. Larger chunks are not cached.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::default(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Bytes of the cached chunks.
+    pub fn size(&self) -> usize {
+        self.entries.lock().unwrap().size
+    }
+
+    /// Number of cached chunks.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        *self.entries.lock().unwrap() = LruEntries::default();
+    }
+}
+
+impl ChunkCache for LruChunkCache {
+    fn get(&self, key: &ChunkCacheKey) -> Option<Bytes> {
+        self.entries.lock().unwrap().touch(key)
+    }
+
+    fn insert(&self, key: ChunkCacheKey, chunk: Bytes) {
+        if chunk.len() > self.capacity {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.touch(&key).is_some() {
+            return;
+        }
+        while entries.size + chunk.len() > self.capacity {
+            let (_, evicted) = entries.lru.pop_first().unwrap();
+            let (evicted, _) = entries.chunks.remove(&evicted).unwrap();
+            entries.size -= evicted.len();
+        }
+        let tick = entries.tick;
+        entries.size += chunk.len();
+        entries.lru.insert(tick, key.clone());
+        entries.chunks.insert(key, (chunk, tick));
+    }
+}
+
+/// The chunk cache of a reader, with the id of its file.
+#[derive(Debug, Clone)]
+pub(crate) struct FileChunkCache {
+    pub(crate) cache: Arc<dyn ChunkCache>,
+    pub(crate) file_id: Arc<str>,
+}
+
+impl FileChunkCache {
+    fn key(&self, offset: u64) -> ChunkCacheKey {
+        ChunkCacheKey {
+            file_id: self.file_id.clone(),
+            offset,
+        }
+    }
+
+    pub(crate) fn get(&self, offset: u64) -> Option<Bytes> {
+        self.cache.get(&self.key(offset))
+    }
+
+    pub(crate) fn insert(&self, offset: u64, chunk: Bytes) {
+        self.cache.insert(self.key(offset), chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(offset: u64) -> ChunkCacheKey {
+        ChunkCacheKey {
+            file_id: "file".into(),
+            offset,
+        }
+    }
+
+    #[test]
+    fn test_lru_chunk_cache() {
+        let cache = LruChunkCache::new(10);
+        cache.insert(key(0), Bytes::from_static(b"aaaa"));
+        cache.insert(key(4), Bytes::from_static(b"bbbb"));
+        // The chunk at 0 is now the most recently used, so the one at 4 is evicted.
+        assert_eq!(cache.get(&key(0)).unwrap(), &b"aaaa"[..]);
+        cache.insert(key(8), Bytes::from_static(b"cccc"));
+        assert!(cache.get(&key(4)).is_none());
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.size(), 8);
+        // Chunks over the capacity are not cached.
+        cache.insert(key(12), Bytes::from(vec![0; 11]));
+        assert!(cache.get(&key(12)).is_none());
+        assert_eq!(cache.len(), 2);
+        let other = ChunkCacheKey {
+            file_id: "other".into(),
+            offset: 0,
+        };
+        assert!(cache.get(&other).is_none());
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.size(), 0);
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use arrow::compute::{cast, take};
+use arrow_array::{
+    make_array, new_null_array, ArrayRef, RecordBatch, RecordBatchOptions, UInt32Array,
+};
+use arrow_schema::{DataType, Field, SchemaRef};
+use fff_core::{errors::Result, general_error, nyi_err};
+
+/// The values of a column in the rows the file does not hold: the rows of a column of the target
+/// schema missing from the file and the rows selected past the end of the file, see
+/// [`FileReaderV2Builder::with_default_value`](super::FileReaderV2Builder::with_default_value).
+#[derive(Debug, Clone, Default)]
+pub enum DefaultValueProvider {
+    /// Nulls, only for nullable columns.
+    #[default]
+    Null,
+    /// The zero of the type: 0, false, empty strings and binaries, and empty lists.
+    Zero,
+    /// The value of an array of length 1, cast to the type of the column if needed.
+    Constant(ArrayRef),
+}
+
+impl DefaultValueProvider {
+    /// An array of `len` default values of `field`.
+    pub fn fill(&self, field: &Field, len: usize) -> Result<ArrayRef> {
+        let array = match self {
+            Self::Null => new_null_array(field.data_type(), len),
+            Self::Zero => match field.data_type() {
+                DataType::Struct(_)
+                | DataType::FixedSizeList(..)
+                | DataType::Map(..)
+                | DataType::Union(..)
+                | DataType::Dictionary(..)
+                | DataType::RunEndEncoded(..) => {
+                    return nyi_err!(format!("Zero values of type {}", field.data_type()));
+                }
+                // Null arrays have zeroed buffers, so dropping the validity leaves zeros.
+                data_type => make_array(
+                    new_null_array(data_type, len)
+                        .to_data()
+                        .into_builder()
+                        .nulls(None)
+                        .build()?,
+                ),
+            },
+            Self::Constant(value) => {
+                if value.len() != 1 {
+                    return Err(general_error!(format!(
+                        "The default value of column {} has {} values, expected 1",
+                        field.name(),
+                        value.len()
+                    )));
+                }
+                let value = cast(value, field.data_type())?;
+                take(&value, &UInt32Array::from(vec![0; len]), None)?
+            }
+        };
+        if !field.is_nullable() && array.null_count() > 0 {
+            return Err(general_error!(format!(
+                "Column {} is not nullable and has no default value",
+                field.name()
+            )));
+        }
+        Ok(array)
+    }
+}
+
+/// A batch of `num_rows` rows of default values, by column name, of `schema`.
+pub(crate) fn default_batch(
+    schema: &SchemaRef,
+    default_values: &HashMap<String, DefaultValueProvider>,
+    num_rows: usize,
+) -> Result<RecordBatch> {
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            default_values
+                .get(field.name())
+                .cloned()
+                .unwrap_or_default()
+                .fill(field, num_rows)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RecordBatch::try_new_with_options(
+        schema.clone(),
+        columns,
+        &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+    )?)
+}
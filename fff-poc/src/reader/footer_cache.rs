@@ -0,0 +1,140 @@
+//! Caching of parsed footers across opens of the same file, see
+//! [`FileReaderV2Builder::with_footer_cache`](super::FileReaderV2Builder::with_footer_cache).
+
+use std::{
+    collections::HashMap,
+    ops::Range,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::UNIX_EPOCH,
+};
+
+use arrow_schema::SchemaRef;
+use bytes::Bytes;
+use fff_core::errors::Result;
+use fff_format::File::fff::flatbuf as fb;
+use semver::Version;
+
+use super::{Projection, RowGroupCntNPointer};
+use crate::{
+    dict::shared_dictionary_cache::SharedDictionaryCache,
+    file::{
+        footer::{MetadataSection, PostScript},
+        key_value_metadata::KeyValueMetadata,
+        sort_order::SortColumn,
+    },
+};
+
+/// Identifies the content of a file in a [`FooterCache`].
+///
+/// `version` must change whenever the file does, e.g., the ETag of an object or the modification
+/// time of a local file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FooterCacheKey {
+    pub path: String,
+    pub version: String,
+    pub size: u64,
+}
+
+impl FooterCacheKey {
+    pub fn new(path: impl Into<String>, version: impl Into<String>, size: u64) -> Self {
+        Self {
+            path: path.into(),
+            version: version.into(),
+            size,
+        }
+    }
+
+    /// Key a local file by its path, modification time and size.
+    pub fn try_from_local_path(path: impl AsRef<Path>) -> Result<Self> {
+        let metadata = std::fs::metadata(path.as_ref())?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        Ok(Self::new(
+            path.as_ref().to_string_lossy(),
+            mtime.to_string(),
+            metadata.len(),
+        ))
+    }
+}
+
+/// The parsed and projected metadata of a file. It does not depend on the reader, so it is shared
+/// by all the readers of the file.
+pub(crate) struct CachedFooter {
+    pub(crate) post_script: PostScript,
+    pub(crate) schema: SchemaRef,
+    pub(crate) grouped_column_metadata_buffers: Vec<Vec<Bytes>>,
+    pub(crate) row_group_cnt_n_pointers: Vec<RowGroupCntNPointer>,
+    pub(crate) wasm_section: Option<MetadataSection>,
+    pub(crate) wasm_modules_section: Option<MetadataSection>,
+    pub(crate) wasm_aot_section: Option<MetadataSection>,
+    pub(crate) encoding_versions: Option<HashMap<fb::EncodingType, Version>>,
+    pub(crate) shared_dictionary_cache: Option<Arc<SharedDictionaryCache>>,
+    /// The IO units of all the column families, sorted by offset.
+    pub(crate) family_iounits: Vec<Range<u64>>,
+    pub(crate) key_value_metadata: KeyValueMetadata,
+    pub(crate) sort_order: Vec<SortColumn>,
+}
+
+/// A cache of parsed footers keyed by file and projection, to be shared between readers via `Arc`.
+///
+/// Entries are never evicted by the cache itself, see [`FooterCache::invalidate`] and
+/// [`FooterCache::clear`].
+#[derive(Default)]
+pub struct FooterCache {
+    entries: Mutex<HashMap<(FooterCacheKey, Projection), Arc<CachedFooter>>>,
+}
+
+impl FooterCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn get(
+        &self,
+        key: &FooterCacheKey,
+        projection: &Projection,
+    ) -> Option<Arc<CachedFooter>> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(&(key.clone(), projection.clone()))
+            .cloned()
+    }
+
+    pub(crate) fn insert(
+        &self,
+        key: FooterCacheKey,
+        projection: Projection,
+        footer: Arc<CachedFooter>,
+    ) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert((key, projection), footer);
+    }
+
+    /// Number of cached (file, projection) pairs.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove all the entries of the file at `path`, whatever their version.
+    pub fn invalidate(&self, path: &str) {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|(key, _), _| key.path != path);
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,237 @@
+//! Reading the row groups of several files in the order of a key column, e.g., for datasets split
+//! by time into many files. See [`InterleavedReader`].
+//!
+//! Row groups are placed by the min/max of the key column in the footer, without decoding it: the
+//! statistics of its chunks, or the zone maps of the EncUnit index for fixed-width columns, which
+//! have no chunk statistics, see
+//! [`FileWriterOptionsBuilder::set_write_encunit_index`](crate::options::FileWriterOptionsBuilder::set_write_encunit_index).
+//! Both compare by their bytes. Row groups are output whole, so the rows are only in key order
+//! when the ranges of the row groups do not overlap, see [`InterleavedReader::is_sorted`].
+
+use std::collections::VecDeque;
+
+use arrow_array::RecordBatch;
+use arrow_schema::{DataType, SchemaRef};
+use fff_core::{errors::Result, general_error};
+
+use super::{
+    get_encunit_index, get_metadata_buffer, read_postscript, FileReaderV2, FileReaderV2Builder,
+};
+use crate::{
+    common::statistics::sortable_key_width,
+    encoder::logical::num_physical_columns,
+    file::footer::{Footer, Statistics},
+    io::reader::Reader,
+};
+
+/// A row group of one of the files of an [`InterleavedReader`] and the bounds of its keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowGroupRange {
+    /// Position of the file in the readers given to [`InterleavedReader::try_new`].
+    pub file: usize,
+    pub row_group: usize,
+    /// First row of the row group in its file.
+    pub first_row: u64,
+    pub num_rows: u64,
+    /// Lower bound of the non-null keys, None if they are all null.
+    pub min: Option<Vec<u8>>,
+    /// Upper bound of the non-null keys, None if they are all null or it is unknown, e.g., for a
+    /// truncated max that could not be rounded up.
+    pub max: Option<Vec<u8>>,
+}
+
+/// Widen the bounds of `range` to cover `statistics`. `first` is whether no statistics were
+/// merged yet.
+fn merge_bounds(range: &mut RowGroupRange, statistics: &Statistics, first: &mut bool) {
+    let Some(min) = statistics.min_value() else {
+        return;
+    };
+    if range.min.as_deref().is_none_or(|cur| min < cur) {
+        range.min = Some(min.to_vec());
+    }
+    let max = statistics.max_value();
+    range.max = match &range.max {
+        _ if *first => max.map(<[u8]>::to_vec),
+        Some(cur) => max.map(|max| cur.as_slice().max(max).to_vec()),
+        None => None,
+    };
+    *first = false;
+}
+
+/// The row groups of the file of `reader`, numbered `file`, with the bounds of `key_column`.
+pub(super) fn row_group_ranges<R: Reader>(
+    reader: &R,
+    file: usize,
+    key_column: &str,
+) -> Result<Vec<RowGroupRange>> {
+    let file_size = reader.size()?;
+    let post_script = read_postscript(reader, file_size)?;
+    let owner = get_metadata_buffer(reader, &post_script)?;
+    let footer = Footer::try_new(&owner, file_size as usize, &post_script)?;
+    let schema = footer.schema();
+    let (col_idx, field) = schema
+        .column_with_name(key_column)
+        .ok_or_else(|| general_error!(format!("Key column {key_column} is not in file {file}")))?;
+    let column_index = schema
+        .fields()
+        .iter()
+        .take(col_idx)
+        .map(|f| num_physical_columns(f.data_type()))
+        .sum::<usize>();
+    // Fixed-width columns only have zone maps.
+    let encunit_index = if sortable_key_width(field.data_type()).is_some() {
+        Some(get_encunit_index(reader)?.ok_or_else(|| {
+            general_error!(format!(
+                "File {file} has no EncUnit index for the {} key column {key_column}",
+                field.data_type()
+            ))
+        })?)
+    } else if matches!(
+        field.data_type(),
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Binary | DataType::LargeBinary
+    ) {
+        None
+    } else {
+        return Err(general_error!(format!(
+            "Key column {key_column} of type {} has no statistics",
+            field.data_type()
+        )));
+    };
+    let mut first_row = 0;
+    let mut ranges = vec![];
+    for (row_group, rg_meta) in footer.row_group_metadatas().iter().enumerate() {
+        let mut range = RowGroupRange {
+            file,
+            row_group,
+            first_row,
+            num_rows: rg_meta.row_count as u64,
+            min: None,
+            max: None,
+        };
+        first_row += range.num_rows;
+        let mut first = true;
+        let chunks = rg_meta.column_metadatas[column_index]
+            .column_chunks()
+            .into_iter()
+            .flatten()
+            .enumerate();
+        for (chunk_idx, chunk) in chunks {
+            match &encunit_index {
+                Some(index) => {
+                    let chunk_index = index
+                        .chunk(column_index as u32, row_group as u32, chunk_idx as u32)
+                        .ok_or_else(|| {
+                            general_error!(format!(
+                                "Chunk {chunk_idx} of row group {row_group} of file {file} is \
+                                 not in the EncUnit index"
+                            ))
+                        })?;
+                    for encunit in &chunk_index.encunits {
+                        if let Some(statistics) = &encunit.statistics {
+                            merge_bounds(&mut range, statistics, &mut first);
+                        }
+                    }
+                }
+                None => {
+                    if let Some(statistics) = chunk.statistics() {
+                        merge_bounds(&mut range, &Statistics::from(&statistics), &mut first);
+                    }
+                }
+            }
+        }
+        ranges.push(range);
+    }
+    Ok(ranges)
+}
+
+/// Iterator over the batches of the row groups of several files, in ascending order of the min of
+/// a top-level key column in each row group, see [`crate::reader::interleave`]. Row groups whose
+/// keys are all null come last. Ties keep the order of the files, then of the row groups.
+///
+/// Complements reading the files one after the other, e.g., the files of a
+/// [`DatasetManifest`](crate::dataset::DatasetManifest), when their key ranges interleave.
+pub struct InterleavedReader<R> {
+    readers: Vec<FileReaderV2<R>>,
+    /// In output order.
+    row_groups: Vec<RowGroupRange>,
+    next_row_group: usize,
+    batches: VecDeque<RecordBatch>,
+}
+
+impl<R: Reader + Clone> InterleavedReader<R> {
+    /// Interleave the row groups of the files of `readers` by `key_column`.
+    pub fn try_new(readers: Vec<R>, key_column: &str) -> Result<Self> {
+        Self::try_new_with(readers, key_column, |builder| builder)
+    }
+
+    /// Like [`Self::try_new`], with the reader of each file configured by `configure`, e.g., with
+    /// a projection. The selection of the readers is ignored.
+    pub fn try_new_with(
+        readers: Vec<R>,
+        key_column: &str,
+        configure: impl Fn(FileReaderV2Builder<R>) -> FileReaderV2Builder<R>,
+    ) -> Result<Self> {
+        let mut row_groups = vec![];
+        let mut file_readers: Vec<FileReaderV2<R>> = vec![];
+        for (file, reader) in readers.into_iter().enumerate() {
+            row_groups.extend(row_group_ranges(&reader, file, key_column)?);
+            let file_reader = configure(FileReaderV2Builder::new(reader)).build()?;
+            if let Some(first) = file_readers.first() {
+                if first.schema() != file_reader.schema() {
+                    return Err(general_error!(format!(
+                        "File {file} has another schema than file 0"
+                    )));
+                }
+            }
+            file_readers.push(file_reader);
+        }
+        // Stable, so ties keep the order of the files and row groups.
+        row_groups.sort_by(|a, b| (a.min.is_none(), &a.min).cmp(&(b.min.is_none(), &b.min)));
+        Ok(Self {
+            readers: file_readers,
+            row_groups,
+            next_row_group: 0,
+            batches: VecDeque::new(),
+        })
+    }
+
+    /// The row groups of all files, in output order.
+    pub fn row_groups(&self) -> &[RowGroupRange] {
+        &self.row_groups
+    }
+
+    /// Whether the rows are output in key order, i.e., each row group starts at or after the
+    /// max of the previous one, apart from the row groups with null keys only at the end. Keys
+    /// are assumed to be sorted within each row group.
+    pub fn is_sorted(&self) -> bool {
+        self.row_groups
+            .iter()
+            .filter(|rg| rg.min.is_some())
+            .collect::<Vec<_>>()
+            .windows(2)
+            .all(
+                |pair| matches!((&pair[0].max, &pair[1].min), (Some(max), Some(min)) if max <= min),
+            )
+    }
+
+    /// Schema of the output batches, the one of the readers of the files.
+    pub fn schema(&self) -> Option<SchemaRef> {
+        self.readers.first().map(|reader| reader.schema())
+    }
+}
+
+impl<R: Reader + Clone> Iterator for InterleavedReader<R> {
+    type Item = Result<RecordBatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.batches.is_empty() {
+            let range = self.row_groups.get(self.next_row_group)?;
+            self.next_row_group += 1;
+            match self.readers[range.file].read_row_group(range.row_group) {
+                Ok(batches) => self.batches.extend(batches),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        self.batches.pop_front().map(Ok)
+    }
+}
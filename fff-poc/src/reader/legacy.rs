@@ -3,7 +3,8 @@ use arrow_buffer::MutableBuffer;
 use crate::file::footer::{Footer, PostScript};
 use crate::io::reader::Reader;
 use crate::reader::{
-    get_metadata_buffer, read_file_based_on_footer, read_postscript, Projection, Selection,
+    get_metadata_buffer, read_file_based_on_footer, read_postscript, Projection, ReadContext,
+    Selection,
 };
 use arrow_array::RecordBatch;
 use fff_core::errors::Result;
@@ -39,13 +40,12 @@ impl<R: Reader> FileReader<R> {
             )
         }?;
         read_file_based_on_footer(
-            &mut self.reader,
+            &self.reader,
             footer,
             &Projection::All,
             &Selection::All,
             None,
-            None,
-            None,
+            &ReadContext::default(),
         )
     }
 
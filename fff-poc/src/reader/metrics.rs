@@ -0,0 +1,58 @@
+use std::collections::BTreeMap;
+
+use fff_ude_wasm::WasmUsage;
+use serde::{Deserialize, Serialize};
+
+use crate::decoder::encunit::DecodePath;
+use crate::io::reader::IoMetrics;
+
+/// Metrics of a scan with the projection and selection of a [`super::FileReaderV2`].
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScanMetrics {
+    /// The distinct decode paths of the EncUnits of each projected physical column,
+    /// in the order they are first used. Shared dictionaries are not included.
+    pub decode_paths: Vec<Vec<DecodePath>>,
+    /// Row groups holding selected rows that are decoded.
+    #[serde(default)]
+    pub row_groups_scanned: u64,
+    /// Row groups holding selected rows that are skipped, see
+    /// [`super::FileReaderV2::prune_row_groups`].
+    #[serde(default)]
+    pub row_groups_pruned: u64,
+}
+
+/// Usage of the WASM runtime of a module, see [`fff_ude_wasm::Runtime::usage`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WasmMetrics {
+    pub num_calls: u64,
+    /// 0 unless the runtimes were created with an engine metering fuel.
+    pub fuel_consumed: u64,
+}
+
+impl From<WasmUsage> for WasmMetrics {
+    fn from(usage: WasmUsage) -> Self {
+        Self {
+            num_calls: usage.num_calls,
+            fuel_consumed: usage.fuel_consumed,
+        }
+    }
+}
+
+/// Resources used by a [`super::FileReaderV2`], returned by
+/// [`super::FileReaderV2::resource_report`], for services attributing the cost of scans to
+/// queries or tenants.
+///
+/// The report only holds counters that are the same for every run of a scan: WASM time is
+/// measured in fuel instead of wall time. The serialization is stable, fields are only added
+/// and missing ones deserialize to their defaults.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResourceReport {
+    pub scan: ScanMetrics,
+    /// Reads of the reader, None if it does not count them, see
+    /// [`crate::io::reader::CountingReader`].
+    pub io: Option<IoMetrics>,
+    /// Usage of each WASM module by WASM id. Runtimes shared with other readers, see
+    /// [`super::FileReaderV2Builder::with_existing_runtimes`], include their usage as well.
+    pub wasm: BTreeMap<u32, WasmMetrics>,
+}
@@ -1,25 +1,51 @@
 use crate::{
-    common::{checksum::ChecksumType, ColumnIndexSequence},
-    context::WASMReadingContext,
+    common::{
+        checksum::ChecksumType, physical_type::physical_type, statistics::sortable_key_width,
+        ColumnIndexSequence,
+    },
+    compression::decompress_data,
+    context::{WASMId, WASMReadingContext},
     counter::EncodingCounter,
-    decoder::logical::{create_list_struct_decoder, create_logical_decoder},
+    decoder::{
+        encunit::decode_path,
+        logical::{create_list_struct_decoder, create_logical_decoder, DecodeOptions},
+    },
     dict::shared_dictionary_cache::SharedDictionaryCache,
-    file::footer::{Footer, GroupedColumnMetadata, PostScript},
-    io::reader::Reader,
+    encoder::logical::num_physical_columns,
+    encryption::Decryptor,
+    file::{
+        bloom_filters::{deserialize_bloom_filters, ChunkBloomFilter},
+        column_families::{deserialize_column_families, ColumnFamily},
+        encunit_index::{deserialize_encunit_index, EncUnitIndex},
+        footer::{Footer, GroupedColumnMetadata, PostScript, Statistics},
+        key_value_metadata::KeyValueMetadata,
+        metadata_segments::lookup_schema_index,
+        schema_fingerprint,
+        sort_order::SortColumn,
+        wasm_aot::{deserialize_wasm_aot_artifacts, WasmAotArtifact},
+    },
+    io::{
+        prefetch::PrefetchedChunks,
+        reader::{ReadMetrics, Reader, SnapshotReader},
+        scheduler::IoScheduler,
+    },
+    memory::MemoryPool,
 };
-use arrow::compute::concat;
-use arrow_array::RecordBatch;
+use arrow::compute::{
+    cast, concat, concat_batches, filter, prep_null_mask_filter, take_record_batch,
+};
+use arrow_array::{new_empty_array, Array, ArrayRef, RecordBatch, RecordBatchOptions, UInt64Array};
 use arrow_buffer::MutableBuffer;
 use arrow_schema::{DataType, Field, FieldRef, Schema, SchemaRef};
 use byteorder::{ByteOrder, LittleEndian};
 use bytes::Bytes;
 use fff_core::{
     errors::{Error, Result},
-    non_nest_types,
+    general_error, non_nest_types, nyi_err,
 };
 use fff_format::File::fff::flatbuf::{self as fb, CompressionType};
-use fff_format::{MAGIC, POSTSCRIPT_SIZE};
-use std::sync::Arc;
+use fff_format::{MAGIC, MAGIC_ENCRYPTED_FOOTER, MAJOR_VERSION, MINOR_VERSION, POSTSCRIPT_SIZE};
+use std::{collections::HashMap, ops::Range, sync::Arc};
 
 mod projection;
 pub use projection::Projection;
@@ -30,7 +56,42 @@ mod legacy;
 pub use legacy::FileReader;
 
 mod builder;
-pub use builder::FileReaderV2Builder;
+pub use builder::{FileReaderV2Builder, Preference};
+
+mod footer_cache;
+pub use footer_cache::{FooterCache, FooterCacheKey};
+
+mod chunk_cache;
+pub(crate) use chunk_cache::FileChunkCache;
+pub use chunk_cache::{ChunkCache, ChunkCacheKey, LruChunkCache};
+
+mod metrics;
+pub use crate::decoder::encunit::{decode_support, DecodePath, DecodeSupport};
+pub use crate::decoder::logical::TimestampNormalization;
+pub use metrics::{ResourceReport, ScanMetrics, WasmMetrics};
+
+mod stream;
+pub use stream::FileStream;
+
+mod row_filter;
+pub use row_filter::RowFilter;
+
+mod schema_evolution;
+use schema_evolution::SchemaEvolution;
+
+mod default_value;
+use default_value::default_batch;
+pub use default_value::DefaultValueProvider;
+
+mod wasm_module_cache;
+pub use wasm_module_cache::{WasmModuleCache, WasmResolver};
+
+mod zone_map;
+use zone_map::{may_match, statistics_key};
+pub use zone_map::{prune_encunits, ComparisonOp};
+
+pub mod interleave;
+pub use interleave::{InterleavedReader, RowGroupRange};
 
 /// Utility function to get the max size of a Chunk in this FFF file.
 pub fn get_max_chunk_size<R: Reader + Clone>(reader: R) -> Result<usize> {
@@ -54,6 +115,7 @@ pub fn get_max_chunk_size<R: Reader + Clone>(reader: R) -> Result<usize> {
     Ok(max_size)
 }
 /// Utility function to get the average size of all the IOUnits of a specific column in this FFF file.
+/// 0 if the column has no IOUnits, e.g., in a file without rows.
 pub fn get_avg_io_unit_size<R: Reader + Clone>(reader: R, col_idx: usize) -> Result<usize> {
     let file_size = reader.size()?;
     let post_script = read_postscript(&reader, file_size)?;
@@ -73,15 +135,288 @@ pub fn get_avg_io_unit_size<R: Reader + Clone>(reader: R, col_idx: usize) -> Res
             total_count += 1;
         });
     }
-    Ok(total_size / total_count)
+    Ok(total_size.checked_div(total_count).unwrap_or(0))
+}
+
+/// Utility function to get the min/max statistics of all the IOUnits of a specific column in this
+/// FFF file, in order. None for the IOUnits without statistics.
+pub fn get_column_statistics<R: Reader + Clone>(
+    reader: R,
+    col_idx: usize,
+) -> Result<Vec<Option<Statistics>>> {
+    let file_size = reader.size()?;
+    let post_script = read_postscript(&reader, file_size)?;
+    let owner = get_metadata_buffer(&reader, &post_script)?;
+    let footer = Footer::try_new(&owner, file_size as usize, &post_script)?;
+    let mut statistics = vec![];
+    for rg_meta in footer.row_group_metadatas() {
+        let col_meta = rg_meta
+            .column_metadatas
+            .get(col_idx)
+            .ok_or(Error::IndexOutOfBound(
+                col_idx,
+                rg_meta.column_metadatas.len(),
+            ))?;
+        for chunk in col_meta.column_chunks().into_iter().flatten() {
+            statistics.push(chunk.statistics().map(|x| Statistics::from(&x)));
+        }
+    }
+    Ok(statistics)
+}
+
+/// Utility function to get the number of bytes of this FFF file before the end of its last column
+/// chunk that no chunk refers to, e.g., the data of columns dropped by
+/// [FileWriter::drop_columns](crate::writer::FileWriter::drop_columns). The reader never reads
+/// them, but they still count in the file checksum.
+pub fn get_unreferenced_bytes<R: Reader>(reader: R) -> Result<u64> {
+    let file_size = reader.size()?;
+    let post_script = read_postscript(&reader, file_size)?;
+    let owner = get_metadata_buffer(&reader, &post_script)?;
+    let footer = Footer::try_new(&owner, file_size as usize, &post_script)?;
+    let mut data_end = 0;
+    let mut referenced = 0;
+    for rg_meta in footer.row_group_metadatas() {
+        for col_meta in rg_meta.column_metadatas.iter() {
+            for chunk in col_meta.column_chunks().into_iter().flatten() {
+                data_end = data_end.max(chunk.offset() + chunk.size_() as u64);
+                referenced += chunk.size_() as u64;
+            }
+        }
+    }
+    Ok(data_end - referenced)
+}
+
+/// Utility function to locate the region reserved before the metadata of this FFF file, if any.
+/// See [FileWriterOptions::footer_padding](crate::options::FileWriterOptions::footer_padding).
+pub fn get_reserved_padding<R: Reader>(reader: &R) -> Result<Option<Range<u64>>> {
+    find_optional_section(reader, "ReservedPadding")
+}
+
+/// Utility function to get the WASMId of the binary encoding each top-level column of this FFF
+/// file, by column index. See
+/// [FileWriterOptionsBuilder::set_column_wasm](crate::options::FileWriterOptionsBuilder::set_column_wasm).
+pub fn get_column_wasms<R: Reader>(reader: &R) -> Result<HashMap<usize, WASMId>> {
+    let Some(range) = find_optional_section(reader, "ColumnWASMs")? else {
+        return Ok(HashMap::new());
+    };
+    let mut buf = vec![0; (range.end - range.start) as usize];
+    reader.read_exact_at(&mut buf, range.start)?;
+    if buf.len() % 8 != 0 {
+        return Err(Error::ParseError(format!(
+            "Invalid size of the column WASMs: {}",
+            buf.len()
+        )));
+    }
+    Ok(buf
+        .chunks_exact(8)
+        .map(|pair| {
+            (
+                LittleEndian::read_u32(&pair[..4]) as usize,
+                WASMId(LittleEndian::read_u32(&pair[4..])),
+            )
+        })
+        .collect())
+}
+
+/// Utility function to get the AOT artifacts of the WASM modules of this FFF file. See
+/// [FileWriterOptionsBuilder::write_wasm_aot](crate::options::FileWriterOptionsBuilder::write_wasm_aot).
+pub fn get_wasm_aot_artifacts<R: Reader>(reader: &R) -> Result<Vec<WasmAotArtifact>> {
+    let Some(range) = find_optional_section(reader, "WASMAotArtifacts")? else {
+        return Ok(vec![]);
+    };
+    let mut buf = vec![0; (range.end - range.start) as usize];
+    reader.read_exact_at(&mut buf, range.start)?;
+    deserialize_wasm_aot_artifacts(&buf)
+}
+
+/// Utility function to get the index of the EncUnits of this FFF file, if it was written. See
+/// [FileWriterOptionsBuilder::set_write_encunit_index](crate::options::FileWriterOptionsBuilder::set_write_encunit_index).
+pub fn get_encunit_index<R: Reader>(reader: &R) -> Result<Option<EncUnitIndex>> {
+    let Some(range) = find_optional_section(reader, "EncUnitIndex")? else {
+        return Ok(None);
+    };
+    let mut buf = vec![0; (range.end - range.start) as usize];
+    reader.read_exact_at(&mut buf, range.start)?;
+    deserialize_encunit_index(&buf).map(Some)
+}
+
+/// Utility function to get the bloom filters of the chunks of this FFF file. See
+/// [FileWriterOptionsBuilder::set_bloom_filter_columns](crate::options::FileWriterOptionsBuilder::set_bloom_filter_columns).
+pub fn get_bloom_filters<R: Reader>(reader: &R) -> Result<Vec<ChunkBloomFilter>> {
+    let Some(range) = find_optional_section(reader, "BloomFilters")? else {
+        return Ok(vec![]);
+    };
+    let mut buf = vec![0; (range.end - range.start) as usize];
+    reader.read_exact_at(&mut buf, range.start)?;
+    deserialize_bloom_filters(&buf)
+}
+
+/// Utility function to get the column families of this FFF file and their IO units. See
+/// [FileWriterOptionsBuilder::set_column_families](crate::options::FileWriterOptionsBuilder::set_column_families).
+pub fn get_column_families<R: Reader>(reader: &R) -> Result<Vec<ColumnFamily>> {
+    let Some(range) = find_optional_section(reader, "ColumnFamilies")? else {
+        return Ok(vec![]);
+    };
+    let mut buf = vec![0; (range.end - range.start) as usize];
+    reader.read_exact_at(&mut buf, range.start)?;
+    deserialize_column_families(&buf)
+}
+
+/// Utility function to find the top-level columns named `names` in this FFF file, with the schema
+/// index of files written with
+/// [budgeted metadata](crate::options::FileWriterOptionsBuilder::set_budgeted_metadata), so that
+/// the schema is not parsed. Names are matched by their 64-bit hash, so a name missing from the
+/// schema is found only in the unlikely case of a collision.
+pub fn find_columns<R: Reader>(reader: &R, names: &[&str]) -> Result<Vec<Option<usize>>> {
+    let Some(range) = find_optional_section(reader, "SchemaIndex")? else {
+        return Err(Error::General("The file has no schema index".to_string()));
+    };
+    let mut buf = vec![0; (range.end - range.start) as usize];
+    reader.read_exact_at(&mut buf, range.start)?;
+    names
+        .iter()
+        .map(|name| lookup_schema_index(&buf, name))
+        .collect()
+}
+
+/// Utility function to get the size of each version of this FFF file, oldest first, the last one
+/// being the size of the file. Files appended to with
+/// [footer versioning](crate::options::FileWriterOptionsBuilder::set_footer_versioning) have a
+/// version per append, other files a single one.
+pub fn get_footer_versions<R: Reader + Clone>(reader: R) -> Result<Vec<u64>> {
+    let mut sizes = vec![reader.size()?];
+    loop {
+        let snapshot = SnapshotReader::new(reader.clone(), *sizes.last().unwrap());
+        let Some(range) = find_optional_section(&snapshot, "PreviousVersion")? else {
+            break;
+        };
+        let mut buf = [0; 8];
+        snapshot.read_exact_at(&mut buf, range.start)?;
+        let size = u64::from_le_bytes(buf);
+        if size >= range.start {
+            return Err(Error::ParseError(format!(
+                "Invalid size {size} of the previous version of the file"
+            )));
+        }
+        sizes.push(size);
+    }
+    sizes.reverse();
+    Ok(sizes)
+}
+
+/// The FFF file read by `reader` as of its `version`-th footer, counting from 0, to be read with
+/// [`FileReaderV2Builder`]. See [`get_footer_versions`].
+pub fn open_at_version<R: Reader + Clone>(reader: R, version: usize) -> Result<SnapshotReader<R>> {
+    let sizes = get_footer_versions(reader.clone())?;
+    let size = *sizes
+        .get(version)
+        .ok_or(Error::IndexOutOfBound(version, sizes.len()))?;
+    Ok(SnapshotReader::new(reader, size))
+}
+
+/// Utility function to get the fingerprint of the schema of this FFF file, reading only its
+/// footer. See [`schema_fingerprint`](crate::file::schema_fingerprint::schema_fingerprint).
+pub fn get_schema_fingerprint<R: Reader>(reader: &R) -> Result<u64> {
+    let post_script = read_postscript(reader, reader.size()?)?;
+    let footer_buffer = get_footer_buffer(reader, &post_script)?;
+    let footer_fbs = fb::root_as_footer(&footer_buffer)
+        .map_err(|e| Error::ParseError(format!("Unable to get root as footer: {e:?}")))?;
+    schema_fingerprint::from_fb(&footer_fbs)
+}
+
+/// Whether the FFF files read by `a` and `b` have the same schema, up to metadata, by their
+/// fingerprints.
+pub fn same_schema<R: Reader, S: Reader>(a: &R, b: &S) -> Result<bool> {
+    Ok(get_schema_fingerprint(a)? == get_schema_fingerprint(b)?)
+}
+
+/// Utility function to group the FFF files read by `readers` by schema, as indices into
+/// `readers`. Groups are in the order of their first file, files in the order of `readers`.
+pub fn group_by_schema<R: Reader>(readers: &[R]) -> Result<Vec<Vec<usize>>> {
+    let mut groups: Vec<Vec<usize>> = vec![];
+    let mut group_of_fingerprint = HashMap::new();
+    for (i, reader) in readers.iter().enumerate() {
+        let fingerprint = get_schema_fingerprint(reader)?;
+        let group = *group_of_fingerprint.entry(fingerprint).or_insert_with(|| {
+            groups.push(vec![]);
+            groups.len() - 1
+        });
+        groups[group].push(i);
+    }
+    Ok(groups)
+}
+
+/// Utility function to get the average number of rows of the EncUnits of a specific column in
+/// this FFF file, from its EncUnit index. None if the file has no index.
+pub fn get_avg_encunit_num_rows<R: Reader>(reader: &R, col_idx: usize) -> Result<Option<usize>> {
+    let Some(index) = get_encunit_index(reader)? else {
+        return Ok(None);
+    };
+    let (num_rows, num_encunits) = index
+        .chunks()
+        .iter()
+        .filter(|chunk| chunk.column_index as usize == col_idx)
+        .flat_map(|chunk| &chunk.encunits)
+        .fold((0, 0), |(num_rows, num_encunits), encunit| {
+            (num_rows + encunit.num_rows as usize, num_encunits + 1)
+        });
+    Ok(Some(num_rows.checked_div(num_encunits).unwrap_or(0)))
+}
+
+/// Locate the optional metadata section `name` of this FFF file, if any.
+fn find_optional_section<R: Reader>(reader: &R, name: &str) -> Result<Option<Range<u64>>> {
+    let post_script = read_postscript(reader, reader.size()?)?;
+    let footer_buffer = get_footer_buffer(reader, &post_script)?;
+    let footer_fbs = fb::root_as_footer(&footer_buffer)
+        .map_err(|e| Error::ParseError(format!("Unable to get root as footer: {e:?}")))?;
+    locate_section(footer_fbs.optional_sections(), name)
+}
+
+/// Locate the optional metadata section `name` among the `sections` of a footer, if any.
+pub(crate) fn locate_section(
+    sections: Option<fb::OptionalMetadataSections>,
+    name: &str,
+) -> Result<Option<Range<u64>>> {
+    let Some(sections) = sections else {
+        return Ok(None);
+    };
+    let Some(pos) = sections
+        .names()
+        .and_then(|names| names.iter().position(|x| x == name))
+    else {
+        return Ok(None);
+    };
+    let offset = sections
+        .offsets()
+        .ok_or_else(|| Error::ParseError("Optional section offsets not found".to_string()))?
+        .get(pos);
+    let size = sections
+        .sizes()
+        .ok_or_else(|| Error::ParseError("Optional section sizes not found".to_string()))?
+        .get(pos);
+    Ok(Some(offset..offset + size as u64))
 }
 
+#[derive(Clone)]
 pub(crate) struct RowGroupCntNPointer {
     pub(crate) row_count: u32,
     pub(crate) _offset: u64,
     pub(crate) _size: u32,
 }
 
+/// A dictionary of the shared dictionary table of a file, see
+/// [`FileReaderV2::list_shared_dictionaries`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedDictionaryInfo {
+    /// Index of the dictionary in the table, referenced by the chunks encoded with it.
+    pub index: usize,
+    pub data_type: DataType,
+    /// Number of values.
+    pub len: usize,
+    /// Bytes of the dictionary in the file.
+    pub compressed_size: usize,
+}
+
 pub struct FileReaderV2<R> {
     reader: R,
     schema: SchemaRef,
@@ -92,39 +427,654 @@ pub struct FileReaderV2<R> {
     row_group_cnt_n_pointers: Vec<RowGroupCntNPointer>,
     /// TODO: remove this Option wrapping when removing V1 reader.
     wasm_context: Option<Arc<WASMReadingContext<R>>>,
-    shared_dictionary_cache: Option<SharedDictionaryCache>,
+    shared_dictionary_cache: Option<Arc<SharedDictionaryCache>>,
     /// Whether we verify the IOUnit checksum.
     checksum_type: Option<ChecksumType>,
+    /// Whether we verify the number of rows decoded from each chunk.
+    verify_decoded_length: bool,
+    /// Whether the statistics of the chunks of top-level columns decoded in full are verified.
+    verify_statistics: bool,
+    /// Whether dictionary-encoded chunks of top-level columns are output as `DictionaryArray`s.
+    preserve_dictionary: bool,
+    /// Whether RLE EncUnits of top-level columns are output as `RunArray`s.
+    preserve_runs: bool,
+    /// How timestamps of top-level columns are output.
+    timestamp_normalization: TimestampNormalization,
+    row_filter: Option<RowFilter>,
+    /// Row groups skipped as no row passes the row filter, see [`Self::prune_row_groups`].
+    /// Sorted.
+    pruned_row_groups: Vec<usize>,
+    /// The IO units of all the column families, sorted by offset.
+    family_iounits: Vec<Range<u64>>,
+    /// How the decoded columns are turned into the target schema, if any.
+    schema_evolution: Option<SchemaEvolution>,
+    /// The values of placeholder rows by column name, null for the other columns.
+    default_values: HashMap<String, DefaultValueProvider>,
+    /// Output rows of default values for the selected rows past the end of the file.
+    fill_out_of_range_rows: bool,
+    key_value_metadata: KeyValueMetadata,
+    sort_order: Vec<SortColumn>,
+    /// Decrypts the encrypted chunks, see [`FileReaderV2Builder::with_key_retriever`].
+    decryptor: Option<Arc<Decryptor>>,
+    /// Caches the chunks read, see [`FileReaderV2Builder::with_chunk_cache`].
+    chunk_cache: Option<FileChunkCache>,
+    /// Pool the chunks held by the column decoders are reserved from, see
+    /// [`FileReaderV2Builder::with_memory_pool`].
+    memory_pool: Option<Arc<dyn MemoryPool>>,
+    /// Schedules the reads of full scans, see [`FileReaderV2Builder::with_io_concurrency`].
+    io_scheduler: Option<IoScheduler>,
 }
 
 impl<R: Reader> FileReaderV2<R> {
+    /// The options of the decoders of the physical columns.
+    fn decode_options(&self) -> DecodeOptions<'_> {
+        DecodeOptions {
+            checksum_type: self.checksum_type,
+            verify_decoded_length: self.verify_decoded_length,
+            verify_statistics: self.verify_statistics,
+            preserve_dictionary: self.preserve_dictionary,
+            preserve_runs: self.preserve_runs,
+            timestamp_normalization: self.timestamp_normalization,
+            prefetched: None,
+            decryptor: self.decryptor.as_deref(),
+            chunk_cache: self.chunk_cache.as_ref(),
+            memory_pool: self.memory_pool.as_ref(),
+        }
+    }
+
+    fn read_context(&self) -> ReadContext<'_> {
+        ReadContext {
+            shared_dictionary_cache: self.shared_dictionary_cache.as_deref(),
+            decode_options: self.decode_options(),
+            row_filter: self.row_filter.as_ref(),
+            family_iounits: &self.family_iounits,
+            io_scheduler: self.io_scheduler.as_ref(),
+        }
+    }
+
+    /// The schema of the file, or the target schema if any, see
+    /// [`FileReaderV2Builder::with_target_schema`].
     pub fn schema(&self) -> SchemaRef {
-        self.schema.clone()
+        match &self.schema_evolution {
+            Some(schema_evolution) => schema_evolution.target_schema(),
+            None => self.schema.clone(),
+        }
+    }
+
+    /// The user key-value metadata of the file, see
+    /// [`FileWriter::add_metadata`](crate::writer::FileWriter::add_metadata). Columns are
+    /// identified by their index in the schema of the file, regardless of the projection.
+    pub fn metadata(&self) -> &KeyValueMetadata {
+        &self.key_value_metadata
+    }
+
+    /// The key columns the rows of each row group are sorted by, see
+    /// [`FileWriterOptionsBuilder::sort_by`](crate::options::FileWriterOptionsBuilder::sort_by).
+    /// Empty if the rows are not sorted. Columns are identified by their index in the schema of
+    /// the file, regardless of the projection.
+    pub fn sort_order(&self) -> &[SortColumn] {
+        &self.sort_order
+    }
+
+    /// The row groups that may hold rows passing `predicate`, from the min/max of its column in
+    /// each row group, see [`RowGroupRange`]. Only filters created by [`RowFilter::compare`] or
+    /// [`RowFilter::eq`] rule out row groups. This is most effective when the rows are sorted by
+    /// the column, see [`Self::sort_order`], and is then applied to the row filter of the reader
+    /// automatically.
+    pub fn prune_row_groups(&self, predicate: &RowFilter) -> Result<Vec<usize>> {
+        let all_row_groups = (0..self.row_group_cnt_n_pointers.len()).collect();
+        let Some((op, value)) = predicate.comparison() else {
+            return Ok(all_row_groups);
+        };
+        let field = self
+            .schema
+            .fields()
+            .get(predicate.column())
+            .ok_or(Error::IndexOutOfBound(
+                predicate.column(),
+                self.schema.fields().len(),
+            ))?;
+        if value.is_null(0) {
+            // Comparisons with null never hold.
+            return Ok(vec![]);
+        }
+        let Some(key) = statistics_key(value.as_ref(), field.data_type())? else {
+            return Ok(all_row_groups);
+        };
+        // Fixed-width columns only have bounds in the zone maps of the EncUnit index.
+        if sortable_key_width(field.data_type()).is_some()
+            && find_optional_section(&self.reader, "EncUnitIndex")?.is_none()
+        {
+            return Ok(all_row_groups);
+        }
+        Ok(interleave::row_group_ranges(&self.reader, 0, field.name())?
+            .into_iter()
+            // Row groups without known bounds are kept.
+            .filter(|range| {
+                range.min.is_none()
+                    || may_match(range.min.as_deref(), range.max.as_deref(), op, &key)
+            })
+            .map(|range| range.row_group)
+            .collect())
     }
 
     pub fn read_file(&mut self) -> Result<Vec<RecordBatch>> {
-        let footer = Footer::try_new_with_projection(
-            &self.row_group_cnt_n_pointers,
-            self.grouped_column_metadata_buffers
+        let selection = std::mem::take(&mut self.selection);
+        let result = self.read_selection(&selection);
+        self.selection = selection;
+        result
+    }
+
+    /// Read several selections in one pass, e.g., the rows probed by each partition of a join.
+    ///
+    /// The rows of all selections are gathered together, so that chunks needed by several
+    /// selections are fetched and decoded once. The output of each selection is a single batch
+    /// with the rows in the requested order, or no batch if it selects no rows. The selection of
+    /// the reader is ignored.
+    pub fn read_multi(&mut self, selections: &[Selection]) -> Result<Vec<Vec<RecordBatch>>> {
+        if self.row_filter.is_some() {
+            return nyi_err!("Row filters with multiple selections");
+        }
+        let num_rows = self.num_rows();
+        for selection in selections.iter().filter(|_| !self.fill_out_of_range_rows) {
+            if let Selection::RowIndexes(row_indexes) = selection {
+                if let Some(&row) = row_indexes.iter().find(|&&row| row >= num_rows) {
+                    return Err(Error::IndexOutOfBound(row as usize, num_rows as usize));
+                }
+            }
+        }
+        let union = if selections.iter().any(|s| matches!(s, Selection::All)) {
+            Selection::All
+        } else {
+            let mut union = selections
                 .iter()
-                .map(|c_buffers| {
-                    c_buffers
-                        .iter()
-                        .map(|c_buffer| c_buffer.as_ref())
-                        .collect::<Vec<_>>()
+                .flat_map(|s| match s {
+                    Selection::RowIndexes(row_indexes) => row_indexes.as_slice(),
+                    Selection::All => &[],
                 })
+                .copied()
+                .filter(|&row| row < num_rows)
+                .collect::<Vec<_>>();
+            union.sort_unstable();
+            union.dedup();
+            Selection::RowIndexes(union)
+        };
+        let batches = match &union {
+            Selection::RowIndexes(row_indexes) if row_indexes.is_empty() => vec![],
+            _ => self.read_selection(&union)?,
+        };
+        let schema = match batches.first() {
+            Some(first) => first.schema(),
+            None if self.fill_out_of_range_rows => self.output_schema(),
+            None => return Ok(vec![vec![]; selections.len()]),
+        };
+        let mut gathered = concat_batches(&schema, &batches)?;
+        // Rows past the end of the file are taken from a row of default values after the others.
+        let placeholder = gathered.num_rows() as u64;
+        if self.fill_out_of_range_rows {
+            let defaults = default_batch(&schema, &self.default_values, 1)?;
+            gathered = concat_batches(&schema, [&gathered, &defaults])?;
+        }
+        selections
+            .iter()
+            .map(|selection| match (selection, &union) {
+                (Selection::All, _) => Ok(vec![gathered.slice(0, placeholder as usize)]),
+                (Selection::RowIndexes(row_indexes), _) if row_indexes.is_empty() => Ok(vec![]),
+                (Selection::RowIndexes(row_indexes), Selection::All) => {
+                    let positions = row_indexes
+                        .iter()
+                        .map(|&row| row.min(placeholder))
+                        .collect::<Vec<_>>();
+                    Ok(vec![take_record_batch(
+                        &gathered,
+                        &UInt64Array::from(positions),
+                    )?])
+                }
+                (Selection::RowIndexes(row_indexes), Selection::RowIndexes(union)) => {
+                    // Positions of the rows in the gathered batch.
+                    let positions = row_indexes
+                        .iter()
+                        .map(|row| {
+                            union
+                                .binary_search(row)
+                                .map_or(placeholder, |position| position as u64)
+                        })
+                        .collect::<Vec<_>>();
+                    Ok(vec![take_record_batch(
+                        &gathered,
+                        &UInt64Array::from(positions),
+                    )?])
+                }
+            })
+            .collect()
+    }
+
+    fn read_selection(&mut self, selection: &Selection) -> Result<Vec<RecordBatch>> {
+        if let Selection::RowIndexes(row_indexes) = selection {
+            let num_rows = self.num_rows();
+            if let Some(&row) = row_indexes.iter().find(|&&row| row >= num_rows) {
+                if self.fill_out_of_range_rows {
+                    return self.read_with_out_of_range_rows(row_indexes, num_rows);
+                }
+                return Err(Error::IndexOutOfBound(row as usize, num_rows as usize));
+            }
+        }
+        self.read_rows_in_file(selection)
+    }
+
+    /// Read `row_indexes`, with a placeholder row of default values for each row past the end
+    /// of the file, in the requested order.
+    fn read_with_out_of_range_rows(
+        &mut self,
+        row_indexes: &[u64],
+        num_rows: u64,
+    ) -> Result<Vec<RecordBatch>> {
+        let in_range = row_indexes
+            .iter()
+            .copied()
+            .filter(|&row| row < num_rows)
+            .collect::<Vec<_>>();
+        let batches = if in_range.is_empty() {
+            vec![]
+        } else {
+            self.read_rows_in_file(&Selection::RowIndexes(in_range))?
+        };
+        let schema = batches
+            .first()
+            .map_or_else(|| self.output_schema(), |batch| batch.schema());
+        let defaults = default_batch(&schema, &self.default_values, 1)?;
+        let gathered = concat_batches(&schema, batches.iter().chain([&defaults]))?;
+        let placeholder = gathered.num_rows() as u64 - 1;
+        let mut next = 0;
+        let positions = row_indexes
+            .iter()
+            .map(|&row| {
+                if row >= num_rows {
+                    return placeholder;
+                }
+                next += 1;
+                next - 1
+            })
+            .collect::<Vec<_>>();
+        Ok(vec![take_record_batch(
+            &gathered,
+            &UInt64Array::from(positions),
+        )?])
+    }
+
+    fn read_rows_in_file(&mut self, selection: &Selection) -> Result<Vec<RecordBatch>> {
+        let (row_groups, selection) = self.unpruned_row_groups(selection);
+        let footer = projected_footer(
+            &self.row_group_cnt_n_pointers,
+            &self.grouped_column_metadata_buffers,
+            &row_groups,
+            self.schema.clone(),
+        )?;
+        read_file_based_on_footer(
+            &self.reader,
+            footer,
+            &self.projections,
+            &selection,
+            self.wasm_context.clone(),
+            &self.read_context(),
+        )
+        .and_then(|batches| self.evolve_schema(batches))
+    }
+
+    /// The row groups that are not pruned, and `selection` in their rows. The selected rows of
+    /// the pruned row groups are dropped.
+    fn unpruned_row_groups(&self, selection: &Selection) -> (Vec<usize>, Selection) {
+        let num_row_groups = self.row_group_cnt_n_pointers.len();
+        let row_groups = (0..num_row_groups)
+            .filter(|rg_idx| self.pruned_row_groups.binary_search(rg_idx).is_err())
+            .collect::<Vec<_>>();
+        let Selection::RowIndexes(row_indexes) = selection else {
+            return (row_groups, Selection::All);
+        };
+        if self.pruned_row_groups.is_empty() {
+            return (row_groups, selection.clone());
+        }
+        // First row of each row group in the file, and without the pruned row groups if kept.
+        let mut starts = vec![];
+        let (mut start, mut unpruned_start) = (0, 0);
+        for (rg_idx, rg) in self.row_group_cnt_n_pointers.iter().enumerate() {
+            let pruned = self.pruned_row_groups.binary_search(&rg_idx).is_ok();
+            starts.push((start, (!pruned).then_some(unpruned_start)));
+            start += rg.row_count as u64;
+            if !pruned {
+                unpruned_start += rg.row_count as u64;
+            }
+        }
+        let row_indexes = row_indexes
+            .iter()
+            .filter_map(|&row| {
+                let (start, unpruned_start) =
+                    starts[starts.partition_point(|(start, _)| *start <= row).max(1) - 1];
+                unpruned_start.map(|unpruned_start| row - start + unpruned_start)
+            })
+            .collect();
+        (row_groups, Selection::RowIndexes(row_indexes))
+    }
+
+    fn num_rows(&self) -> u64 {
+        self.row_group_cnt_n_pointers
+            .iter()
+            .map(|rg| rg.row_count as u64)
+            .sum()
+    }
+
+    /// Schema of the output batches, apart from dictionary-encoded columns when preserved.
+    pub(crate) fn output_schema(&self) -> SchemaRef {
+        match (&self.schema_evolution, &self.projections) {
+            // Batches are already of the target schema.
+            (Some(_), _) => self.schema(),
+            (None, projections) => {
+                let schema = match projections {
+                    Projection::All => self.schema(),
+                    Projection::LeafColumnIndexes(indices) => {
+                        self.schema().project(indices).unwrap().into()
+                    }
+                };
+                Arc::new(Schema::new_with_metadata(
+                    schema
+                        .fields()
+                        .iter()
+                        .map(|field| {
+                            let data_type =
+                                self.timestamp_normalization.output_type(field.data_type());
+                            field.as_ref().clone().with_data_type(data_type)
+                        })
+                        .collect::<Vec<_>>(),
+                    schema.metadata().clone(),
+                ))
+            }
+        }
+    }
+
+    /// Turn the decoded batches into batches of the target schema, if any.
+    fn evolve_schema(&self, batches: Vec<RecordBatch>) -> Result<Vec<RecordBatch>> {
+        match &self.schema_evolution {
+            Some(schema_evolution) => batches
+                .iter()
+                .map(|batch| schema_evolution.apply(batch))
                 .collect(),
+            None => Ok(batches),
+        }
+    }
+
+    /// Like `read_file`, but return the arrays of each projected column instead of batches,
+    /// e.g., for consumers working on chunked columns like Polars or pyarrow.
+    /// The arrays of a column are not concatenated.
+    pub fn read_file_chunked(&mut self) -> Result<Vec<Vec<ArrayRef>>> {
+        let batches = self.read_file()?;
+        let num_columns = batches.first().map_or(0, RecordBatch::num_columns);
+        Ok((0..num_columns)
+            .map(|i| {
+                batches
+                    .iter()
+                    .map(|batch| batch.column(i).clone())
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Iterate over the batches of the file, decoding one row group at a time.
+    pub fn into_stream(self) -> FileStream<R> {
+        FileStream::new(self)
+    }
+
+    /// Decode a single row group, ignoring the selection.
+    pub(crate) fn read_row_group(&mut self, rg_idx: usize) -> Result<Vec<RecordBatch>> {
+        if self.pruned_row_groups.binary_search(&rg_idx).is_ok() {
+            return Ok(vec![]);
+        }
+        let footer = projected_footer(
+            &self.row_group_cnt_n_pointers,
+            &self.grouped_column_metadata_buffers,
+            &[rg_idx],
             self.schema.clone(),
         )?;
         read_file_based_on_footer(
-            &mut self.reader,
+            &self.reader,
             footer,
             &self.projections,
-            &self.selection,
+            &Selection::All,
             self.wasm_context.clone(),
-            self.shared_dictionary_cache.as_ref(),
-            self.checksum_type,
+            &self.read_context(),
         )
+        .and_then(|batches| self.evolve_schema(batches))
+    }
+
+    /// Report how each projected column is decoded by `read_file`, from the metadata only.
+    /// Useful to make sure a benchmark exercises the built-in or the WASM path.
+    pub fn scan_metrics(&self) -> Result<ScanMetrics> {
+        let all_row_groups = (0..self.row_group_cnt_n_pointers.len()).collect::<Vec<_>>();
+        let (row_groups, selection) = self.unpruned_row_groups(&self.selection);
+        let [all_footer, footer] = [&all_row_groups, &row_groups].map(|row_groups| {
+            projected_footer(
+                &self.row_group_cnt_n_pointers,
+                &self.grouped_column_metadata_buffers,
+                row_groups,
+                self.schema.clone(),
+            )
+        });
+        let (all_footer, footer) = (all_footer?, footer?);
+        let selected_rg_metas = process_selection(&selection, footer.row_group_metadatas());
+        let mut metrics = ScanMetrics {
+            row_groups_scanned: selected_rg_metas.len() as u64,
+            row_groups_pruned: (process_selection(
+                &self.selection,
+                all_footer.row_group_metadatas(),
+            )
+            .len()
+                - selected_rg_metas.len()) as u64,
+            ..Default::default()
+        };
+        for (rg_meta, _) in selected_rg_metas {
+            metrics
+                .decode_paths
+                .resize(rg_meta.column_metadatas.len(), vec![]);
+            for (column_meta, paths) in rg_meta
+                .column_metadatas
+                .iter()
+                .zip(metrics.decode_paths.iter_mut())
+            {
+                for chunk in column_meta.column_chunks().into_iter().flatten() {
+                    for encunit in chunk.encunits().into_iter().flatten() {
+                        let encoding = encunit.encoding().ok_or_else(|| {
+                            Error::ParseError("EncUnit without encoding".to_string())
+                        })?;
+                        let path = decode_path(encoding, self.wasm_context.as_deref())?;
+                        if !paths.contains(&path) {
+                            paths.push(path);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(metrics)
+    }
+
+    /// The WASM modules decoding EncUnits of the projected columns that the reader cannot obtain,
+    /// see [`WASMReadingContext::is_available`], sorted by WASMId with their content hash if the
+    /// file records it. Checked when the reader is built, so that scans do not fail on the first
+    /// EncUnit needing a missing module.
+    pub fn missing_wasm_modules(&self) -> Result<Vec<(WASMId, Option<u64>)>> {
+        let Some(wasm_context) = self.wasm_context.as_deref() else {
+            return Ok(vec![]);
+        };
+        let row_groups = (0..self.row_group_cnt_n_pointers.len()).collect::<Vec<_>>();
+        let footer = projected_footer(
+            &self.row_group_cnt_n_pointers,
+            &self.grouped_column_metadata_buffers,
+            &row_groups,
+            self.schema.clone(),
+        )?;
+        let mut wasm_ids = vec![];
+        for rg_meta in footer.row_group_metadatas() {
+            for column_meta in &rg_meta.column_metadatas {
+                for chunk in column_meta.column_chunks().into_iter().flatten() {
+                    for encunit in chunk.encunits().into_iter().flatten() {
+                        let encoding = encunit.encoding().ok_or_else(|| {
+                            Error::ParseError("EncUnit without encoding".to_string())
+                        })?;
+                        if let DecodePath::EmbeddedWasm(wasm_id)
+                        | DecodePath::ExternalRuntime(wasm_id) =
+                            decode_path(encoding, Some(wasm_context))?
+                        {
+                            if !wasm_ids.contains(&wasm_id) {
+                                wasm_ids.push(wasm_id);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        wasm_ids.sort_by_key(|wasm_id| wasm_id.0);
+        let mut missing = vec![];
+        for wasm_id in wasm_ids {
+            if !wasm_context.is_available(wasm_id)? {
+                missing.push((wasm_id, wasm_context.module_hash(wasm_id)?));
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Estimate the size in bytes of the arrays decoded for the selected rows of `column`, the
+    /// index of a flat column of the file schema, from the metadata only, e.g., to admit a query
+    /// before issuing any IO. Fixed-width values take their width per row, variable-width values
+    /// the size of their offsets plus their encoded size per row, and validity one bit per row
+    /// of the chunks that may hold nulls. The shared dictionaries referenced are counted once,
+    /// decoded. Row groups pruned by the row filter are not counted.
+    pub fn estimate_decoded_size(&self, column: usize, selection: &Selection) -> Result<u64> {
+        let field = self
+            .schema
+            .fields()
+            .get(column)
+            .ok_or_else(|| Error::IndexOutOfBound(column, self.schema.fields().len()))?;
+        if !matches!(field.data_type(), non_nest_types!()) {
+            return nyi_err!(format!(
+                "Estimating the decoded size of {} columns",
+                field.data_type()
+            ));
+        }
+        let leaf_column: usize = self.schema.fields()[..column]
+            .iter()
+            .map(|f| num_physical_columns(f.data_type()))
+            .sum();
+        let position = match &self.projections {
+            Projection::All => leaf_column,
+            Projection::LeafColumnIndexes(columns) => columns
+                .iter()
+                .position(|&c| c == leaf_column)
+                .ok_or_else(|| general_error!(format!("Column {column} is not projected")))?,
+        };
+        let (row_groups, selection) = self.unpruned_row_groups(selection);
+        let footer = projected_footer(
+            &self.row_group_cnt_n_pointers,
+            &self.grouped_column_metadata_buffers,
+            &row_groups,
+            self.schema.clone(),
+        )?;
+        let (offset_width, fixed_width) = match field.data_type() {
+            DataType::Utf8 | DataType::Binary => (4, None),
+            DataType::LargeUtf8 | DataType::LargeBinary => (8, None),
+            DataType::Utf8View | DataType::BinaryView => (16, None),
+            data_type => (0, data_type.primitive_width()),
+        };
+        let mut size = 0;
+        let mut shared_dicts = vec![];
+        for (rg_meta, rg_selection) in process_selection(&selection, footer.row_group_metadatas()) {
+            let num_rows = match &rg_selection {
+                Selection::All => rg_meta.row_count as u64,
+                Selection::RowIndexes(rows) => rows.len() as u64,
+            };
+            let chunks = rg_meta.column_metadatas[position]
+                .column_chunks()
+                .ok_or_else(|| Error::ParseError("Column chunks not found".to_string()))?;
+            size += match fixed_width {
+                Some(width) => num_rows * width as u64,
+                // Booleans are bit-packed.
+                None if offset_width == 0 => num_rows.div_ceil(8),
+                None => {
+                    let encoded_size: u64 = chunks.iter().map(|chunk| chunk.size_() as u64).sum();
+                    let encoded_rows: u64 = chunks.iter().map(|chunk| chunk.num_rows()).sum();
+                    num_rows * offset_width + encoded_size * num_rows / encoded_rows.max(1)
+                }
+            };
+            // Chunks without nulls are decoded without validity.
+            if chunks.iter().any(|chunk| chunk.null_count() != Some(0)) {
+                size += num_rows.div_ceil(8);
+            }
+            for chunk in chunks.iter() {
+                if let Some(dict) = chunk.encoding_as_shared_dictionary() {
+                    if !shared_dicts.contains(&dict.shared_dictionary_idx()) {
+                        shared_dicts.push(dict.shared_dictionary_idx());
+                    }
+                }
+            }
+        }
+        if let Some(cache) = self.shared_dictionary_cache.as_deref() {
+            size += shared_dicts
+                .into_iter()
+                .filter_map(|idx| cache.get_dict(idx as usize))
+                .map(|dict| dict.get_array_memory_size() as u64)
+                .sum::<u64>();
+        }
+        Ok(size)
+    }
+
+    /// The requests issued to the remote store so far, None if the reader does not count them,
+    /// see [`ObjectStoreReadAt`](crate::io::reader::ObjectStoreReadAt). Unlike the
+    /// [`ResourceReport`], they include retries and wall time, which vary across runs.
+    pub fn read_metrics(&self) -> Option<ReadMetrics> {
+        self.reader.read_metrics()
+    }
+
+    /// Report the resources used by this reader so far, typically after the scan.
+    pub fn resource_report(&self) -> Result<ResourceReport> {
+        Ok(ResourceReport {
+            scan: self.scan_metrics()?,
+            io: self.reader.io_metrics(),
+            wasm: self
+                .wasm_context
+                .iter()
+                .flat_map(|context| context.loaded_runtimes())
+                .map(|(wasm_id, rt)| (wasm_id.0, rt.usage().into()))
+                .collect(),
+        })
+    }
+
+    /// The shared dictionaries of the file, by index. Dictionaries without values, e.g., those
+    /// whose columns were found to share another dictionary, are skipped.
+    pub fn list_shared_dictionaries(&self) -> Vec<SharedDictionaryInfo> {
+        let Some(cache) = self.shared_dictionary_cache.as_deref() else {
+            return vec![];
+        };
+        (0..cache.num_dicts())
+            .filter_map(|index| {
+                let dict = cache.get_dict(index)?;
+                Some(SharedDictionaryInfo {
+                    index,
+                    data_type: dict.data_type().clone(),
+                    len: dict.len(),
+                    compressed_size: cache.get_dict_size(index).unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+
+    /// The values of the shared dictionary `index`, without decoding any data chunk. Shared
+    /// dictionaries are read when the reader is built, so this does no IO.
+    pub fn read_shared_dictionary(&self, index: usize) -> Result<ArrayRef> {
+        let cache = self.shared_dictionary_cache.as_deref();
+        let num_dicts = cache.map_or(0, SharedDictionaryCache::num_dicts);
+        if index >= num_dicts {
+            return Err(Error::IndexOutOfBound(index, num_dicts));
+        }
+        cache
+            .and_then(|cache| cache.get_dict(index))
+            .ok_or_else(|| general_error!(format!("Shared dictionary {index} has no values")))
     }
 
     #[allow(clippy::type_complexity)]
@@ -144,11 +1094,107 @@ impl<R: Reader> FileReaderV2<R> {
                 .collect(),
             self.schema.clone(),
         )?;
-        get_shared_dict_size_based_on_footer(footer, self.shared_dictionary_cache.as_ref().unwrap())
+        get_shared_dict_size_based_on_footer(
+            footer,
+            self.shared_dictionary_cache.as_deref().unwrap(),
+        )
+    }
+
+    /// Point lookup of the rows `row_ids` of the file, as a single batch of the columns of
+    /// `projection`, top-level columns the reader was built with. The row ids are sorted and
+    /// deduplicated, and the batch holds the rows in ascending order. Only the EncUnits covering
+    /// the rows are read for flat columns and structs of them, the row groups of the rows are
+    /// decoded for lists. The row filter and the pruned row groups of the reader are ignored.
+    pub fn read_rows(&self, projection: &Projection, row_ids: &[u64]) -> Result<RecordBatch> {
+        if self.schema_evolution.is_some() {
+            return nyi_err!("Point lookups with a target schema");
+        }
+        let num_rows = self.num_rows();
+        let mut row_ids = row_ids.to_vec();
+        row_ids.sort_unstable();
+        row_ids.dedup();
+        if let Some(&row) = row_ids.last().filter(|&&row| row >= num_rows) {
+            return Err(Error::IndexOutOfBound(row as usize, num_rows as usize));
+        }
+        let fields = self.schema.fields();
+        let built_columns = match &self.projections {
+            Projection::All => (0..fields.len()).collect(),
+            Projection::LeafColumnIndexes(columns) => columns.clone(),
+        };
+        let columns = match projection {
+            Projection::All => built_columns.clone(),
+            Projection::LeafColumnIndexes(columns) => columns.clone(),
+        };
+        // Each column, and the position of its first column metadata in each row group.
+        let columns = columns
+            .into_iter()
+            .map(|column| {
+                let position = built_columns
+                    .iter()
+                    .position(|&c| c == column)
+                    .ok_or_else(|| general_error!(format!("Column {column} is not projected")))?;
+                let first_meta = built_columns[..position]
+                    .iter()
+                    .map(|&c| num_physical_columns(fields[c].data_type()))
+                    .sum::<usize>();
+                Ok((&fields[column], first_meta as u32))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let row_groups = (0..self.row_group_cnt_n_pointers.len()).collect::<Vec<_>>();
+        let footer = projected_footer(
+            &self.row_group_cnt_n_pointers,
+            &self.grouped_column_metadata_buffers,
+            &row_groups,
+            self.schema.clone(),
+        )?;
+        let num_selected = row_ids.len();
+        let selection = Selection::RowIndexes(row_ids);
+        let mut arrays = vec![vec![]; columns.len()];
+        for (rg_meta, rg_selection) in process_selection(&selection, footer.row_group_metadatas()) {
+            let Selection::RowIndexes(rows) = rg_selection else {
+                unreachable!()
+            };
+            for ((field, first_meta), arrays) in columns.iter().zip(arrays.iter_mut()) {
+                let mut decoder = create_logical_decoder(
+                    &self.reader,
+                    Arc::clone(field),
+                    &rg_meta.column_metadatas,
+                    &mut ColumnIndexSequence::new_start_from(*first_meta),
+                    self.wasm_context.clone(),
+                    self.shared_dictionary_cache.as_deref().unwrap(),
+                    &self.decode_options(),
+                )?;
+                arrays.push(decoder.take_rows(&rows)?);
+            }
+        }
+        let (fields, columns): (Vec<_>, Vec<_>) = columns
+            .into_iter()
+            .zip(arrays)
+            .map(|((field, _), arrays)| {
+                let array = match arrays.as_slice() {
+                    [] => new_empty_array(
+                        &self.timestamp_normalization.output_type(field.data_type()),
+                    ),
+                    [array] => array.clone(),
+                    arrays => concat(&arrays.iter().map(|a| a.as_ref()).collect::<Vec<_>>())?,
+                };
+                let field =
+                    Field::new(field.name(), array.data_type().clone(), field.is_nullable());
+                Ok((field, array))
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+        Ok(RecordBatch::try_new_with_options(
+            Arc::new(Schema::new(fields)),
+            columns,
+            &RecordBatchOptions::new().with_row_count(Some(num_selected)),
+        )?)
     }
 
     /// Access single row id from a leaf column from potentially nested data
     /// Right now it should only work for List of Struct of Primitives to test the pushdown effects.
+    /// See [`Self::read_rows`] for other columns.
     pub fn point_access_list_struct(
         &mut self,
         col_leaf_id: u32,
@@ -175,81 +1221,297 @@ impl<R: Reader> FileReaderV2<R> {
             col_field,
             row_id,
             self.wasm_context.clone(),
-            self.shared_dictionary_cache.as_ref(),
+            self.shared_dictionary_cache.as_deref(),
         )
     }
 }
 
-fn get_metadata_buffer<R: Reader>(reader: &R, post_script: &PostScript) -> Result<MutableBuffer> {
-    if post_script.compression != CompressionType::Uncompressed {
-        return Err(Error::General("Compression type not supported".to_string()));
+/// The footer of the row groups `row_groups`, of the projected column metadata.
+fn projected_footer<'a>(
+    row_group_cnt_n_pointers: &[RowGroupCntNPointer],
+    grouped_column_metadata_buffers: &'a [Vec<Bytes>],
+    row_groups: &[usize],
+    schema: SchemaRef,
+) -> Result<Footer<'a>> {
+    Footer::try_new_with_projection(
+        &row_groups
+            .iter()
+            .map(|&rg_idx| row_group_cnt_n_pointers[rg_idx].clone())
+            .collect::<Vec<_>>(),
+        row_groups
+            .iter()
+            .map(|&rg_idx| {
+                grouped_column_metadata_buffers[rg_idx]
+                    .iter()
+                    .map(|c_buffer| c_buffer.as_ref())
+                    .collect::<Vec<_>>()
+            })
+            .collect(),
+        schema,
+    )
+}
+
+/// Read the footer alone, without the column metadata before it if the metadata is uncompressed.
+pub(crate) fn get_footer_buffer<R: Reader>(
+    reader: &R,
+    post_script: &PostScript,
+) -> Result<MutableBuffer> {
+    let footer_size = post_script.footer_size as usize;
+    if post_script.compression == CompressionType::Uncompressed && !post_script.encrypted_footer {
+        let mut buffer = MutableBuffer::from_len_zeroed(footer_size);
+        reader.read_exact_at(
+            buffer.as_slice_mut(),
+            reader.size()? - POSTSCRIPT_SIZE - footer_size as u64,
+        )?;
+        return Ok(buffer);
     }
+    let metadata = get_metadata_buffer(reader, post_script)?;
+    Ok(MutableBuffer::from(
+        metadata[metadata.len() - footer_size..].to_vec(),
+    ))
+}
+
+/// The column metadata and footer of the file, decompressed. The footer is at the end of the
+/// buffer, and column metadata offsets minus the offset of the metadata in the file index it.
+pub(crate) fn get_metadata_buffer<R: Reader>(
+    reader: &R,
+    post_script: &PostScript,
+) -> Result<MutableBuffer> {
+    read_metadata(reader, post_script, None)
+}
+
+/// Read the column metadata and footer, decrypting them with `decryptor` if they are encrypted.
+pub(crate) fn read_metadata<R: Reader>(
+    reader: &R,
+    post_script: &PostScript,
+    decryptor: Option<&Decryptor>,
+) -> Result<MutableBuffer> {
     let mut buffer = MutableBuffer::from_len_zeroed(post_script.metadata_size as usize);
     reader.read_exact_at(
         buffer.as_slice_mut(),
         reader.size()? - POSTSCRIPT_SIZE - post_script.metadata_size as u64,
     )?;
-    Ok(buffer)
+    if post_script.encrypted_footer {
+        let decryptor = decryptor.ok_or_else(|| {
+            general_error!("The footer is encrypted, read it with a key retriever")
+        })?;
+        buffer = MutableBuffer::from(decryptor.decrypt_metadata(buffer.as_slice())?);
+    }
+    if post_script.compression == CompressionType::Uncompressed {
+        return Ok(buffer);
+    }
+    let metadata = decompress_data(
+        Bytes::from(buffer.as_slice().to_vec()),
+        post_script.compression,
+    )?;
+    Ok(MutableBuffer::from(metadata.to_vec()))
+}
+
+/// What [`read_file_based_on_footer`] uses from the reader besides the footer and the selection.
+#[derive(Default)]
+struct ReadContext<'a> {
+    shared_dictionary_cache: Option<&'a SharedDictionaryCache>,
+    decode_options: DecodeOptions<'a>,
+    row_filter: Option<&'a RowFilter>,
+    /// The IO units of all the column families, sorted by offset.
+    family_iounits: &'a [Range<u64>],
+    io_scheduler: Option<&'a IoScheduler>,
 }
 
 fn read_file_based_on_footer<R: Reader>(
-    reader: &mut R,
+    reader: &R,
     footer: Footer,
     projections: &Projection,
     selection: &Selection,
     wasm_context: Option<Arc<WASMReadingContext<R>>>,
-    shared_dictionary_cache: Option<&SharedDictionaryCache>,
-    checksum_type: Option<ChecksumType>,
+    context: &ReadContext,
 ) -> Result<Vec<RecordBatch>> {
+    let ReadContext {
+        shared_dictionary_cache,
+        decode_options,
+        row_filter,
+        family_iounits,
+        io_scheduler,
+    } = *context;
     let shared_dictionary_cache = shared_dictionary_cache.unwrap();
+    if let (Selection::RowIndexes(row_indexes), Some(_)) = (selection, row_filter) {
+        if !row_indexes.is_sorted() {
+            return nyi_err!("Row filters with unsorted row indexes");
+        }
+    }
     let mut record_batches = vec![];
     let rg_metas = footer.row_group_metadatas();
     // let projections = projections.map(|vec| vec.iter().map(|v| *v).collect::<HashSet<usize>>());
-    let selected_rg_metas = process_selection(selection, rg_metas);
+    let selected_rg_metas = process_selection(selection, rg_metas)
+        .into_iter()
+        // Files written before empty row groups were skipped may contain some.
+        .filter(|(rg_meta, _)| rg_meta.row_count > 0)
+        .collect::<Vec<_>>();
+    // TODO: needs some magic to handle nested data. Basically needs to go over the schema recursively
+    // and figure out which leaf nodes to fetch. Currently projection is only tested on flat data.
+    let mut fields: Vec<&FieldRef> = match projections {
+        Projection::LeafColumnIndexes(projected_indices) => projected_indices
+            .iter()
+            .map(|&v| footer.schema().fields().get(v).unwrap())
+            .collect(),
+        Projection::All => footer.schema().fields().iter().collect(),
+    };
+    let num_output_columns = fields.len();
+    // Position of the filter column in `fields`. A filter column that is not projected is decoded
+    // last, matching the order of the metadata loaded by the builder, and dropped from the output.
+    let filter_field_idx = match row_filter {
+        Some(row_filter) => {
+            let column = row_filter.column();
+            let field = footer
+                .schema()
+                .fields()
+                .get(column)
+                .ok_or(Error::IndexOutOfBound(
+                    column,
+                    footer.schema().fields().len(),
+                ))?;
+            Some(match projections {
+                Projection::All => column,
+                Projection::LeafColumnIndexes(projected_indices) => projected_indices
+                    .iter()
+                    .position(|&v| v == column)
+                    .unwrap_or_else(|| {
+                        fields.push(field);
+                        fields.len() - 1
+                    }),
+            })
+        }
+        None => None,
+    };
+    // Index in the file of each field, as in `Projection::LeafColumnIndexes`, for the spans of
+    // the decoded columns.
+    #[cfg(feature = "tracing")]
+    let leaf_column = |i: usize| match projections {
+        Projection::LeafColumnIndexes(projected_indices) => match projected_indices.get(i) {
+            Some(&column) => column,
+            // The filter column, decoded last when not projected.
+            None => row_filter.map_or(i, RowFilter::column),
+        },
+        Projection::All => i,
+    };
+    // Position of the metadata of the filter column in each row group, whose chunks may be ruled
+    // out by their bloom filters.
+    let filter_column_meta_idx = filter_field_idx.map(|idx| {
+        fields[..idx]
+            .iter()
+            .map(|f| num_physical_columns(f.data_type()))
+            .sum::<usize>()
+    });
+    // The chunks of all the row groups are known up front when all rows are decoded.
+    let mut scheduled_reads = match (io_scheduler, selection, row_filter) {
+        (Some(io_scheduler), Selection::All, None) => Some(
+            io_scheduler.schedule(
+                selected_rg_metas
+                    .iter()
+                    .map(|(rg_meta, _)| rg_meta.column_metadatas.as_slice()),
+            ),
+        ),
+        _ => None,
+    };
     for (rg_meta, selection_in_rg) in selected_rg_metas {
+        let selection_in_rg = match (row_filter, filter_column_meta_idx) {
+            (Some(row_filter), Some(idx)) => {
+                match row_filter.prune_chunks(&rg_meta.column_metadatas[idx], selection_in_rg) {
+                    Some(selection) => selection,
+                    None => continue,
+                }
+            }
+            _ => selection_in_rg,
+        };
+        // Hand the decoders the chunks read by the IO scheduler, if any, otherwise fetch the chunks
+        // of each IO unit of a column family at once when all rows are decoded.
+        let prefetched = match (&mut scheduled_reads, &selection_in_rg, row_filter) {
+            (Some(scheduled_reads), _, _) => Some(scheduled_reads.next_row_group(reader)?),
+            (None, Selection::All, None) if !family_iounits.is_empty() => Some(
+                PrefetchedChunks::try_new(reader, family_iounits, &rg_meta.column_metadatas)?,
+            ),
+            _ => None,
+        }
+        .filter(|prefetched| !prefetched.is_empty());
+        let decode_options = DecodeOptions {
+            prefetched: prefetched.as_ref(),
+            ..decode_options
+        };
         let mut column_idx = ColumnIndexSequence::default();
+        let mut decoders = fields
+            .iter()
+            .map(|&field| {
+                create_logical_decoder(
+                    reader,
+                    Arc::clone(field),
+                    &rg_meta.column_metadatas,
+                    &mut column_idx,
+                    wasm_context.as_ref().map(Arc::clone),
+                    shared_dictionary_cache,
+                    &decode_options,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
         let mut columns = vec![];
-        let mut decode_col = |field: &Arc<Field>| -> Result<()> {
-            let mut col_decoder = create_logical_decoder(
-                reader,
-                Arc::clone(field),
-                &rg_meta.column_metadatas,
-                &mut column_idx,
-                wasm_context.as_ref().map(Arc::clone),
-                shared_dictionary_cache,
-                checksum_type,
-            )?;
-            let arrays = if let Selection::RowIndexes(row_indexes) = &selection_in_rg {
-                col_decoder.decode_row_at(row_indexes[0] as usize, 1)?
-            } else {
-                col_decoder.decode_batch()?
-            };
-            columns.push(arrays);
-            Ok(())
-        };
-        // TODO: needs some magic to handle nested data. Basically needs to go over the schema recursively
-        // and figure out which leaf nodes to fetch. Currently projection is only tested on flat data.
-        match projections {
-            Projection::LeafColumnIndexes(projected_indices) => projected_indices
-                .iter()
-                .try_for_each(|&v| decode_col(footer.schema().fields().get(v).unwrap()))?,
-            Projection::All => {
-                for field in footer.schema().fields().iter() {
-                    // println!("decode col {field_id}");
-                    decode_col(field)?;
+        if let (Some(row_filter), Some(filter_field_idx)) = (row_filter, filter_field_idx) {
+            // Late materialization: decode the filter column, then only the passing rows of the
+            // other columns.
+            let filter_decoder = &mut decoders[filter_field_idx];
+            let filter_column = {
+                trace_span!("decode_column", leaf_column = leaf_column(filter_field_idx));
+                if let Selection::RowIndexes(row_indexes) = &selection_in_rg {
+                    filter_decoder.take_rows(row_indexes)?
+                } else {
+                    let arrays = filter_decoder.decode_batch()?;
+                    concat(&arrays.iter().map(|a| a.as_ref()).collect::<Vec<_>>())?
                 }
+            };
+            let mask = prep_null_mask_filter(&row_filter.evaluate(&filter_column)?);
+            let row_ids: Vec<u64> = match &selection_in_rg {
+                Selection::RowIndexes(row_indexes) => mask
+                    .values()
+                    .set_indices()
+                    .map(|i| row_indexes[i])
+                    .collect(),
+                Selection::All => mask.values().set_indices().map(|i| i as u64).collect(),
+            };
+            if row_ids.is_empty() {
+                continue;
+            }
+            for (i, decoder) in decoders.iter_mut().enumerate() {
+                trace_span!("decode_column", leaf_column = leaf_column(i));
+                columns.push(vec![if i == filter_field_idx {
+                    filter(&filter_column, &mask)?
+                } else {
+                    decoder.take_rows(&row_ids)?
+                }]);
+            }
+        } else {
+            for decoder in decoders.iter_mut() {
+                // `columns` holds the output of the previous decoders.
+                trace_span!("decode_column", leaf_column = leaf_column(columns.len()));
+                columns.push(
+                    if let Selection::RowIndexes(row_indexes) = &selection_in_rg {
+                        vec![decoder.take_rows(row_indexes)?]
+                    } else {
+                        decoder.decode_batch()?
+                    },
+                );
             }
         }
-        // TODO: vortex may not round-trip out the input Arrow type. https://github.com/spiraldb/vortex/issues/1021
-        for i in 0..columns[0].len() {
+        columns.truncate(num_output_columns);
+        // The decoders output the types of the schema, apart from views and preserved
+        // dictionaries, see `from_physical`.
+        for i in 0..columns.first().map_or(0, Vec::len) {
             let columns_this_batch = columns.iter().map(|c| c[i].clone()).collect::<Vec<_>>();
             record_batches.push(RecordBatch::try_new(
-                Schema::new(
+                Schema::new_with_metadata(
                     columns_this_batch
                         .iter()
-                        .zip(footer.schema().fields().iter())
-                        .map(|(c, f)| Field::new(f.name(), c.data_type().clone(), f.is_nullable()))
+                        .zip(fields.iter())
+                        .map(|(c, f)| f.as_ref().clone().with_data_type(c.data_type().clone()))
                         .collect::<Vec<_>>(),
+                    footer.schema().metadata().clone(),
                 )
                 .into(),
                 columns_this_batch,
@@ -257,6 +1519,40 @@ fn read_file_based_on_footer<R: Reader>(
         }
         // record_batches.push(RecordBatch::try_new(footer.schema().clone(), columns)?);
     }
+    if let Selection::RowIndexes(row_indexes) = selection {
+        if !row_indexes.is_sorted() && !record_batches.is_empty() {
+            // Rows are gathered in ascending order, restore the order requested by the caller.
+            // Batches of different row groups may differ in types, e.g., in preserved
+            // dictionaries, so they are cast to the projected schema to be concatenated.
+            let schema = Arc::new(Schema::new_with_metadata(
+                fields[..num_output_columns]
+                    .iter()
+                    .map(|&field| field.clone())
+                    .collect::<Vec<_>>(),
+                footer.schema().metadata().clone(),
+            ));
+            let batches = record_batches
+                .iter()
+                .map(|batch| {
+                    let columns = batch
+                        .columns()
+                        .iter()
+                        .zip(schema.fields())
+                        .map(|(column, field)| cast(column, field.data_type()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let gathered = concat_batches(&schema, &batches)?;
+            let mut order = (0..row_indexes.len() as u64).collect::<Vec<_>>();
+            order.sort_by_key(|&i| row_indexes[i as usize]);
+            let mut positions = vec![0u64; row_indexes.len()];
+            for (sorted_pos, original_pos) in order.into_iter().enumerate() {
+                positions[original_pos as usize] = sorted_pos as u64;
+            }
+            record_batches = vec![take_record_batch(&gathered, &UInt64Array::from(positions))?];
+        }
+    }
     Ok(record_batches)
 }
 
@@ -397,8 +1693,11 @@ fn collect_stat_for_col(
         chunk_size.unwrap()
     );
     match field.data_type() {
-        non_nest_types!() => {}
-        DataType::List(child) | DataType::LargeList(child) => {
+        data_type if matches!(physical_type(data_type), non_nest_types!()) => {}
+        DataType::List(child)
+        | DataType::LargeList(child)
+        | DataType::FixedSizeList(child, _)
+        | DataType::Map(child, _) => {
             collect_stat_for_col(child.clone(), field_id, column_metas, column_idx)?;
         }
         DataType::Struct(child_fields) => {
@@ -406,7 +1705,7 @@ fn collect_stat_for_col(
                 collect_stat_for_col(field.clone(), field_id, column_metas, column_idx)?;
             }
         }
-        _ => todo!("Implement logical encoding for field {}", field),
+        _ => return nyi_err!(format!("Logical decoding of field {}", field)),
     }
     Ok(())
 }
@@ -512,11 +1811,16 @@ pub fn process_selection<'a>(
     }
 }
 
-fn read_postscript<R: Reader + ?Sized>(reader: &R, file_size: u64) -> Result<PostScript> {
+pub(crate) fn read_postscript<R: Reader + ?Sized>(
+    reader: &R,
+    file_size: u64,
+) -> Result<PostScript> {
     // read postscript from file
     let mut postscript_buffer: [u8; POSTSCRIPT_SIZE as usize] = [0; POSTSCRIPT_SIZE as usize];
     reader.read_exact_at(&mut postscript_buffer, file_size - POSTSCRIPT_SIZE)?;
-    if postscript_buffer[postscript_buffer.len() - 2..] != *MAGIC {
+    let magic = &postscript_buffer[postscript_buffer.len() - 2..];
+    let encrypted_footer = magic == MAGIC_ENCRYPTED_FOOTER;
+    if magic != MAGIC && !encrypted_footer {
         return Err(Error::General("Magic number incorrect".to_string()));
     }
     let metadata_size = LittleEndian::read_u32(&postscript_buffer[0..4]);
@@ -527,6 +1831,13 @@ fn read_postscript<R: Reader + ?Sized>(reader: &R, file_size: u64) -> Result<Pos
     let schema_checksum = LittleEndian::read_u64(&postscript_buffer[18..26]);
     let major_version = LittleEndian::read_u16(&postscript_buffer[26..28]);
     let minor_version = LittleEndian::read_u16(&postscript_buffer[28..30]);
+    // Newer minor versions may hold encodings this reader does not know, e.g., BOOLEAN EncUnits.
+    if major_version != MAJOR_VERSION || minor_version > MINOR_VERSION {
+        return Err(general_error!(format!(
+            "File format version {major_version}.{minor_version} is not supported, \
+             this reader supports up to {MAJOR_VERSION}.{MINOR_VERSION}"
+        )));
+    }
     Ok(PostScript {
         metadata_size,
         footer_size,
@@ -536,6 +1847,7 @@ fn read_postscript<R: Reader + ?Sized>(reader: &R, file_size: u64) -> Result<Pos
         schema_checksum,
         major_version,
         minor_version,
+        encrypted_footer,
     })
 }
 
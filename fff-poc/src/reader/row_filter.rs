@@ -0,0 +1,155 @@
+use std::{collections::HashMap, sync::Arc};
+
+use arrow::compute::{
+    cast,
+    kernels::cmp::{eq, gt, gt_eq, lt, lt_eq, neq},
+};
+use arrow_array::{ArrayRef, BooleanArray, Scalar};
+use fff_core::{errors::Result, general_error};
+use fff_format::File::fff::flatbuf as fb;
+
+use super::{ComparisonOp, Selection};
+use crate::common::bloom_filter::{hash_values, BloomFilter};
+
+type Predicate = dyn Fn(&ArrayRef) -> Result<BooleanArray> + Send + Sync;
+
+/// A predicate on a top-level column, evaluated by the reader before decoding other columns.
+///
+/// In each row group, the filter column is decoded first. The other projected columns then only
+/// decode the chunks holding rows that pass the predicate, which saves most of the work for
+/// selective predicates. Rows where the predicate is null are filtered out.
+///
+/// With [`RowFilter::eq`], chunks whose bloom filter rules out the value are not decoded at all.
+/// With [`RowFilter::compare`], row groups whose key range rules out the value are skipped in
+/// sorted files, see [`FileReaderV2::prune_row_groups`](super::FileReaderV2::prune_row_groups).
+#[derive(Clone)]
+pub struct RowFilter {
+    column: usize,
+    predicate: Arc<Predicate>,
+    /// The comparison of a comparison filter, checked against the min/max of row groups.
+    comparison: Option<(ComparisonOp, ArrayRef)>,
+    /// Hash of the value of an equality filter, checked against the bloom filters.
+    value_hash: Option<u64>,
+    /// Bloom filters of the chunks of the filter column, by chunk offset.
+    bloom_filters: Arc<HashMap<u64, BloomFilter>>,
+}
+
+impl RowFilter {
+    /// `column` is an index in the schema of the file. It does not need to be projected.
+    pub fn new(
+        column: usize,
+        predicate: impl Fn(&ArrayRef) -> Result<BooleanArray> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            column,
+            predicate: Arc::new(predicate),
+            comparison: None,
+            value_hash: None,
+            bloom_filters: Default::default(),
+        }
+    }
+
+    /// Keep the rows where `column` equals `value`, a single-value array of the type of the
+    /// column. See [`FileWriterOptionsBuilder::set_bloom_filter_columns`](crate::options::FileWriterOptionsBuilder::set_bloom_filter_columns).
+    pub fn eq(column: usize, value: ArrayRef) -> Result<Self> {
+        let value_hash = hash_values(value.as_ref())?.first().copied().flatten();
+        let mut row_filter = Self::compare(column, ComparisonOp::Eq, value)?;
+        row_filter.value_hash = value_hash;
+        Ok(row_filter)
+    }
+
+    /// Keep the rows where `column op value`, `value` being a single-value array of the type of
+    /// the column.
+    pub fn compare(column: usize, op: ComparisonOp, value: ArrayRef) -> Result<Self> {
+        if value.len() != 1 {
+            return Err(general_error!(format!(
+                "Expected a single value to compare, got {}",
+                value.len()
+            )));
+        }
+        let compared = value.clone();
+        let mut row_filter = Self::new(column, move |array| {
+            // String columns may be decoded as views.
+            let value = if compared.data_type() == array.data_type() {
+                compared.clone()
+            } else {
+                cast(&compared, array.data_type())?
+            };
+            let value = Scalar::new(value);
+            Ok(match op {
+                ComparisonOp::Eq => eq(array, &value)?,
+                ComparisonOp::NotEq => neq(array, &value)?,
+                ComparisonOp::Lt => lt(array, &value)?,
+                ComparisonOp::LtEq => lt_eq(array, &value)?,
+                ComparisonOp::Gt => gt(array, &value)?,
+                ComparisonOp::GtEq => gt_eq(array, &value)?,
+            })
+        });
+        row_filter.comparison = Some((op, value));
+        Ok(row_filter)
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// The comparison of a filter created by [`Self::compare`] or [`Self::eq`].
+    pub(crate) fn comparison(&self) -> Option<(ComparisonOp, &ArrayRef)> {
+        self.comparison.as_ref().map(|(op, value)| (*op, value))
+    }
+
+    /// Hash of the value of an equality filter, if this is one.
+    pub(crate) fn value_hash(&self) -> Option<u64> {
+        self.value_hash
+    }
+
+    pub(crate) fn with_bloom_filters(mut self, bloom_filters: HashMap<u64, BloomFilter>) -> Self {
+        self.bloom_filters = Arc::new(bloom_filters);
+        self
+    }
+
+    /// False if no row of the chunk at `chunk_offset` can pass the filter, according to its bloom
+    /// filter.
+    fn might_match_chunk(&self, chunk_offset: u64) -> bool {
+        match (self.value_hash, self.bloom_filters.get(&chunk_offset)) {
+            (Some(hash), Some(filter)) => filter.check_hash(hash),
+            _ => true,
+        }
+    }
+
+    /// The rows of `selection` in a row group that are not in a chunk of the filter column ruled
+    /// out by its bloom filter. None if no row is left.
+    pub(crate) fn prune_chunks(
+        &self,
+        column_meta: &fb::ColumnMetadata,
+        selection: Selection,
+    ) -> Option<Selection> {
+        if self.value_hash.is_none() || self.bloom_filters.is_empty() {
+            return Some(selection);
+        }
+        let mut kept_rows = vec![];
+        let mut first_row = 0;
+        for chunk in column_meta.column_chunks().into_iter().flatten() {
+            let rows = first_row..first_row + chunk.num_rows();
+            first_row = rows.end;
+            if self.might_match_chunk(chunk.offset()) {
+                kept_rows.push(rows);
+            }
+        }
+        if kept_rows.first() == Some(&(0..first_row)) {
+            return Some(selection);
+        }
+        let row_indexes: Vec<u64> = match selection {
+            Selection::All => kept_rows.into_iter().flatten().collect(),
+            Selection::RowIndexes(row_indexes) => row_indexes
+                .into_iter()
+                .filter(|row| kept_rows.iter().any(|rows| rows.contains(row)))
+                .collect(),
+        };
+        (!row_indexes.is_empty()).then_some(Selection::RowIndexes(row_indexes))
+    }
+
+    pub(crate) fn evaluate(&self, array: &ArrayRef) -> Result<BooleanArray> {
+        (self.predicate)(array)
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use arrow::compute::{cast_with_options, CastOptions};
+use arrow_array::{ArrayRef, RecordBatch, RecordBatchOptions};
+use arrow_schema::{DataType, Schema, SchemaRef};
+use fff_core::{errors::Result, general_error};
+
+use super::{DefaultValueProvider, Projection};
+
+/// Where the values of a field of the target schema come from.
+#[derive(Debug, Clone)]
+enum TargetColumn {
+    /// Position in `file_columns` of the column.
+    File(usize),
+    /// The field is missing from the file.
+    Default(DefaultValueProvider),
+}
+
+/// Reconciliation of the schema of a file with the target schema of a reader, see
+/// [`FileReaderV2Builder::with_target_schema`](super::FileReaderV2Builder::with_target_schema).
+#[derive(Debug, Clone)]
+pub(crate) struct SchemaEvolution {
+    target_schema: SchemaRef,
+    /// Indexes in the file of the columns of the target schema found in it.
+    file_columns: Vec<usize>,
+    /// One for each field of the target schema.
+    columns: Vec<TargetColumn>,
+}
+
+impl SchemaEvolution {
+    /// Match the fields of `target_schema` with the top-level columns of `file_schema` by name.
+    /// Fails if a matched column cannot be promoted to the target type, or if a missing field is
+    /// not nullable and has no non-null value in `default_values`.
+    pub(crate) fn try_new(
+        file_schema: &Schema,
+        target_schema: SchemaRef,
+        default_values: &HashMap<String, DefaultValueProvider>,
+    ) -> Result<Self> {
+        let mut file_columns = vec![];
+        let mut columns = vec![];
+        for field in target_schema.fields() {
+            let Some((idx, file_field)) = file_schema.column_with_name(field.name()) else {
+                let default_value = default_values
+                    .get(field.name())
+                    .cloned()
+                    .unwrap_or_default();
+                if matches!(default_value, DefaultValueProvider::Null) && !field.is_nullable() {
+                    return Err(general_error!(format!(
+                        "Column {} of the target schema is not in the file and not nullable",
+                        field.name()
+                    )));
+                }
+                // Fail early on values of another type or nulls for a non-nullable field.
+                default_value.fill(field, 1)?;
+                columns.push(TargetColumn::Default(default_value));
+                continue;
+            };
+            if !is_type_promotion(file_field.data_type(), field.data_type()) {
+                return Err(general_error!(format!(
+                    "Cannot read column {} of type {} as {}",
+                    field.name(),
+                    file_field.data_type(),
+                    field.data_type()
+                )));
+            }
+            columns.push(TargetColumn::File(file_columns.len()));
+            file_columns.push(idx);
+        }
+        if file_columns.is_empty() {
+            return Err(general_error!(
+                "No column of the target schema is in the file"
+            ));
+        }
+        Ok(Self {
+            target_schema,
+            file_columns,
+            columns,
+        })
+    }
+
+    pub(crate) fn target_schema(&self) -> SchemaRef {
+        self.target_schema.clone()
+    }
+
+    /// The columns of the file to decode, in the order of the target schema.
+    pub(crate) fn projection(&self) -> Projection {
+        Projection::new(&self.file_columns)
+    }
+
+    /// Turn a batch of the columns of [`Self::projection`] into a batch of the target schema.
+    pub(crate) fn apply(&self, batch: &RecordBatch) -> Result<RecordBatch> {
+        let options = CastOptions {
+            safe: false,
+            ..Default::default()
+        };
+        let columns = self
+            .target_schema
+            .fields()
+            .iter()
+            .zip(&self.columns)
+            .map(|(field, column)| match column {
+                TargetColumn::File(position) => Ok(cast_with_options(
+                    batch.column(*position),
+                    field.data_type(),
+                    &options,
+                )?),
+                TargetColumn::Default(default_value) => default_value.fill(field, batch.num_rows()),
+            })
+            .collect::<Result<Vec<ArrayRef>>>()?;
+        Ok(RecordBatch::try_new_with_options(
+            self.target_schema.clone(),
+            columns,
+            &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
+        )?)
+    }
+}
+
+/// Whether values of type `from` can be read as `to` without loss, apart from timestamps read
+/// with a coarser unit, which are truncated.
+fn is_type_promotion(from: &DataType, to: &DataType) -> bool {
+    use DataType::*;
+    from == to
+        || matches!(
+            (from, to),
+            (Int8, Int16 | Int32 | Int64)
+                | (Int16, Int32 | Int64)
+                | (Int32, Int64)
+                | (UInt8, UInt16 | UInt32 | UInt64 | Int16 | Int32 | Int64)
+                | (UInt16, UInt32 | UInt64 | Int32 | Int64)
+                | (UInt32, UInt64 | Int64)
+                | (Float16, Float32 | Float64)
+                | (Float32, Float64)
+                | (Date32, Date64)
+                | (Utf8, LargeUtf8 | Utf8View)
+                | (Binary, LargeBinary | BinaryView)
+        )
+        || matches!((from, to), (Timestamp(_, from_tz), Timestamp(_, to_tz)) if from_tz == to_tz)
+}
@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+
+use arrow::compute::cast;
+use arrow::ffi_stream::FFI_ArrowArrayStream;
+use arrow_array::{RecordBatch, RecordBatchReader};
+use arrow_schema::{ArrowError, SchemaRef};
+use fff_core::errors::Result;
+
+use super::{FileReaderV2, Selection};
+use crate::io::reader::Reader;
+
+/// Iterator over the batches of a file, created by [`FileReaderV2::into_stream`].
+///
+/// Row groups are decoded one at a time when the previous one is consumed. With a selection,
+/// the selected rows are decoded on the first call instead, as they may come from any row group.
+/// Batches are cast to [`RecordBatchReader::schema`], e.g., dictionary arrays output with
+/// dictionary preservation are unpacked. Its timestamp types follow the timestamp normalization
+/// of the reader, unless the reader has a target schema.
+pub struct FileStream<R> {
+    reader: FileReaderV2<R>,
+    /// Schema of the projected columns.
+    schema: SchemaRef,
+    next_row_group: usize,
+    pending: VecDeque<RecordBatch>,
+}
+
+impl<R: Reader> FileStream<R> {
+    pub(super) fn new(reader: FileReaderV2<R>) -> Self {
+        let schema = reader.output_schema();
+        Self {
+            reader,
+            schema,
+            next_row_group: 0,
+            pending: VecDeque::new(),
+        }
+    }
+
+    fn num_row_groups(&self) -> usize {
+        self.reader.row_group_cnt_n_pointers.len()
+    }
+
+    /// Decode the next row group, or the whole selection.
+    fn read_next(&mut self) -> Result<()> {
+        let batches = if let Selection::All = self.reader.selection {
+            self.next_row_group += 1;
+            self.reader.read_row_group(self.next_row_group - 1)?
+        } else {
+            self.next_row_group = self.num_row_groups();
+            self.reader.read_file()?
+        };
+        self.pending.extend(batches);
+        Ok(())
+    }
+
+    fn cast_to_schema(&self, batch: RecordBatch) -> Result<RecordBatch, ArrowError> {
+        let columns = batch
+            .columns()
+            .iter()
+            .zip(self.schema.fields())
+            .map(|(column, field)| cast(column, field.data_type()))
+            .collect::<Result<Vec<_>, _>>()?;
+        RecordBatch::try_new(self.schema.clone(), columns)
+    }
+
+    /// Export the stream through the Arrow C Stream Interface, so that consumers in other
+    /// languages can pull the batches lazily.
+    pub fn into_ffi(self) -> FFI_ArrowArrayStream
+    where
+        Self: Send + 'static,
+    {
+        FFI_ArrowArrayStream::new(Box::new(self))
+    }
+}
+
+impl<R: Reader> Iterator for FileStream<R> {
+    type Item = Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            if self.next_row_group >= self.num_row_groups() {
+                return None;
+            }
+            if let Err(e) = self.read_next() {
+                // Do not resume after an error.
+                self.next_row_group = self.num_row_groups();
+                return Some(Err(ArrowError::ExternalError(e.to_string().into())));
+            }
+        }
+        let batch = self.pending.pop_front()?;
+        Some(self.cast_to_schema(batch))
+    }
+}
+
+impl<R: Reader> RecordBatchReader for FileStream<R> {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
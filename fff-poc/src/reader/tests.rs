@@ -38,6 +38,28 @@ fn test_read_postscript() {
     assert_eq!(postscript.minor_version, MINOR_VERSION);
 }
 
+#[test]
+#[rustfmt::skip]
+fn test_read_postscript_newer_version() {
+    use std::io::Write;
+    let mut file = tempfile::tempfile().unwrap();
+    let cursor = Cursor::new(vec![
+        42,0,0,0, /* metadata size */
+        23,0,0,0, /* footer size */
+        CompressionType::Uncompressed.into(), /* compression type */
+        ChecksumType::XxHash as u8,
+        17,0,0,0,0,0,0,0, /* data checksum */
+        19,0,0,0,0,0,0,0, /* schema checksum */
+        MAJOR_VERSION as u8, 0, /* major version */
+        MINOR_VERSION as u8 + 1, 0, /* minor version */
+        b'F',  b'3', /* magic */
+    ]);
+    file.write_all(cursor.get_ref()).unwrap();
+    let mut reader = FileReader::new(file);
+    let err = reader.read_postscript().unwrap_err();
+    assert!(err.to_string().contains("is not supported"), "{err}");
+}
+
 #[test]
 fn test_footer_roundtrip() {
     let schema = Schema::new(vec![
@@ -0,0 +1,80 @@
+//! Sharing of WASM modules across files by content hash, see
+//! [`FileReaderV2Builder::with_wasm_module_cache`](super::FileReaderV2Builder::with_wasm_module_cache)
+//! and [`FileReaderV2Builder::with_wasm_resolver`](super::FileReaderV2Builder::with_wasm_resolver).
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use fff_core::{errors::Result, general_error};
+use fff_ude_wasm::Runtime;
+
+/// Fetches the WASM modules that files store by reference only, given their content hash and
+/// URI. Implemented for closures.
+pub trait WasmResolver: Send + Sync {
+    fn resolve(&self, hash: u64, uri: &str) -> Result<Vec<u8>>;
+}
+
+impl<F: Fn(u64, &str) -> Result<Vec<u8>> + Send + Sync> WasmResolver for F {
+    fn resolve(&self, hash: u64, uri: &str) -> Result<Vec<u8>> {
+        self(hash, uri)
+    }
+}
+
+/// A cache of WASM runtimes keyed by the content hash of their modules, to be shared between
+/// readers via `Arc`, so that a module used by many files is fetched and compiled once.
+///
+/// Entries are never evicted by the cache itself, see [`WasmModuleCache::clear`].
+#[derive(Default)]
+pub struct WasmModuleCache {
+    runtimes: Mutex<HashMap<u64, Arc<Runtime>>>,
+}
+
+impl WasmModuleCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The runtime of the module with content hash `hash`, instantiating the binary returned by
+    /// `load` if it is not cached yet.
+    pub fn get_or_try_insert(
+        &self,
+        hash: u64,
+        load: impl FnOnce() -> Result<Vec<u8>>,
+    ) -> Result<Arc<Runtime>> {
+        let mut runtimes = self.runtimes.lock().unwrap();
+        if let Some(runtime) = runtimes.get(&hash) {
+            return Ok(runtime.clone());
+        }
+        let runtime = Arc::new(
+            Runtime::try_new(&load()?).map_err(|e| general_error!("Invalid WASM binary", e))?,
+        );
+        runtimes.insert(hash, runtime.clone());
+        Ok(runtime)
+    }
+
+    /// Cache `runtime` as the one of the module with content hash `hash`, e.g., a runtime
+    /// compiled ahead of time.
+    pub fn insert(&self, hash: u64, runtime: Arc<Runtime>) {
+        self.runtimes.lock().unwrap().insert(hash, runtime);
+    }
+
+    /// Whether the module with content hash `hash` is cached.
+    pub fn contains(&self, hash: u64) -> bool {
+        self.runtimes.lock().unwrap().contains_key(&hash)
+    }
+
+    /// Number of cached modules.
+    pub fn len(&self) -> usize {
+        self.runtimes.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.runtimes.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use arrow::compute::{
+    cast, is_null,
+    kernels::{
+        boolean::{and, not, or_kleene},
+        cmp::{eq, gt, gt_eq, lt, lt_eq},
+    },
+    prep_null_mask_filter,
+};
+use arrow_array::{Array, ArrayRef, BinaryArray, BooleanArray, Scalar, UInt64Array};
+use arrow_schema::DataType;
+use byteorder::{BigEndian, ByteOrder};
+use fff_core::{errors::Result, general_error};
+
+use crate::{
+    common::statistics::MinMaxAccumulator,
+    file::{encunit_index::ChunkEncUnitIndex, footer::Statistics},
+};
+
+/// A comparison of the values of a column with a value, as in `column op value`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComparisonOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+}
+
+/// Positions of the EncUnits of `chunk` that may hold values `v` such that `v op value`, from
+/// their zone maps in the EncUnit index. `value` is a single-value array, cast to `data_type`,
+/// the type of the column, if needed. EncUnits without a zone map are always kept, and no
+/// EncUnit is kept for a null value.
+///
+/// The zone maps of all the EncUnits are compared at once with Arrow's comparison kernels, as
+/// `UInt64Array`s, which the kernels vectorize with SIMD, for fixed-width columns, and as
+/// `BinaryArray`s otherwise. This complements the WASM `ppd` path for built-in encodings.
+pub fn prune_encunits(
+    chunk: &ChunkEncUnitIndex,
+    data_type: &DataType,
+    op: ComparisonOp,
+    value: &dyn Array,
+) -> Result<Vec<usize>> {
+    if value.len() != 1 {
+        return Err(general_error!(format!(
+            "Expected a single value to compare, got {}",
+            value.len()
+        )));
+    }
+    if value.is_null(0) {
+        return Ok(vec![]);
+    }
+    let Some(key) = statistics_key(value, data_type)? else {
+        // No zone maps for this type.
+        return Ok((0..chunk.encunits.len()).collect());
+    };
+
+    let bounds = |bound: fn(&Statistics) -> Option<&[u8]>| {
+        chunk
+            .encunits
+            .iter()
+            .map(|encunit| encunit.statistics.as_ref().and_then(bound))
+            .collect::<Vec<_>>()
+    };
+    let (mins, maxes) = (bounds(Statistics::min_value), bounds(Statistics::max_value));
+    // Truncated bounds of fixed-width values are shorter than the value.
+    let fixed_width = key.len() <= 8
+        && mins
+            .iter()
+            .chain(&maxes)
+            .flatten()
+            .all(|bound| bound.len() == key.len());
+    let to_array = |bounds: &[Option<&[u8]>]| -> ArrayRef {
+        if fixed_width {
+            Arc::new(
+                bounds
+                    .iter()
+                    .map(|bound| bound.map(|bound| BigEndian::read_uint(bound, bound.len())))
+                    .collect::<UInt64Array>(),
+            )
+        } else {
+            Arc::new(BinaryArray::from(bounds.to_vec()))
+        }
+    };
+    let (mins, maxes) = (to_array(&mins), to_array(&maxes));
+    let value = Scalar::new(to_array(&[Some(key.as_slice())]));
+
+    let keep = match op {
+        ComparisonOp::Eq => and(
+            &unless_false(lt_eq(&mins, &value)?)?,
+            &unless_false(gt_eq(&maxes, &value)?)?,
+        )?,
+        ComparisonOp::Lt => unless_false(lt(&mins, &value)?)?,
+        ComparisonOp::LtEq => unless_false(lt_eq(&mins, &value)?)?,
+        ComparisonOp::Gt => unless_false(gt(&maxes, &value)?)?,
+        ComparisonOp::GtEq => unless_false(gt_eq(&maxes, &value)?)?,
+        ComparisonOp::NotEq => {
+            // Only EncUnits whose values all equal the value are skipped.
+            let exact = BooleanArray::from(
+                chunk
+                    .encunits
+                    .iter()
+                    .map(|encunit| {
+                        encunit.statistics.as_ref().is_some_and(|statistics| {
+                            statistics.is_min_value_exact() && statistics.is_max_value_exact()
+                        })
+                    })
+                    .collect::<Vec<_>>(),
+            );
+            let all_equal = and(&eq(&mins, &value)?, &eq(&maxes, &value)?)?;
+            not(&and(&prep_null_mask_filter(&all_equal), &exact)?)?
+        }
+    };
+    Ok(keep.values().set_indices().collect())
+}
+
+/// The bytes the non-null single value `value`, cast to `data_type`, compares by with the bounds
+/// of statistics and zone maps. None if the type has none.
+pub(crate) fn statistics_key(value: &dyn Array, data_type: &DataType) -> Result<Option<Vec<u8>>> {
+    let value = cast(value, data_type)?;
+    let mut zone_map = MinMaxAccumulator::default();
+    zone_map.update_zone_map(value.as_ref());
+    Ok(zone_map
+        .finish(None)
+        .and_then(|statistics| statistics.min_value().map(<[u8]>::to_vec)))
+}
+
+/// Whether values between `min` and `max`, None if unknown, may be such that `v op key`.
+/// Without knowing whether the bounds are exact, `NotEq` may always match.
+pub(crate) fn may_match(
+    min: Option<&[u8]>,
+    max: Option<&[u8]>,
+    op: ComparisonOp,
+    key: &[u8],
+) -> bool {
+    let min_below = |strict: bool| min.is_none_or(|min| min < key || (!strict && min == key));
+    let max_above = |strict: bool| max.is_none_or(|max| max > key || (!strict && max == key));
+    match op {
+        ComparisonOp::Eq => min_below(false) && max_above(false),
+        ComparisonOp::NotEq => true,
+        ComparisonOp::Lt => min_below(true),
+        ComparisonOp::LtEq => min_below(false),
+        ComparisonOp::Gt => max_above(true),
+        ComparisonOp::GtEq => max_above(false),
+    }
+}
+
+/// True where `condition` holds or is unknown, i.e., null for EncUnits without this bound.
+fn unless_false(condition: BooleanArray) -> Result<BooleanArray> {
+    Ok(or_kleene(&condition, &is_null(&condition)?)?)
+}
@@ -0,0 +1,323 @@
+//! Deterministic simulation testing (DST) of the IO paths, behind the `dst` feature.
+//!
+//! A [`Simulation`] drives everything from one seed, in the style of FoundationDB: it runs async
+//! code on a current-thread tokio runtime with a paused clock, so that tasks are polled in a
+//! reproducible order and sleeps complete instantly in virtual time, and it wraps readers and
+//! object stores to inject IO errors, latencies and task switches drawn from its seeded RNG.
+//! Replaying a failing seed replays the same faults in the same schedule, see
+//! [`Simulation::trace`].
+
+use std::fmt::{self, Debug, Display};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use fff_core::{errors::Result, general_error};
+use futures::{future::Future, stream::BoxStream};
+use object_store::{
+    path::Path, GetOptions, GetResult, ListResult, MultipartUpload, ObjectMeta, ObjectStore,
+    PutMultipartOpts, PutOptions, PutPayload, PutResult, UploadPart,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::io::reader::{IoMetrics, ReadMetrics, Reader};
+
+/// Faults injected by a [`Simulation`].
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    /// Probability of each read and object store request to fail.
+    pub io_error_probability: f64,
+    /// Upper bound of the virtual latency of each object store request.
+    pub max_latency: Duration,
+    /// Upper bound of the times each object store request yields to other tasks first.
+    pub max_yields: u32,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            io_error_probability: 0.01,
+            max_latency: Duration::from_millis(100),
+            max_yields: 4,
+        }
+    }
+}
+
+/// A seeded simulation, shared by the readers and object stores it wraps.
+#[derive(Clone)]
+pub struct Simulation {
+    seed: u64,
+    config: SimConfig,
+    rng: Arc<Mutex<StdRng>>,
+    trace: Arc<Mutex<Vec<String>>>,
+}
+
+impl Simulation {
+    pub fn new(seed: u64, config: SimConfig) -> Self {
+        Self {
+            seed,
+            config,
+            rng: Arc::new(Mutex::new(StdRng::seed_from_u64(seed))),
+            trace: Arc::default(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Run `future` to completion on a current-thread runtime whose clock only advances when
+    /// all tasks wait for a timer. Tasks spawned by `future` run on the same runtime.
+    pub fn run<F: Future>(&self, future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .start_paused(true)
+            .build()
+            .expect("Failed to build the simulation runtime")
+            .block_on(future)
+    }
+
+    /// The faults injected so far, in order. Equal for two runs of the same seed.
+    pub fn trace(&self) -> Vec<String> {
+        self.trace.lock().unwrap().clone()
+    }
+
+    /// Wrap `inner` to fail its reads at random.
+    pub fn reader<R: Reader>(&self, inner: R) -> SimReader<R> {
+        SimReader {
+            inner,
+            sim: self.clone(),
+        }
+    }
+
+    /// Wrap `inner` to delay its requests and fail them at random.
+    pub fn object_store(&self, inner: Arc<dyn ObjectStore>) -> Arc<SimObjectStore> {
+        Arc::new(SimObjectStore {
+            inner,
+            sim: self.clone(),
+        })
+    }
+
+    /// Whether `op` fails, recording it in the trace if so.
+    fn fails(&self, op: impl FnOnce() -> String) -> bool {
+        let fails = self
+            .rng
+            .lock()
+            .unwrap()
+            .gen_bool(self.config.io_error_probability);
+        if fails {
+            self.trace.lock().unwrap().push(op());
+        }
+        fails
+    }
+
+    /// Yield to other tasks and sleep for random numbers of times and durations.
+    async fn delay(&self) {
+        let (yields, latency) = {
+            let mut rng = self.rng.lock().unwrap();
+            (
+                rng.gen_range(0..=self.config.max_yields),
+                rng.gen_range(Duration::ZERO..=self.config.max_latency),
+            )
+        };
+        for _ in 0..yields {
+            tokio::task::yield_now().await;
+        }
+        tokio::time::sleep(latency).await;
+    }
+
+    /// Delay an object store request, then fail it at random.
+    async fn request(&self, op: impl FnOnce() -> String) -> object_store::Result<()> {
+        self.delay().await;
+        if self.fails(op) {
+            return Err(object_store::Error::Generic {
+                store: "Simulation",
+                source: "Simulated IO error".into(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Debug for Simulation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Simulation(seed {})", self.seed)
+    }
+}
+
+/// A [`Reader`] whose reads fail at random, see [`Simulation::reader`].
+#[derive(Clone)]
+pub struct SimReader<R> {
+    inner: R,
+    sim: Simulation,
+}
+
+impl<R: Reader> Reader for SimReader<R> {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        if self.sim.fails(|| format!("read {offset}+{}", buf.len())) {
+            return Err(general_error!("Simulated IO error"));
+        }
+        self.inner.read_exact_at(buf, offset)
+    }
+
+    fn size(&self) -> Result<u64> {
+        self.inner.size()
+    }
+
+    fn read_ranges(&self, ranges: &[Range<u64>]) -> Result<Vec<bytes::Bytes>> {
+        if self.sim.fails(|| format!("read_ranges {ranges:?}")) {
+            return Err(general_error!("Simulated IO error"));
+        }
+        self.inner.read_ranges(ranges)
+    }
+
+    fn retry_read_exact_at(&self, buf: &mut [u8], offset: u64, attempt: usize) -> Result<bool> {
+        self.inner.retry_read_exact_at(buf, offset, attempt)
+    }
+
+    fn io_metrics(&self) -> Option<IoMetrics> {
+        self.inner.io_metrics()
+    }
+
+    fn read_metrics(&self) -> Option<ReadMetrics> {
+        self.inner.read_metrics()
+    }
+}
+
+/// An [`ObjectStore`] whose requests are delayed and fail at random, see
+/// [`Simulation::object_store`]. Listing is passed through.
+pub struct SimObjectStore {
+    inner: Arc<dyn ObjectStore>,
+    sim: Simulation,
+}
+
+impl Debug for SimObjectStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "SimObjectStore(seed {}, {:?})",
+            self.sim.seed, self.inner
+        )
+    }
+}
+
+impl Display for SimObjectStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SimObjectStore({})", self.inner)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for SimObjectStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        payload: PutPayload,
+        opts: PutOptions,
+    ) -> object_store::Result<PutResult> {
+        self.sim.request(|| format!("put {location}")).await?;
+        self.inner.put_opts(location, payload, opts).await
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        location: &Path,
+        opts: PutMultipartOpts,
+    ) -> object_store::Result<Box<dyn MultipartUpload>> {
+        self.sim
+            .request(|| format!("put_multipart {location}"))
+            .await?;
+        Ok(Box::new(SimUpload {
+            inner: self.inner.put_multipart_opts(location, opts).await?,
+            location: location.clone(),
+            sim: self.sim.clone(),
+            num_parts: 0,
+        }))
+    }
+
+    async fn get_opts(
+        &self,
+        location: &Path,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        let range = options.range.clone();
+        self.sim
+            .request(|| format!("get {location} {range:?}"))
+            .await?;
+        self.inner.get_opts(location, options).await
+    }
+
+    async fn get_ranges(
+        &self,
+        location: &Path,
+        ranges: &[Range<usize>],
+    ) -> object_store::Result<Vec<bytes::Bytes>> {
+        self.sim
+            .request(|| format!("get_ranges {location} {ranges:?}"))
+            .await?;
+        self.inner.get_ranges(location, ranges).await
+    }
+
+    async fn delete(&self, location: &Path) -> object_store::Result<()> {
+        self.sim.request(|| format!("delete {location}")).await?;
+        self.inner.delete(location).await
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+        self.inner.list(prefix)
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> object_store::Result<ListResult> {
+        self.inner.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        self.sim.request(|| format!("copy {from} {to}")).await?;
+        self.inner.copy(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        self.sim
+            .request(|| format!("copy_if_not_exists {from} {to}"))
+            .await?;
+        self.inner.copy_if_not_exists(from, to).await
+    }
+}
+
+/// A multipart upload of a [`SimObjectStore`], whose parts are delayed and fail at random.
+#[derive(Debug)]
+struct SimUpload {
+    inner: Box<dyn MultipartUpload>,
+    location: Path,
+    sim: Simulation,
+    num_parts: usize,
+}
+
+#[async_trait]
+impl MultipartUpload for SimUpload {
+    fn put_part(&mut self, data: PutPayload) -> UploadPart {
+        let op = format!("put_part {} #{}", self.location, self.num_parts);
+        self.num_parts += 1;
+        let sim = self.sim.clone();
+        let part = self.inner.put_part(data);
+        Box::pin(async move {
+            sim.request(|| op).await?;
+            part.await
+        })
+    }
+
+    async fn complete(&mut self) -> object_store::Result<PutResult> {
+        self.sim
+            .request(|| format!("complete {}", self.location))
+            .await?;
+        self.inner.complete().await
+    }
+
+    async fn abort(&mut self) -> object_store::Result<()> {
+        self.sim
+            .request(|| format!("abort {}", self.location))
+            .await?;
+        self.inner.abort().await
+    }
+}
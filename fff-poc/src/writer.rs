@@ -1,57 +1,244 @@
-use std::collections::HashMap;
-use std::io::{BufWriter, Seek, Write};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io::{BufWriter, Cursor, Seek, SeekFrom, Write};
 use std::iter::once;
-use std::sync::Arc;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
 
-use arrow_array::RecordBatch;
+use arrow::compute::{concat, concat_batches};
+use arrow_array::{Array, ArrayRef, RecordBatch};
+use arrow_buffer::MutableBuffer;
 use arrow_ipc::writer::IpcWriteOptions;
 use arrow_ipc::writer::{DictionaryTracker, IpcDataGenerator};
-use arrow_schema::Schema;
-use arrow_schema::SchemaRef;
-use fff_format::File::fff::flatbuf as fb;
+use arrow_schema::{DataType, SchemaRef};
+use arrow_schema::{FieldRef, Schema};
+use bytes::Bytes;
+use fff_format::File::fff::flatbuf::{self as fb, root_as_footer};
 use fff_format::ToFlatBuffer;
-use fff_format::{File::fff::flatbuf::CompressionType, MAGIC, MAJOR_VERSION, MINOR_VERSION};
+use fff_format::{
+    File::fff::flatbuf::CompressionType, MAGIC, MAGIC_ENCRYPTED_FOOTER, MAJOR_VERSION,
+    MINOR_VERSION, POSTSCRIPT_SIZE,
+};
 use flatbuffers::FlatBufferBuilder;
+use fff_ude_wasm::aot::{precompile, AotTarget};
+use fff_ude_wasm::features::required_features;
 
+use crate::common::bloom_filter::{hash_values, BloomFilter};
 use crate::common::checksum::create_checksum;
 use crate::common::checksum::Checksum;
 use crate::common::checksum::ChecksumType;
-use crate::common::ColumnIndexSequence;
-use crate::context::WASMWritingContext;
+use crate::common::physical_type::physical_type;
+use crate::common::statistics::{sortable_key_width, MinMaxAccumulator};
+use crate::common::{checked_u32, ColumnIndexSequence};
+use crate::compression::{
+    check_supported, compress_data, compress_with_trained_dictionary, Compression, CompressionLevel,
+};
+use crate::context::{WASMId, WASMWritingContext};
 use crate::counter::EncodingCounter;
 use crate::dict::shared_dictionary::SharedDictionaryTable;
 use crate::dict::shared_dictionary_context::SharedDictionaryContext;
 use crate::dict::DictionaryTypeOptions;
-use crate::encoder::encoded_column_chunk::EncodedColumnChunk;
+use crate::encoder::encoded_column_chunk::{EncodedColumnChunk, SerializedEncUnit};
 use crate::encoder::logical::LogicalColEncoder;
-use crate::encoder::logical::{create_logical_encoder, LogicalTree};
-use crate::file::footer::create_default_encoding_versions;
-use crate::file::footer::{self, Chunk, ColumnMetadata, RowGroupMetadata, RowGroupsTable};
-use crate::options::FileWriterOptions;
+use crate::encoder::logical::{create_logical_encoder, num_physical_columns, LogicalTree};
+use crate::encryption::Encryptor;
+use crate::file::bloom_filters::{
+    deserialize_bloom_filters, serialize_bloom_filters, ChunkBloomFilter,
+};
+use crate::file::column_families::{
+    deserialize_column_families, serialize_column_families, ColumnFamily,
+};
+use crate::file::encunit_index::{
+    deserialize_encunit_index, encunit_entries, serialize_encunit_index, ChunkEncUnitIndex,
+};
+use crate::file::footer::{
+    self, Chunk, ChunkEncryption, ColumnMetadata, DictionaryEncoding, RowGroupMetadata,
+    RowGroupsTable, Statistics,
+};
+use crate::file::footer::{create_default_encoding_versions, parse_footer};
+use crate::file::key_value_metadata::{KeyValueMetadata, MetadataValue};
+use crate::file::manifest::FileManifest;
+use crate::file::metadata_segments::{ensure_not_segmented, serialize_schema_index};
+use crate::file::schema_fingerprint::schema_fingerprint;
+use crate::file::sort_order::{self, sort_batch, SortColumn};
+use crate::file::wasm_aot::{serialize_wasm_aot_artifacts, WasmAotArtifact};
+use crate::file::wasm_modules::{serialize_wasm_modules, wasm_module_hash, WasmModuleInfo};
+use crate::io::reader::Reader;
+use crate::memory::MemoryReservation;
+use crate::options::{FileWriterOptions, DEFAULT_IOUNIT_SIZE};
+use crate::reader::{
+    get_metadata_buffer, get_reserved_padding, locate_section, read_postscript,
+    FileReaderV2Builder, Projection,
+};
+use crate::writer::input_validation::{validate_batch, InputValidation};
+use crate::writer::layout_planner::AutoColumnChunkSize;
 
-use fff_core::{errors::Result, nyi_err};
+use fff_core::{
+    errors::{Error, Result},
+    general_error, non_nest_types, nyi_err,
+};
+
+mod async_writer;
+pub mod input_validation;
+pub mod layout_planner;
+mod write_batches;
+
+pub use async_writer::{AsyncFileWriter, DEFAULT_PART_SIZE};
+pub use write_batches::{write_batches, write_batches_with_progress, WriteProgress, WriteStats};
+
+/// Key of the column metadata holding the chunk size chosen for the column, as a UInt64, see
+/// [`FileWriterOptionsBuilder::set_auto_column_chunk_size`](crate::options::FileWriterOptionsBuilder::set_auto_column_chunk_size).
+pub const COLUMN_CHUNK_SIZE_KEY: &str = "fff.column_chunk_size";
 
 struct FileWriteState<W: Write + Seek> {
     writer: BufWriter<W>,
     row_groups_table: RowGroupsTable,
-    num_rows_in_file: u32,
+    num_rows_in_file: u64,
     num_physical_columns: usize,
     data_checksum: Box<dyn Checksum>,
     column_counters: Vec<EncodingCounter>,
     enable_io_unit_checksum: bool,
+    /// See [FileWriterOptions::statistics_truncate_length].
+    statistics_truncate_length: Option<usize>,
     /// Metadata for the current row group.
     column_metadatas_in_cur_row_group: Vec<ColumnMetadata>,
     start_offset_of_cur_row_group: u64,
     num_rows_in_cur_row_group: u32,
+    /// Size of the file being appended to. The rewritten file must not be shorter,
+    /// as the underlying writer cannot be truncated.
+    min_file_size: u64,
+    /// The EncUnits of the chunks flushed so far, if the index is written.
+    encunit_index: Option<Vec<ChunkEncUnitIndex>>,
+    /// Hashes of the values not flushed in a chunk yet, by physical index of the columns with a
+    /// bloom filter.
+    bloom_filter_hashes: HashMap<u32, VecDeque<Option<u64>>>,
+    /// See [FileWriterOptions::bloom_filter_fpp].
+    bloom_filter_fpp: f64,
+    /// The bloom filters of the chunks flushed so far.
+    bloom_filters: Vec<ChunkBloomFilter>,
+    /// Position in `families` of the family of each physical column in one.
+    column_families: HashMap<u32, usize>,
+    /// The column families and their IO units written so far.
+    families: Vec<ColumnFamily>,
+    /// The records of the chunks of an existing file that are copied to other offsets, by their
+    /// offset in that file.
+    copied_chunks: HashMap<u64, CopiedChunk>,
+    /// The chunks of each family not written yet, and their size in bytes.
+    family_buffers: Vec<(Vec<EncodedColumnChunk>, u64)>,
+    /// Size in bytes after which the buffered chunks of a family are written.
+    iounit_size: u64,
+    /// The key of each encrypted physical column.
+    encryptors: HashMap<u32, Encryptor>,
+    /// The compression of EncUnits and the maximum size of the Zstd dictionary trained on the
+    /// EncUnits of each chunk, if enabled.
+    zstd_dictionary: Option<(Compression, usize)>,
 }
 
 impl<W> FileWriteState<W>
 where
     W: Write + Seek,
 {
+    /// Write `chunk`, or buffer it with the chunks of its column family, if any.
     pub fn flush_chunk(&mut self, chunk: EncodedColumnChunk) -> Result<()> {
+        let Some(&family) = self.column_families.get(&chunk.column_index) else {
+            return self.write_chunk(chunk);
+        };
+        let (chunks, size) = &mut self.family_buffers[family];
+        *size += chunk.size();
+        chunks.push(chunk);
+        if *size >= self.iounit_size {
+            self.flush_family(family)?;
+        }
+        Ok(())
+    }
+
+    /// Write the buffered chunks of all column families.
+    fn flush_families(&mut self) -> Result<()> {
+        for family in 0..self.family_buffers.len() {
+            self.flush_family(family)?;
+        }
+        Ok(())
+    }
+
+    /// Size in bytes of the chunks buffered by the column families.
+    fn family_buffers_size(&self) -> u64 {
+        self.family_buffers.iter().map(|(_, size)| size).sum()
+    }
+
+    /// Write the buffered chunks of a column family next to each other, as one of its IO units.
+    fn flush_family(&mut self, family: usize) -> Result<()> {
+        let (chunks, _) = std::mem::take(&mut self.family_buffers[family]);
+        if chunks.is_empty() {
+            return Ok(());
+        }
+        let start = self.writer.stream_position()?;
+        chunks
+            .into_iter()
+            .try_for_each(|chunk| self.write_chunk(chunk))?;
+        let end = self.writer.stream_position()?;
+        checked_u32(end - start, "Size of an IO unit of a column family")?;
+        self.families[family].iounits.push(start..end);
+        Ok(())
+    }
+
+    fn write_chunk(&mut self, chunk: EncodedColumnChunk) -> Result<()> {
         let column_index = chunk.column_index;
+        let encrypted = self.encryptors.contains_key(&column_index);
+        let copied = chunk
+            .source_offset
+            .and_then(|offset| self.copied_chunks.remove(&offset))
+            .unwrap_or_default();
+        let encunits = self.encunit_index.is_some().then(|| {
+            let mut encunits = encunit_entries(
+                chunk
+                    .encunits
+                    .iter()
+                    .map(|unit| (unit.num_rows(), unit.bytes().len() as u32)),
+            );
+            // Zone maps would leak the values of encrypted columns.
+            if !encrypted {
+                for (entry, unit) in encunits.iter_mut().zip(&chunk.encunits) {
+                    entry.statistics = unit.min_max().finish(self.statistics_truncate_length);
+                }
+            }
+            // Copied EncUnits have no min/max, they keep their zone maps.
+            if let Some(zone_maps) = copied.zone_maps.filter(|maps| maps.len() == encunits.len()) {
+                for (entry, zone_map) in encunits.iter_mut().zip(zone_maps) {
+                    entry.statistics = zone_map;
+                }
+            }
+            encunits
+        });
+        // Chunks copied from an existing file have no hashes, they keep their filter.
+        let bloom_filter = self
+            .bloom_filter_hashes
+            .get_mut(&column_index)
+            .filter(|hashes| hashes.len() >= chunk.num_rows)
+            .map(|hashes| {
+                let hashes: HashSet<u64> = hashes.drain(..chunk.num_rows).flatten().collect();
+                let mut filter = BloomFilter::new(hashes.len(), self.bloom_filter_fpp);
+                hashes.into_iter().for_each(|hash| filter.insert_hash(hash));
+                filter
+            })
+            .or(copied.filter);
         let chunk_meta = self.flush_chunk_and_get_metadata(chunk)?;
+        if let Some(filter) = bloom_filter {
+            self.bloom_filters.push(ChunkBloomFilter {
+                column_index,
+                chunk_offset: chunk_meta.offset(),
+                filter,
+            });
+        }
+        if let (Some(index), Some(encunits)) = (&mut self.encunit_index, encunits) {
+            index.push(ChunkEncUnitIndex {
+                column_index,
+                row_group: self.row_groups_table.row_counts().len() as u32,
+                chunk: self.column_metadatas_in_cur_row_group[column_index as usize]
+                    .chunks()
+                    .len() as u32,
+                chunk_offset: chunk_meta.offset(),
+                encunits,
+            });
+        }
         // use chunk.column_index to let the metadata knows which physical column does this chunk belong to
         self.column_metadatas_in_cur_row_group[column_index as usize].add_chunk(chunk_meta);
         Ok(())
@@ -64,47 +251,106 @@ where
     }
 
     pub fn flush_chunk_and_get_metadata(&mut self, chunk: EncodedColumnChunk) -> Result<Chunk> {
+        trace_span!(
+            "write_chunk",
+            column = chunk.column_index,
+            bytes = chunk.size()
+        );
         // println!("flush chunk with index {}", chunk.column_index);
         let offset = self.writer.stream_position()?;
         let mut iounit_checksum = self
             .enable_io_unit_checksum
             .then_some(create_checksum(&ChecksumType::XxHash));
-        let encunit_metas = chunk
-            .encunits
+        let encryptor = self.encryptors.get(&chunk.column_index).cloned();
+        let mut encryption = encryptor.as_ref().map(|encryptor| ChunkEncryption {
+            key_id: encryptor.key_id().to_string(),
+            ..Default::default()
+        });
+        let mut encunits = chunk.encunits;
+        let mut compression_dictionary = None;
+        if let Some((compression, max_size)) = self.zstd_dictionary {
+            let compressed: Vec<_> = encunits
+                .iter()
+                .map(|unit| (unit.bytes(), unit.compression_type()))
+                .collect();
+            if let Some((dictionary, compressed)) =
+                compress_with_trained_dictionary(&compressed, compression, max_size)?
+            {
+                encunits = encunits
+                    .into_iter()
+                    .zip(compressed)
+                    .map(|(unit, (bytes, compression_type))| {
+                        unit.with_compressed_bytes(bytes, compression_type)
+                    })
+                    .collect();
+                compression_dictionary = Some(dictionary.to_vec());
+            }
+        }
+        let encunit_metas = encunits
             .into_iter()
             .map(|unit| {
-                let buf = unit.bytes();
-                self.write_and_update_file_level_checksum(buf.as_ref())
-                    .unwrap();
+                let mut buf = unit.bytes();
+                if let (Some(encryptor), Some(encryption)) = (&encryptor, &mut encryption) {
+                    let mut encrypted = buf.to_vec();
+                    let (nonce, tag) = encryptor.encrypt(&mut encrypted)?;
+                    encryption.nonces.extend_from_slice(&nonce);
+                    encryption.tags.extend_from_slice(&tag);
+                    buf = Bytes::from(encrypted);
+                }
+                let size = checked_u32(buf.len() as u64, "Size of an EncUnit")?;
+                self.write_and_update_file_level_checksum(buf.as_ref())?;
                 if let Some(checksum) = &mut iounit_checksum {
                     checksum.update(buf.as_ref());
                 }
-                footer::EncUnit::new(
-                    buf.len() as u32,
+                Ok(footer::EncUnit::new(
+                    size,
                     unit.num_rows(),
                     unit.encoding().clone(),
                     unit.compression_type(),
                 )
+                .with_validity_size(unit.validity_size()))
             })
-            .collect();
+            .collect::<Result<Vec<_>>>()?;
         let size: u64 = self.writer.stream_position()? - offset;
         // use chunk.column_index to let the metadata knows which physical column does this chunk belong to
         Ok(Chunk::new(
             offset,
-            size as u32,
+            checked_u32(size, "Size of a chunk")?,
             chunk.num_rows as u64,
             chunk.dict_encoding,
             encunit_metas,
             iounit_checksum.map(|c| c.finalize()),
-        ))
+            chunk.null_count,
+        )
+        .with_statistics(
+            chunk
+                .min_max
+                .finish(self.statistics_truncate_length)
+                .filter(|_| encryption.is_none()),
+        )
+        .with_encryption(encryption)
+        .with_compression_dictionary(compression_dictionary))
     }
 
     /// Finish the current row group and add it to the row groups table.
+    /// Nothing is added for an empty row group, e.g., when finishing a file without rows.
     pub fn finish_row_group(&mut self) -> Result<()> {
+        self.flush_families()?;
+        if self.num_rows_in_cur_row_group == 0
+            && self
+                .column_metadatas_in_cur_row_group
+                .iter()
+                .all(|column_metadata| column_metadata.chunks().is_empty())
+        {
+            return Ok(());
+        }
         self.row_groups_table.add_meta(
             self.num_rows_in_cur_row_group,
             self.start_offset_of_cur_row_group,
-            (self.writer.stream_position()? - self.start_offset_of_cur_row_group) as u32,
+            checked_u32(
+                self.writer.stream_position()? - self.start_offset_of_cur_row_group,
+                "Size of a row group",
+            )?,
             RowGroupMetadata::new(std::mem::replace(
                 &mut self.column_metadatas_in_cur_row_group,
                 vec![ColumnMetadata::default(); self.num_physical_columns],
@@ -115,6 +361,112 @@ where
         Ok(())
     }
 
+    /// Copy the chunks of a column from `reader`, regrouping their EncUnits into chunks of about
+    /// `iounit_size` bytes. EncUnits are copied as is.
+    /// Min/max statistics are dropped, as the metadata only keeps their truncated bounds.
+    fn repack_column_chunks<R: Reader>(
+        &mut self,
+        reader: &R,
+        column_index: u32,
+        column_meta: fb::ColumnMetadata,
+        iounit_size: u64,
+    ) -> Result<()> {
+        let new_chunk = |null_count| EncodedColumnChunk {
+            column_index,
+            null_count,
+            ..Default::default()
+        };
+        let mut accumulated_chunk = new_chunk(Some(0));
+        let mut accumulated_size = 0;
+        for chunk in column_meta.column_chunks().into_iter().flatten() {
+            if chunk.encryption().is_some() {
+                return nyi_err!("Repacking encrypted chunks");
+            }
+            if chunk.compression_dictionary().is_some() {
+                return nyi_err!("Repacking chunks compressed with a dictionary");
+            }
+            let encunit_metas: Vec<_> = chunk.encunits().into_iter().flatten().collect();
+            if encunit_metas.iter().map(|e| e.size_() as u64).sum::<u64>() != chunk.size_() as u64 {
+                return Err(Error::ParseError(format!(
+                    "EncUnit sizes do not add up to the size of the chunk at offset {}",
+                    chunk.offset()
+                )));
+            }
+            let mut buf = vec![0; chunk.size_() as usize];
+            reader.read_exact_at(&mut buf, chunk.offset())?;
+            let mut data = Bytes::from(buf);
+            let encunits: Vec<_> = encunit_metas
+                .iter()
+                .map(|encunit| {
+                    SerializedEncUnit::new(
+                        data.split_to(encunit.size_() as usize),
+                        encunit.num_rows(),
+                        encunit
+                            .encoding()
+                            .map(|encoding| footer::Encoding::from(&encoding))
+                            .unwrap_or_default(),
+                        encunit.compression(),
+                    )
+                    .with_validity_size(
+                        (encunit.validity_encoding() != fb::ValidityEncoding::NONE)
+                            .then_some(encunit.validity_size()),
+                    )
+                })
+                .collect();
+
+            let dict_encoding = DictionaryEncoding::from(&chunk);
+            let has_records = self.copied_chunks.contains_key(&chunk.offset());
+            if !matches!(dict_encoding, DictionaryEncoding::NoDictionary) || has_records {
+                // EncUnits refer to the dictionary by their index in the chunk, and bloom filters
+                // and EncUnit index records to the whole chunk, keep it whole.
+                if !accumulated_chunk.encunits.is_empty() {
+                    self.flush_chunk(std::mem::replace(
+                        &mut accumulated_chunk,
+                        new_chunk(Some(0)),
+                    ))?;
+                    accumulated_size = 0;
+                }
+                self.flush_chunk(EncodedColumnChunk {
+                    encunits,
+                    num_rows: chunk.num_rows() as usize,
+                    dict_encoding,
+                    column_index,
+                    null_count: chunk.null_count(),
+                    source_offset: Some(chunk.offset()),
+                    ..Default::default()
+                })?;
+                continue;
+            }
+
+            // Null counts are only known per chunk, so they are lost for split chunks.
+            accumulated_chunk.null_count = accumulated_chunk
+                .null_count
+                .zip(chunk.null_count())
+                .map(|(a, b)| a + b);
+            let num_encunits = encunits.len();
+            for (i, encunit) in encunits.into_iter().enumerate() {
+                accumulated_size += encunit.bytes().len() as u64;
+                accumulated_chunk.num_rows += encunit.num_rows() as usize;
+                accumulated_chunk.encunits.push(encunit);
+                if accumulated_size > iounit_size {
+                    let split = i + 1 < num_encunits;
+                    if split {
+                        accumulated_chunk.null_count = None;
+                    }
+                    self.flush_chunk(std::mem::replace(
+                        &mut accumulated_chunk,
+                        new_chunk((!split).then_some(0)),
+                    ))?;
+                    accumulated_size = 0;
+                }
+            }
+        }
+        if !accumulated_chunk.encunits.is_empty() {
+            self.flush_chunk(accumulated_chunk)?;
+        }
+        Ok(())
+    }
+
     // Deprecated flush logic with null info
     // pub fn flush_chunk(&mut self, chunk: EncodedColumnChunk) -> Result<()> {
     //     let offset = self.writer.stream_position()?;
@@ -168,6 +520,15 @@ where
     // }
 }
 
+/// The first rows of a file, encoded to size the chunks of each top-level column, see
+/// [`AutoColumnChunkSize`].
+struct ChunkSizeSample {
+    auto: AutoColumnChunkSize,
+    num_rows: u64,
+    /// Bytes of the chunks flushed by each top-level column so far.
+    flushed_bytes: Vec<u64>,
+}
+
 #[allow(clippy::arc_with_non_send_sync)]
 pub struct FileWriter<W: Write + Seek> {
     schema: Schema,
@@ -178,6 +539,44 @@ pub struct FileWriter<W: Write + Seek> {
     wasm_context: Arc<WASMWritingContext>,
     custom_encunit_len: HashMap<usize, usize>,
     row_group_size: u64,
+    memory_budget: Option<u64>,
+    max_buffered_bytes: Option<u64>,
+    /// Reservation of [`Self::buffered_bytes`] from the memory pool of the options, if any.
+    reservation: Option<MemoryReservation>,
+    /// Number of threads encoding the columns of each batch, 1 with shared dictionaries.
+    encoding_parallelism: usize,
+    footer_padding: u64,
+    metadata_compression: CompressionType,
+    /// WASMId of the binary encoding each top-level column, for the "ColumnWASMs" section.
+    column_wasm_ids: BTreeMap<usize, WASMId>,
+    /// Content hash and URI of the WASM binaries stored by reference only.
+    wasm_references: HashMap<u64, String>,
+    /// Whether an AOT artifact of each embedded WASM binary is compiled for the host.
+    write_wasm_aot: bool,
+    /// AOT artifacts of the WASM binaries provided by the caller, by content hash.
+    wasm_aot_artifacts: HashMap<u64, Vec<(AotTarget, Vec<u8>)>>,
+    /// Physical column index of the top-level columns with a bloom filter.
+    bloom_filter_columns: HashMap<usize, u32>,
+    /// Size of the file before it was appended to, if its footer is kept as a version.
+    previous_version_size: Option<u64>,
+    /// Number of columns per segment of column metadata, with budgeted metadata.
+    metadata_segment_columns: Option<usize>,
+    /// Schema index written with budgeted metadata, see crate::file::metadata_segments.
+    schema_index: Option<Vec<u8>>,
+    /// The key the metadata is encrypted with, if any.
+    footer_encryptor: Option<Encryptor>,
+    /// User key-value metadata written to the footer.
+    key_value_metadata: KeyValueMetadata,
+    /// Key columns the rows of each new row group are sorted by.
+    sort_order: Vec<SortColumn>,
+    /// Sort order written to the footer, which all row groups must follow.
+    footer_sort_order: Vec<SortColumn>,
+    /// Batches of the current row group, buffered until it is full to be sorted.
+    sort_buffer: Vec<RecordBatch>,
+    /// How non-canonical input values are treated.
+    input_validation: InputValidation,
+    /// Sample of the first rows, until the chunk size of each column is chosen.
+    chunk_size_sample: Option<ChunkSizeSample>,
     shared_dictionary_context: SharedDictionaryContext,
 }
 
@@ -186,35 +585,183 @@ impl<W: Write + Seek> FileWriter<W> {
     pub fn try_new(schema: SchemaRef, writer: W, mut options: FileWriterOptions) -> Result<Self> {
         let checksum_type = options.checksum_type();
         let mut column_idx = ColumnIndexSequence::default();
-        let wasm_context = Arc::new(
-            match (
-                options.write_built_in_wasm(),
-                !options.custom_encoding_options().is_empty(),
-            ) {
-                (true, false) => WASMWritingContext::default_with_always_set_custom_wasm(),
-                (false, true) => options.take_custom_encoding_options().into_context(),
-                (false, false) => WASMWritingContext::empty(),
-                _ => todo!("Cleanup this stupid code"),
-            },
-        );
+        let mut wasm_context = match (
+            options.write_built_in_wasm(),
+            !options.custom_encoding_options().is_empty(),
+        ) {
+            (true, false) => WASMWritingContext::default_with_always_set_custom_wasm(),
+            (false, true) => options.take_custom_encoding_options().into_context(),
+            (false, false) => WASMWritingContext::empty(),
+            (true, true) => {
+                return Err(general_error!(
+                    "The built-in WASM cannot be written along with custom encodings"
+                ))
+            }
+        }
+        .with_adaptive_encoding(options.adaptive_encoding());
+        wasm_context.validate_wasm_ids()?;
+        let wasm_ids = options
+            .wasm_modules()
+            .iter()
+            .map(|wasm_binary| wasm_context.register_wasm(wasm_binary.clone()))
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(&column) = options
+            .column_wasm_ids()
+            .keys()
+            .find(|column| **column >= schema.fields().len())
+        {
+            return Err(Error::IndexOutOfBound(column, schema.fields().len()));
+        }
+        if let Some(name) = options
+            .column_wasm_names()
+            .keys()
+            .find(|name| schema.index_of(name).is_err())
+        {
+            return Err(Error::General(format!(
+                "Column {name} assigned a WASM binary not found in the schema"
+            )));
+        }
+        let mut column_wasm_ids = BTreeMap::new();
+        for (field_id, field) in schema.fields().iter().enumerate() {
+            let Some(wasm_id) = options.column_wasm(field_id, field.name()) else {
+                continue;
+            };
+            let wasm_id = *wasm_ids
+                .get(wasm_id.0 as usize)
+                .ok_or(Error::IndexOutOfBound(wasm_id.0 as usize, wasm_ids.len()))?;
+            if !matches!(field.data_type(), non_nest_types!()) {
+                return nyi_err!(format!("WASM encoder for {} columns", field.data_type()));
+            }
+            let data_type = physical_type(field.data_type());
+            if wasm_context
+                .decode_support(wasm_id, &data_type, fb::EncodingType::CUSTOM_WASM)
+                .wasm_refuses()
+            {
+                return Err(Error::General(format!(
+                    "WASM binary {} does not decode {data_type} EncUnits of column {}",
+                    wasm_id.0,
+                    field.name()
+                )));
+            }
+            column_wasm_ids.insert(field_id, wasm_id);
+        }
+        let wasm_hashes = wasm_context
+            .get_sorted_wasms()
+            .into_iter()
+            .map(wasm_module_hash)
+            .collect::<Vec<_>>();
+        if let Some(hash) = options
+            .wasm_references()
+            .keys()
+            .find(|hash| !wasm_hashes.contains(hash))
+        {
+            return Err(general_error!(format!(
+                "No WASM binary to store by reference with hash {hash:016x}"
+            )));
+        }
+        if let Some(&column) = options
+            .bloom_filter_columns()
+            .iter()
+            .find(|column| **column >= schema.fields().len())
+        {
+            return Err(Error::IndexOutOfBound(column, schema.fields().len()));
+        }
+        let mut family_of_column = HashMap::new();
+        for (family, columns) in options.column_families().iter().enumerate() {
+            for &column in columns {
+                if column >= schema.fields().len() {
+                    return Err(Error::IndexOutOfBound(column, schema.fields().len()));
+                }
+                if family_of_column.insert(column, family).is_some() {
+                    return Err(general_error!(format!(
+                        "Column {column} is in more than one column family"
+                    )));
+                }
+            }
+        }
+        let encryptor = |key_id: &str| match (options.key_provider(), options.key_retriever()) {
+            (Some(key_provider), _) => Encryptor::try_new_envelope(key_provider.as_ref(), key_id),
+            (None, Some(key_retriever)) => Encryptor::try_new(key_retriever.as_ref(), key_id),
+            (None, None) => Err(general_error!(
+                "Encryption needs a key retriever or a key provider"
+            )),
+        };
+        let mut column_encryptors = HashMap::new();
+        for (&column, key_id) in options.encrypted_columns() {
+            if column >= schema.fields().len() {
+                return Err(Error::IndexOutOfBound(column, schema.fields().len()));
+            }
+            if options.bloom_filter_columns().contains(&column) {
+                return Err(general_error!(format!(
+                    "Column {column} is encrypted and cannot have a bloom filter"
+                )));
+            }
+            column_encryptors.insert(column, encryptor(key_id)?);
+        }
+        if !column_encryptors.is_empty()
+            && options.dictionary_type() != DictionaryTypeOptions::EncoderDictionary
+        {
+            // Shared dictionaries are written apart from the chunks of their columns.
+            return nyi_err!("Encrypted columns with shared dictionaries");
+        }
+        let footer_encryptor = options.footer_key_id().map(encryptor).transpose()?;
+        check_supported(options.compression_type())?;
+        check_supported(options.metadata_compression())?;
+        if options.encoding_parallelism() == 0 {
+            return Err(general_error!("Encoding parallelism must be at least 1"));
+        }
+        if options.zstd_dictionary_size().is_some()
+            && options.compression_type() != fb::CompressionType::Zstd
+        {
+            return Err(general_error!("Zstd dictionaries need Zstd compression"));
+        }
+        let sort_order = SortColumn::try_from_names(&schema, options.sort_by())?;
+        let schema_index = options
+            .budgeted_metadata()
+            .map(|_| serialize_schema_index(&schema))
+            .transpose()?;
+        let wasm_context = Arc::new(wasm_context);
+        let mut bloom_filter_columns = HashMap::new();
+        let mut column_families = HashMap::new();
+        let mut encryptors = HashMap::new();
         let mut column_encoders = vec![];
         let mut child_trees = vec![];
         let shared_dictionary_context = SharedDictionaryContext::new(
             options.encoding_unit_len(),
             options.iounit_size(),
-            options.dictionary_type() == DictionaryTypeOptions::GlobalDictionaryMultiColSharing,
-            options.compression_type(),
+            options.dictionary_type(),
+            options.compression(),
         );
         for (field_id, field) in schema.fields().iter().enumerate() {
+            if options.bloom_filter_columns().contains(&field_id) {
+                if !matches!(field.data_type(), non_nest_types!()) {
+                    return nyi_err!(format!("Bloom filters of {} columns", field.data_type()));
+                }
+                bloom_filter_columns.insert(field_id, column_idx.get_current_index());
+            }
+            let first_column_index = column_idx.get_current_index();
             let (encoder, child_tree) = create_logical_encoder(
                 Arc::clone(field),
                 field_id as i32,
-                options.iounit_size(),
+                options.column_chunk_size(field_id),
                 &mut column_idx,
-                wasm_context.clone(),
+                match column_wasm_ids.get(&field_id) {
+                    Some(&wasm_id) => Arc::new(wasm_context.for_column(wasm_id)?),
+                    None => wasm_context.clone(),
+                },
                 options.dictionary_type(),
-                options.compression_type(),
+                options.compression(),
             )?;
+            if let Some(&family) = family_of_column.get(&field_id) {
+                for column_index in first_column_index..column_idx.get_current_index() {
+                    column_families.insert(column_index, family);
+                }
+            }
+            if let Some(encryptor) = column_encryptors.get(&field_id) {
+                for column_index in first_column_index..column_idx.get_current_index() {
+                    encryptors.insert(column_index, encryptor.clone());
+                }
+            }
             column_encoders.push(encoder);
             child_trees.push(child_tree);
         }
@@ -237,79 +784,1208 @@ impl<W: Write + Seek> FileWriter<W> {
                 data_checksum: create_checksum(&checksum_type),
                 column_counters: vec![EncodingCounter::default(); num_physical_columns],
                 enable_io_unit_checksum: options.enable_io_unit_checksum(),
+                statistics_truncate_length: options.statistics_truncate_length(),
+                min_file_size: 0,
+                encunit_index: options.write_encunit_index().then(Vec::new),
+                bloom_filter_hashes: bloom_filter_columns
+                    .values()
+                    .map(|&column_index| (column_index, VecDeque::new()))
+                    .collect(),
+                bloom_filter_fpp: options.bloom_filter_fpp(),
+                bloom_filters: vec![],
+                column_families,
+                copied_chunks: HashMap::new(),
+                families: options
+                    .column_families()
+                    .iter()
+                    .map(|columns| ColumnFamily {
+                        columns: columns.iter().map(|&column| column as u32).collect(),
+                        iounits: vec![],
+                    })
+                    .collect(),
+                family_buffers: options
+                    .column_families()
+                    .iter()
+                    .map(|_| Default::default())
+                    .collect(),
+                iounit_size: options.iounit_size(),
+                encryptors,
+                zstd_dictionary: options
+                    .zstd_dictionary_size()
+                    .map(|max_size| (options.compression(), max_size)),
             },
             schema_checksum: create_checksum(&checksum_type),
             wasm_context,
             custom_encunit_len: options.custom_encunit_len().clone(),
             row_group_size: options.row_group_size(),
+            memory_budget: options.memory_budget(),
+            max_buffered_bytes: options.max_buffered_bytes(),
+            reservation: options
+                .memory_pool()
+                .map(|pool| MemoryReservation::new(pool.clone(), "FileWriter")),
+            encoding_parallelism: match options.dictionary_type() {
+                DictionaryTypeOptions::NoDictionary
+                | DictionaryTypeOptions::EncoderDictionary
+                | DictionaryTypeOptions::LocalDictionary => options.encoding_parallelism(),
+                // The dictionaries of all columns are built in the same context.
+                _ => 1,
+            },
+            footer_padding: options.footer_padding(),
+            metadata_compression: options.metadata_compression(),
+            column_wasm_ids,
+            wasm_references: options.wasm_references().clone(),
+            write_wasm_aot: options.write_wasm_aot(),
+            wasm_aot_artifacts: options.wasm_aot_artifacts().clone(),
+            bloom_filter_columns,
+            previous_version_size: None,
+            metadata_segment_columns: options.budgeted_metadata(),
+            schema_index,
+            footer_encryptor,
+            key_value_metadata: KeyValueMetadata::default(),
+            footer_sort_order: sort_order.clone(),
+            sort_order,
+            sort_buffer: vec![],
+            input_validation: options.input_validation(),
+            chunk_size_sample: options
+                .auto_column_chunk_size()
+                .map(|auto| ChunkSizeSample {
+                    auto,
+                    num_rows: 0,
+                    flushed_bytes: vec![0; schema.fields().len()],
+                }),
             shared_dictionary_context,
         })
     }
 
-    pub fn write_batch(&mut self, batch: &RecordBatch) -> Result<()> {
-        // push each array into the column writer
-        // the logic of metadata should also be in the column writer
-        for (i, col) in batch.columns().iter().enumerate() {
-            let encoder = self.column_encoders[i].as_mut();
-            // TODO: currently this is for research experiments.
-            // A detailed API similar to Parquet's write_batch with correct internal buffer should be added.
-            // Currently requires the input batch size to be the multiple of the custom encunit size.
-            if let Some(encunit_len) = self.custom_encunit_len.get(&i) {
-                if batch.num_rows() < *encunit_len {
-                    return nyi_err!("Batch size should be larger than the custom encunit size");
+    /// Open an existing F3 file to append new row groups to it.
+    ///
+    /// `reader` and `writer` must refer to the same file. The existing footer is parsed and
+    /// `schema` is validated against the schema of the file. Writing resumes right after the last
+    /// data byte, and `finish` rewrites the WASM binaries, metadata, footer and postscript.
+    /// The options must produce the same WASM binaries as the existing file.
+    ///
+    /// With [footer versioning](crate::options::FileWriterOptionsBuilder::set_footer_versioning),
+    /// writing resumes at the end of the file instead, so that the existing footer stays
+    /// readable with [`open_at_version`](crate::reader::open_at_version).
+    pub fn try_append<R: Reader>(
+        schema: SchemaRef,
+        reader: R,
+        mut writer: W,
+        options: FileWriterOptions,
+    ) -> Result<Self> {
+        let existing = read_existing_footer(&reader, "Appending to")?;
+        let footer_fbs = existing.footer()?;
+        let (existing_schema, _logical_tree, row_groups, _, optional_sections, _) =
+            parse_footer(&footer_fbs)?;
+        check_append_schema(&existing_schema, &schema)?;
+        ensure_not_segmented(optional_sections, "Appending to the row groups")?;
+
+        // Rebuild the writer-side row groups table from the existing metadata.
+        let (existing_row_groups, data_end) =
+            read_existing_row_groups(&row_groups, &existing.metadata, existing.data_size)?;
+        let mut row_groups_table = RowGroupsTable::default();
+        let mut num_rows_in_file = 0;
+        for row_group in existing_row_groups {
+            num_rows_in_file += row_group.row_count as u64;
+            row_groups_table.add_meta(
+                row_group.row_count,
+                row_group.offset,
+                row_group.size,
+                RowGroupMetadata::new(row_group.column_metadatas),
+            );
+        }
+
+        // The WASM binaries are rewritten by `finish`, and existing EncUnits refer to them by id.
+        let existing_wasms = read_wasm_binaries(&reader, optional_sections)?;
+        let existing_sections = read_existing_sections(&reader, optional_sections)?;
+
+        // Keep the whole file, existing metadata included, to keep its footer as a version.
+        let footer_versioning = options.footer_versioning();
+        let data_end = if footer_versioning {
+            existing.file_size
+        } else {
+            data_end
+        };
+
+        // The data checksum cannot be resumed from the postscript, recompute it over kept data.
+        let data_checksum = checksum_data(&reader, data_end, &options.checksum_type(), |_| Ok(()))?;
+
+        writer.seek(SeekFrom::Start(data_end))?;
+        let existing_schema = Arc::new(existing_schema);
+        let mut file_writer = Self::try_new(existing_schema.clone(), writer, options)?;
+        file_writer.check_existing_wasms(&existing_wasms)?;
+        file_writer.key_value_metadata = KeyValueMetadata::from_fb(&footer_fbs)?;
+        // The new row groups are sorted as the existing ones only with the same order.
+        if sort_order::from_fb(&footer_fbs) != file_writer.sort_order {
+            file_writer.footer_sort_order.clear();
+        }
+        if let Some(row_group) = row_groups_table.row_group_metadata().first() {
+            if row_group.col_metadatas().len() != file_writer.state.num_physical_columns {
+                return Err(general_error!(
+                    "Number of physical columns does not match the existing file"
+                ));
+            }
+        }
+        let column_ids = (0..existing_schema.fields().len()).collect::<Vec<_>>();
+        file_writer.carry_existing_sections(
+            existing_sections,
+            &existing_schema,
+            &column_ids,
+            false,
+        );
+        let state = &mut file_writer.state;
+        state.row_groups_table = row_groups_table;
+        state.num_rows_in_file = num_rows_in_file;
+        state.data_checksum = data_checksum;
+        state.start_offset_of_cur_row_group = data_end;
+        if footer_versioning {
+            file_writer.previous_version_size = Some(existing.file_size);
+        } else {
+            state.min_file_size = existing.file_size;
+        }
+        Ok(file_writer)
+    }
+
+    /// Rewrite the F3 file read by `reader` to `writer` with its column chunks regrouped into
+    /// IO units of `options.iounit_size()`, e.g., to re-target a file from local SSDs to S3.
+    ///
+    /// EncUnits are self-contained, so their bytes are copied without re-encoding and only the
+    /// metadata is rewritten. Row groups are kept, and so are chunks with a local dictionary, a
+    /// bloom filter or indexed EncUnits, which keep their records.
+    /// As with [`Self::try_append`], the options must produce the same WASM binaries as the file.
+    pub fn repack<R: Reader>(reader: R, writer: W, options: FileWriterOptions) -> Result<()> {
+        let iounit_size = options.iounit_size();
+        let existing = read_existing_footer(&reader, "Repacking")?;
+        let footer_fbs = existing.footer()?;
+        let (schema, _logical_tree, row_groups, _, optional_sections, _) =
+            parse_footer(&footer_fbs)?;
+        let existing_wasms = read_wasm_binaries(&reader, optional_sections)?;
+        let existing_sections = read_existing_sections(&reader, optional_sections)?;
+
+        let schema = Arc::new(schema);
+        let mut file_writer = Self::try_new(schema.clone(), writer, options)?;
+        file_writer.check_existing_wasms(&existing_wasms)?;
+        file_writer.key_value_metadata = KeyValueMetadata::from_fb(&footer_fbs)?;
+        file_writer.footer_sort_order = sort_order::from_fb(&footer_fbs);
+        let column_ids = (0..schema.fields().len()).collect::<Vec<_>>();
+        file_writer.carry_existing_sections(existing_sections, &schema, &column_ids, true);
+        let state = &mut file_writer.state;
+        for (i, (row_group_meta, row_count)) in row_groups
+            .row_group_metadatas()
+            .ok_or_else(|| Error::ParseError("Row group metadatas not found".to_string()))?
+            .iter()
+            .zip(
+                row_groups
+                    .row_counts()
+                    .ok_or_else(|| Error::ParseError("Row counts not found".to_string()))?,
+            )
+            .enumerate()
+        {
+            // The last row group is finished by `finish`.
+            if i > 0 {
+                state.finish_row_group()?;
+            }
+            let col_metadatas = row_group_meta
+                .col_metadatas()
+                .ok_or_else(|| Error::ParseError("Column metadatas not found".to_string()))?;
+            if col_metadatas.len() != state.num_physical_columns {
+                return Err(general_error!(
+                    "Number of physical columns does not match the schema"
+                ));
+            }
+            for (column_index, section) in col_metadatas.iter().enumerate() {
+                let start = (section.offset() - existing.data_size) as usize;
+                let column_meta = flatbuffers::root::<fb::ColumnMetadata>(
+                    &existing.metadata[start..start + section.size_() as usize],
+                )?;
+                state.repack_column_chunks(
+                    &reader,
+                    column_index as u32,
+                    column_meta,
+                    iounit_size,
+                )?;
+            }
+            state.num_rows_in_cur_row_group = row_count;
+            state.num_rows_in_file += row_count as u64;
+        }
+        file_writer.finish()?;
+        Ok(())
+    }
+
+    /// Write the F3 file read by `reader` to `writer` with the columns of `new_columns` appended
+    /// to its schema, e.g., to backfill a feature column.
+    ///
+    /// The data of the existing columns is copied verbatim and only the metadata is rewritten,
+    /// so nothing is re-encoded. `new_columns` must have as many rows as the file, and its
+    /// batches are split along the existing row groups. As with [`Self::try_append`], the
+    /// options must produce the same WASM binaries as the file.
+    pub fn add_columns<R: Reader>(
+        reader: R,
+        mut writer: W,
+        new_columns: impl IntoIterator<Item = RecordBatch>,
+        options: FileWriterOptions,
+    ) -> Result<()> {
+        let existing = read_existing_footer(&reader, "Adding columns to")?;
+        let footer_fbs = existing.footer()?;
+        let (existing_schema, _logical_tree, row_groups, _, optional_sections, _) =
+            parse_footer(&footer_fbs)?;
+        let mut new_columns = new_columns.into_iter().peekable();
+        let new_schema = new_columns
+            .peek()
+            .ok_or_else(|| general_error!("No new columns to add"))?
+            .schema();
+        if let Some(field) = new_schema
+            .fields()
+            .iter()
+            .find(|field| existing_schema.field_with_name(field.name()).is_ok())
+        {
+            return Err(general_error!(format!(
+                "Column {} already exists in the file",
+                field.name()
+            )));
+        }
+        let (existing_row_groups, data_end) =
+            read_existing_row_groups(&row_groups, &existing.metadata, existing.data_size)?;
+        let existing_wasms = read_wasm_binaries(&reader, optional_sections)?;
+        let existing_sections = read_existing_sections(&reader, optional_sections)?;
+
+        // Chunk offsets in the existing metadata stay valid as the data is copied as is.
+        let data_checksum = checksum_data(&reader, data_end, &options.checksum_type(), |buf| {
+            Ok(writer.write_all(buf)?)
+        })?;
+
+        let num_existing_columns = existing_schema.fields().len();
+        let schema = Schema::new_with_metadata(
+            existing_schema
+                .fields()
+                .iter()
+                .chain(new_schema.fields())
+                .cloned()
+                .collect::<Vec<_>>(),
+            existing_schema.metadata().clone(),
+        );
+        let mut file_writer = Self::try_new(Arc::new(schema), writer, options)?;
+        file_writer.check_existing_wasms(&existing_wasms)?;
+        file_writer.key_value_metadata = KeyValueMetadata::from_fb(&footer_fbs)?;
+        // The new columns come last, so the existing ones keep their index.
+        file_writer.footer_sort_order = sort_order::from_fb(&footer_fbs);
+        let column_ids = (0..num_existing_columns).collect::<Vec<_>>();
+        file_writer.carry_existing_sections(
+            existing_sections,
+            &existing_schema,
+            &column_ids,
+            false,
+        );
+        file_writer.state.data_checksum = data_checksum;
+        file_writer.state.start_offset_of_cur_row_group = data_end;
+        let mut pending: Option<RecordBatch> = None;
+        for row_group in existing_row_groups {
+            let num_existing_physical_columns = row_group.column_metadatas.len();
+            if num_existing_physical_columns > file_writer.state.num_physical_columns {
+                return Err(general_error!(
+                    "Number of physical columns does not match the existing file"
+                ));
+            }
+            file_writer.state.column_metadatas_in_cur_row_group[..num_existing_physical_columns]
+                .clone_from_slice(&row_group.column_metadatas);
+            let mut remaining = row_group.row_count as usize;
+            while remaining > 0 {
+                let batch = match pending.take() {
+                    Some(batch) => batch,
+                    None => new_columns.next().ok_or_else(|| {
+                        general_error!("New columns have fewer rows than the file")
+                    })?,
+                };
+                if batch.schema() != new_schema {
+                    return Err(general_error!("Schema of the new columns changed"));
                 }
-                for j in 0..(batch.num_rows() / encunit_len) {
-                    // slice col to the correct range, then encode
-                    let col_sliced = col.slice(
-                        j * encunit_len,
-                        std::cmp::min(*encunit_len, col.len() - j * encunit_len),
-                    );
-                    if let Some(res) = encoder.encode(
-                        col_sliced.clone(),
-                        &mut self.state.column_counters[i],
-                        &mut self.shared_dictionary_context,
-                    )? {
-                        res.into_iter()
-                            .try_for_each(|chunk| self.state.flush_chunk(chunk))?;
-                    };
+                let len = remaining.min(batch.num_rows());
+                file_writer.encode_columns(num_existing_columns, &batch.slice(0, len))?;
+                if len < batch.num_rows() {
+                    pending = Some(batch.slice(len, batch.num_rows() - len));
                 }
-            } else if let Some(res) = encoder.encode(
+                remaining -= len;
+            }
+            file_writer.flush_pending_chunks()?;
+            let state = &mut file_writer.state;
+            state.num_rows_in_cur_row_group = row_group.row_count;
+            state.num_rows_in_file += row_group.row_count as u64;
+            state.finish_row_group()?;
+        }
+        if pending
+            .into_iter()
+            .chain(new_columns)
+            .any(|batch| batch.num_rows() > 0)
+        {
+            return Err(general_error!("New columns have more rows than the file"));
+        }
+        file_writer.finish()?;
+        Ok(())
+    }
+
+    /// Write the F3 file read by `reader` to `writer` with the min/max statistics of its chunks
+    /// recomputed, e.g., to upgrade a file written before they existed so that readers can prune
+    /// it. The bloom filters of `options.bloom_filter_columns()` and, if enabled, the EncUnit
+    /// index with its zone maps are computed along.
+    ///
+    /// The file is scanned once, decoding only the non-nested columns that get statistics, and
+    /// its data is kept as is. With `in_place`, `reader` and `writer` must refer to the same file,
+    /// and only what follows the data is rewritten. The new metadata then takes the place of the
+    /// region reserved by
+    /// [`FileWriterOptionsBuilder::set_footer_padding`](crate::options::FileWriterOptionsBuilder::set_footer_padding),
+    /// if any, instead of reserving padding again, so that the file keeps its size as long as the
+    /// new metadata fits in the padding and the existing metadata. As with [`Self::try_append`],
+    /// the options must produce the same WASM binaries as the file.
+    pub fn backfill_statistics<R: Reader + Clone>(
+        reader: R,
+        mut writer: W,
+        in_place: bool,
+        options: FileWriterOptions,
+    ) -> Result<()> {
+        let existing = read_existing_footer(&reader, "Backfilling the statistics of")?;
+        let footer_fbs = existing.footer()?;
+        let (schema, _logical_tree, row_groups, _, optional_sections, _) =
+            parse_footer(&footer_fbs)?;
+        ensure_not_segmented(optional_sections, "Backfilling the statistics")?;
+        let (existing_row_groups, data_end) =
+            read_existing_row_groups(&row_groups, &existing.metadata, existing.data_size)?;
+        let existing_wasms = read_wasm_binaries(&reader, optional_sections)?;
+        let reserved_padding = get_reserved_padding(&reader)?;
+
+        // Chunk offsets in the existing metadata stay valid as the data is kept as is.
+        let data_checksum = if in_place {
+            let data_checksum =
+                checksum_data(&reader, data_end, &options.checksum_type(), |_| Ok(()))?;
+            writer.seek(SeekFrom::Start(data_end))?;
+            data_checksum
+        } else {
+            checksum_data(&reader, data_end, &options.checksum_type(), |buf| {
+                Ok(writer.write_all(buf)?)
+            })?
+        };
+
+        let statistics_truncate_length = options.statistics_truncate_length();
+        let bloom_filter_fpp = options.bloom_filter_fpp();
+        let bloom_filter_fields = options.bloom_filter_columns().to_vec();
+        let zone_maps = options.write_encunit_index();
+        let schema = Arc::new(schema);
+        let mut file_writer = Self::try_new(schema.clone(), writer, options)?;
+        file_writer.check_existing_wasms(&existing_wasms)?;
+        file_writer.key_value_metadata = KeyValueMetadata::from_fb(&footer_fbs)?;
+        file_writer.footer_sort_order = sort_order::from_fb(&footer_fbs);
+        let num_physical_columns = file_writer.state.num_physical_columns;
+        if existing_row_groups
+            .iter()
+            .any(|row_group| row_group.column_metadatas.len() != num_physical_columns)
+        {
+            return Err(general_error!(
+                "Number of physical columns does not match the existing file"
+            ));
+        }
+
+        // The top-level columns to decode, with their physical column index. Encrypted chunks
+        // get no statistics, as they would leak their values.
+        let mut decoded_columns = vec![];
+        let mut bloom_filter_columns = HashSet::new();
+        let mut column_index = 0;
+        for (field_id, field) in schema.fields().iter().enumerate() {
+            let encrypted = existing_row_groups.iter().any(|row_group| {
+                row_group.column_metadatas[column_index]
+                    .chunks()
+                    .iter()
+                    .any(Chunk::is_encrypted)
+            });
+            let bloom_filter = bloom_filter_fields.contains(&field_id);
+            if encrypted && bloom_filter {
+                return Err(general_error!(format!(
+                    "Column {field_id} is encrypted and cannot have a bloom filter"
+                )));
+            }
+            let has_statistics = matches!(
+                field.data_type(),
+                DataType::Utf8 | DataType::LargeUtf8 | DataType::Binary | DataType::LargeBinary
+            ) || (zone_maps
+                && sortable_key_width(field.data_type()).is_some());
+            if !encrypted && (bloom_filter || has_statistics) {
+                decoded_columns.push((field_id, column_index));
+            }
+            if bloom_filter {
+                bloom_filter_columns.insert(column_index);
+            }
+            column_index += num_physical_columns(field.data_type());
+        }
+        let mut file_reader = FileReaderV2Builder::new(reader)
+            .with_projections(Projection::new(
+                decoded_columns
+                    .iter()
+                    .map(|&(field_id, _)| field_id)
+                    .collect::<Vec<_>>(),
+            ))
+            .build()?;
+
+        let state = &mut file_writer.state;
+        for (row_group, existing_row_group) in existing_row_groups.into_iter().enumerate() {
+            let batches = if decoded_columns.is_empty() || existing_row_group.row_count == 0 {
+                vec![]
+            } else {
+                file_reader.read_row_group(row_group)?
+            };
+            let mut arrays = HashMap::new();
+            if !batches.is_empty() {
+                for (i, &(_, column_index)) in decoded_columns.iter().enumerate() {
+                    let columns: Vec<_> = batches
+                        .iter()
+                        .map(|batch| batch.column(i).as_ref())
+                        .collect();
+                    arrays.insert(column_index, concat(&columns)?);
+                }
+            }
+            let mut column_metadatas = existing_row_group.column_metadatas;
+            for (column_index, column_metadata) in column_metadatas.iter_mut().enumerate() {
+                let array = arrays.get(&column_index);
+                let num_rows: u64 = column_metadata.chunks().iter().map(Chunk::num_rows).sum();
+                if array.is_some_and(|array| array.len() as u64 != num_rows) {
+                    return Err(general_error!(format!(
+                        "Chunks of column {column_index} in row group {row_group} do not match \
+                         its rows"
+                    )));
+                }
+                let mut backfilled = ColumnMetadata::default();
+                let mut first_row = 0;
+                for (chunk_idx, chunk) in column_metadata.chunks().iter().enumerate() {
+                    let values =
+                        array.map(|array| array.slice(first_row, chunk.num_rows() as usize));
+                    first_row += chunk.num_rows() as usize;
+                    let mut chunk = chunk.clone();
+                    if let Some(values) = &values {
+                        let mut min_max = MinMaxAccumulator::default();
+                        min_max.update(values.as_ref());
+                        chunk = chunk.with_statistics(min_max.finish(statistics_truncate_length));
+                        if bloom_filter_columns.contains(&column_index) {
+                            let hashes: HashSet<u64> = hash_values(values.as_ref())?
+                                .into_iter()
+                                .flatten()
+                                .collect();
+                            let mut filter = BloomFilter::new(hashes.len(), bloom_filter_fpp);
+                            hashes.into_iter().for_each(|hash| filter.insert_hash(hash));
+                            state.bloom_filters.push(ChunkBloomFilter {
+                                column_index: column_index as u32,
+                                chunk_offset: chunk.offset(),
+                                filter,
+                            });
+                        }
+                    }
+                    if let Some(index) = &mut state.encunit_index {
+                        let mut encunits = encunit_entries(
+                            chunk
+                                .encunits()
+                                .iter()
+                                .map(|unit| (unit.num_rows(), unit.size())),
+                        );
+                        let encunit_rows: u64 =
+                            encunits.iter().map(|entry| entry.num_rows as u64).sum();
+                        if let Some(values) = values.filter(|_| encunit_rows == chunk.num_rows()) {
+                            for entry in &mut encunits {
+                                let mut zone_map = MinMaxAccumulator::default();
+                                zone_map.update_zone_map(
+                                    values
+                                        .slice(entry.first_row as usize, entry.num_rows as usize)
+                                        .as_ref(),
+                                );
+                                entry.statistics = zone_map.finish(statistics_truncate_length);
+                            }
+                        }
+                        index.push(ChunkEncUnitIndex {
+                            column_index: column_index as u32,
+                            row_group: row_group as u32,
+                            chunk: chunk_idx as u32,
+                            chunk_offset: chunk.offset(),
+                            encunits,
+                        });
+                    }
+                    backfilled.add_chunk(chunk);
+                }
+                *column_metadata = backfilled;
+            }
+            state.row_groups_table.add_meta(
+                existing_row_group.row_count,
+                existing_row_group.offset,
+                existing_row_group.size,
+                RowGroupMetadata::new(column_metadatas),
+            );
+            state.num_rows_in_file += existing_row_group.row_count as u64;
+        }
+        state.data_checksum = data_checksum;
+        state.start_offset_of_cur_row_group = data_end;
+        if in_place {
+            state.min_file_size = existing.file_size;
+            if reserved_padding.is_some() {
+                file_writer.footer_padding = 0;
+            }
+        }
+        file_writer.finish()?;
+        Ok(())
+    }
+
+    /// Write the F3 file read by `reader` to `writer` without the top-level columns named
+    /// `columns`.
+    ///
+    /// Only the metadata is rewritten: the data of the dropped columns is left in the file,
+    /// unreferenced, see [`get_unreferenced_bytes`](crate::reader::get_unreferenced_bytes).
+    /// With `compact`, only the chunks of the kept columns are copied instead, regrouped as by
+    /// [`Self::repack`]. As with [`Self::try_append`], the options must produce the same WASM
+    /// binaries as the file.
+    pub fn drop_columns<R: Reader>(
+        reader: R,
+        writer: W,
+        columns: &[&str],
+        compact: bool,
+        options: FileWriterOptions,
+    ) -> Result<()> {
+        Self::rewrite_columns(
+            reader,
+            writer,
+            |schema| {
+                if let Some(name) = columns
+                    .iter()
+                    .find(|name| schema.field_with_name(name).is_err())
+                {
+                    return Err(general_error!(format!("Column {name} not found")));
+                }
+                Ok(schema
+                    .fields()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, field)| !columns.contains(&field.name().as_str()))
+                    .map(|(i, field)| (i, field.clone()))
+                    .collect())
+            },
+            compact,
+            options,
+        )
+    }
+
+    /// Write the F3 file read by `reader` to `writer` with the top-level columns renamed
+    /// according to the `(old name, new name)` pairs of `renames`. Only the metadata is
+    /// rewritten, the data is copied as is.
+    pub fn rename_columns<R: Reader>(
+        reader: R,
+        writer: W,
+        renames: &[(&str, &str)],
+        options: FileWriterOptions,
+    ) -> Result<()> {
+        Self::rewrite_columns(
+            reader,
+            writer,
+            |schema| {
+                if let Some((name, _)) = renames
+                    .iter()
+                    .find(|(name, _)| schema.field_with_name(name).is_err())
+                {
+                    return Err(general_error!(format!("Column {name} not found")));
+                }
+                let fields: Vec<_> = schema
+                    .fields()
+                    .iter()
+                    .map(|field| {
+                        match renames
+                            .iter()
+                            .find(|(name, _)| field.name().as_str() == *name)
+                        {
+                            Some((_, new_name)) => {
+                                Arc::new(field.as_ref().clone().with_name(*new_name))
+                            }
+                            None => field.clone(),
+                        }
+                    })
+                    .collect();
+                if fields
+                    .iter()
+                    .enumerate()
+                    .any(|(i, field)| fields[..i].iter().any(|f| f.name() == field.name()))
+                {
+                    return Err(general_error!("Renamed columns must have distinct names"));
+                }
+                Ok(fields.into_iter().enumerate().collect())
+            },
+            false,
+            options,
+        )
+    }
+
+    /// Write the file read by `reader` with the top-level columns returned by `columns`, as
+    /// pairs of the index of the column in the existing schema and its new field.
+    fn rewrite_columns<R: Reader>(
+        reader: R,
+        mut writer: W,
+        columns: impl FnOnce(&Schema) -> Result<Vec<(usize, FieldRef)>>,
+        compact: bool,
+        options: FileWriterOptions,
+    ) -> Result<()> {
+        let iounit_size = options.iounit_size();
+        let existing = read_existing_footer(&reader, "Rewriting the columns of")?;
+        let footer_fbs = existing.footer()?;
+        let (existing_schema, _logical_tree, row_groups, _, optional_sections, _) =
+            parse_footer(&footer_fbs)?;
+        ensure_not_segmented(optional_sections, "Rewriting the columns")?;
+        let columns = columns(&existing_schema)?;
+        let column_ids = columns.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+        let mut key_value_metadata = KeyValueMetadata::from_fb(&footer_fbs)?;
+        key_value_metadata.select_columns(&column_ids);
+        let footer_sort_order =
+            sort_order::select_columns(&sort_order::from_fb(&footer_fbs), &column_ids);
+        // Physical columns of each top-level column of the existing file.
+        let physical_columns = physical_column_ranges(&existing_schema);
+        let kept_physical_columns = columns
+            .iter()
+            .flat_map(|(i, _)| physical_columns[*i].clone())
+            .collect::<Vec<_>>();
+        let (existing_row_groups, data_end) =
+            read_existing_row_groups(&row_groups, &existing.metadata, existing.data_size)?;
+        let existing_wasms = read_wasm_binaries(&reader, optional_sections)?;
+        let existing_sections = read_existing_sections(&reader, optional_sections)?;
+
+        let data_checksum = if compact {
+            None
+        } else {
+            // Chunk offsets in the existing metadata stay valid as the data is copied as is.
+            Some(checksum_data(
+                &reader,
+                data_end,
+                &options.checksum_type(),
+                |buf| Ok(writer.write_all(buf)?),
+            )?)
+        };
+        let schema = Schema::new_with_metadata(
+            columns
+                .into_iter()
+                .map(|(_, field)| field)
+                .collect::<Vec<_>>(),
+            existing_schema.metadata().clone(),
+        );
+        let mut file_writer = Self::try_new(Arc::new(schema), writer, options)?;
+        file_writer.check_existing_wasms(&existing_wasms)?;
+        file_writer.key_value_metadata = key_value_metadata;
+        file_writer.footer_sort_order = footer_sort_order;
+        file_writer.carry_existing_sections(
+            existing_sections,
+            &existing_schema,
+            &column_ids,
+            compact,
+        );
+        let state = &mut file_writer.state;
+        if kept_physical_columns.len() != state.num_physical_columns
+            || physical_columns.last().map_or(0, |range| range.end)
+                != existing_row_groups
+                    .first()
+                    .map_or(0, |row_group| row_group.column_metadatas.len())
+        {
+            return Err(general_error!(
+                "Number of physical columns does not match the schema"
+            ));
+        }
+        if let Some(data_checksum) = data_checksum {
+            for row_group in existing_row_groups {
+                state.row_groups_table.add_meta(
+                    row_group.row_count,
+                    row_group.offset,
+                    row_group.size,
+                    RowGroupMetadata::new(
+                        kept_physical_columns
+                            .iter()
+                            .map(|&i| row_group.column_metadatas[i].clone())
+                            .collect(),
+                    ),
+                );
+                state.num_rows_in_file += row_group.row_count as u64;
+            }
+            state.data_checksum = data_checksum;
+            state.start_offset_of_cur_row_group = data_end;
+        } else {
+            for (i, (row_group_meta, row_count)) in row_groups
+                .row_group_metadatas()
+                .ok_or_else(|| Error::ParseError("Row group metadatas not found".to_string()))?
+                .iter()
+                .zip(
+                    row_groups
+                        .row_counts()
+                        .ok_or_else(|| Error::ParseError("Row counts not found".to_string()))?,
+                )
+                .enumerate()
+            {
+                // The last row group is finished by `finish`.
+                if i > 0 {
+                    state.finish_row_group()?;
+                }
+                let col_metadatas = row_group_meta
+                    .col_metadatas()
+                    .ok_or_else(|| Error::ParseError("Column metadatas not found".to_string()))?;
+                for (column_index, &existing_index) in kept_physical_columns.iter().enumerate() {
+                    let section = col_metadatas.get(existing_index);
+                    let start = (section.offset() - existing.data_size) as usize;
+                    let column_meta = flatbuffers::root::<fb::ColumnMetadata>(
+                        &existing.metadata[start..start + section.size_() as usize],
+                    )?;
+                    state.repack_column_chunks(
+                        &reader,
+                        column_index as u32,
+                        column_meta,
+                        iounit_size,
+                    )?;
+                }
+                state.num_rows_in_cur_row_group = row_count;
+                state.num_rows_in_file += row_count as u64;
+            }
+        }
+        file_writer.finish()?;
+        Ok(())
+    }
+
+    /// Encode the columns of `batch` as the top-level columns starting at `first_column`.
+    fn encode_columns(&mut self, first_column: usize, batch: &RecordBatch) -> Result<()> {
+        for (i, col) in batch.columns().iter().enumerate() {
+            let i = first_column + i;
+            self.hash_bloom_filter_values(i, col.as_ref())?;
+            if let Some(res) = self.column_encoders[i].encode(
                 col.clone(),
                 &mut self.state.column_counters[i],
                 &mut self.shared_dictionary_context,
             )? {
-                res.into_iter()
-                    .try_for_each(|chunk| self.state.flush_chunk(chunk))?;
+                self.flush_column_chunks(i, res)?;
             }
         }
-        self.state.num_rows_in_file += batch.num_rows() as u32;
-        self.state.num_rows_in_cur_row_group += batch.num_rows() as u32;
-        if self.state.num_rows_in_cur_row_group as u64 >= self.row_group_size {
-            self.flush_pending_chunks()?;
-            self.state.finish_row_group()?;
+        Ok(())
+    }
+
+    /// Queue the hashes of `array` for the bloom filters of the chunks of the top-level column
+    /// `field_id`, if it has any. Must be called before the array is encoded.
+    fn hash_bloom_filter_values(&mut self, field_id: usize, array: &dyn Array) -> Result<()> {
+        if let Some(column_index) = self.bloom_filter_columns.get(&field_id) {
+            self.state
+                .bloom_filter_hashes
+                .get_mut(column_index)
+                .unwrap()
+                .extend(hash_values(array)?);
         }
         Ok(())
     }
 
+    /// Carry the bloom filters, EncUnit index and column families of an existing file with the
+    /// schema `existing_schema`, whose column `column_ids[i]` is the top-level column `i` of this
+    /// file. With `moved`, the chunks are copied to other offsets by
+    /// [`FileWriteState::repack_column_chunks`], which carries their records along, and the IO
+    /// units of the families are left behind.
+    fn carry_existing_sections(
+        &mut self,
+        existing: ExistingSections,
+        existing_schema: &Schema,
+        column_ids: &[usize],
+        moved: bool,
+    ) {
+        let existing_physical_columns = physical_column_ranges(existing_schema);
+        let physical_columns = physical_column_ranges(&self.schema);
+        let mut new_columns = HashMap::new();
+        let mut new_physical_columns = HashMap::new();
+        for (i, &column_id) in column_ids.iter().enumerate() {
+            new_columns.insert(column_id as u32, i as u32);
+            for (existing_index, index) in existing_physical_columns[column_id]
+                .clone()
+                .zip(physical_columns[i].clone())
+            {
+                new_physical_columns.insert(existing_index as u32, index as u32);
+            }
+        }
+        let state = &mut self.state;
+        if existing.encunit_index.is_some() {
+            state.encunit_index.get_or_insert_with(Vec::new);
+        }
+        if moved {
+            for record in existing.bloom_filters {
+                let copied = state.copied_chunks.entry(record.chunk_offset).or_default();
+                copied.filter = Some(record.filter);
+            }
+            for record in existing.encunit_index.into_iter().flatten() {
+                let copied = state.copied_chunks.entry(record.chunk_offset).or_default();
+                copied.zone_maps =
+                    Some(record.encunits.into_iter().map(|e| e.statistics).collect());
+            }
+        } else {
+            state
+                .bloom_filters
+                .extend(existing.bloom_filters.into_iter().filter_map(|mut record| {
+                    record.column_index = *new_physical_columns.get(&record.column_index)?;
+                    Some(record)
+                }));
+            if let Some(index) = &mut state.encunit_index {
+                index.extend(existing.encunit_index.into_iter().flatten().filter_map(
+                    |mut record| {
+                        record.column_index = *new_physical_columns.get(&record.column_index)?;
+                        Some(record)
+                    },
+                ));
+            }
+        }
+        for family in existing.families {
+            let columns: Vec<u32> = family
+                .columns
+                .iter()
+                .filter_map(|column| new_columns.get(column).copied())
+                .collect();
+            if columns.is_empty() {
+                continue;
+            }
+            let iounits = if moved { vec![] } else { family.iounits };
+            if let Some(same) = state.families.iter_mut().find(|f| f.columns == columns) {
+                same.iounits.splice(0..0, iounits);
+                continue;
+            }
+            // The columns keep being grouped, unless the options put them in another family.
+            let position = state.families.len();
+            for &column in &columns {
+                for column_index in physical_columns[column as usize].clone() {
+                    state
+                        .column_families
+                        .entry(column_index as u32)
+                        .or_insert(position);
+                }
+            }
+            state.families.push(ColumnFamily { columns, iounits });
+            state.family_buffers.push(Default::default());
+        }
+    }
+
+    /// EncUnits copied from an existing file refer to its WASM binaries by id.
+    fn check_existing_wasms(&self, existing_wasms: &[Vec<u8>]) -> Result<()> {
+        if existing_wasms != self.wasm_context.get_sorted_wasms() {
+            return Err(general_error!(
+                "WASM binaries of the writer do not match the ones in the existing file"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Store `value` under `key` in the metadata of the file, replacing any previous value.
+    /// It is kept when appending to or rewriting the file, and read with
+    /// [`FileReaderV2::metadata`](crate::reader::FileReaderV2::metadata).
+    pub fn add_metadata(&mut self, key: impl Into<String>, value: impl Into<MetadataValue>) {
+        self.key_value_metadata.insert(key.into(), value.into());
+    }
+
+    /// Store `value` under `key` in the metadata of the top-level column `column`, replacing any
+    /// previous value.
+    pub fn add_column_metadata(
+        &mut self,
+        column: usize,
+        key: impl Into<String>,
+        value: impl Into<MetadataValue>,
+    ) -> Result<()> {
+        if column >= self.schema.fields().len() {
+            return Err(Error::IndexOutOfBound(column, self.schema.fields().len()));
+        }
+        self.key_value_metadata
+            .insert_column(column, key.into(), value.into());
+        Ok(())
+    }
+
+    pub fn write_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+        let batch = &validate_batch(batch, self.input_validation)?;
+        if self.sort_order.is_empty() {
+            return self.encode_batch(batch);
+        }
+        if batch.num_rows() > 0 {
+            self.sort_buffer.push(batch.clone());
+        }
+        if self.num_buffered_rows() >= self.row_group_size {
+            self.write_sort_buffer()?;
+        }
+        Ok(())
+    }
+
+    fn num_buffered_rows(&self) -> u64 {
+        self.sort_buffer
+            .iter()
+            .map(|batch| batch.num_rows() as u64)
+            .sum()
+    }
+
+    /// Sort the buffered batches and encode them, which fills the current row group.
+    fn write_sort_buffer(&mut self) -> Result<()> {
+        let batches = std::mem::take(&mut self.sort_buffer);
+        let Some(first) = batches.first() else {
+            return Ok(());
+        };
+        let batch = concat_batches(first.schema_ref(), &batches)?;
+        self.encode_batch(&sort_batch(&batch, &self.sort_order)?)
+    }
+
+    fn encode_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+        let num_rows_in_cur_row_group = checked_u32(
+            self.state.num_rows_in_cur_row_group as u64 + batch.num_rows() as u64,
+            "Number of rows in a row group",
+        )?;
+        // push each array into the column writer
+        // the logic of metadata should also be in the column writer
+        for (i, col) in batch.columns().iter().enumerate() {
+            self.hash_bloom_filter_values(i, col.as_ref())?;
+        }
+        if self.encoding_parallelism > 1 && batch.num_columns() > 1 {
+            let encoded = self.encode_columns_in_parallel(batch)?;
+            for (i, chunks) in encoded.into_iter().enumerate() {
+                self.flush_column_chunks(i, chunks)?;
+            }
+        } else {
+            for (i, col) in batch.columns().iter().enumerate() {
+                trace_span!("encode_column", column = i);
+                let chunks = encode_column(
+                    self.column_encoders[i].as_mut(),
+                    &mut self.state.column_counters[i],
+                    &mut self.shared_dictionary_context,
+                    col,
+                    self.custom_encunit_len.get(&i).copied(),
+                )?;
+                self.flush_column_chunks(i, chunks)?;
+            }
+        }
+        self.enforce_memory_budget()?;
+        if self
+            .max_buffered_bytes
+            .is_some_and(|max| self.buffered_bytes() > max)
+        {
+            self.flush()?;
+        }
+        self.state.num_rows_in_file += batch.num_rows() as u64;
+        self.state.num_rows_in_cur_row_group = num_rows_in_cur_row_group;
+        if self.state.num_rows_in_cur_row_group as u64 >= self.row_group_size {
+            self.write_row_group()?;
+        }
+        if let Some(sample) = &mut self.chunk_size_sample {
+            sample.num_rows += batch.num_rows() as u64;
+            if sample.num_rows >= sample.auto.sample_rows {
+                self.set_column_chunk_sizes_from_sample();
+            }
+        }
+        self.reserve_buffered_bytes()
+    }
+
+    /// Write the chunks of the top-level column `column`, accounting them in the sample of the
+    /// first rows if it is still encoded.
+    fn flush_column_chunks(
+        &mut self,
+        column: usize,
+        chunks: Vec<EncodedColumnChunk>,
+    ) -> Result<()> {
+        if let Some(sample) = &mut self.chunk_size_sample {
+            sample.flushed_bytes[column] +=
+                chunks.iter().map(EncodedColumnChunk::size).sum::<u64>();
+        }
+        chunks
+            .into_iter()
+            .try_for_each(|chunk| self.state.flush_chunk(chunk))
+    }
+
+    /// Size the chunks of each top-level column from the rows sampled so far, and record the
+    /// sizes in the metadata of the columns.
+    fn set_column_chunk_sizes_from_sample(&mut self) {
+        let Some(sample) = self.chunk_size_sample.take() else {
+            return;
+        };
+        for (i, encoder) in self.column_encoders.iter_mut().enumerate() {
+            let encoded_bytes = sample.flushed_bytes[i] + encoder.memory_size() as u64;
+            let chunk_size = sample.auto.chunk_size(
+                encoded_bytes,
+                sample.num_rows,
+                self.row_group_size,
+                self.state.iounit_size,
+            );
+            encoder.set_column_chunk_size(chunk_size);
+            self.key_value_metadata.insert_column(
+                i,
+                COLUMN_CHUNK_SIZE_KEY.to_string(),
+                chunk_size.into(),
+            );
+        }
+    }
+
+    /// Resize the memory reservation to the buffered data, flushing it if the pool refuses.
+    fn reserve_buffered_bytes(&mut self) -> Result<()> {
+        if self.reservation.is_none() {
+            return Ok(());
+        }
+        let size = self.buffered_bytes() as usize;
+        let reservation = self.reservation.as_mut().unwrap();
+        if reservation.try_resize(size).is_ok() {
+            return Ok(());
+        }
+        self.flush()?;
+        let size = self.buffered_bytes() as usize;
+        self.reservation.as_mut().unwrap().try_resize(size)
+    }
+
+    /// Encode the columns of `batch` on up to `encoding_parallelism` threads, each taking the
+    /// next column not encoded yet, and return the chunks of each column in column order.
+    fn encode_columns_in_parallel(
+        &mut self,
+        batch: &RecordBatch,
+    ) -> Result<Vec<Vec<EncodedColumnChunk>>> {
+        let columns = Mutex::new(
+            self.column_encoders
+                .iter_mut()
+                .zip(self.state.column_counters.iter_mut())
+                .zip(batch.columns())
+                .enumerate(),
+        );
+        let custom_encunit_len = &self.custom_encunit_len;
+        let num_threads = self.encoding_parallelism.min(batch.num_columns());
+        let mut encoded: Vec<_> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..num_threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        // Not shared across columns, as parallel encoding has no shared
+                        // dictionaries.
+                        let mut shared_dictionary_context = SharedDictionaryContext::default();
+                        let mut encoded = vec![];
+                        loop {
+                            // Unlock before encoding.
+                            let next = columns.lock().unwrap().next();
+                            let Some((i, ((encoder, counter), col))) = next else {
+                                break;
+                            };
+                            trace_span!("encode_column", column = i);
+                            let chunks = encode_column(
+                                encoder.as_mut(),
+                                counter,
+                                &mut shared_dictionary_context,
+                                col,
+                                custom_encunit_len.get(&i).copied(),
+                            );
+                            encoded.push((i, chunks));
+                        }
+                        encoded
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| {
+                    worker
+                        .join()
+                        .unwrap_or_else(|e| std::panic::resume_unwind(e))
+                })
+                .collect()
+        });
+        encoded.sort_by_key(|(i, _)| *i);
+        encoded.into_iter().map(|(_, chunks)| chunks).collect()
+    }
+
+    /// Size in bytes of the data buffered by the column encoders.
     pub fn memory_size(&self) -> usize {
         self.column_encoders.iter().map(|e| e.memory_size()).sum()
     }
 
-    /// For testing memory usage if we correctly implement row groups
+    /// Size in bytes of the data buffered by the writer and not written yet: the data of the
+    /// column encoders and the chunks of the column families waiting to fill an IO unit. Batches
+    /// buffered to be sorted are not included.
+    pub fn buffered_bytes(&self) -> u64 {
+        self.memory_size() as u64 + self.state.family_buffers_size()
+    }
+
+    /// Close the chunks accumulated by all column encoders and write them with the chunks
+    /// buffered by the column families, instead of waiting for them to fill up or for the row
+    /// group to end. Shared dictionary encoders spill their data to temporary files instead, as
+    /// their dictionaries are only known when the file is finished. The row group stays open,
+    /// and batches buffered to be sorted are not flushed.
+    ///
+    /// Called when [`FileWriterOptions::max_buffered_bytes`] is exceeded. Flushing often writes
+    /// more, smaller chunks and IO units.
+    pub fn flush(&mut self) -> Result<()> {
+        for i in 0..self.column_encoders.len() {
+            if let Some(res) = self.column_encoders[i].spill()? {
+                self.flush_column_chunks(i, res)?;
+            }
+        }
+        self.state.flush_families()
+    }
+
+    /// Spill the largest column encoders until the buffered data fits in the memory budget.
+    fn enforce_memory_budget(&mut self) -> Result<()> {
+        let Some(memory_budget) = self.memory_budget else {
+            return Ok(());
+        };
+        let mut column_ids: Vec<usize> = (0..self.column_encoders.len()).collect();
+        column_ids.sort_by_key(|&i| std::cmp::Reverse(self.column_encoders[i].memory_size()));
+        for i in column_ids {
+            if self.memory_size() as u64 <= memory_budget {
+                break;
+            }
+            if let Some(res) = self.column_encoders[i].spill()? {
+                self.flush_column_chunks(i, res)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of bytes flushed to the underlying writer so far.
+    pub fn bytes_written(&mut self) -> Result<u64> {
+        Ok(self.state.writer.stream_position()?)
+    }
+
+    /// Number of rows written to the file so far, including the ones buffered to be sorted.
+    pub fn num_rows(&self) -> u64 {
+        self.state.num_rows_in_file + self.num_buffered_rows()
+    }
+
+    /// Finish the chunks of all column encoders and write them, as done when a row group is
+    /// finished. Unlike [`Self::flush`], this also ends the dictionaries of the encoders, so
+    /// calling it in the middle of a row group writes more chunks than needed.
     pub fn flush_pending_chunks(&mut self) -> Result<()> {
-        for (i, encoder) in self.column_encoders.iter_mut().enumerate() {
-            if let Some(res) = encoder.finish(
+        self.assign_shared_dictionaries()?;
+        for i in 0..self.column_encoders.len() {
+            if let Some(res) = self.column_encoders[i].finish(
                 &mut self.state.column_counters[i],
                 &mut self.shared_dictionary_context,
             )? {
-                res.into_iter()
-                    .try_for_each(|chunk| self.state.flush_chunk(chunk))?;
+                self.flush_column_chunks(i, res)?;
             };
         }
         Ok(())
     }
 
-    pub fn finish(mut self) -> Result<Vec<EncodingCounter>> {
+    /// Flush the chunks pending in the encoders and finish the current row group.
+    fn write_row_group(&mut self) -> Result<()> {
+        trace_span!(
+            "write_row_group",
+            row_group = self.state.row_groups_table.row_counts().len()
+        );
+        self.flush_pending_chunks()?;
+        self.state.finish_row_group()
+    }
+
+    /// With [`DictionaryTypeOptions::GlobalDictionaryAutoSharing`], sample the values buffered
+    /// by each column and let the columns whose values overlap share one dictionary. Done once,
+    /// before the first dictionaries are encoded.
+    fn assign_shared_dictionaries(&mut self) -> Result<()> {
+        if !self.shared_dictionary_context.needs_sharing_assignment() {
+            return Ok(());
+        }
+        for encoder in self.column_encoders.iter_mut() {
+            encoder.submit_dict(&mut self.shared_dictionary_context)?;
+        }
+        self.shared_dictionary_context.assign_shared_dicts()
+    }
+
+    pub fn finish(self) -> Result<Vec<EncodingCounter>> {
+        self.finish_with_manifest().map(|(counters, _)| counters)
+    }
+
+    /// Finish the file and also return its [`FileManifest`], e.g., to write it next to the file
+    /// with [`FileManifest::write_next_to`].
+    pub fn finish_with_manifest(mut self) -> Result<(Vec<EncodingCounter>, FileManifest)> {
+        self.write_sort_buffer()?;
+        // Files shorter than the sample record the chunk sizes of all their rows.
+        self.set_column_chunk_sizes_from_sample();
         // if dictionary mode is global with sharing, first submit all values to dictionary context
         if self.shared_dictionary_context.is_multi_col_sharing() {
             for encoder in self.column_encoders.iter_mut() {
@@ -318,11 +1994,8 @@ impl<W: Write + Seek> FileWriter<W> {
             self.shared_dictionary_context.merge_dicts()?;
         }
 
-        // flush pendding data in encoders
-        self.flush_pending_chunks()?;
-
-        // Make sure flushed pending data added to row group metadata
-        self.state.finish_row_group()?;
+        // flush pendding data in encoders, and add it to the row group metadata
+        self.write_row_group()?;
 
         // flush shared dictionary
         let (dict_chunks, merge_peers, dict_dtypes) = self
@@ -338,19 +2011,41 @@ impl<W: Write + Seek> FileWriter<W> {
             })
             .collect::<Result<Vec<_>, _>>()?;
 
+        trace_span!("write_footer");
         let mut fbb = FlatBufferBuilder::new();
         // write WASM binaries.
+        let mut wasm_modules = vec![];
+        let mut aot_binaries = vec![];
         let wasms: Vec<_> = self
             .wasm_context
             .get_sorted_wasms()
             .into_iter()
-            .map(|wasm| {
+            .enumerate()
+            .map(|(wasm_id, wasm)| {
+                let hash = wasm_module_hash(wasm);
+                let uri = self.wasm_references.get(&hash).cloned();
                 let offset = self.state.writer.stream_position()?;
-                self.state.write_and_update_file_level_checksum(wasm)?;
+                // Binaries stored by reference only keep their WASMId with an empty location.
+                if uri.is_none() {
+                    self.state.write_and_update_file_level_checksum(wasm)?;
+                    let wasm_id = WASMId(wasm_id as u32);
+                    let aot_error = |e| general_error!("Unable to compile a WASM binary", e);
+                    if self.write_wasm_aot && required_features(wasm).map_err(aot_error)?.is_empty()
+                    {
+                        let aot_binary = precompile(wasm).map_err(aot_error)?;
+                        aot_binaries.push((wasm_id, AotTarget::host(), aot_binary));
+                    }
+                    for (target, aot_binary) in
+                        self.wasm_aot_artifacts.get(&hash).into_iter().flatten()
+                    {
+                        aot_binaries.push((wasm_id, target.clone(), aot_binary.clone()));
+                    }
+                }
+                wasm_modules.push(WasmModuleInfo { hash, uri });
                 let size = self.state.writer.stream_position()? - offset;
                 let mut b = fb::MetadataSectionBuilder::new(&mut fbb);
                 b.add_offset(offset);
-                b.add_size_(size as u32);
+                b.add_size_(checked_u32(size, "Size of a WASM binary")?);
                 b.add_compression_type(CompressionType::Uncompressed);
                 Ok(b.finish())
             })
@@ -366,11 +2061,111 @@ impl<W: Write + Seek> FileWriter<W> {
         self.state.write_and_update_file_level_checksum(wasms)?;
         let wasm_meta_size = self.state.writer.stream_position()? - wasm_meta_start;
 
-        // write ColumnMetadata and update indirect_row_group_metadata
-        let metadata_start = self
+        // write the content hash and URI of each WASM binary.
+        let wasm_modules_start = self.state.writer.stream_position()?;
+        let wasm_modules = serialize_wasm_modules(&wasm_modules);
+        self.state
+            .write_and_update_file_level_checksum(&wasm_modules)?;
+
+        // write the AOT artifacts of the embedded WASM binaries, then their locations, see
+        // crate::file::wasm_aot.
+        let mut wasm_aot_artifacts = vec![];
+        for (wasm_id, target, aot_binary) in aot_binaries {
+            let offset = self.state.writer.stream_position()?;
+            self.state
+                .write_and_update_file_level_checksum(&aot_binary)?;
+            wasm_aot_artifacts.push(WasmAotArtifact {
+                wasm_id,
+                target,
+                offset,
+                size: aot_binary.len() as u64,
+            });
+        }
+        let wasm_aot_start = self.state.writer.stream_position()?;
+        let wasm_aot_artifacts = serialize_wasm_aot_artifacts(&wasm_aot_artifacts);
+        self.state
+            .write_and_update_file_level_checksum(&wasm_aot_artifacts)?;
+
+        // write the WASMId of each top-level column encoded by a WASM binary, as little-endian
+        // u32 pairs of column index and WASMId.
+        let column_wasms_start = self.state.writer.stream_position()?;
+        let column_wasms = self
+            .column_wasm_ids
+            .iter()
+            .flat_map(|(&column, wasm_id)| [column as u32, wasm_id.0])
+            .flat_map(u32::to_le_bytes)
+            .collect::<Vec<_>>();
+        self.state
+            .write_and_update_file_level_checksum(&column_wasms)?;
+
+        // write the EncUnits of each chunk, see crate::file::encunit_index.
+        let encunit_index_start = self.state.writer.stream_position()?;
+        let encunit_index = self
             .state
-            .row_groups_table
-            .to_indirect_and_flush(&mut self.state.writer, self.state.data_checksum.as_mut())?;
+            .encunit_index
+            .as_deref()
+            .map(serialize_encunit_index);
+        if let Some(encunit_index) = &encunit_index {
+            self.state
+                .write_and_update_file_level_checksum(encunit_index)?;
+        }
+
+        // write the bloom filter of each chunk of the selected columns, see
+        // crate::file::bloom_filters.
+        let bloom_filters_start = self.state.writer.stream_position()?;
+        let bloom_filters = serialize_bloom_filters(&self.state.bloom_filters);
+        self.state
+            .write_and_update_file_level_checksum(&bloom_filters)?;
+
+        // write the columns and IO units of each column family, see crate::file::column_families.
+        let column_families_start = self.state.writer.stream_position()?;
+        let column_families = serialize_column_families(&self.state.families);
+        self.state
+            .write_and_update_file_level_checksum(&column_families)?;
+
+        // write the number of columns per column metadata segment and the schema index, see
+        // crate::file::metadata_segments.
+        let metadata_segments_start = self.state.writer.stream_position()?;
+        if let Some(columns_per_segment) = self.metadata_segment_columns {
+            self.state.write_and_update_file_level_checksum(
+                &(columns_per_segment as u32).to_le_bytes(),
+            )?;
+        }
+        let schema_index_start = self.state.writer.stream_position()?;
+        if let Some(schema_index) = &self.schema_index {
+            self.state
+                .write_and_update_file_level_checksum(schema_index)?;
+        }
+
+        // write the size of the previous version of the file as a little-endian u64.
+        let previous_version_start = self.state.writer.stream_position()?;
+        if let Some(size) = self.previous_version_size {
+            self.state
+                .write_and_update_file_level_checksum(&size.to_le_bytes())?;
+        }
+
+        // reserve padding before the metadata, so that it is not read along with the footer.
+        let padding_start = self.state.writer.stream_position()?;
+        if self.footer_padding > 0 {
+            self.state
+                .write_and_update_file_level_checksum(&vec![0; self.footer_padding as usize])?;
+        }
+
+        // write ColumnMetadata and update indirect_row_group_metadata. The metadata is buffered
+        // so that it can be compressed as a whole, with the offsets it has uncompressed.
+        let metadata_start = self.state.writer.stream_position()?;
+        let mut metadata = MetadataBuffer::new(metadata_start);
+        match self.metadata_segment_columns {
+            Some(columns_per_segment) => self.state.row_groups_table.to_segmented_and_flush(
+                &mut metadata,
+                create_checksum(&ChecksumType::XxHash).as_mut(),
+                columns_per_segment,
+            )?,
+            None => self.state.row_groups_table.to_indirect_and_flush(
+                &mut metadata,
+                create_checksum(&ChecksumType::XxHash).as_mut(),
+            )?,
+        };
 
         // write RowGroups fbs table to file
         let mut fbb = FlatBufferBuilder::new();
@@ -448,11 +2243,88 @@ impl<W: Write + Seek> FileWriter<W> {
         let logical_tree = self.logical_tree.to_fb(&mut fbb);
 
         let optional_metadata_section = {
-            let name = fbb.create_string("WASMBinaries");
-            let names = fbb.create_vector(&[name]);
-            let offsets = fbb.create_vector(&[wasm_meta_start]);
-            let sizes = fbb.create_vector(&[wasm_meta_size as u32]);
-            let compression_types = fbb.create_vector(&[CompressionType::Uncompressed]);
+            let mut names = vec![fbb.create_string("WASMBinaries")];
+            let mut offsets = vec![wasm_meta_start];
+            let mut sizes = vec![checked_u32(wasm_meta_size, "Size of the WASM metadata")?];
+            if !wasm_modules.is_empty() {
+                names.push(fbb.create_string("WASMModules"));
+                offsets.push(wasm_modules_start);
+                sizes.push(checked_u32(
+                    wasm_modules.len() as u64,
+                    "Size of the WASM modules",
+                )?);
+            }
+            if !wasm_aot_artifacts.is_empty() {
+                names.push(fbb.create_string("WASMAotArtifacts"));
+                offsets.push(wasm_aot_start);
+                sizes.push(checked_u32(
+                    wasm_aot_artifacts.len() as u64,
+                    "Size of the WASM AOT artifacts",
+                )?);
+            }
+            if !column_wasms.is_empty() {
+                names.push(fbb.create_string("ColumnWASMs"));
+                offsets.push(column_wasms_start);
+                sizes.push(checked_u32(
+                    column_wasms.len() as u64,
+                    "Size of the column WASMs",
+                )?);
+            }
+            if let Some(encunit_index) = &encunit_index {
+                names.push(fbb.create_string("EncUnitIndex"));
+                offsets.push(encunit_index_start);
+                sizes.push(checked_u32(
+                    encunit_index.len() as u64,
+                    "Size of the EncUnit index",
+                )?);
+            }
+            if !bloom_filters.is_empty() {
+                names.push(fbb.create_string("BloomFilters"));
+                offsets.push(bloom_filters_start);
+                sizes.push(checked_u32(
+                    bloom_filters.len() as u64,
+                    "Size of the bloom filters",
+                )?);
+            }
+            if !column_families.is_empty() {
+                names.push(fbb.create_string("ColumnFamilies"));
+                offsets.push(column_families_start);
+                sizes.push(checked_u32(
+                    column_families.len() as u64,
+                    "Size of the column families",
+                )?);
+            }
+            if self.metadata_segment_columns.is_some() {
+                names.push(fbb.create_string("MetadataSegments"));
+                offsets.push(metadata_segments_start);
+                sizes.push(4);
+            }
+            if let Some(schema_index) = &self.schema_index {
+                names.push(fbb.create_string("SchemaIndex"));
+                offsets.push(schema_index_start);
+                sizes.push(checked_u32(
+                    schema_index.len() as u64,
+                    "Size of the schema index",
+                )?);
+            }
+            if self.previous_version_size.is_some() {
+                names.push(fbb.create_string("PreviousVersion"));
+                offsets.push(previous_version_start);
+                sizes.push(8);
+            }
+            if self.footer_padding > 0 {
+                names.push(fbb.create_string("ReservedPadding"));
+                offsets.push(padding_start);
+                sizes.push(checked_u32(
+                    self.footer_padding,
+                    "Size of the footer padding",
+                )?);
+            }
+            let compression_types =
+                fbb.create_vector(&vec![CompressionType::Uncompressed; names.len()]);
+            let names = fbb.create_vector(&names);
+            let offsets = fbb.create_vector(&offsets);
+            let sizes = fbb.create_vector(&sizes);
             let mut builder = fb::OptionalMetadataSectionsBuilder::new(&mut fbb);
             builder.add_names(names);
             builder.add_offsets(offsets);
@@ -468,6 +2340,8 @@ impl<W: Write + Seek> FileWriter<W> {
             .map(|ev| ev.to_fb(&mut fbb))
             .collect::<Vec<_>>();
         let encoding_versions_fb = fbb.create_vector(&encoding_versions_fb);
+        let (key_values, column_key_values) = self.key_value_metadata.to_fb(&mut fbb);
+        let footer_sort_order = sort_order::to_fb(&mut fbb, &self.footer_sort_order);
 
         let footer = {
             let mut footer_builder = fb::FooterBuilder::new(&mut fbb);
@@ -477,28 +2351,370 @@ impl<W: Write + Seek> FileWriter<W> {
             footer_builder.add_optional_sections(optional_metadata_section);
             footer_builder.add_shared_dictionary_table(shared_dict_table);
             footer_builder.add_encoding_versions(encoding_versions_fb);
+            footer_builder.add_schema_fingerprint(schema_fingerprint(&self.schema));
+            if let Some(key_values) = key_values {
+                footer_builder.add_key_values(key_values);
+            }
+            if let Some(column_key_values) = column_key_values {
+                footer_builder.add_column_key_values(column_key_values);
+            }
+            if let Some(footer_sort_order) = footer_sort_order {
+                footer_builder.add_sort_order(footer_sort_order);
+            }
             footer_builder.finish()
         };
         fbb.finish(footer, None);
         let footer_data = fbb.finished_data();
-        self.state
-            .write_and_update_file_level_checksum(footer_data)?;
+        // When appending, pad before the footer so that the file does not end earlier than the
+        // original one. The footer is located from the end of the file, so padding is never read.
+        // Compressing the metadata would make the file shorter again, so it is skipped then.
+        let metadata_compression = if self.state.min_file_size > 0 {
+            CompressionType::Uncompressed
+        } else {
+            self.metadata_compression
+        };
+        let file_end = metadata.stream_position()? + footer_data.len() as u64 + POSTSCRIPT_SIZE;
+        if file_end < self.state.min_file_size {
+            metadata.write_all(&vec![0; (self.state.min_file_size - file_end) as usize])?;
+        }
+        metadata.write_all(footer_data)?;
+        let (metadata, metadata_compression) = compress_data(
+            Bytes::from(metadata.into_inner()),
+            Compression::new(
+                metadata_compression,
+                CompressionLevel::Fixed(zstd::DEFAULT_COMPRESSION_LEVEL),
+            ),
+        )?;
+        let metadata = match &self.footer_encryptor {
+            Some(encryptor) => Bytes::from(encryptor.encrypt_metadata(&metadata)?),
+            None => metadata,
+        };
+        self.state.write_and_update_file_level_checksum(&metadata)?;
 
         // write postscript to file
         let writer = &mut self.state.writer;
-        let metadata_size = (writer.stream_position()? - metadata_start) as u32;
+        let metadata_size = checked_u32(metadata.len() as u64, "Size of the metadata")?;
         writer.write_all(metadata_size.to_le_bytes().as_ref())?;
-        let footer_size = footer_data.len() as u32;
+        let footer_size = checked_u32(footer_data.len() as u64, "Size of the footer")?;
         writer.write_all(footer_size.to_le_bytes().as_ref())?;
-        let footer_compression = CompressionType::Uncompressed;
-        writer.write_all(u8::from(footer_compression).to_le_bytes().as_ref())?;
+        writer.write_all(u8::from(metadata_compression).to_le_bytes().as_ref())?;
         writer.write_all((ChecksumType::XxHash as u8).to_le_bytes().as_ref())?;
         writer.write_all(self.state.data_checksum.finalize().to_le_bytes().as_ref())?;
         writer.write_all(schema_checksum.to_le_bytes().as_ref())?;
         writer.write_all(MAJOR_VERSION.to_le_bytes().as_ref())?;
         writer.write_all(MINOR_VERSION.to_le_bytes().as_ref())?;
-        writer.write_all(MAGIC)?;
+        writer.write_all(match self.footer_encryptor {
+            Some(_) => MAGIC_ENCRYPTED_FOOTER,
+            None => MAGIC,
+        })?;
         writer.flush()?;
-        Ok(self.state.column_counters)
+        let manifest = FileManifest::new(
+            &self.schema,
+            self.state.num_rows_in_file,
+            writer.stream_position()?,
+            self.state.num_physical_columns,
+            self.state.row_groups_table.row_group_metadata(),
+            &self.wasm_context.get_sorted_wasms(),
+        );
+        Ok((self.state.column_counters, manifest))
+    }
+}
+
+/// Appended batches must have the same fields as the existing file.
+/// A nullable field in the file can take non-nullable data, but not vice versa.
+fn check_append_schema(existing: &Schema, appended: &Schema) -> Result<()> {
+    if existing.fields().len() != appended.fields().len() {
+        return Err(Error::General(format!(
+            "Expected {} fields to append, got {}",
+            existing.fields().len(),
+            appended.fields().len()
+        )));
+    }
+    for (existing_field, appended_field) in existing.fields().iter().zip(appended.fields().iter()) {
+        if existing_field.name() != appended_field.name()
+            || existing_field.data_type() != appended_field.data_type()
+            || (!existing_field.is_nullable() && appended_field.is_nullable())
+        {
+            return Err(Error::General(format!(
+                "Field {appended_field} is not compatible with existing field {existing_field}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Encode `col` with `encoder`, in EncUnits of `custom_encunit_len` rows if any, and return the
+/// chunks to flush.
+fn encode_column(
+    encoder: &mut dyn LogicalColEncoder,
+    counter: &mut EncodingCounter,
+    shared_dictionary_context: &mut SharedDictionaryContext,
+    col: &ArrayRef,
+    custom_encunit_len: Option<usize>,
+) -> Result<Vec<EncodedColumnChunk>> {
+    // TODO: currently this is for research experiments.
+    // A detailed API similar to Parquet's write_batch with correct internal buffer should be added.
+    // Currently requires the input batch size to be the multiple of the custom encunit size.
+    let Some(encunit_len) = custom_encunit_len else {
+        return Ok(encoder
+            .encode(col.clone(), counter, shared_dictionary_context)?
+            .unwrap_or_default());
+    };
+    if col.len() < encunit_len {
+        return nyi_err!("Batch size should be larger than the custom encunit size");
+    }
+    let mut chunks = vec![];
+    for j in 0..(col.len() / encunit_len) {
+        // slice col to the correct range, then encode
+        let col_sliced = col.slice(
+            j * encunit_len,
+            std::cmp::min(encunit_len, col.len() - j * encunit_len),
+        );
+        if let Some(res) = encoder.encode(col_sliced, counter, shared_dictionary_context)? {
+            chunks.extend(res);
+        }
+    }
+    Ok(chunks)
+}
+
+/// The metadata of an existing file, see [`read_existing_footer`].
+struct ExistingFooter {
+    file_size: u64,
+    /// Size of the data, which the metadata follows.
+    data_size: u64,
+    /// The column metadata and the footer.
+    metadata: MutableBuffer,
+    footer_size: usize,
+}
+
+impl ExistingFooter {
+    fn footer(&self) -> Result<fb::Footer<'_>> {
+        root_as_footer(&self.metadata[self.metadata.len() - self.footer_size..])
+            .map_err(|e| Error::ParseError(format!("Unable to get root as footer: {e:?}")))
+    }
+}
+
+/// Read the metadata of the existing file read by `reader`, to rewrite it with `operation`,
+/// e.g., "Appending to". Files with shared dictionaries are not supported, as the dictionaries
+/// refer to the physical columns by index.
+fn read_existing_footer<R: Reader>(reader: &R, operation: &str) -> Result<ExistingFooter> {
+    let file_size = reader.size()?;
+    let post_script = read_postscript(reader, file_size)?;
+    let existing = ExistingFooter {
+        file_size,
+        data_size: file_size - POSTSCRIPT_SIZE - post_script.metadata_size as u64,
+        metadata: get_metadata_buffer(reader, &post_script)?,
+        footer_size: post_script.footer_size as usize,
+    };
+    if existing
+        .footer()?
+        .shared_dictionary_table()
+        .and_then(|table| table.dictionary_chunks())
+        .is_some_and(|chunks| !chunks.is_empty())
+    {
+        return nyi_err!(format!("{operation} a file with shared dictionaries"));
+    }
+    Ok(existing)
+}
+
+/// Read the WASM binaries referred by the "WASMBinaries" optional metadata section.
+fn read_wasm_binaries<R: Reader>(
+    reader: &R,
+    optional_sections: Option<fb::OptionalMetadataSections>,
+) -> Result<Vec<Vec<u8>>> {
+    let Some(range) = locate_section(optional_sections, "WASMBinaries")? else {
+        return Ok(vec![]);
+    };
+    let mut buf = vec![0; (range.end - range.start) as usize];
+    reader.read_exact_at(&mut buf, range.start)?;
+    let wasm_binaries = flatbuffers::root::<fb::WASMBinaries>(&buf)?;
+    wasm_binaries
+        .wasm_binaries()
+        .into_iter()
+        .flatten()
+        .map(|loc| {
+            let mut wasm = vec![0; loc.size_() as usize];
+            reader.read_exact_at(&mut wasm, loc.offset())?;
+            Ok(wasm)
+        })
+        .collect()
+}
+
+/// The records of a chunk of an existing file, carried to the chunk it is copied to.
+#[derive(Default)]
+struct CopiedChunk {
+    filter: Option<BloomFilter>,
+    /// The zone map of each EncUnit.
+    zone_maps: Option<Vec<Option<Statistics>>>,
+}
+
+/// The optional metadata sections of an existing file that refer to its chunks or columns, see
+/// [`FileWriter::carry_existing_sections`].
+struct ExistingSections {
+    bloom_filters: Vec<ChunkBloomFilter>,
+    encunit_index: Option<Vec<ChunkEncUnitIndex>>,
+    families: Vec<ColumnFamily>,
+}
+
+/// Read the "BloomFilters", "EncUnitIndex" and "ColumnFamilies" optional metadata sections.
+fn read_existing_sections<R: Reader>(
+    reader: &R,
+    optional_sections: Option<fb::OptionalMetadataSections>,
+) -> Result<ExistingSections> {
+    let read_section = |name| -> Result<Option<Vec<u8>>> {
+        let Some(range) = locate_section(optional_sections, name)? else {
+            return Ok(None);
+        };
+        let mut buf = vec![0; (range.end - range.start) as usize];
+        reader.read_exact_at(&mut buf, range.start)?;
+        Ok(Some(buf))
+    };
+    Ok(ExistingSections {
+        bloom_filters: match read_section("BloomFilters")? {
+            Some(buf) => deserialize_bloom_filters(&buf)?,
+            None => vec![],
+        },
+        encunit_index: match read_section("EncUnitIndex")? {
+            Some(buf) => Some(deserialize_encunit_index(&buf)?.chunks().to_vec()),
+            None => None,
+        },
+        families: match read_section("ColumnFamilies")? {
+            Some(buf) => deserialize_column_families(&buf)?,
+            None => vec![],
+        },
+    })
+}
+
+/// The physical columns of each top-level column of `schema`.
+fn physical_column_ranges(schema: &Schema) -> Vec<Range<usize>> {
+    schema
+        .fields()
+        .iter()
+        .scan(0, |start, field| {
+            let range = *start..*start + num_physical_columns(field.data_type());
+            *start = range.end;
+            Some(range)
+        })
+        .collect()
+}
+
+/// A row group of an existing file, see [`read_existing_row_groups`].
+struct ExistingRowGroup {
+    row_count: u32,
+    offset: u64,
+    size: u32,
+    column_metadatas: Vec<ColumnMetadata>,
+}
+
+/// Parse the row groups of an existing file, and return them with the end of the last chunk.
+fn read_existing_row_groups(
+    row_groups: &fb::RowGroups,
+    metadata: &[u8],
+    data_size: u64,
+) -> Result<(Vec<ExistingRowGroup>, u64)> {
+    let mut data_end = 0;
+    let row_groups = itertools::izip!(
+        row_groups
+            .row_group_metadatas()
+            .ok_or_else(|| Error::ParseError("Row group metadatas not found".to_string()))?,
+        row_groups
+            .row_counts()
+            .ok_or_else(|| Error::ParseError("Row counts not found".to_string()))?,
+        row_groups
+            .offsets()
+            .ok_or_else(|| Error::ParseError("Offsets not found".to_string()))?,
+        row_groups
+            .sizes()
+            .ok_or_else(|| Error::ParseError("Sizes not found".to_string()))?,
+    )
+    .map(|(row_group_meta, row_count, offset, size)| {
+        let column_metadatas = row_group_meta
+            .col_metadatas()
+            .ok_or_else(|| Error::ParseError("Column metadatas not found".to_string()))?
+            .iter()
+            .map(|section| -> Result<ColumnMetadata> {
+                let start = (section.offset() - data_size) as usize;
+                let column_meta = flatbuffers::root::<fb::ColumnMetadata>(
+                    &metadata[start..start + section.size_() as usize],
+                )?;
+                for chunk in column_meta.column_chunks().into_iter().flatten() {
+                    data_end = data_end.max(chunk.offset() + chunk.size_() as u64);
+                }
+                Ok(ColumnMetadata::from(&column_meta))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ExistingRowGroup {
+            row_count,
+            offset,
+            size,
+            column_metadatas,
+        })
+    })
+    .collect::<Result<Vec<_>>>()?;
+    Ok((row_groups, data_end))
+}
+
+/// Compute the data checksum of the first `data_end` bytes of an existing file, passing each
+/// buffer read to `f`.
+fn checksum_data<R: Reader>(
+    reader: &R,
+    data_end: u64,
+    checksum_type: &ChecksumType,
+    mut f: impl FnMut(&[u8]) -> Result<()>,
+) -> Result<Box<dyn Checksum>> {
+    let mut data_checksum = create_checksum(checksum_type);
+    let mut buf = vec![0; DEFAULT_IOUNIT_SIZE.min(data_end) as usize];
+    let mut pos = 0;
+    while pos < data_end {
+        let len = (data_end - pos).min(buf.len() as u64) as usize;
+        reader.read_exact_at(&mut buf[..len], pos)?;
+        data_checksum.update(&buf[..len]);
+        f(&buf[..len])?;
+        pos += len as u64;
+    }
+    Ok(data_checksum)
+}
+
+/// In-memory sink for the file metadata, whose positions start at `base`, the offset of the
+/// metadata in the file.
+struct MetadataBuffer {
+    base: u64,
+    buf: Cursor<Vec<u8>>,
+}
+
+impl MetadataBuffer {
+    fn new(base: u64) -> Self {
+        Self {
+            base,
+            buf: Cursor::new(vec![]),
+        }
+    }
+
+    fn into_inner(self) -> Vec<u8> {
+        self.buf.into_inner()
+    }
+}
+
+impl Write for MetadataBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for MetadataBuffer {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => {
+                SeekFrom::Start(pos.checked_sub(self.base).ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidInput, "Seek before metadata")
+                })?)
+            }
+            pos => pos,
+        };
+        Ok(self.base + self.buf.seek(pos)?)
     }
 }
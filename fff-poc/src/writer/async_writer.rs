@@ -0,0 +1,166 @@
+//! Write an F3 file directly to an object store, e.g., S3, instead of writing it locally and
+//! uploading it.
+
+use std::io::{Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex};
+
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use fff_core::errors::{Error, Result};
+use object_store::{path::Path, ObjectStore, WriteMultipart};
+
+use super::FileWriter;
+use crate::counter::EncodingCounter;
+use crate::file::key_value_metadata::MetadataValue;
+use crate::options::FileWriterOptions;
+
+/// Size in bytes of the parts uploaded by [`AsyncFileWriter::try_new`], above the 5 MiB minimum
+/// of S3.
+pub const DEFAULT_PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// Number of parts uploaded concurrently before writing waits for one of them to complete.
+const MAX_CONCURRENT_PARTS: usize = 8;
+
+/// Bytes written by the [`FileWriter`] of an [`AsyncFileWriter`] and not handed to the upload yet.
+/// Files are written sequentially, so only the position is tracked.
+#[derive(Clone, Default)]
+struct PendingBytes {
+    inner: Arc<Mutex<(Vec<u8>, u64)>>,
+}
+
+impl PendingBytes {
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut self.inner.lock().unwrap().0)
+    }
+}
+
+impl Write for PendingBytes {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let (bytes, position) = &mut *self.inner.lock().unwrap();
+        bytes.extend_from_slice(buf);
+        *position += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for PendingBytes {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = self.inner.lock().unwrap().1;
+        match pos {
+            SeekFrom::Start(offset) if offset == position => Ok(position),
+            SeekFrom::Current(0) => Ok(position),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "Uploads to an object store are written sequentially",
+            )),
+        }
+    }
+}
+
+/// Writer of an F3 file to an [`ObjectStore`] through a multipart upload.
+///
+/// Batches are encoded by a [`FileWriter`] on the caller task, and the chunks it flushes are
+/// uploaded in parts as they are produced, so that only the data of the parts in flight and of
+/// the chunks being encoded is buffered. [`Self::finish`] writes the metadata and completes the
+/// upload, the object is not visible before. Dropping the writer without finishing it leaves the
+/// upload incomplete, see [`Self::abort`].
+pub struct AsyncFileWriter {
+    writer: FileWriter<PendingBytes>,
+    pending: PendingBytes,
+    upload: WriteMultipart,
+}
+
+impl AsyncFileWriter {
+    /// Start the upload of a new F3 file of `schema` to `location`, in parts of
+    /// [`DEFAULT_PART_SIZE`] bytes.
+    pub async fn try_new(
+        schema: SchemaRef,
+        object_store: Arc<dyn ObjectStore>,
+        location: &Path,
+        options: FileWriterOptions,
+    ) -> Result<Self> {
+        Self::try_new_with_part_size(schema, object_store, location, options, DEFAULT_PART_SIZE)
+            .await
+    }
+
+    /// Like [`Self::try_new`], with parts of `part_size` bytes, apart from the last one.
+    pub async fn try_new_with_part_size(
+        schema: SchemaRef,
+        object_store: Arc<dyn ObjectStore>,
+        location: &Path,
+        options: FileWriterOptions,
+        part_size: usize,
+    ) -> Result<Self> {
+        let pending = PendingBytes::default();
+        // Validate the options before starting the upload.
+        let writer = FileWriter::try_new(schema, pending.clone(), options)?;
+        let upload = object_store
+            .put_multipart(location)
+            .await
+            .map_err(Error::ObjectStore)?;
+        Ok(Self {
+            writer,
+            pending,
+            upload: WriteMultipart::new_with_chunk_size(upload, part_size),
+        })
+    }
+
+    /// Encode `batch`, and upload the chunks flushed meanwhile.
+    pub async fn write_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+        self.writer.write_batch(batch)?;
+        self.upload_pending().await
+    }
+
+    /// See [`FileWriter::add_metadata`].
+    pub fn add_metadata(&mut self, key: impl Into<String>, value: impl Into<MetadataValue>) {
+        self.writer.add_metadata(key, value);
+    }
+
+    /// Number of rows written so far.
+    pub fn num_rows(&self) -> u64 {
+        self.writer.num_rows()
+    }
+
+    /// Hand the bytes flushed so far to the upload, waiting while too many parts are in flight.
+    async fn upload_pending(&mut self) -> Result<()> {
+        let bytes = self.pending.take();
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        self.upload
+            .wait_for_capacity(MAX_CONCURRENT_PARTS)
+            .await
+            .map_err(Error::ObjectStore)?;
+        self.upload.write(&bytes);
+        Ok(())
+    }
+
+    /// Write the remaining chunks and the metadata, and complete the upload. The upload is
+    /// aborted if the file cannot be finished.
+    pub async fn finish(self) -> Result<Vec<EncodingCounter>> {
+        let Self {
+            writer,
+            pending,
+            mut upload,
+        } = self;
+        let counters = match writer.finish() {
+            Ok(counters) => counters,
+            Err(e) => {
+                upload.abort().await.map_err(Error::ObjectStore)?;
+                return Err(e);
+            }
+        };
+        upload.write(&pending.take());
+        upload.finish().await.map_err(Error::ObjectStore)?;
+        Ok(counters)
+    }
+
+    /// Abort the upload, discarding the parts uploaded so far.
+    pub async fn abort(self) -> Result<()> {
+        self.upload.abort().await.map_err(Error::ObjectStore)
+    }
+}
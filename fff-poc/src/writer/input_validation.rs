@@ -0,0 +1,355 @@
+//! Validation of the batches given to the writer, see
+//! [`FileWriterOptionsBuilder::set_input_validation`](crate::options::FileWriterOptionsBuilder::set_input_validation).
+//!
+//! Some values have several representations, which encoders comparing or hashing values by their
+//! bits tell apart: -0.0 and 0.0, NaNs of different payloads, and dictionary arrays repeating a
+//! value or holding null values instead of null keys. Validation finds them before they reach the
+//! encoders, and either rewrites them in canonical form or rejects the batch, naming the column
+//! and the row.
+
+use std::{collections::HashMap, sync::Arc};
+
+use arrow::{
+    compute::cast,
+    row::{RowConverter, SortField},
+};
+use arrow_array::{
+    cast::AsArray,
+    make_array,
+    types::{ArrowPrimitiveType, Float16Type, Float32Type, Float64Type},
+    Array, ArrayRef, PrimitiveArray, RecordBatch,
+};
+use arrow_buffer::ArrowNativeType;
+use arrow_schema::DataType;
+use fff_core::errors::{Error, Result};
+use half::f16;
+
+/// How the writer treats non-canonical input values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InputValidation {
+    /// Write the values as given.
+    #[default]
+    Off,
+    /// Write -0.0 as 0.0, NaNs as the canonical NaN, and the rows of dictionary arrays with
+    /// repeated or null values with a dictionary of distinct non-null values.
+    Normalize,
+    /// Reject batches with non-canonical values.
+    Strict,
+}
+
+trait CanonicalFloat: ArrowNativeType {
+    fn canonical(self) -> Self;
+
+    /// Why the value is not canonical, if it is not.
+    fn problem(self) -> Option<&'static str>;
+}
+
+macro_rules! impl_canonical_float {
+    ($t:ty, $zero:expr, $nan:expr) => {
+        impl CanonicalFloat for $t {
+            fn canonical(self) -> Self {
+                match self {
+                    v if v.is_nan() => $nan,
+                    // -0.0 == 0.0
+                    v if v == $zero => $zero,
+                    v => v,
+                }
+            }
+
+            fn problem(self) -> Option<&'static str> {
+                if self.to_bits() == self.canonical().to_bits() {
+                    None
+                } else if self.is_nan() {
+                    Some("NaN with a non-canonical payload")
+                } else {
+                    Some("negative zero")
+                }
+            }
+        }
+    };
+}
+
+impl_canonical_float!(f16, f16::ZERO, f16::NAN);
+impl_canonical_float!(f32, 0.0, f32::NAN);
+impl_canonical_float!(f64, 0.0, f64::NAN);
+
+/// Check the columns of `batch`, and normalize them if `validation` says so. Rows in errors are
+/// counted from the start of `batch`.
+pub(crate) fn validate_batch(
+    batch: &RecordBatch,
+    validation: InputValidation,
+) -> Result<RecordBatch> {
+    match validation {
+        InputValidation::Off => Ok(batch.clone()),
+        InputValidation::Normalize => {
+            let columns = batch
+                .columns()
+                .iter()
+                .map(normalize)
+                .collect::<Result<Vec<_>>>()?;
+            Ok(RecordBatch::try_new(batch.schema(), columns)?)
+        }
+        InputValidation::Strict => {
+            for (field, column) in batch.schema().fields().iter().zip(batch.columns()) {
+                if let Some((row, problem)) = first_non_canonical(column.as_ref())? {
+                    return Err(Error::General(format!(
+                        "Non-canonical input in column {} at row {row}: {problem}",
+                        field.name()
+                    )));
+                }
+            }
+            Ok(batch.clone())
+        }
+    }
+}
+
+fn first_non_canonical_float<T>(array: &PrimitiveArray<T>) -> Option<(usize, &'static str)>
+where
+    T: ArrowPrimitiveType,
+    T::Native: CanonicalFloat,
+{
+    array
+        .iter()
+        .enumerate()
+        .find_map(|(row, value)| Some((row, value?.problem()?)))
+}
+
+fn normalize_floats<T>(array: &PrimitiveArray<T>) -> ArrayRef
+where
+    T: ArrowPrimitiveType,
+    T::Native: CanonicalFloat,
+{
+    Arc::new(array.unary::<_, T>(CanonicalFloat::canonical))
+}
+
+/// For each value of a dictionary, whether it is null or repeats an earlier value.
+fn non_canonical_dictionary_values(values: &dyn Array) -> Result<Vec<bool>> {
+    let converter = RowConverter::new(vec![SortField::new(values.data_type().clone())])?;
+    let rows = converter.convert_columns(&[make_array(values.to_data())])?;
+    let mut seen = HashMap::new();
+    Ok((0..values.len())
+        .map(|i| values.is_null(i) || *seen.entry(rows.row(i)).or_insert(i) != i)
+        .collect())
+}
+
+/// The first row holding a non-canonical value, with why it is not canonical.
+fn first_non_canonical(array: &dyn Array) -> Result<Option<(usize, &'static str)>> {
+    Ok(match array.data_type() {
+        DataType::Float16 => first_non_canonical_float(array.as_primitive::<Float16Type>()),
+        DataType::Float32 => first_non_canonical_float(array.as_primitive::<Float32Type>()),
+        DataType::Float64 => first_non_canonical_float(array.as_primitive::<Float64Type>()),
+        DataType::Dictionary(_, _) => {
+            let dictionary = array.as_any_dictionary();
+            let non_canonical = non_canonical_dictionary_values(dictionary.values().as_ref())?;
+            let keys = dictionary.keys();
+            dictionary
+                .normalized_keys()
+                .into_iter()
+                .enumerate()
+                .find(|&(row, key)| keys.is_valid(row) && non_canonical[key])
+                .map(|(row, key)| {
+                    let problem = if dictionary.values().is_null(key) {
+                        "null dictionary value, nulls must be null keys"
+                    } else {
+                        "repeated dictionary value"
+                    };
+                    (row, problem)
+                })
+        }
+        DataType::Struct(_) => {
+            let mut first: Option<(usize, &'static str)> = None;
+            for column in array.as_struct().columns() {
+                if let Some((row, problem)) = first_non_canonical(column.as_ref())? {
+                    if first.is_none_or(|(first_row, _)| row < first_row) {
+                        first = Some((row, problem));
+                    }
+                }
+            }
+            first
+        }
+        DataType::List(_) => {
+            let list = array.as_list::<i32>();
+            let offsets = list.value_offsets().iter().map(|&o| o as usize).collect();
+            first_non_canonical_in_values(list.values().as_ref(), offsets)?
+        }
+        DataType::LargeList(_) => {
+            let list = array.as_list::<i64>();
+            let offsets = list.value_offsets().iter().map(|&o| o as usize).collect();
+            first_non_canonical_in_values(list.values().as_ref(), offsets)?
+        }
+        DataType::Map(_, _) => {
+            let map = array.as_map();
+            let offsets = map.value_offsets().iter().map(|&o| o as usize).collect();
+            first_non_canonical_in_values(map.entries(), offsets)?
+        }
+        DataType::FixedSizeList(_, size) => {
+            let list = array.as_fixed_size_list();
+            let offsets = (0..=list.len()).map(|row| row * *size as usize).collect();
+            first_non_canonical_in_values(list.values().as_ref(), offsets)?
+        }
+        _ => None,
+    })
+}
+
+/// The first row holding a non-canonical value of the child `values` of a list-like array whose
+/// rows span `offsets`.
+fn first_non_canonical_in_values(
+    values: &dyn Array,
+    offsets: Vec<usize>,
+) -> Result<Option<(usize, &'static str)>> {
+    let (start, end) = (offsets[0], offsets[offsets.len() - 1]);
+    let values = values.slice(start, end - start);
+    Ok(first_non_canonical(values.as_ref())?.map(|(idx, problem)| {
+        let row = offsets.partition_point(|&offset| offset <= start + idx) - 1;
+        (row, problem)
+    }))
+}
+
+fn normalize(array: &ArrayRef) -> Result<ArrayRef> {
+    Ok(match array.data_type() {
+        DataType::Float16 => normalize_floats(array.as_primitive::<Float16Type>()),
+        DataType::Float32 => normalize_floats(array.as_primitive::<Float32Type>()),
+        DataType::Float64 => normalize_floats(array.as_primitive::<Float64Type>()),
+        DataType::Dictionary(_, value_type) => {
+            if first_non_canonical(array.as_ref())?.is_none() {
+                return Ok(array.clone());
+            }
+            // Casting the values back to a dictionary builds one of distinct values.
+            cast(&cast(array, value_type)?, array.data_type())?
+        }
+        DataType::Struct(_)
+        | DataType::List(_)
+        | DataType::LargeList(_)
+        | DataType::FixedSizeList(_, _)
+        | DataType::Map(_, _) => {
+            let data = array.to_data();
+            let children = data
+                .child_data()
+                .iter()
+                .map(|child| Ok(normalize(&make_array(child.clone()))?.to_data()))
+                .collect::<Result<Vec<_>>>()?;
+            make_array(data.into_builder().child_data(children).build()?)
+        }
+        _ => array.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{
+        builder::{ListBuilder, StringDictionaryBuilder},
+        types::Int32Type,
+        DictionaryArray, Float64Array, Int32Array, StringArray,
+    };
+    use arrow_schema::{Field, Schema};
+
+    use super::*;
+
+    fn batch(columns: Vec<ArrayRef>) -> RecordBatch {
+        let fields = columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| Field::new(format!("c{i}"), c.data_type().clone(), true))
+            .collect::<Vec<_>>();
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap()
+    }
+
+    fn strict_error(columns: Vec<ArrayRef>) -> String {
+        validate_batch(&batch(columns), InputValidation::Strict)
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn test_floats() {
+        let payload_nan = f64::from_bits(f64::NAN.to_bits() | 1);
+        let floats: ArrayRef = Arc::new(Float64Array::from(vec![
+            Some(1.0),
+            None,
+            Some(-0.0),
+            Some(payload_nan),
+            Some(f64::NAN),
+        ]));
+        let err = strict_error(vec![floats.clone()]);
+        assert!(err.contains("column c0 at row 2: negative zero"), "{err}");
+        let err = strict_error(vec![floats.slice(3, 2)]);
+        assert!(
+            err.contains("at row 0: NaN with a non-canonical payload"),
+            "{err}"
+        );
+
+        let normalized = validate_batch(&batch(vec![floats]), InputValidation::Normalize).unwrap();
+        let normalized = normalized.column(0).as_primitive::<Float64Type>();
+        assert!(normalized.is_null(1));
+        assert_eq!(normalized.value(2).to_bits(), 0.0f64.to_bits());
+        assert_eq!(normalized.value(3).to_bits(), f64::NAN.to_bits());
+        let normalized: ArrayRef = Arc::new(normalized.clone());
+        validate_batch(&batch(vec![normalized]), InputValidation::Strict).unwrap();
+
+        // Off writes the values as given.
+        let floats: ArrayRef = Arc::new(Float64Array::from(vec![-0.0]));
+        let off = validate_batch(&batch(vec![floats]), InputValidation::Off).unwrap();
+        assert!(off
+            .column(0)
+            .as_primitive::<Float64Type>()
+            .value(0)
+            .is_sign_negative());
+    }
+
+    #[test]
+    fn test_dictionaries() {
+        let keys = Int32Array::from(vec![Some(0), None, Some(1), Some(2)]);
+        let values = StringArray::from(vec![Some("a"), Some("b"), Some("a")]);
+        let repeated: ArrayRef =
+            Arc::new(DictionaryArray::<Int32Type>::try_new(keys, Arc::new(values)).unwrap());
+        let ints: ArrayRef = Arc::new(Int32Array::from(vec![1, 2, 3, 4]));
+        let err = strict_error(vec![ints, repeated.clone()]);
+        assert!(
+            err.contains("column c1 at row 3: repeated dictionary value"),
+            "{err}"
+        );
+
+        let keys = Int32Array::from(vec![0, 1]);
+        let values = StringArray::from(vec![Some("a"), None]);
+        let null_value: ArrayRef =
+            Arc::new(DictionaryArray::<Int32Type>::try_new(keys, Arc::new(values)).unwrap());
+        let err = strict_error(vec![null_value]);
+        assert!(err.contains("at row 1: null dictionary value"), "{err}");
+
+        let normalized =
+            validate_batch(&batch(vec![repeated.clone()]), InputValidation::Normalize).unwrap();
+        let dictionary = normalized.column(0).as_dictionary::<Int32Type>();
+        assert_eq!(dictionary.values().len(), 2);
+        assert_eq!(
+            cast(dictionary, &DataType::Utf8).unwrap().as_ref(),
+            cast(&repeated, &DataType::Utf8).unwrap().as_ref()
+        );
+
+        let mut builder = StringDictionaryBuilder::<Int32Type>::new();
+        builder.append_value("a");
+        builder.append_null();
+        let canonical: ArrayRef = Arc::new(builder.finish());
+        validate_batch(&batch(vec![canonical]), InputValidation::Strict).unwrap();
+    }
+
+    #[test]
+    fn test_nested() {
+        let mut builder = ListBuilder::new(arrow_array::builder::Float64Builder::new());
+        builder.append_value([Some(1.0), Some(2.0)]);
+        builder.append_null();
+        builder.append_value([Some(3.0), Some(-0.0)]);
+        let list: ArrayRef = Arc::new(builder.finish());
+        let err = strict_error(vec![list.clone()]);
+        assert!(err.contains("at row 2: negative zero"), "{err}");
+        // Rows are counted in the slice.
+        let err = strict_error(vec![list.slice(1, 2)]);
+        assert!(err.contains("at row 1: negative zero"), "{err}");
+
+        let normalized = validate_batch(&batch(vec![list]), InputValidation::Normalize).unwrap();
+        let values = normalized.column(0).as_list::<i32>().value(2);
+        assert!(!values
+            .as_primitive::<Float64Type>()
+            .value(1)
+            .is_sign_negative());
+    }
+}
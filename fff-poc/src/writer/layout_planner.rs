@@ -0,0 +1,237 @@
+//! Plan the row group size and the chunk size of each column from a target IOUnit size.
+//!
+//! With a single chunk size for all columns, a row group of a wide table holds large chunks for
+//! the poorly compressed columns and tiny ones for the well compressed columns, each of which
+//! then costs a request on object stores. The planner sizes row groups so that the best
+//! compressed column still fills about one IOUnit, within the row count bounds, and splits the
+//! other columns into chunks of even sizes close to the IOUnit size.
+//!
+//! Without estimates ahead of writing, [`AutoColumnChunkSize`] lets the writer size the chunks
+//! of each column the same way from the encoded size of the first rows of the file.
+
+use arrow_array::Array;
+
+use crate::options::DEFAULT_IOUNIT_SIZE;
+
+/// Expected size of a column once encoded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColumnSizeEstimate {
+    /// Size of a value before encoding, in bytes.
+    uncompressed_bytes_per_row: f64,
+    /// Size before encoding divided by size after encoding, e.g., observed in earlier files.
+    compression_ratio: f64,
+}
+
+impl ColumnSizeEstimate {
+    pub fn new(uncompressed_bytes_per_row: f64, compression_ratio: f64) -> Self {
+        Self {
+            uncompressed_bytes_per_row,
+            compression_ratio,
+        }
+    }
+
+    /// Estimate the uncompressed size from a sample of the column.
+    pub fn from_sample(sample: &dyn Array, compression_ratio: f64) -> Self {
+        Self::new(
+            sample.get_buffer_memory_size() as f64 / sample.len().max(1) as f64,
+            compression_ratio,
+        )
+    }
+
+    pub fn encoded_bytes_per_row(&self) -> f64 {
+        self.uncompressed_bytes_per_row / self.compression_ratio.max(f64::MIN_POSITIVE)
+    }
+}
+
+/// Output of [`LayoutPlanner::plan`], applied with
+/// [`FileWriterOptionsBuilder::set_layout_plan`](crate::options::FileWriterOptionsBuilder::set_layout_plan).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutPlan {
+    row_group_size: u64,
+    column_chunk_sizes: Vec<u64>,
+}
+
+impl LayoutPlan {
+    /// Number of rows of a row group.
+    pub fn row_group_size(&self) -> u64 {
+        self.row_group_size
+    }
+
+    /// Size in bytes after which the chunk of each top-level column is flushed.
+    pub fn column_chunk_sizes(&self) -> &[u64] {
+        &self.column_chunk_sizes
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LayoutPlanner {
+    iounit_size: u64,
+    min_row_group_size: u64,
+    max_row_group_size: u64,
+}
+
+impl Default for LayoutPlanner {
+    fn default() -> Self {
+        Self::new(DEFAULT_IOUNIT_SIZE)
+    }
+}
+
+impl LayoutPlanner {
+    /// By default, row groups have between 1Ki and 1Mi rows.
+    pub fn new(iounit_size: u64) -> Self {
+        Self {
+            iounit_size,
+            min_row_group_size: 1024,
+            max_row_group_size: 1024 * 1024,
+        }
+    }
+
+    /// Bounds of the number of rows of a row group. The upper bound limits the memory buffered by
+    /// the writer.
+    pub fn with_row_group_size_bounds(mut self, min: u64, max: u64) -> Self {
+        assert!(0 < min && min <= max);
+        self.min_row_group_size = min;
+        self.max_row_group_size = max;
+        self
+    }
+
+    /// Plan the layout of a table with the given top-level columns.
+    pub fn plan(&self, columns: &[ColumnSizeEstimate]) -> LayoutPlan {
+        let iounit_size = self.iounit_size as f64;
+        let encoded_sizes: Vec<f64> = columns
+            .iter()
+            .map(ColumnSizeEstimate::encoded_bytes_per_row)
+            .collect();
+        let smallest = encoded_sizes
+            .iter()
+            .copied()
+            .filter(|&size| size > 0.0)
+            .fold(f64::INFINITY, f64::min);
+        let row_group_size = if smallest.is_finite() {
+            ((iounit_size / smallest).ceil() as u64)
+                .clamp(self.min_row_group_size, self.max_row_group_size)
+        } else {
+            self.max_row_group_size
+        };
+        let column_chunk_sizes = encoded_sizes
+            .iter()
+            .map(|&size| even_chunk_size(row_group_size as f64 * size, self.iounit_size))
+            .collect();
+        LayoutPlan {
+            row_group_size,
+            column_chunk_sizes,
+        }
+    }
+}
+
+/// Size of the chunks splitting evenly `column_size` bytes into chunks of up to `iounit_size`
+/// bytes. A column smaller than an IOUnit is kept in a single chunk.
+fn even_chunk_size(column_size: f64, iounit_size: u64) -> u64 {
+    let num_chunks = (column_size / iounit_size as f64).ceil().max(1.0);
+    ((column_size / num_chunks).ceil() as u64).clamp(1, iounit_size)
+}
+
+/// Size the chunks of each top-level column from the encoded size of its first rows, see
+/// [`FileWriterOptionsBuilder::set_auto_column_chunk_size`](crate::options::FileWriterOptionsBuilder::set_auto_column_chunk_size).
+///
+/// Once `sample_rows` rows are encoded, the chunks of each column are sized so that a row group
+/// of the column is split evenly into chunks of up to one IOUnit, as [`LayoutPlanner::plan`]
+/// does, then clamped between `min_chunk_size` and `max_chunk_size`. The chunks encoded before
+/// keep the size set in the options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoColumnChunkSize {
+    pub sample_rows: u64,
+    pub min_chunk_size: u64,
+    pub max_chunk_size: u64,
+}
+
+impl AutoColumnChunkSize {
+    /// By default, chunks are sized from the first 64Ki rows, between 64KiB and an IOUnit.
+    pub fn new(iounit_size: u64) -> Self {
+        Self {
+            sample_rows: 64 * 1024,
+            min_chunk_size: (64 * 1024).min(iounit_size),
+            max_chunk_size: iounit_size,
+        }
+    }
+
+    /// Chunk size of a column whose first `num_rows` rows are encoded into `encoded_bytes`, with
+    /// row groups of `row_group_size` rows.
+    pub fn chunk_size(
+        &self,
+        encoded_bytes: u64,
+        num_rows: u64,
+        row_group_size: u64,
+        iounit_size: u64,
+    ) -> u64 {
+        let bytes_per_row = encoded_bytes as f64 / num_rows.max(1) as f64;
+        even_chunk_size(row_group_size as f64 * bytes_per_row, iounit_size).clamp(
+            self.min_chunk_size,
+            self.max_chunk_size.max(self.min_chunk_size),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::Int64Array;
+
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+
+    #[test]
+    fn test_plan() {
+        let planner = LayoutPlanner::new(8 * MIB).with_row_group_size_bounds(1024, 10 * MIB);
+        let plan = planner.plan(&[
+            // 1 byte per row once encoded.
+            ColumnSizeEstimate::new(8.0, 8.0),
+            // 20 bytes per row once encoded.
+            ColumnSizeEstimate::new(40.0, 2.0),
+        ]);
+        // The best compressed column fills one IOUnit.
+        assert_eq!(plan.row_group_size(), 8 * MIB);
+        assert_eq!(plan.column_chunk_sizes()[0], 8 * MIB);
+        // 160MiB split into 20 chunks.
+        assert_eq!(plan.column_chunk_sizes()[1], 8 * MIB);
+
+        // 1.5 IOUnits are split into two even chunks instead of a full one and a half one.
+        let plan = planner.plan(&[
+            ColumnSizeEstimate::new(2.0, 1.0),
+            ColumnSizeEstimate::new(3.0, 1.0),
+        ]);
+        assert_eq!(plan.row_group_size(), 4 * MIB);
+        assert_eq!(plan.column_chunk_sizes(), &[8 * MIB, 6 * MIB]);
+    }
+
+    #[test]
+    fn test_plan_bounds() {
+        let planner = LayoutPlanner::new(8 * MIB).with_row_group_size_bounds(1024, 1024 * 1024);
+        let plan = planner.plan(&[ColumnSizeEstimate::new(8.0, 1000.0)]);
+        assert_eq!(plan.row_group_size(), 1024 * 1024);
+        assert_eq!(plan.column_chunk_sizes()[0], 8 * 1024 * 1024 / 1000 + 1);
+        let plan = planner.plan(&[ColumnSizeEstimate::new(0.0, 1.0)]);
+        assert_eq!(plan.row_group_size(), 1024 * 1024);
+        assert_eq!(plan.column_chunk_sizes(), &[1]);
+
+        let sample = Int64Array::from_iter_values(0..1000);
+        let estimate = ColumnSizeEstimate::from_sample(&sample, 4.0);
+        assert!((estimate.encoded_bytes_per_row() - 2.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_auto_column_chunk_size() {
+        let auto = AutoColumnChunkSize::new(8 * MIB);
+        // 1 byte per row, 1Mi rows per row group.
+        assert_eq!(auto.chunk_size(1000, 1000, MIB, 8 * MIB), MIB);
+        // 12MiB per row group split into two even chunks.
+        assert_eq!(auto.chunk_size(12_000, 1000, MIB, 8 * MIB), 6 * MIB);
+        // Clamped to the bounds.
+        assert_eq!(auto.chunk_size(1, 1000, MIB, 8 * MIB), 64 * 1024);
+        let auto = AutoColumnChunkSize {
+            max_chunk_size: 4 * MIB,
+            ..auto
+        };
+        assert_eq!(auto.chunk_size(12_000, 1000, MIB, 8 * MIB), 4 * MIB);
+    }
+}
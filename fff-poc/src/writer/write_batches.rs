@@ -0,0 +1,159 @@
+//! Write a sequence of batches to a file in one call.
+
+use std::borrow::Borrow;
+use std::io::{Seek, Write};
+use std::time::{Duration, Instant};
+
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use fff_core::errors::Result;
+
+use super::FileWriter;
+use crate::counter::EncodingCounter;
+use crate::file::manifest::FileManifest;
+use crate::options::FileWriterOptions;
+
+/// Passed to the progress callback of [`write_batches_with_progress`] after each batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteProgress {
+    pub num_batches: usize,
+    pub num_rows: u64,
+    /// Bytes flushed to the sink so far. Buffered data is not counted.
+    pub bytes_written: u64,
+    /// Memory buffered by the column encoders.
+    pub memory_size: usize,
+}
+
+/// Result of [`write_batches`].
+#[derive(Debug, Clone)]
+pub struct WriteStats {
+    pub num_batches: usize,
+    /// Highest memory buffered by the column encoders after a batch.
+    pub peak_memory_size: usize,
+    /// Average memory buffered by the column encoders, over the batches after which some data was
+    /// still buffered.
+    pub avg_memory_size: usize,
+    pub elapsed: Duration,
+    pub encoding_counters: Vec<EncodingCounter>,
+    /// Rows, row groups and size of the file.
+    pub manifest: FileManifest,
+}
+
+/// Write `batches` of `schema` to `sink` and finish the file.
+pub fn write_batches<W: Write + Seek>(
+    schema: SchemaRef,
+    batches: impl IntoIterator<Item = impl Borrow<RecordBatch>>,
+    sink: W,
+    options: FileWriterOptions,
+) -> Result<WriteStats> {
+    write_batches_with_progress(schema, batches, sink, options, |_| {})
+}
+
+/// Like [`write_batches`], calling `progress` after each batch, e.g., to log or to report the
+/// progress of a long conversion.
+pub fn write_batches_with_progress<W: Write + Seek>(
+    schema: SchemaRef,
+    batches: impl IntoIterator<Item = impl Borrow<RecordBatch>>,
+    sink: W,
+    options: FileWriterOptions,
+    mut progress: impl FnMut(&WriteProgress),
+) -> Result<WriteStats> {
+    let start = Instant::now();
+    let mut writer = FileWriter::try_new(schema, sink, options)?;
+    let mut num_batches = 0;
+    let mut peak_memory_size = 0;
+    let mut memory_size_sum = 0;
+    let mut num_buffering_batches = 0;
+    for batch in batches {
+        writer.write_batch(batch.borrow())?;
+        num_batches += 1;
+        let memory_size = writer.memory_size();
+        peak_memory_size = peak_memory_size.max(memory_size);
+        if memory_size != 0 {
+            memory_size_sum += memory_size;
+            num_buffering_batches += 1;
+        }
+        progress(&WriteProgress {
+            num_batches,
+            num_rows: writer.num_rows(),
+            bytes_written: writer.bytes_written()?,
+            memory_size,
+        });
+    }
+    let (encoding_counters, manifest) = writer.finish_with_manifest()?;
+    Ok(WriteStats {
+        num_batches,
+        peak_memory_size,
+        avg_memory_size: memory_size_sum
+            .checked_div(num_buffering_batches)
+            .unwrap_or(0),
+        elapsed: start.elapsed(),
+        encoding_counters,
+        manifest,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Int32Array, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+
+    use super::*;
+    use crate::options::FileWriterOptionsBuilder;
+    use crate::reader::FileReaderV2Builder;
+
+    #[test]
+    fn test_write_batches() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Utf8, true),
+        ]));
+        let batches: Vec<_> = (0..5)
+            .map(|i| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Int32Array::from_iter_values(i * 100..(i + 1) * 100)),
+                        Arc::new(StringArray::from_iter(
+                            (0..100).map(|x| (x % 3 != 0).then(|| format!("s{x}"))),
+                        )),
+                    ],
+                )
+                .unwrap()
+            })
+            .collect();
+        let file = tempfile::tempfile().unwrap();
+        let mut progress = vec![];
+        let stats = write_batches_with_progress(
+            schema.clone(),
+            &batches,
+            &file,
+            FileWriterOptionsBuilder::with_defaults()
+                .set_row_group_size(200)
+                .build(),
+            |p| progress.push(*p),
+        )
+        .unwrap();
+        assert_eq!(stats.num_batches, 5);
+        assert_eq!(stats.manifest.num_rows, 500);
+        assert_eq!(stats.manifest.num_row_groups, 3);
+        assert_eq!(stats.manifest.size, file.metadata().unwrap().len());
+        assert_eq!(
+            progress.iter().map(|p| p.num_rows).collect::<Vec<_>>(),
+            vec![100, 200, 300, 400, 500]
+        );
+        assert!(progress
+            .windows(2)
+            .all(|w| w[0].bytes_written <= w[1].bytes_written));
+        assert!(stats.peak_memory_size >= stats.avg_memory_size);
+
+        let read = FileReaderV2Builder::new(Arc::new(file))
+            .build()
+            .unwrap()
+            .read_file()
+            .unwrap();
+        assert_eq!(read.iter().map(RecordBatch::num_rows).sum::<usize>(), 500);
+    }
+}
@@ -7,6 +7,7 @@ use std::{
 use arrow_array::RecordBatch;
 use fff_core::errors::Error;
 use fff_poc::{
+    io::reader::ReplicaSet,
     options::FileWriterOptions,
     reader::{FileReaderV2Builder, Selection},
     writer::FileWriter,
@@ -56,6 +57,39 @@ fn corrupted_iounit() {
     ));
 }
 
+#[test]
+fn corrupted_iounit_replica() {
+    let options = FileWriterOptions::builder()
+        .enable_io_unit_checksum(true)
+        .build();
+    let replica = prepare_test_file(options);
+    let corrupted = {
+        let mut src = replica.clone();
+        src.seek(SeekFrom::Start(0)).unwrap();
+        let mut dst = tempfile::tempfile().unwrap();
+        std::io::copy(&mut src, &mut dst).unwrap();
+        dst.seek(SeekFrom::Start(100)).unwrap();
+        dst.write_all(&[0; 100]).unwrap();
+        Arc::new(dst)
+    };
+
+    let read = |replicas| {
+        FileReaderV2Builder::new(ReplicaSet::try_new(replicas).unwrap())
+            .with_verify_io_unit_checksum(true)
+            .with_selection(Selection::RowIndexes(vec![5]))
+            .build()
+            .unwrap()
+            .read_file()
+    };
+    // The corrupted chunk is read again from the second replica.
+    let expected = read(vec![replica.clone()]).unwrap();
+    assert_eq!(read(vec![corrupted.clone(), replica]).unwrap(), expected);
+    assert!(matches!(
+        read(vec![corrupted.clone(), corrupted]),
+        Err(Error::General(e)) if e.eq("Checksum verification failed")
+    ));
+}
+
 #[test]
 fn corrupted_flatbuffer() {
     let options = FileWriterOptions::builder().build();
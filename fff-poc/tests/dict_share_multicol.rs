@@ -1,8 +1,12 @@
 use std::sync::Arc;
 
-use arrow_array::{ArrayRef, Int32Array, RecordBatch};
-use arrow_schema::{Field, Schema};
-use fff_poc::{options::FileWriterOptions, reader::FileReaderV2Builder, writer::FileWriter};
+use arrow_array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use fff_poc::{
+    options::{DictionaryTypeOptions, FileWriterOptions},
+    reader::FileReaderV2Builder,
+    writer::FileWriter,
+};
 
 #[test]
 fn test_multi_col_share_dict() {
@@ -62,3 +66,61 @@ fn test_multi_col_share_dict() {
     eprintln!("Shared counters: {:?}", counters);
     eprintln!("Sharing peers: {:?}", sharing_peers);
 }
+
+#[test]
+fn test_auto_share_dict() {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("origin", DataType::Utf8, false),
+        Field::new("destination", DataType::Utf8, false),
+        Field::new("carrier", DataType::Utf8, false),
+        Field::new("gate", DataType::Int32, false),
+    ]));
+    let num_rows = 20_000;
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(StringArray::from_iter_values(
+                (0..num_rows).map(|x| format!("city{}", x * 7 % 500)),
+            )) as ArrayRef,
+            Arc::new(StringArray::from_iter_values(
+                (0..num_rows).map(|x| format!("city{}", x * 13 % 500)),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                (0..num_rows).map(|x| format!("carrier{}", x % 40)),
+            )),
+            Arc::new(Int32Array::from_iter_values((0..num_rows).map(|x| x % 500))),
+        ],
+    )
+    .unwrap();
+    let write = |dictionary_type| {
+        let file = Arc::new(tempfile::tempfile().unwrap());
+        let options = FileWriterOptions::builder()
+            .set_dictionary_type(dictionary_type)
+            .set_row_group_size(8_000)
+            .build();
+        let mut writer = FileWriter::try_new(schema.clone(), file.clone(), options).unwrap();
+        for offset in (0..num_rows as usize).step_by(4_000) {
+            writer.write_batch(&batch.slice(offset, 4_000)).unwrap();
+        }
+        writer.finish().unwrap();
+        file
+    };
+    let file = write(DictionaryTypeOptions::GlobalDictionaryAutoSharing);
+    let mut reader = FileReaderV2Builder::new(file.clone()).build().unwrap();
+    let read = reader.read_file().unwrap();
+    let read = arrow::compute::concat_batches(read[0].schema_ref(), &read).unwrap();
+    for (read, written) in read.columns().iter().zip(batch.columns()) {
+        // Strings may be read as views.
+        let read = arrow::compute::cast(read, written.data_type()).unwrap();
+        assert_eq!(&read, written);
+    }
+    // The two columns of cities share one dictionary, the others keep their own.
+    let (_, sharing_peers) = reader.get_shared_dict_sizes().unwrap();
+    assert!(sharing_peers[0].iter().all(|&(peer, _)| peer == 1));
+    assert!(sharing_peers[1].iter().any(|&(peer, _)| peer == 0));
+    assert!(sharing_peers[2].is_empty());
+    assert!(sharing_peers[3].is_empty());
+
+    let unshared = write(DictionaryTypeOptions::GlobalDictionary);
+    assert!(file.metadata().unwrap().len() < unshared.metadata().unwrap().len());
+}
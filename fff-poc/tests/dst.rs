@@ -0,0 +1,200 @@
+//! Deterministic simulation tests, run with `cargo test --features dst --test dst`. A failure
+//! reports its seed, which replays the same faults and schedule.
+#![cfg(feature = "dst")]
+
+use std::{io::Write, sync::Arc, time::Duration};
+
+use arrow_array::{Int64Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use fff_poc::{
+    options::FileWriterOptionsBuilder,
+    reader::FileReaderV2Builder,
+    sim::{SimConfig, Simulation},
+    writer::{AsyncFileWriter, FileWriter},
+};
+use object_store::{memory::InMemory, path::Path, ObjectStore};
+
+const NUM_SEEDS: u64 = 32;
+
+fn batches() -> Vec<RecordBatch> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int64, false),
+        Field::new("b", DataType::Utf8, true),
+    ]));
+    (0..4)
+        .map(|i| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from_iter_values(i * 5_000..(i + 1) * 5_000)),
+                    Arc::new(StringArray::from_iter(
+                        (i * 5_000..(i + 1) * 5_000).map(|x| (x % 3 != 0).then(|| x.to_string())),
+                    )),
+                ],
+            )
+            .unwrap()
+        })
+        .collect()
+}
+
+fn read_all(file: Arc<std::fs::File>) -> Vec<RecordBatch> {
+    FileReaderV2Builder::new(file)
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap()
+}
+
+fn assert_same_rows(actual: &[RecordBatch], expected: &[RecordBatch]) {
+    let schema = expected[0].schema();
+    assert_eq!(
+        arrow::compute::concat_batches(&schema, actual).unwrap(),
+        arrow::compute::concat_batches(&schema, expected).unwrap()
+    );
+}
+
+/// Reads either fail or return the file, and a seed always takes the same path.
+#[test]
+fn test_sim_reader() {
+    let batches = batches();
+    let file = Arc::new(tempfile::tempfile().unwrap());
+    let mut writer = FileWriter::try_new(
+        batches[0].schema(),
+        file.clone(),
+        FileWriterOptionsBuilder::with_defaults()
+            .set_row_group_size(5_000)
+            .build(),
+    )
+    .unwrap();
+    for batch in &batches {
+        writer.write_batch(batch).unwrap();
+    }
+    writer.finish().unwrap();
+
+    let config = SimConfig {
+        io_error_probability: 0.05,
+        ..Default::default()
+    };
+    let run = |seed| {
+        let sim = Simulation::new(seed, config.clone());
+        let result = FileReaderV2Builder::new(sim.reader(file.clone()))
+            .build()
+            .and_then(|mut reader| reader.read_file());
+        (result, sim.trace())
+    };
+    let mut num_failures = 0;
+    for seed in 0..NUM_SEEDS {
+        let (result, trace) = run(seed);
+        match &result {
+            Ok(read) => {
+                assert!(trace.is_empty(), "seed {seed}");
+                assert_same_rows(read, &batches);
+            }
+            Err(_) => {
+                assert!(!trace.is_empty(), "seed {seed}");
+                num_failures += 1;
+            }
+        }
+        let (replayed, replayed_trace) = run(seed);
+        assert_eq!(replayed.is_ok(), result.is_ok(), "seed {seed}");
+        assert_eq!(replayed_trace, trace, "seed {seed}");
+    }
+    assert!(num_failures > 0 && num_failures < NUM_SEEDS);
+}
+
+/// Uploads either complete with the whole file or leave no object, and a seed always takes the
+/// same path.
+#[test]
+fn test_sim_async_writer() {
+    let batches = batches();
+    let config = SimConfig {
+        io_error_probability: 0.02,
+        ..Default::default()
+    };
+    let run = |seed| {
+        let sim = Simulation::new(seed, config.clone());
+        let inner: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let store: Arc<dyn ObjectStore> = sim.object_store(inner.clone());
+        let location = Path::from("sim.fff");
+        let result = sim.run(async {
+            let mut writer = AsyncFileWriter::try_new_with_part_size(
+                batches[0].schema(),
+                store,
+                &location,
+                FileWriterOptionsBuilder::with_defaults()
+                    .set_row_group_size(5_000)
+                    .build(),
+                16 * 1024,
+            )
+            .await?;
+            for batch in &batches {
+                writer.write_batch(batch).await?;
+            }
+            writer.finish().await
+        });
+        let uploaded = sim.run(async {
+            match inner.get(&location).await {
+                Ok(object) => Some(object.bytes().await.unwrap()),
+                Err(_) => None,
+            }
+        });
+        (result.is_ok(), uploaded, sim.trace())
+    };
+    let mut num_failures = 0;
+    for seed in 0..NUM_SEEDS {
+        let (ok, uploaded, trace) = run(seed);
+        if ok {
+            let mut file = tempfile::tempfile().unwrap();
+            file.write_all(&uploaded.expect("Finished uploads are visible"))
+                .unwrap();
+            assert_same_rows(&read_all(Arc::new(file)), &batches);
+        } else {
+            assert!(!trace.is_empty(), "seed {seed}");
+            assert!(uploaded.is_none(), "seed {seed}");
+            num_failures += 1;
+        }
+        let (replayed_ok, _, replayed_trace) = run(seed);
+        assert_eq!(replayed_ok, ok, "seed {seed}");
+        assert_eq!(replayed_trace, trace, "seed {seed}");
+    }
+    assert!(num_failures < NUM_SEEDS);
+}
+
+/// Writers cancelled by a timeout in the middle of the upload leave no object.
+#[test]
+fn test_sim_cancelled_upload() {
+    let batches = batches();
+    let config = SimConfig {
+        io_error_probability: 0.0,
+        max_latency: Duration::from_secs(1),
+        ..Default::default()
+    };
+    for seed in 0..NUM_SEEDS {
+        let sim = Simulation::new(seed, config.clone());
+        let inner: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let store: Arc<dyn ObjectStore> = sim.object_store(inner.clone());
+        let location = Path::from("cancelled.fff");
+        let write = async {
+            let mut writer = AsyncFileWriter::try_new_with_part_size(
+                batches[0].schema(),
+                store,
+                &location,
+                FileWriterOptionsBuilder::with_defaults().build(),
+                16 * 1024,
+            )
+            .await
+            .unwrap();
+            for batch in &batches {
+                writer.write_batch(batch).await.unwrap();
+            }
+            writer.finish().await.unwrap();
+        };
+        let cancelled = sim.run(async {
+            tokio::time::timeout(Duration::from_millis(1500), write)
+                .await
+                .is_err()
+        });
+        let exists = sim.run(async { inner.head(&location).await.is_ok() });
+        assert_eq!(exists, !cancelled, "seed {seed}");
+    }
+}
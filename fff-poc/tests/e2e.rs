@@ -1,7 +1,10 @@
 use arrow::{
     array::AsArray,
     compute::take_record_batch,
-    datatypes::{BinaryType, BinaryViewType, LargeUtf8Type, StringViewType, Utf8Type},
+    datatypes::{
+        i256, BinaryType, BinaryViewType, Float64Type, Int32Type, LargeBinaryType, LargeUtf8Type,
+        StringViewType, Utf8Type,
+    },
 };
 use core::panic;
 use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
@@ -10,17 +13,44 @@ use rstest_reuse::apply;
 use std::{collections::HashMap, io::Seek, path::Path, sync::Arc};
 
 use arrow::{
-    array::{Int32Builder, ListBuilder},
+    array::{Int32Builder, ListBuilder, MapBuilder, StringBuilder},
     compute::concat_batches,
 };
-use arrow_array::{Array, ArrayRef, GenericByteViewArray, Int32Array, RecordBatch, UInt64Array};
-use arrow_schema::{ArrowError, DataType, Field, Schema};
+use arrow_array::{
+    Array, ArrayRef, BooleanArray, Decimal128Array, Decimal256Array, DictionaryArray,
+    FixedSizeListArray, Float64Array, GenericByteViewArray, Int32Array, Int64Array,
+    LargeBinaryArray, LargeStringArray, RecordBatch, RecordBatchReader, StringArray,
+    TimestampMicrosecondArray, TimestampMillisecondArray, TimestampNanosecondArray,
+    TimestampSecondArray, UInt32Array, UInt64Array,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema, TimeUnit};
 use fff_poc::{
     context::{WASMId, WasmLib},
-    io::reader::{ObjectStoreReadAt, Reader},
-    options::{CustomEncodingOptions, FileWriterOptions, FileWriterOptionsBuilder},
-    reader::{FileReaderV2Builder, Projection, Selection},
-    writer::FileWriter,
+    dataset::{DatasetManifest, DatasetWriter},
+    diff::diff_files,
+    file::{
+        key_value_metadata::MetadataValue, manifest::FileManifest, wasm_modules::wasm_module_hash,
+    },
+    io::{
+        mmap::LocalFile,
+        read_ahead::{AccessPattern, ReadAheadReader},
+        reader::{CountingReader, ObjectStoreReadAt, Reader},
+    },
+    options::{
+        AdaptiveEncodingOptions, CompressionCostModel, CompressionLevel, CustomEncodingOptions,
+        DictionaryTypeOptions, FileWriterOptions, FileWriterOptionsBuilder,
+    },
+    reader::{
+        decode_support, find_columns, get_avg_encunit_num_rows, get_avg_io_unit_size,
+        get_bloom_filters, get_column_families, get_column_statistics, get_column_wasms,
+        get_encunit_index, get_footer_versions, get_reserved_padding, get_schema_fingerprint,
+        get_unreferenced_bytes, get_wasm_aot_artifacts, group_by_schema, open_at_version,
+        prune_encunits, same_schema, ComparisonOp, DecodePath, DecodeSupport, DefaultValueProvider,
+        FileReaderV2Builder, FooterCache, FooterCacheKey, InterleavedReader, LruChunkCache,
+        Preference, Projection, ResourceReport, RowFilter, Selection, TimestampNormalization,
+        WasmModuleCache, WasmResolver,
+    },
+    writer::{input_validation::InputValidation, FileWriter},
 };
 use object_store::{aws::AmazonS3Builder, ObjectStore};
 
@@ -37,7 +67,12 @@ fn read_parquet_file(file_path: impl AsRef<Path>, batch_size: usize) -> Vec<Reco
 fn array_equal(i: &Arc<dyn Array>, o: &Arc<dyn Array>) {
     assert_eq!(i.len(), o.len());
 
-    if o.data_type().is_primitive() || matches!(o.data_type(), DataType::Binary | DataType::Utf8) {
+    if o.data_type().is_primitive()
+        || matches!(
+            o.data_type(),
+            DataType::Binary | DataType::Utf8 | DataType::Dictionary(_, _)
+        )
+    {
         assert_eq!(i, o)
     } else if o.as_byte_view_opt::<StringViewType>().is_some() {
         match *i.data_type() {
@@ -55,8 +90,10 @@ fn array_equal(i: &Arc<dyn Array>, o: &Arc<dyn Array>) {
             _ => panic!(),
         }
     } else if o.as_byte_view_opt::<BinaryViewType>().is_some() {
-        let i: GenericByteViewArray<BinaryViewType> =
-            GenericByteViewArray::from(i.as_bytes::<BinaryType>());
+        let i: GenericByteViewArray<BinaryViewType> = match *i.data_type() {
+            DataType::LargeBinary => GenericByteViewArray::from(i.as_bytes::<LargeBinaryType>()),
+            _ => GenericByteViewArray::from(i.as_bytes::<BinaryType>()),
+        };
         assert_eq!(&(Arc::new(i) as Arc<dyn Array>), o);
     } else if let DataType::Struct(_) = o.data_type() {
         let i = i.as_struct();
@@ -68,6 +105,23 @@ fn array_equal(i: &Arc<dyn Array>, o: &Arc<dyn Array>) {
         let i = i.as_list::<i32>();
         let o = o.as_list::<i32>();
         array_equal(i.values(), o.values());
+    } else if let DataType::FixedSizeList(_, _) = o.data_type() {
+        let i = i.as_fixed_size_list();
+        let o = o.as_fixed_size_list();
+        assert_eq!(i.nulls(), o.nulls());
+        for row in (0..i.len()).filter(|&row| i.is_valid(row)) {
+            array_equal(&i.value(row), &o.value(row));
+        }
+    } else if let DataType::Map(_, _) = o.data_type() {
+        let i = i.as_map();
+        let o = o.as_map();
+        assert_eq!(i.nulls(), o.nulls());
+        for row in (0..i.len()).filter(|&row| i.is_valid(row)) {
+            array_equal(
+                &(Arc::new(i.value(row)) as ArrayRef),
+                &(Arc::new(o.value(row)) as ArrayRef),
+            );
+        }
     } else {
         unimplemented!()
     }
@@ -303,6 +357,81 @@ fn test_complex_list_roundtrip2() {
     );
 }
 
+fn map_array() -> ArrayRef {
+    let mut builder = MapBuilder::new(None, StringBuilder::new(), Int32Builder::new());
+    for i in 0..3000 {
+        if i % 7 == 0 {
+            builder.append(false).unwrap();
+            continue;
+        }
+        for j in 0..i % 4 {
+            builder.keys().append_value(format!("k{j}"));
+            builder.values().append_option((j != 1).then_some(i));
+        }
+        builder.append(true).unwrap();
+    }
+    Arc::new(builder.finish())
+}
+
+fn fixed_size_list_array() -> ArrayRef {
+    Arc::new(FixedSizeListArray::from_iter_primitive::<Int32Type, _, _>(
+        (0..3000)
+            .map(|i| (i % 5 != 0).then(|| vec![Some(i), (i % 3 != 0).then_some(2 * i), Some(-i)])),
+        3,
+    ))
+}
+
+#[rstest]
+#[case::map(map_array())]
+#[case::fixed_size_list(fixed_size_list_array())]
+#[case::large_binary(Arc::new(LargeBinaryArray::from_iter(
+    (0..3000).map(|i| (i % 4 != 0).then(|| format!("b{i}").into_bytes()))
+)))]
+#[case::large_utf8(Arc::new(LargeStringArray::from_iter(
+    (0..3000).map(|i| (i % 4 != 0).then(|| format!("s{i}")))
+)))]
+#[case::decimal128(Arc::new(
+    Decimal128Array::from_iter((0..3000).map(|i| (i % 6 != 0).then_some(1001 * i - 50_000)))
+        .with_precision_and_scale(10, 2)
+        .unwrap()
+))]
+#[case::wide_decimal128(Arc::new(
+    Decimal128Array::from_iter((0..3000).map(|i| (i % 6 != 0).then_some(-i * 10i128.pow(30))))
+        .with_precision_and_scale(38, 5)
+        .unwrap()
+))]
+#[case::decimal256(Arc::new(
+    Decimal256Array::from_iter((0..3000).map(|i| {
+        (i % 6 != 0).then(|| i256::from_i128(i).wrapping_mul(i256::from_i128(10i128.pow(30))))
+    }))
+    .with_precision_and_scale(70, 4)
+    .unwrap()
+))]
+#[case::dictionary(Arc::new(DictionaryArray::<Int32Type>::from_iter(
+    (0..3000).map(|i| (i % 9 != 0).then(|| ["x", "y", "z"][i % 3]))
+)))]
+fn test_logical_type_roundtrip(#[case] array: ArrayRef) {
+    let schema = Arc::new(Schema::new(vec![Field::new(
+        "a",
+        array.data_type().clone(),
+        true,
+    )]));
+    let input_batches = (0..3)
+        .map(|i| RecordBatch::try_new(schema.clone(), vec![array.slice(i * 1000, 1000)]).unwrap())
+        .collect::<Vec<_>>();
+    for selection in [
+        Selection::All,
+        Selection::new([0, 5, 999, 1000, 2001, 2999]),
+    ] {
+        test_read_file_roundtrip(
+            &input_batches,
+            Projection::default(),
+            FileWriterOptions::default(),
+            selection,
+        );
+    }
+}
+
 #[test]
 fn test_projection() {
     let schema = Schema::new(vec![
@@ -324,27 +453,4052 @@ fn test_projection() {
     );
 }
 
-#[apply(enable_built_in_wasm)]
-fn test_row_selection_basic(#[case] enable_built_in_wasm: bool) {
-    let schema = Schema::new(vec![
-        Field::new("a", DataType::Int32, false),
-        Field::new("b", DataType::Int32, true),
-    ]);
-    // create some data
-    let a = Int32Array::from(vec![1, 2, 3, 4, 5]);
-    let b = Int32Array::from(vec![5, 4, 3, 2, 1]);
-
-    // build a record batch
-    let input_batch =
-        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(a), Arc::new(b)]).unwrap();
-    test_read_file_roundtrip(
-        &[input_batch],
-        Projection::default(),
+#[apply(enable_built_in_wasm)]
+fn test_row_selection_basic(#[case] enable_built_in_wasm: bool) {
+    let schema = Schema::new(vec![
+        Field::new("a", DataType::Int32, false),
+        Field::new("b", DataType::Int32, true),
+    ]);
+    // create some data
+    let a = Int32Array::from(vec![1, 2, 3, 4, 5]);
+    let b = Int32Array::from(vec![5, 4, 3, 2, 1]);
+
+    // build a record batch
+    let input_batch =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(a), Arc::new(b)]).unwrap();
+    test_read_file_roundtrip(
+        &[input_batch],
+        Projection::default(),
+        FileWriterOptionsBuilder::with_defaults()
+            .write_built_in_wasm(enable_built_in_wasm)
+            .build(),
+        Selection::RowIndexes(vec![3]),
+    );
+}
+
+#[apply(enable_built_in_wasm)]
+fn test_append_row_groups(#[case] enable_built_in_wasm: bool) {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, true),
+    ]));
+    let batch = |start: i32| {
+        RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(start..start + 1000)),
+                Arc::new(arrow::array::StringArray::from_iter_values(
+                    (start..start + 1000).map(|i| i.to_string()),
+                )),
+            ],
+        )
+        .unwrap()
+    };
+    let options = || {
+        FileWriterOptionsBuilder::with_defaults()
+            .write_built_in_wasm(enable_built_in_wasm)
+            .build()
+    };
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(&mut file, &[batch(0)], options());
+
+    let reader = Arc::new(file.try_clone().unwrap());
+    let mut writer = FileWriter::try_append(schema.clone(), reader, &mut file, options()).unwrap();
+    writer.write_batch(&batch(1000)).unwrap();
+    writer.finish().unwrap();
+
+    // A non-nullable field can be appended to a nullable one, but not a different type.
+    let incompatible = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int64, false),
+        Field::new("b", DataType::Utf8, true),
+    ]));
+    let reader = Arc::new(file.try_clone().unwrap());
+    assert!(FileWriter::try_append(incompatible, reader, &mut file, options()).is_err());
+
+    file.rewind().unwrap();
+    let file = Arc::new(file);
+    FileReaderV2Builder::new(file.clone())
+        .with_verify_file_checksum(true)
+        .build()
+        .unwrap();
+    test_read(
+        file,
+        &[batch(0), batch(1000)],
+        Projection::default(),
+        Selection::default(),
+    );
+}
+
+#[test]
+fn test_footer_versioning() {
+    let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+    let batch = |start: i32, len: i32| {
+        RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(start..start + len))],
+        )
+        .unwrap()
+    };
+    let options = || {
+        FileWriterOptionsBuilder::with_defaults()
+            .set_footer_versioning(true)
+            .build()
+    };
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(&mut file, &[batch(0, 1000)], options());
+    for (start, len) in [(1000, 500), (1500, 200)] {
+        let reader = Arc::new(file.try_clone().unwrap());
+        let mut writer =
+            FileWriter::try_append(schema.clone(), reader, &mut file, options()).unwrap();
+        writer.write_batch(&batch(start, len)).unwrap();
+        writer.finish().unwrap();
+    }
+
+    let file = Arc::new(file);
+    let versions = get_footer_versions(file.clone()).unwrap();
+    assert_eq!(versions.len(), 3);
+    assert!(versions.windows(2).all(|w| w[0] < w[1]));
+    assert_eq!(*versions.last().unwrap(), file.size().unwrap());
+    for (version, num_rows) in [1000, 1500, 1700].into_iter().enumerate() {
+        let snapshot = open_at_version(file.clone(), version).unwrap();
+        FileReaderV2Builder::new(snapshot.clone())
+            .with_verify_file_checksum(true)
+            .build()
+            .unwrap();
+        test_read(
+            snapshot,
+            &[batch(0, num_rows)],
+            Projection::default(),
+            Selection::default(),
+        );
+    }
+    assert!(open_at_version(file.clone(), 3).is_err());
+    test_read(
+        file,
+        &[batch(0, 1700)],
+        Projection::default(),
+        Selection::default(),
+    );
+}
+
+#[test]
+fn test_schema_fingerprint() {
+    let write = |schema: Schema| {
+        let schema = Arc::new(schema);
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![Some(1), None, Some(3)])),
+                Arc::new(StringArray::from(vec!["a", "b", "c"])),
+            ],
+        )
+        .unwrap();
+        let mut file = tempfile::tempfile().unwrap();
+        write_batches(
+            &mut file,
+            &[batch],
+            FileWriterOptionsBuilder::with_defaults().build(),
+        );
+        Arc::new(file)
+    };
+    let fields = vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, false),
+    ];
+    let plain = write(Schema::new(fields.clone()));
+    // Metadata does not change the fingerprint.
+    let with_metadata = write(Schema::new_with_metadata(
+        fields,
+        HashMap::from([("origin".to_string(), "test".to_string())]),
+    ));
+    let other = write(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("c", DataType::Utf8, false),
+    ]));
+
+    let fingerprint = get_schema_fingerprint(&plain).unwrap();
+    assert_ne!(fingerprint, 0);
+    assert_eq!(get_schema_fingerprint(&with_metadata).unwrap(), fingerprint);
+    assert_ne!(get_schema_fingerprint(&other).unwrap(), fingerprint);
+    assert!(same_schema(&plain, &with_metadata).unwrap());
+    assert!(!same_schema(&plain, &other).unwrap());
+    assert_eq!(
+        group_by_schema(&[plain.clone(), other.clone(), with_metadata, other]).unwrap(),
+        vec![vec![0, 2], vec![1, 3]]
+    );
+}
+
+#[test]
+fn test_budgeted_metadata() {
+    let num_columns = 2000;
+    let schema = Arc::new(Schema::new(
+        (0..num_columns)
+            .map(|i| Field::new(format!("c{i}"), DataType::Int32, true))
+            .collect::<Vec<_>>(),
+    ));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        (0..num_columns)
+            .map(|i| Arc::new(Int32Array::from_iter_values(i..i + 100)) as ArrayRef)
+            .collect(),
+    )
+    .unwrap();
+    let write = |budgeted_metadata| {
+        let mut file = tempfile::tempfile().unwrap();
+        write_batches(
+            &mut file,
+            &[batch.clone()],
+            FileWriterOptionsBuilder::with_defaults()
+                .set_budgeted_metadata(budgeted_metadata)
+                .build(),
+        );
+        Arc::new(file)
+    };
+    let plain = write(None);
+    let budgeted = write(Some(256));
+
+    assert_eq!(
+        find_columns(&budgeted, &["c1", "c1500", "missing"]).unwrap(),
+        vec![Some(1), Some(1500), None]
+    );
+    assert!(find_columns(&plain, &["c1"]).is_err());
+
+    // Opening reads the segments of the projected columns instead of a section per column.
+    let projection = Projection::new([1, 1500]);
+    let open_bytes_read = |file: Arc<std::fs::File>| {
+        let reader = CountingReader::new(file);
+        FileReaderV2Builder::new(reader.clone())
+            .with_projections(projection.clone())
+            .build()
+            .unwrap();
+        reader.metrics().bytes_read
+    };
+    assert!(open_bytes_read(budgeted.clone()) < open_bytes_read(plain));
+
+    test_read(
+        budgeted.clone(),
+        &[batch.clone()],
+        projection,
+        Selection::default(),
+    );
+    test_read(
+        budgeted.clone(),
+        &[batch],
+        Projection::default(),
+        Selection::default(),
+    );
+    assert!(get_column_statistics(budgeted, 0).is_err());
+}
+
+#[test]
+fn test_column_families() {
+    let schema = Arc::new(Schema::new(
+        (0..4)
+            .map(|i| Field::new(format!("c{i}"), DataType::Int32, true))
+            .collect::<Vec<_>>(),
+    ));
+    let batches: Vec<_> = (0..10)
+        .map(|i| {
+            RecordBatch::try_new(
+                schema.clone(),
+                (0..4)
+                    .map(|j| {
+                        Arc::new(Int32Array::from_iter_values(i * 1000 + j..i * 1000 + 1000))
+                            as ArrayRef
+                    })
+                    .collect(),
+            )
+            .unwrap()
+        })
+        .collect();
+    let options = || {
+        // A chunk per column and batch.
+        FileWriterOptionsBuilder::with_defaults()
+            .set_column_chunk_sizes((0..4).map(|i| (i, 1)).collect())
+            .set_row_group_size(5000)
+    };
+    let write = |options: FileWriterOptionsBuilder| {
+        let mut file = tempfile::tempfile().unwrap();
+        write_batches(&mut file, &batches, options.build());
+        Arc::new(file)
+    };
+    let plain = write(options());
+    let families = write(options().set_column_families(vec![vec![0, 2]]));
+
+    assert!(get_column_families(&plain).unwrap().is_empty());
+    let column_families = get_column_families(&families).unwrap();
+    assert_eq!(column_families.len(), 1);
+    assert_eq!(column_families[0].columns, vec![0, 2]);
+    // The chunks of a row group are written at once at its end.
+    assert_eq!(column_families[0].iounits.len(), 2);
+
+    // The chunks of the projected columns of a family are fetched in one read per IO unit.
+    let projection = Projection::new([0, 2]);
+    let scan_requests = |file: Arc<std::fs::File>| {
+        let reader = CountingReader::new(file);
+        let mut file_reader = FileReaderV2Builder::new(reader.clone())
+            .with_projections(projection.clone())
+            .build()
+            .unwrap();
+        let open_requests = reader.metrics().num_requests;
+        file_reader.read_file().unwrap();
+        reader.metrics().num_requests - open_requests
+    };
+    assert_eq!(scan_requests(plain), 20);
+    assert_eq!(scan_requests(families.clone()), 2);
+
+    test_read(families.clone(), &batches, projection, Selection::default());
+    test_read(
+        families.clone(),
+        &batches,
+        Projection::default(),
+        Selection::default(),
+    );
+    test_read(
+        families,
+        &batches,
+        Projection::new([2, 3]),
+        Selection::RowIndexes(vec![3, 4999, 5000, 9999]),
+    );
+
+    let mut file = tempfile::tempfile().unwrap();
+    assert!(FileWriter::try_new(
+        schema.clone(),
+        &mut file,
+        options()
+            .set_column_families(vec![vec![0, 1], vec![1, 2]])
+            .build(),
+    )
+    .is_err());
+    assert!(FileWriter::try_new(
+        schema,
+        &mut file,
+        options().set_column_families(vec![vec![4]]).build(),
+    )
+    .is_err());
+}
+
+#[test]
+fn test_read_ranges() {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, false),
+        Field::new("b", DataType::Int32, false),
+    ]));
+    let batches: Vec<_> = (0..4)
+        .map(|i| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter_values(i * 1000..(i + 1) * 1000)),
+                    Arc::new(Int32Array::from_iter_values((0..1000).map(|x| x % 7))),
+                ],
+            )
+            .unwrap()
+        })
+        .collect();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &batches,
+        FileWriterOptionsBuilder::with_defaults()
+            .set_column_chunk_sizes([(0, 1), (1, 1)].into())
+            .set_column_families(vec![vec![0, 1]])
+            .set_row_group_size(2000)
+            .build(),
+    );
+    let mut bytes = vec![0; Reader::size(&file).unwrap() as usize];
+    Reader::read_exact_at(&file, &mut bytes, 0).unwrap();
+    let store = Arc::new(object_store::memory::InMemory::new());
+    let location = object_store::path::Path::from("ranges.fff");
+    futures::executor::block_on(store.put(&location, bytes.clone().into())).unwrap();
+    let reader = ObjectStoreReadAt::new(store, location.into());
+
+    let ranges = [0..4, 100..300, 10..20];
+    let read = reader.read_ranges(&ranges).unwrap();
+    assert_eq!(read.len(), ranges.len());
+    for (range, read) in ranges.iter().zip(&read) {
+        assert_eq!(
+            read.as_ref(),
+            &bytes[range.start as usize..range.end as usize]
+        );
+    }
+    assert_eq!(Reader::read_ranges(&file, &ranges).unwrap(), read);
+
+    // The chunks of each IO unit of the family are fetched in one vectored request.
+    let counting = CountingReader::new(reader.clone());
+    let mut file_reader = FileReaderV2Builder::new(counting.clone()).build().unwrap();
+    let open_requests = counting.metrics().num_requests;
+    file_reader.read_file().unwrap();
+    assert_eq!(counting.metrics().num_requests - open_requests, 2);
+    test_read(reader, &batches, Projection::All, Selection::All);
+}
+
+#[apply(enable_built_in_wasm)]
+fn test_repack_iounit_size(#[case] enable_built_in_wasm: bool) {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, false),
+    ]));
+    let batches: Vec<_> = (0..4)
+        .map(|i| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter(
+                        (i * 10_000..(i + 1) * 10_000).map(|x| (x % 7 != 0).then_some(x)),
+                    )),
+                    Arc::new(StringArray::from_iter_values(
+                        (i * 10_000..(i + 1) * 10_000).map(|x| format!("value{}", x)),
+                    )),
+                ],
+            )
+            .unwrap()
+        })
+        .collect();
+    let options = |iounit_size| {
+        FileWriterOptionsBuilder::with_defaults()
+            .write_built_in_wasm(enable_built_in_wasm)
+            .set_row_group_size(20_000)
+            .set_iounit_size(iounit_size)
+            .enable_io_unit_checksum(true)
+            .build()
+    };
+    // One EncUnit per chunk, then repacked into one chunk per row group and back.
+    let mut original = tempfile::tempfile().unwrap();
+    write_batches(&mut original, &batches, options(1));
+    let mut large = tempfile::tempfile().unwrap();
+    FileWriter::repack(
+        Arc::new(original.try_clone().unwrap()),
+        &mut large,
+        options(64 * 1024),
+    )
+    .unwrap();
+    let mut small = tempfile::tempfile().unwrap();
+    FileWriter::repack(Arc::new(large.try_clone().unwrap()), &mut small, options(1)).unwrap();
+
+    let original = Arc::new(original);
+    let large = Arc::new(large);
+    let diff = diff_files(original.clone(), large.clone()).unwrap();
+    assert!(diff.is_semantically_equal(), "{}", diff);
+    let items: Vec<_> = diff.layout_differences.iter().map(|d| &d.item).collect();
+    assert!(items.contains(&&"column 0 chunks".to_string()));
+    assert!(!items.contains(&&"column 0 encunits".to_string()));
+    assert!(!items.contains(&&"row groups".to_string()));
+    // Null counts of split chunks are lost, so only the chunking is the same as the original.
+    let diff = diff_files(original, Arc::new(small)).unwrap();
+    assert!(diff.is_semantically_equal(), "{}", diff);
+    assert!(!diff
+        .layout_differences
+        .iter()
+        .any(|d| d.item.ends_with("chunks") || d.item.ends_with("encunits")));
+
+    FileReaderV2Builder::new(large.clone())
+        .with_verify_file_checksum(true)
+        .build()
+        .unwrap();
+    test_read(large.clone(), &batches, Projection::All, Selection::All);
+    test_read(
+        large,
+        &batches,
+        Projection::All,
+        Selection::RowIndexes(vec![0, 999, 1000, 20_001, 39_999]),
+    );
+}
+
+#[test]
+fn test_add_columns() {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, false),
+    ]));
+    let new_schema = Arc::new(Schema::new(vec![Field::new("c", DataType::Int64, false)]));
+    let batches: Vec<_> = (0..8)
+        .map(|i| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter(
+                        (i * 5_000..(i + 1) * 5_000).map(|x| (x % 7 != 0).then_some(x)),
+                    )),
+                    Arc::new(StringArray::from_iter_values(
+                        (i * 5_000..(i + 1) * 5_000).map(|x| format!("value{}", x)),
+                    )),
+                ],
+            )
+            .unwrap()
+        })
+        .collect();
+    let new_column = |start: i64, len: i64| {
+        RecordBatch::try_new(
+            new_schema.clone(),
+            vec![Arc::new(Int64Array::from_iter_values(
+                (start..start + len).map(|x| x * x),
+            ))],
+        )
+        .unwrap()
+    };
+    let options = || {
+        FileWriterOptionsBuilder::with_defaults()
+            .set_row_group_size(15_000)
+            .build()
+    };
+    let mut input = tempfile::tempfile().unwrap();
+    write_batches(&mut input, &batches, options());
+
+    // The batches of the new column do not line up with the row groups.
+    let mut output = tempfile::tempfile().unwrap();
+    FileWriter::add_columns(
+        Arc::new(input.try_clone().unwrap()),
+        &mut output,
+        (0..40_000)
+            .step_by(7_000)
+            .map(|start| new_column(start, 7_000.min(40_000 - start))),
+        options(),
+    )
+    .unwrap();
+
+    // The existing data is copied as is, and only the new column and the metadata are written in
+    // addition. A file of the new column alone, with the same row groups, bounds the latter.
+    let mut new_column_only = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut new_column_only,
+        &(0..40_000)
+            .step_by(5_000)
+            .map(|start| new_column(start, 5_000))
+            .collect::<Vec<_>>(),
+        options(),
+    );
+    let bytes = |mut file: &std::fs::File| {
+        let mut buf = vec![];
+        file.rewind().unwrap();
+        std::io::Read::read_to_end(&mut file, &mut buf).unwrap();
+        buf
+    };
+    let (input_bytes, output_bytes) = (bytes(&input), bytes(&output));
+    let common_prefix = input_bytes
+        .iter()
+        .zip(&output_bytes)
+        .take_while(|(x, y)| x == y)
+        .count();
+    // The data of the input is followed by the WASMBinaries section without binaries, which takes
+    // a few bytes, the metadata and the postscript.
+    let postscript = &input_bytes[input_bytes.len() - 32..];
+    let metadata_size = u32::from_le_bytes(postscript[..4].try_into().unwrap()) as usize;
+    assert!(common_prefix + 64 + metadata_size + 32 >= input_bytes.len());
+    assert!(output_bytes.len() - input_bytes.len() < bytes(&new_column_only).len());
+
+    let output = Arc::new(output);
+    FileReaderV2Builder::new(output.clone())
+        .with_verify_file_checksum(true)
+        .build()
+        .unwrap();
+    let expected: Vec<_> = batches
+        .iter()
+        .enumerate()
+        .map(|(i, batch)| {
+            let c = new_column(i as i64 * 5_000, 5_000);
+            let mut columns = batch.columns().to_vec();
+            columns.push(c.column(0).clone());
+            RecordBatch::try_new(
+                Arc::new(Schema::new(
+                    schema
+                        .fields()
+                        .iter()
+                        .chain(new_schema.fields())
+                        .cloned()
+                        .collect::<Vec<_>>(),
+                )),
+                columns,
+            )
+            .unwrap()
+        })
+        .collect();
+    test_read(output, &expected, Projection::All, Selection::All);
+
+    // The new column must have the rows of the file, and a new name.
+    let input = Arc::new(input);
+    for new_columns in [
+        vec![new_column(0, 39_999)],
+        vec![new_column(0, 40_000), new_column(0, 1)],
+    ] {
+        assert!(FileWriter::add_columns(
+            input.clone(),
+            tempfile::tempfile().unwrap(),
+            new_columns,
+            options()
+        )
+        .is_err());
+    }
+    assert!(FileWriter::add_columns(
+        input,
+        tempfile::tempfile().unwrap(),
+        [batches[0].clone()],
+        options()
+    )
+    .is_err());
+}
+
+#[apply(enable_built_in_wasm)]
+fn test_drop_rename_columns(#[case] enable_built_in_wasm: bool) {
+    let s_fields = arrow_schema::Fields::from(vec![
+        Field::new("x", DataType::Int32, false),
+        Field::new("y", DataType::Utf8, false),
+    ]);
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("s", DataType::Struct(s_fields.clone()), true),
+        Field::new("c", DataType::Int64, false),
+    ]));
+    let batches: Vec<_> = (0..4)
+        .map(|i| {
+            let range = i * 5_000..(i + 1) * 5_000;
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter(
+                        range.clone().map(|x| (x % 7 != 0).then_some(x)),
+                    )),
+                    Arc::new(arrow::array::StructArray::new(
+                        s_fields.clone(),
+                        vec![
+                            Arc::new(Int32Array::from_iter_values(range.clone())),
+                            Arc::new(StringArray::from_iter_values(
+                                range.clone().map(|x| format!("value{}", x)),
+                            )),
+                        ],
+                        None,
+                    )),
+                    Arc::new(Int64Array::from_iter_values(range.map(|x| x as i64 * 3))),
+                ],
+            )
+            .unwrap()
+        })
+        .collect();
+    let options = || {
+        FileWriterOptionsBuilder::with_defaults()
+            .write_built_in_wasm(enable_built_in_wasm)
+            .set_row_group_size(10_000)
+            .build()
+    };
+    let mut input = tempfile::tempfile().unwrap();
+    write_batches(&mut input, &batches, options());
+    let input = Arc::new(input);
+    assert_eq!(get_unreferenced_bytes(input.clone()).unwrap(), 0);
+    let without_s: Vec<_> = batches
+        .iter()
+        .map(|batch| batch.project(&[0, 2]).unwrap())
+        .collect();
+
+    // The data of the dropped column is left in the file unless compacted.
+    let mut dropped = tempfile::tempfile().unwrap();
+    FileWriter::drop_columns(input.clone(), &mut dropped, &["s"], false, options()).unwrap();
+    let mut compacted = tempfile::tempfile().unwrap();
+    FileWriter::drop_columns(input.clone(), &mut compacted, &["s"], true, options()).unwrap();
+    let (dropped, compacted) = (Arc::new(dropped), Arc::new(compacted));
+    let unreferenced = get_unreferenced_bytes(dropped.clone()).unwrap();
+    assert!(unreferenced > 0);
+    assert_eq!(get_unreferenced_bytes(compacted.clone()).unwrap(), 0);
+    assert!(compacted.metadata().unwrap().len() < dropped.metadata().unwrap().len());
+    for file in [dropped, compacted] {
+        FileReaderV2Builder::new(file.clone())
+            .with_verify_file_checksum(true)
+            .build()
+            .unwrap();
+        test_read(file, &without_s, Projection::All, Selection::All);
+    }
+
+    let mut renamed = tempfile::tempfile().unwrap();
+    FileWriter::rename_columns(
+        input.clone(),
+        &mut renamed,
+        &[("a", "id"), ("c", "a")],
+        options(),
+    )
+    .unwrap();
+    let renamed = Arc::new(renamed);
+    let output = FileReaderV2Builder::new(renamed.clone())
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    assert_eq!(
+        output[0]
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().as_str())
+            .collect::<Vec<_>>(),
+        vec!["id", "s", "a"]
+    );
+    assert_eq!(get_unreferenced_bytes(renamed.clone()).unwrap(), 0);
+    test_read(renamed, &batches, Projection::All, Selection::All);
+
+    // Unknown columns, and duplicate names after renaming.
+    let rewrite = || tempfile::tempfile().unwrap();
+    assert!(FileWriter::drop_columns(input.clone(), rewrite(), &["z"], false, options()).is_err());
+    assert!(
+        FileWriter::rename_columns(input.clone(), rewrite(), &[("z", "y")], options()).is_err()
+    );
+    assert!(FileWriter::rename_columns(input, rewrite(), &[("a", "c")], options()).is_err());
+}
+
+#[rstest]
+#[case(DictionaryTypeOptions::GlobalDictionary)]
+#[case(DictionaryTypeOptions::GlobalDictionaryMultiColSharing)]
+#[case(DictionaryTypeOptions::GlobalDictionaryAutoSharing)]
+#[case(DictionaryTypeOptions::EncoderDictionary)]
+fn test_memory_budget_spill(#[case] dictionary_type: DictionaryTypeOptions) {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, false),
+        Field::new("c", DataType::Int32, false),
+    ]));
+    let batches: Vec<_> = (0..5)
+        .map(|i| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter(
+                        (0..10_000).map(|x| (x % 11 != 0).then_some(x % 100 + i)),
+                    )),
+                    Arc::new(StringArray::from_iter_values(
+                        (0..10_000).map(|x| format!("value{}", (x + i) % 500)),
+                    )),
+                    Arc::new(Int32Array::from_iter_values(
+                        (0..10_000).map(|x| (x + i * 10_000) % 300),
+                    )),
+                ],
+            )
+            .unwrap()
+        })
+        .collect();
+    let options = FileWriterOptionsBuilder::with_defaults()
+        .set_dictionary_type(dictionary_type)
+        .set_memory_budget(1)
+        .build();
+    let mut file = tempfile::tempfile().unwrap();
+    let mut writer = FileWriter::try_new(schema, &mut file, options).unwrap();
+    for batch in &batches {
+        writer.write_batch(batch).unwrap();
+        assert_eq!(writer.memory_size(), 0);
+    }
+    writer.finish().unwrap();
+    file.rewind().unwrap();
+    test_read(Arc::new(file), &batches, Projection::All, Selection::All);
+}
+
+#[rstest]
+#[case(DictionaryTypeOptions::EncoderDictionary)]
+#[case(DictionaryTypeOptions::LocalDictionary)]
+#[case(DictionaryTypeOptions::GlobalDictionary)]
+fn test_flush(#[case] dictionary_type: DictionaryTypeOptions) {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int64, false),
+        Field::new("b", DataType::Utf8, true),
+        Field::new("c", DataType::Int64, false),
+    ]));
+    let batches: Vec<_> = (0..6)
+        .map(|i| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from_iter_values(i * 10_000..(i + 1) * 10_000)),
+                    Arc::new(StringArray::from_iter(
+                        (0..10_000).map(|x| (x % 7 != 0).then(|| format!("value{}", x % 300))),
+                    )),
+                    Arc::new(Int64Array::from_iter_values(
+                        (0..10_000).map(|x| (x * 31 + i) % 1000),
+                    )),
+                ],
+            )
+            .unwrap()
+        })
+        .collect();
+    let options = || {
+        FileWriterOptionsBuilder::with_defaults()
+            .set_dictionary_type(dictionary_type)
+            .set_column_families(vec![vec![0, 2]])
+            .set_row_group_size(30_000)
+    };
+
+    // Forced flushes write everything buffered, without ending the row group. Shared dictionary
+    // encoders spill instead.
+    let shared = dictionary_type == DictionaryTypeOptions::GlobalDictionary;
+    let mut file = tempfile::tempfile().unwrap();
+    let mut writer = FileWriter::try_new(schema.clone(), &mut file, options().build()).unwrap();
+    for batch in &batches {
+        writer.write_batch(batch).unwrap();
+        assert!(writer.buffered_bytes() > 0);
+        let bytes_written = writer.bytes_written().unwrap();
+        writer.flush().unwrap();
+        assert_eq!(writer.buffered_bytes(), 0);
+        assert_eq!(writer.bytes_written().unwrap() > bytes_written, !shared);
+    }
+    writer.finish().unwrap();
+    file.rewind().unwrap();
+    let file = Arc::new(file);
+    if !shared {
+        assert_eq!(get_column_families(&file).unwrap()[0].iounits.len(), 6);
+    }
+    test_read(file, &batches, Projection::All, Selection::All);
+
+    // Automatic flushes keep the buffered data under the threshold.
+    let max_buffered_bytes = 64 * 1024;
+    let mut file = tempfile::tempfile().unwrap();
+    let mut writer = FileWriter::try_new(
+        schema,
+        &mut file,
+        options().set_max_buffered_bytes(max_buffered_bytes).build(),
+    )
+    .unwrap();
+    for batch in &batches {
+        writer.write_batch(batch).unwrap();
+        assert!(writer.buffered_bytes() <= max_buffered_bytes);
+    }
+    writer.finish().unwrap();
+    file.rewind().unwrap();
+    test_read(Arc::new(file), &batches, Projection::All, Selection::All);
+}
+
+#[rstest]
+#[case(DictionaryTypeOptions::EncoderDictionary)]
+#[case(DictionaryTypeOptions::LocalDictionary)]
+#[case(DictionaryTypeOptions::GlobalDictionary)]
+fn test_take_dictionary_rows(#[case] dictionary_type: DictionaryTypeOptions) {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Utf8, true),
+        Field::new("b", DataType::Int64, false),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(StringArray::from_iter(
+                (0..100_000).map(|x| (x % 7 != 0).then(|| format!("value{}", x % 300))),
+            )),
+            Arc::new(Int64Array::from_iter_values(
+                (0..100_000).map(|x| (x * 31) % 1000),
+            )),
+        ],
+    )
+    .unwrap();
+    test_read_file_roundtrip(
+        &[batch],
+        Projection::All,
+        FileWriterOptionsBuilder::with_defaults()
+            .set_dictionary_type(dictionary_type)
+            .build(),
+        Selection::RowIndexes(vec![0, 7, 8, 64 * 1024 - 1, 64 * 1024, 99_999]),
+    );
+}
+
+#[test]
+fn test_memory_pool() {
+    use fff_poc::memory::{MemoryPool, TrackingPool};
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int64, false),
+        Field::new("b", DataType::Utf8, true),
+    ]));
+    let batches: Vec<_> = (0..8)
+        .map(|i| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from_iter_values(i * 10_000..(i + 1) * 10_000)),
+                    Arc::new(StringArray::from_iter(
+                        (0..10_000).map(|x| (x % 7 != 0).then(|| format!("value{}", x % 300))),
+                    )),
+                ],
+            )
+            .unwrap()
+        })
+        .collect();
+
+    // The writer flushes when the pool refuses to reserve its buffered data.
+    let limit = 64 * 1024;
+    let pool = Arc::new(TrackingPool::with_limit(limit));
+    let mut file = tempfile::tempfile().unwrap();
+    let mut writer = FileWriter::try_new(
+        schema.clone(),
+        &mut file,
+        FileWriterOptionsBuilder::with_defaults()
+            .set_memory_pool(pool.clone())
+            .build(),
+    )
+    .unwrap();
+    for batch in &batches {
+        writer.write_batch(batch).unwrap();
+        assert_eq!(pool.reserved() as u64, writer.buffered_bytes());
+    }
+    writer.finish().unwrap();
+    assert_eq!(pool.reserved(), 0);
+    assert!(pool.peak() <= limit);
+    file.rewind().unwrap();
+    let file = Arc::new(file);
+    test_read(file.clone(), &batches, Projection::All, Selection::All);
+
+    // Readers reserve the chunks held by the column decoders.
+    let pool = Arc::new(TrackingPool::new());
+    let read = FileReaderV2Builder::new(file.clone())
+        .with_memory_pool(pool.clone())
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    assert_eq!(read.iter().map(|b| b.num_rows()).sum::<usize>(), 80_000);
+    assert_eq!(pool.reserved(), 0);
+    assert!(pool.peak() > 0);
+    assert!(FileReaderV2Builder::new(file)
+        .with_memory_pool(Arc::new(TrackingPool::with_limit(1)))
+        .build()
+        .unwrap()
+        .read_file()
+        .is_err());
+}
+
+#[test]
+fn test_auto_column_chunk_size() {
+    use fff_poc::writer::{layout_planner::AutoColumnChunkSize, COLUMN_CHUNK_SIZE_KEY};
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int64, false),
+        Field::new("b", DataType::Utf8, true),
+    ]));
+    let batches: Vec<_> = (0..8)
+        .map(|i| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from_iter_values(i * 10_000..(i + 1) * 10_000)),
+                    Arc::new(StringArray::from_iter((i * 10_000..(i + 1) * 10_000).map(
+                        |x| (x % 7 != 0).then(|| format!("value{}", x * 7919 % 1_000_003)),
+                    ))),
+                ],
+            )
+            .unwrap()
+        })
+        .collect();
+    let iounit_size = 1024 * 1024;
+    let auto = AutoColumnChunkSize {
+        sample_rows: 20_000,
+        min_chunk_size: 4 * 1024,
+        max_chunk_size: iounit_size,
+    };
+    let mut file = tempfile::tempfile().unwrap();
+    let mut writer = FileWriter::try_new(
+        schema.clone(),
+        &mut file,
+        FileWriterOptionsBuilder::with_defaults()
+            .set_iounit_size(iounit_size)
+            .set_row_group_size(40_000)
+            .set_auto_column_chunk_size(auto)
+            .build(),
+    )
+    .unwrap();
+    for batch in &batches {
+        writer.write_batch(batch).unwrap();
+    }
+    writer.finish().unwrap();
+    file.rewind().unwrap();
+    let file = Arc::new(file);
+    test_read(file.clone(), &batches, Projection::All, Selection::All);
+
+    // The poorly compressed strings get larger chunks than the sequential integers.
+    let reader = FileReaderV2Builder::new(file).build().unwrap();
+    let chunk_sizes: Vec<u64> = (0..2)
+        .map(|column| {
+            reader
+                .metadata()
+                .get_column(column, COLUMN_CHUNK_SIZE_KEY)
+                .and_then(MetadataValue::as_u64)
+                .unwrap()
+        })
+        .collect();
+    assert!(chunk_sizes
+        .iter()
+        .all(|size| (auto.min_chunk_size..=auto.max_chunk_size).contains(size)));
+    assert!(chunk_sizes[0] < chunk_sizes[1]);
+}
+
+#[rstest]
+#[case(DictionaryTypeOptions::LocalDictionary)]
+#[case(DictionaryTypeOptions::GlobalDictionary)]
+fn test_dictionary_preservation(#[case] dictionary_type: DictionaryTypeOptions) {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int64, true),
+        Field::new("b", DataType::Utf8, false),
+    ]));
+    let batches: Vec<_> = (0..4)
+        .map(|i| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from_iter(
+                        (0..10_000).map(|x| (x % 13 != 0).then_some(x % 50 + i)),
+                    )),
+                    Arc::new(StringArray::from_iter_values(
+                        (0..10_000).map(|x| format!("value{}", x % 300)),
+                    )),
+                ],
+            )
+            .unwrap()
+        })
+        .collect();
+    let options = FileWriterOptionsBuilder::with_defaults()
+        .set_dictionary_type(dictionary_type)
+        .set_encoding_unit_len(10_000)
+        .build();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(&mut file, &batches, options);
+    let file = Arc::new(file);
+
+    let output_batches = FileReaderV2Builder::new(file.clone())
+        .with_dictionary_preservation(true)
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    assert!(output_batches.len() > 1);
+    for col in 0..schema.fields().len() {
+        let dicts: Vec<_> = output_batches
+            .iter()
+            .map(|batch| {
+                batch
+                    .column(col)
+                    .as_dictionary_opt::<arrow::datatypes::UInt32Type>()
+                    .expect("dictionary-encoded chunks should be output as DictionaryArray")
+                    .clone()
+            })
+            .collect();
+        if dictionary_type == DictionaryTypeOptions::GlobalDictionary {
+            assert!(dicts
+                .windows(2)
+                .all(|w| Arc::ptr_eq(w[0].values(), w[1].values())));
+        }
+        let output =
+            arrow::compute::concat(&dicts.iter().map(|d| d as &dyn Array).collect::<Vec<_>>())
+                .unwrap();
+        let input = arrow::compute::concat(
+            &batches
+                .iter()
+                .map(|b| b.column(col).as_ref())
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+        let output = arrow::compute::cast(&output, input.data_type()).unwrap();
+        assert_eq!(&output, &input);
+    }
+    // The default read path still materializes the values.
+    test_read(file, &batches, Projection::All, Selection::All);
+}
+
+/// Expand the runs of `array`, if any.
+fn expand_runs(array: &ArrayRef) -> ArrayRef {
+    let Some(runs) = array.as_run_opt::<Int32Type>() else {
+        return array.clone();
+    };
+    let physical = runs
+        .get_physical_indices(&(0..runs.len() as u32).collect::<Vec<_>>())
+        .unwrap();
+    let indices = UInt32Array::from_iter_values(physical.into_iter().map(|i| i as u32));
+    arrow::compute::take(runs.values(), &indices, None).unwrap()
+}
+
+#[test]
+fn test_preferred_output_encoded() {
+    let batch = RecordBatch::try_from_iter(vec![
+        (
+            "runs",
+            Arc::new(Int64Array::from_iter_values((0..100_000).map(|i| i / 4096))) as ArrayRef,
+        ),
+        (
+            "values",
+            Arc::new(Int64Array::from_iter_values(
+                (0..100_000).map(|i| i * 7919 % 1000),
+            )),
+        ),
+    ])
+    .unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch.clone()],
+        FileWriterOptionsBuilder::with_defaults()
+            .set_adaptive_encoding(Some(AdaptiveEncodingOptions::default()))
+            .build(),
+    );
+    let file = Arc::new(file);
+    let read = |preference: Preference| {
+        FileReaderV2Builder::new(file.clone())
+            .with_preferred_output(preference)
+            .build()
+            .unwrap()
+            .read_file()
+            .unwrap()
+    };
+
+    let encoded = read(Preference::Encoded);
+    assert!(encoded
+        .iter()
+        .all(|batch| matches!(batch.column(0).data_type(), DataType::RunEndEncoded(_, _))));
+    assert!(encoded
+        .iter()
+        .all(|batch| batch.column(1).data_type() == &DataType::Int64));
+    for i in 0..batch.num_columns() {
+        let output = encoded
+            .iter()
+            .map(|batch| expand_runs(batch.column(i)))
+            .collect::<Vec<_>>();
+        let output = arrow::compute::concat(
+            &output
+                .iter()
+                .map(|array| array.as_ref())
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+        assert_eq!(&output, batch.column(i));
+    }
+    // Runs are expanded by default, and when rows are selected.
+    let decoded = concat_batches(&batch.schema(), &read(Preference::Decoded)).unwrap();
+    assert_eq!(decoded.columns(), batch.columns());
+    let selected = FileReaderV2Builder::new(file.clone())
+        .with_preferred_output(Preference::Encoded)
+        .with_selection(Selection::RowIndexes(vec![3, 4096, 99_999]))
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    assert_eq!(selected[0].column(0).data_type(), &DataType::Int64);
+
+    // Dictionary-encoded chunks are output as dictionaries.
+    let batch = RecordBatch::try_from_iter(vec![(
+        "names",
+        Arc::new(StringArray::from_iter_values(
+            (0..10_000).map(|i| format!("name{}", i % 10)),
+        )) as ArrayRef,
+    )])
+    .unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch],
+        FileWriterOptionsBuilder::with_defaults()
+            .set_dictionary_type(DictionaryTypeOptions::LocalDictionary)
+            .build(),
+    );
+    let output = FileReaderV2Builder::new(Arc::new(file))
+        .with_preferred_output(Preference::Encoded)
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    assert!(output
+        .iter()
+        .all(|batch| matches!(batch.column(0).data_type(), DataType::Dictionary(_, _))));
+}
+
+#[apply(enable_built_in_wasm)]
+fn test_scan_metrics_decode_paths(#[case] enable_built_in_wasm: bool) {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, false),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from_iter_values(0..1000)),
+            Arc::new(StringArray::from_iter_values(
+                (0..1000).map(|x| format!("value{}", x)),
+            )),
+        ],
+    )
+    .unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch],
+        FileWriterOptionsBuilder::with_defaults()
+            .write_built_in_wasm(enable_built_in_wasm)
+            .build(),
+    );
+    let file = Arc::new(file);
+    let metrics = FileReaderV2Builder::new(file.clone())
+        .with_projections(Projection::LeafColumnIndexes(vec![1]))
+        .build()
+        .unwrap()
+        .scan_metrics()
+        .unwrap();
+    let expected = if enable_built_in_wasm {
+        DecodePath::EmbeddedWasm(WASMId(0))
+    } else {
+        DecodePath::BuiltIn
+    };
+    assert_eq!(metrics.decode_paths, vec![vec![expected]]);
+
+    if enable_built_in_wasm {
+        let rt = fff_ude_wasm::Runtime::try_new(
+            &std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap(),
+        )
+        .unwrap();
+        let metrics = FileReaderV2Builder::new(file)
+            .with_existing_runtimes(HashMap::from([(WASMId(0), Arc::new(rt))]))
+            .build()
+            .unwrap()
+            .scan_metrics()
+            .unwrap();
+        assert_eq!(
+            metrics.decode_paths,
+            vec![vec![DecodePath::ExternalRuntime(WASMId(0))]; 2]
+        );
+    }
+}
+
+#[test]
+fn test_estimate_decoded_size() {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, false),
+    ]));
+    let batches: Vec<_> = (0..2)
+        .map(|i| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter(
+                        (i * 1000..(i + 1) * 1000).map(|x| (x % 5 != 0).then_some(x)),
+                    )),
+                    Arc::new(StringArray::from_iter_values(
+                        (i * 1000..(i + 1) * 1000).map(|x| format!("value{}", x)),
+                    )),
+                ],
+            )
+            .unwrap()
+        })
+        .collect();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &batches,
+        FileWriterOptionsBuilder::with_defaults()
+            .set_row_group_size(1000)
+            .build(),
+    );
+    let file = Arc::new(file);
+    let reader = FileReaderV2Builder::new(file.clone()).build().unwrap();
+    // 4 bytes per value and a validity bit per row.
+    assert_eq!(
+        reader.estimate_decoded_size(0, &Selection::All).unwrap(),
+        2000 * 4 + 2 * 125
+    );
+    assert_eq!(
+        reader
+            .estimate_decoded_size(0, &Selection::new([0, 1500]))
+            .unwrap(),
+        2 * 4 + 2
+    );
+    let strings = reader.estimate_decoded_size(1, &Selection::All).unwrap();
+    assert!(strings > 2000 * 4);
+    assert!(reader.estimate_decoded_size(2, &Selection::All).is_err());
+
+    let reader = FileReaderV2Builder::new(file)
+        .with_projections(Projection::LeafColumnIndexes(vec![1]))
+        .build()
+        .unwrap();
+    assert_eq!(
+        reader.estimate_decoded_size(1, &Selection::All).unwrap(),
+        strings
+    );
+    assert!(reader.estimate_decoded_size(0, &Selection::All).is_err());
+}
+
+#[test]
+fn test_read_shared_dictionary() {
+    let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Utf8, true)]));
+    let batches: Vec<_> = (0..2)
+        .map(|_| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(StringArray::from_iter(
+                    (0..10_000).map(|x| (x % 7 != 0).then(|| format!("value{}", x % 300))),
+                ))],
+            )
+            .unwrap()
+        })
+        .collect();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &batches,
+        FileWriterOptionsBuilder::with_defaults()
+            .set_dictionary_type(DictionaryTypeOptions::GlobalDictionary)
+            .set_row_group_size(10_000)
+            .build(),
+    );
+    let reader = FileReaderV2Builder::new(Arc::new(file)).build().unwrap();
+    let dicts = reader.list_shared_dictionaries();
+    assert!(!dicts.is_empty());
+    let mut values = vec![];
+    for info in &dicts {
+        let dict = reader.read_shared_dictionary(info.index).unwrap();
+        assert_eq!(dict.data_type(), &info.data_type);
+        assert_eq!(dict.len(), info.len);
+        assert!(info.compressed_size > 0);
+        let dict = arrow::compute::cast(&dict, &DataType::Utf8).unwrap();
+        values.extend(dict.as_string::<i32>().iter().flatten().map(str::to_string));
+    }
+    values.sort();
+    values.dedup();
+    assert_eq!(values.len(), 300);
+    assert!(reader.read_shared_dictionary(usize::MAX).is_err());
+
+    // Files without shared dictionaries have none to list.
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &batches,
+        FileWriterOptionsBuilder::with_defaults().build(),
+    );
+    let reader = FileReaderV2Builder::new(Arc::new(file)).build().unwrap();
+    assert!(reader.list_shared_dictionaries().is_empty());
+    assert!(reader.read_shared_dictionary(0).is_err());
+}
+
+#[test]
+fn test_wasm_encoder() {
+    let wasm_binary = std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap();
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, false),
+        Field::new("c", DataType::Int64, false),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from_iter(
+                (0..100_000).map(|x| (x % 7 != 0).then_some(x)),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                (0..100_000).map(|x| format!("value{}", x % 1000)),
+            )),
+            Arc::new(Int64Array::from_iter_values(0..100_000)),
+        ],
+    )
+    .unwrap();
+    let options = FileWriterOptionsBuilder::with_defaults()
+        .with_wasm_encoder(0, wasm_binary.clone())
+        .with_wasm_encoder(1, wasm_binary.clone())
+        .build();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch.slice(0, 60_000), batch.slice(60_000, 40_000)],
+        options,
+    );
+    let file = Arc::new(file);
+    let metrics = FileReaderV2Builder::new(file.clone())
+        .build()
+        .unwrap()
+        .scan_metrics()
+        .unwrap();
+    // Both columns share the binary, written once.
+    assert_eq!(
+        metrics.decode_paths,
+        vec![
+            vec![DecodePath::EmbeddedWasm(WASMId(0))],
+            vec![DecodePath::EmbeddedWasm(WASMId(0))],
+            vec![DecodePath::BuiltIn],
+        ]
+    );
+    test_read(
+        file,
+        &[batch],
+        Projection::All,
+        Selection::RowIndexes(vec![0, 7, 59_999, 60_000, 99_999]),
+    );
+
+    let list = {
+        let mut builder = ListBuilder::new(Int32Builder::new());
+        builder.values().append_value(1);
+        builder.append(true);
+        builder.finish()
+    };
+    let schema = Arc::new(Schema::new(vec![Field::new(
+        "l",
+        list.data_type().clone(),
+        true,
+    )]));
+    let err = FileWriter::try_new(
+        schema,
+        tempfile::tempfile().unwrap(),
+        FileWriterOptionsBuilder::with_defaults()
+            .with_wasm_encoder(0, wasm_binary)
+            .build(),
+    )
+    .err()
+    .unwrap();
+    assert!(matches!(err, fff_core::errors::Error::NYI(_)));
+}
+
+#[test]
+fn test_wasm_aot() {
+    use fff_ude_wasm::{aot::AotTarget, features::required_features};
+
+    let builtin = std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap();
+    let hash = wasm_module_hash(&builtin);
+    let batch = RecordBatch::try_from_iter(vec![(
+        "a",
+        Arc::new(Int32Array::from_iter(
+            (0..1000).map(|x| (x % 7 != 0).then_some(x)),
+        )) as ArrayRef,
+    )])
+    .unwrap();
+    let other_target = AotTarget {
+        triple: "riscv64-none".to_string(),
+        wasmtime_version: "1.0.0".to_string(),
+    };
+    let write = |write_wasm_aot: bool, host_artifact: Option<Vec<u8>>| {
+        let mut options = FileWriterOptionsBuilder::with_defaults()
+            .write_built_in_wasm(true)
+            .write_wasm_aot(write_wasm_aot)
+            .add_wasm_aot_artifact(hash, other_target.clone(), vec![1, 2, 3]);
+        if let Some(host_artifact) = host_artifact {
+            options = options.add_wasm_aot_artifact(hash, AotTarget::host(), host_artifact);
+        }
+        let mut file = tempfile::tempfile().unwrap();
+        write_batches(&mut file, &[batch.clone()], options.build());
+        Arc::new(file)
+    };
+    let read = |file: &Arc<std::fs::File>| {
+        let output = FileReaderV2Builder::new(file.clone())
+            .with_trusted_wasm_aot(true)
+            .build()
+            .unwrap()
+            .read_file()
+            .unwrap();
+        assert_eq!(output[0].column(0), batch.column(0));
+    };
+
+    // Modules requiring optional WASM features are not compiled ahead of time.
+    let compiled = required_features(&builtin).unwrap().is_empty();
+    let file = write(true, None);
+    let artifacts = get_wasm_aot_artifacts(&file).unwrap();
+    let mut expected_targets = vec![other_target.clone()];
+    if compiled {
+        expected_targets.insert(0, AotTarget::host());
+    }
+    assert_eq!(
+        artifacts
+            .iter()
+            .map(|artifact| artifact.target.clone())
+            .collect::<Vec<_>>(),
+        expected_targets
+    );
+    assert!(artifacts
+        .iter()
+        .all(|artifact| artifact.wasm_id == WASMId(0)));
+    read(&file);
+
+    // Artifacts failing to load fall back to compiling the module.
+    let file = write(false, Some(vec![0; 16]));
+    assert_eq!(get_wasm_aot_artifacts(&file).unwrap().len(), 2);
+    read(&file);
+}
+
+#[test]
+fn test_wasm_reference_only() {
+    let builtin = std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap();
+    let hash = wasm_module_hash(&builtin);
+    let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+    let batch = |start: i32| {
+        RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter(
+                (start..start + 1000).map(|x| (x % 7 != 0).then_some(x)),
+            ))],
+        )
+        .unwrap()
+    };
+    let options = |reference_only: bool| {
+        let builder = FileWriterOptionsBuilder::with_defaults().write_built_in_wasm(true);
+        if reference_only {
+            builder.wasm_reference_only(hash, "wasm/builtin.wasm")
+        } else {
+            builder
+        }
+        .build()
+    };
+    let write = |start: i32, reference_only: bool| {
+        let mut file = tempfile::tempfile().unwrap();
+        write_batches(&mut file, &[batch(start)], options(reference_only));
+        Arc::new(file)
+    };
+    let embedded = [write(0, false), write(1000, false)];
+    let referenced = [write(0, true), write(1000, true)];
+    assert!(
+        referenced[0].metadata().unwrap().len() + builtin.len() as u64
+            <= embedded[0].metadata().unwrap().len()
+    );
+
+    let read = |file: &Arc<std::fs::File>,
+                cache: &Arc<WasmModuleCache>,
+                resolver: Option<Arc<dyn WasmResolver>>| {
+        let mut builder =
+            FileReaderV2Builder::new(file.clone()).with_wasm_module_cache(cache.clone());
+        if let Some(resolver) = resolver {
+            builder = builder.with_wasm_resolver(resolver);
+        }
+        builder.build().and_then(|mut reader| reader.read_file())
+    };
+    // Files embedding the same module share its runtime.
+    let cache = Arc::new(WasmModuleCache::new());
+    for (i, file) in embedded.iter().enumerate() {
+        let output = read(file, &cache, None).unwrap();
+        assert_eq!(output[0].column(0), batch(i as i32 * 1000).column(0));
+    }
+    assert_eq!(cache.len(), 1);
+
+    // Modules stored by reference only are fetched once by the resolver.
+    let cache = Arc::new(WasmModuleCache::new());
+    let err = read(&referenced[0], &cache, None).unwrap_err();
+    assert!(err.to_string().contains(&format!("{hash:016x}")));
+    let num_resolved = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let resolver: Arc<dyn WasmResolver> = {
+        let builtin = builtin.clone();
+        let num_resolved = num_resolved.clone();
+        Arc::new(move |resolved_hash: u64, uri: &str| {
+            assert_eq!((resolved_hash, uri), (hash, "wasm/builtin.wasm"));
+            num_resolved.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Ok(builtin.clone())
+        })
+    };
+    for (i, file) in referenced.iter().enumerate() {
+        let output = read(file, &cache, Some(resolver.clone())).unwrap();
+        assert_eq!(output[0].column(0), batch(i as i32 * 1000).column(0));
+    }
+    assert_eq!(num_resolved.load(std::sync::atomic::Ordering::Relaxed), 1);
+    // Cached modules need no resolver.
+    let reader = FileReaderV2Builder::new(referenced[0].clone())
+        .with_wasm_module_cache(cache.clone())
+        .build()
+        .unwrap();
+    assert!(reader.missing_wasm_modules().unwrap().is_empty());
+
+    // A resolved module must match the hash.
+    let wrong_resolver: Arc<dyn WasmResolver> = Arc::new(|_: u64, _: &str| Ok(vec![0, 1, 2]));
+    assert!(read(
+        &referenced[0],
+        &Arc::new(WasmModuleCache::new()),
+        Some(wrong_resolver)
+    )
+    .is_err());
+
+    assert!(FileWriter::try_new(
+        schema.clone(),
+        tempfile::tempfile().unwrap(),
+        FileWriterOptionsBuilder::with_defaults()
+            .wasm_reference_only(hash, "wasm/builtin.wasm")
+            .build(),
+    )
+    .is_err());
+}
+
+#[test]
+fn test_column_wasms() {
+    let builtin = std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap();
+    let noop = std::fs::read(fff_test_util::NOOP_PATH.as_path()).unwrap();
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, false),
+        Field::new("c", DataType::Int64, false),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from_iter(
+                (0..10_000).map(|x| (x % 7 != 0).then_some(x)),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                (0..10_000).map(|x| format!("value{}", x % 100)),
+            )),
+            Arc::new(Int64Array::from_iter_values(0..10_000)),
+        ],
+    )
+    .unwrap();
+    let mut builder = FileWriterOptionsBuilder::with_defaults();
+    let noop_id = builder.register_wasm(noop.clone());
+    let builtin_id = builder.register_wasm(builtin.clone());
+    assert_eq!((noop_id, builtin_id), (WASMId(0), WASMId(1)));
+    assert_eq!(builder.register_wasm(builtin.clone()), builtin_id);
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch.clone()],
+        builder.set_column_wasm("b", builtin_id).build(),
+    );
+    assert_eq!(
+        get_column_wasms(&file).unwrap(),
+        HashMap::from([(1, builtin_id)])
+    );
+
+    let file = Arc::new(file);
+    let mut reader = FileReaderV2Builder::new(file.clone()).build().unwrap();
+    let output = reader.read_file().unwrap();
+    assert_eq!(concat_batches(&schema, &output).unwrap(), batch);
+    let report = reader.resource_report().unwrap();
+    assert_eq!(
+        report.scan.decode_paths,
+        vec![
+            vec![DecodePath::BuiltIn],
+            vec![DecodePath::EmbeddedWasm(builtin_id)],
+            vec![DecodePath::BuiltIn],
+        ]
+    );
+    // The module not referenced by any EncUnit is never instantiated.
+    assert_eq!(report.wasm.keys().copied().collect::<Vec<_>>(), vec![1]);
+
+    // A column not in the schema, and a binary without an encoder.
+    for (column, wasm_id) in [("d", builtin_id), ("a", noop_id)] {
+        let mut builder = FileWriterOptionsBuilder::with_defaults();
+        builder.register_wasm(noop.clone());
+        builder.register_wasm(builtin.clone());
+        let options = builder.set_column_wasm(column, wasm_id).build();
+        assert!(
+            FileWriter::try_new(schema.clone(), tempfile::tempfile().unwrap(), options).is_err()
+        );
+    }
+}
+
+#[test]
+fn test_decode_support() {
+    use fff_format::File::fff::flatbuf::EncodingType;
+    use fff_ude_wasm::Runtime;
+
+    let builtin = std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap();
+    let builtin = Runtime::try_new(&builtin).unwrap();
+    let noop = std::fs::read(fff_test_util::NOOP_PATH.as_path()).unwrap();
+    let noop = Runtime::try_new(&noop).unwrap();
+    let support = |data_type, encoding, runtime| decode_support(&data_type, encoding, runtime);
+    assert_eq!(
+        support(DataType::Utf8, EncodingType::CASCADE, None),
+        DecodeSupport {
+            built_in: true,
+            wasm: None
+        }
+    );
+    assert_eq!(
+        support(DataType::Utf8, EncodingType::CUSTOM_WASM, Some(&builtin)),
+        DecodeSupport {
+            built_in: false,
+            wasm: Some(true)
+        }
+    );
+    // RLE is built in for fixed-width primitives only, and the built-in module declares it does
+    // not decode it.
+    let rle = support(DataType::Utf8, EncodingType::RLE, Some(&builtin));
+    assert!(rle.wasm_refuses() && !rle.is_supported());
+    assert!(support(DataType::Int64, EncodingType::RLE, Some(&builtin)).is_supported());
+    // Modules declaring no capability are not known either way.
+    let custom = support(DataType::Int32, EncodingType::CUSTOM_WASM, Some(&noop));
+    assert!(!custom.wasm_refuses() && !custom.is_supported());
+}
+
+#[test]
+fn test_wasm_ids() {
+    let builtin = std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap();
+    let noop = std::fs::read(fff_test_util::NOOP_PATH.as_path()).unwrap();
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, false),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from_iter_values(0..1000)),
+            Arc::new(StringArray::from_iter_values(
+                (0..1000).map(|x| format!("value{}", x % 10)),
+            )),
+        ],
+    )
+    .unwrap();
+    // The built-in binary is WASMId 1 in the first file and WASMId 0 in the second one.
+    let files = [vec![noop.clone(), builtin.clone()], vec![builtin.clone()]].map(|wasms| {
+        let mut builder = FileWriterOptionsBuilder::with_defaults();
+        let wasm_ids = wasms
+            .into_iter()
+            .map(|wasm| builder.register_wasm(wasm))
+            .collect::<Vec<_>>();
+        let mut file = tempfile::tempfile().unwrap();
+        write_batches(
+            &mut file,
+            &[batch.clone()],
+            builder
+                .set_column_wasm("b", *wasm_ids.last().unwrap())
+                .build(),
+        );
+        Arc::new(file)
+    });
+    let runtime = Arc::new(fff_ude_wasm::Runtime::try_new(&builtin).unwrap());
+    for file in files {
+        let output = FileReaderV2Builder::new(file)
+            .with_existing_runtimes_by_hash(HashMap::from([(
+                wasm_module_hash(&builtin),
+                runtime.clone(),
+            )]))
+            .build()
+            .unwrap()
+            .read_file()
+            .unwrap();
+        assert_eq!(concat_batches(&schema, &output).unwrap(), batch);
+    }
+
+    let mut options = CustomEncodingOptions::default();
+    let lib = |wasm: &Vec<u8>| WasmLib::new("/".into(), wasm.clone());
+    assert_eq!(
+        options.add_wasm(lib(&builtin), [DataType::Int32]),
+        WASMId(0)
+    );
+    assert_eq!(options.add_wasm(lib(&noop), []), WASMId(1));
+    // WASMIds must be the positions of the binaries, map to a binary and be unique per binary.
+    for options in [
+        CustomEncodingOptions::new(
+            HashMap::from([(WASMId(1), lib(&builtin))]),
+            HashMap::from([(DataType::Int32, WASMId(1))]),
+        ),
+        CustomEncodingOptions::new(
+            HashMap::from([(WASMId(0), lib(&builtin))]),
+            HashMap::from([(DataType::Int32, WASMId(1))]),
+        ),
+        CustomEncodingOptions::new(
+            HashMap::from([(WASMId(0), lib(&builtin)), (WASMId(1), lib(&builtin))]),
+            HashMap::from([(DataType::Int32, WASMId(1))]),
+        ),
+    ] {
+        let options = FileWriterOptionsBuilder::with_defaults()
+            .set_custom_encoding_options(options)
+            .build();
+        assert!(
+            FileWriter::try_new(schema.clone(), tempfile::tempfile().unwrap(), options).is_err()
+        );
+    }
+}
+
+#[apply(enable_built_in_wasm)]
+fn test_resource_report(#[case] enable_built_in_wasm: bool) {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, false),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from_iter_values(0..1000)),
+            Arc::new(StringArray::from_iter_values(
+                (0..1000).map(|x| format!("value{}", x)),
+            )),
+        ],
+    )
+    .unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch],
+        FileWriterOptionsBuilder::with_defaults()
+            .set_row_group_size(300)
+            .write_built_in_wasm(enable_built_in_wasm)
+            .build(),
+    );
+    let file = Arc::new(file);
+    let scan = || {
+        let reader = CountingReader::new(file.clone());
+        let mut file_reader = FileReaderV2Builder::new(reader.clone()).build().unwrap();
+        let footer_io = reader.metrics();
+        assert!(footer_io.num_requests > 0);
+        file_reader.read_file().unwrap();
+        let report = file_reader.resource_report().unwrap();
+        let io = report.io.unwrap();
+        assert!(io.num_requests > footer_io.num_requests);
+        assert!(io.bytes_read > 0);
+        report
+    };
+    let report = scan();
+    if enable_built_in_wasm {
+        assert_eq!(report.wasm.keys().copied().collect::<Vec<_>>(), vec![0]);
+        assert!(report.wasm[&0].num_calls > 0);
+    } else {
+        assert!(report.wasm.is_empty());
+    }
+    // The report is the same for every run, and round-trips through its serialization.
+    assert_eq!(scan(), report);
+    let json = serde_json::to_string(&report).unwrap();
+    assert_eq!(
+        serde_json::from_str::<ResourceReport>(&json).unwrap(),
+        report
+    );
+    assert_eq!(
+        serde_json::from_str::<ResourceReport>("{}").unwrap(),
+        ResourceReport::default()
+    );
+}
+
+/// Small EncUnits of WASM encodings are decoded several per call.
+#[test]
+fn test_batched_wasm_calls() {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, false),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from_iter(
+                (0..10_000).map(|x| (x % 11 != 0).then_some(x)),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                (0..10_000).map(|x| format!("value{}", x % 300)),
+            )),
+        ],
+    )
+    .unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch.clone()],
+        FileWriterOptionsBuilder::with_defaults()
+            .write_built_in_wasm(true)
+            .set_custom_encunit_len(HashMap::from([(0, 100), (1, 100)]))
+            .build(),
+    );
+    let mut file_reader = FileReaderV2Builder::new(Arc::new(file)).build().unwrap();
+    let read = file_reader.read_file().unwrap();
+    assert_eq!(
+        arrow::compute::concat_batches(&batch.schema(), &read).unwrap(),
+        batch
+    );
+    // Far fewer calls than the 200 EncUnits.
+    let report = file_reader.resource_report().unwrap();
+    assert!(report.wasm[&0].num_calls < 100);
+}
+
+#[apply(enable_built_in_wasm)]
+fn test_footer_padding(#[case] enable_built_in_wasm: bool) {
+    let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![Arc::new(Int32Array::from_iter_values(0..1000))],
+    )
+    .unwrap();
+    let options = |footer_padding| {
+        FileWriterOptionsBuilder::with_defaults()
+            .write_built_in_wasm(enable_built_in_wasm)
+            .set_footer_padding(footer_padding)
+            .build()
+    };
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(&mut file, &[batch.clone()], options(0));
+    assert_eq!(get_reserved_padding(&file).unwrap(), None);
+
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(&mut file, &[batch.clone()], options(4096));
+    let padding = get_reserved_padding(&file).unwrap().unwrap();
+    assert_eq!(padding.end - padding.start, 4096);
+    let mut buf = vec![1; 4096];
+    fff_poc::io::reader::Reader::read_exact_at(&file, &mut buf, padding.start).unwrap();
+    assert!(buf.iter().all(|b| *b == 0));
+
+    let file = Arc::new(file);
+    FileReaderV2Builder::new(file.clone())
+        .with_verify_file_checksum(true)
+        .build()
+        .unwrap();
+    test_read(file, &[batch], Projection::All, Selection::All);
+}
+
+#[apply(enable_built_in_wasm)]
+fn test_compress_metadata(#[case] enable_built_in_wasm: bool) {
+    use fff_format::File::fff::flatbuf::CompressionType;
+
+    let schema = Arc::new(Schema::new(
+        (0..20)
+            .map(|i| Field::new(format!("c{i}"), DataType::Int32, true))
+            .collect::<Vec<_>>(),
+    ));
+    let batches: Vec<_> = (0..10)
+        .map(|i| {
+            RecordBatch::try_new(
+                schema.clone(),
+                (0..20)
+                    .map(|j| {
+                        Arc::new(Int32Array::from_iter_values(i * 100 + j..(i + 1) * 100 + j))
+                            as ArrayRef
+                    })
+                    .collect(),
+            )
+            .unwrap()
+        })
+        .collect();
+    let options = |compression| {
+        FileWriterOptionsBuilder::with_defaults()
+            .write_built_in_wasm(enable_built_in_wasm)
+            .set_row_group_size(100)
+            .compress_metadata(compression)
+            .build()
+    };
+    let mut uncompressed = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut uncompressed,
+        &batches,
+        options(CompressionType::Uncompressed),
+    );
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(&mut file, &batches, options(CompressionType::Zstd));
+    let file_size = file.metadata().unwrap().len();
+    assert!(file_size < uncompressed.metadata().unwrap().len());
+    let mut postscript = [0; 32];
+    Reader::read_exact_at(&file, &mut postscript, file_size - 32).unwrap();
+    assert_eq!(CompressionType::from(postscript[8]), CompressionType::Zstd);
+
+    let file = Arc::new(file);
+    let uncompressed = Arc::new(uncompressed);
+    assert_eq!(
+        get_column_statistics(file.clone(), 3).unwrap(),
+        get_column_statistics(uncompressed, 3).unwrap()
+    );
+    for read_ahead in [false, true] {
+        let output = FileReaderV2Builder::new(file.clone())
+            .with_read_ahead(read_ahead)
+            .with_verify_file_checksum(true)
+            .build()
+            .unwrap()
+            .read_file()
+            .unwrap();
+        assert_eq!(
+            concat_batches(&schema, &output).unwrap(),
+            concat_batches(&schema, &batches).unwrap()
+        );
+    }
+    test_read(
+        file,
+        &batches,
+        Projection::LeafColumnIndexes(vec![1, 7]),
+        Selection::All,
+    );
+}
+
+#[test]
+fn test_encunit_index() {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, false),
+        Field::new("b", DataType::Utf8, false),
+    ]));
+    let batches: Vec<_> = (0..4)
+        .map(|i| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter_values(i * 100..(i + 1) * 100)),
+                    Arc::new(StringArray::from_iter_values(
+                        (i * 100..(i + 1) * 100).map(|x| format!("value{x:04}")),
+                    )),
+                ],
+            )
+            .unwrap()
+        })
+        .collect();
+    let options = |write_encunit_index| {
+        FileWriterOptionsBuilder::with_defaults()
+            .set_encoding_unit_len(100)
+            .set_write_encunit_index(write_encunit_index)
+            .build()
+    };
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(&mut file, &batches, options(false));
+    assert_eq!(get_encunit_index(&file).unwrap(), None);
+    assert_eq!(get_avg_encunit_num_rows(&file, 0).unwrap(), None);
+
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(&mut file, &batches, options(true));
+    let index = get_encunit_index(&file).unwrap().unwrap();
+    let chunk = index.chunk(1, 0, 0).unwrap();
+    assert_eq!(chunk.encunits.len(), 4);
+    assert_eq!(
+        chunk
+            .encunits
+            .iter()
+            .map(|encunit| encunit.first_row)
+            .collect::<Vec<_>>(),
+        vec![0, 100, 200, 300]
+    );
+    assert_eq!(chunk.find(250), Some(2));
+    let statistics = chunk.encunits[2].statistics.as_ref().unwrap();
+    assert_eq!(statistics.min_value().unwrap(), b"value0200");
+    assert_eq!(statistics.max_value().unwrap(), b"value0299");
+    // Zone maps prune the EncUnits of both fixed-width and string columns.
+    let ints = index.chunk(0, 0, 0).unwrap();
+    assert!(ints.encunits.iter().all(|e| e.statistics.is_some()));
+    let prune = |chunk, data_type: &DataType, op, value: ArrayRef| {
+        prune_encunits(chunk, data_type, op, value.as_ref()).unwrap()
+    };
+    let int = |v: i32| Arc::new(Int32Array::from(vec![v])) as ArrayRef;
+    assert_eq!(
+        prune(ints, &DataType::Int32, ComparisonOp::Eq, int(250)),
+        vec![2]
+    );
+    assert_eq!(
+        prune(ints, &DataType::Int32, ComparisonOp::Gt, int(250)),
+        vec![2, 3]
+    );
+    assert_eq!(
+        prune(ints, &DataType::Int32, ComparisonOp::Lt, int(100)),
+        vec![0]
+    );
+    assert_eq!(
+        prune(ints, &DataType::Int32, ComparisonOp::GtEq, int(400)),
+        vec![]
+    );
+    assert_eq!(
+        prune(ints, &DataType::Int32, ComparisonOp::NotEq, int(0)),
+        vec![0, 1, 2, 3]
+    );
+    // The value is cast to the type of the column.
+    assert_eq!(
+        prune(
+            ints,
+            &DataType::Int32,
+            ComparisonOp::LtEq,
+            Arc::new(Int64Array::from(vec![-1]))
+        ),
+        vec![]
+    );
+    assert_eq!(
+        prune(
+            ints,
+            &DataType::Int32,
+            ComparisonOp::Eq,
+            Arc::new(Int32Array::from(vec![None]))
+        ),
+        vec![]
+    );
+    assert_eq!(
+        prune(
+            chunk,
+            &DataType::Utf8,
+            ComparisonOp::Eq,
+            Arc::new(StringArray::from(vec!["value0150"]))
+        ),
+        vec![1]
+    );
+    assert_eq!(get_avg_encunit_num_rows(&file, 1).unwrap(), Some(100));
+
+    let file = Arc::new(file);
+    test_read(file, &batches, Projection::All, Selection::All);
+}
+
+#[apply(enable_built_in_wasm)]
+fn test_dataset_writer_rollover(#[case] enable_built_in_wasm: bool) {
+    let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+    let batches: Vec<_> = (0..5)
+        .map(|i| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int32Array::from_iter_values(
+                    i * 1000..(i + 1) * 1000,
+                ))],
+            )
+            .unwrap()
+        })
+        .collect();
+    let options = FileWriterOptionsBuilder::with_defaults()
+        .write_built_in_wasm(enable_built_in_wasm)
+        .build();
+    let dir = tempfile::tempdir().unwrap();
+    let mut writer = DatasetWriter::try_new(dir.path(), schema.clone(), options)
+        .unwrap()
+        .with_max_rows_per_file(2000);
+    for batch in &batches {
+        writer.write_batch(batch).unwrap();
+    }
+    let manifest = writer.finish().unwrap();
+    assert_eq!(
+        manifest
+            .files()
+            .iter()
+            .map(|f| f.num_rows)
+            .collect::<Vec<_>>(),
+        vec![2000, 2000, 1000]
+    );
+    assert_eq!(DatasetManifest::try_load(dir.path()).unwrap(), manifest);
+    assert_eq!(manifest.schema(), schema);
+    assert_eq!(manifest.num_rows(), 5000);
+    for (path, expected) in manifest
+        .file_paths(dir.path())
+        .into_iter()
+        .zip(batches.chunks(2))
+    {
+        test_read(
+            Arc::new(std::fs::File::open(path).unwrap()),
+            expected,
+            Projection::default(),
+            Selection::default(),
+        );
+    }
+}
+
+#[apply(enable_built_in_wasm)]
+fn test_file_manifest(#[case] enable_built_in_wasm: bool) {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, false),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from_iter(
+                (0..3000).map(|x| (x % 10 != 0).then_some(x)),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                (0..3000).map(|x| format!("value{}", x)),
+            )),
+        ],
+    )
+    .unwrap();
+    let options = FileWriterOptionsBuilder::with_defaults()
+        .write_built_in_wasm(enable_built_in_wasm)
+        .set_row_group_size(1000)
+        .build();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("data.fff");
+    let file = std::fs::File::create(&path).unwrap();
+    let mut writer = FileWriter::try_new(schema.clone(), file, options).unwrap();
+    writer.write_batch(&batch).unwrap();
+    let (_, manifest) = writer.finish_with_manifest().unwrap();
+
+    let manifest_path = manifest.write_next_to(&path).unwrap();
+    assert_eq!(manifest_path, dir.path().join("data.fff.manifest.json"));
+    assert_eq!(FileManifest::try_load(&path).unwrap(), manifest);
+    assert_eq!(manifest.arrow_schema().unwrap(), schema);
+    assert_eq!(manifest.num_rows, 3000);
+    assert_eq!(manifest.num_row_groups, 3);
+    assert_eq!(manifest.size, std::fs::metadata(&path).unwrap().len());
+    assert_eq!(
+        manifest
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.nullable))
+            .collect::<Vec<_>>(),
+        vec![("a", true), ("b", false)]
+    );
+    assert_eq!(manifest.columns.len(), 2);
+    assert_eq!(manifest.columns[0].null_count, Some(300));
+    assert_eq!(manifest.columns[1].null_count, Some(0));
+    assert_ne!(
+        manifest.columns[0].stats_digest,
+        manifest.columns[1].stats_digest
+    );
+    assert_eq!(manifest.wasm_modules.len(), enable_built_in_wasm as usize);
+    let json: serde_json::Value = serde_json::from_str(&manifest.to_json().unwrap()).unwrap();
+    assert_eq!(json["num_rows"], 3000);
+}
+
+#[rstest]
+#[case(DictionaryTypeOptions::EncoderDictionary)]
+#[case(DictionaryTypeOptions::LocalDictionary)]
+#[case(DictionaryTypeOptions::GlobalDictionary)]
+#[case(DictionaryTypeOptions::GlobalDictionaryMultiColSharing)]
+#[case(DictionaryTypeOptions::GlobalDictionaryAutoSharing)]
+fn test_empty_file(#[case] dictionary_type: DictionaryTypeOptions) {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, false),
+    ]));
+    let options = || {
+        FileWriterOptionsBuilder::with_defaults()
+            .set_dictionary_type(dictionary_type)
+            .build()
+    };
+    let empty_batch = RecordBatch::new_empty(schema.clone());
+    // Without any batch, and with zero-row batches only.
+    for batches in [vec![], vec![empty_batch.clone(), empty_batch]] {
+        let mut file = tempfile::tempfile().unwrap();
+        let mut writer = FileWriter::try_new(schema.clone(), &mut file, options()).unwrap();
+        for batch in &batches {
+            writer.write_batch(batch).unwrap();
+        }
+        let (_, manifest) = writer.finish_with_manifest().unwrap();
+        assert_eq!(manifest.num_rows, 0);
+        assert_eq!(manifest.num_row_groups, 0);
+        assert_eq!(manifest.columns.len(), 2);
+        assert!(manifest.columns.iter().all(|c| c.num_chunks == 0));
+
+        let file = Arc::new(file);
+        assert_eq!(get_avg_io_unit_size(file.clone(), 0).unwrap(), 0);
+        let mut reader = FileReaderV2Builder::new(file.clone()).build().unwrap();
+        assert_eq!(reader.schema(), schema);
+        assert!(reader.read_file().unwrap().is_empty());
+        let mut reader = FileReaderV2Builder::new(file)
+            .with_selection(Selection::new([0]))
+            .build()
+            .unwrap();
+        assert!(reader.read_file().unwrap().is_empty());
+    }
+}
+
+#[rstest]
+#[case(DictionaryTypeOptions::EncoderDictionary)]
+#[case(DictionaryTypeOptions::GlobalDictionary)]
+fn test_zero_row_batches(#[case] dictionary_type: DictionaryTypeOptions) {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, false),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from_iter(
+                (0..1000).map(|x| (x % 3 != 0).then_some(x)),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                (0..1000).map(|x| format!("value{}", x % 10)),
+            )),
+        ],
+    )
+    .unwrap();
+    let empty_batch = RecordBatch::new_empty(schema.clone());
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[empty_batch.clone(), batch.clone(), empty_batch],
+        FileWriterOptionsBuilder::with_defaults()
+            .set_dictionary_type(dictionary_type)
+            .set_row_group_size(1000)
+            .build(),
+    );
+    test_read(
+        Arc::new(file),
+        &[batch],
+        Projection::default(),
+        Selection::default(),
+    );
+}
+
+#[rstest]
+#[case(DictionaryTypeOptions::EncoderDictionary, Some(8))]
+#[case(DictionaryTypeOptions::LocalDictionary, Some(8))]
+#[case(DictionaryTypeOptions::EncoderDictionary, None)]
+fn test_string_statistics_truncation(
+    #[case] dictionary_type: DictionaryTypeOptions,
+    #[case] truncate_length: Option<usize>,
+) {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, false),
+        Field::new("b", DataType::Utf8, true),
+    ]));
+    let min = format!("aaaa{}", "x".repeat(100));
+    let max = format!("zzzz{}", "\u{10ffff}".repeat(100));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from_iter_values(0..4)),
+            Arc::new(StringArray::from(vec![
+                Some("mmmm"),
+                Some(min.as_str()),
+                None,
+                Some(max.as_str()),
+            ])),
+        ],
+    )
+    .unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch],
+        FileWriterOptionsBuilder::with_defaults()
+            .set_dictionary_type(dictionary_type)
+            .set_statistics_truncate_length(truncate_length)
+            .build(),
+    );
+    let file = Arc::new(file);
+    // No statistics for primitive columns yet.
+    assert!(get_column_statistics(file.clone(), 0)
+        .unwrap()
+        .iter()
+        .all(Option::is_none));
+    let statistics = get_column_statistics(file, 1).unwrap();
+    assert_eq!(statistics.len(), 1);
+    let statistics = statistics[0].as_ref().unwrap();
+    match truncate_length {
+        Some(len) => {
+            assert_eq!(statistics.min_value().unwrap(), &min.as_bytes()[..len]);
+            assert!(!statistics.is_min_value_exact());
+            // U+10FFFF cannot be incremented, so the last "z" is.
+            assert_eq!(statistics.max_value().unwrap(), b"zzz{");
+            assert!(!statistics.is_max_value_exact());
+        }
+        None => {
+            assert_eq!(statistics.min_value().unwrap(), min.as_bytes());
+            assert_eq!(statistics.max_value().unwrap(), max.as_bytes());
+            assert!(statistics.is_min_value_exact() && statistics.is_max_value_exact());
+        }
+    }
+}
+
+#[rstest]
+#[case(DictionaryTypeOptions::EncoderDictionary)]
+#[case(DictionaryTypeOptions::GlobalDictionary)]
+fn test_footer_cache(#[case] dictionary_type: DictionaryTypeOptions) {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, false),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from_iter_values(0..3000)),
+            Arc::new(StringArray::from_iter_values(
+                (0..3000).map(|x| format!("value{}", x % 10)),
+            )),
+        ],
+    )
+    .unwrap();
+    let options = || {
+        FileWriterOptionsBuilder::with_defaults()
+            .set_dictionary_type(dictionary_type)
+            .set_row_group_size(1000)
+            .build()
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("data.fff");
+    write_batches(
+        &mut std::fs::File::create(&path).unwrap(),
+        &[batch.clone()],
+        options(),
+    );
+
+    let cache = Arc::new(FooterCache::new());
+    let reader = CountingReader::new(Arc::new(std::fs::File::open(&path).unwrap()));
+    let open = |projection: Projection| {
+        let num_reads = reader.metrics().num_requests;
+        let mut file_reader = FileReaderV2Builder::new(reader.clone())
+            .with_projections(projection)
+            .with_footer_cache(
+                cache.clone(),
+                FooterCacheKey::try_from_local_path(&path).unwrap(),
+            )
+            .build()
+            .unwrap();
+        let build_reads = reader.metrics().num_requests - num_reads;
+        let batches = file_reader.read_file().unwrap();
+        (
+            build_reads,
+            concat_batches(&batches[0].schema(), &batches).unwrap(),
+        )
+    };
+    let (first_reads, first) = open(Projection::All);
+    assert!(first_reads > 0);
+    assert_eq!(cache.len(), 1);
+    let (cached_reads, cached) = open(Projection::All);
+    assert_eq!(cached_reads, 0);
+    assert_eq!(cached, first);
+    assert_eq!(cache.len(), 1);
+
+    let (_, projected) = open(Projection::LeafColumnIndexes(vec![1]));
+    assert_eq!(cache.len(), 2);
+    assert_eq!(projected.column(0), first.column(1));
+
+    cache.invalidate(&path.to_string_lossy());
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn test_chunk_cache() {
+    let batch = RecordBatch::try_from_iter(vec![
+        (
+            "a",
+            Arc::new(Int32Array::from_iter_values(0..3000)) as ArrayRef,
+        ),
+        (
+            "b",
+            Arc::new(StringArray::from_iter_values(
+                (0..3000).map(|x| format!("value{}", x % 10)),
+            )),
+        ),
+    ])
+    .unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch.clone()],
+        FileWriterOptionsBuilder::with_defaults()
+            .set_row_group_size(1000)
+            .build(),
+    );
+    let reader = CountingReader::new(Arc::new(file));
+    let cache = Arc::new(LruChunkCache::new(1 << 20));
+    let point_reads = |row_indexes: Vec<u64>| {
+        let mut file_reader = FileReaderV2Builder::new(reader.clone())
+            .with_selection(Selection::RowIndexes(row_indexes.clone()))
+            .with_chunk_cache(cache.clone(), "data.fff")
+            .build()
+            .unwrap();
+        let num_reads = reader.metrics().num_requests;
+        let batches = file_reader.read_file().unwrap();
+        let output = concat_batches(&batches[0].schema(), &batches).unwrap();
+        let expected = take_record_batch(&batch, &UInt64Array::from(row_indexes)).unwrap();
+        assert_eq!(output.columns(), expected.columns());
+        reader.metrics().num_requests - num_reads
+    };
+    assert!(point_reads(vec![5, 1500]) > 0);
+    let num_chunks = cache.len();
+    assert!(num_chunks > 0);
+    assert!(cache.size() > 0);
+    // The chunks holding the rows are cached, whatever the rows.
+    assert_eq!(point_reads(vec![6, 1499]), 0);
+    assert_eq!(cache.len(), num_chunks);
+    assert!(point_reads(vec![2999]) > 0);
+    assert!(cache.len() > num_chunks);
+
+    // Chunks over the budget are read every time.
+    let cache = Arc::new(LruChunkCache::new(1));
+    let mut file_reader = FileReaderV2Builder::new(reader.clone())
+        .with_chunk_cache(cache.clone(), "data.fff")
+        .build()
+        .unwrap();
+    file_reader.read_file().unwrap();
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn test_input_validation() {
+    let keys = Int32Array::from(vec![0, 1, 2, 1]);
+    let values = StringArray::from(vec!["x", "y", "x"]);
+    let batch = RecordBatch::try_from_iter(vec![
+        (
+            "a",
+            Arc::new(Float64Array::from(vec![1.0, -0.0, f64::NAN, -f64::NAN])) as ArrayRef,
+        ),
+        (
+            "b",
+            Arc::new(DictionaryArray::try_new(keys, Arc::new(values)).unwrap()),
+        ),
+    ])
+    .unwrap();
+    let write = |input_validation| {
+        let mut file = tempfile::tempfile().unwrap();
+        let options = FileWriterOptionsBuilder::with_defaults()
+            .set_input_validation(input_validation)
+            .build();
+        let mut writer = FileWriter::try_new(batch.schema(), &mut file, options).unwrap();
+        writer.write_batch(&batch)?;
+        writer.finish().unwrap();
+        Ok::<_, fff_core::errors::Error>(file)
+    };
+
+    let err = write(InputValidation::Strict).unwrap_err().to_string();
+    assert!(err.contains("column a at row 1: negative zero"), "{err}");
+
+    let mut file = write(InputValidation::Normalize).unwrap();
+    file.rewind().unwrap();
+    let output = FileReaderV2Builder::new(Arc::new(file))
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    let output = concat_batches(output[0].schema_ref(), &output).unwrap();
+    let floats = output.column(0).as_primitive::<Float64Type>();
+    assert_eq!(floats.value(1).to_bits(), 0.0f64.to_bits());
+    assert_eq!(floats.value(2).to_bits(), f64::NAN.to_bits());
+    assert_eq!(floats.value(3).to_bits(), f64::NAN.to_bits());
+    let strings = arrow::compute::cast(output.column(1), &DataType::Utf8).unwrap();
+    assert_eq!(
+        strings.as_string::<i32>().iter().collect::<Vec<_>>(),
+        [Some("x"), Some("y"), Some("x"), Some("y")]
+    );
+}
+
+#[test]
+fn test_io_concurrency() {
+    let batch = RecordBatch::try_from_iter(vec![
+        (
+            "a",
+            Arc::new(Int32Array::from_iter_values(0..3000)) as ArrayRef,
+        ),
+        (
+            "b",
+            Arc::new(StringArray::from_iter_values(
+                (0..3000).map(|x| format!("value{x}")),
+            )),
+        ),
+        (
+            "c",
+            Arc::new(Int64Array::from_iter_values(0..3000)) as ArrayRef,
+        ),
+    ])
+    .unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch.clone()],
+        FileWriterOptionsBuilder::with_defaults()
+            .set_row_group_size(1000)
+            .build(),
+    );
+    let reader = CountingReader::new(Arc::new(file));
+    let scan = |projection: Projection, max_concurrency: Option<usize>| {
+        let mut builder = FileReaderV2Builder::new(reader.clone()).with_projections(projection);
+        if let Some(max_concurrency) = max_concurrency {
+            builder = builder.with_io_concurrency(max_concurrency);
+        }
+        let mut file_reader = builder.build().unwrap();
+        let num_reads = reader.metrics().num_requests;
+        let batches = file_reader.read_file().unwrap();
+        let output = concat_batches(&batches[0].schema(), &batches).unwrap();
+        (output, reader.metrics().num_requests - num_reads)
+    };
+    let (expected, num_reads) = scan(Projection::All, None);
+    assert_eq!(expected.columns(), batch.columns());
+    let (output, num_scheduled_reads) = scan(Projection::All, Some(4));
+    assert_eq!(output.columns(), batch.columns());
+    assert!(num_scheduled_reads < num_reads);
+    // A range per request.
+    let (output, num_serial_reads) = scan(Projection::All, Some(1));
+    assert_eq!(output.columns(), batch.columns());
+    assert!(num_serial_reads > num_scheduled_reads);
+
+    // Only the chunks of the projected columns are scheduled, in a single wave.
+    let projection = Projection::new(vec![0, 2]);
+    let (_, num_reads) = scan(projection.clone(), None);
+    let (output, num_scheduled_reads) = scan(projection, Some(64));
+    assert_eq!(
+        output.columns(),
+        [batch.column(0).clone(), batch.column(2).clone()]
+    );
+    assert!(num_scheduled_reads < num_reads);
+}
+
+#[test]
+fn test_prefer_mmap() {
+    let batch = RecordBatch::try_from_iter(vec![
+        (
+            "a",
+            Arc::new(Int32Array::from_iter_values(0..3000)) as ArrayRef,
+        ),
+        (
+            "b",
+            Arc::new(StringArray::from_iter_values(
+                (0..3000).map(|x| format!("value{x}")),
+            )),
+        ),
+    ])
+    .unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch.clone()],
+        FileWriterOptionsBuilder::with_defaults()
+            .set_row_group_size(1000)
+            .build(),
+    );
+    let file = Arc::new(file);
+    // SAFETY: the file is not modified while read.
+    for (prefer_mmap, max_concurrency) in [(false, 1), (true, 1), (true, 4)] {
+        let builder = unsafe {
+            FileReaderV2Builder::new(LocalFile::from(file.clone())).prefer_mmap(prefer_mmap)
+        }
+        .with_io_concurrency(max_concurrency);
+        let batches = builder.build().unwrap().read_file().unwrap();
+        let output = concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(output.columns(), batch.columns());
+    }
+    let batches = unsafe { FileReaderV2Builder::new(LocalFile::from(file)).prefer_mmap(true) }
+        .with_selection(Selection::RowIndexes(vec![5, 1500, 2999]))
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    let output = concat_batches(&batches[0].schema(), &batches).unwrap();
+    assert_eq!(
+        output.column(1).as_ref(),
+        &StringArray::from(vec!["value5", "value1500", "value2999"]) as &dyn Array
+    );
+}
+
+#[apply(enable_built_in_wasm)]
+fn test_no_null_fast_path(#[case] enable_built_in_wasm: bool) {
+    let schema = Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Int32, true),
+    ]);
+    let a = Int32Array::from_iter_values(0..1000);
+    let b = Int32Array::from_iter((0..1000).map(|i| (i % 3 != 0).then_some(i)));
+    let input_batch =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(a), Arc::new(b)]).unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[input_batch.clone()],
+        FileWriterOptionsBuilder::with_defaults()
+            .write_built_in_wasm(enable_built_in_wasm)
+            .build(),
+    );
+    file.rewind().unwrap();
+    let output_batches = FileReaderV2Builder::new(Arc::new(file))
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    for batch in &output_batches {
+        // Column a has no nulls, so the reader should not materialize its validity.
+        assert!(batch.column(0).nulls().is_none());
+    }
+    let output_single_batch =
+        concat_batches(output_batches[0].schema_ref(), &output_batches).unwrap();
+    array_equal(input_batch.column(0), output_single_batch.column(0));
+    array_equal(input_batch.column(1), output_single_batch.column(1));
+}
+
+#[apply(enable_built_in_wasm)]
+fn test_multi_row_selection(#[case] enable_built_in_wasm: bool) {
+    let schema = Schema::new(vec![
+        Field::new("a", DataType::Int32, false),
+        Field::new("b", DataType::Utf8, true),
+    ]);
+    let a = Int32Array::from_iter_values(0..200_000);
+    let b = arrow::array::StringArray::from_iter((0..200_000).map(|i| {
+        if i % 7 == 0 {
+            None
+        } else {
+            Some(i.to_string())
+        }
+    }));
+    let input_batch =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(a), Arc::new(b)]).unwrap();
+    // Unsorted, duplicated and spanning multiple EncUnits and row groups.
+    test_read_file_roundtrip(
+        &[
+            input_batch.slice(0, 100_000),
+            input_batch.slice(100_000, 100_000),
+        ],
+        Projection::default(),
+        FileWriterOptionsBuilder::with_defaults()
+            .write_built_in_wasm(enable_built_in_wasm)
+            .set_row_group_size(100_000)
+            .build(),
+        Selection::RowIndexes(vec![199_999, 3, 64 * 1024 + 42, 3, 0, 100_000, 99_999]),
+    );
+}
+
+#[apply(enable_built_in_wasm)]
+fn test_read_multi(#[case] enable_built_in_wasm: bool) {
+    let schema = Schema::new(vec![
+        Field::new("a", DataType::Int32, false),
+        Field::new("b", DataType::Utf8, true),
+    ]);
+    let a = Int32Array::from_iter_values(0..200_000);
+    let b = arrow::array::StringArray::from_iter(
+        (0..200_000).map(|i| (i % 7 != 0).then(|| i.to_string())),
+    );
+    let input_batch =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(a), Arc::new(b)]).unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[input_batch.clone()],
+        FileWriterOptionsBuilder::with_defaults()
+            .write_built_in_wasm(enable_built_in_wasm)
+            .set_row_group_size(100_000)
+            .build(),
+    );
+    let reader = CountingReader::new(Arc::new(file));
+    let build = || FileReaderV2Builder::new(reader.clone()).build().unwrap();
+    let selections = [
+        Selection::RowIndexes(vec![100_001, 5, 5]),
+        Selection::RowIndexes(vec![6, 100_002, 199_999]),
+        Selection::RowIndexes(vec![]),
+    ];
+
+    let mut file_reader = build();
+    let before = reader.metrics();
+    let outputs = file_reader.read_multi(&selections).unwrap();
+    let bytes_read_multi = reader.metrics().bytes_read - before.bytes_read;
+    assert_eq!(outputs.len(), 3);
+    assert!(outputs[2].is_empty());
+    for (selection, output) in selections.iter().zip(&outputs).take(2) {
+        let Selection::RowIndexes(row_indexes) = selection else {
+            unreachable!()
+        };
+        let expected =
+            take_record_batch(&input_batch, &UInt64Array::from(row_indexes.clone())).unwrap();
+        assert_eq!(output.len(), 1);
+        array_equal(expected.column(0), output[0].column(0));
+        array_equal(expected.column(1), output[0].column(1));
+    }
+
+    // The chunks shared by both selections are only read once.
+    let mut bytes_read_separately = 0;
+    for selection in &selections[..2] {
+        let mut file_reader = build();
+        let before = reader.metrics();
+        file_reader
+            .read_multi(std::slice::from_ref(selection))
+            .unwrap();
+        bytes_read_separately += reader.metrics().bytes_read - before.bytes_read;
+    }
+    assert!(bytes_read_multi < bytes_read_separately);
+
+    let outputs = build()
+        .read_multi(&[Selection::All, Selection::RowIndexes(vec![3, 150_000])])
+        .unwrap();
+    let output_single_batch = concat_batches(outputs[0][0].schema_ref(), &outputs[0]).unwrap();
+    array_equal(input_batch.column(0), output_single_batch.column(0));
+    array_equal(input_batch.column(1), output_single_batch.column(1));
+    assert_eq!(
+        outputs[1][0].column(0).as_ref(),
+        &Int32Array::from(vec![3, 150_000]) as &dyn Array
+    );
+
+    assert!(build()
+        .read_multi(&[Selection::RowIndexes(vec![200_000])])
+        .is_err());
+    // The unsorted path rejects out-of-range rows instead of dropping them.
+    assert!(FileReaderV2Builder::new(reader.clone())
+        .with_selection(Selection::RowIndexes(vec![5, 200_000, 3]))
+        .build()
+        .unwrap()
+        .read_file()
+        .is_err());
+}
+
+#[test]
+fn test_read_rows() {
+    let s_fields = arrow_schema::Fields::from(vec![
+        Field::new("x", DataType::Int32, false),
+        Field::new("y", DataType::Utf8, true),
+    ]);
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int64, false),
+        Field::new("b", DataType::Utf8, true),
+        Field::new("s", DataType::Struct(s_fields.clone()), true),
+        Field::new(
+            "l",
+            DataType::List(Arc::new(Field::new("item", DataType::Int32, true))),
+            true,
+        ),
+    ]));
+    let batches: Vec<_> = (0..3)
+        .map(|i| {
+            let range = i * 10_000..(i + 1) * 10_000;
+            let mut list = ListBuilder::new(Int32Builder::new());
+            for x in range.clone() {
+                list.values().append_slice(&vec![x as i32; x as usize % 4]);
+                list.append(x % 5 != 0);
+            }
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from_iter_values(range.clone())),
+                    Arc::new(StringArray::from_iter(
+                        range
+                            .clone()
+                            .map(|x| (x % 7 != 0).then(|| format!("value{x}"))),
+                    )),
+                    Arc::new(arrow::array::StructArray::new(
+                        s_fields.clone(),
+                        vec![
+                            Arc::new(Int32Array::from_iter_values(
+                                range.clone().map(|x| x as i32),
+                            )),
+                            Arc::new(StringArray::from_iter(
+                                range.clone().map(|x| (x % 3 != 0).then(|| x.to_string())),
+                            )),
+                        ],
+                        Some(arrow::buffer::NullBuffer::from(
+                            range.clone().map(|x| x % 11 != 0).collect::<Vec<_>>(),
+                        )),
+                    )),
+                    Arc::new(list.finish()),
+                ],
+            )
+            .unwrap()
+        })
+        .collect();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &batches,
+        FileWriterOptionsBuilder::with_defaults()
+            .set_row_group_size(10_000)
+            .set_encoding_unit_len(1000)
+            .build(),
+    );
+    let file = Arc::new(file);
+    let input = concat_batches(&schema, &batches).unwrap();
+
+    // Row ids are sorted and deduplicated, across row groups.
+    let reader = FileReaderV2Builder::new(file.clone()).build().unwrap();
+    let output = reader
+        .read_rows(&Projection::All, &[25_000, 3, 9_999, 3, 17_123, 0, 11])
+        .unwrap();
+    let expected = take_record_batch(
+        &input,
+        &UInt64Array::from(vec![0, 3, 11, 9_999, 17_123, 25_000]),
+    )
+    .unwrap();
+    assert_eq!(output.num_rows(), expected.num_rows());
+    for (output, expected) in output.columns().iter().zip(expected.columns()) {
+        let output = arrow::compute::cast(output, expected.data_type()).unwrap();
+        assert_eq!(&output, expected);
+    }
+    let output = reader
+        .read_rows(&Projection::new([2, 0]), &[17_123])
+        .unwrap();
+    assert_eq!(output.schema().field(0).name(), "s");
+    assert_eq!(
+        output.column(1).as_ref(),
+        &Int64Array::from(vec![17_123]) as &dyn Array
+    );
+    assert_eq!(
+        reader.read_rows(&Projection::All, &[]).unwrap().num_rows(),
+        0
+    );
+    assert!(reader.read_rows(&Projection::All, &[30_000]).is_err());
+
+    // Only the chunks covering the rows are read, and only the projected columns are readable.
+    let counting = CountingReader::new(file.clone());
+    let reader = FileReaderV2Builder::new(counting.clone())
+        .with_projections(Projection::new([0]))
+        .build()
+        .unwrap();
+    let open_bytes = counting.metrics().bytes_read;
+    reader.read_rows(&Projection::All, &[17_123]).unwrap();
+    let lookup_bytes = counting.metrics().bytes_read - open_bytes;
+    let counting = CountingReader::new(file.clone());
+    let mut reader = FileReaderV2Builder::new(counting.clone())
+        .with_projections(Projection::new([0]))
+        .build()
+        .unwrap();
+    reader.read_file().unwrap();
+    assert!(lookup_bytes * 2 < counting.metrics().bytes_read - open_bytes);
+    assert!(reader.read_rows(&Projection::new([1]), &[0]).is_err());
+}
+
+#[apply(enable_built_in_wasm)]
+fn test_verify_decoded_length(#[case] enable_built_in_wasm: bool) {
+    let list = {
+        let mut builder = ListBuilder::new(Int32Builder::new());
+        for i in 0..100_000 {
+            builder.values().append_slice(&vec![i; i as usize % 4]);
+            builder.append(i % 5 != 0);
+        }
+        builder.finish()
+    };
+    let schema = Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("l", list.data_type().clone(), true),
+    ]);
+    let a = Int32Array::from_iter((0..100_000).map(|i| (i % 3 != 0).then_some(i)));
+    let input_batch =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(a), Arc::new(list)]).unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[input_batch.clone()],
+        FileWriterOptionsBuilder::with_defaults()
+            .write_built_in_wasm(enable_built_in_wasm)
+            .build(),
+    );
+    file.rewind().unwrap();
+    let file = Arc::new(file);
+    let output_batches = FileReaderV2Builder::new(file.clone())
+        .with_verify_decoded_length(true)
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    let output_single_batch =
+        concat_batches(output_batches[0].schema_ref(), &output_batches).unwrap();
+    array_equal(input_batch.column(0), output_single_batch.column(0));
+    array_equal(input_batch.column(1), output_single_batch.column(1));
+
+    let output_batches = FileReaderV2Builder::new(file)
+        .with_verify_decoded_length(true)
+        .with_projections(Projection::LeafColumnIndexes(vec![0]))
+        .with_selection(Selection::RowIndexes(vec![3, 64 * 1024, 99_999]))
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    let output_single_batch =
+        concat_batches(output_batches[0].schema_ref(), &output_batches).unwrap();
+    assert_eq!(
+        output_single_batch.column(0).as_ref(),
+        &Int32Array::from(vec![None, Some(64 * 1024), None]) as &dyn Array
+    );
+
+    // Record one row more than the EncUnits of the single chunk hold.
+    let schema = Schema::new(vec![Field::new("n", DataType::Int32, false)]);
+    let n = Int32Array::from_iter_values(0..70_000);
+    let input_batch = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(n)]).unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[input_batch],
+        FileWriterOptionsBuilder::with_defaults()
+            .write_built_in_wasm(enable_built_in_wasm)
+            .build(),
+    );
+    let summary = fff_poc::inspect::inspect_file(&file).unwrap();
+    assert_eq!(summary.row_groups[0].columns[0][0].num_rows, 70_000);
+    file.rewind().unwrap();
+    let mut buf = vec![];
+    std::io::Read::read_to_end(&mut file, &mut buf).unwrap();
+    // The chunk metadata precedes the footer, and only the chunk records 70000 rows as a u64.
+    let footer_start =
+        buf.len() - fff_format::POSTSCRIPT_SIZE as usize - summary.footer_size as usize;
+    let pos = buf[..footer_start]
+        .windows(8)
+        .rposition(|window| window == 70_000u64.to_le_bytes())
+        .unwrap();
+    buf[pos..pos + 8].copy_from_slice(&70_001u64.to_le_bytes());
+    let mut wrong_length = tempfile::tempfile().unwrap();
+    std::io::Write::write_all(&mut wrong_length, &buf).unwrap();
+    let err = FileReaderV2Builder::new(Arc::new(wrong_length))
+        .with_verify_decoded_length(true)
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Decoded 70000 rows but expected 70001 from chunk 0 of column 0"),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_verify_statistics() {
+    let schema = Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("s", DataType::Utf8, false),
+    ]);
+    let a = Int32Array::from_iter((0..1000).map(|i| (i % 3 != 0).then_some(i)));
+    let s = StringArray::from_iter_values((0..1000).map(|i| format!("value{i:04}")));
+    let input_batch =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(a), Arc::new(s)]).unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[input_batch.clone()],
+        FileWriterOptions::default(),
+    );
+    file.rewind().unwrap();
+    let mut buf = vec![];
+    std::io::Read::read_to_end(&mut file, &mut buf).unwrap();
+    let output_batches = FileReaderV2Builder::new(Arc::new(file))
+        .with_verify_statistics(true)
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    let output_single_batch =
+        concat_batches(output_batches[0].schema_ref(), &output_batches).unwrap();
+    assert_eq!(output_single_batch, input_batch);
+
+    // Make the min statistic of the string chunk, stored after its values, stale.
+    let pos = buf
+        .windows(9)
+        .rposition(|window| window == b"value0000")
+        .unwrap();
+    buf[pos..pos + 9].copy_from_slice(b"value0001");
+    let mut stale = tempfile::tempfile().unwrap();
+    std::io::Write::write_all(&mut stale, &buf).unwrap();
+    let stale = Arc::new(stale);
+    let err = FileReaderV2Builder::new(stale.clone())
+        .with_verify_statistics(true)
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap_err();
+    assert!(err.to_string().contains("do not match its values"), "{err}");
+    assert!(FileReaderV2Builder::new(stale)
+        .build()
+        .unwrap()
+        .read_file()
+        .is_ok());
+}
+
+#[apply(enable_built_in_wasm)]
+fn test_boolean_with_validity_roundtrip(#[case] enable_built_in_wasm: bool) {
+    let schema = Schema::new(vec![
+        Field::new("a", DataType::Boolean, true),
+        Field::new("b", DataType::Boolean, false),
+        Field::new("c", DataType::Boolean, true),
+    ]);
+    let a = BooleanArray::from_iter((0..100_000).map(|i| (i % 1000 >= 10).then_some(i % 3 == 0)));
+    let b = BooleanArray::from_iter_values((0..100_000).map(|i| i % 7 == 0));
+    let c = BooleanArray::from(vec![None::<bool>; 100_000]);
+    let input_batch = RecordBatch::try_new(
+        Arc::new(schema),
+        vec![Arc::new(a), Arc::new(b), Arc::new(c)],
+    )
+    .unwrap();
+    let options = || {
+        FileWriterOptionsBuilder::with_defaults()
+            .write_built_in_wasm(enable_built_in_wasm)
+            .build()
+    };
+    test_read_file_roundtrip(
+        &[input_batch.clone()],
+        Projection::All,
+        options(),
+        Selection::All,
+    );
+    test_read_file_roundtrip(
+        &[input_batch],
+        Projection::All,
+        options(),
+        Selection::RowIndexes(vec![0, 9, 10, 64 * 1024 + 5, 99_999]),
+    );
+}
+
+#[apply(enable_built_in_wasm)]
+fn test_adaptive_encoding_roundtrip(#[case] enable_built_in_wasm: bool) {
+    let schema = Schema::new(vec![
+        Field::new(
+            "ts",
+            DataType::Timestamp(arrow_schema::TimeUnit::Nanosecond, None),
+            false,
+        ),
+        Field::new("runs", DataType::Int64, true),
+        Field::new("flags", DataType::Boolean, true),
+        Field::new("names", DataType::Utf8, false),
+    ]);
+    let ts = TimestampNanosecondArray::from_iter_values(
+        (0..100_000).map(|i| 1_700_000_000_000_000_000 + i * 1_000_000_007),
+    );
+    let runs = Int64Array::from_iter((0..100_000).map(|i| (i % 5000 >= 50).then_some(i / 4096)));
+    let flags =
+        BooleanArray::from_iter((0..100_000).map(|i| (i % 1000 >= 10).then_some(i % 3 == 0)));
+    let names = StringArray::from_iter_values((0..100_000).map(|i| format!("name{}", i % 100)));
+    let input_batch = RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(ts),
+            Arc::new(runs),
+            Arc::new(flags),
+            Arc::new(names),
+        ],
+    )
+    .unwrap();
+    let options = || {
+        FileWriterOptionsBuilder::with_defaults()
+            .write_built_in_wasm(enable_built_in_wasm)
+            .set_adaptive_encoding(Some(AdaptiveEncodingOptions::default()))
+            .build()
+    };
+    test_read_file_roundtrip(
+        &[input_batch.clone()],
+        Projection::All,
+        options(),
+        Selection::All,
+    );
+    test_read_file_roundtrip(
+        &[input_batch],
+        Projection::All,
+        options(),
+        Selection::RowIndexes(vec![0, 49, 50, 64 * 1024 + 5, 99_999]),
+    );
+}
+
+#[apply(enable_built_in_wasm)]
+fn test_timestamp_normalization(#[case] enable_built_in_wasm: bool) {
+    // Shaped like ClickBench's EventTime (seconds without time zone), a TPC-H date stored as a
+    // timestamp in milliseconds, and nanoseconds with a time zone.
+    let num_rows = 10_000;
+    let event_time =
+        TimestampSecondArray::from_iter_values((0..num_rows).map(|i| 1_373_000_000 + i * 17));
+    let ship_date = TimestampMillisecondArray::from_iter(
+        (0..num_rows).map(|i| (i % 7 != 0).then_some(694_224_000_000 + i / 10 * 86_400_000)),
+    );
+    let ts = TimestampNanosecondArray::from_iter_values(
+        (0..num_rows).map(|i| 1_700_000_000_000_000_000 + i * 1_000_000_007),
+    )
+    .with_timezone("+08:00");
+    let batch = RecordBatch::try_from_iter(vec![
+        ("EventTime", Arc::new(event_time.clone()) as ArrayRef),
+        ("l_shipdate", Arc::new(ship_date.clone())),
+        ("ts", Arc::new(ts.clone())),
+    ])
+    .unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch.clone()],
+        FileWriterOptionsBuilder::with_defaults()
+            .set_row_group_size(4000)
+            .write_built_in_wasm(enable_built_in_wasm)
+            .build(),
+    );
+    let file = Arc::new(file);
+    let builder = |normalization: TimestampNormalization, selection: Selection| {
+        FileReaderV2Builder::new(file.clone())
+            .with_timestamp_normalization(normalization)
+            .with_selection(selection)
+    };
+    let read = |normalization: TimestampNormalization, selection: Selection| {
+        let batches = builder(normalization, selection)
+            .build()
+            .unwrap()
+            .read_file()
+            .unwrap();
+        concat_batches(&batches[0].schema(), &batches).unwrap()
+    };
+
+    let preserved = read(TimestampNormalization::Preserve, Selection::All);
+    assert_eq!(preserved.schema(), batch.schema());
+    assert_eq!(preserved, batch);
+
+    let utc_micros = DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()));
+    let expected = RecordBatch::try_from_iter(vec![
+        (
+            "EventTime",
+            Arc::new(
+                TimestampMicrosecondArray::from_iter_values(
+                    event_time.values().iter().map(|v| v * 1_000_000),
+                )
+                .with_timezone("UTC"),
+            ) as ArrayRef,
+        ),
+        (
+            "l_shipdate",
+            Arc::new(
+                TimestampMicrosecondArray::from_iter(ship_date.iter().map(|v| v.map(|v| v * 1000)))
+                    .with_timezone("UTC"),
+            ),
+        ),
+        (
+            "ts",
+            Arc::new(
+                TimestampMicrosecondArray::from_iter_values(ts.values().iter().map(|v| v / 1000))
+                    .with_timezone("UTC"),
+            ),
+        ),
+    ])
+    .unwrap();
+    let normalized = read(TimestampNormalization::UtcMicros, Selection::All);
+    for field in normalized.schema().fields() {
+        assert_eq!(field.data_type(), &utc_micros);
+    }
+    assert_eq!(normalized, expected);
+
+    let row_indexes = vec![0, 7, 3999, 4000, 9_999];
+    let selected = read(
+        TimestampNormalization::UtcMicros,
+        Selection::RowIndexes(row_indexes.clone()),
+    );
+    assert_eq!(
+        selected,
+        take_record_batch(&expected, &UInt64Array::from(row_indexes)).unwrap()
+    );
+
+    let stream = builder(TimestampNormalization::UtcMicros, Selection::All)
+        .build()
+        .unwrap()
+        .into_stream();
+    assert_eq!(stream.schema(), expected.schema());
+    let batches = stream.collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(
+        concat_batches(&expected.schema(), &batches).unwrap(),
+        expected
+    );
+}
+
+/// Temporal types come back with the unit, time zone and interval unit of the schema, and the
+/// metadata of the schema and fields are kept.
+#[test]
+fn test_temporal_type_fidelity() {
+    use arrow_array::{
+        Date64Array, DurationMicrosecondArray, IntervalDayTimeArray, IntervalMonthDayNanoArray,
+        IntervalYearMonthArray, Time32SecondArray,
+    };
+    use arrow_buffer::{IntervalDayTime, IntervalMonthDayNano};
+
+    let num_rows = 5000;
+    let valid = |i: i64| i % 7 != 0;
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(
+            TimestampMillisecondArray::from_iter(
+                (0..num_rows).map(|i| valid(i).then_some(1_700_000_000_000 + i * 999)),
+            )
+            .with_timezone("America/New_York"),
+        ),
+        Arc::new(TimestampSecondArray::from_iter_values(0..num_rows).with_timezone("+08:00")),
+        Arc::new(DurationMicrosecondArray::from_iter(
+            (0..num_rows).map(|i| valid(i).then_some(i * 1_000_003 - 7)),
+        )),
+        Arc::new(IntervalYearMonthArray::from_iter(
+            (0..num_rows).map(|i| valid(i).then_some(i as i32 - 100)),
+        )),
+        Arc::new(IntervalDayTimeArray::from_iter((0..num_rows).map(|i| {
+            valid(i).then(|| IntervalDayTime::new(i as i32 % 40 - 20, -(i as i32) * 1000))
+        }))),
+        Arc::new(IntervalMonthDayNanoArray::from_iter((0..num_rows).map(
+            |i| {
+                valid(i)
+                    .then(|| IntervalMonthDayNano::new(i as i32 % 13, -(i as i32), i * 1_000_001))
+            },
+        ))),
+        Arc::new(Time32SecondArray::from_iter_values(
+            (0..num_rows).map(|i| (i % 86_400) as i32),
+        )),
+        Arc::new(Date64Array::from_iter_values(
+            (0..num_rows).map(|i| i * 86_400_000),
+        )),
+    ];
+    let schema = Arc::new(Schema::new_with_metadata(
+        columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                Field::new(format!("c{i}"), column.data_type().clone(), true).with_metadata(
+                    HashMap::from([("origin".to_string(), format!("column {i}"))]),
+                )
+            })
+            .collect::<Vec<_>>(),
+        HashMap::from([("writer".to_string(), "engine".to_string())]),
+    ));
+    let batch = RecordBatch::try_new(schema.clone(), columns).unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch.slice(0, 2000), batch.slice(2000, 3000)],
+        FileWriterOptions::default(),
+    );
+    let file = Arc::new(file);
+
+    let read = FileReaderV2Builder::new(file.clone())
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    assert!(read.iter().all(|b| b.schema() == schema));
+    assert_eq!(concat_batches(&schema, &read).unwrap(), batch);
+
+    let row_indexes = vec![4999, 0, 2001, 7];
+    let selected = FileReaderV2Builder::new(file)
+        .with_selection(Selection::RowIndexes(row_indexes.clone()))
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    assert_eq!(
+        concat_batches(&schema, &selected).unwrap(),
+        take_record_batch(&batch, &UInt64Array::from(row_indexes)).unwrap()
+    );
+}
+
+#[test]
+fn test_target_schema() {
+    let num_rows = 10_000;
+    let a = Int32Array::from_iter((0..num_rows).map(|i| (i % 5 != 0).then_some(i)));
+    let b = StringArray::from_iter_values((0..num_rows).map(|i| format!("b{i}")));
+    let c = TimestampMillisecondArray::from_iter_values((0..num_rows as i64).map(|i| i * 1001));
+    let batch = RecordBatch::try_from_iter(vec![
+        ("a", Arc::new(a.clone()) as ArrayRef),
+        ("b", Arc::new(b.clone())),
+        ("c", Arc::new(c.clone())),
+        (
+            "dropped",
+            Arc::new(Int32Array::from_iter_values(0..num_rows)),
+        ),
+    ])
+    .unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch],
+        FileWriterOptionsBuilder::with_defaults()
+            .set_row_group_size(4000)
+            .build(),
+    );
+    let file = Arc::new(file);
+
+    // Columns are reordered, promoted and added, and the dropped column is not read.
+    let target_schema = Arc::new(Schema::new(vec![
+        Field::new("b", DataType::LargeUtf8, false),
+        Field::new("a", DataType::Int64, true),
+        Field::new("added", DataType::Int32, true),
+        Field::new("c", DataType::Timestamp(TimeUnit::Microsecond, None), false),
+    ]));
+    let expected = RecordBatch::try_new(
+        target_schema.clone(),
+        vec![
+            Arc::new(arrow_array::LargeStringArray::from_iter_values(
+                b.iter().flatten(),
+            )),
+            Arc::new(Int64Array::from_iter(a.iter().map(|v| v.map(i64::from)))),
+            Arc::new(Int32Array::new_null(num_rows as usize)),
+            Arc::new(TimestampMicrosecondArray::from_iter_values(
+                c.values().iter().map(|v| v * 1000),
+            )),
+        ],
+    )
+    .unwrap();
+    let builder =
+        || FileReaderV2Builder::new(file.clone()).with_target_schema(target_schema.clone());
+    let mut reader = builder().build().unwrap();
+    assert_eq!(reader.schema(), target_schema);
+    let batches = reader.read_file().unwrap();
+    assert_eq!(concat_batches(&target_schema, &batches).unwrap(), expected);
+
+    let row_indexes = vec![9_999, 0, 4000];
+    let selected = builder()
+        .with_selection(Selection::RowIndexes(row_indexes.clone()))
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    assert_eq!(
+        concat_batches(&target_schema, &selected).unwrap(),
+        take_record_batch(&expected, &UInt64Array::from(row_indexes)).unwrap()
+    );
+
+    let stream = builder().build().unwrap().into_stream();
+    assert_eq!(stream.schema(), target_schema);
+    let batches = stream.collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(concat_batches(&target_schema, &batches).unwrap(), expected);
+
+    let read_as = |fields: Vec<Field>| {
+        FileReaderV2Builder::new(file.clone())
+            .with_target_schema(Arc::new(Schema::new(fields)))
+            .build()
+    };
+    // Narrowing and unrelated types are not promotions.
+    assert!(read_as(vec![Field::new("a", DataType::Int16, true)]).is_err());
+    assert!(read_as(vec![Field::new("b", DataType::Int64, false)]).is_err());
+    // Missing columns must be nullable, and some column must be in the file.
+    assert!(read_as(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("added", DataType::Int32, false),
+    ])
+    .is_err());
+    assert!(read_as(vec![Field::new("added", DataType::Int32, true)]).is_err());
+    assert!(builder()
+        .with_projections(Projection::new([0]))
+        .build()
+        .is_err());
+}
+
+#[test]
+fn test_default_values() {
+    let num_rows = 1000;
+    let batch = RecordBatch::try_from_iter(vec![
+        (
+            "a",
+            Arc::new(Int32Array::from_iter_values(0..num_rows)) as ArrayRef,
+        ),
+        (
+            "b",
+            Arc::new(StringArray::from_iter_values(
+                (0..num_rows).map(|i| format!("b{i}")),
+            )),
+        ),
+    ])
+    .unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch.clone()],
+        FileWriterOptionsBuilder::with_defaults()
+            .set_row_group_size(400)
+            .build(),
+    );
+    let file = Arc::new(file);
+
+    // Missing columns of the target schema, including non-nullable ones, take their default.
+    let target_schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int64, false),
+        Field::new("zero", DataType::Float64, false),
+        Field::new("constant", DataType::Utf8, false),
+        Field::new("null", DataType::Int32, true),
+    ]));
+    let builder = || {
+        FileReaderV2Builder::new(file.clone())
+            .with_target_schema(target_schema.clone())
+            .with_default_value("zero", DefaultValueProvider::Zero)
+            .with_default_value(
+                "constant",
+                DefaultValueProvider::Constant(Arc::new(StringArray::from(vec!["unknown"]))),
+            )
+    };
+    let expected = RecordBatch::try_new(
+        target_schema.clone(),
+        vec![
+            Arc::new(Int64Array::from_iter_values(0..num_rows as i64)),
+            Arc::new(arrow_array::Float64Array::from(vec![
+                0.0;
+                num_rows as usize
+            ])),
+            Arc::new(StringArray::from(vec!["unknown"; num_rows as usize])),
+            Arc::new(Int32Array::new_null(num_rows as usize)),
+        ],
+    )
+    .unwrap();
+    let batches = builder().build().unwrap().read_file().unwrap();
+    assert_eq!(concat_batches(&target_schema, &batches).unwrap(), expected);
+
+    // Placeholder rows of the non-nullable column a, which has no default value, are invalid.
+    assert!(builder()
+        .with_fill_out_of_range_rows(true)
+        .build()
+        .unwrap()
+        .read_multi(&[Selection::RowIndexes(vec![3, 2000])])
+        .is_err());
+
+    // Rows past the end of the file are placeholder rows, in the requested position.
+    let builder = || {
+        FileReaderV2Builder::new(file.clone())
+            .with_fill_out_of_range_rows(true)
+            .with_default_value(
+                "a",
+                DefaultValueProvider::Constant(Arc::new(Int64Array::from(vec![-1]))),
+            )
+            .with_default_value("b", DefaultValueProvider::Zero)
+    };
+    let row_indexes = vec![5, 5000, 0, 1000];
+    let expected = RecordBatch::try_from_iter(vec![
+        (
+            "a",
+            Arc::new(Int32Array::from(vec![5, -1, 0, -1])) as ArrayRef,
+        ),
+        ("b", Arc::new(StringArray::from(vec!["b5", "", "b0", ""]))),
+    ])
+    .unwrap();
+    let selected = builder()
+        .with_selection(Selection::RowIndexes(row_indexes.clone()))
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    assert_eq!(
+        concat_batches(&expected.schema(), &selected).unwrap(),
+        expected
+    );
+    let outputs = builder()
+        .build()
+        .unwrap()
+        .read_multi(&[
+            Selection::RowIndexes(row_indexes),
+            Selection::RowIndexes(vec![2000]),
+            Selection::All,
+        ])
+        .unwrap();
+    assert_eq!(outputs[0], vec![expected]);
+    assert_eq!(outputs[1][0].num_rows(), 1);
+    assert_eq!(outputs[2], vec![batch]);
+
+    // Without filling, rows past the end of the file are out of bound.
+    assert!(FileReaderV2Builder::new(file.clone())
+        .build()
+        .unwrap()
+        .read_multi(&[Selection::RowIndexes(vec![1000])])
+        .is_err());
+    // Default values must have the type of their column and a single value.
+    assert!(FileReaderV2Builder::new(file.clone())
+        .with_target_schema(Arc::new(Schema::new(vec![Field::new(
+            "missing",
+            DataType::Int32,
+            false
+        )])))
+        .with_default_value(
+            "missing",
+            DefaultValueProvider::Constant(Arc::new(Int32Array::from(vec![1, 2])))
+        )
+        .build()
+        .is_err());
+}
+
+#[test]
+fn test_interleaved_row_groups() {
+    let write_file = |keys: Vec<i64>, write_encunit_index: bool| {
+        let batch = RecordBatch::try_from_iter(vec![
+            ("ts", Arc::new(Int64Array::from(keys.clone())) as ArrayRef),
+            (
+                "k",
+                Arc::new(StringArray::from_iter_values(
+                    keys.iter().map(|k| format!("k{k:05}")),
+                )),
+            ),
+        ])
+        .unwrap();
+        let mut file = tempfile::tempfile().unwrap();
+        write_batches(
+            &mut file,
+            &[batch],
+            FileWriterOptionsBuilder::with_defaults()
+                .set_row_group_size(250)
+                .set_write_encunit_index(write_encunit_index)
+                .build(),
+        );
+        Arc::new(file)
+    };
+    let read_keys = |reader: InterleavedReader<Arc<std::fs::File>>| {
+        reader
+            .flat_map(|batch| {
+                let batch = batch.unwrap();
+                batch
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>()
+    };
+    // Files whose row groups alternate in time.
+    let first = write_file((0..500).chain(1000..1500).collect(), true);
+    let second = write_file((500..1000).chain(1500..2000).collect(), true);
+    let files = vec![second.clone(), first.clone()];
+    let reader = InterleavedReader::try_new(files.clone(), "ts").unwrap();
+    assert!(reader.is_sorted());
+    assert_eq!(
+        reader
+            .row_groups()
+            .iter()
+            .map(|rg| (rg.file, rg.row_group))
+            .collect::<Vec<_>>(),
+        vec![
+            (1, 0),
+            (1, 1),
+            (0, 0),
+            (0, 1),
+            (1, 2),
+            (1, 3),
+            (0, 2),
+            (0, 3)
+        ]
+    );
+    assert_eq!(read_keys(reader), (0..2000).collect::<Vec<_>>());
+    // String keys have chunk statistics.
+    let reader = InterleavedReader::try_new(files.clone(), "k").unwrap();
+    assert!(reader.is_sorted());
+    assert_eq!(read_keys(reader), (0..2000).collect::<Vec<_>>());
+    let reader = InterleavedReader::try_new_with(files, "ts", |builder| {
+        builder.with_projections(Projection::new([1]))
+    })
+    .unwrap();
+    assert_eq!(
+        reader.map(|batch| batch.unwrap().num_rows()).sum::<usize>(),
+        2000
+    );
+
+    // Overlapping row groups are output whole, by their min.
+    let overlapping = write_file((100..200).collect(), true);
+    let reader = InterleavedReader::try_new(vec![first.clone(), overlapping], "ts").unwrap();
+    assert!(!reader.is_sorted());
+    let keys = read_keys(reader);
+    assert_eq!(keys[..250], (0..250).collect::<Vec<_>>());
+    assert_eq!(keys[250..350], (100..200).collect::<Vec<_>>());
+
+    // Fixed-width keys need the EncUnit index.
+    let unindexed = write_file((0..500).collect(), false);
+    assert!(InterleavedReader::try_new(vec![first.clone(), unindexed.clone()], "ts").is_err());
+    assert!(InterleavedReader::try_new(vec![first, unindexed], "k").is_ok());
+}
+
+#[test]
+fn test_sort_by() {
+    use arrow::compute::{lexsort_to_indices, SortColumn, SortOptions};
+    use fff_poc::{file::sort_order::SortColumn as FileSortColumn, options::SortDirection};
+    use rand::Rng;
+
+    let mut rng = rand::thread_rng();
+    let batches: Vec<_> = (0..3)
+        .map(|i| {
+            RecordBatch::try_from_iter(vec![
+                (
+                    "a",
+                    Arc::new(Int32Array::from_iter((0..700).map(|_| {
+                        (rng.gen_range(0..10) != 0).then(|| rng.gen_range(0..50))
+                    }))) as ArrayRef,
+                ),
+                (
+                    "b",
+                    Arc::new(StringArray::from_iter_values(
+                        (0..700).map(|j| format!("{}", i * 700 + j)),
+                    )),
+                ),
+                (
+                    "c",
+                    Arc::new(Int64Array::from_iter_values((0..700).map(|j| i * 700 + j))),
+                ),
+            ])
+            .unwrap()
+        })
+        .collect();
+    let options = || {
+        FileWriterOptionsBuilder::with_defaults()
+            .set_row_group_size(1000)
+            .sort_by(vec![
+                ("a", SortDirection::Ascending),
+                ("b", SortDirection::Descending),
+            ])
+            .build()
+    };
+    let sorted = |batches: &[RecordBatch]| {
+        let batch = concat_batches(&batches[0].schema(), batches).unwrap();
+        let indices = lexsort_to_indices(
+            &[
+                SortColumn {
+                    values: batch.column(0).clone(),
+                    options: Some(SortOptions::default()),
+                },
+                SortColumn {
+                    values: batch.column(1).clone(),
+                    options: Some(SortOptions {
+                        descending: true,
+                        nulls_first: true,
+                    }),
+                },
+            ],
+            None,
+        )
+        .unwrap();
+        take_record_batch(&batch, &indices).unwrap()
+    };
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(&mut file, &batches, options());
+    let file = Arc::new(file);
+
+    // Batches are buffered until they fill a row group, then sorted.
+    let mut reader = FileReaderV2Builder::new(file.clone()).build().unwrap();
+    let expected_order = vec![
+        FileSortColumn {
+            column: 0,
+            descending: false,
+            nulls_first: true,
+        },
+        FileSortColumn {
+            column: 1,
+            descending: true,
+            nulls_first: true,
+        },
+    ];
+    assert_eq!(reader.sort_order(), expected_order);
+    let output = reader.read_file().unwrap();
+    let output = concat_batches(&batches[0].schema(), &output).unwrap();
+    assert_eq!(output.slice(0, 1400), sorted(&batches[..2]));
+    assert_eq!(output.slice(1400, 700), sorted(&batches[2..]));
+
+    // Dropping a key column keeps the keys before it.
+    let mut dropped = tempfile::tempfile().unwrap();
+    FileWriter::drop_columns(
+        file.clone(),
+        &mut dropped,
+        &["b"],
+        false,
+        FileWriterOptionsBuilder::with_defaults().build(),
+    )
+    .unwrap();
+    let reader = FileReaderV2Builder::new(Arc::new(dropped)).build().unwrap();
+    assert_eq!(reader.sort_order(), &expected_order[..1]);
+
+    // Row groups appended without sorting clear the order.
+    let mut appended = tempfile::tempfile().unwrap();
+    write_batches(&mut appended, &batches, options());
+    let reader = Arc::new(appended.try_clone().unwrap());
+    let mut writer = FileWriter::try_append(
+        batches[0].schema(),
+        reader.clone(),
+        &mut appended,
+        FileWriterOptionsBuilder::with_defaults().build(),
+    )
+    .unwrap();
+    writer.write_batch(&batches[0]).unwrap();
+    writer.finish().unwrap();
+    let reader = FileReaderV2Builder::new(reader).build().unwrap();
+    assert!(reader.sort_order().is_empty());
+
+    assert!(FileWriter::try_new(
+        batches[0].schema(),
+        tempfile::tempfile().unwrap(),
+        FileWriterOptionsBuilder::with_defaults()
+            .sort_by(vec![("missing", SortDirection::Ascending)])
+            .build(),
+    )
+    .is_err());
+}
+
+#[test]
+fn test_adaptive_read_ahead() {
+    let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+    let batches: Vec<_> = (0..100)
+        .map(|i| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int32Array::from_iter_values(
+                    (i * 1000..i * 1000 + 1000).map(|v: i32| v.wrapping_mul(-1_640_531_535)),
+                ))],
+            )
+            .unwrap()
+        })
+        .collect();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &batches,
+        // A chunk per batch, written one after the other.
+        FileWriterOptionsBuilder::with_defaults()
+            .set_column_chunk_sizes([(0, 1)].into())
+            .build(),
+    );
+    let file = Arc::new(file);
+
+    // A scan is detected as sequential after a few chunks, and the next ones are read ahead.
+    let scan_requests = |read_ahead: bool| {
+        let counting = CountingReader::new(file.clone());
+        let output = if read_ahead {
+            let reader = ReadAheadReader::new(counting.clone());
+            let output = FileReaderV2Builder::new(reader.clone())
+                .build()
+                .unwrap()
+                .read_file()
+                .unwrap();
+            assert_eq!(reader.access_pattern(), AccessPattern::Sequential);
+            output
+        } else {
+            FileReaderV2Builder::new(counting.clone())
+                .build()
+                .unwrap()
+                .read_file()
+                .unwrap()
+        };
+        assert_eq!(
+            concat_batches(&schema, &output).unwrap(),
+            concat_batches(&schema, &batches).unwrap()
+        );
+        counting.metrics().num_requests
+    };
+    let plain_requests = scan_requests(false);
+    assert!(plain_requests > 100);
+    assert!(scan_requests(true) < plain_requests / 2);
+
+    // Rows far apart are read without reading ahead.
+    let reader = ReadAheadReader::new(CountingReader::new(file.clone()));
+    let row_indexes: Vec<u64> = (0..10).map(|i| i * 10_000 + 5).collect();
+    let output = FileReaderV2Builder::new(reader.clone())
+        .with_selection(Selection::RowIndexes(row_indexes.clone()))
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    assert_eq!(
+        concat_batches(&schema, &output).unwrap(),
+        take_record_batch(
+            &concat_batches(&schema, &batches).unwrap(),
+            &UInt64Array::from(row_indexes)
+        )
+        .unwrap()
+    );
+    assert_eq!(reader.access_pattern(), AccessPattern::Random);
+    assert_eq!(reader.read_ahead(), 0);
+}
+
+#[test]
+fn test_key_value_metadata() {
+    let batch = RecordBatch::try_from_iter(vec![
+        (
+            "a",
+            Arc::new(Int32Array::from_iter_values(0..1000)) as ArrayRef,
+        ),
+        (
+            "b",
+            Arc::new(StringArray::from_iter_values(
+                (0..1000).map(|i| format!("b{i}")),
+            )),
+        ),
+    ])
+    .unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    let mut writer = FileWriter::try_new(
+        batch.schema(),
+        &mut file,
+        FileWriterOptionsBuilder::with_defaults().build(),
+    )
+    .unwrap();
+    writer.write_batch(&batch).unwrap();
+    writer.add_metadata("lineage", "s3://bucket/source.parquet");
+    writer.add_metadata("table_version", 7u64);
+    writer.add_metadata("table_version", 8u64);
+    writer.add_metadata("sample_rate", 0.5);
+    writer.add_metadata("key_id", vec![1u8, 2, 3]);
+    writer.add_column_metadata(1, "pii", true).unwrap();
+    assert!(writer.add_column_metadata(2, "pii", true).is_err());
+    writer.finish().unwrap();
+    let file = Arc::new(file);
+
+    let check = |file: Arc<std::fs::File>, column: usize| {
+        let reader = FileReaderV2Builder::new(file).build().unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(
+            metadata.get("lineage").and_then(MetadataValue::as_str),
+            Some("s3://bucket/source.parquet")
+        );
+        assert_eq!(
+            metadata
+                .get("table_version")
+                .and_then(MetadataValue::as_u64),
+            Some(8)
+        );
+        assert_eq!(
+            metadata.get("sample_rate").and_then(MetadataValue::as_f64),
+            Some(0.5)
+        );
+        assert_eq!(
+            metadata.get("key_id").and_then(MetadataValue::as_bytes),
+            Some(&[1u8, 2, 3][..])
+        );
+        assert_eq!(metadata.get("table_version").unwrap().as_i64(), None);
+        assert_eq!(metadata.file_metadata().len(), 4);
+        assert_eq!(
+            metadata
+                .get_column(column, "pii")
+                .and_then(MetadataValue::as_bool),
+            Some(true)
+        );
+        assert_eq!(metadata.column_metadata(1 - column), None);
+    };
+    check(file.clone(), 1);
+
+    // The metadata is kept when rewriting, following the columns.
+    let mut renamed = tempfile::tempfile().unwrap();
+    FileWriter::rename_columns(
+        file.clone(),
+        &mut renamed,
+        &[("a", "id")],
+        FileWriterOptionsBuilder::with_defaults().build(),
+    )
+    .unwrap();
+    check(Arc::new(renamed), 1);
+    let mut dropped = tempfile::tempfile().unwrap();
+    FileWriter::drop_columns(
+        file.clone(),
+        &mut dropped,
+        &["a"],
+        false,
+        FileWriterOptionsBuilder::with_defaults().build(),
+    )
+    .unwrap();
+    check(Arc::new(dropped), 0);
+
+    // Files without metadata have an empty one.
+    let mut plain = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut plain,
+        &[batch],
+        FileWriterOptionsBuilder::with_defaults().build(),
+    );
+    let reader = FileReaderV2Builder::new(Arc::new(plain)).build().unwrap();
+    assert!(reader.metadata().is_empty());
+}
+
+#[test]
+fn test_column_encryption() {
+    let batch = RecordBatch::try_from_iter(vec![
+        (
+            "id",
+            Arc::new(Int64Array::from_iter_values(0..10_000)) as ArrayRef,
+        ),
+        (
+            "social_security_number",
+            Arc::new(StringArray::from_iter_values(
+                (0..10_000).map(|i| format!("secret-{i:06}")),
+            )),
+        ),
+    ])
+    .unwrap();
+    let keys: HashMap<String, Vec<u8>> = [
+        ("pii".to_string(), vec![1; 32]),
+        ("footer".to_string(), vec![2; 16]),
+    ]
+    .into();
+    let options = || {
+        FileWriterOptionsBuilder::with_defaults()
+            .set_encoding_unit_len(1000)
+            .set_iounit_size(16 * 1024)
+            .encrypt_columns([(1, "pii".to_string())].into())
+            .set_key_retriever(Arc::new(keys.clone()))
+    };
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(&mut file, &[batch.clone()], options().build());
+    let mut bytes = vec![];
+    file.rewind().unwrap();
+    std::io::Read::read_to_end(&mut file, &mut bytes).unwrap();
+    assert!(!bytes.windows(7).any(|window| window == b"secret-"));
+    let file = Arc::new(file);
+
+    let check = |output: &[RecordBatch]| {
+        for i in 0..batch.num_columns() {
+            let output = arrow::compute::concat(
+                &output
+                    .iter()
+                    .map(|batch| batch.column(i).as_ref())
+                    .collect::<Vec<_>>(),
+            )
+            .unwrap();
+            array_equal(batch.column(i), &output);
+        }
+    };
+    let output = FileReaderV2Builder::new(file.clone())
+        .with_key_retriever(Box::new(keys.clone()))
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    check(&output);
+    // Rows are read from partially read and decrypted chunks too.
+    let output = FileReaderV2Builder::new(file.clone())
+        .with_key_retriever(Box::new(keys.clone()))
+        .with_selection(Selection::RowIndexes(vec![1, 5000, 9999]))
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    assert_eq!(
+        output[0].column(1).as_string_view().value(1),
+        "secret-005000"
+    );
+
+    // Without the key, the other columns are still readable.
+    let mut reader = FileReaderV2Builder::new(file.clone()).build().unwrap();
+    assert!(reader.read_file().is_err());
+    let output = FileReaderV2Builder::new(file.clone())
+        .with_projections(Projection::new(&[0]))
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    array_equal(batch.column(0), output[0].column(0));
+    // Nor from the chunks cached by a reader with the key.
+    let cache = Arc::new(LruChunkCache::new(1 << 20));
+    let output = FileReaderV2Builder::new(file.clone())
+        .with_key_retriever(Box::new(keys.clone()))
+        .with_chunk_cache(cache.clone(), "data.fff")
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    check(&output);
+    assert!(!cache.is_empty());
+    let mut reader = FileReaderV2Builder::new(file.clone())
+        .with_chunk_cache(cache.clone(), "data.fff")
+        .build()
+        .unwrap();
+    assert!(reader.read_file().is_err());
+    let wrong_keys: HashMap<String, Vec<u8>> = [("pii".to_string(), vec![3; 32])].into();
+    let mut reader = FileReaderV2Builder::new(file.clone())
+        .with_key_retriever(Box::new(wrong_keys))
+        .build()
+        .unwrap();
+    assert!(reader.read_file().is_err());
+
+    // With an encrypted footer, the schema is only readable with the key.
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch.clone()],
+        options().encrypt_footer("footer").build(),
+    );
+    let mut bytes = vec![];
+    file.rewind().unwrap();
+    std::io::Read::read_to_end(&mut file, &mut bytes).unwrap();
+    assert!(!bytes
+        .windows(22)
+        .any(|window| window == b"social_security_number"));
+    let file = Arc::new(file);
+    assert!(FileReaderV2Builder::new(file.clone()).build().is_err());
+    let output = FileReaderV2Builder::new(file.clone())
+        .with_key_retriever(Box::new(keys.clone()))
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    check(&output);
+
+    // Encryption needs a key retriever and the key, and hides the values from bloom filters.
+    let mut file = tempfile::tempfile().unwrap();
+    for options in [
+        FileWriterOptionsBuilder::with_defaults()
+            .encrypt_columns([(1, "pii".to_string())].into())
+            .build(),
+        options().encrypt_footer("missing").build(),
+        options().set_bloom_filter_columns(vec![1]).build(),
+        options()
+            .encrypt_columns([(2, "pii".to_string())].into())
+            .build(),
+    ] {
+        assert!(FileWriter::try_new(batch.schema(), &mut file, options).is_err());
+    }
+}
+
+#[test]
+fn test_envelope_encryption() {
+    let batch = RecordBatch::try_from_iter(vec![
+        (
+            "id",
+            Arc::new(Int64Array::from_iter_values(0..10_000)) as ArrayRef,
+        ),
+        (
+            "social_security_number",
+            Arc::new(StringArray::from_iter_values(
+                (0..10_000).map(|i| format!("secret-{i:06}")),
+            )),
+        ),
+    ])
+    .unwrap();
+    let master_keys: HashMap<String, Vec<u8>> = [("master".to_string(), vec![1; 32])].into();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch.clone()],
         FileWriterOptionsBuilder::with_defaults()
-            .write_built_in_wasm(enable_built_in_wasm)
+            .encrypt_columns([(1, "master".to_string())].into())
+            .encrypt_footer("master")
+            .set_key_provider(Arc::new(master_keys.clone()))
             .build(),
-        Selection::RowIndexes(vec![3]),
     );
+    let mut bytes = vec![];
+    file.rewind().unwrap();
+    std::io::Read::read_to_end(&mut file, &mut bytes).unwrap();
+    assert!(!bytes.windows(7).any(|window| window == b"secret-"));
+    let file = Arc::new(file);
+
+    // Readers only need the master key, not the data keys.
+    let output = FileReaderV2Builder::new(file.clone())
+        .with_key_provider(Arc::new(master_keys.clone()))
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    for i in 0..batch.num_columns() {
+        let output = arrow::compute::concat(
+            &output
+                .iter()
+                .map(|batch| batch.column(i).as_ref())
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+        array_equal(batch.column(i), &output);
+    }
+    assert!(FileReaderV2Builder::new(file.clone()).build().is_err());
+    assert!(FileReaderV2Builder::new(file.clone())
+        .with_key_retriever(Box::new(master_keys))
+        .build()
+        .is_err());
+    let wrong_keys: HashMap<String, Vec<u8>> = [("master".to_string(), vec![2; 32])].into();
+    assert!(FileReaderV2Builder::new(file)
+        .with_key_provider(Arc::new(wrong_keys))
+        .build()
+        .is_err());
 }
 
 #[apply(enable_built_in_wasm)]
@@ -797,4 +4951,779 @@ fn test_compression(#[case] enable_built_in_wasm: bool) {
             .build(),
         Selection::default(),
     );
+
+    test_read_file_roundtrip(
+        &batches,
+        Projection::default(),
+        FileWriterOptionsBuilder::with_defaults()
+            .write_built_in_wasm(enable_built_in_wasm)
+            .set_compression_type(CompressionType::Zstd)
+            .set_compression_level(CompressionLevel::Fixed(3))
+            .build(),
+        Selection::default(),
+    );
+
+    test_read_file_roundtrip(
+        &batches,
+        Projection::default(),
+        FileWriterOptionsBuilder::with_defaults()
+            .write_built_in_wasm(enable_built_in_wasm)
+            .set_compression_type(CompressionType::Zstd)
+            .set_compression_level(CompressionLevel::Auto(CompressionCostModel {
+                min_compress_size: 0,
+                target_throughput_mb_s: 10.0,
+                ..Default::default()
+            }))
+            .build(),
+        Selection::default(),
+    );
+}
+
+#[cfg(any(feature = "snappy", feature = "brotli"))]
+#[apply(enable_built_in_wasm)]
+fn test_parquet_compression(#[case] enable_built_in_wasm: bool) {
+    use fff_format::File::fff::flatbuf::CompressionType;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int64, false),
+        Field::new("b", DataType::Utf8, true),
+    ]));
+    let batches = (0..4)
+        .map(|batch_idx| {
+            let rows = batch_idx * 10_000..(batch_idx + 1) * 10_000;
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from_iter_values(rows.clone().map(|i| i % 16))),
+                    Arc::new(StringArray::from_iter(
+                        rows.map(|i| (i % 5 != 0).then(|| format!("value{}", i % 100))),
+                    )),
+                ],
+            )
+            .unwrap()
+        })
+        .collect::<Vec<_>>();
+    let compression_types = [
+        #[cfg(feature = "snappy")]
+        CompressionType::Snappy,
+        #[cfg(feature = "brotli")]
+        CompressionType::Brotli,
+    ];
+    for compression_type in compression_types {
+        test_read_file_roundtrip(
+            &batches,
+            Projection::default(),
+            FileWriterOptionsBuilder::with_defaults()
+                .write_built_in_wasm(enable_built_in_wasm)
+                .set_compression_type(compression_type)
+                .set_compression_level(CompressionLevel::Fixed(1))
+                .build(),
+            Selection::default(),
+        );
+    }
+}
+
+#[test]
+fn test_zstd_dictionary() {
+    use fff_format::File::fff::flatbuf::CompressionType;
+
+    // Small EncUnits of similar strings, which compress poorly alone.
+    let batch = RecordBatch::try_from_iter(vec![(
+        "url",
+        Arc::new(StringArray::from_iter_values((0..20_000).map(|i| {
+            format!(
+                "https://example.com/products/category-{}/item?id={i}",
+                i % 7
+            )
+        }))) as ArrayRef,
+    )])
+    .unwrap();
+    let options = || {
+        FileWriterOptionsBuilder::with_defaults()
+            .set_encoding_unit_len(64)
+            .set_compression_type(CompressionType::Zstd)
+    };
+    let mut plain = tempfile::tempfile().unwrap();
+    write_batches(&mut plain, &[batch.clone()], options().build());
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch.clone()],
+        options().train_zstd_dictionary(4096).build(),
+    );
+    let summary = fff_poc::inspect::inspect_file(&file).unwrap();
+    assert!(summary.row_groups[0].columns[0]
+        .iter()
+        .any(|chunk| chunk.compression_dictionary_size.is_some()));
+    assert!(file.metadata().unwrap().len() < plain.metadata().unwrap().len());
+
+    let file = Arc::new(file);
+    let output = FileReaderV2Builder::new(file.clone())
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    let output = arrow::compute::concat(
+        &output
+            .iter()
+            .map(|batch| batch.column(0).as_ref())
+            .collect::<Vec<_>>(),
+    )
+    .unwrap();
+    array_equal(batch.column(0), &output);
+    let output = FileReaderV2Builder::new(file.clone())
+        .with_selection(Selection::RowIndexes(vec![3, 10_000, 19_999]))
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    assert_eq!(
+        output[0].column(0).as_string_view().value(1),
+        "https://example.com/products/category-4/item?id=10000"
+    );
+
+    // Dictionaries are only trained for Zstd.
+    let options = FileWriterOptionsBuilder::with_defaults()
+        .set_compression_type(CompressionType::Lz4)
+        .train_zstd_dictionary(4096)
+        .build();
+    assert!(FileWriter::try_new(batch.schema(), &mut plain, options).is_err());
+}
+
+#[apply(enable_built_in_wasm)]
+fn test_stream_and_chunked_output(#[case] enable_built_in_wasm: bool) {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, false),
+        Field::new("b", DataType::Utf8, true),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from_iter_values(0..1000)),
+            Arc::new(StringArray::from_iter(
+                (0..1000).map(|x| (x % 5 != 0).then(|| format!("s{x}"))),
+            )),
+        ],
+    )
+    .unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch.clone()],
+        FileWriterOptionsBuilder::with_defaults()
+            .set_row_group_size(300)
+            .write_built_in_wasm(enable_built_in_wasm)
+            .build(),
+    );
+    let file = Arc::new(file);
+
+    let columns = FileReaderV2Builder::new(file.clone())
+        .build()
+        .unwrap()
+        .read_file_chunked()
+        .unwrap();
+    assert_eq!(columns.len(), 2);
+    assert!(columns[0].len() >= 4);
+    for (column, expected) in columns.iter().zip(batch.columns()) {
+        let refs = column.iter().map(|a| a.as_ref()).collect::<Vec<_>>();
+        array_equal(expected, &arrow::compute::concat(&refs).unwrap());
+    }
+
+    // Row groups are decoded one at a time, and batches are cast to the projected schema.
+    let stream = FileReaderV2Builder::new(file.clone())
+        .with_projections(Projection::LeafColumnIndexes(vec![1]))
+        .build()
+        .unwrap()
+        .into_stream();
+    assert_eq!(stream.schema().field(0).name(), "b");
+    let batches = stream.collect::<Result<Vec<_>, _>>().unwrap();
+    assert!(batches.iter().all(|b| b.num_rows() <= 300));
+    assert_eq!(
+        concat_batches(&batches[0].schema(), &batches).unwrap(),
+        batch.project(&[1]).unwrap()
+    );
+
+    // Through the Arrow C Stream Interface.
+    let stream = FileReaderV2Builder::new(file.clone())
+        .build()
+        .unwrap()
+        .into_stream()
+        .into_ffi();
+    let reader = arrow::ffi_stream::ArrowArrayStreamReader::try_new(stream).unwrap();
+    assert_eq!(reader.schema(), schema);
+    let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(concat_batches(&schema, &batches).unwrap(), batch);
+}
+
+#[test]
+fn test_bloom_filter() {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int64, false),
+        Field::new("id", DataType::Utf8, true),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int64Array::from_iter_values(0..4000)),
+            Arc::new(StringArray::from_iter(
+                (0..4000).map(|x| (x % 9 != 0).then(|| format!("id{x:05}"))),
+            )),
+        ],
+    )
+    .unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch.clone()],
+        FileWriterOptionsBuilder::with_defaults()
+            .set_row_group_size(2000)
+            .set_encoding_unit_len(250)
+            .set_iounit_size(4 * 1024)
+            .set_dictionary_type(DictionaryTypeOptions::NoDictionary)
+            .set_bloom_filter_columns(vec![1])
+            .build(),
+    );
+    let file = Arc::new(file);
+    let bloom_filters = get_bloom_filters(&file).unwrap();
+    assert!(bloom_filters.len() > 2, "{} filters", bloom_filters.len());
+    assert!(bloom_filters.iter().all(|f| f.column_index == 1));
+
+    let read = |row_filter: RowFilter| {
+        let reader = CountingReader::new(file.clone());
+        let batches = FileReaderV2Builder::new(reader.clone())
+            .with_row_filter(row_filter)
+            .build()
+            .unwrap()
+            .read_file()
+            .unwrap();
+        (batches, reader.metrics().bytes_read)
+    };
+    let value = Arc::new(StringArray::from(vec!["id01234"])) as ArrayRef;
+    let (batches, bloom_bytes_read) = read(RowFilter::eq(1, value.clone()).unwrap());
+    let output = concat_batches(&batches[0].schema(), &batches).unwrap();
+    assert_eq!(output.num_rows(), 1);
+    assert_eq!(
+        output
+            .column(0)
+            .as_primitive::<arrow::datatypes::Int64Type>()
+            .value(0),
+        1234
+    );
+
+    // The same filter without its value cannot skip chunks.
+    let (_, bytes_read) = read(RowFilter::new(1, move |array| {
+        let value = arrow::compute::cast(&value, array.data_type())?;
+        Ok(arrow::compute::kernels::cmp::eq(
+            array,
+            &arrow_array::Scalar::new(value),
+        )?)
+    }));
+    assert!(
+        bloom_bytes_read < bytes_read,
+        "{bloom_bytes_read} >= {bytes_read}"
+    );
+
+    let absent = Arc::new(StringArray::from(vec!["id99999"])) as ArrayRef;
+    let (batches, _) = read(RowFilter::eq(1, absent).unwrap());
+    assert!(batches.iter().all(|b| b.num_rows() == 0));
+
+    // Nested columns cannot have a bloom filter.
+    let nested = Arc::new(Schema::new(vec![Field::new(
+        "l",
+        DataType::List(Arc::new(Field::new_list_field(DataType::Int32, true))),
+        true,
+    )]));
+    assert!(FileWriter::try_new(
+        nested,
+        tempfile::tempfile().unwrap(),
+        FileWriterOptionsBuilder::with_defaults()
+            .set_bloom_filter_columns(vec![0])
+            .build(),
+    )
+    .is_err());
+}
+
+#[test]
+fn test_bloom_filter_after_append() {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int64, false),
+        Field::new("id", DataType::Utf8, true),
+    ]));
+    let batch = |start: i64| {
+        RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from_iter_values(start..start + 2000)),
+                Arc::new(StringArray::from_iter_values(
+                    (start..start + 2000).map(|x| format!("id{x:05}")),
+                )),
+            ],
+        )
+        .unwrap()
+    };
+    let options = || {
+        FileWriterOptionsBuilder::with_defaults()
+            .set_encoding_unit_len(250)
+            .set_iounit_size(4 * 1024)
+            .set_dictionary_type(DictionaryTypeOptions::NoDictionary)
+            .set_bloom_filter_columns(vec![1])
+            .build()
+    };
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(&mut file, &[batch(0)], options());
+    let num_filters = get_bloom_filters(&Arc::new(file.try_clone().unwrap()))
+        .unwrap()
+        .len();
+    let reader = Arc::new(file.try_clone().unwrap());
+    let mut writer = FileWriter::try_append(schema.clone(), reader, &mut file, options()).unwrap();
+    writer.write_batch(&batch(2000)).unwrap();
+    writer.finish().unwrap();
+
+    let file = Arc::new(file);
+    let bloom_filters = get_bloom_filters(&file).unwrap();
+    assert!(bloom_filters.len() > num_filters);
+    assert!(bloom_filters.iter().all(|f| f.column_index == 1));
+    let read = |row_filter: RowFilter| {
+        let reader = CountingReader::new(file.clone());
+        let batches = FileReaderV2Builder::new(reader.clone())
+            .with_row_filter(row_filter)
+            .build()
+            .unwrap()
+            .read_file()
+            .unwrap();
+        (batches, reader.metrics().bytes_read)
+    };
+    // Chunks of the existing row group keep pruning, as do the appended ones.
+    for (value, row) in [("id01234", 1234), ("id03456", 3456)] {
+        let value = Arc::new(StringArray::from(vec![value])) as ArrayRef;
+        let (batches, bloom_bytes_read) = read(RowFilter::eq(1, value.clone()).unwrap());
+        let output = concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(output.num_rows(), 1);
+        assert_eq!(
+            output
+                .column(0)
+                .as_primitive::<arrow::datatypes::Int64Type>()
+                .value(0),
+            row
+        );
+        let (_, bytes_read) = read(RowFilter::new(1, move |array| {
+            let value = arrow::compute::cast(&value, array.data_type())?;
+            Ok(arrow::compute::kernels::cmp::eq(
+                array,
+                &arrow_array::Scalar::new(value),
+            )?)
+        }));
+        assert!(
+            bloom_bytes_read < bytes_read,
+            "{bloom_bytes_read} >= {bytes_read}"
+        );
+    }
+}
+
+#[apply(enable_built_in_wasm)]
+fn test_row_filter(#[case] enable_built_in_wasm: bool) {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, true),
+        Field::new("c", DataType::Int64, false),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from_iter(
+                (0..1000).map(|x| (x % 7 != 0).then_some(x)),
+            )),
+            Arc::new(StringArray::from_iter(
+                (0..1000).map(|x| (x % 5 != 0).then(|| format!("s{x}"))),
+            )),
+            Arc::new(Int64Array::from_iter_values(0..1000)),
+        ],
+    )
+    .unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(
+        &mut file,
+        &[batch.clone()],
+        FileWriterOptionsBuilder::with_defaults()
+            .set_row_group_size(300)
+            .write_built_in_wasm(enable_built_in_wasm)
+            .build(),
+    );
+    let file = Arc::new(file);
+    // Rows 350..400 with a non-null a. Nulls are filtered out.
+    let row_filter = RowFilter::new(0, |array| {
+        let a = array.as_primitive::<arrow::datatypes::Int32Type>();
+        Ok(BooleanArray::from_iter(
+            a.iter().map(|x| x.map(|x| (350..400).contains(&x))),
+        ))
+    });
+    let mask =
+        BooleanArray::from_iter((0..1000).map(|x| Some(x % 7 != 0 && (350..400).contains(&x))));
+    let expected = arrow::compute::filter_record_batch(&batch, &mask).unwrap();
+
+    let check = |projection: Projection, selection: Selection, expected: &RecordBatch| {
+        let batches = FileReaderV2Builder::new(file.clone())
+            .with_projections(projection)
+            .with_selection(selection)
+            .with_row_filter(row_filter.clone())
+            .build()
+            .unwrap()
+            .read_file()
+            .unwrap();
+        let output = concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(output.num_columns(), expected.num_columns());
+        for (field, expected_field) in output
+            .schema()
+            .fields()
+            .iter()
+            .zip(expected.schema().fields())
+        {
+            assert_eq!(field.name(), expected_field.name());
+        }
+        for (column, expected_column) in output.columns().iter().zip(expected.columns()) {
+            array_equal(expected_column, column);
+        }
+    };
+    check(Projection::All, Selection::All, &expected);
+    // The filter column does not need to be projected.
+    check(
+        Projection::LeafColumnIndexes(vec![2, 1]),
+        Selection::All,
+        &expected.project(&[2, 1]).unwrap(),
+    );
+    // Filters apply to the selected rows.
+    let row_indexes: Vec<u64> = (0..1000).filter(|x| x % 2 == 0).collect();
+    let expected_selected =
+        take_record_batch(&batch, &UInt64Array::from(row_indexes.clone())).unwrap();
+    let mask = BooleanArray::from_iter(
+        row_indexes
+            .iter()
+            .map(|&x| Some(x % 7 != 0 && (350..400).contains(&x))),
+    );
+    check(
+        Projection::LeafColumnIndexes(vec![2]),
+        Selection::new(&row_indexes),
+        &arrow::compute::filter_record_batch(&expected_selected, &mask)
+            .unwrap()
+            .project(&[2])
+            .unwrap(),
+    );
+}
+
+#[test]
+fn test_prune_row_groups() {
+    use fff_poc::options::SortDirection;
+
+    // Each row group of 250 rows holds a distinct range of keys, sorted by the writer.
+    let batches: Vec<_> = (0..4)
+        .map(|i| {
+            RecordBatch::try_from_iter(vec![
+                (
+                    "a",
+                    Arc::new(Int32Array::from_iter_values(
+                        (0..250).map(|x| 1000 - 250 * i - x - 1),
+                    )) as ArrayRef,
+                ),
+                (
+                    "b",
+                    Arc::new(StringArray::from_iter_values(
+                        (0..250).map(|x| format!("s{x}")),
+                    )),
+                ),
+            ])
+            .unwrap()
+        })
+        .collect();
+    let write = |sort: bool| {
+        let mut file = tempfile::tempfile().unwrap();
+        let options = FileWriterOptionsBuilder::with_defaults()
+            .set_row_group_size(250)
+            .set_write_encunit_index(true);
+        let options = if sort {
+            options.sort_by(vec![("a", SortDirection::Ascending)])
+        } else {
+            options
+        };
+        write_batches(&mut file, &batches, options.build());
+        Arc::new(file)
+    };
+    let file = write(true);
+    let value = || Arc::new(Int32Array::from(vec![300])) as ArrayRef;
+    let reader = FileReaderV2Builder::new(file.clone()).build().unwrap();
+    let lt = RowFilter::compare(0, ComparisonOp::Lt, value()).unwrap();
+    assert_eq!(reader.prune_row_groups(&lt).unwrap(), vec![2, 3]);
+    let eq = RowFilter::eq(0, value()).unwrap();
+    assert_eq!(reader.prune_row_groups(&eq).unwrap(), vec![2]);
+    let other = RowFilter::new(0, |array| Ok(BooleanArray::from(vec![true; array.len()])));
+    assert_eq!(reader.prune_row_groups(&other).unwrap(), vec![0, 1, 2, 3]);
+
+    // Applied automatically to a row filter on a sort key.
+    let read = |file: Arc<std::fs::File>, selection: Selection| {
+        let mut reader = FileReaderV2Builder::new(file)
+            .with_selection(selection)
+            .with_row_filter(RowFilter::compare(0, ComparisonOp::GtEq, value()).unwrap())
+            .build()
+            .unwrap();
+        let batches = reader.read_file().unwrap();
+        let output = concat_batches(&batches[0].schema(), &batches).unwrap();
+        (output, reader.scan_metrics().unwrap())
+    };
+    let (output, metrics) = read(file.clone(), Selection::All);
+    assert_eq!(output.num_rows(), 700);
+    assert!(output
+        .column(0)
+        .as_primitive::<arrow::datatypes::Int32Type>()
+        .values()
+        .iter()
+        .all(|&a| a >= 300));
+    assert_eq!(
+        (metrics.row_groups_scanned, metrics.row_groups_pruned),
+        (3, 1)
+    );
+
+    // Selected rows of pruned row groups are dropped, the others keep their position.
+    let row_indexes: Vec<u64> = (0..1000).step_by(3).collect();
+    let (output, metrics) = read(file.clone(), Selection::new(&row_indexes));
+    let expected = row_indexes
+        .iter()
+        .map(|&row| 1000 - 250 * (row as i32 / 250) - 250 + row as i32 % 250)
+        .filter(|&a| a >= 300)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        output
+            .column(0)
+            .as_primitive::<arrow::datatypes::Int32Type>()
+            .values()
+            .to_vec(),
+        expected
+    );
+    assert_eq!(
+        (metrics.row_groups_scanned, metrics.row_groups_pruned),
+        (3, 1)
+    );
+
+    // Without a sort order, row groups are only pruned on request.
+    let (output, metrics) = read(write(false), Selection::All);
+    assert_eq!(output.num_rows(), 700);
+    assert_eq!(
+        (metrics.row_groups_scanned, metrics.row_groups_pruned),
+        (4, 0)
+    );
+}
+
+#[test]
+fn test_backfill_statistics() {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, true),
+    ]));
+    let batches: Vec<_> = (0..4)
+        .map(|i| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter(
+                        (i * 5_000..(i + 1) * 5_000).map(|x| (x % 7 != 0).then_some(x)),
+                    )),
+                    Arc::new(StringArray::from_iter(
+                        (i * 5_000..(i + 1) * 5_000).map(|x| (x % 9 != 0).then(|| format!("v{x}"))),
+                    )),
+                ],
+            )
+            .unwrap()
+        })
+        .collect();
+    let options = || {
+        FileWriterOptionsBuilder::with_defaults()
+            .set_row_group_size(10_000)
+            .set_footer_padding(64 * 1024)
+    };
+    let backfill_options = || {
+        options()
+            .set_write_encunit_index(true)
+            .set_bloom_filter_columns(vec![1])
+            .build()
+    };
+    let mut input = tempfile::tempfile().unwrap();
+    write_batches(&mut input, &batches, options().build());
+    let input = Arc::new(input);
+    assert!(get_bloom_filters(&input).unwrap().is_empty());
+    assert!(get_encunit_index(&input).unwrap().is_none());
+
+    let check = |file: Arc<std::fs::File>| {
+        FileReaderV2Builder::new(file.clone())
+            .with_verify_file_checksum(true)
+            .build()
+            .unwrap();
+        test_read(
+            file.clone(),
+            &batches,
+            Projection::default(),
+            Selection::default(),
+        );
+        assert_eq!(
+            get_column_statistics(file.clone(), 1).unwrap(),
+            get_column_statistics(input.clone(), 1).unwrap()
+        );
+        let num_chunks = get_column_statistics(file.clone(), 1).unwrap().len();
+        let bloom_filters = get_bloom_filters(&file).unwrap();
+        assert_eq!(bloom_filters.len(), num_chunks);
+        assert!(bloom_filters.iter().all(|f| f.column_index == 1));
+        let index = get_encunit_index(&file).unwrap().unwrap();
+        assert!(index
+            .chunks()
+            .iter()
+            .filter(|chunk| chunk.column_index == 0)
+            .flat_map(|chunk| &chunk.encunits)
+            .all(|encunit| encunit.statistics.is_some()));
+
+        let value = Arc::new(StringArray::from(vec!["v12345"])) as ArrayRef;
+        let batches = FileReaderV2Builder::new(file)
+            .with_row_filter(RowFilter::eq(1, value).unwrap())
+            .build()
+            .unwrap()
+            .read_file()
+            .unwrap();
+        let output = concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(output.num_rows(), 1);
+    };
+
+    let mut output = tempfile::tempfile().unwrap();
+    FileWriter::backfill_statistics(input.clone(), &mut output, false, backfill_options()).unwrap();
+    check(Arc::new(output));
+
+    // In place, the new metadata takes the place of the reserved padding.
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(&mut file, &batches, options().build());
+    let file_size = file.metadata().unwrap().len();
+    let reader = Arc::new(file.try_clone().unwrap());
+    FileWriter::backfill_statistics(reader, &mut file, true, backfill_options()).unwrap();
+    assert_eq!(file.metadata().unwrap().len(), file_size);
+    check(Arc::new(file));
+}
+
+#[test]
+fn test_encoding_parallelism() {
+    let columns: Vec<(String, ArrayRef)> = (0..16)
+        .map(|i| {
+            let array: ArrayRef = if i % 2 == 0 {
+                Arc::new(Int64Array::from_iter_values((0..20_000).map(|x| x * i)))
+            } else {
+                Arc::new(StringArray::from_iter_values(
+                    (0..20_000).map(|x| format!("c{i}-{}", x % 97)),
+                ))
+            };
+            (format!("c{i}"), array)
+        })
+        .collect();
+    let batch = RecordBatch::try_from_iter(columns).unwrap();
+    let batches: Vec<_> = (0..4).map(|i| batch.slice(i * 5_000, 5_000)).collect();
+    let write = |dictionary_type: DictionaryTypeOptions, encoding_parallelism: usize| {
+        let mut file = tempfile::tempfile().unwrap();
+        write_batches(
+            &mut file,
+            &batches,
+            FileWriterOptionsBuilder::with_defaults()
+                .set_row_group_size(10_000)
+                .set_dictionary_type(dictionary_type)
+                .set_encoding_parallelism(encoding_parallelism)
+                .build(),
+        );
+        file.rewind().unwrap();
+        let mut bytes = vec![];
+        std::io::Read::read_to_end(&mut file, &mut bytes).unwrap();
+        (Arc::new(file), bytes)
+    };
+
+    // Chunks are flushed in column order whatever the number of threads.
+    for dictionary_type in [
+        DictionaryTypeOptions::EncoderDictionary,
+        DictionaryTypeOptions::GlobalDictionary,
+    ] {
+        let (_, sequential) = write(dictionary_type, 1);
+        let (file, parallel) = write(dictionary_type, 4);
+        assert!(sequential == parallel);
+        test_read(file, &batches, Projection::default(), Selection::default());
+    }
+
+    let mut file = tempfile::tempfile().unwrap();
+    assert!(FileWriter::try_new(
+        batch.schema(),
+        &mut file,
+        FileWriterOptionsBuilder::with_defaults()
+            .set_encoding_parallelism(0)
+            .build(),
+    )
+    .is_err());
+}
+
+#[tokio::test]
+async fn test_async_writer() {
+    use fff_poc::writer::AsyncFileWriter;
+    use object_store::{memory::InMemory, path::Path as ObjectPath};
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("a", DataType::Int64, false),
+        Field::new("b", DataType::Utf8, true),
+    ]));
+    let batches: Vec<_> = (0..8)
+        .map(|i| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from_iter_values(i * 10_000..(i + 1) * 10_000)),
+                    Arc::new(StringArray::from_iter(
+                        (i * 10_000..(i + 1) * 10_000).map(|x| (x % 5 != 0).then(|| x.to_string())),
+                    )),
+                ],
+            )
+            .unwrap()
+        })
+        .collect();
+    let options = || {
+        FileWriterOptionsBuilder::with_defaults()
+            .set_row_group_size(20_000)
+            .build()
+    };
+    let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+    let location = ObjectPath::from("async.fff");
+    let mut writer = AsyncFileWriter::try_new_with_part_size(
+        schema.clone(),
+        store.clone(),
+        &location,
+        options(),
+        64 * 1024,
+    )
+    .await
+    .unwrap();
+    for batch in &batches {
+        writer.write_batch(batch).await.unwrap();
+    }
+    // Not visible before the upload completes.
+    assert!(store.head(&location).await.is_err());
+    writer.finish().await.unwrap();
+
+    // The uploaded file is the one written locally.
+    let uploaded = store.get(&location).await.unwrap().bytes().await.unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    write_batches(&mut file, &batches, options());
+    file.rewind().unwrap();
+    let mut local = vec![];
+    std::io::Read::read_to_end(&mut file, &mut local).unwrap();
+    assert!(uploaded.as_ref() == local.as_slice());
+    test_read(
+        Arc::new(file),
+        &batches,
+        Projection::default(),
+        Selection::default(),
+    );
+
+    // Aborted uploads leave no object.
+    let location = ObjectPath::from("aborted.fff");
+    let mut writer = AsyncFileWriter::try_new(schema, store.clone(), &location, options())
+        .await
+        .unwrap();
+    writer.write_batch(&batches[0]).await.unwrap();
+    writer.abort().await.unwrap();
+    assert!(store.head(&location).await.is_err());
 }
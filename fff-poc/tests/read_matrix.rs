@@ -0,0 +1,264 @@
+//! Read path integration tests over the combinations of encoding, compression, data type,
+//! nullability, selection and projection, on both the native and the Wasm decoding paths.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::{Int32Builder, ListBuilder},
+    compute::{cast, concat_batches, take_record_batch},
+};
+use arrow_array::{
+    ArrayRef, BooleanArray, Float64Array, Int32Array, Int64Array, RecordBatch, StringArray,
+    TimestampNanosecondArray, UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use fff_format::File::fff::flatbuf::CompressionType;
+use fff_poc::{
+    options::{AdaptiveEncodingOptions, DictionaryTypeOptions, FileWriterOptionsBuilder},
+    reader::{FileReaderV2Builder, Projection, Selection},
+    writer::FileWriter,
+};
+use rstest::rstest;
+
+const NUM_BATCHES: usize = 3;
+const BATCH_SIZE: usize = 2000;
+const ROW_GROUP_SIZE: u64 = 4000;
+const ENCODING_UNIT_LEN: u64 = 1000;
+
+#[derive(Debug, Clone, Copy)]
+enum Encoding {
+    Vortex,
+    Adaptive,
+    EncoderDictionary,
+    LocalDictionary,
+    GlobalDictionary,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ColumnType {
+    Int32,
+    Int64,
+    Float64,
+    Utf8,
+    Boolean,
+    Timestamp,
+    ListInt32,
+}
+
+impl ColumnType {
+    fn data_type(&self) -> DataType {
+        match self {
+            ColumnType::Int32 => DataType::Int32,
+            ColumnType::Int64 => DataType::Int64,
+            ColumnType::Float64 => DataType::Float64,
+            ColumnType::Utf8 => DataType::Utf8,
+            ColumnType::Boolean => DataType::Boolean,
+            ColumnType::Timestamp => DataType::Timestamp(TimeUnit::Nanosecond, None),
+            ColumnType::ListInt32 => {
+                DataType::List(Arc::new(Field::new_list_field(DataType::Int32, true)))
+            }
+        }
+    }
+
+    /// Low cardinality values, so that dictionaries are worth it, with every 7th row null if
+    /// `nullable`.
+    fn array(&self, rows: std::ops::Range<usize>, nullable: bool) -> ArrayRef {
+        let valid = |x: usize| !nullable || x % 7 != 3;
+        let values = rows.clone().map(move |x| valid(x).then_some(x % 97));
+        match self {
+            ColumnType::Int32 => {
+                Arc::new(Int32Array::from_iter(values.map(|v| v.map(|v| v as i32))))
+            }
+            ColumnType::Int64 => Arc::new(Int64Array::from_iter(
+                values.map(|v| v.map(|v| v as i64 * 1_000_000_007)),
+            )),
+            ColumnType::Float64 => Arc::new(Float64Array::from_iter(
+                values.map(|v| v.map(|v| v as f64 / 4.0)),
+            )),
+            ColumnType::Utf8 => Arc::new(StringArray::from_iter(
+                values.map(|v| v.map(|v| format!("value{}", v))),
+            )),
+            ColumnType::Boolean => Arc::new(BooleanArray::from_iter(
+                values.map(|v| v.map(|v| v % 3 == 0)),
+            )),
+            ColumnType::Timestamp => {
+                Arc::new(TimestampNanosecondArray::from_iter(rows.map(|x| {
+                    valid(x).then_some(1_700_000_000_000_000_000 + x as i64 * 1_000)
+                })))
+            }
+            ColumnType::ListInt32 => {
+                let mut builder = ListBuilder::new(Int32Builder::new());
+                for (x, v) in rows.zip(values) {
+                    match v {
+                        Some(v) => {
+                            for i in 0..x % 4 {
+                                builder.values().append_value((v + i) as i32);
+                            }
+                            builder.append(true);
+                        }
+                        None => builder.append(false),
+                    }
+                }
+                Arc::new(builder.finish())
+            }
+        }
+    }
+
+    fn is_nested(&self) -> bool {
+        matches!(self, ColumnType::ListInt32)
+    }
+}
+
+/// Batches of an `id` column followed by the tested column.
+fn make_batches(column_type: ColumnType, nullable: bool) -> Vec<RecordBatch> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("col", column_type.data_type(), nullable),
+    ]));
+    (0..NUM_BATCHES)
+        .map(|i| {
+            let rows = i * BATCH_SIZE..(i + 1) * BATCH_SIZE;
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from_iter_values(rows.clone().map(|x| x as i64))),
+                    column_type.array(rows, nullable),
+                ],
+            )
+            .unwrap()
+        })
+        .collect()
+}
+
+fn read_and_compare(
+    file: Arc<std::fs::File>,
+    input_batches: &[RecordBatch],
+    projection: Projection,
+    selection: Selection,
+) {
+    let output_batches = FileReaderV2Builder::new(file)
+        .with_projections(projection.clone())
+        .with_selection(selection.clone())
+        .with_verify_io_unit_checksum(true)
+        .with_verify_decoded_length(true)
+        .build()
+        .unwrap()
+        .read_file()
+        .unwrap();
+    let mut expected = concat_batches(input_batches[0].schema_ref(), input_batches).unwrap();
+    if let Projection::LeafColumnIndexes(indexes) = &projection {
+        expected = expected.project(indexes).unwrap();
+    }
+    if let Selection::RowIndexes(indexes) = &selection {
+        expected = take_record_batch(&expected, &UInt64Array::from(indexes.clone())).unwrap();
+    }
+    let num_rows: usize = output_batches.iter().map(|b| b.num_rows()).sum();
+    assert_eq!(
+        num_rows,
+        expected.num_rows(),
+        "{projection:?} {selection:?}"
+    );
+    for (col_idx, expected_col) in expected.columns().iter().enumerate() {
+        // The reader may output view types, so compare after casting to the input type.
+        let output = output_batches
+            .iter()
+            .map(|b| cast(b.column(col_idx), expected_col.data_type()).unwrap())
+            .collect::<Vec<_>>();
+        let output =
+            arrow::compute::concat(&output.iter().map(|a| a.as_ref()).collect::<Vec<_>>()).unwrap();
+        assert_eq!(&output, expected_col, "{projection:?} {selection:?}");
+    }
+}
+
+#[rstest]
+fn test_read_matrix(
+    #[values(
+        Encoding::Vortex,
+        Encoding::Adaptive,
+        Encoding::EncoderDictionary,
+        Encoding::LocalDictionary,
+        Encoding::GlobalDictionary
+    )]
+    encoding: Encoding,
+    #[values(
+        CompressionType::Uncompressed,
+        CompressionType::Zstd,
+        CompressionType::Lz4
+    )]
+    compression_type: CompressionType,
+    #[values(
+        ColumnType::Int32,
+        ColumnType::Int64,
+        ColumnType::Float64,
+        ColumnType::Utf8,
+        ColumnType::Boolean,
+        ColumnType::Timestamp,
+        ColumnType::ListInt32
+    )]
+    column_type: ColumnType,
+    #[values(false, true)] nullable: bool,
+    #[values(false, true)] built_in_wasm: bool,
+) {
+    let (dictionary_type, adaptive_encoding) = match encoding {
+        Encoding::Vortex => (DictionaryTypeOptions::NoDictionary, None),
+        Encoding::Adaptive => (
+            DictionaryTypeOptions::NoDictionary,
+            Some(AdaptiveEncodingOptions::default()),
+        ),
+        Encoding::EncoderDictionary => (DictionaryTypeOptions::EncoderDictionary, None),
+        Encoding::LocalDictionary => (DictionaryTypeOptions::LocalDictionary, None),
+        Encoding::GlobalDictionary => (DictionaryTypeOptions::GlobalDictionary, None),
+    };
+    let options = FileWriterOptionsBuilder::with_defaults()
+        .set_dictionary_type(dictionary_type)
+        .set_adaptive_encoding(adaptive_encoding)
+        .set_compression_type(compression_type)
+        .set_row_group_size(ROW_GROUP_SIZE)
+        .set_encoding_unit_len(ENCODING_UNIT_LEN)
+        .enable_io_unit_checksum(true)
+        .write_built_in_wasm(built_in_wasm)
+        .build();
+    let batches = make_batches(column_type, nullable);
+    let mut file = tempfile::tempfile().unwrap();
+    let mut writer = FileWriter::try_new(batches[0].schema(), &mut file, options).unwrap();
+    for batch in &batches {
+        writer.write_batch(batch).unwrap();
+    }
+    writer.finish().unwrap();
+    let file = Arc::new(file);
+
+    let last_row = (NUM_BATCHES * BATCH_SIZE) as u64 - 1;
+    let selections = [
+        Selection::All,
+        // Crosses EncUnit and row group boundaries.
+        Selection::RowIndexes(vec![
+            0,
+            999,
+            1000,
+            ROW_GROUP_SIZE - 1,
+            ROW_GROUP_SIZE,
+            last_row,
+        ]),
+        Selection::RowIndexes(vec![last_row, 3, ROW_GROUP_SIZE + 1, 3, 0]),
+    ];
+    // Projection is only supported on flat columns, so nested columns are skipped by projecting
+    // the id column alone.
+    let projections = [
+        Projection::All,
+        Projection::LeafColumnIndexes(if column_type.is_nested() {
+            vec![0]
+        } else {
+            vec![1]
+        }),
+    ];
+    for projection in &projections {
+        for selection in &selections {
+            read_and_compare(
+                file.clone(),
+                &batches,
+                projection.clone(),
+                selection.clone(),
+            );
+        }
+    }
+}
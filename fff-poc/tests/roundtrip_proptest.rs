@@ -0,0 +1,364 @@
+//! Property tests of the write -> read round trip over random schemas, arrays and writer options.
+//!
+//! `test_roundtrip` runs a CI-sized number of cases with every `cargo test`. The extended run is
+//! ignored by default, run it with
+//! `cargo test -p fff-poc --test roundtrip_proptest -- --ignored`.
+
+use std::sync::Arc;
+
+use arrow::compute::{cast, concat, concat_batches};
+use arrow_array::{
+    ArrayRef, BinaryArray, BooleanArray, Date32Array, Float32Array, Float64Array, Int16Array,
+    Int32Array, Int64Array, Int8Array, LargeStringArray, ListArray, RecordBatch, StringArray,
+    StructArray, TimestampMicrosecondArray, TimestampMillisecondArray, TimestampNanosecondArray,
+    TimestampSecondArray, UInt16Array, UInt32Array, UInt64Array, UInt8Array,
+};
+use arrow_buffer::{NullBuffer, OffsetBuffer};
+use arrow_schema::{DataType, Field, Fields, Schema, SchemaRef, TimeUnit};
+use fff_format::File::fff::flatbuf::CompressionType;
+use fff_poc::{
+    options::{
+        AdaptiveEncodingOptions, DictionaryTypeOptions, FileWriterOptions, FileWriterOptionsBuilder,
+    },
+    reader::FileReaderV2Builder,
+    writer::FileWriter,
+};
+use proptest::prelude::*;
+
+/// Maximum nesting of lists and structs.
+const MAX_DEPTH: u32 = 3;
+const MAX_COLUMNS: usize = 4;
+const MAX_BATCHES: usize = 3;
+
+/// Probability of a value of a nullable field to be valid.
+const VALID_PROBABILITY: f64 = 0.8;
+
+fn arb_primitive_type() -> impl Strategy<Value = DataType> {
+    prop_oneof![
+        Just(DataType::Boolean),
+        Just(DataType::Int8),
+        Just(DataType::Int16),
+        Just(DataType::Int32),
+        Just(DataType::Int64),
+        Just(DataType::UInt8),
+        Just(DataType::UInt16),
+        Just(DataType::UInt32),
+        Just(DataType::UInt64),
+        Just(DataType::Float32),
+        Just(DataType::Float64),
+        Just(DataType::Date32),
+        Just(DataType::Timestamp(TimeUnit::Second, None)),
+        Just(DataType::Timestamp(TimeUnit::Millisecond, None)),
+        Just(DataType::Timestamp(TimeUnit::Microsecond, None)),
+        Just(DataType::Timestamp(TimeUnit::Nanosecond, None)),
+        Just(DataType::Utf8),
+        Just(DataType::LargeUtf8),
+        Just(DataType::Binary),
+    ]
+}
+
+/// Primitive types, and lists and structs of them nested up to [`MAX_DEPTH`].
+fn arb_data_type() -> impl Strategy<Value = DataType> {
+    arb_primitive_type().prop_recursive(MAX_DEPTH, 16, 3, |inner| {
+        prop_oneof![
+            (inner.clone(), any::<bool>()).prop_map(|(data_type, nullable)| {
+                DataType::List(Arc::new(Field::new_list_field(data_type, nullable)))
+            }),
+            prop::collection::vec((inner, any::<bool>()), 1..=3).prop_map(|children| {
+                DataType::Struct(
+                    children
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, (data_type, nullable))| {
+                            Field::new(format!("f{i}"), data_type, nullable)
+                        })
+                        .collect::<Fields>(),
+                )
+            }),
+        ]
+    })
+}
+
+/// `len` values, some of them null if `nullable`.
+fn arb_values<T: std::fmt::Debug + 'static>(
+    value: impl Strategy<Value = T> + 'static,
+    len: usize,
+    nullable: bool,
+) -> BoxedStrategy<Vec<Option<T>>> {
+    if nullable {
+        prop::collection::vec(prop::option::weighted(VALID_PROBABILITY, value), len).boxed()
+    } else {
+        prop::collection::vec(value.prop_map(Some), len).boxed()
+    }
+}
+
+macro_rules! arb_array_of {
+    ($array:ty, $value:expr, $len:expr, $nullable:expr) => {
+        arb_values($value, $len, $nullable)
+            .prop_map(|values| Arc::new(<$array>::from_iter(values)) as ArrayRef)
+            .boxed()
+    };
+}
+
+fn arb_array(data_type: &DataType, len: usize, nullable: bool) -> BoxedStrategy<ArrayRef> {
+    // NaNs are left out, as their payload is not guaranteed to round trip.
+    let float32 = {
+        use prop::num::f32::*;
+        POSITIVE | NEGATIVE | NORMAL | SUBNORMAL | ZERO
+    };
+    let float64 = {
+        use prop::num::f64::*;
+        POSITIVE | NEGATIVE | NORMAL | SUBNORMAL | ZERO
+    };
+    match data_type {
+        DataType::Boolean => arb_array_of!(BooleanArray, any::<bool>(), len, nullable),
+        DataType::Int8 => arb_array_of!(Int8Array, any::<i8>(), len, nullable),
+        DataType::Int16 => arb_array_of!(Int16Array, any::<i16>(), len, nullable),
+        DataType::Int32 => arb_array_of!(Int32Array, any::<i32>(), len, nullable),
+        DataType::Int64 => arb_array_of!(Int64Array, any::<i64>(), len, nullable),
+        DataType::UInt8 => arb_array_of!(UInt8Array, any::<u8>(), len, nullable),
+        DataType::UInt16 => arb_array_of!(UInt16Array, any::<u16>(), len, nullable),
+        DataType::UInt32 => arb_array_of!(UInt32Array, any::<u32>(), len, nullable),
+        DataType::UInt64 => arb_array_of!(UInt64Array, any::<u64>(), len, nullable),
+        DataType::Float32 => arb_array_of!(Float32Array, float32, len, nullable),
+        DataType::Float64 => arb_array_of!(Float64Array, float64, len, nullable),
+        DataType::Date32 => arb_array_of!(Date32Array, any::<i32>(), len, nullable),
+        DataType::Timestamp(TimeUnit::Second, None) => {
+            arb_array_of!(TimestampSecondArray, any::<i64>(), len, nullable)
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, None) => {
+            arb_array_of!(TimestampMillisecondArray, any::<i64>(), len, nullable)
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, None) => {
+            arb_array_of!(TimestampMicrosecondArray, any::<i64>(), len, nullable)
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, None) => {
+            arb_array_of!(TimestampNanosecondArray, any::<i64>(), len, nullable)
+        }
+        DataType::Utf8 => arb_array_of!(StringArray, ".{0,12}", len, nullable),
+        DataType::LargeUtf8 => arb_array_of!(LargeStringArray, ".{0,12}", len, nullable),
+        DataType::Binary => arb_array_of!(
+            BinaryArray,
+            prop::collection::vec(any::<u8>(), 0..12),
+            len,
+            nullable
+        ),
+        DataType::List(child) => {
+            let child = child.clone();
+            arb_values(0..4usize, len, nullable)
+                .prop_flat_map(move |lengths| {
+                    let offsets =
+                        OffsetBuffer::<i32>::from_lengths(lengths.iter().map(|l| l.unwrap_or(0)));
+                    let nulls = nullable.then(|| {
+                        NullBuffer::from(lengths.iter().map(Option::is_some).collect::<Vec<_>>())
+                    });
+                    let child = child.clone();
+                    arb_array(
+                        child.data_type(),
+                        *offsets.last().unwrap() as usize,
+                        child.is_nullable(),
+                    )
+                    .prop_map(move |values| {
+                        Arc::new(ListArray::new(
+                            child.clone(),
+                            offsets.clone(),
+                            values,
+                            nulls.clone(),
+                        )) as ArrayRef
+                    })
+                })
+                .boxed()
+        }
+        DataType::Struct(fields) => {
+            let children = fields
+                .iter()
+                .map(|f| arb_array(f.data_type(), len, f.is_nullable()))
+                .collect::<Vec<_>>();
+            let fields = fields.clone();
+            (arb_values(Just(()), len, nullable), children)
+                .prop_map(move |(validity, children)| {
+                    let nulls = nullable.then(|| {
+                        NullBuffer::from(validity.iter().map(Option::is_some).collect::<Vec<_>>())
+                    });
+                    Arc::new(StructArray::new(fields.clone(), children, nulls)) as ArrayRef
+                })
+                .boxed()
+        }
+        _ => unreachable!("{data_type} is not generated"),
+    }
+}
+
+fn arb_schema() -> impl Strategy<Value = SchemaRef> {
+    prop::collection::vec((arb_data_type(), any::<bool>()), 1..=MAX_COLUMNS).prop_map(|columns| {
+        Arc::new(Schema::new(
+            columns
+                .into_iter()
+                .enumerate()
+                .map(|(i, (data_type, nullable))| Field::new(format!("c{i}"), data_type, nullable))
+                .collect::<Vec<_>>(),
+        ))
+    })
+}
+
+fn arb_batch(schema: SchemaRef, num_rows: usize) -> BoxedStrategy<RecordBatch> {
+    schema
+        .fields()
+        .iter()
+        .map(|f| arb_array(f.data_type(), num_rows, f.is_nullable()))
+        .collect::<Vec<_>>()
+        .prop_map(move |columns| RecordBatch::try_new(schema.clone(), columns).unwrap())
+        .boxed()
+}
+
+/// Up to [`MAX_BATCHES`] non-empty batches of a random schema.
+fn arb_batches(max_rows: usize) -> impl Strategy<Value = Vec<RecordBatch>> {
+    arb_schema().prop_flat_map(move |schema| {
+        prop::collection::vec(1..=max_rows, 1..=MAX_BATCHES).prop_flat_map(move |num_rows| {
+            num_rows
+                .into_iter()
+                .map(|n| arb_batch(schema.clone(), n))
+                .collect::<Vec<_>>()
+        })
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Encoding {
+    Vortex,
+    Adaptive,
+    EncoderDictionary,
+    GlobalDictionary,
+}
+
+/// The randomized writer options, kept apart from [`FileWriterOptions`] to be printed on failure.
+#[derive(Debug, Clone)]
+struct WriterConfig {
+    encoding: Encoding,
+    compression_type: CompressionType,
+    metadata_compression: CompressionType,
+    row_group_size: u64,
+    encoding_unit_len: u64,
+    io_unit_checksum: bool,
+    encunit_index: bool,
+}
+
+impl WriterConfig {
+    fn options(&self) -> FileWriterOptions {
+        let (dictionary_type, adaptive_encoding) = match self.encoding {
+            Encoding::Vortex => (DictionaryTypeOptions::NoDictionary, None),
+            Encoding::Adaptive => (
+                DictionaryTypeOptions::NoDictionary,
+                Some(AdaptiveEncodingOptions::default()),
+            ),
+            Encoding::EncoderDictionary => (DictionaryTypeOptions::EncoderDictionary, None),
+            Encoding::GlobalDictionary => (DictionaryTypeOptions::GlobalDictionary, None),
+        };
+        FileWriterOptionsBuilder::with_defaults()
+            .set_dictionary_type(dictionary_type)
+            .set_adaptive_encoding(adaptive_encoding)
+            .set_compression_type(self.compression_type)
+            .compress_metadata(self.metadata_compression)
+            .set_row_group_size(self.row_group_size)
+            .set_encoding_unit_len(self.encoding_unit_len)
+            .enable_io_unit_checksum(self.io_unit_checksum)
+            .set_write_encunit_index(self.encunit_index)
+            .build()
+    }
+}
+
+fn arb_compression_type() -> impl Strategy<Value = CompressionType> {
+    prop_oneof![
+        Just(CompressionType::Uncompressed),
+        Just(CompressionType::Zstd),
+        Just(CompressionType::Lz4),
+        Just(CompressionType::Snappy),
+        Just(CompressionType::Brotli),
+    ]
+}
+
+fn arb_writer_config(max_rows: usize) -> impl Strategy<Value = WriterConfig> {
+    (
+        prop_oneof![
+            Just(Encoding::Vortex),
+            Just(Encoding::Adaptive),
+            Just(Encoding::EncoderDictionary),
+            Just(Encoding::GlobalDictionary),
+        ],
+        arb_compression_type(),
+        arb_compression_type(),
+        1..=(max_rows * MAX_BATCHES) as u64,
+        16..=1024u64,
+        any::<bool>(),
+        any::<bool>(),
+    )
+        .prop_map(
+            |(
+                encoding,
+                compression_type,
+                metadata_compression,
+                row_group_size,
+                encoding_unit_len,
+                io_unit_checksum,
+                encunit_index,
+            )| WriterConfig {
+                encoding,
+                compression_type,
+                metadata_compression,
+                row_group_size,
+                encoding_unit_len,
+                io_unit_checksum,
+                encunit_index,
+            },
+        )
+}
+
+fn check_roundtrip(batches: &[RecordBatch], config: &WriterConfig) -> Result<(), TestCaseError> {
+    let mut file = tempfile::tempfile().unwrap();
+    let mut writer = FileWriter::try_new(batches[0].schema(), &mut file, config.options())
+        .map_err(|e| TestCaseError::fail(format!("create writer: {e}")))?;
+    for batch in batches {
+        writer
+            .write_batch(batch)
+            .map_err(|e| TestCaseError::fail(format!("write batch: {e}")))?;
+    }
+    writer
+        .finish()
+        .map_err(|e| TestCaseError::fail(format!("finish: {e}")))?;
+
+    let output_batches = FileReaderV2Builder::new(Arc::new(file))
+        .build()
+        .and_then(|mut reader| reader.read_file())
+        .map_err(|e| TestCaseError::fail(format!("read: {e}")))?;
+    let expected = concat_batches(batches[0].schema_ref(), batches).unwrap();
+    let num_rows: usize = output_batches.iter().map(|b| b.num_rows()).sum();
+    prop_assert_eq!(num_rows, expected.num_rows());
+    for (col_idx, expected_col) in expected.columns().iter().enumerate() {
+        // The reader may output view types, so compare after casting to the input type.
+        let output = output_batches
+            .iter()
+            .map(|b| cast(b.column(col_idx), expected_col.data_type()).unwrap())
+            .collect::<Vec<_>>();
+        let output = concat(&output.iter().map(|a| a.as_ref()).collect::<Vec<_>>()).unwrap();
+        prop_assert_eq!(&output, expected_col, "column {}", col_idx);
+    }
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn test_roundtrip(batches in arb_batches(300), config in arb_writer_config(300)) {
+        check_roundtrip(&batches, &config)?;
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    #[ignore]
+    fn test_roundtrip_extended(batches in arb_batches(5000), config in arb_writer_config(5000)) {
+        check_roundtrip(&batches, &config)?;
+    }
+}
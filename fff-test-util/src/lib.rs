@@ -30,5 +30,20 @@ pub const VORTEX_WASM_FUNC_GENERAL: &str = "decode_vortex_general_ffi";
 pub static BUILTIN_WASM_PATH: LazyLock<PathBuf> =
     LazyLock::new(|| BASE_PATH.join("target/wasm32-wasip1/opt-size-lvl3/fff_ude_example_fff.wasm"));
 pub const WASM_FUNC_GENERAL: &str = "decode_general_ffi";
+/// Decodes a batch of inputs like [`WASM_FUNC_GENERAL`] per call.
+pub const WASM_FUNC_GENERAL_BATCH: &str = "decode_general_batch_ffi";
+/// Takes the dictionary values and the keys as two inputs.
+pub const WASM_FUNC_DICT_GENERAL: &str = "decode_dict_general_ffi";
+/// Outputs through the Arrow C Data Interface.
+pub const WASM_FUNC_C_DATA: &str = "decode_c_data_ffi";
+/// Outputs several arrays, see `wasm_test_encoders::decode_fff_multi_array`.
+pub const WASM_FUNC_MULTI_ARRAY: &str = "decode_multi_array_ffi";
+/// Encoder run by the writer, whose output is decoded by [`WASM_FUNC_GENERAL`].
+pub const WASM_FUNC_ENCODE: &str = "encode_ffi";
+
+/// Exports [`WASM_FUNC_GENERAL`] and [`WASM_FUNC_GENERAL_BATCH`], allocating from the arena of
+/// the host.
+pub static ARENA_WASM_PATH: LazyLock<PathBuf> =
+    LazyLock::new(|| BASE_PATH.join("target/wasm32-wasip1/release/fff_ude_example_arena.wasm"));
 
 pub const TEST_SCHEMES: [&str; 6] = ["pco", "lz4", "flsbp", "fff", "gzip", "zstd"];
@@ -0,0 +1,68 @@
+//! Ahead-of-time compilation of modules, so that readers on the same platform instantiate them
+//! without compiling, see [`crate::Runtime::try_new_from_aot`].
+//!
+//! AOT artifacts are native code for one target and one version of wasmtime, which rejects the
+//! artifacts of other versions when loading them. Artifacts are tagged with an [`AotTarget`], so
+//! that readers can pick the one matching their host and compile the portable module otherwise.
+
+use std::fmt::{self, Display};
+
+use anyhow::{ensure, Result};
+
+use crate::{features::required_features, ENGINE};
+
+/// Version of the `wasmtime` dependency of the workspace.
+pub const WASMTIME_VERSION: &str = "28.0.0";
+
+/// The platform an AOT artifact was compiled for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AotTarget {
+    /// Architecture and OS, e.g., `x86_64-linux`.
+    pub triple: String,
+    pub wasmtime_version: String,
+}
+
+impl AotTarget {
+    /// The target of the artifacts compiled and loaded by this build.
+    pub fn host() -> Self {
+        Self {
+            triple: format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS),
+            wasmtime_version: WASMTIME_VERSION.to_string(),
+        }
+    }
+}
+
+impl Display for AotTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (wasmtime {})", self.triple, self.wasmtime_version)
+    }
+}
+
+/// Compile `binary` for [`AotTarget::host`], to be loaded with
+/// [`crate::Runtime::try_new_from_aot`]. Modules requiring optional features, see
+/// [`required_features`], are not supported, as AOT runtimes do not enable them.
+pub fn precompile(binary: &[u8]) -> Result<Vec<u8>> {
+    let features = required_features(binary)?;
+    ensure!(
+        features.is_empty(),
+        "modules requiring {features:?} cannot be compiled ahead of time"
+    );
+    ENGINE.precompile_module(binary)
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmtime::Module;
+
+    use super::*;
+
+    #[test]
+    fn test_precompile() {
+        let binary = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        let aot_binary = precompile(&binary).unwrap();
+        unsafe { Module::deserialize(&ENGINE, &aot_binary) }.unwrap();
+        assert!(unsafe { Module::deserialize(&ENGINE, &binary) }.is_err());
+        assert!(precompile(&binary[..6]).is_err());
+        assert_eq!(AotTarget::host().wasmtime_version, WASMTIME_VERSION);
+    }
+}
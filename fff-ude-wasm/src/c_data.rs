@@ -0,0 +1,310 @@
+//! Import of the arrays a WASM decoder exports through the Arrow C Data Interface.
+//!
+//! The guest outputs an `ArrowArrayPair` of `fff_ude::ffi`, i.e., an `ArrowArray` followed by its
+//! `ArrowSchema`, laid out for wasm32 where pointers are 4 bytes. The schema is rebuilt on the
+//! host to parse the data type. The buffers are then copied out of the linear memory, their sizes
+//! following from the data type like in the Arrow C Data Interface, and the array is validated.
+
+use anyhow::{bail, ensure, Context, Result};
+use arrow_buffer::{bit_util, Buffer};
+use arrow_data::{layout, ArrayData, BufferSpec};
+use arrow_schema::ffi::{FFI_ArrowSchema, Flags};
+use arrow_schema::DataType;
+
+use crate::validation::ValidationError;
+
+/// Size of `ArrowArray` on wasm32: 5 i64s, then 5 pointers, padded to 8 bytes.
+const ARRAY_SIZE: u32 = 64;
+/// Size of `ArrowSchema` on wasm32: 3 pointers, padding, 2 i64s, then 4 pointers.
+const SCHEMA_SIZE: u32 = 48;
+/// Size of `ArrowArrayPair` on wasm32.
+pub(crate) const PAIR_SIZE: u32 = ARRAY_SIZE + SCHEMA_SIZE;
+
+/// Nesting deeper than this is taken as a cycle in the guest structures.
+const MAX_DEPTH: usize = 64;
+
+/// Read access to the linear memory of an instance.
+struct Memory<'a>(&'a [u8]);
+
+impl Memory<'_> {
+    fn out_of_bounds(&self, ptr: u32, len: usize) -> ValidationError {
+        ValidationError::OutOfBounds {
+            ptr,
+            len: u32::try_from(len).unwrap_or(u32::MAX),
+            memory_size: self.0.len(),
+        }
+    }
+
+    fn slice(&self, ptr: u32, len: usize) -> Result<&[u8]> {
+        let out_of_bounds = || self.out_of_bounds(ptr, len);
+        let end = (ptr as usize).checked_add(len).ok_or_else(out_of_bounds)?;
+        Ok(self.0.get(ptr as usize..end).ok_or_else(out_of_bounds)?)
+    }
+
+    /// The address `offset` bytes after `ptr`, which the guest may have made overflow.
+    fn offset(&self, ptr: u32, offset: usize) -> Result<u32> {
+        let address = u32::try_from(offset)
+            .ok()
+            .and_then(|offset| ptr.checked_add(offset));
+        Ok(address.ok_or_else(|| self.out_of_bounds(ptr, offset))?)
+    }
+
+    /// The address of the `i`-th element of `width` bytes of the array at `ptr`.
+    fn element(&self, ptr: u32, i: usize, width: usize) -> Result<u32> {
+        let offset = i
+            .checked_mul(width)
+            .ok_or_else(|| self.out_of_bounds(ptr, usize::MAX))?;
+        self.offset(ptr, offset)
+    }
+
+    /// The size of `count` values of `width` bytes of the buffer at `ptr`.
+    fn size(&self, ptr: u32, count: usize, width: usize) -> Result<usize> {
+        Ok(count
+            .checked_mul(width)
+            .ok_or_else(|| self.out_of_bounds(ptr, usize::MAX))?)
+    }
+
+    fn u32(&self, ptr: u32) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.slice(ptr, 4)?.try_into()?))
+    }
+
+    fn i64(&self, ptr: u32) -> Result<i64> {
+        Ok(i64::from_le_bytes(self.slice(ptr, 8)?.try_into()?))
+    }
+
+    fn usize(&self, ptr: u32) -> Result<usize> {
+        let value = self.i64(ptr)?;
+        usize::try_from(value).with_context(|| format!("negative value {value} at {ptr}"))
+    }
+
+    /// A NUL-terminated string.
+    fn str(&self, ptr: u32) -> Result<&str> {
+        let bytes = self.0.get(ptr as usize..).context("string out of bounds")?;
+        let len = bytes
+            .iter()
+            .position(|&b| b == 0)
+            .context("string is not NUL-terminated")?;
+        Ok(std::str::from_utf8(&bytes[..len])?)
+    }
+
+    /// Copy `len` bytes at `ptr` out of the linear memory.
+    fn buffer(&self, ptr: u32, len: usize) -> Result<Buffer> {
+        Ok(Buffer::from_slice_ref(self.slice(ptr, len)?))
+    }
+}
+
+/// Import the array of the `ArrowArrayPair` at `pair_ptr` in `memory`.
+pub(crate) fn import_array(memory: &[u8], pair_ptr: u32) -> Result<ArrayData> {
+    let memory = Memory(memory);
+    let schema = import_schema(&memory, memory.offset(pair_ptr, ARRAY_SIZE as usize)?, 0)?;
+    let data_type = DataType::try_from(&schema)?;
+    let data = import_array_data(&memory, pair_ptr, data_type, 0)?;
+    Ok(data)
+}
+
+fn import_schema(memory: &Memory, ptr: u32, depth: usize) -> Result<FFI_ArrowSchema> {
+    ensure!(depth < MAX_DEPTH, "schema nested too deeply");
+    let field = |offset| memory.offset(ptr, offset);
+    let format = memory.str(memory.u32(ptr)?)?;
+    let name_ptr = memory.u32(field(4)?)?;
+    let flags = memory.i64(field(16)?)?;
+    let n_children = memory.usize(field(24)?)?;
+    let children_ptr = memory.u32(field(32)?)?;
+    let dictionary_ptr = memory.u32(field(36)?)?;
+    let children = (0..n_children)
+        .map(|i| {
+            let child_ptr = memory.u32(memory.element(children_ptr, i, 4)?)?;
+            import_schema(memory, child_ptr, depth + 1)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let dictionary = match dictionary_ptr {
+        0 => None,
+        ptr => Some(import_schema(memory, ptr, depth + 1)?),
+    };
+    let mut schema = FFI_ArrowSchema::try_new(format, children, dictionary)?
+        .with_flags(Flags::from_bits_truncate(flags))?;
+    if name_ptr != 0 {
+        schema = schema.with_name(memory.str(name_ptr)?)?;
+    }
+    Ok(schema)
+}
+
+/// Types whose first buffer after the validity holds `len + 1` offsets of the given width.
+fn offsets_width(data_type: &DataType) -> Option<usize> {
+    match data_type {
+        DataType::Utf8 | DataType::Binary | DataType::List(_) | DataType::Map(_, _) => Some(4),
+        DataType::LargeUtf8 | DataType::LargeBinary | DataType::LargeList(_) => Some(8),
+        _ => None,
+    }
+}
+
+fn child_types(data_type: &DataType) -> Result<Vec<DataType>> {
+    Ok(match data_type {
+        DataType::List(field)
+        | DataType::LargeList(field)
+        | DataType::FixedSizeList(field, _)
+        | DataType::Map(field, _) => vec![field.data_type().clone()],
+        DataType::Struct(fields) => fields.iter().map(|f| f.data_type().clone()).collect(),
+        DataType::RunEndEncoded(run_ends, values) => {
+            vec![run_ends.data_type().clone(), values.data_type().clone()]
+        }
+        DataType::Union(_, _) | DataType::ListView(_) | DataType::LargeListView(_) => {
+            bail!("importing {data_type} is not supported")
+        }
+        _ => vec![],
+    })
+}
+
+fn import_array_data(
+    memory: &Memory,
+    ptr: u32,
+    data_type: DataType,
+    depth: usize,
+) -> Result<ArrayData> {
+    ensure!(depth < MAX_DEPTH, "array nested too deeply");
+    let field = |offset| memory.offset(ptr, offset);
+    let len = memory.usize(ptr)?;
+    let offset = memory.usize(field(16)?)?;
+    let n_buffers = memory.usize(field(24)?)?;
+    let n_children = memory.usize(field(32)?)?;
+    let buffers_ptr = memory.u32(field(40)?)?;
+    let children_ptr = memory.u32(field(44)?)?;
+    let dictionary_ptr = memory.u32(field(48)?)?;
+    let buffer_ptr = |i: usize| memory.u32(memory.element(buffers_ptr, i, 4)?);
+    let end = offset.checked_add(len).context("array length overflow")?;
+
+    let layout = layout(&data_type);
+    let num_fixed = layout.buffers.len() + layout.can_contain_null_mask as usize;
+    if layout.variadic {
+        // The last buffer holds the sizes of the variadic buffers.
+        ensure!(
+            n_buffers > num_fixed,
+            ValidationError::BufferCount {
+                expected: num_fixed + 1,
+                actual: n_buffers
+            }
+        );
+    } else {
+        ensure!(
+            n_buffers == num_fixed,
+            ValidationError::BufferCount {
+                expected: num_fixed,
+                actual: n_buffers
+            }
+        );
+    }
+
+    let nulls = match (layout.can_contain_null_mask, buffer_ptr(0)) {
+        (false, _) | (true, Ok(0)) => None,
+        (true, ptr) => Some(memory.buffer(ptr?, bit_util::ceil(end, 8))?),
+    };
+    let mut buffers = vec![];
+    for (i, spec) in layout.buffers.iter().enumerate() {
+        let index = i + layout.can_contain_null_mask as usize;
+        let ptr = buffer_ptr(index)?;
+        let size = match spec {
+            BufferSpec::FixedWidth { byte_width, .. } => match offsets_width(&data_type) {
+                Some(width) if i == 0 => memory.size(ptr, end.saturating_add(1), width)?,
+                _ => memory.size(ptr, end, *byte_width)?,
+            },
+            BufferSpec::BitMap => bit_util::ceil(end, 8),
+            BufferSpec::VariableWidth => {
+                // The values end at the last offset.
+                let width = offsets_width(&data_type).context("variable width without offsets")?;
+                let offsets_ptr = buffer_ptr(index - 1)?;
+                let last_offset = memory.element(offsets_ptr, end, width)?;
+                match width {
+                    4 => memory.u32(last_offset)? as usize,
+                    _ => memory.usize(last_offset)?,
+                }
+            }
+            BufferSpec::AlwaysNull => 0,
+        };
+        buffers.push(if size == 0 {
+            Buffer::from_vec(Vec::<u8>::new())
+        } else {
+            memory.buffer(ptr, size)?
+        });
+    }
+    if layout.variadic {
+        let sizes_ptr = buffer_ptr(n_buffers - 1)?;
+        for i in num_fixed..n_buffers - 1 {
+            let size = memory.usize(memory.element(sizes_ptr, i - num_fixed, 8)?)?;
+            buffers.push(memory.buffer(buffer_ptr(i)?, size)?);
+        }
+    }
+
+    let child_data = match &data_type {
+        DataType::Dictionary(_, value_type) => {
+            ensure!(dictionary_ptr != 0, "dictionary array without dictionary");
+            vec![import_array_data(
+                memory,
+                dictionary_ptr,
+                value_type.as_ref().clone(),
+                depth + 1,
+            )?]
+        }
+        _ => {
+            let child_types = child_types(&data_type)?;
+            ensure!(
+                n_children == child_types.len(),
+                "expected {} children for {data_type}, got {n_children}",
+                child_types.len()
+            );
+            child_types
+                .into_iter()
+                .enumerate()
+                .map(|(i, child_type)| {
+                    let child_ptr = memory.u32(memory.element(children_ptr, i, 4)?)?;
+                    import_array_data(memory, child_ptr, child_type, depth + 1)
+                })
+                .collect::<Result<Vec<_>>>()?
+        }
+    };
+    Ok(ArrayData::try_new(
+        data_type, len, nulls, offset, buffers, child_data,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(memory: &mut [u8], ptr: usize, bytes: &[u8]) {
+        memory[ptr..ptr + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn out_of_bounds(result: Result<ArrayData>) -> ValidationError {
+        result.unwrap_err().downcast::<ValidationError>().unwrap()
+    }
+
+    #[test]
+    fn test_overflowing_pointers() {
+        let memory = vec![0; 512];
+        assert_eq!(
+            out_of_bounds(import_array(&memory, u32::MAX - 8)),
+            ValidationError::OutOfBounds {
+                ptr: u32::MAX - 8,
+                len: ARRAY_SIZE,
+                memory_size: 512
+            }
+        );
+
+        // An Int32 array whose length overflows the size of its values.
+        let mut memory = vec![0; 512];
+        write(&mut memory, 0, &i64::MAX.to_le_bytes());
+        write(&mut memory, 24, &2i64.to_le_bytes());
+        write(&mut memory, 40, &300u32.to_le_bytes());
+        write(&mut memory, ARRAY_SIZE as usize, &200u32.to_le_bytes());
+        write(&mut memory, 200, b"i\0");
+        write(&mut memory, 304, &400u32.to_le_bytes());
+        assert_eq!(
+            out_of_bounds(import_array(&memory, 0)),
+            ValidationError::OutOfBounds {
+                ptr: 400,
+                len: u32::MAX,
+                memory_size: 512
+            }
+        );
+    }
+}
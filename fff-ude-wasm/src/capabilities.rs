@@ -0,0 +1,109 @@
+//! Capability flags of modules, see [`crate::Runtime::supports`].
+//!
+//! A module declares the EncUnits it decodes by exporting a marker per encoding, like the
+//! `FFFUDE_VERSION_` marker of its ABI version:
+//!
+//! - `FFFUDE_SUPPORTS_<ENCODING>` for EncUnits of `ENCODING` of any data type, e.g.,
+//!   `FFFUDE_SUPPORTS_CASCADE`;
+//! - `FFFUDE_SUPPORTS_<ENCODING>_<TYPE>` for those of a data type only, named by [`type_flag`],
+//!   e.g., `FFFUDE_SUPPORTS_CUSTOM_WASM_I64`.
+//!
+//! Encodings are named as in the footer, e.g., `CASCADE` or `CUSTOM_WASM`. Modules declaring no
+//! flag at all, e.g., those built before flags were, may decode anything.
+//!
+//! ```ignore
+//! #[no_mangle]
+//! #[used]
+//! pub static FFFUDE_SUPPORTS_CUSTOM_WASM_I64: () = ();
+//! ```
+
+use arrow_schema::DataType;
+
+/// Prefix of the exports declaring capabilities.
+pub const CAPABILITY_PREFIX: &str = "FFFUDE_SUPPORTS_";
+
+/// The name of `data_type` in capability flags, by the physical layout of its values: temporal
+/// types are named after the integers they are stored as. None for types without a flag, e.g.,
+/// nested ones, which only `FFFUDE_SUPPORTS_<ENCODING>` covers.
+pub fn type_flag(data_type: &DataType) -> Option<&'static str> {
+    Some(match data_type {
+        DataType::Boolean => "BOOL",
+        DataType::Int8 => "I8",
+        DataType::Int16 => "I16",
+        DataType::Int32 | DataType::Date32 | DataType::Time32(_) => "I32",
+        DataType::Int64
+        | DataType::Date64
+        | DataType::Time64(_)
+        | DataType::Timestamp(_, _)
+        | DataType::Duration(_) => "I64",
+        DataType::UInt8 => "U8",
+        DataType::UInt16 => "U16",
+        DataType::UInt32 => "U32",
+        DataType::UInt64 => "U64",
+        DataType::Float16 => "F16",
+        DataType::Float32 => "F32",
+        DataType::Float64 => "F64",
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => "STRING",
+        DataType::Binary | DataType::LargeBinary | DataType::BinaryView => "BINARY",
+        _ => return None,
+    })
+}
+
+/// Whether the module exporting `exports` decodes EncUnits of `data_type` encoded with
+/// `encoding`, None if it declares no capability.
+pub(crate) fn supports<'a>(
+    exports: impl IntoIterator<Item = &'a str>,
+    data_type: &DataType,
+    encoding: &str,
+) -> Option<bool> {
+    let type_flag = type_flag(data_type);
+    let mut declared = false;
+    for flag in exports
+        .into_iter()
+        .filter_map(|name| name.strip_prefix(CAPABILITY_PREFIX))
+    {
+        declared = true;
+        let Some(rest) = flag.strip_prefix(encoding) else {
+            continue;
+        };
+        if rest.is_empty() || rest.strip_prefix('_') == type_flag {
+            return Some(true);
+        }
+    }
+    declared.then_some(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_schema::TimeUnit;
+
+    use super::*;
+
+    #[test]
+    fn test_supports() {
+        assert_eq!(
+            supports(["FFFUDE_VERSION_1_7"], &DataType::Int32, "CASCADE"),
+            None
+        );
+
+        let exports = [
+            "FFFUDE_VERSION_1_7",
+            "FFFUDE_SUPPORTS_CASCADE",
+            "FFFUDE_SUPPORTS_CUSTOM_WASM_I64",
+        ];
+        assert_eq!(supports(exports, &DataType::Utf8, "CASCADE"), Some(true));
+        assert_eq!(
+            supports(exports, &DataType::Int64, "CUSTOM_WASM"),
+            Some(true)
+        );
+        let timestamp = DataType::Timestamp(TimeUnit::Microsecond, None);
+        assert_eq!(supports(exports, &timestamp, "CUSTOM_WASM"), Some(true));
+        assert_eq!(
+            supports(exports, &DataType::Int32, "CUSTOM_WASM"),
+            Some(false)
+        );
+        // Encodings prefixing others are told apart.
+        assert_eq!(supports(exports, &DataType::Int64, "CUSTOM"), Some(false));
+        assert_eq!(supports(exports, &DataType::Int64, "RLE"), Some(false));
+    }
+}
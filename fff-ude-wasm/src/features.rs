@@ -0,0 +1,184 @@
+//! Detection of the WASM proposals a module requires, see [`crate::Config::deny_wasm_feature`].
+//!
+//! Codecs may be compiled with SIMD, relaxed SIMD or threads. The features a module uses are
+//! found by validating it without each of them, and the module is compiled by an engine enabling
+//! exactly those, shared by all the modules requiring the same features.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use wasmparser::{Validator, WasmFeatures};
+use wasmtime::Engine;
+
+/// A WASM proposal a module may require beyond the features enabled by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum WasmFeature {
+    /// 128-bit SIMD.
+    Simd,
+    /// Relaxed SIMD, executed deterministically so that decoding does not depend on the host.
+    RelaxedSimd,
+    /// Atomics and shared memories.
+    Threads,
+}
+
+impl WasmFeature {
+    pub const ALL: [WasmFeature; 3] = [
+        WasmFeature::Simd,
+        WasmFeature::RelaxedSimd,
+        WasmFeature::Threads,
+    ];
+
+    fn parser_features(self) -> WasmFeatures {
+        match self {
+            WasmFeature::Simd => WasmFeatures::SIMD,
+            WasmFeature::RelaxedSimd => WasmFeatures::RELAXED_SIMD,
+            WasmFeature::Threads => WasmFeatures::THREADS,
+        }
+    }
+}
+
+impl Display for WasmFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WasmFeature::Simd => write!(f, "simd"),
+            WasmFeature::RelaxedSimd => write!(f, "relaxed-simd"),
+            WasmFeature::Threads => write!(f, "threads"),
+        }
+    }
+}
+
+/// A module requires a feature denied by the [`crate::Config`] of the runtime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedWasmFeature {
+    pub feature: WasmFeature,
+}
+
+impl Display for UnsupportedWasmFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the WASM module requires the {} feature, which is not allowed",
+            self.feature
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedWasmFeature {}
+
+fn validate(binary: &[u8], features: WasmFeatures) -> Result<(), wasmparser::BinaryReaderError> {
+    Validator::new_with_features(features)
+        .validate_all(binary)
+        .map(|_| ())
+}
+
+/// The features of [`WasmFeature::ALL`] required by `binary`, sorted.
+pub fn required_features(binary: &[u8]) -> Result<Vec<WasmFeature>> {
+    let optional = WasmFeature::ALL
+        .iter()
+        .fold(WasmFeatures::empty(), |acc, f| acc | f.parser_features());
+    // Most modules need none of them, which takes a single validation.
+    if validate(binary, WasmFeatures::all() - optional).is_ok() {
+        return Ok(vec![]);
+    }
+    validate(binary, WasmFeatures::all()).map_err(|e| anyhow!("invalid wasm binary: {e}"))?;
+    Ok(WasmFeature::ALL
+        .into_iter()
+        .filter(|f| validate(binary, WasmFeatures::all() - f.parser_features()).is_err())
+        .collect())
+}
+
+static ENGINES: Mutex<Option<HashMap<Vec<WasmFeature>, Engine>>> = Mutex::new(None);
+
+/// The engine compiling modules requiring `features`, created on first use.
+pub(crate) fn engine_for(features: &[WasmFeature]) -> Result<Engine> {
+    let mut engines = ENGINES.lock().unwrap();
+    let engines = engines.get_or_insert_with(HashMap::new);
+    if let Some(engine) = engines.get(features) {
+        return Ok(engine.clone());
+    }
+    let has = |feature| features.contains(&feature);
+    let relaxed_simd = has(WasmFeature::RelaxedSimd);
+    let engine = Engine::new(
+        wasmtime::Config::new()
+            .cranelift_opt_level(wasmtime::OptLevel::None)
+            .parallel_compilation(true)
+            .wasm_simd(has(WasmFeature::Simd) || relaxed_simd)
+            .wasm_relaxed_simd(relaxed_simd)
+            .relaxed_simd_deterministic(relaxed_simd)
+            .wasm_threads(has(WasmFeature::Threads)),
+    )?;
+    engines.insert(features.to_vec(), engine.clone());
+    Ok(engine)
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmtime::Module;
+
+    use super::*;
+    use crate::{Config, Runtime};
+
+    const HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+    /// A module with a function computing a `v128.const` and dropping it.
+    fn simd_module() -> Vec<u8> {
+        let mut binary = HEADER.to_vec();
+        // Type section: () -> (), function section: one function of type 0.
+        binary.extend_from_slice(&[0x01, 0x04, 0x01, 0x60, 0x00, 0x00]);
+        binary.extend_from_slice(&[0x03, 0x02, 0x01, 0x00]);
+        // Code section: no locals, v128.const 0, drop, end.
+        binary.extend_from_slice(&[0x0a, 0x17, 0x01, 0x15, 0x00, 0xfd, 0x0c]);
+        binary.extend_from_slice(&[0; 16]);
+        binary.extend_from_slice(&[0x1a, 0x0b]);
+        binary
+    }
+
+    /// A module with a shared memory of one page.
+    fn threads_module() -> Vec<u8> {
+        let mut binary = HEADER.to_vec();
+        binary.extend_from_slice(&[0x05, 0x04, 0x01, 0x03, 0x01, 0x01]);
+        binary
+    }
+
+    #[test]
+    fn test_required_features() {
+        assert_eq!(required_features(&HEADER).unwrap(), vec![]);
+        assert_eq!(
+            required_features(&simd_module()).unwrap(),
+            vec![WasmFeature::Simd]
+        );
+        assert_eq!(
+            required_features(&threads_module()).unwrap(),
+            vec![WasmFeature::Threads]
+        );
+        assert!(required_features(&HEADER[..6]).is_err());
+
+        for (binary, features) in [
+            (simd_module(), [WasmFeature::Simd]),
+            (threads_module(), [WasmFeature::Threads]),
+        ] {
+            let engine = engine_for(&features).unwrap();
+            Module::from_binary(&engine, &binary).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_denied_features() {
+        let config = Config::default().deny_wasm_feature(WasmFeature::Threads);
+        let err = Runtime::with_config(&threads_module(), config).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<UnsupportedWasmFeature>(),
+            Some(&UnsupportedWasmFeature {
+                feature: WasmFeature::Threads
+            })
+        );
+        // The module is compiled, then rejected for not exporting the UDE ABI version.
+        let err = Runtime::with_config(&threads_module(), Config::default()).unwrap_err();
+        assert!(err.downcast_ref::<UnsupportedWasmFeature>().is_none());
+        let config = Config::default().deny_wasm_feature(WasmFeature::Threads);
+        let err = Runtime::with_config(&simd_module(), config).unwrap_err();
+        assert!(err.downcast_ref::<UnsupportedWasmFeature>().is_none());
+    }
+}
@@ -18,21 +18,36 @@
 
 use anyhow::{anyhow, bail, ensure, Context};
 use arrow_buffer::Buffer;
+use arrow_data::ArrayData;
+use arrow_schema::ffi::FFI_ArrowSchema;
+use arrow_schema::DataType;
+use features::{engine_for, required_features, UnsupportedWasmFeature, WasmFeature};
+use metrics::{FunctionCounters, MetricsCollector, RuntimeMetrics};
 use ram_file::{RamFile, RamFileRef};
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::Debug;
 use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use validation::{validate_buffers, ValidationError};
 use wasi_common::{sync::WasiCtxBuilder, WasiCtx};
 use wasm_buffer::WasmBuffer;
 use wasmtime::*;
 
+pub mod aot;
+mod c_data;
+pub mod capabilities;
+pub mod features;
+pub mod metrics;
 mod ram_file;
 // pub mod wasm_array;
+pub mod validation;
 pub mod wasm_buffer;
 
 /// 128 is not working for pco, lz4, flsbp
 const INPUT_ALIGNMENT: u32 = 4;
+/// Alignment of the arena of [`Config::arena_size`], enough for any allocation of the guest.
+const ARENA_ALIGNMENT: u32 = 16;
 
 /// The WASM UDF runtime.
 ///
@@ -49,6 +64,11 @@ pub struct Runtime {
     instances: Mutex<VecDeque<Arc<Mutex<Instance>>>>,
     /// ABI version. (major, minor)
     abi_version: (u8, u8),
+    /// Shared with the instances.
+    usage: Arc<UsageCounters>,
+    /// Features required by the module, beyond the ones enabled by default.
+    features: Vec<WasmFeature>,
+    metrics: Arc<MetricsCollector>,
 }
 
 /// Configurations.
@@ -59,6 +79,18 @@ pub struct Config {
     memory_size_limit: Option<usize>,
     /// File size limit in bytes.
     file_size_limit: Option<usize>,
+    /// Validate the output buffers of decoders, see [`Runtime::call_multi_buf_validated`].
+    strict_validation: bool,
+    /// Copy output buffers out of the WASM memory.
+    copy_outputs: bool,
+    /// Instances whose linear memory grew beyond this size are not returned to the pool.
+    max_retained_memory: Option<usize>,
+    /// Instances are not returned to the pool after this many calls.
+    reset_after_n_calls: Option<usize>,
+    /// Features modules may not require.
+    denied_features: HashSet<WasmFeature>,
+    /// Size of the arena of each instance in the arena ABI mode.
+    arena_size: Option<usize>,
 }
 
 impl Config {
@@ -73,6 +105,67 @@ impl Config {
         self.file_size_limit = Some(limit);
         self
     }
+
+    /// Validate the buffers returned by WASM decoders against the expected row count and data
+    /// type, turning malformed outputs into [`ValidationError`]s.
+    pub fn strict_validation(mut self, enabled: bool) -> Self {
+        self.strict_validation = enabled;
+        self
+    }
+
+    /// Copy the buffers returned by WASM functions into host memory and release them in the guest
+    /// right away.
+    ///
+    /// By default, output buffers point into the linear memory of the instance and keep it alive,
+    /// which is unsafe if the memory grows while they are in use. Copying costs a memcpy per
+    /// buffer but leaves outputs independent of the instance, so it can be reused immediately.
+    pub fn copy_outputs(mut self, enabled: bool) -> Self {
+        self.copy_outputs = enabled;
+        self
+    }
+
+    /// Drop instances whose linear memory exceeds `limit` bytes after a call instead of
+    /// returning them to the pool. WASM memory never shrinks, so without this a single large
+    /// decode keeps its memory for the lifetime of the runtime.
+    pub fn max_retained_memory(mut self, limit: usize) -> Self {
+        self.max_retained_memory = Some(limit);
+        self
+    }
+
+    /// Drop instances after `n` calls instead of returning them to the pool, so that the next call
+    /// starts from a fresh instance.
+    pub fn reset_after_n_calls(mut self, n: usize) -> Self {
+        self.reset_after_n_calls = Some(n);
+        self
+    }
+
+    /// Pre-allocate an arena of `size` bytes in the memory of each instance, from which the guest
+    /// bump-allocates its outputs and temporaries instead of calling `malloc`/`free` per EncUnit.
+    ///
+    /// Only guests allocating with `fff_ude::memory::ArenaAllocator` (ABI 1.7) use it, the others
+    /// give it back. The arena is reset when an instance is taken out of the pool for a call and
+    /// nothing references it anymore, i.e., the outputs of the earlier calls are dropped, which
+    /// [`Config::copy_outputs`] does right away.
+    pub fn arena_size(mut self, size: usize) -> Self {
+        self.arena_size = Some(size);
+        self
+    }
+
+    /// Reject modules requiring `feature` with an [`UnsupportedWasmFeature`] error instead of
+    /// compiling them with the feature enabled. All features are allowed by default.
+    pub fn deny_wasm_feature(mut self, feature: WasmFeature) -> Self {
+        self.denied_features.insert(feature);
+        self
+    }
+
+    /// The features required by `binary`, or an error if one of them is denied.
+    fn check_features(&self, binary: &[u8]) -> Result<Vec<WasmFeature>> {
+        let features = required_features(binary)?;
+        if let Some(&feature) = features.iter().find(|f| self.denied_features.contains(f)) {
+            return Err(UnsupportedWasmFeature { feature }.into());
+        }
+        Ok(features)
+    }
 }
 
 impl Debug for Config {
@@ -80,6 +173,12 @@ impl Debug for Config {
         f.debug_struct("Config")
             .field("memory_size_limit", &self.memory_size_limit)
             .field("file_size_limit", &self.file_size_limit)
+            .field("strict_validation", &self.strict_validation)
+            .field("copy_outputs", &self.copy_outputs)
+            .field("max_retained_memory", &self.max_retained_memory)
+            .field("reset_after_n_calls", &self.reset_after_n_calls)
+            .field("denied_features", &self.denied_features)
+            .field("arena_size", &self.arena_size)
             .finish()
     }
 }
@@ -102,6 +201,17 @@ pub struct Instance {
     // decode_ffi
     // extern "C" fn(decoder: *mut WasmDecoder,out: *mut CSlice) -> i32
     decode: Option<TypedFunc<(u32, u32), i32>>,
+    // close_ffi
+    // extern "C" fn(decoder: *mut WasmDecoder, out_peak_memory: *mut usize)
+    close: Option<TypedFunc<(u32, u32), ()>>,
+    // read_batch_ffi
+    // extern "C" fn(decoder: *mut WasmDecoder, selection_ptr: *const u32, selection_len: usize, out: *mut CSlice) -> i32
+    read_batch: Option<TypedFunc<(u32, u32, u32, u32), i32>>,
+    // extern "C" fn(pair: *mut ArrowArrayPair)
+    arrow_array_pair_drop: Option<TypedFunc<u32, ()>>,
+    // arena_reset_ffi, if the guest allocates from the arena of `Config::arena_size`
+    // extern "C" fn()
+    arena_reset: Option<TypedFunc<(), ()>>,
     // extern "C" fn(ptr: *const u8, len: usize, out: *mut CSlice) -> i32
     functions: HashMap<String, TypedFunc<(u32, u32, u32), i32>>,
     // Input pointer which can be reused during the lifetime of this instance
@@ -113,6 +223,11 @@ pub struct Instance {
     store: Store<(WasiCtx, StoreLimits)>,
     stdout: RamFileRef,
     stderr: RamFileRef,
+    // Number of calls to functions returning buffers, for the pool policy.
+    num_calls: usize,
+    usage: Arc<UsageCounters>,
+    // Fuel left when last recorded in `usage`, None if the engine does not meter fuel.
+    fuel_level: Option<u64>,
 }
 
 impl Debug for Runtime {
@@ -162,7 +277,21 @@ static ENGINE: once_cell::sync::Lazy<Engine> = once_cell::sync::Lazy::new(|| {
 impl Runtime {
     /// Create a new UDF runtime from a WASM binary.
     pub fn try_new(binary: &[u8]) -> Result<Self> {
-        Self::with_config_engine(binary, Config::default(), &ENGINE)
+        Self::with_config(binary, Config::default())
+    }
+
+    /// Create a new UDF runtime from a WASM binary, compiled by an engine enabling the features it
+    /// requires, see [`required_features`]. Fails with [`UnsupportedWasmFeature`] if `config`
+    /// denies one of them.
+    pub fn with_config(binary: &[u8], config: Config) -> Result<Self> {
+        let features = config.check_features(binary)?;
+        let engine = if features.is_empty() {
+            ENGINE.clone()
+        } else {
+            engine_for(&features)?
+        };
+        let module = Module::from_binary(&engine, binary).context("failed to load wasm binary")?;
+        Self::init_from_module(module, config, features)
     }
 
     /// Create a new UDF runtime from an AOT compiled binary.
@@ -170,7 +299,11 @@ impl Runtime {
         Self::with_config_engine_from_aot(aot_binary, Config::default(), &ENGINE)
     }
 
-    fn init_from_module(module: Module, config: Config) -> Result<Self> {
+    fn init_from_module(
+        module: Module,
+        config: Config,
+        features: Vec<WasmFeature>,
+    ) -> Result<Self> {
         // check abi version
         let version = module
             .exports()
@@ -201,13 +334,18 @@ impl Runtime {
             types,
             instances: Mutex::new(vec![].into()),
             abi_version: (major, minor),
+            usage: Arc::default(),
+            features,
+            metrics: Arc::default(),
         })
     }
 
-    /// Create a new UDF runtime from a WASM binary with a customized engine.
+    /// Create a new UDF runtime from a WASM binary with a customized engine, which must enable
+    /// the features the module requires.
     pub fn with_config_engine(binary: &[u8], config: Config, engine: &Engine) -> Result<Self> {
+        let features = config.check_features(binary)?;
         let module = Module::from_binary(engine, binary).context("failed to load wasm binary")?;
-        Self::init_from_module(module, config)
+        Self::init_from_module(module, config, features)
     }
 
     /// Create a new UDF runtime from a WASM AOT-compiled binary with a customized engine.
@@ -219,7 +357,8 @@ impl Runtime {
         let module = unsafe {
             Module::deserialize(engine, aot_binary).context("failed to load wasm binary")?
         };
-        Self::init_from_module(module, config)
+        // The features of AOT-compiled modules are checked by the engine when deserializing.
+        Self::init_from_module(module, config, vec![])
     }
 
     /// Return available functions.
@@ -237,6 +376,18 @@ impl Runtime {
         self.abi_version
     }
 
+    /// Return the features required by the module, empty for AOT-compiled ones.
+    pub fn features(&self) -> &[WasmFeature] {
+        &self.features
+    }
+
+    /// Whether the module decodes EncUnits of `data_type` encoded with `encoding`, named as in
+    /// the footer, e.g., `CASCADE`, by the capability flags it exports. None if it declares
+    /// none, see [`capabilities`].
+    pub fn supports(&self, data_type: &DataType, encoding: &str) -> Option<bool> {
+        capabilities::supports(self.functions(), data_type, encoding)
+    }
+
     /// Given a function signature that inlines struct types, find the function name.
     ///
     /// # Example
@@ -333,24 +484,173 @@ impl Runtime {
 
     /// Call a function that returns a Buffer Iterator.
     pub fn call_multi_buf(&self, name: &str, input: &[u8]) -> Result<impl Iterator<Item = Buffer>> {
+        self.call_buffer_iter(name, input)
+    }
+
+    /// Call a function that returns the buffers of an array of `num_rows` rows of `data_type`.
+    ///
+    /// With [`Config::strict_validation`], the buffers are checked with [`validate_buffers`] and
+    /// malformed outputs are returned as errors wrapping a [`ValidationError`] instead of panicking.
+    pub fn call_multi_buf_validated(
+        &self,
+        name: &str,
+        input: &[u8],
+        data_type: &DataType,
+        num_rows: usize,
+    ) -> Result<Vec<Buffer>> {
+        let mut iter = self.call_buffer_iter(name, input)?;
+        if !self.config.strict_validation {
+            return Ok(iter.collect());
+        }
+        let mut buffers = vec![];
+        loop {
+            let next = iter.next();
+            match iter.instance_arc.lock().unwrap().append_stdio(next)? {
+                Some(buffer) => buffers.push(buffer),
+                None => break,
+            }
+        }
+        validate_buffers(data_type, num_rows, &buffers)
+            .with_context(|| format!("invalid output of {name}"))?;
+        Ok(buffers)
+    }
+
+    /// Call a function of several inputs exported with `multi_input_wrapper`, e.g., a decoder
+    /// taking a shared dictionary and the data referring to it. Returns a Buffer Iterator.
+    pub fn call_multi_input(
+        &self,
+        name: &str,
+        inputs: &[&[u8]],
+    ) -> Result<impl Iterator<Item = Buffer>> {
         if !self.functions.contains(name) {
             bail!("function not found: {name}");
         }
+        let call = self
+            .metrics
+            .start_call(name, inputs.iter().map(|input| input.len()).sum());
+        let counters = call.counters();
+        let instance = self.take_instance()?;
+        let mut guard = instance.lock().unwrap();
+        let output = guard.call_multi_input(name, inputs, instance.clone());
+        // An instance that failed is not reused, it is dropped with the last output.
+        if output.is_ok() {
+            self.release(&instance, &mut guard);
+        }
+        call.finish(&output, |_| 0);
+        output.map(|iter| {
+            iter.with_copy_outputs(self.config.copy_outputs)
+                .with_counters(counters)
+        })
+    }
 
-        let mut instance = if let Some(instance) = self.instances.lock().unwrap().pop_front() {
-            instance
-        } else {
-            // dbg!("new instance1");
-            Arc::new(Mutex::new(Instance::new(self)?))
-        };
+    /// Call a function exported with `c_data_wrapper`, whose output goes through the Arrow C Data
+    /// Interface. The array is copied out of the instance and validated.
+    pub fn call_c_data(&self, name: &str, input: &[u8]) -> Result<ArrayData> {
+        if !self.functions.contains(name) {
+            bail!("function not found: {name}");
+        }
+        let call = self.metrics.start_call(name, input.len());
+        let instance = self.take_instance()?;
+        let mut guard = instance.lock().unwrap();
+        let output = guard.call_c_data(name, input);
+        // An instance that failed is not reused.
+        if output.is_ok() {
+            self.release(&instance, &mut guard);
+        }
+        call.finish(&output, ArrayData::get_array_memory_size);
+        output
+    }
+
+    /// Call a function exported with `multi_array_wrapper`, which outputs several arrays at once,
+    /// e.g., the children of a struct encoded together. Returns the buffers of each array.
+    pub fn call_multi_array(&self, name: &str, input: &[u8]) -> Result<Vec<ArrayBuffers>> {
+        split_arrays(self.call_buffer_iter(name, input)?)
+    }
+
+    /// Call a function exported with `batch_wrapper`, which decodes each of `inputs` in a single
+    /// call, e.g., the small EncUnits of a chunk. Returns the buffers output for each input.
+    pub fn call_batch(&self, name: &str, inputs: &[&[u8]]) -> Result<Vec<Vec<Buffer>>> {
+        split_batch(self.call_multi_input(name, inputs)?, inputs.len())
+    }
+
+    /// Like [`Self::call_batch`], for inputs decoded into arrays of `data_type` whose numbers of
+    /// rows are `num_rows`, validated like [`Self::call_multi_buf_validated`].
+    pub fn call_batch_validated(
+        &self,
+        name: &str,
+        inputs: &[&[u8]],
+        data_type: &DataType,
+        num_rows: &[usize],
+    ) -> Result<Vec<Vec<Buffer>>> {
+        ensure!(
+            inputs.len() == num_rows.len(),
+            "{} inputs but {} numbers of rows",
+            inputs.len(),
+            num_rows.len()
+        );
+        let outputs = self.call_batch(name, inputs)?;
+        if self.config.strict_validation {
+            for (i, (buffers, &num_rows)) in outputs.iter().zip(num_rows).enumerate() {
+                validate_buffers(data_type, num_rows, buffers)
+                    .with_context(|| format!("invalid output {i} of {name}"))?;
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Call an encoder exported with `encode_wrapper` on `data`, which must have no children and
+    /// an offset of 0. Returns the encoded bytes.
+    pub fn call_encode(&self, name: &str, data: &ArrayData) -> Result<Vec<u8>> {
+        if !self.functions.contains(name) {
+            bail!("function not found: {name}");
+        }
+        ensure!(
+            data.child_data().is_empty(),
+            "encoding {} is not supported",
+            data.data_type()
+        );
+        ensure!(
+            data.offset() == 0 && data.nulls().is_none_or(|nulls| nulls.offset() == 0),
+            "encoding an array with an offset is not supported"
+        );
+        let format = FFI_ArrowSchema::try_from(data.data_type())?;
+        let len = (data.len() as u64).to_le_bytes();
+        let mut inputs: Vec<&[u8]> = vec![
+            format.format().as_bytes(),
+            &len[..],
+            data.nulls()
+                .map_or(&[][..], |nulls| nulls.buffer().as_slice()),
+        ];
+        inputs.extend(data.buffers().iter().map(|buffer| buffer.as_slice()));
+        let call = self
+            .metrics
+            .start_call(name, inputs.iter().map(|input| input.len()).sum());
+        let instance = self.take_instance()?;
+        let mut guard = instance.lock().unwrap();
+        let output = guard.call_encode(name, &inputs);
+        // An instance that failed is not reused.
+        if output.is_ok() {
+            self.release(&instance, &mut guard);
+        }
+        call.finish(&output, Vec::len);
+        output
+    }
+
+    fn call_buffer_iter(&self, name: &str, input: &[u8]) -> Result<BufferIter> {
+        if !self.functions.contains(name) {
+            bail!("function not found: {name}");
+        }
+
+        let call = self.metrics.start_call(name, input.len());
+        let counters = call.counters();
+        let mut instance = self.take_instance()?;
         // call the function
         let mut guard = instance.lock().unwrap();
-        // dbg!(guard.memory_size());
-        let mut output = guard.call_generic_function(name, input, instance.clone());
+        let mut output = guard.call_buffer_iter(name, input, instance.clone());
 
         // put the instance back to the pool
         if output.is_ok() {
-            self.instances.lock().unwrap().push_back(instance.clone());
+            self.release(&instance, &mut guard);
         } else {
             // println!("{:?}", output.as_ref().err());
             // dbg!("new instance2");
@@ -359,46 +659,130 @@ impl Runtime {
             // We drop the instance here, but it may still be Arc'ed in some output Arrow Arrays.
             drop(instance);
             instance = Arc::new(Mutex::new(Instance::new(self)?));
+            self.metrics.instance_recreated();
             guard = instance.lock().unwrap();
-            output = guard.call_generic_function(name, input, instance.clone());
+            output = guard.call_buffer_iter(name, input, instance.clone());
             assert!(output.is_ok(), "error: {:?}", output.as_ref().err());
+            self.release(&instance, &mut guard);
+        }
+        call.finish(&output, |_| 0);
+
+        output.map(|iter| {
+            iter.with_copy_outputs(self.config.copy_outputs)
+                .with_counters(counters)
+        })
+    }
+
+    /// An instance out of the pool, or a new one. Its arena is reset if nothing else references
+    /// the instance, e.g., the outputs of earlier calls, see [`Config::arena_size`].
+    fn take_instance(&self) -> Result<Arc<Mutex<Instance>>> {
+        let instance = match self.instances.lock().unwrap().pop_front() {
+            Some(instance) => instance,
+            None => return Ok(Arc::new(Mutex::new(Instance::new(self)?))),
+        };
+        if Arc::strong_count(&instance) == 1 {
+            instance.lock().unwrap().reset_arena()?;
+        }
+        Ok(instance)
+    }
+
+    /// Return an instance to the pool, unless the pool policy of the config says to drop it.
+    fn release(&self, instance: &Arc<Mutex<Instance>>, guard: &mut Instance) {
+        guard.record_fuel();
+        let memory_size = guard.memory_size();
+        self.metrics.record_memory(memory_size);
+        let exceeds_memory = self
+            .config
+            .max_retained_memory
+            .is_some_and(|limit| memory_size > limit);
+        let exceeds_calls = self
+            .config
+            .reset_after_n_calls
+            .is_some_and(|n| guard.num_calls >= n);
+        if !exceeds_memory && !exceeds_calls {
             self.instances.lock().unwrap().push_back(instance.clone());
         }
+    }
 
-        output
+    /// Usage of all the instances of this runtime so far.
+    pub fn usage(&self) -> WasmUsage {
+        WasmUsage {
+            num_calls: self.usage.num_calls.load(Ordering::Relaxed),
+            fuel_consumed: self.usage.fuel_consumed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Calls to the guest functions, instances and memory of this runtime so far, see
+    /// [`metrics`].
+    pub fn metrics(&self) -> RuntimeMetrics {
+        self.metrics.load()
+    }
+
+    /// Statistics of the instance pool.
+    pub fn pool_stats(&self) -> PoolStats {
+        let instances = self.instances.lock().unwrap();
+        PoolStats {
+            num_instances: instances.len(),
+            retained_memory: instances
+                .iter()
+                .map(|instance| instance.lock().unwrap().memory_size())
+                .sum(),
+        }
     }
 
-    /// NYI
+    /// Decode the rows of `selection` of an EncUnit with the stateful API of the guest.
+    ///
+    /// A decoder is created on `input` with `init_ffi`, `read_batch_ffi` is called until it
+    /// returns None, then the decoder is closed. Return the buffers of each batch.
     pub fn read_batch(
         &self,
-        name: &str,
         input: &[u8],
-        selection: RowSelection,
-        reused_instance: Option<Arc<Mutex<Instance>>>,
-    ) -> Result<StreamReadResult<impl Iterator<Item = Buffer>>> {
-        if !self.functions.contains(name) {
-            bail!("function not found: {name}");
+        kwargs: &[u8],
+        selection: &RowSelection,
+    ) -> Result<Vec<Vec<Buffer>>> {
+        if !self.functions.contains("read_batch_ffi") {
+            bail!("function not found: read_batch_ffi");
         }
-
-        let instance = if let Some(instance) = reused_instance {
-            instance
-        } else {
-            Arc::new(Mutex::new(Instance::new(self)?))
-        };
-
-        // call the function
-        let mut guard = instance.lock().unwrap();
-        let output = guard.read_batch(name, input, selection, instance.clone());
-        assert!(output.is_ok(), "error: {:?}", output.as_ref().err());
-        let output = output.unwrap();
-        // put the instance back to the pool if no more results
-        if let Some(output) = output {
-            drop(guard);
-            Ok(StreamReadResult::Batch((output, instance)))
-        } else {
-            self.instances.lock().unwrap().push_back(instance.clone());
-            Ok(StreamReadResult::End)
+        if matches!(selection, RowSelection::Select(ranges) if ranges.is_empty()) {
+            return Ok(vec![]);
         }
+        let call = self.metrics.start_call("read_batch_ffi", input.len());
+        let batches = (|| -> Result<Vec<Vec<Buffer>>> {
+            let mut decoder = self.init_decoder(input, kwargs)?;
+            let mut batches = vec![];
+            while let StreamReadResult::Batch((iter, _)) = decoder.read_batch(selection)? {
+                batches.push(iter.collect());
+            }
+            let instance = decoder.instance.clone();
+            decoder.close()?;
+            self.release(&instance, &mut instance.lock().unwrap());
+            Ok(batches)
+        })();
+        call.finish(&batches, |batches| {
+            batches.iter().flatten().map(|buffer| buffer.len()).sum()
+        });
+        batches
+    }
+
+    /// Create a decoder with the Init API of the guest, holding an instance until it is dropped.
+    /// The instance is not returned to the pool.
+    pub fn init_decoder(&self, input: &[u8], kwargs: &[u8]) -> Result<StatefulDecoder> {
+        ensure!(
+            self.functions.contains("init_ffi") && self.functions.contains("decode_ffi"),
+            "function not found: init_ffi or decode_ffi"
+        );
+        let call = self
+            .metrics
+            .start_call("init_ffi", input.len() + kwargs.len());
+        let instance = self.take_instance()?;
+        let slice = instance.lock().unwrap().call_init(input, kwargs);
+        call.finish(&slice, |_| 0);
+        let slice = slice?;
+        Ok(StatefulDecoder {
+            decoder: slice.ptr,
+            instance,
+            closed: false,
+        })
     }
 
     /// WARNING: This function is for testing only.
@@ -406,24 +790,191 @@ impl Runtime {
         Instance::new(self)
     }
 
-    // WARNING: This function is for testing only.
-    pub fn memory_size(&self) -> usize {
+    /// WARNING: This function is for testing only.
+    /// Size of the linear memory of the first pooled instance, None if the pool is empty, e.g.,
+    /// when the pool policy dropped the instances.
+    pub fn memory_size(&self) -> Option<usize> {
         let guard = self.instances.lock().unwrap();
-        let guard2 = guard[0].lock().unwrap();
-        guard2.memory.data_size(&guard2.store)
+        guard
+            .front()
+            .map(|instance| instance.lock().unwrap().memory_size())
+    }
+}
+
+/// Cumulative usage of the instances of a [`Runtime`], see [`Runtime::usage`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WasmUsage {
+    /// Number of calls to guest functions returning buffers.
+    pub num_calls: u64,
+    /// Fuel consumed by the guest, a deterministic measure of the executed instructions.
+    /// 0 unless the engine is created with [`wasmtime::Config::consume_fuel`]. Fuel consumed by
+    /// instances out of the pool, e.g., still referenced by output buffers, is counted once they
+    /// are returned or dropped.
+    pub fuel_consumed: u64,
+}
+
+#[derive(Default)]
+struct UsageCounters {
+    num_calls: AtomicU64,
+    fuel_consumed: AtomicU64,
+}
+
+/// The buffers of one of the arrays output by [`Runtime::call_multi_array`], in the order of a
+/// Buffer Iterator: the validity, then the data buffers and the buffers of the children.
+#[derive(Debug, Clone)]
+pub struct ArrayBuffers {
+    /// Number of rows of the array.
+    pub len: usize,
+    pub buffers: Vec<Buffer>,
+}
+
+/// Split the output of a function exported with `multi_array_wrapper` by its leading manifest.
+fn split_arrays(mut iter: impl Iterator<Item = Buffer>) -> Result<Vec<ArrayBuffers>> {
+    let manifest = iter
+        .next()
+        .context("missing manifest of the output arrays")?;
+    fn read<const N: usize>(manifest: &mut &[u8]) -> Result<[u8; N]> {
+        ensure!(
+            manifest.len() >= N,
+            "truncated manifest of the output arrays"
+        );
+        let (bytes, rest) = manifest.split_at(N);
+        *manifest = rest;
+        Ok(bytes.try_into()?)
+    }
+    let mut manifest = manifest.as_slice();
+    let num_arrays = u32::from_le_bytes(read(&mut manifest)?) as usize;
+    let mut arrays = Vec::with_capacity(num_arrays);
+    for i in 0..num_arrays {
+        let num_buffers = u32::from_le_bytes(read(&mut manifest)?) as usize;
+        let len = u64::from_le_bytes(read(&mut manifest)?) as usize;
+        let buffers: Vec<_> = iter.by_ref().take(num_buffers).collect();
+        ensure!(
+            buffers.len() == num_buffers,
+            "array {i} has {} buffers instead of {num_buffers}",
+            buffers.len()
+        );
+        arrays.push(ArrayBuffers { len, buffers });
+    }
+    ensure!(
+        manifest.is_empty(),
+        "trailing bytes in the manifest of the output arrays"
+    );
+    ensure!(iter.next().is_none(), "more buffers than in the manifest");
+    Ok(arrays)
+}
+
+/// Split the output of a function exported with `batch_wrapper` by its leading manifest into the
+/// buffers of each of its `num_inputs` inputs.
+fn split_batch(
+    mut iter: impl Iterator<Item = Buffer>,
+    num_inputs: usize,
+) -> Result<Vec<Vec<Buffer>>> {
+    let manifest = iter.next().context("missing manifest of the batch")?;
+    ensure!(manifest.len() % 4 == 0, "truncated manifest of the batch");
+    let mut counts = manifest
+        .chunks_exact(4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()) as usize);
+    let num_outputs = counts.next().context("truncated manifest of the batch")?;
+    ensure!(
+        num_outputs == num_inputs,
+        "{num_outputs} outputs for {num_inputs} inputs"
+    );
+    let mut outputs = Vec::with_capacity(num_outputs);
+    for i in 0..num_outputs {
+        let num_buffers = counts.next().context("truncated manifest of the batch")?;
+        let buffers: Vec<_> = iter.by_ref().take(num_buffers).collect();
+        ensure!(
+            buffers.len() == num_buffers,
+            "output {i} has {} buffers instead of {num_buffers}",
+            buffers.len()
+        );
+        outputs.push(buffers);
     }
+    ensure!(
+        counts.next().is_none(),
+        "trailing bytes in the manifest of the batch"
+    );
+    ensure!(iter.next().is_none(), "more buffers than in the manifest");
+    Ok(outputs)
+}
+
+/// Statistics of the instance pool of a [`Runtime`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Number of idle instances in the pool.
+    pub num_instances: usize,
+    /// Total size of the linear memories of the idle instances in bytes.
+    pub retained_memory: usize,
+}
+
+/// A decoder created by [`Runtime::init_decoder`].
+///
+/// Dropping the decoder before [`StatefulDecoder::close`], e.g., when a scan is cancelled or the
+/// column is projected out, still releases its state in the guest.
+pub struct StatefulDecoder {
+    decoder: u32,
+    instance: Arc<Mutex<Instance>>,
+    closed: bool,
 }
 
+impl StatefulDecoder {
+    /// Call the `Decode` API, None once the decoder is exhausted.
+    pub fn decode(&mut self) -> Result<Option<impl Iterator<Item = Buffer>>> {
+        self.instance
+            .lock()
+            .unwrap()
+            .call_decode(self.decoder, self.instance.clone())
+    }
+
+    /// Call `read_batch_ffi`, see [`Runtime::read_batch`].
+    pub fn read_batch(
+        &mut self,
+        selection: &RowSelection,
+    ) -> Result<StreamReadResult<impl Iterator<Item = Buffer>>> {
+        let output = self.instance.lock().unwrap().read_batch(
+            self.decoder,
+            selection,
+            self.instance.clone(),
+        )?;
+        Ok(match output {
+            Some(iter) => StreamReadResult::Batch((iter, self.instance.clone())),
+            None => StreamReadResult::End,
+        })
+    }
+
+    /// Release the decoder and return the peak memory it used in the guest in bytes, 0 if the
+    /// guest does not use `fff_ude::memory::TrackingAllocator`.
+    pub fn close(mut self) -> Result<usize> {
+        self.closed = true;
+        self.instance.lock().unwrap().call_close(self.decoder)
+    }
+}
+
+impl Drop for StatefulDecoder {
+    fn drop(&mut self) {
+        if !self.closed {
+            // Older guests without close_ffi leak the decoder, as before.
+            let _ = self.instance.lock().unwrap().call_close(self.decoder);
+        }
+    }
+}
+
+/// Output of [`StatefulDecoder::read_batch`].
 pub enum StreamReadResult<Iter>
 where
     Iter: Iterator<Item = Buffer>,
 {
+    /// The buffers of a batch, and the instance holding them.
     Batch((Iter, Arc<Mutex<Instance>>)),
     End,
 }
 
+/// Rows to decode with [`Runtime::read_batch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RowSelection {
     All,
+    /// Sorted and non-overlapping ranges of row ids in the EncUnit.
     Select(Vec<Range<usize>>),
 }
 
@@ -433,9 +984,23 @@ struct BufferIter {
     // alloc_len: u32,
     // FIXME: it may be too large overhead here. Need re-evaluate to see the performance impact.
     instance_arc: Arc<Mutex<Instance>>,
+    /// See [`Config::copy_outputs`].
+    copy_outputs: bool,
+    /// Counters of the function called, recording the bytes of the buffers read.
+    counters: Option<Arc<FunctionCounters>>,
 }
 
 impl BufferIter {
+    fn with_copy_outputs(mut self, copy_outputs: bool) -> Self {
+        self.copy_outputs = copy_outputs;
+        self
+    }
+
+    fn with_counters(mut self, counters: Arc<FunctionCounters>) -> Self {
+        self.counters = Some(counters);
+        self
+    }
+
     /// Get the next record batch.
     fn next(&mut self) -> Result<Option<Buffer>> {
         let mut guard = self.instance_arc.lock().unwrap();
@@ -450,13 +1015,24 @@ impl BufferIter {
             // end of iteration
             return Ok(None);
         }
+        if let Some(counters) = &self.counters {
+            counters.add_bytes_out(out_len as usize);
+        }
 
         // read output from memory
-        let out_bytes = guard
-            .memory
-            .data(&guard.store)
-            .get(out_ptr as usize..(out_ptr + out_len) as usize)
-            .context("output slice out of bounds")?;
+        let memory = guard.memory.data(&guard.store);
+        let out_bytes = memory
+            .get(out_ptr as usize..out_ptr as usize + out_len as usize)
+            .ok_or(ValidationError::OutOfBounds {
+                ptr: out_ptr,
+                len: out_len,
+                memory_size: memory.len(),
+            })?;
+        if self.copy_outputs {
+            let batch = Buffer::from_slice_ref(out_bytes);
+            guard.buffer_drop(arrow_buffer_address)?;
+            return Ok(Some(batch));
+        }
         // println!(
         //     "host:{}, guest:{}, len:{}",
         //     out_bytes.as_ptr() as usize,
@@ -502,6 +1078,9 @@ impl Drop for BufferIter {
 }
 
 /// Store output like the Slice of WasmDecoder from Init()
+///
+/// Pointers and lengths are u32 as wasm32 memories are at most 4 GiB. Offsets in the file are
+/// u64, the writer keeps each EncUnit small enough to fit.
 pub struct WasmSlice {
     ptr: u32,
     len: u32,
@@ -547,6 +1126,8 @@ impl Instance {
         };
         let mut store = Store::new(engine, (wasi, limits));
         store.limiter(|(_, limiter)| limiter);
+        // Fails if the engine does not meter fuel. Set before instantiating, which runs code.
+        let fuel_level = store.set_fuel(u64::MAX).ok().map(|_| u64::MAX);
 
         let instance = linker.instantiate(&mut store, module)?;
         // let mut store = Store::new(engine, ());
@@ -567,18 +1148,45 @@ impl Instance {
                 }
             }
         }
-        let alloc = instance.get_typed_func(&mut store, "alloc")?;
-        let dealloc = instance.get_typed_func(&mut store, "dealloc")?;
+        let alloc: TypedFunc<(u32, u32), u32> = instance.get_typed_func(&mut store, "alloc")?;
+        let dealloc: TypedFunc<(u32, u32, u32), ()> =
+            instance.get_typed_func(&mut store, "dealloc")?;
 
         let buffer_iterator_next = instance.get_typed_func(&mut store, "buffer_iterator_next")?;
         let buffer_iterator_drop = instance.get_typed_func(&mut store, "buffer_iterator_drop")?;
         let buffer_drop = instance.get_typed_func(&mut store, "buffer_drop")?;
         let init = instance.get_typed_func(&mut store, "init_ffi").ok();
         let decode = instance.get_typed_func(&mut store, "decode_ffi").ok();
+        let close = instance.get_typed_func(&mut store, "close_ffi").ok();
+        let read_batch = instance.get_typed_func(&mut store, "read_batch_ffi").ok();
+        let arrow_array_pair_drop = instance
+            .get_typed_func(&mut store, "arrow_array_pair_drop")
+            .ok();
         let memory = instance
             .get_memory(&mut store, "memory")
             .context("no memory")?;
+        let arena_init = instance
+            .get_typed_func::<(u32, u32), i32>(&mut store, "arena_init_ffi")
+            .ok();
+        let arena_reset = match (rt.config.arena_size, arena_init) {
+            (Some(arena_size), Some(arena_init)) => {
+                let arena_size = u32::try_from(arena_size).context("arena too large")?;
+                let ptr = alloc.call(&mut store, (arena_size, ARENA_ALIGNMENT))?;
+                ensure!(
+                    ptr != 0,
+                    "failed to allocate an arena of {arena_size} bytes"
+                );
+                if arena_init.call(&mut store, (ptr, arena_size))? == 1 {
+                    Some(instance.get_typed_func(&mut store, "arena_reset_ffi")?)
+                } else {
+                    dealloc.call(&mut store, (ptr, arena_size, ARENA_ALIGNMENT))?;
+                    None
+                }
+            }
+            _ => None,
+        };
 
+        rt.metrics.instance_created();
         Ok(Instance {
             alloc,
             dealloc,
@@ -587,6 +1195,10 @@ impl Instance {
             buffer_drop,
             init,
             decode,
+            close,
+            read_batch,
+            arrow_array_pair_drop,
+            arena_reset,
             memory,
             store,
             functions,
@@ -594,9 +1206,30 @@ impl Instance {
             cached_alloc_len: None,
             stdout,
             stderr,
+            num_calls: 0,
+            usage: rt.usage.clone(),
+            fuel_level,
         })
     }
 
+    /// Size of the linear memory in bytes.
+    pub fn memory_size(&self) -> usize {
+        self.memory.data_size(&self.store)
+    }
+
+    /// Whether the guest allocates from an arena, see [`Config::arena_size`].
+    pub fn has_arena(&self) -> bool {
+        self.arena_reset.is_some()
+    }
+
+    /// Free the allocations of the guest in the arena, if any. Nothing may refer to them anymore.
+    fn reset_arena(&mut self) -> Result<()> {
+        if let Some(arena_reset) = &self.arena_reset {
+            arena_reset.call(&mut self.store, ())?;
+        }
+        Ok(())
+    }
+
     /// Call a scalar function.
     pub fn call_scalar_function(&mut self, name: &str, input: &[u8]) -> Result<(&[u8], u32)> {
         // get function
@@ -654,7 +1287,7 @@ impl Instance {
         let out_bytes = self
             .memory
             .data(&self.store)
-            .get(out_ptr as usize..(out_ptr + out_len) as usize)
+            .get(out_ptr as usize..out_ptr as usize + out_len as usize)
             .context("output slice out of bounds")?;
         let result = match errno {
             0 => Ok(out_bytes),
@@ -675,6 +1308,17 @@ impl Instance {
         input: &[u8],
         instance_arc: Arc<Mutex<Instance>>,
     ) -> Result<impl Iterator<Item = Buffer>> {
+        self.call_buffer_iter(name, input, instance_arc)
+    }
+
+    fn call_buffer_iter(
+        &mut self,
+        name: &str,
+        input: &[u8],
+        instance_arc: Arc<Mutex<Instance>>,
+    ) -> Result<BufferIter> {
+        self.num_calls += 1;
+        self.usage.num_calls.fetch_add(1, Ordering::Relaxed);
         // allocate memory for input buffer and output struct
         let len = u32::try_from(input.len() + 4 * 3).context("input too large")?;
         // The following comment is deprecated. Host must dealloc the mem it alloc to have no bugs.
@@ -774,7 +1418,7 @@ impl Instance {
         let out_bytes = self
             .memory
             .data(&self.store)
-            .get(out_ptr as usize..(out_ptr + out_len) as usize)
+            .get(out_ptr as usize..out_ptr as usize + out_len as usize)
             .context("output slice out of bounds")?;
         let ptr = match errno {
             0 => out_ptr,
@@ -790,9 +1434,174 @@ impl Instance {
             ptr,
             alloc_ptr,
             instance_arc,
+            copy_outputs: false,
+            counters: None,
+        })
+    }
+
+    /// Call a function exported with `multi_input_wrapper`.
+    ///
+    /// The region for inputs and outputs holds the output struct, then an array of (ptr, len)
+    /// pairs of u32, one per input, then the inputs, each aligned to [`INPUT_ALIGNMENT`].
+    fn call_multi_input(
+        &mut self,
+        name: &str,
+        inputs: &[&[u8]],
+        instance_arc: Arc<Mutex<Instance>>,
+    ) -> Result<BufferIter> {
+        self.num_calls += 1;
+        self.usage.num_calls.fetch_add(1, Ordering::Relaxed);
+        let (alloc_ptr, slices_ptr) = self.write_inputs(inputs)?;
+
+        // get function
+        let func = self
+            .functions
+            .get(name)
+            .with_context(|| format!("function not found: {name}"))?;
+        // call the function
+        let result = func.call(
+            &mut self.store,
+            (slices_ptr, inputs.len() as u32, alloc_ptr),
+        );
+        let errno = self.append_stdio(result)?;
+
+        // get return values
+        let out_ptr = self.read_u32(alloc_ptr)?;
+        let out_len = self.read_u32(alloc_ptr + 4)?;
+        if errno != 0 {
+            let out_bytes = self
+                .memory
+                .data(&self.store)
+                .get(out_ptr as usize..out_ptr as usize + out_len as usize)
+                .context("output slice out of bounds")?;
+            return Err(anyhow!(
+                "error number: {}, out bytes: {}",
+                errno,
+                std::str::from_utf8(out_bytes)?
+            ));
+        }
+        Ok(BufferIter {
+            ptr: out_ptr,
+            alloc_ptr,
+            instance_arc,
+            copy_outputs: false,
+            counters: None,
         })
     }
 
+    /// Write `inputs` to the region for inputs and outputs, returning the pointers to the region
+    /// and to the (ptr, len) pairs. See [`Self::call_multi_input`] for the layout.
+    fn write_inputs(&mut self, inputs: &[&[u8]]) -> Result<(u32, u32)> {
+        let slices_offset = 4 * 3;
+        let mut offset = slices_offset + 8 * inputs.len();
+        let mut offsets = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            offset = offset.next_multiple_of(INPUT_ALIGNMENT as usize);
+            offsets.push(offset);
+            offset += input.len();
+        }
+        let len = u32::try_from(offset).context("input too large")?;
+        let alloc_ptr = self.alloc_io(len)?;
+        let mut slices = Vec::with_capacity(8 * inputs.len());
+        for (input, offset) in inputs.iter().zip(&offsets) {
+            let in_ptr = alloc_ptr + *offset as u32;
+            self.memory.write(&mut self.store, in_ptr as usize, input)?;
+            slices.extend_from_slice(&in_ptr.to_le_bytes());
+            slices.extend_from_slice(&(input.len() as u32).to_le_bytes());
+        }
+        let slices_ptr = alloc_ptr + slices_offset as u32;
+        self.memory
+            .write(&mut self.store, slices_ptr as usize, &slices)?;
+        Ok((alloc_ptr, slices_ptr))
+    }
+
+    /// Call a function exported with `encode_wrapper` on the inputs built by
+    /// [`Runtime::call_encode`], and copy the encoded bytes out of the instance.
+    fn call_encode(&mut self, name: &str, inputs: &[&[u8]]) -> Result<Vec<u8>> {
+        self.num_calls += 1;
+        self.usage.num_calls.fetch_add(1, Ordering::Relaxed);
+        let (alloc_ptr, slices_ptr) = self.write_inputs(inputs)?;
+
+        // call the function
+        let func = self
+            .functions
+            .get(name)
+            .with_context(|| format!("function not found: {name}"))?;
+        let result = func.call(
+            &mut self.store,
+            (slices_ptr, inputs.len() as u32, alloc_ptr),
+        );
+        let errno = self.append_stdio(result)?;
+
+        // get return values
+        let out_ptr = self.read_u32(alloc_ptr)?;
+        let out_len = self.read_u32(alloc_ptr + 4)?;
+        let out_bytes = self
+            .memory
+            .data(&self.store)
+            .get(out_ptr as usize..out_ptr as usize + out_len as usize)
+            .context("output slice out of bounds")?;
+        if errno != 0 {
+            return Err(anyhow!(
+                "error number: {}, out bytes: {}",
+                errno,
+                std::str::from_utf8(out_bytes)?
+            ));
+        }
+        let encoded = out_bytes.to_vec();
+        if out_len != 0 {
+            self.dealloc(out_ptr, out_len, 1)?;
+        }
+        Ok(encoded)
+    }
+
+    /// Call a function exported with `c_data_wrapper` and import its output array.
+    pub fn call_c_data(&mut self, name: &str, input: &[u8]) -> Result<ArrayData> {
+        self.num_calls += 1;
+        self.usage.num_calls.fetch_add(1, Ordering::Relaxed);
+        let pair_drop = self
+            .arrow_array_pair_drop
+            .context("function not found: arrow_array_pair_drop")?;
+        // allocate memory for input buffer and output struct
+        let len = u32::try_from(input.len() + 4 * 2).context("input too large")?;
+        let alloc_ptr = self.alloc_io(len)?;
+        let in_ptr = alloc_ptr + 4 * 2;
+        self.memory.write(&mut self.store, in_ptr as usize, input)?;
+
+        // call the function
+        let func = self
+            .functions
+            .get(name)
+            .with_context(|| format!("function not found: {name}"))?;
+        let result = func.call(&mut self.store, (in_ptr, input.len() as u32, alloc_ptr));
+        let errno = self.append_stdio(result)?;
+
+        // get return values
+        let out_ptr = self.read_u32(alloc_ptr)?;
+        let out_len = self.read_u32(alloc_ptr + 4)?;
+        if errno != 0 {
+            let out_bytes = self
+                .memory
+                .data(&self.store)
+                .get(out_ptr as usize..out_ptr as usize + out_len as usize)
+                .context("output slice out of bounds")?;
+            return Err(anyhow!(
+                "error number: {}, out bytes: {}",
+                errno,
+                std::str::from_utf8(out_bytes)?
+            ));
+        }
+        ensure!(
+            out_len == c_data::PAIR_SIZE,
+            "expected an ArrowArrayPair of {} bytes, got {out_len} bytes",
+            c_data::PAIR_SIZE
+        );
+        let data = c_data::import_array(self.memory.data(&self.store), out_ptr);
+        // Release the guest array even if it could not be imported.
+        pair_drop.call(&mut self.store, out_ptr)?;
+        data
+    }
+
     /// Call the adv init API
     pub fn call_init(&mut self, input: &[u8], kwargs: &[u8]) -> Result<WasmSlice> {
         // allocate memory for input buffer and output struct
@@ -867,7 +1676,7 @@ impl Instance {
         let out_bytes = self
             .memory
             .data(&self.store)
-            .get(out_ptr as usize..(out_ptr + out_len) as usize)
+            .get(out_ptr as usize..out_ptr as usize + out_len as usize)
             .context("output slice out of bounds")?;
         let ptr = match errno {
             0 => out_ptr,
@@ -951,7 +1760,7 @@ impl Instance {
         let out_bytes = self
             .memory
             .data(&self.store)
-            .get(out_ptr as usize..(out_ptr + out_len) as usize)
+            .get(out_ptr as usize..out_ptr as usize + out_len as usize)
             .context("output slice out of bounds")?;
         let ptr = match errno {
             0 => Some(out_ptr),
@@ -968,93 +1777,114 @@ impl Instance {
             ptr,
             alloc_ptr,
             instance_arc,
+            copy_outputs: false,
+            counters: None,
         }))
     }
 
-    #[allow(unreachable_code)]
+    /// Release a decoder created by `call_init` and return the peak memory it used in bytes.
+    ///
+    /// Guests before ABI 1.1 do not export `close_ffi` and release the decoder in the `Decode`
+    /// call returning None instead.
+    pub fn call_close(&mut self, decoder: u32) -> Result<usize> {
+        let close = self.close.as_ref().context("close_ffi not found")?;
+        // The output slot of call_init is reused for the peak memory.
+        let out_ptr = self
+            .cached_alloc_ptr
+            .context("no decoder was initialized")?;
+        let result = close.call(&mut self.store, (decoder, out_ptr));
+        self.append_stdio(result)?;
+        self.record_fuel();
+        Ok(self.read_u32(out_ptr)? as usize)
+    }
+
+    /// Call `read_batch_ffi` on a decoder created by `call_init`, decoding only the rows of
+    /// `selection`. None once the decoder is exhausted.
     pub fn read_batch(
         &mut self,
-        _name: &str,
-        _input: &[u8],
-        _selection: RowSelection,
-        _instance_arc: Arc<Mutex<Instance>>,
+        decoder: u32,
+        selection: &RowSelection,
+        instance_arc: Arc<Mutex<Instance>>,
     ) -> Result<Option<impl Iterator<Item = Buffer>>> {
-        todo!();
-        // get function
-        let func = self
-            .functions
-            .get(_name)
-            .with_context(|| format!("function not found: {_name}"))?;
-
-        // allocate memory for input buffer and output struct
-        let len = u32::try_from(_input.len() + 4 * 2).context("input too large")?;
-        let alloc_len = match self.cached_alloc_len {
-            Some(cached_len) => {
-                if cached_len < len {
-                    // resize cached_alloc_ptr if input size is larger than cached size.
-                    self.dealloc.call(
-                        &mut self.store,
-                        (self.cached_alloc_ptr.unwrap(), cached_len, INPUT_ALIGNMENT),
-                    )?;
-                    self.cached_alloc_ptr =
-                        Some(self.alloc.call(&mut self.store, (len, INPUT_ALIGNMENT))?);
-                    len
-                } else {
-                    cached_len
-                }
-            }
-            None => {
-                self.cached_alloc_len = Some(len);
-                len
-            }
-        };
-        let alloc_ptr = match self.cached_alloc_ptr {
-            Some(ptr) => ptr,
-            None => {
-                let ptr = self
-                    .alloc
-                    .call(&mut self.store, (alloc_len, INPUT_ALIGNMENT))?;
-                self.cached_alloc_ptr = Some(ptr);
-                ptr
-            }
+        // An empty list of ranges means all rows.
+        let ranges: &[Range<usize>] = match selection {
+            RowSelection::All => &[],
+            RowSelection::Select(ranges) => ranges,
         };
-        ensure!(alloc_ptr != 0, "failed to allocate for input");
-        let in_ptr = alloc_ptr + 4 * 2;
-        // write input to memory
+        let mut serialized = Vec::with_capacity(ranges.len() * 8);
+        for range in ranges {
+            ensure!(
+                range.start < range.end,
+                "empty range in selection: {range:?}"
+            );
+            serialized.extend_from_slice(&u32::try_from(range.start)?.to_le_bytes());
+            serialized.extend_from_slice(&u32::try_from(range.end)?.to_le_bytes());
+        }
+        // allocate memory for output struct and the selection
+        let len = u32::try_from(4 * 3 + serialized.len()).context("selection too large")?;
+        let alloc_ptr = self.alloc_io(len)?;
+        let selection_ptr = alloc_ptr + 4 * 3;
         self.memory
-            .write(&mut self.store, in_ptr as usize, _input)?;
+            .write(&mut self.store, selection_ptr as usize, &serialized)?;
 
         // call the function
-        let result = func.call(&mut self.store, (in_ptr, _input.len() as u32, alloc_ptr));
+        let result = self
+            .read_batch
+            .as_ref()
+            .context("function not found: read_batch_ffi")?
+            .call(
+                &mut self.store,
+                (decoder, selection_ptr, ranges.len() as u32, alloc_ptr),
+            );
         let errno = self.append_stdio(result)?;
+        self.num_calls += 1;
+        self.usage.num_calls.fetch_add(1, Ordering::Relaxed);
 
         // get return values
         let out_ptr = self.read_u32(alloc_ptr)?;
         let out_len = self.read_u32(alloc_ptr + 4)?;
-
-        // read output from memory
-        let out_bytes = self
-            .memory
-            .data(&self.store)
-            .get(out_ptr as usize..(out_ptr + out_len) as usize)
-            .context("output slice out of bounds")?;
         let ptr = match errno {
-            0 => out_ptr,
+            0 => Some(out_ptr),
+            1 => None,
             _ => {
+                let out_bytes = self
+                    .memory
+                    .data(&self.store)
+                    .get(out_ptr as usize..out_ptr as usize + out_len as usize)
+                    .context("output slice out of bounds")?;
                 return Err(anyhow!(
                     "error number: {}, out bytes: {}",
                     errno,
                     std::str::from_utf8(out_bytes)?
-                ))
+                ));
             }
         };
-        Ok(Some(BufferIter {
+        Ok(ptr.map(|ptr| BufferIter {
             ptr,
             alloc_ptr,
-            instance_arc: _instance_arc,
+            instance_arc,
+            copy_outputs: false,
+            counters: None,
         }))
     }
 
+    /// Return the cached region for inputs and outputs, growing it to at least `len` bytes.
+    fn alloc_io(&mut self, len: u32) -> Result<u32> {
+        match (self.cached_alloc_ptr, self.cached_alloc_len) {
+            (Some(ptr), Some(cached_len)) if cached_len >= len => return Ok(ptr),
+            (Some(ptr), Some(cached_len)) => {
+                self.dealloc
+                    .call(&mut self.store, (ptr, cached_len, INPUT_ALIGNMENT))?;
+            }
+            _ => {}
+        }
+        let ptr = self.alloc.call(&mut self.store, (len, INPUT_ALIGNMENT))?;
+        ensure!(ptr != 0, "failed to allocate for input");
+        self.cached_alloc_ptr = Some(ptr);
+        self.cached_alloc_len = Some(len);
+        Ok(ptr)
+    }
+
     pub fn dealloc(&mut self, ptr: u32, len: u32, align: u32) -> Result<()> {
         self.dealloc.call(&mut self.store, (ptr, len, align))?;
         Ok(())
@@ -1073,9 +1903,21 @@ impl Instance {
 
     fn buffer_iterator_drop(&mut self, ptr: u32) -> Result<()> {
         self.buffer_iterator_drop.call(&mut self.store, ptr)?;
+        self.record_fuel();
         Ok(())
     }
 
+    /// Add the fuel consumed since the last call to the usage of the runtime.
+    fn record_fuel(&mut self) {
+        if let Some(level) = self.fuel_level {
+            let remaining = self.store.get_fuel().unwrap_or(level);
+            self.usage
+                .fuel_consumed
+                .fetch_add(level - remaining, Ordering::Relaxed);
+            self.fuel_level = Some(remaining);
+        }
+    }
+
     pub fn print_stdio(&self) {
         self.stdout.print();
     }
@@ -1085,11 +1927,6 @@ impl Instance {
         Ok(())
     }
 
-    /// WARNING: This function is for testing only.
-    pub fn memory_size(&self) -> usize {
-        self.memory.data_size(&self.store)
-    }
-
     pub fn store(&mut self) -> &mut Store<(WasiCtx, StoreLimits)> {
         &mut self.store
     }
@@ -1097,7 +1934,10 @@ impl Instance {
     /// Read a `u32` from memory.
     fn read_u32(&mut self, ptr: u32) -> Result<u32> {
         Ok(u32::from_le_bytes(
-            self.memory.data(&self.store)[ptr as usize..(ptr + 4) as usize]
+            self.memory
+                .data(&self.store)
+                .get(ptr as usize..ptr as usize + 4)
+                .context("read out of bounds")?
                 .try_into()
                 .unwrap(),
         ))
@@ -1131,6 +1971,7 @@ impl Drop for Instance {
                 )
                 .unwrap();
         }
+        self.record_fuel();
     }
 }
 
@@ -1168,13 +2009,15 @@ fn _base64_decode(input: &str) -> Result<String> {
 #[cfg(test)]
 mod tests {
     use std::sync::{Arc, Mutex};
+    use std::time::Duration;
 
-    use arrow_array::{ArrayRef, UInt32Array};
+    use arrow_array::{make_array, Array, ArrayRef, StringArray, StructArray, UInt32Array};
+    use arrow_schema::{DataType, Field};
     use fff_core::util::buffer_to_array::primitive_array_from_arrow_buffers_iter;
-    use wasm_test_encoders::encode_fff_general;
+    use wasm_test_encoders::{encode_fff_general, encode_fff_multi_array};
     use wasmtime::Engine;
 
-    use crate::{Config, Instance, Runtime};
+    use crate::{Config, Instance, PoolStats, RowSelection, Runtime, WasmUsage, ENGINE};
 
     #[test]
     #[ignore]
@@ -1242,4 +2085,430 @@ mod tests {
             primitive_array_from_arrow_buffers_iter(array.data_type(), iter, full_size).unwrap();
         assert_eq!(*array, *out);
     }
+
+    #[test]
+    #[ignore]
+    fn test_adv_close() {
+        let rt = Runtime::try_new(
+            &std::fs::read(
+                "/home/xinyu/fff-devel/target/wasm32-wasip1/opt-size-lvl3/adv_ude_fff.wasm",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(rt.abi_version(), (1, 4));
+        let array = Arc::new(UInt32Array::from_iter_values(0..65536)) as ArrayRef;
+        let encoded = encode_fff_general(array.clone());
+
+        let mut decoder = rt.init_decoder(&encoded, &[]).unwrap();
+        let iter = decoder.decode().unwrap().unwrap();
+        let out = primitive_array_from_arrow_buffers_iter(array.data_type(), iter, 65536).unwrap();
+        assert_eq!(*array, *out);
+        assert!(decoder.decode().unwrap().is_none());
+        assert!(decoder.close().unwrap() > 0);
+
+        // Closed before decoding, e.g., a cancelled scan.
+        let decoder = rt.init_decoder(&encoded, &[]).unwrap();
+        assert!(decoder.close().unwrap() > 0);
+
+        let batches = rt
+            .read_batch(
+                &encoded,
+                &[],
+                &RowSelection::Select(vec![10..20, 1000..1005]),
+            )
+            .unwrap();
+        assert_eq!(batches.len(), 1);
+        let out = primitive_array_from_arrow_buffers_iter(
+            array.data_type(),
+            batches.into_iter().next().unwrap().into_iter(),
+            15,
+        )
+        .unwrap();
+        assert_eq!(
+            *out,
+            UInt32Array::from_iter_values((10..20).chain(1000..1005))
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_copy_outputs() {
+        let rt = Runtime::with_config_engine(
+            &std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap(),
+            Config::default().copy_outputs(true),
+            &ENGINE,
+        )
+        .unwrap();
+        let array = Arc::new(UInt32Array::from_iter_values(0..10_000)) as ArrayRef;
+        let encoded = encode_fff_general(array.clone());
+        let decode = || {
+            let buffers = rt
+                .call_multi_buf(fff_test_util::WASM_FUNC_GENERAL, &encoded)
+                .unwrap();
+            primitive_array_from_arrow_buffers_iter(array.data_type(), buffers, 10_000).unwrap()
+        };
+        let first = decode();
+        let second = decode();
+        assert_eq!(*first, *array);
+        assert_eq!(*second, *array);
+        // The outputs do not pin the pooled instance.
+        let instances = rt.instances.lock().unwrap();
+        assert_eq!(instances.len(), 1);
+        assert_eq!(Arc::strong_count(&instances[0]), 1);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_arena() {
+        let array = Arc::new(UInt32Array::from_iter_values(0..10_000)) as ArrayRef;
+        let encoded = encode_fff_general(array.clone());
+        let decode = |rt: &Runtime| {
+            let buffers = rt
+                .call_multi_buf(fff_test_util::WASM_FUNC_GENERAL, &encoded)
+                .unwrap();
+            primitive_array_from_arrow_buffers_iter(array.data_type(), buffers, 10_000).unwrap()
+        };
+        let has_arena = |rt: &Runtime| {
+            let instances = rt.instances.lock().unwrap();
+            instances[0].lock().unwrap().has_arena()
+        };
+        let binary = std::fs::read(fff_test_util::ARENA_WASM_PATH.as_path()).unwrap();
+        for copy_outputs in [true, false] {
+            let config = Config::default()
+                .arena_size(1 << 20)
+                .copy_outputs(copy_outputs);
+            let rt = Runtime::with_config_engine(&binary, config, &ENGINE).unwrap();
+            // Outputs still referencing the instance keep the arena from being reset.
+            let outputs = (0..10).map(|_| decode(&rt)).collect::<Vec<_>>();
+            assert!(outputs.iter().all(|output| **output == *array));
+            assert!(has_arena(&rt));
+            drop(outputs);
+            for _ in 0..10 {
+                assert_eq!(*decode(&rt), *array);
+            }
+            assert_eq!(rt.pool_stats().num_instances, 1);
+        }
+
+        // Guests not allocating from the arena give it back.
+        let binary = std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap();
+        let config = Config::default().arena_size(1 << 20);
+        let rt = Runtime::with_config_engine(&binary, config, &ENGINE).unwrap();
+        assert_eq!(*decode(&rt), *array);
+        assert!(!has_arena(&rt));
+    }
+
+    #[test]
+    #[ignore]
+    fn test_pool_policy() {
+        let binary = std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap();
+        let array = Arc::new(UInt32Array::from_iter_values(0..10_000)) as ArrayRef;
+        let encoded = encode_fff_general(array.clone());
+        let decode = |rt: &Runtime| {
+            let buffers: Vec<_> = rt
+                .call_multi_buf(fff_test_util::WASM_FUNC_GENERAL, &encoded)
+                .unwrap()
+                .collect();
+            drop(buffers);
+            rt.pool_stats()
+        };
+
+        let rt = Runtime::with_config_engine(&binary, Config::default(), &ENGINE).unwrap();
+        let stats = decode(&rt);
+        assert_eq!(stats.num_instances, 1);
+        assert!(stats.retained_memory > 0);
+
+        let rt =
+            Runtime::with_config_engine(&binary, Config::default().reset_after_n_calls(2), &ENGINE)
+                .unwrap();
+        assert_eq!(decode(&rt).num_instances, 1);
+        assert_eq!(decode(&rt).num_instances, 0);
+        assert_eq!(decode(&rt).num_instances, 1);
+
+        let rt =
+            Runtime::with_config_engine(&binary, Config::default().max_retained_memory(0), &ENGINE)
+                .unwrap();
+        assert_eq!(
+            decode(&rt),
+            PoolStats {
+                num_instances: 0,
+                retained_memory: 0
+            }
+        );
+    }
+    #[test]
+    fn test_multi_input() {
+        let rt = Runtime::with_config_engine(
+            &std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap(),
+            Config::default(),
+            &ENGINE,
+        )
+        .unwrap();
+        let values = Arc::new(UInt32Array::from_iter_values((0..100).map(|x| x * 7))) as ArrayRef;
+        let keys =
+            Arc::new(UInt32Array::from_iter_values((0..10_000).map(|x| x % 100))) as ArrayRef;
+        let expected = Arc::new(UInt32Array::from_iter_values(
+            (0..10_000).map(|x| x % 100 * 7),
+        )) as ArrayRef;
+        let encoded_values = encode_fff_general(values);
+        let encoded_keys = encode_fff_general(keys);
+        for _ in 0..2 {
+            let buffers = rt
+                .call_multi_input(
+                    fff_test_util::WASM_FUNC_DICT_GENERAL,
+                    &[&encoded_values, &encoded_keys],
+                )
+                .unwrap();
+            let out =
+                primitive_array_from_arrow_buffers_iter(expected.data_type(), buffers, 10_000)
+                    .unwrap();
+            assert_eq!(*out, *expected);
+        }
+        assert_eq!(rt.usage().num_calls, 2);
+
+        let err = rt
+            .call_multi_input(fff_test_util::WASM_FUNC_DICT_GENERAL, &[&encoded_keys])
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("expected 2 inputs, got 1"));
+    }
+
+    #[test]
+    fn test_call_batch() {
+        let rt = Runtime::with_config_engine(
+            &std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap(),
+            Config::default(),
+            &ENGINE,
+        )
+        .unwrap();
+        let arrays = (0..16)
+            .map(|i| {
+                Arc::new(UInt32Array::from_iter(
+                    (0..100 + i).map(|x| (x % 7 != 0).then_some(x * i)),
+                )) as ArrayRef
+            })
+            .collect::<Vec<_>>();
+        let encoded = arrays
+            .iter()
+            .map(|array| encode_fff_general(array.clone()))
+            .collect::<Vec<_>>();
+        let inputs = encoded.iter().map(|e| e.as_slice()).collect::<Vec<_>>();
+        let num_rows = arrays.iter().map(|array| array.len()).collect::<Vec<_>>();
+        let outputs = rt
+            .call_batch_validated(
+                fff_test_util::WASM_FUNC_GENERAL_BATCH,
+                &inputs,
+                &DataType::UInt32,
+                &num_rows,
+            )
+            .unwrap();
+        assert_eq!(outputs.len(), arrays.len());
+        for (buffers, array) in outputs.into_iter().zip(&arrays) {
+            let out = primitive_array_from_arrow_buffers_iter(
+                array.data_type(),
+                buffers.into_iter(),
+                array.len() as u64,
+            )
+            .unwrap();
+            assert_eq!(&out, array);
+        }
+        // All the inputs are decoded by a single call.
+        assert_eq!(rt.usage().num_calls, 1);
+        assert!(rt
+            .call_batch(fff_test_util::WASM_FUNC_GENERAL_BATCH, &[])
+            .unwrap()
+            .is_empty());
+
+        // The whole batch fails with any of its inputs.
+        assert!(rt
+            .call_batch(
+                fff_test_util::WASM_FUNC_GENERAL_BATCH,
+                &[inputs[0], &[1, 2, 3][..]]
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_c_data() {
+        let rt = Runtime::with_config_engine(
+            &std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap(),
+            Config::default(),
+            &ENGINE,
+        )
+        .unwrap();
+        let ints = Arc::new(UInt32Array::from_iter(
+            (0..10_000).map(|x| (x % 3 != 0).then_some(x)),
+        )) as ArrayRef;
+        let nested = Arc::new(StructArray::from(vec![
+            (
+                Arc::new(Field::new("a", DataType::UInt32, false)),
+                Arc::new(UInt32Array::from_iter_values(0..1000)) as ArrayRef,
+            ),
+            (
+                Arc::new(Field::new("b", DataType::UInt32, true)),
+                Arc::new(UInt32Array::from_iter(
+                    (0..1000).map(|x| (x % 5 != 0).then_some(x * 2)),
+                )),
+            ),
+        ])) as ArrayRef;
+        for array in [ints, nested] {
+            let encoded = encode_fff_general(array.clone());
+            let data = rt
+                .call_c_data(fff_test_util::WASM_FUNC_C_DATA, &encoded)
+                .unwrap();
+            assert_eq!(&make_array(data), &array);
+        }
+        // The outputs are copied, the instance goes back to the pool.
+        assert_eq!(rt.pool_stats().num_instances, 1);
+    }
+
+    #[test]
+    fn test_encode() {
+        let rt = Runtime::with_config_engine(
+            &std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap(),
+            Config::default(),
+            &ENGINE,
+        )
+        .unwrap();
+        let ints = Arc::new(UInt32Array::from_iter(
+            (0..10_000).map(|x| (x % 3 != 0).then_some(x)),
+        )) as ArrayRef;
+        let strings = Arc::new(StringArray::from_iter(
+            (0..10_000).map(|x| (x % 5 != 0).then(|| format!("s{}", x % 100))),
+        )) as ArrayRef;
+        for array in [ints, strings] {
+            let encoded = rt
+                .call_encode(fff_test_util::WASM_FUNC_ENCODE, &array.to_data())
+                .unwrap();
+            let buffers = rt
+                .call_multi_buf(fff_test_util::WASM_FUNC_GENERAL, &encoded)
+                .unwrap();
+            let out = primitive_array_from_arrow_buffers_iter(array.data_type(), buffers, 10_000)
+                .unwrap();
+            assert_eq!(&out, &array);
+        }
+        assert_eq!(rt.pool_stats().num_instances, 1);
+
+        let nested = StructArray::from(vec![(
+            Arc::new(Field::new("a", DataType::UInt32, false)),
+            Arc::new(UInt32Array::from_iter_values(0..10)) as ArrayRef,
+        )]);
+        let err = rt
+            .call_encode(fff_test_util::WASM_FUNC_ENCODE, &nested.to_data())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("not supported"));
+    }
+
+    #[test]
+    fn test_call_multi_array() {
+        let rt = Runtime::with_config_engine(
+            &std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap(),
+            Config::default(),
+            &ENGINE,
+        )
+        .unwrap();
+        let ints = Arc::new(UInt32Array::from_iter(
+            (0..10_000).map(|x| (x % 3 != 0).then_some(x)),
+        )) as ArrayRef;
+        let strings = Arc::new(StringArray::from_iter(
+            (0..500).map(|x| (x % 5 != 0).then(|| format!("s{}", x % 100))),
+        )) as ArrayRef;
+        let encoded = encode_fff_multi_array(&[ints.clone(), strings.clone()]);
+        let arrays = rt
+            .call_multi_array(fff_test_util::WASM_FUNC_MULTI_ARRAY, &encoded)
+            .unwrap();
+        assert_eq!(arrays.len(), 2);
+        for (array, expected) in arrays.into_iter().zip([ints, strings]) {
+            assert_eq!(array.len, expected.len());
+            let decoded = primitive_array_from_arrow_buffers_iter(
+                expected.data_type(),
+                array.buffers.into_iter(),
+                array.len as u64,
+            )
+            .unwrap();
+            assert_eq!(&decoded, &expected);
+        }
+    }
+
+    #[test]
+    fn test_supports() {
+        let binary = std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap();
+        let rt = Runtime::try_new(&binary).unwrap();
+        for data_type in [DataType::Int32, DataType::Utf8, DataType::Boolean] {
+            assert_eq!(rt.supports(&data_type, "CASCADE"), Some(true));
+            assert_eq!(rt.supports(&data_type, "CUSTOM_WASM"), Some(true));
+        }
+        assert_eq!(rt.supports(&DataType::Int32, "RLE"), Some(false));
+
+        // Modules declaring no capability.
+        let binary = std::fs::read(fff_test_util::NOOP_PATH.as_path()).unwrap();
+        let rt = Runtime::try_new(&binary).unwrap();
+        assert_eq!(rt.supports(&DataType::Int32, "CASCADE"), None);
+    }
+
+    #[test]
+    fn test_usage() {
+        let binary = std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap();
+        let array = Arc::new(UInt32Array::from_iter_values(0..10_000)) as ArrayRef;
+        let encoded = encode_fff_general(array.clone());
+        let decode = |rt: &Runtime| {
+            let buffers: Vec<_> = rt
+                .call_multi_buf(fff_test_util::WASM_FUNC_GENERAL, &encoded)
+                .unwrap()
+                .collect();
+            drop(buffers);
+        };
+
+        let rt = Runtime::with_config_engine(&binary, Config::default(), &ENGINE).unwrap();
+        decode(&rt);
+        assert_eq!(
+            rt.usage(),
+            WasmUsage {
+                num_calls: 1,
+                fuel_consumed: 0
+            }
+        );
+
+        let engine = Engine::new(wasmtime::Config::new().consume_fuel(true)).unwrap();
+        let rt = Runtime::with_config_engine(&binary, Config::default(), &engine).unwrap();
+        decode(&rt);
+        let first = rt.usage();
+        assert_eq!(first.num_calls, 1);
+        assert!(first.fuel_consumed > 0);
+        decode(&rt);
+        let second = rt.usage();
+        assert_eq!(second.num_calls, 2);
+        assert!(second.fuel_consumed > first.fuel_consumed);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let binary = std::fs::read(fff_test_util::BUILTIN_WASM_PATH.as_path()).unwrap();
+        let rt = Runtime::try_new(&binary).unwrap();
+        let array = Arc::new(UInt32Array::from_iter_values(0..10_000)) as ArrayRef;
+        let encoded = encode_fff_general(array.clone());
+        let mut bytes_out = 0;
+        for _ in 0..2 {
+            let buffers: Vec<_> = rt
+                .call_multi_buf(fff_test_util::WASM_FUNC_GENERAL, &encoded)
+                .unwrap()
+                .collect();
+            bytes_out += buffers.iter().map(|b| b.len() as u64).sum::<u64>();
+        }
+        assert!(bytes_out >= 80_000);
+        let metrics = rt.metrics();
+        assert_eq!(metrics.instances_created, 1);
+        assert_eq!(metrics.instances_recreated, 0);
+        assert!(metrics.peak_memory >= 40_000);
+        let decode = metrics.functions[fff_test_util::WASM_FUNC_GENERAL];
+        assert_eq!(decode.num_calls, 2);
+        assert_eq!(decode.num_errors, 0);
+        assert_eq!(decode.bytes_in, 2 * encoded.len() as u64);
+        assert_eq!(decode.bytes_out, bytes_out);
+        assert!(decode.wall_time > Duration::ZERO);
+
+        assert!(rt.call_c_data("missing_ffi", &encoded).is_err());
+        assert_eq!(rt.metrics().functions.len(), 1);
+    }
 }
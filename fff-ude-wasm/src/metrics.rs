@@ -0,0 +1,155 @@
+//! Metrics of the calls to the guest functions of a [`crate::Runtime`], see
+//! [`crate::Runtime::metrics`].
+//!
+//! Each call records its wall time and the bytes it transfers: the inputs written to the guest
+//! memory, and the output buffers as they are read, i.e., when the iterator of buffers returned
+//! by calls like [`crate::Runtime::call_multi_buf`] is consumed. With the `tracing` feature, each
+//! call also runs in a `wasm_call` span with the `function` and the `bytes` of its inputs.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Metrics of a [`crate::Runtime`] since its creation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuntimeMetrics {
+    /// Calls by guest function name.
+    pub functions: BTreeMap<String, FunctionMetrics>,
+    pub instances_created: u64,
+    /// Instances created to retry a call that failed on a pooled one, included in
+    /// `instances_created`.
+    pub instances_recreated: u64,
+    /// Largest linear memory of an instance after a call, in bytes.
+    pub peak_memory: usize,
+}
+
+/// Calls to a guest function, see [`RuntimeMetrics::functions`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FunctionMetrics {
+    pub num_calls: u64,
+    pub num_errors: u64,
+    /// Time spent in the calls, taking an instance out of the pool included.
+    pub wall_time: Duration,
+    /// Bytes of the inputs written to the guest memory.
+    pub bytes_in: u64,
+    /// Bytes of the outputs read from the guest memory.
+    pub bytes_out: u64,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct FunctionCounters {
+    num_calls: AtomicU64,
+    num_errors: AtomicU64,
+    wall_time_nanos: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+}
+
+impl FunctionCounters {
+    pub(crate) fn add_bytes_out(&self, bytes: usize) {
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    fn load(&self) -> FunctionMetrics {
+        FunctionMetrics {
+            num_calls: self.num_calls.load(Ordering::Relaxed),
+            num_errors: self.num_errors.load(Ordering::Relaxed),
+            wall_time: Duration::from_nanos(self.wall_time_nanos.load(Ordering::Relaxed)),
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The collector of the [`RuntimeMetrics`] of a runtime, shared with its instances.
+#[derive(Debug, Default)]
+pub(crate) struct MetricsCollector {
+    functions: Mutex<HashMap<String, Arc<FunctionCounters>>>,
+    instances_created: AtomicU64,
+    instances_recreated: AtomicU64,
+    peak_memory: AtomicUsize,
+}
+
+impl MetricsCollector {
+    /// Start timing a call to `function` with inputs of `bytes_in` bytes.
+    pub(crate) fn start_call(&self, function: &str, bytes_in: usize) -> CallTimer {
+        let counters = self
+            .functions
+            .lock()
+            .unwrap()
+            .entry(function.to_string())
+            .or_default()
+            .clone();
+        counters.num_calls.fetch_add(1, Ordering::Relaxed);
+        counters
+            .bytes_in
+            .fetch_add(bytes_in as u64, Ordering::Relaxed);
+        CallTimer {
+            counters,
+            start: Instant::now(),
+            #[cfg(feature = "tracing")]
+            _span: tracing::debug_span!("wasm_call", function, bytes = bytes_in).entered(),
+        }
+    }
+
+    pub(crate) fn instance_created(&self) {
+        self.instances_created.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn instance_recreated(&self) {
+        self.instances_recreated.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_memory(&self, memory_size: usize) {
+        self.peak_memory.fetch_max(memory_size, Ordering::Relaxed);
+    }
+
+    pub(crate) fn load(&self) -> RuntimeMetrics {
+        RuntimeMetrics {
+            functions: self
+                .functions
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(name, counters)| (name.clone(), counters.load()))
+                .collect(),
+            instances_created: self.instances_created.load(Ordering::Relaxed),
+            instances_recreated: self.instances_recreated.load(Ordering::Relaxed),
+            peak_memory: self.peak_memory.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A call in progress, whose wall time is recorded when dropped.
+pub(crate) struct CallTimer {
+    counters: Arc<FunctionCounters>,
+    start: Instant,
+    #[cfg(feature = "tracing")]
+    _span: tracing::span::EnteredSpan,
+}
+
+impl CallTimer {
+    /// The counters of the function, for outputs read after the call.
+    pub(crate) fn counters(&self) -> Arc<FunctionCounters> {
+        self.counters.clone()
+    }
+
+    /// Record the outcome of the call, with the bytes of its output if already read.
+    pub(crate) fn finish<T, E>(self, output: &Result<T, E>, bytes_out: impl FnOnce(&T) -> usize) {
+        match output {
+            Ok(output) => self.counters.add_bytes_out(bytes_out(output)),
+            Err(_) => {
+                self.counters.num_errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl Drop for CallTimer {
+    fn drop(&mut self) {
+        self.counters
+            .wall_time_nanos
+            .fetch_add(self.start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,277 @@
+//! Validation of the buffers returned by a WASM decoder, enabled by [`crate::Config::strict_validation`].
+//!
+//! The buffers are expected in the layout the host builds Arrow arrays from:
+//! | validity | values | for fixed-width types and booleans,
+//! | validity | views | data buffers... | for strings and binaries,
+//! | validity | offsets | for the offsets of lists.
+//! An empty validity buffer means no nulls.
+
+use std::fmt::{self, Display};
+
+use arrow_buffer::{bit_util, Buffer};
+use arrow_schema::DataType;
+
+/// Strings up to this length are inlined in their view.
+const MAX_INLINE_VIEW_LEN: u32 = 12;
+
+/// A violation of the expected output layout by a WASM decoder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// A returned slice is outside of the linear memory of the instance.
+    OutOfBounds {
+        ptr: u32,
+        len: u32,
+        memory_size: usize,
+    },
+    /// The number of returned buffers does not match the layout of the data type.
+    BufferCount { expected: usize, actual: usize },
+    /// A buffer is shorter than required by the row count.
+    BufferTooShort {
+        index: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// A buffer is not aligned to its element type.
+    Misaligned { index: usize, alignment: usize },
+    /// List offsets are negative or decreasing.
+    InvalidOffsets { row: usize },
+    /// A string or binary view points outside of the data buffers.
+    InvalidView { row: usize },
+    /// The layout of the data type is not known.
+    UnsupportedDataType(DataType),
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::OutOfBounds {
+                ptr,
+                len,
+                memory_size,
+            } => write!(
+                f,
+                "output slice out of bounds: {len} bytes at {ptr} in a memory of {memory_size} bytes"
+            ),
+            ValidationError::BufferCount { expected, actual } => {
+                write!(f, "expected {expected} output buffers, got {actual}")
+            }
+            ValidationError::BufferTooShort {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "output buffer {index} has {actual} bytes, expected at least {expected}"
+            ),
+            ValidationError::Misaligned { index, alignment } => {
+                write!(f, "output buffer {index} is not aligned to {alignment} bytes")
+            }
+            ValidationError::InvalidOffsets { row } => {
+                write!(f, "invalid list offsets at row {row}")
+            }
+            ValidationError::InvalidView { row } => {
+                write!(f, "view at row {row} is out of the data buffers")
+            }
+            ValidationError::UnsupportedDataType(data_type) => {
+                write!(f, "cannot validate the output of data type {data_type}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+type Result<T> = std::result::Result<T, ValidationError>;
+
+fn check_len(buffers: &[Buffer], index: usize, expected: usize) -> Result<()> {
+    let actual = buffers[index].len();
+    if actual < expected {
+        return Err(ValidationError::BufferTooShort {
+            index,
+            expected,
+            actual,
+        });
+    }
+    Ok(())
+}
+
+fn check_alignment(buffers: &[Buffer], index: usize, alignment: usize) -> Result<()> {
+    if buffers[index].as_ptr().align_offset(alignment) != 0 {
+        return Err(ValidationError::Misaligned { index, alignment });
+    }
+    Ok(())
+}
+
+fn check_count(buffers: &[Buffer], expected: usize) -> Result<()> {
+    if buffers.len() != expected {
+        return Err(ValidationError::BufferCount {
+            expected,
+            actual: buffers.len(),
+        });
+    }
+    Ok(())
+}
+
+fn check_offsets<const N: usize>(offsets: &[u8], num_rows: usize) -> Result<()> {
+    let mut prev = 0i64;
+    for (row, offset) in offsets.chunks_exact(N).take(num_rows + 1).enumerate() {
+        let offset = if N == 4 {
+            i32::from_le_bytes(offset.try_into().unwrap()) as i64
+        } else {
+            i64::from_le_bytes(offset.try_into().unwrap())
+        };
+        if offset < prev {
+            return Err(ValidationError::InvalidOffsets { row });
+        }
+        prev = offset;
+    }
+    Ok(())
+}
+
+fn check_views(buffers: &[Buffer], num_rows: usize) -> Result<()> {
+    let validity = &buffers[0];
+    let data_buffers = &buffers[2..];
+    for (row, view) in buffers[1].chunks_exact(16).take(num_rows).enumerate() {
+        if !validity.is_empty() && !bit_util::get_bit(validity, row) {
+            continue;
+        }
+        let view = u128::from_le_bytes(view.try_into().unwrap());
+        let len = view as u32;
+        if len <= MAX_INLINE_VIEW_LEN {
+            continue;
+        }
+        let buffer_index = (view >> 64) as u32 as usize;
+        let offset = (view >> 96) as u32 as usize;
+        match data_buffers.get(buffer_index) {
+            Some(data) if offset + len as usize <= data.len() => {}
+            _ => return Err(ValidationError::InvalidView { row }),
+        }
+    }
+    Ok(())
+}
+
+/// Check that `buffers` form a valid array of `num_rows` rows of `data_type`.
+pub fn validate_buffers(data_type: &DataType, num_rows: usize, buffers: &[Buffer]) -> Result<()> {
+    if buffers.is_empty() {
+        return Err(ValidationError::BufferCount {
+            expected: 2,
+            actual: 0,
+        });
+    }
+    if !buffers[0].is_empty() {
+        check_len(buffers, 0, bit_util::ceil(num_rows, 8))?;
+    }
+    match data_type {
+        DataType::Boolean => {
+            check_count(buffers, 2)?;
+            check_len(buffers, 1, bit_util::ceil(num_rows, 8))
+        }
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Binary | DataType::LargeBinary => {
+            if buffers.len() < 2 {
+                return Err(ValidationError::BufferCount {
+                    expected: 2,
+                    actual: buffers.len(),
+                });
+            }
+            check_len(buffers, 1, num_rows * 16)?;
+            check_alignment(buffers, 1, std::mem::align_of::<u128>())?;
+            check_views(buffers, num_rows)
+        }
+        DataType::List(_) => {
+            check_count(buffers, 2)?;
+            check_len(buffers, 1, (num_rows + 1) * 4)?;
+            check_alignment(buffers, 1, 4)?;
+            check_offsets::<4>(&buffers[1], num_rows)
+        }
+        DataType::LargeList(_) => {
+            check_count(buffers, 2)?;
+            check_len(buffers, 1, (num_rows + 1) * 8)?;
+            check_alignment(buffers, 1, 8)?;
+            check_offsets::<8>(&buffers[1], num_rows)
+        }
+        data_type => match data_type.primitive_width() {
+            Some(width) => {
+                check_count(buffers, 2)?;
+                check_len(buffers, 1, num_rows * width)?;
+                check_alignment(buffers, 1, width)
+            }
+            None => Err(ValidationError::UnsupportedDataType(data_type.clone())),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_schema::Field;
+
+    use super::*;
+
+    fn view(len: u32, buffer_index: u32, offset: u32) -> u128 {
+        len as u128 | (buffer_index as u128) << 64 | (offset as u128) << 96
+    }
+
+    #[test]
+    fn test_validate_primitive() {
+        let values = Buffer::from_vec((0..10i32).collect::<Vec<_>>());
+        let no_nulls = Buffer::from_vec(Vec::<u8>::new());
+        validate_buffers(&DataType::Int32, 10, &[no_nulls.clone(), values.clone()]).unwrap();
+        assert_eq!(
+            validate_buffers(&DataType::Int32, 11, &[no_nulls.clone(), values.clone()]),
+            Err(ValidationError::BufferTooShort {
+                index: 1,
+                expected: 44,
+                actual: 40
+            })
+        );
+        assert_eq!(
+            validate_buffers(&DataType::Int32, 10, &[no_nulls.clone()]),
+            Err(ValidationError::BufferCount {
+                expected: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            validate_buffers(&DataType::Int32, 8, &[no_nulls.clone(), values.slice(1)]),
+            Err(ValidationError::Misaligned {
+                index: 1,
+                alignment: 4
+            })
+        );
+        let short_validity = Buffer::from_vec(vec![0xffu8]);
+        assert!(matches!(
+            validate_buffers(&DataType::Int32, 10, &[short_validity, values]),
+            Err(ValidationError::BufferTooShort { index: 0, .. })
+        ));
+        assert_eq!(
+            validate_buffers(&DataType::Utf8View, 0, &[no_nulls]),
+            Err(ValidationError::UnsupportedDataType(DataType::Utf8View))
+        );
+    }
+
+    #[test]
+    fn test_validate_views_and_offsets() {
+        let no_nulls = Buffer::from_vec(Vec::<u8>::new());
+        let data = Buffer::from_vec(b"a string longer than 12 bytes".to_vec());
+        let views = Buffer::from_vec(vec![view(3, 0, 0), view(29, 0, 0)]);
+        validate_buffers(&DataType::Utf8, 2, &[no_nulls.clone(), views, data.clone()]).unwrap();
+        let views = Buffer::from_vec(vec![view(3, 0, 0), view(29, 1, 0)]);
+        assert_eq!(
+            validate_buffers(&DataType::Utf8, 2, &[no_nulls.clone(), views.clone(), data]),
+            Err(ValidationError::InvalidView { row: 1 })
+        );
+        // The invalid view is null.
+        let validity = Buffer::from_vec(vec![0b01u8]);
+        validate_buffers(&DataType::Utf8, 2, &[validity, views]).unwrap();
+
+        let list = DataType::List(Arc::new(Field::new_list_field(DataType::Int32, true)));
+        let offsets = Buffer::from_vec(vec![0i32, 2, 2, 5]);
+        validate_buffers(&list, 3, &[no_nulls.clone(), offsets]).unwrap();
+        let offsets = Buffer::from_vec(vec![0i32, 2, 1, 5]);
+        assert_eq!(
+            validate_buffers(&list, 3, &[no_nulls, offsets]),
+            Err(ValidationError::InvalidOffsets { row: 2 })
+        );
+    }
+}
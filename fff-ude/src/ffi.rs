@@ -2,11 +2,18 @@
 
 //! FFI interfaces.
 
+use std::ops::Range;
+
+use arrow_array::ffi::{to_ffi, FFI_ArrowArray, FFI_ArrowSchema};
 use arrow_buffer::Buffer;
+use arrow_data::ArrayData;
+use arrow_schema::DataType;
 use fff_core::errors::Error;
+use fff_core::general_error;
 
 use crate::{
-    Decode, GeneralDecode, GeneralDecodeV2, Init, ScalarDecode, StatefulWasmDecoder, StringDecode,
+    arraydata_to_buffers, memory, Decode, Encode, GeneralDecode, GeneralDecodeV2, GeneralDecodeV3,
+    Init, MultiArrayDecode, ScalarDecode, StatefulWasmDecoder, StringDecode,
 };
 
 /// A symbol indicating the ABI version.
@@ -18,9 +25,19 @@ use crate::{
 /// # Changelog
 ///
 /// - 1.0: Initial version.
+/// - 1.1: Stateful decoders are released by `close_ffi` instead of the last `decode_ffi` call.
+///   Add `read_batch_ffi` to decode a selection of rows.
+/// - 1.2: Add [`multi_input_wrapper`] for functions taking several byte sequences.
+/// - 1.3: Add [`c_data_wrapper`] and `arrow_array_pair_drop` to output Arrow C Data Interface
+///   arrays.
+/// - 1.4: Add [`encode_wrapper`] for encoders run by the writer.
+/// - 1.5: Add [`multi_array_wrapper`] for functions outputting several arrays.
+/// - 1.6: Add [`batch_wrapper`] to decode a batch of inputs per call.
+/// - 1.7: Add `arena_init_ffi` and `arena_reset_ffi` for the arena ABI mode, see
+///   [`memory::ArenaAllocator`]. Memory allocated by the host with `alloc` is never in the arena.
 #[no_mangle]
 #[used]
-pub static FFFUDE_VERSION_1_0: () = ();
+pub static FFFUDE_VERSION_1_7: () = ();
 
 /// Allocate memory.
 ///
@@ -29,7 +46,9 @@ pub static FFFUDE_VERSION_1_0: () = ();
 /// See [`std::alloc::GlobalAlloc::alloc`].
 #[no_mangle]
 pub unsafe extern "C" fn alloc(len: usize, align: usize) -> *mut u8 {
-    std::alloc::alloc(std::alloc::Layout::from_size_align_unchecked(len, align))
+    memory::outside_arena(|| {
+        std::alloc::alloc(std::alloc::Layout::from_size_align_unchecked(len, align))
+    })
 }
 
 /// Deallocate memory.
@@ -45,6 +64,26 @@ pub unsafe extern "C" fn dealloc(ptr: *mut u8, len: usize, align: usize) {
     );
 }
 
+/// Hand the guest the arena of `len` bytes at `ptr`, allocated by the host with `alloc`.
+///
+/// The return value is 1 if the guest allocates from it, i.e., its global allocator is
+/// [`memory::ArenaAllocator`], 0 otherwise, in which case the host may deallocate it.
+///
+/// # Safety
+///
+/// `ptr` must point to `len` bytes that are neither used nor deallocated afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn arena_init_ffi(ptr: *mut u8, len: usize) -> i32 {
+    memory::init_arena(ptr, len) as i32
+}
+
+/// Free all the allocations in the arena, once the host is done with the outputs, iterators and
+/// decoders of the calls since the last reset.
+#[no_mangle]
+pub extern "C" fn arena_reset_ffi() {
+    memory::reset_arena();
+}
+
 /// A FFI-safe slice.
 #[repr(C)]
 #[derive(Debug)]
@@ -276,6 +315,328 @@ fn call_generalv2(
     Ok(Box::new(BufferIter { iter }))
 }
 
+/// A wrapper for calling general decoding functions of several inputs from C,
+/// e.g., a shared dictionary and the data referring to it.
+///
+/// The inputs are read from an array of `num_inputs` slices pointed to by `inputs`.
+/// On wasm32, each slice is a (ptr, len) pair of u32 written by the host.
+///
+/// The output and the return value are the same as [`general_wrapper`].
+///
+/// # Safety
+///
+/// `inputs` must point to `num_inputs` slices of valid buffers, `out_slice` must point to a valid
+/// buffer.
+pub unsafe fn multi_input_wrapper(
+    function: GeneralDecodeV2,
+    inputs: *const CSlice,
+    num_inputs: usize,
+    out_slice: *mut CSlice,
+) -> i32 {
+    let inputs: Vec<&[u8]> = if num_inputs == 0 {
+        vec![]
+    } else {
+        std::slice::from_raw_parts(inputs, num_inputs)
+            .iter()
+            .map(|slice| match slice.len {
+                0 => &[][..],
+                len => std::slice::from_raw_parts(slice.ptr, len),
+            })
+            .collect()
+    };
+    match call_generalv2(function, &inputs) {
+        Ok(iter) => {
+            out_slice.write(CSlice {
+                ptr: Box::into_raw(iter) as *const u8,
+                len: std::mem::size_of::<BufferIter>(),
+            });
+            0
+        }
+        Err(err) => {
+            let msg = err.to_string().into_boxed_str();
+            out_slice.write(CSlice {
+                ptr: msg.as_ptr(),
+                len: msg.len(),
+            });
+            std::mem::forget(msg);
+            -1
+        }
+    }
+}
+
+/// An array exported through the Arrow C Data Interface, with its schema.
+///
+/// On wasm32, `array` takes 64 bytes and `schema` the next 48 bytes.
+#[repr(C)]
+pub struct ArrowArrayPair {
+    pub array: FFI_ArrowArray,
+    pub schema: FFI_ArrowSchema,
+}
+
+/// A wrapper for calling general decoding functions whose output is exported through the Arrow
+/// C Data Interface.
+///
+/// The input encoded data is read from the buffer pointed to by `ptr` and `len`.
+///
+/// The return value is 0 on success, -1 on error.
+/// If successful, a pointer to an [`ArrowArrayPair`] and its size are written to `out_slice`.
+/// The caller imports the array then releases the pair with `arrow_array_pair_drop`.
+/// If failed, the error message is written to `out_slice`.
+///
+/// # Safety
+///
+/// `ptr`, `len`, `out_slice` must point to a valid buffer.
+pub unsafe fn c_data_wrapper(
+    function: GeneralDecodeV3,
+    ptr: *const u8,
+    len: usize,
+    out_slice: *mut CSlice,
+) -> i32 {
+    let input = std::slice::from_raw_parts(ptr, len);
+    match call_c_data(function, input) {
+        Ok(pair) => {
+            out_slice.write(CSlice {
+                ptr: Box::into_raw(pair) as *const u8,
+                len: std::mem::size_of::<ArrowArrayPair>(),
+            });
+            0
+        }
+        Err(err) => {
+            let msg = err.to_string().into_boxed_str();
+            out_slice.write(CSlice {
+                ptr: msg.as_ptr(),
+                len: msg.len(),
+            });
+            std::mem::forget(msg);
+            -1
+        }
+    }
+}
+
+fn call_c_data(
+    function: GeneralDecodeV3,
+    input_bytes: &[u8],
+) -> Result<Box<ArrowArrayPair>, Error> {
+    let data = function(input_bytes)?;
+    let (array, schema) = to_ffi(&data)?;
+    Ok(Box::new(ArrowArrayPair { array, schema }))
+}
+
+/// Release the array and the schema output by [`c_data_wrapper`].
+///
+/// # Safety
+///
+/// `pair` must be a pointer output by [`c_data_wrapper`].
+#[no_mangle]
+pub unsafe extern "C" fn arrow_array_pair_drop(pair: *mut ArrowArrayPair) {
+    drop(Box::from_raw(pair));
+}
+
+/// A wrapper for calling decoding functions outputting several arrays from C.
+///
+/// The input and the return value are the same as [`general_wrapper`]. The output Buffer
+/// iterator starts with a manifest of the arrays, then yields the buffers of each array in order,
+/// as flattened by [`arraydata_to_buffers`]. The manifest holds the number of arrays as a
+/// little-endian u32, then for each array its number of buffers as a little-endian u32 and its
+/// length as a little-endian u64.
+///
+/// # Safety
+///
+/// `ptr`, `len`, `out_slice` must point to a valid buffer.
+pub unsafe fn multi_array_wrapper(
+    function: MultiArrayDecode,
+    ptr: *const u8,
+    len: usize,
+    out_slice: *mut CSlice,
+) -> i32 {
+    let input = std::slice::from_raw_parts(ptr, len);
+    match call_multi_array(function, input) {
+        Ok(iter) => {
+            out_slice.write(CSlice {
+                ptr: Box::into_raw(iter) as *const u8,
+                len: std::mem::size_of::<BufferIter>(),
+            });
+            0
+        }
+        Err(err) => {
+            let msg = err.to_string().into_boxed_str();
+            out_slice.write(CSlice {
+                ptr: msg.as_ptr(),
+                len: msg.len(),
+            });
+            std::mem::forget(msg);
+            -1
+        }
+    }
+}
+
+fn call_multi_array(
+    function: MultiArrayDecode,
+    input_bytes: &[u8],
+) -> Result<Box<BufferIter>, Error> {
+    let arrays = function(input_bytes)?;
+    let mut manifest = (arrays.len() as u32).to_le_bytes().to_vec();
+    let mut buffers = vec![];
+    for data in &arrays {
+        let start = buffers.len();
+        arraydata_to_buffers(&mut buffers, data);
+        manifest.extend_from_slice(&((buffers.len() - start) as u32).to_le_bytes());
+        manifest.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    }
+    let iter = std::iter::once(Buffer::from_vec(manifest)).chain(buffers);
+    Ok(Box::new(BufferIter {
+        iter: Box::new(iter),
+    }))
+}
+
+/// A wrapper for calling general decoding functions on a batch of inputs from C, e.g., the small
+/// EncUnits of a chunk, so that the call overhead is paid once per batch instead of per input.
+///
+/// The inputs are laid out like for [`multi_input_wrapper`], and each of them is decoded by
+/// `function` on its own. The return value is the same as [`general_wrapper`]. The output Buffer
+/// iterator starts with a manifest of the outputs, then yields the buffers of each output in the
+/// order of the inputs. The manifest holds the number of outputs as a little-endian u32, then for
+/// each output its number of buffers as a little-endian u32. The call fails on the first input
+/// failing to decode.
+///
+/// # Safety
+///
+/// `inputs` must point to `num_inputs` slices of valid buffers, `out_slice` must point to a valid
+/// buffer.
+pub unsafe fn batch_wrapper(
+    function: GeneralDecode,
+    inputs: *const CSlice,
+    num_inputs: usize,
+    out_slice: *mut CSlice,
+) -> i32 {
+    let inputs: Vec<&[u8]> = if num_inputs == 0 {
+        vec![]
+    } else {
+        std::slice::from_raw_parts(inputs, num_inputs)
+            .iter()
+            .map(|slice| match slice.len {
+                0 => &[][..],
+                len => std::slice::from_raw_parts(slice.ptr, len),
+            })
+            .collect()
+    };
+    match call_batch(function, &inputs) {
+        Ok(iter) => {
+            out_slice.write(CSlice {
+                ptr: Box::into_raw(iter) as *const u8,
+                len: std::mem::size_of::<BufferIter>(),
+            });
+            0
+        }
+        Err(err) => {
+            let msg = err.to_string().into_boxed_str();
+            out_slice.write(CSlice {
+                ptr: msg.as_ptr(),
+                len: msg.len(),
+            });
+            std::mem::forget(msg);
+            -1
+        }
+    }
+}
+
+fn call_batch(function: GeneralDecode, inputs: &[&[u8]]) -> Result<Box<BufferIter>, Error> {
+    let mut manifest = (inputs.len() as u32).to_le_bytes().to_vec();
+    let mut buffers = vec![];
+    for input in inputs {
+        let start = buffers.len();
+        buffers.extend(function(input)?);
+        manifest.extend_from_slice(&((buffers.len() - start) as u32).to_le_bytes());
+    }
+    let iter = std::iter::once(Buffer::from_vec(manifest)).chain(buffers);
+    Ok(Box::new(BufferIter {
+        iter: Box::new(iter),
+    }))
+}
+
+/// A wrapper for calling encoding functions from C.
+///
+/// The array to encode is read from `num_inputs` slices pointed to by `inputs`, laid out like for
+/// [`multi_input_wrapper`]:
+/// - the format string of its data type in the Arrow C Data Interface,
+/// - its length as a little-endian u64,
+/// - its validity bitmap, empty if it has no nulls,
+/// - its buffers, starting at offset 0.
+///
+/// The return value is 0 on success, -1 on error.
+/// If successful, the encoded bytes are written to `out_slice`. The caller is responsible for
+/// deallocating them with an alignment of 1.
+/// If failed, the error message is written to `out_slice`.
+///
+/// # Safety
+///
+/// `inputs` must point to `num_inputs` slices of valid buffers, `out_slice` must point to a valid
+/// buffer.
+pub unsafe fn encode_wrapper(
+    function: Encode,
+    inputs: *const CSlice,
+    num_inputs: usize,
+    out_slice: *mut CSlice,
+) -> i32 {
+    let inputs: Vec<&[u8]> = if num_inputs == 0 {
+        vec![]
+    } else {
+        std::slice::from_raw_parts(inputs, num_inputs)
+            .iter()
+            .map(|slice| match slice.len {
+                0 => &[][..],
+                len => std::slice::from_raw_parts(slice.ptr, len),
+            })
+            .collect()
+    };
+    match call_encode(function, &inputs) {
+        Ok(data) => {
+            let data = data.into_boxed_slice();
+            out_slice.write(CSlice {
+                ptr: data.as_ptr(),
+                len: data.len(),
+            });
+            std::mem::forget(data);
+            0
+        }
+        Err(err) => {
+            let msg = err.to_string().into_boxed_str();
+            out_slice.write(CSlice {
+                ptr: msg.as_ptr(),
+                len: msg.len(),
+            });
+            std::mem::forget(msg);
+            -1
+        }
+    }
+}
+
+fn call_encode(function: Encode, inputs: &[&[u8]]) -> Result<Vec<u8>, Error> {
+    let [format, len, validity, buffers @ ..] = inputs else {
+        return Err(general_error!(format!(
+            "expected at least 3 inputs, got {}",
+            inputs.len()
+        )));
+    };
+    let format = std::str::from_utf8(format).map_err(|e| general_error!("invalid format", e))?;
+    let data_type = DataType::try_from(&FFI_ArrowSchema::try_new(format, vec![], None)?)?;
+    let len = u64::from_le_bytes(
+        (*len)
+            .try_into()
+            .map_err(|e| general_error!("invalid length", e))?,
+    ) as usize;
+    let nulls = (!validity.is_empty()).then(|| Buffer::from_slice_ref(validity));
+    let data = ArrayData::try_new(
+        data_type,
+        len,
+        nulls,
+        0,
+        buffers.iter().map(|b| Buffer::from_slice_ref(b)).collect(),
+        vec![],
+    )?;
+    function(data)
+}
+
 /// Get the next Buffer from the iterator.
 ///
 /// The output Buffer is written to the buffer pointed to by `out`.
@@ -333,11 +694,44 @@ pub unsafe extern "C" fn buffer_drop(buffer: *mut Buffer) {
 /// An opaque type for stateful Wasm Decoder API.
 pub struct WasmDecoder {
     inner: Box<dyn StatefulWasmDecoder>,
+    /// Bytes allocated when the decoder was created.
+    base_memory: usize,
+    /// See [`WasmDecoder::peak_memory`].
+    peak_memory: usize,
 }
 
 impl WasmDecoder {
     pub fn decode(&mut self) -> crate::Result<Option<Box<dyn Iterator<Item = Buffer>>>> {
-        self.inner.decode()
+        memory::reset_peak();
+        let res = self.inner.decode();
+        self.update_peak_memory();
+        res
+    }
+
+    /// Decode the rows of `selection`, all rows if None.
+    pub fn read_batch(
+        &mut self,
+        selection: Option<&[Range<usize>]>,
+    ) -> crate::Result<Option<Box<dyn Iterator<Item = Buffer>>>> {
+        memory::reset_peak();
+        let res = match selection {
+            Some(selection) => self.inner.read_batch(selection),
+            None => self.inner.decode(),
+        };
+        self.update_peak_memory();
+        res
+    }
+
+    /// Peak bytes allocated by the guest during `Init` and `Decode` calls of this decoder, on top
+    /// of the bytes allocated before `Init`. Only measured with [`memory::TrackingAllocator`].
+    pub fn peak_memory(&self) -> usize {
+        self.peak_memory
+    }
+
+    fn update_peak_memory(&mut self) {
+        self.peak_memory = self
+            .peak_memory
+            .max(memory::peak_bytes().saturating_sub(self.base_memory));
     }
 }
 
@@ -387,8 +781,15 @@ pub unsafe fn init_wrapper(
 }
 
 fn call_init(function: Init, input: &[u8], kwargs: &[u8]) -> Result<Box<WasmDecoder>, Error> {
+    let base_memory = memory::reset_peak();
     let inner = function(input, kwargs)?;
-    Ok(Box::new(WasmDecoder { inner }))
+    let mut decoder = WasmDecoder {
+        inner,
+        base_memory,
+        peak_memory: 0,
+    };
+    decoder.update_peak_memory();
+    Ok(Box::new(decoder))
 }
 
 /// A wrapper for calling the `Decode` API from C.
@@ -433,4 +834,65 @@ pub unsafe fn decode_wrapper(
         }
     }
 }
+
+/// A wrapper for calling [`StatefulWasmDecoder::read_batch`] from C, exported by the guest as
+/// `read_batch_ffi`.
+///
+/// The selection is read from `selection_len` pairs of `u32` pointed to by `selection_ptr`, the
+/// start (inclusive) and end (exclusive) of each range. An empty selection means all rows.
+///
+/// The return values are the same as [`decode_wrapper`].
+///
+/// # Safety
+///
+/// `wasm_decoder`, `selection_ptr`, `out_slice` must point to a valid buffer.
+pub unsafe fn read_batch_wrapper(
+    wasm_decoder: *mut WasmDecoder,
+    selection_ptr: *const u32,
+    selection_len: usize,
+    out_slice: *mut CSlice,
+) -> i32 {
+    let decoder = wasm_decoder.as_mut().expect("null pointer");
+    let selection = (selection_len > 0).then(|| {
+        std::slice::from_raw_parts(selection_ptr, selection_len * 2)
+            .chunks_exact(2)
+            .map(|range| range[0] as usize..range[1] as usize)
+            .collect::<Vec<_>>()
+    });
+    match decoder.read_batch(selection.as_deref()) {
+        Ok(Some(iter)) => {
+            let iter = Box::new(BufferIter { iter });
+            out_slice.write(CSlice {
+                ptr: Box::into_raw(iter) as *const u8,
+                len: std::mem::size_of::<BufferIter>(),
+            });
+            0
+        }
+        Ok(None) => 1,
+        Err(err) => {
+            let msg = err.to_string().into_boxed_str();
+            out_slice.write(CSlice {
+                ptr: msg.as_ptr(),
+                len: msg.len(),
+            });
+            std::mem::forget(msg);
+            -1
+        }
+    }
+}
+
+/// Release a decoder created by `init_ffi`, also when the host stops before `decode_ffi`
+/// returns None, e.g., when the scan is cancelled.
+///
+/// The peak memory used by the decoder in bytes is written to `out_peak_memory`.
+///
+/// # Safety
+///
+/// `decoder` must be a decoder returned by `init_ffi` and not closed yet. `out_peak_memory`
+/// must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn close_ffi(decoder: *mut WasmDecoder, out_peak_memory: *mut usize) {
+    let decoder = Box::from_raw(decoder);
+    out_peak_memory.write(decoder.peak_memory());
+}
 //----------END APIs with advanced features support (kwargs) ----------//
@@ -3,10 +3,13 @@
 use arrow_buffer::{Buffer, MutableBuffer};
 use arrow_data::ArrayData;
 pub use fff_core::errors::Result;
+use fff_core::nyi_err;
 use ffi::WasmDecoder;
+use std::ops::Range;
 
 pub mod ffi;
 pub mod kwargs;
+pub mod memory;
 
 /// Decode scalar data like int32 and float32. i.e., single type, single buffer as input/output
 pub type ScalarDecode = fn(input: &[u8]) -> Result<Box<[u8]>>;
@@ -16,10 +19,19 @@ pub type StringDecode = fn(input: &[u8]) -> Result<Vec<Box<[u8]>>>;
 
 /// A general decode function that returns an iterator of the decoded buffers.
 pub type GeneralDecode = fn(input: &[u8]) -> Result<Box<dyn Iterator<Item = Buffer>>>;
-/// An experimental API for more than one byte sequence of input
+/// A general decode function of more than one byte sequence of input, e.g., a shared dictionary
+/// and the data referring to it. Exported with [`ffi::multi_input_wrapper`].
 pub type GeneralDecodeV2 = fn(inputs: &[&[u8]]) -> Result<Box<dyn Iterator<Item = Buffer>>>;
-/// An experiemntal API using Arrow FFI
-pub type GeneralDecodeV3 = fn(inputs: &[u8]) -> Result<arrow_array::ffi::FFI_ArrowArray>;
+/// A general decode function whose output is exported through the Arrow C Data Interface by
+/// [`ffi::c_data_wrapper`]. Unlike [`GeneralDecode`], nested arrays do not rely on the order of
+/// their buffers.
+pub type GeneralDecodeV3 = fn(input: &[u8]) -> Result<ArrayData>;
+/// A decode function outputting several arrays at once, e.g., the children of a struct encoded
+/// together, exported with [`ffi::multi_array_wrapper`].
+pub type MultiArrayDecode = fn(input: &[u8]) -> Result<Vec<ArrayData>>;
+/// An encode function run by the writer, exported with [`ffi::encode_wrapper`]. The array has no
+/// children. Its output is decoded by a decode function of the same library.
+pub type Encode = fn(input: ArrayData) -> Result<Vec<u8>>;
 
 pub fn arraydata_to_buffers(res: &mut Vec<Buffer>, array_data: &ArrayData) {
     res.push(match array_data.nulls() {
@@ -41,9 +53,18 @@ pub fn arraydata_to_buffers(res: &mut Vec<Buffer>, array_data: &ArrayData) {
 /// Stateful WasmDecoder for the Prepare-Init-Decode APIs
 pub trait StatefulWasmDecoder {
     fn decode(&mut self) -> Result<Option<Box<dyn Iterator<Item = Buffer>>>>;
+
+    /// Like `decode`, but only output the rows in `selection`, sorted and non-overlapping ranges
+    /// of row ids in the EncUnit. Called by the host through `read_batch_ffi`.
+    fn read_batch(
+        &mut self,
+        _selection: &[Range<usize>],
+    ) -> Result<Option<Box<dyn Iterator<Item = Buffer>>>> {
+        nyi_err!("read_batch")
+    }
 }
 
 /// Init API
 pub type Init = fn(input: &[u8], kwargs: &[u8]) -> Result<Box<dyn StatefulWasmDecoder>>;
-/// Decode API
+/// Decode API. The decoder must not be freed here, the host releases it with `close_ffi`.
 pub type Decode = fn(input: *mut WasmDecoder) -> Result<Option<Box<dyn Iterator<Item = Buffer>>>>;
@@ -0,0 +1,190 @@
+//! Memory accounting of the guest, used to report the peak memory of stateful decoders, and the
+//! arena the host may provide for the allocations of decoders, see [`ArenaAllocator`].
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+/// A global allocator counting the bytes allocated by the guest.
+///
+/// Decoding libraries opt in with
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: fff_ude::memory::TrackingAllocator = fff_ude::memory::TrackingAllocator;
+/// ```
+///
+/// Without it, decoders report a peak memory of 0.
+pub struct TrackingAllocator;
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(allocated, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            if new_size >= layout.size() {
+                let grown = new_size - layout.size();
+                let allocated = ALLOCATED.fetch_add(grown, Ordering::Relaxed) + grown;
+                PEAK.fetch_max(allocated, Ordering::Relaxed);
+            } else {
+                ALLOCATED.fetch_sub(layout.size() - new_size, Ordering::Relaxed);
+            }
+        }
+        new_ptr
+    }
+}
+
+/// Bytes currently allocated through [`TrackingAllocator`].
+pub fn allocated_bytes() -> usize {
+    ALLOCATED.load(Ordering::Relaxed)
+}
+
+/// Start a new measurement window, returning the bytes currently allocated.
+pub(crate) fn reset_peak() -> usize {
+    let allocated = allocated_bytes();
+    PEAK.store(allocated, Ordering::Relaxed);
+    allocated
+}
+
+/// Highest number of bytes allocated since the last [`reset_peak`].
+pub(crate) fn peak_bytes() -> usize {
+    PEAK.load(Ordering::Relaxed)
+}
+
+// Bounds of the arena provided by the host, and the start of its free space. 0 without arena.
+static ARENA_START: AtomicUsize = AtomicUsize::new(0);
+static ARENA_END: AtomicUsize = AtomicUsize::new(0);
+static ARENA_NEXT: AtomicUsize = AtomicUsize::new(0);
+/// Whether [`ArenaAllocator`] is the global allocator, known once it allocated.
+static ARENA_ALLOCATOR: AtomicBool = AtomicBool::new(false);
+/// Set within [`outside_arena`].
+static BYPASS_ARENA: AtomicBool = AtomicBool::new(false);
+
+/// A global allocator bump-allocating from an arena provided by the host, in the arena ABI mode.
+///
+/// The host allocates the arena once per instance and hands it to the guest with
+/// `arena_init_ffi`, then resets it with `arena_reset_ffi` between calls, once it is done with
+/// their outputs. Decoding an EncUnit then costs no `malloc`/`free` in the guest: deallocations
+/// are no-ops, apart from the last allocation which is given back, and growing the last
+/// allocation, e.g., a `Vec` being filled, happens in place. Allocations not fitting in the arena,
+/// or made by the host through `alloc`, go to [`TrackingAllocator`], so the peak memory of
+/// decoders only accounts for them.
+///
+/// Allocations must not outlive the call making them. Statics initialized lazily during a call,
+/// e.g., the encodings context of Vortex, are initialized within [`outside_arena`].
+///
+/// Decoding libraries opt in with
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: fff_ude::memory::ArenaAllocator = fff_ude::memory::ArenaAllocator;
+/// ```
+pub struct ArenaAllocator;
+
+fn in_arena(ptr: *mut u8) -> bool {
+    let ptr = ptr as usize;
+    ptr >= ARENA_START.load(Ordering::Relaxed) && ptr < ARENA_END.load(Ordering::Relaxed)
+}
+
+fn arena_alloc(layout: Layout) -> Option<*mut u8> {
+    if BYPASS_ARENA.load(Ordering::Relaxed) || ARENA_START.load(Ordering::Relaxed) == 0 {
+        return None;
+    }
+    let start = ARENA_NEXT
+        .load(Ordering::Relaxed)
+        .checked_next_multiple_of(layout.align())?;
+    let end = start.checked_add(layout.size())?;
+    if end > ARENA_END.load(Ordering::Relaxed) {
+        return None;
+    }
+    ARENA_NEXT.store(end, Ordering::Relaxed);
+    Some(start as *mut u8)
+}
+
+unsafe impl GlobalAlloc for ArenaAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ARENA_ALLOCATOR.store(true, Ordering::Relaxed);
+        match arena_alloc(layout) {
+            Some(ptr) => ptr,
+            None => TrackingAllocator.alloc(layout),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if !in_arena(ptr) {
+            return TrackingAllocator.dealloc(ptr, layout);
+        }
+        // Give the last allocation back, e.g., a temporary buffer.
+        let end = ptr as usize + layout.size();
+        let _ =
+            ARENA_NEXT.compare_exchange(end, ptr as usize, Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if !in_arena(ptr) {
+            return TrackingAllocator.realloc(ptr, layout, new_size);
+        }
+        let new_end = ptr as usize + new_size;
+        if ARENA_NEXT.load(Ordering::Relaxed) == ptr as usize + layout.size()
+            && new_end <= ARENA_END.load(Ordering::Relaxed)
+        {
+            ARENA_NEXT.store(new_end, Ordering::Relaxed);
+            return ptr;
+        }
+        if new_size <= layout.size() {
+            return ptr;
+        }
+        let new_ptr = self.alloc(Layout::from_size_align_unchecked(new_size, layout.align()));
+        if !new_ptr.is_null() {
+            std::ptr::copy_nonoverlapping(ptr, new_ptr, layout.size());
+            self.dealloc(ptr, layout);
+        }
+        new_ptr
+    }
+}
+
+/// Run `f` allocating outside of the arena, for memory kept across calls, e.g., the inputs the
+/// host writes or lazily initialized statics.
+pub fn outside_arena<T>(f: impl FnOnce() -> T) -> T {
+    let bypass = BYPASS_ARENA.swap(true, Ordering::Relaxed);
+    let res = f();
+    BYPASS_ARENA.store(bypass, Ordering::Relaxed);
+    res
+}
+
+/// Bump-allocate from the `len` bytes at `ptr` from now on, if [`ArenaAllocator`] is the global
+/// allocator. Return whether it is.
+pub(crate) fn init_arena(ptr: *mut u8, len: usize) -> bool {
+    if !ARENA_ALLOCATOR.load(Ordering::Relaxed) || ptr.is_null() {
+        return false;
+    }
+    ARENA_START.store(ptr as usize, Ordering::Relaxed);
+    ARENA_NEXT.store(ptr as usize, Ordering::Relaxed);
+    ARENA_END.store(ptr as usize + len, Ordering::Relaxed);
+    true
+}
+
+/// Free all the allocations in the arena at once.
+pub(crate) fn reset_arena() {
+    ARENA_NEXT.store(ARENA_START.load(Ordering::Relaxed), Ordering::Relaxed);
+}
+
+/// Bytes allocated in the arena since it was last reset.
+pub fn arena_used_bytes() -> usize {
+    ARENA_NEXT.load(Ordering::Relaxed) - ARENA_START.load(Ordering::Relaxed)
+}
@@ -1,5 +1,9 @@
 #![allow(unused_imports)]
+use std::ops::Range;
+
+use arrow_array::{Array, ArrayRef};
 use arrow_buffer::Buffer;
+use arrow_select::concat::concat;
 use bytes::Bytes;
 use datafusion_substrait::substrait::proto::ExtendedExpression;
 use fff_encoding::schemes::vortex::VortexDecoder;
@@ -9,6 +13,7 @@ use fff_encoding::schemes::Decoder;
 use fff_ude::arraydata_to_buffers;
 use fff_ude::ffi::decode_wrapper;
 use fff_ude::ffi::init_wrapper;
+use fff_ude::ffi::read_batch_wrapper;
 use fff_ude::ffi::WasmDecoder;
 use fff_ude::kwargs::kwargs_deserialize;
 use fff_ude::kwargs::ArchivedOperator;
@@ -22,6 +27,9 @@ use vortex_array::IntoArrayData;
 use vortex_sampling_compressor::ALL_ENCODINGS_CONTEXT;
 use vortex_scalar::Scalar;
 
+#[global_allocator]
+static ALLOCATOR: fff_ude::memory::TrackingAllocator = fff_ude::memory::TrackingAllocator;
+
 #[no_mangle]
 pub unsafe extern "C" fn init_ffi(
     input_ptr: *const u8,
@@ -39,20 +47,39 @@ struct BasicDecoder {
     done: bool,
 }
 
+fn into_buffers(array: ArrayRef) -> Box<dyn Iterator<Item = Buffer>> {
+    let mut res: Vec<Buffer> = vec![];
+    arraydata_to_buffers(&mut res, &array.to_data());
+    Box::new(res.into_iter())
+}
+
 impl StatefulWasmDecoder for BasicDecoder {
     fn decode(&mut self) -> Result<Option<Box<dyn Iterator<Item = Buffer>>>> {
         if self.done {
             Ok(None)
         } else {
-            let data = self.decoder.decode_all_as_array().unwrap().to_data();
-
-            let mut res: Vec<Buffer> = vec![];
-            arraydata_to_buffers(&mut res, &data);
+            let array = self.decoder.decode_all_as_array().unwrap();
             self.done = true;
-            Ok(Some(
-                Box::new(res.into_iter()) as Box<dyn Iterator<Item = Buffer>>
-            ))
+            Ok(Some(into_buffers(array)))
+        }
+    }
+
+    /// Decodes the whole EncUnit, but only the selected rows are copied out.
+    fn read_batch(
+        &mut self,
+        selection: &[Range<usize>],
+    ) -> Result<Option<Box<dyn Iterator<Item = Buffer>>>> {
+        if self.done {
+            return Ok(None);
         }
+        let array = self.decoder.decode_all_as_array()?;
+        self.done = true;
+        let slices = selection
+            .iter()
+            .map(|range| array.slice(range.start, range.len()))
+            .collect::<Vec<_>>();
+        let array = concat(&slices.iter().map(|a| a.as_ref()).collect::<Vec<_>>())?;
+        Ok(Some(into_buffers(array)))
     }
 }
 
@@ -94,6 +121,16 @@ fn init_fff(input: &[u8], kwargs: &[u8]) -> Result<Box<dyn StatefulWasmDecoder>>
     }))
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn read_batch_ffi(
+    decoder: *mut WasmDecoder,
+    selection_ptr: *const u32,
+    selection_len: usize,
+    out: *mut fff_ude::ffi::CSlice,
+) -> i32 {
+    read_batch_wrapper(decoder, selection_ptr, selection_len, out)
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn decode_ffi(
     decoder: *mut WasmDecoder,
@@ -103,11 +140,7 @@ pub unsafe extern "C" fn decode_ffi(
 }
 
 fn decode_fff(input: *mut WasmDecoder) -> Result<Option<Box<dyn Iterator<Item = Buffer>>>> {
-    let mut decoder = unsafe { Box::from_raw(input) };
-    let res = decoder.decode()?;
-    if res.is_some() {
-        // Do not free the pointer if there is still some to decode.
-        let _ = Box::into_raw(decoder);
-    }
-    Ok(res)
+    // The decoder is freed by close_ffi.
+    let decoder = unsafe { input.as_mut() }.expect("null pointer");
+    decoder.decode()
 }
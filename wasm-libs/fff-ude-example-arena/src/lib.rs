@@ -0,0 +1,36 @@
+//! The general decoder of `fff-ude-example-fff`, allocating from the arena of the host, see
+//! `fff_ude::memory::ArenaAllocator`.
+
+use arrow_buffer::Buffer;
+use fff_ude::ffi::{batch_wrapper, general_wrapper};
+use fff_ude::memory::outside_arena;
+use fff_ude::Result;
+use vortex_sampling_compressor::ALL_ENCODINGS_CONTEXT;
+use wasm_test_encoders::decode_fff_general;
+
+#[global_allocator]
+static ALLOCATOR: fff_ude::memory::ArenaAllocator = fff_ude::memory::ArenaAllocator;
+
+fn decode(input: &[u8]) -> Result<Box<dyn Iterator<Item = Buffer>>> {
+    // The context is initialized on first use, and must outlive the arena.
+    outside_arena(|| ALL_ENCODINGS_CONTEXT.clone());
+    decode_fff_general(input)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn decode_general_ffi(
+    ptr: *const u8,
+    len: usize,
+    out: *mut fff_ude::ffi::CSlice,
+) -> i32 {
+    general_wrapper(decode, ptr, len, out)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn decode_general_batch_ffi(
+    inputs: *const fff_ude::ffi::CSlice,
+    num_inputs: usize,
+    out: *mut fff_ude::ffi::CSlice,
+) -> i32 {
+    batch_wrapper(decode, inputs, num_inputs, out)
+}
@@ -1,5 +1,21 @@
-use fff_ude::ffi::general_wrapper;
-use wasm_test_encoders::decode_fff_general;
+use fff_ude::ffi::{
+    batch_wrapper, c_data_wrapper, encode_wrapper, general_wrapper, multi_array_wrapper,
+    multi_input_wrapper,
+};
+use wasm_test_encoders::{
+    decode_fff_c_data, decode_fff_dict_general, decode_fff_general, decode_fff_multi_array,
+    encode_fff,
+};
+
+/// Capability flags, see `fff_ude_wasm::capabilities`: the cascades of the writer of any data
+/// type, tagged as `CASCADE` or, when the writer sets the built-in module, as `CUSTOM_WASM`.
+#[no_mangle]
+#[used]
+pub static FFFUDE_SUPPORTS_CASCADE: () = ();
+
+#[no_mangle]
+#[used]
+pub static FFFUDE_SUPPORTS_CUSTOM_WASM: () = ();
 
 // use talc::*;
 
@@ -22,3 +38,48 @@ pub unsafe extern "C" fn decode_general_ffi(
 ) -> i32 {
     general_wrapper(decode_fff_general, ptr, len, out)
 }
+
+#[no_mangle]
+pub unsafe extern "C" fn decode_general_batch_ffi(
+    inputs: *const fff_ude::ffi::CSlice,
+    num_inputs: usize,
+    out: *mut fff_ude::ffi::CSlice,
+) -> i32 {
+    batch_wrapper(decode_fff_general, inputs, num_inputs, out)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn decode_dict_general_ffi(
+    inputs: *const fff_ude::ffi::CSlice,
+    num_inputs: usize,
+    out: *mut fff_ude::ffi::CSlice,
+) -> i32 {
+    multi_input_wrapper(decode_fff_dict_general, inputs, num_inputs, out)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn decode_c_data_ffi(
+    ptr: *const u8,
+    len: usize,
+    out: *mut fff_ude::ffi::CSlice,
+) -> i32 {
+    c_data_wrapper(decode_fff_c_data, ptr, len, out)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn decode_multi_array_ffi(
+    ptr: *const u8,
+    len: usize,
+    out: *mut fff_ude::ffi::CSlice,
+) -> i32 {
+    multi_array_wrapper(decode_fff_multi_array, ptr, len, out)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn encode_ffi(
+    inputs: *const fff_ude::ffi::CSlice,
+    num_inputs: usize,
+    out: *mut fff_ude::ffi::CSlice,
+) -> i32 {
+    encode_wrapper(encode_fff, inputs, num_inputs, out)
+}
@@ -1,6 +1,29 @@
 use fff_ude::ffi::general_wrapper;
 use wasm_test_encoders::decode_pco_real_general;
 
+/// Capability flags, see `fff_ude_wasm::capabilities`: Pco only compresses numbers of 16 bits
+/// and more.
+macro_rules! supports {
+    ($($flag:ident),*) => {
+        $(
+            #[no_mangle]
+            #[used]
+            pub static $flag: () = ();
+        )*
+    };
+}
+
+supports!(
+    FFFUDE_SUPPORTS_CUSTOM_WASM_I16,
+    FFFUDE_SUPPORTS_CUSTOM_WASM_U16,
+    FFFUDE_SUPPORTS_CUSTOM_WASM_I32,
+    FFFUDE_SUPPORTS_CUSTOM_WASM_U32,
+    FFFUDE_SUPPORTS_CUSTOM_WASM_I64,
+    FFFUDE_SUPPORTS_CUSTOM_WASM_U64,
+    FFFUDE_SUPPORTS_CUSTOM_WASM_F32,
+    FFFUDE_SUPPORTS_CUSTOM_WASM_F64
+);
+
 #[no_mangle]
 pub unsafe extern "C" fn decode_general_ffi(
     ptr: *const u8,
@@ -13,11 +13,13 @@ use arrow_array::{
     make_array, Array, ArrayRef, ArrowPrimitiveType, PrimitiveArray,
 };
 use arrow_buffer::{Buffer, MutableBuffer};
+use arrow_data::ArrayData;
 use arrow_schema::DataType;
 use bytemuck::AnyBitPattern;
 use byteorder::{LittleEndian, ReadBytesExt};
 use bytes::Bytes;
 use fastlanes::BitPacking;
+use fff_core::general_error;
 use fff_encoding::schemes::{
     vortex::{VortexDecoder, VortexEncoder},
     Decoder, Encoder,
@@ -43,6 +45,16 @@ pub fn encode_fff_general(input: ArrayRef) -> Vec<u8> {
         .into_inner()
 }
 
+/// Like [`encode_fff_general`], run by the writer through the `encode_ffi` ABI. The output is
+/// decoded by [`decode_fff_general`].
+pub fn encode_fff(input: ArrayData) -> Result<Vec<u8>> {
+    let enc = VortexEncoder::default();
+    Ok(enc
+        .encode(make_array(input))?
+        .try_serialize(Cursor::new(vec![]))?
+        .into_inner())
+}
+
 pub fn decode_fff_general_normal_ver(input: &[u8]) -> Result<Box<dyn Iterator<Item = Buffer>>> {
     // let bytes = unsafe {
     //     Bytes::from(Vec::from_raw_parts(
@@ -83,6 +95,65 @@ pub fn decode_fff_general(input: &[u8]) -> Result<Box<dyn Iterator<Item = Buffer
     Ok(Box::new(res.into_iter()))
 }
 
+/// Like [`decode_fff_general`], but output the array to be exported through the Arrow C Data
+/// Interface.
+pub fn decode_fff_c_data(input: &[u8]) -> Result<ArrayData> {
+    let bytes = Bytes::copy_from_slice(input);
+    let mut vortex_decoder = VortexDecoder::try_new(bytes, ALL_ENCODINGS_CONTEXT.clone())?;
+    Ok(vortex_decoder.decode_all_as_array()?.to_data())
+}
+
+/// Decode a dictionary-encoded array from two inputs: the dictionary values and the keys
+/// indexing into them, both encoded with [`encode_fff_general`]. Outputs the values of the keys.
+pub fn decode_fff_dict_general(inputs: &[&[u8]]) -> Result<Box<dyn Iterator<Item = Buffer>>> {
+    let [values, keys] = *inputs else {
+        return Err(general_error!(format!(
+            "expected 2 inputs, got {}",
+            inputs.len()
+        )));
+    };
+    let decode = |input: &[u8]| -> Result<ArrayRef> {
+        let bytes = Bytes::copy_from_slice(input);
+        let mut vortex_decoder = VortexDecoder::try_new(bytes, ALL_ENCODINGS_CONTEXT.clone())?;
+        vortex_decoder.decode_all_as_array()
+    };
+    let values = decode(values)?;
+    let keys = decode(keys)?;
+    let data = arrow_select::take::take(&values, &keys, None)?.to_data();
+    let mut res: Vec<Buffer> = vec![];
+    arraydata_to_buffers(&mut res, &data);
+    Ok(Box::new(res.into_iter()))
+}
+
+/// Encode several arrays into one input of [`decode_fff_multi_array`], each encoded with
+/// [`encode_fff_general`] and prefixed by its size as a little-endian u32.
+pub fn encode_fff_multi_array(inputs: &[ArrayRef]) -> Vec<u8> {
+    let mut res = vec![];
+    for input in inputs {
+        let encoded = encode_fff_general(input.clone());
+        res.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+        res.extend_from_slice(&encoded);
+    }
+    res
+}
+
+/// Decode the arrays encoded by [`encode_fff_multi_array`], output through the
+/// `multi_array_wrapper` ABI.
+pub fn decode_fff_multi_array(mut input: &[u8]) -> Result<Vec<ArrayData>> {
+    let mut res = vec![];
+    while !input.is_empty() {
+        let len = input.read_u32::<LittleEndian>()? as usize;
+        let encoded = input
+            .get(..len)
+            .ok_or_else(|| general_error!("truncated input"))?;
+        let bytes = Bytes::copy_from_slice(encoded);
+        let mut vortex_decoder = VortexDecoder::try_new(bytes, ALL_ENCODINGS_CONTEXT.clone())?;
+        res.push(vortex_decoder.decode_all_as_array()?.to_data());
+        input = &input[len..];
+    }
+    Ok(res)
+}
+
 // pub fn decode_fff_general_ffi(input: &[u8]) -> Result<ffi::FFI_ArrowArray> {
 //     // We have to always copy here, since the vortx decoder may zero-copy from the input to output
 //     let bytes = Bytes::copy_from_slice(input);